@@ -0,0 +1,69 @@
+use std::io::{Read, Write};
+use std::net::{IpAddr, Ipv4Addr};
+use std::thread::sleep;
+use std::time::Instant;
+
+use radish::arp::cache::{announcement, consts, ArpCache};
+use radish::arp::packet::Packet as ArpPacket;
+use radish::dissect::dissect;
+use radish::ethernet::builder::FrameBuilder;
+use radish::ethernet::frame::{EtherType, Frame, MacAddr};
+use radish::net_device::tap::TapDevice;
+
+/// usage:
+/// 1. run `cargo build --example arp-responder` to build
+/// 2. find executable file in `target/debug/examples`
+/// 3. run `sudo ./arp-responder` to create a tap interface "tap-radish" and answer for 192.168.234.234
+/// 4. run `ping 192.168.234.234` in a new terminal, then `ip neigh` shows the resolved address
+fn main() {
+    let ip_addr = Ipv4Addr::new(192, 168, 234, 234);
+    let mac_addr = MacAddr([0x02, 0x00, 0x00, 0x00, 0x00, 0x01]);
+
+    let name = String::from("tap-radish");
+    let mut device = TapDevice::new(&name).expect("create a new tap device");
+    device
+        .address(IpAddr::from(Ipv4Addr::new(192, 168, 234, 233)))
+        .expect("set ipv4 address")
+        .netmask(IpAddr::from(Ipv4Addr::new(255, 255, 255, 0)))
+        .expect("set ipv4 netmask")
+        .flags(libc::IFF_UP as i16)
+        .expect("set flags");
+
+    let announcement = FrameBuilder::default()
+        .src_addr(mac_addr)
+        .ether_type(EtherType::Arp)
+        .payload(announcement(mac_addr, ip_addr))
+        .build_vec();
+    for _ in 0..consts::ANNOUNCE_NUM {
+        device.write_all(&announcement).expect("write a frame to tap device");
+        sleep(consts::ANNOUNCE_INTERVAL);
+    }
+
+    let mut cache = ArpCache::default();
+    let mut buf = [0; 1514];
+    loop {
+        let len = device.read(&mut buf).expect("read a frame from tap device");
+        let frame = match Frame::new_checked(&buf[..len]) {
+            Ok(frame) if frame.ether_type() == EtherType::Arp => frame,
+            _ => continue,
+        };
+        let packet = match ArpPacket::new_checked(frame.payload()) {
+            Ok(packet) => packet,
+            Err(err) => {
+                println!("{:?}", err);
+                continue;
+            }
+        };
+        println!("{}", dissect(&buf[..len]));
+
+        if let Some(reply) = cache.process(&packet, ip_addr, mac_addr, Instant::now()).reply {
+            let reply = FrameBuilder::default()
+                .dest_addr(frame.src_addr())
+                .src_addr(mac_addr)
+                .ether_type(EtherType::Arp)
+                .payload(reply)
+                .build_vec();
+            device.write_all(&reply).expect("write a frame to tap device");
+        }
+    }
+}
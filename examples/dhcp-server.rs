@@ -0,0 +1,39 @@
+use std::net::Ipv4Addr;
+use std::sync::{Arc, Mutex};
+
+use radish::dhcp::packet::consts::SERVER_PORT;
+use radish::dhcp::server::Server;
+use radish::ipv4::interface::Interface;
+use radish::ipv4::reassembly::Reassembler;
+use radish::net_device::tun::TunDevice;
+use radish::udp::udp_socket::UdpSocket;
+
+///  usage:
+/// 1. follow `./examples/tun-device` to create tun interface "tun-radish"
+/// 2. build and run this example to give the addresses 192.168.233.100 to 192.168.233.199
+///    to the DHCP clients of the network
+fn main() {
+    let name = String::from("tun-radish");
+    let device = TunDevice::new(&name).expect("connect to an existed tun device");
+
+    let mut interface = Interface::new(device, Reassembler::default());
+    interface.set_ip_addr(Ipv4Addr::new(192, 168, 233, 234));
+    interface.set_netmask(Ipv4Addr::new(255, 255, 255, 0));
+    let interface = Arc::new(Mutex::new(interface));
+
+    let socket = UdpSocket::bind(&interface, Ipv4Addr::UNSPECIFIED, SERVER_PORT).expect("bind port 67");
+    socket.set_broadcast(true);
+
+    let mut server = Server::new(
+        Ipv4Addr::new(192, 168, 233, 234),
+        Ipv4Addr::new(255, 255, 255, 0),
+        Ipv4Addr::new(192, 168, 233, 100),
+        Ipv4Addr::new(192, 168, 233, 199),
+    )
+    .expect("create the server");
+    server.set_router(Some(Ipv4Addr::new(192, 168, 233, 234)));
+
+    loop {
+        server.serve(&socket).expect("serve a request");
+    }
+}
@@ -0,0 +1,25 @@
+use std::env;
+use std::fs::File;
+use std::io::BufReader;
+
+use radish::dissect::{dissect, dissect_ip};
+use radish::pcap::{LinkType, Reader};
+
+/// usage:
+/// 1. capture packets with `tcpdump -w capture.pcap` or wireshark
+/// 2. run `cargo run --example pcap-dump -- capture.pcap`
+/// 3. every packet of the capture is printed, layer by layer
+fn main() {
+    let path = env::args().nth(1).expect("a pcap or pcapng file to read");
+    let file = File::open(path).expect("open the capture file");
+    let reader = Reader::new(BufReader::new(file)).expect("read the file header");
+
+    for (index, record) in reader.enumerate() {
+        let record = record.expect("read a record");
+        let dissection = match record.link_type {
+            LinkType::Ethernet => dissect(&record.data),
+            _ => dissect_ip(&record.data),
+        };
+        println!("#{} {:?}\n{}\n", index + 1, record.timestamp, dissection);
+    }
+}
@@ -1,7 +1,9 @@
 use std::io::{Read, Write};
 
 use radish::checksum::checksum;
+use radish::dissect::dissect_ip;
 use radish::error::Result;
+use radish::icmpv4::error::Error as Icmpv4Error;
 use radish::icmpv4::packet::{EchoAndEchoReplyPacket, MessageType};
 use radish::ipv4::packet::{Packet as Ipv4Packet, Protocol};
 use radish::net_device::tun::TunDevice;
@@ -11,7 +13,6 @@ use radish::net_device::tun::TunDevice;
 /// 2. build and run this example to start a ping server
 /// 3. run `ping 192.168.233.234` in a new terminal
 /// 4. the received icmp echo reply packet will be printed
-
 fn main() {
     let mtu = 1500;
     let name = String::from("tun-radish");
@@ -26,7 +27,7 @@ fn main() {
 
         if read_byte_number > 0 {
             let result = reply(buf, counter).and_then(|packet| {
-                println!("send echo replay {}: {:?}", counter, packet);
+                println!("send echo reply {}:\n{}", counter, dissect_ip(packet.as_ref()));
 
                 device.write_all(packet.as_ref()).map_err(|err| err.into())
             });
@@ -46,7 +47,7 @@ fn reply(buf: Vec<u8>, identification: u16) -> Result<Ipv4Packet<Vec<u8>>> {
     let mut echo_packet = EchoAndEchoReplyPacket::new_checked(ipv4_packet.payload_mut())?;
 
     if !echo_packet.is_request() {
-        return Err("not an icmp echo request packet".into());
+        return Err(Icmpv4Error::InvalidMessageType.into());
     }
 
     echo_packet.set_type(MessageType::EchoReply);
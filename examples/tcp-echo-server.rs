@@ -0,0 +1,42 @@
+use std::io::{Read, Write};
+use std::net::Ipv4Addr;
+use std::sync::{Arc, Mutex};
+
+use radish::ipv4::interface::Interface;
+use radish::ipv4::reassembly::Reassembler;
+use radish::net_device::tun::TunDevice;
+use radish::tcp::listener::TcpListener;
+
+///  usage:
+/// 1. follow `./examples/tun-device` to create tun interface "tun-radish"
+/// 2. build and run this example to start an echo server on port 7
+/// 3. run `nc 192.168.233.234 7` in a new terminal and type some lines
+fn main() {
+    let name = String::from("tun-radish");
+    let device = TunDevice::new(&name).expect("connect to an existed tun device");
+
+    let mut interface = Interface::new(device, Reassembler::default());
+    interface.set_ip_addr(Ipv4Addr::new(192, 168, 233, 234));
+    let interface = Arc::new(Mutex::new(interface));
+
+    let listener = TcpListener::bind(&interface, 7).expect("bind port 7");
+
+    loop {
+        let (mut stream, remote) = listener.accept().expect("accept a connection");
+        println!("accepted connection from {}", remote);
+
+        let mut buf = [0; 1024];
+        loop {
+            match stream.read(&mut buf) {
+                Ok(0) => break,
+                Ok(len) => stream.write_all(&buf[..len]).expect("echo received data"),
+                Err(err) => {
+                    println!("{}", err);
+                    break;
+                }
+            }
+        }
+
+        println!("connection from {} closed", remote);
+    }
+}
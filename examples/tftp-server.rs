@@ -0,0 +1,28 @@
+use std::net::Ipv4Addr;
+use std::sync::{Arc, Mutex};
+
+use radish::ipv4::interface::Interface;
+use radish::ipv4::reassembly::Reassembler;
+use radish::net_device::tun::TunDevice;
+use radish::tftp::server::Server;
+
+///  usage:
+/// 1. follow `./examples/tun-device` to create tun interface "tun-radish"
+/// 2. build and run this example to serve the files of the current directory on port 69
+/// 3. run `tftp 192.168.233.234 -m binary -c get Cargo.toml` in a new terminal
+fn main() {
+    let name = String::from("tun-radish");
+    let device = TunDevice::new(&name).expect("connect to an existed tun device");
+
+    let mut interface = Interface::new(device, Reassembler::default());
+    interface.set_ip_addr(Ipv4Addr::new(192, 168, 233, 234));
+    let interface = Arc::new(Mutex::new(interface));
+
+    let server = Server::bind(&interface, ".").expect("bind port 69");
+    loop {
+        match server.serve() {
+            Ok(len) => println!("transferred {} octets", len),
+            Err(e) => println!("transfer failed: {}", e),
+        }
+    }
+}
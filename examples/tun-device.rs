@@ -1,28 +1,28 @@
-use std::net::{IpAddr, Ipv4Addr};
+use std::net::Ipv4Addr;
 use std::process::Command;
 
-use radish::net_device::tun::TunDevice;
+use radish::net_device::route::Route;
+use radish::net_device::tun_builder::TunDeviceBuilder;
 
 /// usage:
 /// 1. run `cargo build --example tun-device` to build
 /// 2. find executable file in `target/debug/examples`
 /// 3. run `sudo ./tun-device` to create a tun interface
-
 fn main() {
     let name = String::from("tun-radish");
-    let device = TunDevice::new(&name).expect("create a new tun device");
-
-    device
+    let _device = TunDeviceBuilder::new(&name)
+        .address("192.168.233.233/24".parse().expect("a valid address"))
+        .up()
         .persist()
-        .expect("persist current tun device")
-        .address(IpAddr::from(Ipv4Addr::new(192, 168, 233, 233)))
-        .expect("set ipv4 address")
-        .netmask(IpAddr::from(Ipv4Addr::new(255, 255, 255, 0)))
-        .expect("set ipv4 netmask")
-        .flags(libc::IFF_UP as i16)
-        .expect("set flags");
+        .build()
+        .expect("create a new tun device");
+
+    // route another subnet to the tun interface, like `ip route add 192.168.234.0/24 dev tun-radish`
+    Route::new(Ipv4Addr::new(192, 168, 234, 0), Ipv4Addr::new(255, 255, 255, 0))
+        .add(&name)
+        .expect("add a route");
 
-    let command = format!("ip link | grep {}", name);
+    let command = format!("ip link | grep {0}; ip route | grep {0}", name);
     let output = Command::new("sh")
         .arg("-c")
         .arg(command)
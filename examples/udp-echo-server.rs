@@ -0,0 +1,29 @@
+use std::net::Ipv4Addr;
+use std::sync::{Arc, Mutex};
+
+use radish::ipv4::interface::Interface;
+use radish::ipv4::reassembly::Reassembler;
+use radish::net_device::tun::TunDevice;
+use radish::udp::udp_socket::UdpSocket;
+
+///  usage:
+/// 1. follow `./examples/tun-device` to create tun interface "tun-radish"
+/// 2. build and run this example to start an echo server on port 7
+/// 3. run `nc -u 192.168.233.234 7` in a new terminal and type some lines
+fn main() {
+    let name = String::from("tun-radish");
+    let device = TunDevice::new(&name).expect("connect to an existed tun device");
+
+    let mut interface = Interface::new(device, Reassembler::default());
+    interface.set_ip_addr(Ipv4Addr::new(192, 168, 233, 234));
+    let interface = Arc::new(Mutex::new(interface));
+
+    let socket = UdpSocket::bind(&interface, Ipv4Addr::UNSPECIFIED, 7).expect("bind port 7");
+
+    let mut buf = [0; 1500];
+    loop {
+        let (len, remote) = socket.recv_from(&mut buf).expect("receive a datagram");
+        println!("received {} octets from {}", len, remote);
+        socket.send_to(&buf[..len], remote).expect("echo the datagram");
+    }
+}
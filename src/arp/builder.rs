@@ -0,0 +1,99 @@
+use alloc::vec;
+use alloc::vec::Vec;
+use core::net::Ipv4Addr;
+
+use crate::arp::packet::{consts, Operation, Packet};
+use crate::ethernet::frame::MacAddr;
+
+pub struct PacketBuilder {
+    operation: Operation,
+    sender_hardware_addr: MacAddr,
+    sender_protocol_addr: Ipv4Addr,
+    target_hardware_addr: MacAddr,
+    target_protocol_addr: Ipv4Addr,
+}
+
+impl PacketBuilder {
+    pub fn operation(mut self, operation: Operation) -> Self {
+        self.operation = operation;
+        self
+    }
+
+    pub fn sender_hardware_addr(mut self, addr: MacAddr) -> Self {
+        self.sender_hardware_addr = addr;
+        self
+    }
+
+    pub fn sender_protocol_addr(mut self, addr: Ipv4Addr) -> Self {
+        self.sender_protocol_addr = addr;
+        self
+    }
+
+    /// Left unspecified in requests, the address being what is asked for.
+    pub fn target_hardware_addr(mut self, addr: MacAddr) -> Self {
+        self.target_hardware_addr = addr;
+        self
+    }
+
+    pub fn target_protocol_addr(mut self, addr: Ipv4Addr) -> Self {
+        self.target_protocol_addr = addr;
+        self
+    }
+
+    pub fn build_vec(self) -> Vec<u8> {
+        let mut buffer: Vec<u8> = vec![0; consts::LEN];
+
+        let mut packet = Packet::new_unchecked(buffer.as_mut_slice());
+        packet.set_address_types();
+        packet.set_operation(self.operation);
+        packet.set_sender_hardware_addr(self.sender_hardware_addr);
+        packet.set_sender_protocol_addr(self.sender_protocol_addr);
+        packet.set_target_hardware_addr(self.target_hardware_addr);
+        packet.set_target_protocol_addr(self.target_protocol_addr);
+
+        buffer
+    }
+
+    pub fn build(self) -> Packet<Vec<u8>> {
+        Packet::new_unchecked(self.build_vec())
+    }
+}
+
+impl Default for PacketBuilder {
+    fn default() -> Self {
+        Self {
+            operation: Operation::Request,
+            sender_hardware_addr: MacAddr::UNSPECIFIED,
+            sender_protocol_addr: Ipv4Addr::UNSPECIFIED,
+            target_hardware_addr: MacAddr::UNSPECIFIED,
+            target_protocol_addr: Ipv4Addr::UNSPECIFIED,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use super::PacketBuilder;
+    use crate::arp::packet::{Operation, Packet};
+    use crate::ethernet::frame::MacAddr;
+
+    #[test]
+    fn build() {
+        let bytes = PacketBuilder::default()
+            .operation(Operation::Reply)
+            .sender_hardware_addr(MacAddr([0x02, 0, 0, 0, 0, 0x02]))
+            .sender_protocol_addr(Ipv4Addr::new(10, 0, 0, 2))
+            .target_hardware_addr(MacAddr([0x02, 0, 0, 0, 0, 0x01]))
+            .target_protocol_addr(Ipv4Addr::new(10, 0, 0, 1))
+            .build_vec();
+
+        let packet = Packet::new_checked(bytes.as_slice()).unwrap();
+        assert_eq!(packet.operation(), Operation::Reply);
+        assert_eq!(packet.sender_hardware_addr(), MacAddr([0x02, 0, 0, 0, 0, 0x02]));
+        assert_eq!(packet.sender_protocol_addr(), Ipv4Addr::new(10, 0, 0, 2));
+        assert_eq!(packet.target_hardware_addr(), MacAddr([0x02, 0, 0, 0, 0, 0x01]));
+        assert_eq!(packet.target_protocol_addr(), Ipv4Addr::new(10, 0, 0, 1));
+    }
+}
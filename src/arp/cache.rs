@@ -0,0 +1,561 @@
+use std::collections::{HashMap, VecDeque};
+use std::net::Ipv4Addr;
+use std::time::{Duration, Instant};
+
+use crate::arp::builder::PacketBuilder;
+use crate::arp::error::Error;
+use crate::arp::packet::{Operation, Packet};
+use crate::error::Result;
+use crate::ethernet::frame::MacAddr;
+use crate::macros::diagnostics::debug;
+
+pub mod consts {
+    use std::time::Duration;
+
+    pub const DEFAULT_ENTRY_TIMEOUT: Duration = Duration::from_secs(60); // How long a resolved address is trusted
+    pub const STALE_TIMEOUT: Duration = Duration::from_secs(600); // Unused stale entries are dropped past it
+    pub const FAILED_TIMEOUT: Duration = Duration::from_secs(20); // How long sending to an unreachable address fails
+    pub const RETRANSMIT_INTERVAL: Duration = Duration::from_secs(1); // Doubled after each unanswered request
+    pub const MAX_REQUESTS: usize = 3; // Requests sent before an address is given up as unreachable
+    pub const MAX_PROBES: usize = 3; // Unicast requests confirming a stale address before it is given up
+    pub const MAX_PENDING: usize = 16; // Packets queued per unresolved address, the oldest ones are dropped
+    pub const ANNOUNCE_NUM: usize = 2; // RFC 5227 section 1.1, announcements sent for a configured address
+    pub const ANNOUNCE_INTERVAL: Duration = Duration::from_secs(2);
+}
+
+/// The reachability state of a neighbor, as Neighbor Unreachability Detection tracks it for IPv6
+/// (RFC 4861 section 7.3.2).
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum State {
+    /// Requests were broadcast, the packets to the neighbor wait for the reply.
+    Incomplete,
+    /// The neighbor answered recently.
+    Reachable,
+    /// The address may be outdated, it is used but probed the next time a packet is sent.
+    Stale,
+    /// Requests are unicast to the known address to confirm it, which is used meanwhile.
+    Probe,
+    /// The neighbor did not answer, sending to it fails for a while.
+    Failed,
+    /// The entry was installed by hand, it never expires.
+    Static,
+}
+
+#[derive(Debug)]
+enum Entry {
+    Static(MacAddr),
+    /// Reachable until the timer, then stale until it is used or the timer expires again.
+    /// A probed entry sends its next request at the timer.
+    Resolved {
+        mac_addr: MacAddr,
+        state: State,
+        probes: usize,
+        timer: Instant,
+    },
+    Pending {
+        queue: VecDeque<Vec<u8>>,
+        requests: usize,
+        next_request: Instant,
+    },
+    Failed {
+        until: Instant,
+    },
+}
+
+/// A request to send.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Request {
+    pub target: Ipv4Addr,
+    /// The address to unicast a probe to, none to broadcast the request.
+    pub mac_addr: Option<MacAddr>,
+}
+
+/// The outcome of running the timers of the cache.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Polled {
+    pub requests: Vec<Request>,
+    /// The addresses given up as unreachable, with the packets that waited for them,
+    /// whose senders are to be told the host is unreachable.
+    pub unreachable: Vec<(Ipv4Addr, Vec<Vec<u8>>)>,
+}
+
+/// The outcome of an ARP packet handled by the cache.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Processed {
+    /// The reply to send back to the sender of a request for our address.
+    pub reply: Option<Vec<u8>>,
+    /// The packets which waited for the sender to be resolved, now to be sent to it.
+    pub flushed: Vec<Vec<u8>>,
+}
+
+/// A cache of the Ethernet addresses of IPv4 neighbors (RFC 826), queueing the packets
+/// to a neighbor while its address is being resolved. Entries go through the reachability states of `State`:
+/// a stale address is probed when it is used again, and given up when the probes are not answered.
+#[derive(Debug)]
+pub struct ArpCache {
+    entries: HashMap<Ipv4Addr, Entry>,
+    entry_timeout: Duration,
+    /// The networks, as address and netmask, answered for by proxy ARP (RFC 1027).
+    proxy_networks: Vec<(Ipv4Addr, Ipv4Addr)>,
+}
+
+impl ArpCache {
+    pub fn new(entry_timeout: Duration) -> Self {
+        Self {
+            entries: HashMap::new(),
+            entry_timeout,
+            proxy_networks: vec![],
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Forget every entry, dropping the queued packets.
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    /// Remove the entry of `addr`, static or not.
+    pub fn remove(&mut self, addr: Ipv4Addr) {
+        self.entries.remove(&addr);
+    }
+
+    pub fn state(&self, addr: Ipv4Addr) -> Option<State> {
+        match self.entries.get(&addr)? {
+            Entry::Static(_) => Some(State::Static),
+            Entry::Resolved { state, .. } => Some(*state),
+            Entry::Pending { .. } => Some(State::Incomplete),
+            Entry::Failed { .. } => Some(State::Failed),
+        }
+    }
+
+    /// Returns the Ethernet address to send a packet to `addr` at `now`, if resolved.
+    /// Sending to a stale neighbor has it probed by the next `poll`.
+    pub fn lookup(&mut self, addr: Ipv4Addr, now: Instant) -> Option<MacAddr> {
+        match self.entries.get_mut(&addr)? {
+            Entry::Static(mac_addr) => Some(*mac_addr),
+            Entry::Resolved {
+                mac_addr,
+                state,
+                probes,
+                timer,
+            } => {
+                if *state == State::Stale || (*state == State::Reachable && *timer <= now) {
+                    *state = State::Probe;
+                    *probes = 0;
+                    *timer = now;
+                }
+                Some(*mac_addr)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn is_static(&self, addr: Ipv4Addr) -> bool {
+        matches!(self.entries.get(&addr), Some(Entry::Static(_)))
+    }
+
+    /// Answer the requests for the addresses of `network` with our own Ethernet address,
+    /// the interface forwarding what it receives for them.
+    pub fn add_proxy_network(&mut self, network: Ipv4Addr, netmask: Ipv4Addr) {
+        let network = Ipv4Addr::from(u32::from(network) & u32::from(netmask));
+        if !self.proxy_networks.contains(&(network, netmask)) {
+            self.proxy_networks.push((network, netmask));
+        }
+    }
+
+    pub fn remove_proxy_network(&mut self, network: Ipv4Addr, netmask: Ipv4Addr) {
+        let network = Ipv4Addr::from(u32::from(network) & u32::from(netmask));
+        self.proxy_networks.retain(|proxied| *proxied != (network, netmask));
+    }
+
+    fn is_proxied(&self, addr: Ipv4Addr) -> bool {
+        self.proxy_networks
+            .iter()
+            .any(|(network, netmask)| u32::from(addr) & u32::from(*netmask) == u32::from(*network))
+    }
+
+    /// Queue `packet` until `addr` is resolved, returns whether a request for it must be sent now.
+    /// Fails while `addr` is known to be unreachable.
+    pub fn enqueue(&mut self, addr: Ipv4Addr, packet: Vec<u8>, now: Instant) -> Result<bool> {
+        match self.entries.get_mut(&addr) {
+            Some(Entry::Pending { queue, .. }) => {
+                if queue.len() >= consts::MAX_PENDING {
+                    queue.pop_front();
+                }
+                queue.push_back(packet);
+                return Ok(false);
+            }
+            Some(Entry::Failed { until }) if *until > now => return Err(Error::HostUnreachable.into()),
+            _ => {}
+        }
+
+        self.entries.insert(
+            addr,
+            Entry::Pending {
+                queue: VecDeque::from(vec![packet]),
+                requests: 1,
+                next_request: now + backoff(1),
+            },
+        );
+        Ok(true)
+    }
+
+    /// Learn that `addr` is at `mac_addr`, returns the packets which waited for it.
+    /// A static entry of `addr` is kept.
+    pub fn insert(&mut self, addr: Ipv4Addr, mac_addr: MacAddr, now: Instant) -> Vec<Vec<u8>> {
+        if self.is_static(addr) {
+            return vec![];
+        }
+        let resolved = Entry::Resolved {
+            mac_addr,
+            state: State::Reachable,
+            probes: 0,
+            timer: now + self.entry_timeout,
+        };
+        match self.entries.insert(addr, resolved) {
+            Some(Entry::Pending { queue, .. }) => queue.into(),
+            _ => vec![],
+        }
+    }
+
+    /// Install a static entry, which never expires and is not updated by the packets received.
+    /// Returns the packets which waited for `addr`.
+    pub fn insert_static(&mut self, addr: Ipv4Addr, mac_addr: MacAddr) -> Vec<Vec<u8>> {
+        match self.entries.insert(addr, Entry::Static(mac_addr)) {
+            Some(Entry::Pending { queue, .. }) => queue.into(),
+            _ => vec![],
+        }
+    }
+
+    /// Run the timers of the entries: reachable entries become stale, stale ones unused for long are dropped,
+    /// and unanswered requests are sent again, each one waiting twice as long as the previous one.
+    /// An address unanswered after its requests or probes fails, handing back its queued packets.
+    pub fn poll(&mut self, now: Instant) -> Polled {
+        let mut polled = Polled::default();
+        let mut forgotten = vec![];
+        for (addr, entry) in self.entries.iter_mut() {
+            match entry {
+                Entry::Static(_) => {}
+                Entry::Resolved {
+                    mac_addr,
+                    state,
+                    probes,
+                    timer,
+                } => {
+                    if *timer > now {
+                        continue;
+                    }
+                    match state {
+                        State::Reachable => {
+                            *state = State::Stale;
+                            *timer = now + consts::STALE_TIMEOUT;
+                        }
+                        State::Probe if *probes < consts::MAX_PROBES => {
+                            *probes += 1;
+                            *timer = now + backoff(*probes);
+                            polled.requests.push(Request {
+                                target: *addr,
+                                mac_addr: Some(*mac_addr),
+                            });
+                        }
+                        State::Probe => {
+                            debug!("arp: {} no longer reachable", addr);
+                            *entry = Entry::Failed {
+                                until: now + consts::FAILED_TIMEOUT,
+                            };
+                        }
+                        _ => forgotten.push(*addr),
+                    }
+                }
+                Entry::Pending {
+                    queue,
+                    requests,
+                    next_request,
+                } => {
+                    if *next_request > now {
+                        continue;
+                    }
+                    if *requests >= consts::MAX_REQUESTS {
+                        debug!("arp: {} unreachable, {} queued packets dropped", addr, queue.len());
+                        polled.unreachable.push((*addr, std::mem::take(queue).into()));
+                        *entry = Entry::Failed {
+                            until: now + consts::FAILED_TIMEOUT,
+                        };
+                        continue;
+                    }
+                    *requests += 1;
+                    *next_request = now + backoff(*requests);
+                    polled.requests.push(Request {
+                        target: *addr,
+                        mac_addr: None,
+                    });
+                }
+                Entry::Failed { .. } => {}
+            }
+        }
+        for addr in forgotten {
+            self.entries.remove(&addr);
+        }
+        self.entries
+            .retain(|_, entry| !matches!(entry, Entry::Failed { until } if *until <= now));
+        polled
+    }
+
+    /// Handle an ARP packet received by the interface at `ip_addr` and `mac_addr`,
+    /// following the packet reception algorithm of RFC 826.
+    /// Requests for the proxied networks are answered too, but for the senders asking for their own address.
+    pub fn process(&mut self, packet: &Packet<&[u8]>, ip_addr: Ipv4Addr, mac_addr: MacAddr, now: Instant) -> Processed {
+        let sender = packet.sender_protocol_addr();
+        let target = packet.target_protocol_addr();
+        let mut processed = Processed::default();
+
+        if sender == ip_addr && packet.sender_hardware_addr() != mac_addr {
+            debug!(
+                "arp: address {} also claimed by {}",
+                ip_addr,
+                packet.sender_hardware_addr()
+            );
+            return processed;
+        }
+
+        // A probe (RFC 5227) has no sender address to learn.
+        let learnable = !sender.is_unspecified() && packet.sender_hardware_addr().is_unicast();
+        let known = self.entries.contains_key(&sender);
+        if learnable && known {
+            processed.flushed = self.insert(sender, packet.sender_hardware_addr(), now);
+        }
+
+        if target != ip_addr {
+            let proxied = target != sender && self.is_proxied(target);
+            if proxied && packet.operation() == Operation::Request {
+                processed.reply = Some(reply(packet, target, mac_addr));
+            }
+            return processed;
+        }
+        if learnable && !known {
+            self.insert(sender, packet.sender_hardware_addr(), now);
+        }
+        if packet.operation() == Operation::Request {
+            processed.reply = Some(reply(packet, ip_addr, mac_addr));
+        }
+        processed
+    }
+}
+
+impl Default for ArpCache {
+    fn default() -> Self {
+        Self::new(consts::DEFAULT_ENTRY_TIMEOUT)
+    }
+}
+
+/// Returns how long to wait for the answer to the `attempts`th request.
+fn backoff(attempts: usize) -> Duration {
+    consts::RETRANSMIT_INTERVAL * 2u32.pow(attempts.clamp(1, 6) as u32 - 1)
+}
+
+/// Build the reply to `request`, telling that `ip_addr` is at `mac_addr`.
+fn reply(request: &Packet<&[u8]>, ip_addr: Ipv4Addr, mac_addr: MacAddr) -> Vec<u8> {
+    PacketBuilder::default()
+        .operation(Operation::Reply)
+        .sender_hardware_addr(mac_addr)
+        .sender_protocol_addr(ip_addr)
+        .target_hardware_addr(request.sender_hardware_addr())
+        .target_protocol_addr(request.sender_protocol_addr())
+        .build_vec()
+}
+
+/// Build a request for the Ethernet address of `target`, to broadcast.
+pub fn request(mac_addr: MacAddr, ip_addr: Ipv4Addr, target: Ipv4Addr) -> Vec<u8> {
+    PacketBuilder::default()
+        .operation(Operation::Request)
+        .sender_hardware_addr(mac_addr)
+        .sender_protocol_addr(ip_addr)
+        .target_protocol_addr(target)
+        .build_vec()
+}
+
+/// Build a gratuitous ARP announcing that `ip_addr` is at `mac_addr`, to broadcast
+/// `ANNOUNCE_NUM` times once the address is configured so that neighbors update their caches (RFC 5227).
+pub fn announcement(mac_addr: MacAddr, ip_addr: Ipv4Addr) -> Vec<u8> {
+    request(mac_addr, ip_addr, ip_addr)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+    use std::time::{Duration, Instant};
+
+    use super::{announcement, consts, request, ArpCache, Polled, Request, State};
+    use crate::arp::builder::PacketBuilder;
+    use crate::arp::packet::{Operation, Packet};
+    use crate::ethernet::frame::MacAddr;
+
+    const IP_ADDR: Ipv4Addr = Ipv4Addr::new(10, 0, 0, 1);
+    const MAC_ADDR: MacAddr = MacAddr([0x02, 0, 0, 0, 0, 0x01]);
+    const NEIGHBOR: Ipv4Addr = Ipv4Addr::new(10, 0, 0, 2);
+    const NEIGHBOR_MAC: MacAddr = MacAddr([0x02, 0, 0, 0, 0, 0x02]);
+
+    #[test]
+    fn pending() {
+        let mut cache = ArpCache::default();
+        let now = Instant::now();
+
+        // The first packet triggers a request, later ones only wait.
+        assert!(cache.enqueue(NEIGHBOR, vec![1], now).unwrap());
+        assert!(!cache.enqueue(NEIGHBOR, vec![2], now).unwrap());
+        assert_eq!(cache.lookup(NEIGHBOR, now), None);
+
+        let reply = PacketBuilder::default()
+            .operation(Operation::Reply)
+            .sender_hardware_addr(NEIGHBOR_MAC)
+            .sender_protocol_addr(NEIGHBOR)
+            .target_hardware_addr(MAC_ADDR)
+            .target_protocol_addr(IP_ADDR)
+            .build_vec();
+        let processed = cache.process(&Packet::new_checked(reply.as_slice()).unwrap(), IP_ADDR, MAC_ADDR, now);
+        assert_eq!(processed.reply, None);
+        assert_eq!(processed.flushed, vec![vec![1], vec![2]]);
+        assert_eq!(cache.lookup(NEIGHBOR, now), Some(NEIGHBOR_MAC));
+
+        // A reachable entry becomes stale, and is probed once used again.
+        let later = now + consts::DEFAULT_ENTRY_TIMEOUT;
+        assert_eq!(cache.poll(later), Polled::default());
+        assert_eq!(cache.state(NEIGHBOR), Some(State::Stale));
+        assert_eq!(cache.lookup(NEIGHBOR, later), Some(NEIGHBOR_MAC));
+        assert_eq!(cache.state(NEIGHBOR), Some(State::Probe));
+        let probe = Request {
+            target: NEIGHBOR,
+            mac_addr: Some(NEIGHBOR_MAC),
+        };
+        assert_eq!(cache.poll(later).requests, vec![probe]);
+        assert_eq!(cache.poll(later + consts::RETRANSMIT_INTERVAL).requests, vec![probe]);
+        let processed = cache.process(
+            &Packet::new_checked(reply.as_slice()).unwrap(),
+            IP_ADDR,
+            MAC_ADDR,
+            later,
+        );
+        assert!(processed.flushed.is_empty());
+        assert_eq!(cache.state(NEIGHBOR), Some(State::Reachable));
+
+        // An unused stale entry is dropped.
+        cache.poll(later + consts::DEFAULT_ENTRY_TIMEOUT * 2);
+        cache.poll(later + consts::DEFAULT_ENTRY_TIMEOUT * 2 + consts::STALE_TIMEOUT);
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn unreachable() {
+        let mut cache = ArpCache::default();
+        let now = Instant::now();
+
+        // A request is sent again, each time waiting twice as long, until the address is given up.
+        assert!(cache.enqueue(NEIGHBOR, vec![3], now).unwrap());
+        let request = Request {
+            target: NEIGHBOR,
+            mac_addr: None,
+        };
+        let mut later = now;
+        for attempt in 1..consts::MAX_REQUESTS {
+            later += consts::RETRANSMIT_INTERVAL * 2u32.pow(attempt as u32 - 1);
+            assert_eq!(cache.poll(later - Duration::from_millis(1)), Polled::default());
+            assert_eq!(cache.poll(later).requests, vec![request]);
+        }
+        later += consts::RETRANSMIT_INTERVAL * 2u32.pow(consts::MAX_REQUESTS as u32 - 1);
+        let polled = cache.poll(later);
+        assert_eq!(polled.unreachable, vec![(NEIGHBOR, vec![vec![3]])]);
+        assert_eq!(cache.state(NEIGHBOR), Some(State::Failed));
+        assert_eq!(cache.lookup(NEIGHBOR, later), None);
+        assert!(cache.enqueue(NEIGHBOR, vec![4], later).is_err());
+
+        cache.poll(later + consts::FAILED_TIMEOUT);
+        assert!(cache.is_empty());
+        assert!(cache
+            .enqueue(NEIGHBOR, vec![4], later + consts::FAILED_TIMEOUT)
+            .unwrap());
+
+        // A probed neighbor which stops answering fails as well.
+        cache.insert(NEIGHBOR, NEIGHBOR_MAC, now);
+        let later = now + consts::DEFAULT_ENTRY_TIMEOUT;
+        assert_eq!(cache.lookup(NEIGHBOR, later), Some(NEIGHBOR_MAC));
+        let mut at = later;
+        for probe in 1..=consts::MAX_PROBES {
+            assert_eq!(cache.poll(at).requests.len(), 1);
+            at += consts::RETRANSMIT_INTERVAL * 2u32.pow(probe as u32 - 1);
+        }
+        assert!(cache.poll(at).unreachable.is_empty());
+        assert_eq!(cache.state(NEIGHBOR), Some(State::Failed));
+    }
+
+    #[test]
+    fn reply() {
+        let mut cache = ArpCache::default();
+        let now = Instant::now();
+
+        let bytes = request(NEIGHBOR_MAC, NEIGHBOR, IP_ADDR);
+        let processed = cache.process(&Packet::new_checked(bytes.as_slice()).unwrap(), IP_ADDR, MAC_ADDR, now);
+        let reply = processed.reply.unwrap();
+        let reply = Packet::new_checked(reply.as_slice()).unwrap();
+        assert_eq!(reply.operation(), Operation::Reply);
+        assert_eq!(reply.sender_hardware_addr(), MAC_ADDR);
+        assert_eq!(reply.target_hardware_addr(), NEIGHBOR_MAC);
+        assert_eq!(reply.target_protocol_addr(), NEIGHBOR);
+        // The requester is learnt, as it is about to talk to us.
+        assert_eq!(cache.lookup(NEIGHBOR, now), Some(NEIGHBOR_MAC));
+
+        // Requests for other hosts are not answered, and their senders are not learnt.
+        let other = Ipv4Addr::new(10, 0, 0, 3);
+        let bytes = request(MacAddr([0x02, 0, 0, 0, 0, 0x03]), other, NEIGHBOR);
+        let processed = cache.process(&Packet::new_checked(bytes.as_slice()).unwrap(), IP_ADDR, MAC_ADDR, now);
+        assert_eq!(processed.reply, None);
+        assert_eq!(cache.lookup(other, now), None);
+    }
+
+    #[test]
+    fn proxy_static() {
+        let mut cache = ArpCache::default();
+        let now = Instant::now();
+
+        // A static entry is neither updated nor expired.
+        assert!(cache.enqueue(NEIGHBOR, vec![1], now).unwrap());
+        assert_eq!(cache.insert_static(NEIGHBOR, NEIGHBOR_MAC), vec![vec![1]]);
+        let moved = announcement(MacAddr([0x02, 0, 0, 0, 0, 0x09]), NEIGHBOR);
+        cache.process(&Packet::new_checked(moved.as_slice()).unwrap(), IP_ADDR, MAC_ADDR, now);
+        assert_eq!(cache.poll(now + consts::DEFAULT_ENTRY_TIMEOUT * 2), Polled::default());
+        assert_eq!(cache.lookup(NEIGHBOR, now), Some(NEIGHBOR_MAC));
+
+        // A gratuitous ARP updates the cached address of its sender.
+        let other = Ipv4Addr::new(10, 0, 0, 3);
+        cache.insert(other, MacAddr([0x02, 0, 0, 0, 0, 0x03]), now);
+        let moved = announcement(MacAddr([0x02, 0, 0, 0, 0, 0x04]), other);
+        let processed = cache.process(&Packet::new_checked(moved.as_slice()).unwrap(), IP_ADDR, MAC_ADDR, now);
+        assert_eq!(processed.reply, None);
+        assert_eq!(cache.lookup(other, now), Some(MacAddr([0x02, 0, 0, 0, 0, 0x04])));
+
+        // Requests for the proxied network are answered with our address, announcements are not.
+        cache.add_proxy_network(Ipv4Addr::new(10, 1, 2, 3), Ipv4Addr::new(255, 255, 0, 0));
+        let bytes = request(NEIGHBOR_MAC, NEIGHBOR, Ipv4Addr::new(10, 1, 9, 9));
+        let reply = cache
+            .process(&Packet::new_checked(bytes.as_slice()).unwrap(), IP_ADDR, MAC_ADDR, now)
+            .reply
+            .unwrap();
+        let reply = Packet::new_checked(reply.as_slice()).unwrap();
+        assert_eq!(reply.sender_protocol_addr(), Ipv4Addr::new(10, 1, 9, 9));
+        assert_eq!(reply.sender_hardware_addr(), MAC_ADDR);
+        let bytes = announcement(NEIGHBOR_MAC, Ipv4Addr::new(10, 1, 9, 9));
+        let processed = cache.process(&Packet::new_checked(bytes.as_slice()).unwrap(), IP_ADDR, MAC_ADDR, now);
+        assert_eq!(processed.reply, None);
+
+        cache.remove_proxy_network(Ipv4Addr::new(10, 1, 0, 0), Ipv4Addr::new(255, 255, 0, 0));
+        let bytes = request(NEIGHBOR_MAC, NEIGHBOR, Ipv4Addr::new(10, 1, 9, 9));
+        let processed = cache.process(&Packet::new_checked(bytes.as_slice()).unwrap(), IP_ADDR, MAC_ADDR, now);
+        assert_eq!(processed.reply, None);
+    }
+}
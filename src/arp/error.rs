@@ -0,0 +1,22 @@
+use core::fmt::{Display, Formatter};
+
+#[derive(Debug)]
+pub enum Error {
+    InvalidLength,
+    /// Only Ethernet hardware addresses and IPv4 protocol addresses are supported.
+    UnsupportedAddress,
+    /// The neighbor did not answer the requests for its address.
+    HostUnreachable,
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Error::InvalidLength => write!(f, "invalid length"),
+            Error::UnsupportedAddress => write!(f, "unsupported address type"),
+            Error::HostUnreachable => write!(f, "host unreachable"),
+        }
+    }
+}
+
+impl core::error::Error for Error {}
@@ -0,0 +1,5 @@
+pub mod builder;
+#[cfg(feature = "std")]
+pub mod cache;
+pub mod error;
+pub mod packet;
@@ -0,0 +1,196 @@
+use core::convert::TryInto;
+use core::fmt::{Debug, Formatter};
+use core::net::Ipv4Addr;
+
+use crate::arp::error::Error;
+use crate::c_like_enum;
+use crate::error::Result;
+use crate::ethernet::frame::{EtherType, MacAddr};
+
+pub mod consts {
+    pub const LEN: usize = 28; // RFC 826, with Ethernet and IPv4 addresses
+    pub const HARDWARE_ETHERNET: u16 = 1;
+    pub const HARDWARE_LEN: u8 = 6;
+    pub const PROTOCOL_LEN: u8 = 4;
+}
+
+c_like_enum!(
+    /// ARP operation codes (RFC 826)
+    #[derive(Debug, Copy, Clone, PartialEq, Eq)]
+    pub enum Operation(u16) {
+        Request = 1,
+        Reply = 2,
+    }
+);
+
+/// An ARP packet resolving IPv4 addresses to Ethernet addresses (RFC 826).
+pub struct Packet<Buf> {
+    buffer: Buf,
+}
+
+impl<Buf> Packet<Buf>
+where
+    Buf: AsRef<[u8]>,
+{
+    pub fn new_unchecked(buffer: Buf) -> Self {
+        Packet { buffer }
+    }
+
+    pub fn new_checked(buffer: Buf) -> Result<Self> {
+        let packet = Self::new_unchecked(buffer);
+        if packet.buffer.as_ref().len() < consts::LEN {
+            return Err(Error::InvalidLength.into());
+        }
+        if packet.hardware_type() != consts::HARDWARE_ETHERNET
+            || packet.protocol_type() != EtherType::Ipv4
+            || packet.hardware_len() != consts::HARDWARE_LEN
+            || packet.protocol_len() != consts::PROTOCOL_LEN
+        {
+            return Err(Error::UnsupportedAddress.into());
+        }
+        Ok(packet)
+    }
+
+    pub fn hardware_type(&self) -> u16 {
+        u16::from_be_bytes([self.buffer.as_ref()[0], self.buffer.as_ref()[1]])
+    }
+
+    pub fn protocol_type(&self) -> EtherType {
+        u16::from_be_bytes([self.buffer.as_ref()[2], self.buffer.as_ref()[3]]).into()
+    }
+
+    pub fn hardware_len(&self) -> u8 {
+        self.buffer.as_ref()[4]
+    }
+
+    pub fn protocol_len(&self) -> u8 {
+        self.buffer.as_ref()[5]
+    }
+
+    pub fn operation(&self) -> Operation {
+        u16::from_be_bytes([self.buffer.as_ref()[6], self.buffer.as_ref()[7]]).into()
+    }
+
+    pub fn sender_hardware_addr(&self) -> MacAddr {
+        MacAddr(self.buffer.as_ref()[8..14].try_into().unwrap())
+    }
+
+    pub fn sender_protocol_addr(&self) -> Ipv4Addr {
+        let buffer = self.buffer.as_ref();
+        Ipv4Addr::new(buffer[14], buffer[15], buffer[16], buffer[17])
+    }
+
+    pub fn target_hardware_addr(&self) -> MacAddr {
+        MacAddr(self.buffer.as_ref()[18..24].try_into().unwrap())
+    }
+
+    pub fn target_protocol_addr(&self) -> Ipv4Addr {
+        let buffer = self.buffer.as_ref();
+        Ipv4Addr::new(buffer[24], buffer[25], buffer[26], buffer[27])
+    }
+}
+
+impl<Buf> Packet<Buf>
+where
+    Buf: AsRef<[u8]> + AsMut<[u8]>,
+{
+    /// Set the hardware and protocol types and lengths of Ethernet and IPv4.
+    pub fn set_address_types(&mut self) {
+        let buffer = self.buffer.as_mut();
+        buffer[0..=1].copy_from_slice(consts::HARDWARE_ETHERNET.to_be_bytes().as_ref());
+        buffer[2..=3].copy_from_slice(u16::from(EtherType::Ipv4).to_be_bytes().as_ref());
+        buffer[4] = consts::HARDWARE_LEN;
+        buffer[5] = consts::PROTOCOL_LEN;
+    }
+
+    pub fn set_operation(&mut self, operation: Operation) {
+        self.buffer.as_mut()[6..=7].copy_from_slice(u16::from(operation).to_be_bytes().as_ref());
+    }
+
+    pub fn set_sender_hardware_addr(&mut self, addr: MacAddr) {
+        self.buffer.as_mut()[8..14].copy_from_slice(addr.0.as_ref());
+    }
+
+    pub fn set_sender_protocol_addr(&mut self, addr: Ipv4Addr) {
+        self.buffer.as_mut()[14..18].copy_from_slice(addr.octets().as_ref());
+    }
+
+    pub fn set_target_hardware_addr(&mut self, addr: MacAddr) {
+        self.buffer.as_mut()[18..24].copy_from_slice(addr.0.as_ref());
+    }
+
+    pub fn set_target_protocol_addr(&mut self, addr: Ipv4Addr) {
+        self.buffer.as_mut()[24..28].copy_from_slice(addr.octets().as_ref());
+    }
+}
+
+impl<Buf> Debug for Packet<Buf>
+where
+    Buf: AsRef<[u8]>,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "operation: {:?}, sender: {} at {}, target: {} at {}",
+            self.operation(),
+            self.sender_protocol_addr(),
+            self.sender_hardware_addr(),
+            self.target_protocol_addr(),
+            self.target_hardware_addr(),
+        )
+    }
+}
+
+impl<Buf> AsRef<[u8]> for Packet<Buf>
+where
+    Buf: AsRef<[u8]>,
+{
+    fn as_ref(&self) -> &[u8] {
+        self.buffer.as_ref()
+    }
+}
+
+impl<Buf> AsMut<[u8]> for Packet<Buf>
+where
+    Buf: AsMut<[u8]>,
+{
+    fn as_mut(&mut self) -> &mut [u8] {
+        self.buffer.as_mut()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use super::{Operation, Packet};
+    use crate::ethernet::frame::MacAddr;
+
+    #[test]
+    fn new_checked() {
+        let mut bytes: Vec<u8> = vec![
+            // hardware and protocol types and lengths
+            0x00, 0x01, 0x08, 0x00, 0x06, 0x04, //
+            // operation
+            0x00, 0x01, //
+            // sender addresses
+            0x02, 0x00, 0x00, 0x00, 0x00, 0x01, 0xc0, 0xa8, 0xe9, 0xe9, //
+            // target addresses
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xc0, 0xa8, 0xe9, 0xea, //
+            // padding of the frame
+            0x00, 0x00,
+        ];
+
+        let packet = Packet::new_checked(bytes.as_slice()).expect("a valid arp packet");
+        assert_eq!(packet.operation(), Operation::Request);
+        assert_eq!(packet.sender_hardware_addr(), MacAddr([0x02, 0, 0, 0, 0, 0x01]));
+        assert_eq!(packet.sender_protocol_addr(), Ipv4Addr::new(192, 168, 233, 233));
+        assert!(packet.target_hardware_addr().is_unspecified());
+        assert_eq!(packet.target_protocol_addr(), Ipv4Addr::new(192, 168, 233, 234));
+        assert!(Packet::new_checked(&bytes[..27]).is_err());
+
+        // IPv6 protocol addresses are not supported.
+        bytes[2..4].copy_from_slice(&[0x86, 0xdd]);
+        assert!(Packet::new_checked(bytes.as_slice()).is_err());
+    }
+}
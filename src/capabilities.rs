@@ -0,0 +1,123 @@
+use std::fmt::{Display, Formatter};
+
+/// Every cargo feature of the crate, with whether this build has it enabled.
+const FEATURES: [(&str, bool); 6] = [
+    ("std", cfg!(feature = "std")),
+    ("tokio", cfg!(feature = "tokio")),
+    ("defmt", cfg!(feature = "defmt")),
+    ("tracing", cfg!(feature = "tracing")),
+    ("arbitrary", cfg!(feature = "arbitrary")),
+    ("proptest", cfg!(feature = "proptest")),
+];
+
+/// The device backend the stack is built on.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Backend {
+    Tun,
+    Tap,
+    /// A packet socket bound to a network interface of the host.
+    RawSocket,
+}
+
+/// Work that is offloaded to the device instead of being done by the stack.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub struct Offload {
+    pub checksum: bool,
+    pub segmentation: bool,
+}
+
+/// A runtime report of what this build of radish supports,
+/// meant to be included in bug reports and diagnostics dumps.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Capabilities {
+    pub version: &'static str,
+    pub features: Vec<&'static str>,
+    pub protocols: Vec<&'static str>,
+    pub backends: Vec<Backend>,
+    pub offload: Offload,
+}
+
+impl Capabilities {
+    pub fn supports_protocol(&self, protocol: &str) -> bool {
+        self.protocols
+            .iter()
+            .any(|supported| supported.eq_ignore_ascii_case(protocol))
+    }
+
+    pub fn has_feature(&self, feature: &str) -> bool {
+        self.features.contains(&feature)
+    }
+}
+
+impl Display for Capabilities {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "radish {}, features: [{}], protocols: [{}], backends: {:?}, checksum offload: {}, segmentation offload: {}",
+            self.version,
+            self.features.join(", "),
+            self.protocols.join(", "),
+            self.backends,
+            self.offload.checksum,
+            self.offload.segmentation,
+        )
+    }
+}
+
+/// Returns the capabilities of the running build.
+pub fn capabilities() -> Capabilities {
+    let features = FEATURES
+        .iter()
+        .filter(|(_, enabled)| *enabled)
+        .map(|(feature, _)| *feature)
+        .collect();
+    // TAP devices and packet sockets are only supported on Linux.
+    let backends = if cfg!(target_os = "linux") {
+        vec![Backend::Tun, Backend::Tap, Backend::RawSocket]
+    } else {
+        vec![Backend::Tun]
+    };
+
+    Capabilities {
+        version: env!("CARGO_PKG_VERSION"),
+        features,
+        protocols: vec![
+            "ethernet", "lldp", "arp", "ipv4", "ipv6", "6in4", "icmpv4", "icmpv6", "ndp", "mld", "tcp", "udp",
+            "udplite", "dns", "dhcp", "tftp",
+        ],
+        backends,
+        offload: Offload::default(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn capabilities() {
+        let capabilities = super::capabilities();
+
+        assert_eq!(capabilities.version, env!("CARGO_PKG_VERSION"));
+        assert!(capabilities.supports_protocol("IPv4"));
+        assert!(!capabilities.supports_protocol("sctp"));
+        assert!(capabilities.to_string().starts_with("radish "));
+        assert!(capabilities.has_feature("std"));
+        assert!(capabilities.supports_protocol("udplite"));
+    }
+
+    #[test]
+    fn features() {
+        let manifest = include_str!("../Cargo.toml");
+        let declared: Vec<&str> = manifest
+            .lines()
+            .skip_while(|line| *line != "[features]")
+            .skip(1)
+            .take_while(|line| !line.starts_with('['))
+            .filter_map(|line| line.split_once(" = "))
+            .map(|(feature, _)| feature)
+            .filter(|feature| *feature != "default")
+            .collect();
+        let listed: Vec<&str> = super::FEATURES.iter().map(|(feature, _)| *feature).collect();
+
+        assert_eq!(listed, declared);
+    }
+}
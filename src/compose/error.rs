@@ -0,0 +1,20 @@
+use core::fmt::{Display, Formatter};
+
+#[derive(Debug)]
+pub enum Error {
+    /// A transport layer is not enclosed by an IP layer its checksum could be computed with.
+    MissingIpLayer,
+    /// The protocol of a layer's payload could not be inferred and was not set.
+    UnknownProtocol,
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Error::MissingIpLayer => write!(f, "missing ip layer"),
+            Error::UnknownProtocol => write!(f, "unknown protocol"),
+        }
+    }
+}
+
+impl core::error::Error for Error {}
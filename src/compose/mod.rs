@@ -0,0 +1,550 @@
+//! Compose packets by stacking layers, outermost first, with `/` the way scapy does:
+//! `Ipv4Layer::new(src, dest) / IcmpLayer::echo(1, 1) / payload`.
+//!
+//! Building the stack fills what the layers imply of each other, the lengths, the protocol of
+//! the payload and all the checksums, so only the fields that matter have to be set.
+
+use alloc::vec;
+use alloc::vec::Vec;
+use core::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use core::ops::Div;
+
+use crate::compose::error::Error;
+use crate::error::Result;
+use crate::ethernet::builder::FrameBuilder;
+use crate::ethernet::frame::{EtherType, MacAddr};
+use crate::icmpv4::builder::EchoBuilder as Icmpv4EchoBuilder;
+use crate::icmpv6::builder::EchoBuilder as Icmpv6EchoBuilder;
+use crate::ipv4::builder::PacketBuilder as Ipv4PacketBuilder;
+use crate::ipv4::packet::Protocol;
+use crate::ipv6::builder::PacketBuilder as Ipv6PacketBuilder;
+use crate::tcp::packet::Packet as TcpPacket;
+use crate::tcp::repr::{Control, Repr as TcpRepr};
+use crate::udp::builder::PacketBuilder as UdpPacketBuilder;
+
+pub mod error;
+
+mod consts {
+    pub const DEFAULT_TTL: u8 = 64;
+}
+
+/// An Ethernet header, the EtherType being inferred from the layer it carries unless set.
+#[derive(Debug, Clone)]
+pub struct EthernetLayer {
+    src_addr: MacAddr,
+    dest_addr: MacAddr,
+    ether_type: Option<EtherType>,
+}
+
+impl EthernetLayer {
+    pub fn new(src_addr: MacAddr, dest_addr: MacAddr) -> Self {
+        Self {
+            src_addr,
+            dest_addr,
+            ether_type: None,
+        }
+    }
+
+    pub fn ether_type(mut self, ether_type: EtherType) -> Self {
+        self.ether_type = Some(ether_type);
+        self
+    }
+}
+
+/// An IPv4 header, the protocol being inferred from the layer it carries unless set.
+#[derive(Debug, Clone)]
+pub struct Ipv4Layer {
+    src_addr: Ipv4Addr,
+    dest_addr: Ipv4Addr,
+    tos: u8,
+    identification: u16,
+    flags: u8,
+    ttl: u8,
+    protocol: Option<Protocol>,
+}
+
+impl Ipv4Layer {
+    pub fn new(src_addr: Ipv4Addr, dest_addr: Ipv4Addr) -> Self {
+        Self {
+            src_addr,
+            dest_addr,
+            tos: 0,
+            identification: 0,
+            flags: 0,
+            ttl: consts::DEFAULT_TTL,
+            protocol: None,
+        }
+    }
+
+    pub fn tos(mut self, tos: u8) -> Self {
+        self.tos = tos;
+        self
+    }
+
+    pub fn identification(mut self, identification: u16) -> Self {
+        self.identification = identification;
+        self
+    }
+
+    pub fn flags(mut self, flags: u8) -> Self {
+        self.flags = flags;
+        self
+    }
+
+    pub fn ttl(mut self, ttl: u8) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    pub fn protocol(mut self, protocol: Protocol) -> Self {
+        self.protocol = Some(protocol);
+        self
+    }
+}
+
+/// An IPv6 header, the next header being inferred from the layer it carries unless set.
+#[derive(Debug, Clone)]
+pub struct Ipv6Layer {
+    src_addr: Ipv6Addr,
+    dest_addr: Ipv6Addr,
+    traffic_class: u8,
+    flow_label: u32,
+    hop_limit: u8,
+    next_header: Option<Protocol>,
+}
+
+impl Ipv6Layer {
+    pub fn new(src_addr: Ipv6Addr, dest_addr: Ipv6Addr) -> Self {
+        Self {
+            src_addr,
+            dest_addr,
+            traffic_class: 0,
+            flow_label: 0,
+            hop_limit: consts::DEFAULT_TTL,
+            next_header: None,
+        }
+    }
+
+    pub fn traffic_class(mut self, traffic_class: u8) -> Self {
+        self.traffic_class = traffic_class;
+        self
+    }
+
+    pub fn flow_label(mut self, flow_label: u32) -> Self {
+        self.flow_label = flow_label;
+        self
+    }
+
+    pub fn hop_limit(mut self, hop_limit: u8) -> Self {
+        self.hop_limit = hop_limit;
+        self
+    }
+
+    pub fn next_header(mut self, next_header: Protocol) -> Self {
+        self.next_header = Some(next_header);
+        self
+    }
+}
+
+/// A TCP header, checksummed with the addresses of the enclosing IP layer.
+#[derive(Debug, Clone)]
+pub struct TcpLayer {
+    repr: TcpRepr,
+}
+
+impl TcpLayer {
+    pub fn new(src_port: u16, dest_port: u16) -> Self {
+        Self {
+            repr: TcpRepr {
+                src_port,
+                dest_port,
+                ..TcpRepr::default()
+            },
+        }
+    }
+
+    /// Use every field of `repr` but the payload length, which is that of the layers carried.
+    pub fn repr(mut self, repr: TcpRepr) -> Self {
+        self.repr = repr;
+        self
+    }
+
+    pub fn control(mut self, control: Control) -> Self {
+        self.repr.control = control;
+        self
+    }
+
+    pub fn seq_number(mut self, seq_number: u32) -> Self {
+        self.repr.seq_number = seq_number;
+        self
+    }
+
+    pub fn ack_number(mut self, ack_number: u32) -> Self {
+        self.repr.ack_number = Some(ack_number);
+        self
+    }
+
+    pub fn window(mut self, window: u16) -> Self {
+        self.repr.window = window;
+        self
+    }
+}
+
+/// A UDP header, checksummed with the addresses of the enclosing IP layer.
+#[derive(Debug, Clone)]
+pub struct UdpLayer {
+    src_port: u16,
+    dest_port: u16,
+}
+
+impl UdpLayer {
+    pub fn new(src_port: u16, dest_port: u16) -> Self {
+        Self { src_port, dest_port }
+    }
+}
+
+/// An ICMP echo request or reply, the layers carried being its data.
+#[derive(Debug, Clone)]
+pub struct IcmpLayer {
+    reply: bool,
+    identifier: u16,
+    sequence_number: u16,
+}
+
+impl IcmpLayer {
+    pub fn echo(identifier: u16, sequence_number: u16) -> Self {
+        Self {
+            reply: false,
+            identifier,
+            sequence_number,
+        }
+    }
+
+    pub fn echo_reply(identifier: u16, sequence_number: u16) -> Self {
+        Self {
+            reply: true,
+            identifier,
+            sequence_number,
+        }
+    }
+}
+
+/// An ICMPv6 echo request or reply, checksummed with the addresses of the enclosing IPv6 layer.
+#[derive(Debug, Clone)]
+pub struct Icmpv6Layer {
+    reply: bool,
+    identifier: u16,
+    sequence_number: u16,
+}
+
+impl Icmpv6Layer {
+    pub fn echo(identifier: u16, sequence_number: u16) -> Self {
+        Self {
+            reply: false,
+            identifier,
+            sequence_number,
+        }
+    }
+
+    pub fn echo_reply(identifier: u16, sequence_number: u16) -> Self {
+        Self {
+            reply: true,
+            identifier,
+            sequence_number,
+        }
+    }
+}
+
+/// A layer of a stack.
+#[derive(Debug, Clone)]
+pub enum Layer {
+    Ethernet(EthernetLayer),
+    Ipv4(Ipv4Layer),
+    Ipv6(Ipv6Layer),
+    Tcp(TcpLayer),
+    Udp(UdpLayer),
+    Icmp(IcmpLayer),
+    Icmpv6(Icmpv6Layer),
+    /// Octets copied as they are, usually the innermost payload.
+    Raw(Vec<u8>),
+}
+
+impl Layer {
+    /// Returns the protocol number an IP layer carrying this layer is given.
+    fn protocol(&self) -> Option<Protocol> {
+        match self {
+            Layer::Ipv6(_) => Some(Protocol::Ipv6),
+            Layer::Tcp(_) => Some(Protocol::Tcp),
+            Layer::Udp(_) => Some(Protocol::Udp),
+            Layer::Icmp(_) => Some(Protocol::Icmp),
+            Layer::Icmpv6(_) => Some(Protocol::Icmpv6),
+            _ => None,
+        }
+    }
+
+    /// Returns the EtherType an Ethernet layer carrying this layer is given.
+    fn ether_type(&self) -> Option<EtherType> {
+        match self {
+            Layer::Ipv4(_) => Some(EtherType::Ipv4),
+            Layer::Ipv6(_) => Some(EtherType::Ipv6),
+            _ => None,
+        }
+    }
+}
+
+macro_rules! layer_from {
+    ($($variant:ident($layer:ty),)+) => {
+        $(
+            impl From<$layer> for Layer {
+                fn from(layer: $layer) -> Self {
+                    Layer::$variant(layer)
+                }
+            }
+
+            impl<L: Into<Layer>> Div<L> for $layer {
+                type Output = Stack;
+
+                fn div(self, inner: L) -> Stack {
+                    Stack::new(self) / inner
+                }
+            }
+        )+
+    };
+}
+
+layer_from! {
+    Ethernet(EthernetLayer),
+    Ipv4(Ipv4Layer),
+    Ipv6(Ipv6Layer),
+    Tcp(TcpLayer),
+    Udp(UdpLayer),
+    Icmp(IcmpLayer),
+    Icmpv6(Icmpv6Layer),
+}
+
+impl From<Vec<u8>> for Layer {
+    fn from(payload: Vec<u8>) -> Self {
+        Layer::Raw(payload)
+    }
+}
+
+impl From<&[u8]> for Layer {
+    fn from(payload: &[u8]) -> Self {
+        Layer::Raw(payload.to_vec())
+    }
+}
+
+/// Layers stacked outermost first, each carrying the ones after it.
+#[derive(Debug, Clone)]
+pub struct Stack {
+    layers: Vec<Layer>,
+}
+
+impl Stack {
+    pub fn new(layer: impl Into<Layer>) -> Self {
+        Self {
+            layers: vec![layer.into()],
+        }
+    }
+
+    pub fn layers(&self) -> &[Layer] {
+        &self.layers
+    }
+
+    /// Build the packet from the innermost layer outward, each layer wrapping the octets of the
+    /// layers it carries.
+    pub fn build(&self) -> Result<Vec<u8>> {
+        let mut payload = vec![];
+
+        for (index, layer) in self.layers.iter().enumerate().rev() {
+            let inner = self.layers.get(index + 1);
+
+            payload = match layer {
+                Layer::Ethernet(ethernet) => {
+                    let ether_type = ethernet
+                        .ether_type
+                        .or_else(|| inner.and_then(Layer::ether_type))
+                        .ok_or(Error::UnknownProtocol)?;
+                    FrameBuilder::default()
+                        .src_addr(ethernet.src_addr)
+                        .dest_addr(ethernet.dest_addr)
+                        .ether_type(ether_type)
+                        .payload(payload)
+                        .build_vec()
+                }
+                Layer::Ipv4(ipv4) => {
+                    let protocol = ipv4
+                        .protocol
+                        .or_else(|| inner.and_then(Layer::protocol))
+                        .ok_or(Error::UnknownProtocol)?;
+                    Ipv4PacketBuilder::default()
+                        .tos(ipv4.tos)
+                        .identification(ipv4.identification)
+                        .flags(ipv4.flags)
+                        .ttl(ipv4.ttl)
+                        .protocol(protocol)
+                        .src_addr(ipv4.src_addr)
+                        .dest_addr(ipv4.dest_addr)
+                        .payload(payload)
+                        .build_vec()
+                }
+                Layer::Ipv6(ipv6) => {
+                    let next_header = ipv6
+                        .next_header
+                        .or_else(|| inner.and_then(Layer::protocol))
+                        .unwrap_or(Protocol::NoNextHeader);
+                    Ipv6PacketBuilder::default()
+                        .traffic_class(ipv6.traffic_class)
+                        .flow_label(ipv6.flow_label)
+                        .hop_limit(ipv6.hop_limit)
+                        .next_header(next_header)
+                        .src_addr(ipv6.src_addr)
+                        .dest_addr(ipv6.dest_addr)
+                        .payload(payload)
+                        .build_vec()
+                }
+                Layer::Tcp(tcp) => {
+                    let (src_addr, dest_addr) = self.ip_addrs(index)?;
+                    let repr = TcpRepr {
+                        payload_len: payload.len(),
+                        ..tcp.repr
+                    };
+                    let mut buffer = vec![0; repr.header_len()];
+                    buffer.append(&mut payload);
+                    repr.emit(
+                        &mut TcpPacket::new_unchecked(buffer.as_mut_slice()),
+                        src_addr,
+                        dest_addr,
+                    );
+                    buffer
+                }
+                Layer::Udp(udp) => {
+                    let (src_addr, dest_addr) = self.ip_addrs(index)?;
+                    UdpPacketBuilder::default()
+                        .src_port(udp.src_port)
+                        .dest_port(udp.dest_port)
+                        .src_addr(src_addr)
+                        .dest_addr(dest_addr)
+                        .payload(payload)
+                        .build_vec()
+                }
+                Layer::Icmp(icmp) => Icmpv4EchoBuilder::default()
+                    .reply(icmp.reply)
+                    .identifier(icmp.identifier)
+                    .sequence_number(icmp.sequence_number)
+                    .payload(payload)
+                    .build_vec(),
+                Layer::Icmpv6(icmp) => {
+                    let (IpAddr::V6(src_addr), IpAddr::V6(dest_addr)) = self.ip_addrs(index)? else {
+                        return Err(Error::MissingIpLayer.into());
+                    };
+                    Icmpv6EchoBuilder::default()
+                        .reply(icmp.reply)
+                        .identifier(icmp.identifier)
+                        .sequence_number(icmp.sequence_number)
+                        .src_addr(src_addr)
+                        .dest_addr(dest_addr)
+                        .payload(payload)
+                        .build_vec()
+                }
+                Layer::Raw(raw) => {
+                    let mut buffer = raw.clone();
+                    buffer.append(&mut payload);
+                    buffer
+                }
+            };
+        }
+
+        Ok(payload)
+    }
+
+    /// Returns the addresses of the IP layer nearest outside the layer at `index`.
+    fn ip_addrs(&self, index: usize) -> Result<(IpAddr, IpAddr)> {
+        self.layers[..index]
+            .iter()
+            .rev()
+            .find_map(|layer| match layer {
+                Layer::Ipv4(ipv4) => Some((ipv4.src_addr.into(), ipv4.dest_addr.into())),
+                Layer::Ipv6(ipv6) => Some((ipv6.src_addr.into(), ipv6.dest_addr.into())),
+                _ => None,
+            })
+            .ok_or(Error::MissingIpLayer.into())
+    }
+}
+
+impl<L: Into<Layer>> Div<L> for Stack {
+    type Output = Stack;
+
+    fn div(mut self, inner: L) -> Stack {
+        self.layers.push(inner.into());
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    use super::{EthernetLayer, IcmpLayer, Ipv4Layer, Ipv6Layer, Stack, TcpLayer, UdpLayer};
+    use crate::checksum::checksum;
+    use crate::dissect::{dissect, Layer};
+    use crate::ethernet::frame::MacAddr;
+    use crate::icmpv4::packet::EchoAndEchoReplyPacket;
+    use crate::ipv4::packet::{Packet as Ipv4Packet, Protocol};
+    use crate::ipv4::verify::verify_header;
+    use crate::tcp::packet::Packet as TcpPacket;
+    use crate::tcp::repr::Control;
+    use crate::udp::packet::Packet as UdpPacket;
+
+    const SRC_ADDR: Ipv4Addr = Ipv4Addr::new(192, 168, 233, 233);
+    const DEST_ADDR: Ipv4Addr = Ipv4Addr::new(192, 168, 233, 234);
+
+    #[test]
+    fn compose() {
+        let packet = (Ipv4Layer::new(SRC_ADDR, DEST_ADDR) / IcmpLayer::echo(7, 1) / b"ping".to_vec())
+            .build()
+            .unwrap();
+        let ipv4 = Ipv4Packet::new_checked(packet.as_slice()).unwrap();
+        assert!(verify_header(&ipv4));
+        assert_eq!(ipv4.total_len() as usize, packet.len());
+        assert_eq!(ipv4.protocol(), Protocol::Icmp);
+        assert_eq!(checksum(ipv4.payload()), 0);
+        let echo = EchoAndEchoReplyPacket::new_checked(ipv4.payload()).unwrap();
+        assert!(echo.is_request());
+        assert_eq!((echo.identifier(), echo.sequence_number()), (7, 1));
+        assert_eq!(echo.payload(), b"ping");
+
+        let packet = (Ipv4Layer::new(SRC_ADDR, DEST_ADDR)
+            / TcpLayer::new(40000, 80).control(Control::Syn).seq_number(1000)
+            / b"data".as_slice())
+        .build()
+        .unwrap();
+        let ipv4 = Ipv4Packet::new_checked(packet.as_slice()).unwrap();
+        let tcp = TcpPacket::new_checked(ipv4.payload()).unwrap();
+        assert!(tcp.verify_checksum(SRC_ADDR, DEST_ADDR));
+        assert!(tcp.syn());
+        assert_eq!(tcp.payload(), b"data");
+
+        let (src_addr, dest_addr) = (Ipv6Addr::LOCALHOST, Ipv6Addr::LOCALHOST);
+        let frame = (EthernetLayer::new(MacAddr([0x02, 0, 0, 0, 0, 0x01]), MacAddr::BROADCAST)
+            / Ipv6Layer::new(src_addr, dest_addr)
+            / UdpLayer::new(5353, 53)
+            / vec![1, 2, 3])
+        .build()
+        .unwrap();
+        let dissection = dissect(&frame);
+        assert!(!dissection.is_malformed());
+        assert!(matches!(
+            dissection.layers()[1],
+            Layer::Ipv6 {
+                next_header: Protocol::Udp,
+                ..
+            }
+        ));
+        let udp = UdpPacket::new_checked(&frame[14 + 40..]).unwrap();
+        assert!(udp.verify_checksum(src_addr, dest_addr));
+        assert_eq!(udp.length(), 8 + 3);
+
+        assert!(Stack::new(UdpLayer::new(5353, 53)).build().is_err());
+    }
+}
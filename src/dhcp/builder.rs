@@ -0,0 +1,122 @@
+use alloc::vec;
+use alloc::vec::Vec;
+use core::net::Ipv4Addr;
+
+use crate::dhcp::packet::{consts, MessageType, Packet};
+
+pub struct PacketBuilder {
+    op: u8,
+    xid: u32,
+    flags: u16,
+    ciaddr: Ipv4Addr,
+    yiaddr: Ipv4Addr,
+    siaddr: Ipv4Addr,
+    giaddr: Ipv4Addr,
+    chaddr: Vec<u8>,
+    options: Vec<(u8, Vec<u8>)>,
+}
+
+impl PacketBuilder {
+    pub fn op(mut self, op: u8) -> Self {
+        self.op = op;
+        self
+    }
+
+    pub fn xid(mut self, xid: u32) -> Self {
+        self.xid = xid;
+        self
+    }
+
+    pub fn flags(mut self, flags: u16) -> Self {
+        self.flags = flags;
+        self
+    }
+
+    pub fn ciaddr(mut self, ciaddr: Ipv4Addr) -> Self {
+        self.ciaddr = ciaddr;
+        self
+    }
+
+    pub fn yiaddr(mut self, yiaddr: Ipv4Addr) -> Self {
+        self.yiaddr = yiaddr;
+        self
+    }
+
+    pub fn siaddr(mut self, siaddr: Ipv4Addr) -> Self {
+        self.siaddr = siaddr;
+        self
+    }
+
+    pub fn giaddr(mut self, giaddr: Ipv4Addr) -> Self {
+        self.giaddr = giaddr;
+        self
+    }
+
+    pub fn chaddr(mut self, chaddr: &[u8]) -> Self {
+        self.chaddr = chaddr.to_vec();
+        self
+    }
+
+    pub fn message_type(self, message_type: MessageType) -> Self {
+        self.option(consts::OPTION_MESSAGE_TYPE, vec![message_type.into()])
+    }
+
+    /// Append an option, values longer than 255 octets are truncated.
+    pub fn option(mut self, code: u8, mut value: Vec<u8>) -> Self {
+        value.truncate(u8::MAX as usize);
+        self.options.push((code, value));
+        self
+    }
+
+    pub fn addr_option(self, code: u8, addr: Ipv4Addr) -> Self {
+        self.option(code, addr.octets().to_vec())
+    }
+
+    /// Build the message, padded to the smallest length relays accept.
+    pub fn build_vec(self) -> Vec<u8> {
+        let mut buffer: Vec<u8> = vec![0; consts::OPTIONS_OFFSET];
+        for (code, value) in self.options.iter() {
+            buffer.push(*code);
+            buffer.push(value.len() as u8);
+            buffer.extend_from_slice(value);
+        }
+        buffer.push(consts::OPTION_END);
+        if buffer.len() < consts::MIN_LEN {
+            buffer.resize(consts::MIN_LEN, consts::OPTION_PAD);
+        }
+
+        let mut packet = Packet::new_unchecked(buffer.as_mut_slice());
+        packet.set_op(self.op);
+        packet.set_htype(consts::HTYPE_ETHERNET);
+        packet.set_xid(self.xid);
+        packet.set_flags(self.flags);
+        packet.set_ciaddr(self.ciaddr);
+        packet.set_yiaddr(self.yiaddr);
+        packet.set_siaddr(self.siaddr);
+        packet.set_giaddr(self.giaddr);
+        packet.set_chaddr(&self.chaddr);
+        packet.set_magic_cookie();
+
+        buffer
+    }
+
+    pub fn build(self) -> Packet<Vec<u8>> {
+        Packet::new_unchecked(self.build_vec())
+    }
+}
+
+impl Default for PacketBuilder {
+    fn default() -> Self {
+        Self {
+            op: consts::BOOT_REQUEST,
+            xid: 0,
+            flags: 0,
+            ciaddr: Ipv4Addr::UNSPECIFIED,
+            yiaddr: Ipv4Addr::UNSPECIFIED,
+            siaddr: Ipv4Addr::UNSPECIFIED,
+            giaddr: Ipv4Addr::UNSPECIFIED,
+            chaddr: vec![],
+            options: vec![],
+        }
+    }
+}
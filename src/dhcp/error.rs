@@ -0,0 +1,22 @@
+use core::fmt::{Display, Formatter};
+
+#[derive(Debug)]
+pub enum Error {
+    InvalidLength,
+    InvalidMagicCookie,
+    InvalidOption,
+    InvalidPool,
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Error::InvalidLength => write!(f, "invalid length"),
+            Error::InvalidMagicCookie => write!(f, "invalid magic cookie"),
+            Error::InvalidOption => write!(f, "invalid option"),
+            Error::InvalidPool => write!(f, "invalid address pool"),
+        }
+    }
+}
+
+impl core::error::Error for Error {}
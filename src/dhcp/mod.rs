@@ -0,0 +1,5 @@
+pub mod builder;
+pub mod error;
+pub mod packet;
+#[cfg(feature = "std")]
+pub mod server;
@@ -0,0 +1,352 @@
+use core::fmt::{Debug, Formatter};
+use core::net::Ipv4Addr;
+
+use crate::c_like_enum;
+use crate::dhcp::error::Error;
+use crate::error::Result;
+
+pub mod consts {
+    pub const SERVER_PORT: u16 = 67;
+    pub const CLIENT_PORT: u16 = 68;
+    pub const MAGIC_COOKIE: [u8; 4] = [99, 130, 83, 99]; // RFC 2131 section 3
+    pub const OPTIONS_OFFSET: usize = 240; // Fixed fields of RFC 2131 section 2 and the magic cookie
+    pub const MIN_LEN: usize = 300; // RFC 1542 section 2.1, the smallest BOOTP message relays accept
+    pub const BOOT_REQUEST: u8 = 1;
+    pub const BOOT_REPLY: u8 = 2;
+    pub const HTYPE_ETHERNET: u8 = 1;
+    pub const FLAG_BROADCAST: u16 = 0x8000;
+
+    // Option codes of RFC 2132
+    pub const OPTION_PAD: u8 = 0;
+    pub const OPTION_SUBNET_MASK: u8 = 1;
+    pub const OPTION_ROUTER: u8 = 3;
+    pub const OPTION_DNS_SERVERS: u8 = 6;
+    pub const OPTION_REQUESTED_ADDR: u8 = 50;
+    pub const OPTION_LEASE_TIME: u8 = 51;
+    pub const OPTION_MESSAGE_TYPE: u8 = 53;
+    pub const OPTION_SERVER_ID: u8 = 54;
+    pub const OPTION_RENEWAL_TIME: u8 = 58;
+    pub const OPTION_REBINDING_TIME: u8 = 59;
+    pub const OPTION_CLIENT_ID: u8 = 61;
+    pub const OPTION_END: u8 = 255;
+}
+
+c_like_enum!(
+    /// DHCP message types (RFC 2132 section 9.6)
+    #[derive(Debug, Copy, Clone, PartialEq, Eq)]
+    pub enum MessageType(u8) {
+        Discover = 1,
+        Offer = 2,
+        Request = 3,
+        Decline = 4,
+        Ack = 5,
+        Nak = 6,
+        Release = 7,
+        Inform = 8,
+    }
+);
+
+/// A DHCP message (RFC 2131 section 2).
+pub struct Packet<Buf> {
+    buffer: Buf,
+}
+
+impl<Buf> Packet<Buf>
+where
+    Buf: AsRef<[u8]>,
+{
+    pub fn new_unchecked(buffer: Buf) -> Self {
+        Packet { buffer }
+    }
+
+    pub fn new_checked(buffer: Buf) -> Result<Self> {
+        let packet = Self::new_unchecked(buffer);
+        if packet.buffer.as_ref().len() < consts::OPTIONS_OFFSET {
+            return Err(Error::InvalidLength.into());
+        }
+        if packet.buffer.as_ref()[236..240] != consts::MAGIC_COOKIE {
+            return Err(Error::InvalidMagicCookie.into());
+        }
+        for option in packet.options() {
+            option?;
+        }
+        Ok(packet)
+    }
+
+    pub fn op(&self) -> u8 {
+        self.buffer.as_ref()[0]
+    }
+
+    pub fn htype(&self) -> u8 {
+        self.buffer.as_ref()[1]
+    }
+
+    pub fn hlen(&self) -> u8 {
+        self.buffer.as_ref()[2]
+    }
+
+    /// The transaction ID, chosen by the client and copied in the replies.
+    pub fn xid(&self) -> u32 {
+        let buffer = self.buffer.as_ref();
+        u32::from_be_bytes([buffer[4], buffer[5], buffer[6], buffer[7]])
+    }
+
+    pub fn flags(&self) -> u16 {
+        u16::from_be_bytes([self.buffer.as_ref()[10], self.buffer.as_ref()[11]])
+    }
+
+    /// The address of a client that already has one.
+    pub fn ciaddr(&self) -> Ipv4Addr {
+        self.addr(12)
+    }
+
+    /// The address given to the client.
+    pub fn yiaddr(&self) -> Ipv4Addr {
+        self.addr(16)
+    }
+
+    pub fn siaddr(&self) -> Ipv4Addr {
+        self.addr(20)
+    }
+
+    /// The address of the relay agent the message went through.
+    pub fn giaddr(&self) -> Ipv4Addr {
+        self.addr(24)
+    }
+
+    /// Returns the hardware address of the client.
+    pub fn chaddr(&self) -> &[u8] {
+        &self.buffer.as_ref()[28..28 + (self.hlen() as usize).min(16)]
+    }
+
+    fn addr(&self, offset: usize) -> Ipv4Addr {
+        let buffer = self.buffer.as_ref();
+        Ipv4Addr::new(
+            buffer[offset],
+            buffer[offset + 1],
+            buffer[offset + 2],
+            buffer[offset + 3],
+        )
+    }
+
+    /// Returns the options as (code, value) pairs, stopping at the end option.
+    pub fn options(&self) -> Options<'_> {
+        Options {
+            buffer: &self.buffer.as_ref()[consts::OPTIONS_OFFSET..],
+        }
+    }
+
+    /// Returns the value of the first option with `code`.
+    pub fn option(&self, code: u8) -> Option<&[u8]> {
+        self.options()
+            .filter_map(|option| option.ok())
+            .find(|(found, _)| *found == code)
+            .map(|(_, value)| value)
+    }
+
+    pub fn message_type(&self) -> Option<MessageType> {
+        match self.option(consts::OPTION_MESSAGE_TYPE)? {
+            [message_type] => Some((*message_type).into()),
+            _ => None,
+        }
+    }
+
+    pub fn requested_addr(&self) -> Option<Ipv4Addr> {
+        option_addr(self.option(consts::OPTION_REQUESTED_ADDR)?)
+    }
+
+    pub fn server_id(&self) -> Option<Ipv4Addr> {
+        option_addr(self.option(consts::OPTION_SERVER_ID)?)
+    }
+
+    /// Returns what identifies the client: its client identifier option, or its hardware address
+    /// (RFC 2131 section 4.2).
+    pub fn client_id(&self) -> &[u8] {
+        match self.option(consts::OPTION_CLIENT_ID) {
+            Some(client_id) if !client_id.is_empty() => client_id,
+            _ => self.chaddr(),
+        }
+    }
+}
+
+fn option_addr(value: &[u8]) -> Option<Ipv4Addr> {
+    match value {
+        [a, b, c, d] => Some(Ipv4Addr::new(*a, *b, *c, *d)),
+        _ => None,
+    }
+}
+
+impl<Buf> Packet<Buf>
+where
+    Buf: AsRef<[u8]> + AsMut<[u8]>,
+{
+    pub fn set_op(&mut self, op: u8) {
+        self.buffer.as_mut()[0] = op;
+    }
+
+    pub fn set_htype(&mut self, htype: u8) {
+        self.buffer.as_mut()[1] = htype;
+    }
+
+    pub fn set_hlen(&mut self, hlen: u8) {
+        self.buffer.as_mut()[2] = hlen;
+    }
+
+    pub fn set_xid(&mut self, xid: u32) {
+        self.buffer.as_mut()[4..=7].copy_from_slice(xid.to_be_bytes().as_ref());
+    }
+
+    pub fn set_flags(&mut self, flags: u16) {
+        self.buffer.as_mut()[10..=11].copy_from_slice(flags.to_be_bytes().as_ref());
+    }
+
+    pub fn set_ciaddr(&mut self, ciaddr: Ipv4Addr) {
+        self.buffer.as_mut()[12..=15].copy_from_slice(ciaddr.octets().as_ref());
+    }
+
+    pub fn set_yiaddr(&mut self, yiaddr: Ipv4Addr) {
+        self.buffer.as_mut()[16..=19].copy_from_slice(yiaddr.octets().as_ref());
+    }
+
+    pub fn set_siaddr(&mut self, siaddr: Ipv4Addr) {
+        self.buffer.as_mut()[20..=23].copy_from_slice(siaddr.octets().as_ref());
+    }
+
+    pub fn set_giaddr(&mut self, giaddr: Ipv4Addr) {
+        self.buffer.as_mut()[24..=27].copy_from_slice(giaddr.octets().as_ref());
+    }
+
+    /// Set the hardware address of the client, at most 16 octets.
+    pub fn set_chaddr(&mut self, chaddr: &[u8]) {
+        let len = chaddr.len().min(16);
+        self.buffer.as_mut()[28..44].fill(0);
+        self.buffer.as_mut()[28..28 + len].copy_from_slice(&chaddr[..len]);
+        self.set_hlen(len as u8);
+    }
+
+    pub fn set_magic_cookie(&mut self) {
+        self.buffer.as_mut()[236..240].copy_from_slice(consts::MAGIC_COOKIE.as_ref());
+    }
+}
+
+/// An iterator over the options of a message.
+pub struct Options<'a> {
+    buffer: &'a [u8],
+}
+
+impl<'a> Iterator for Options<'a> {
+    type Item = Result<(u8, &'a [u8])>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let (code, rest) = self.buffer.split_first()?;
+            match *code {
+                consts::OPTION_PAD => self.buffer = rest,
+                consts::OPTION_END => {
+                    self.buffer = &[];
+                    return None;
+                }
+                code => {
+                    let len = match rest.first() {
+                        Some(len) if rest.len() > *len as usize => *len as usize,
+                        _ => {
+                            self.buffer = &[];
+                            return Some(Err(Error::InvalidOption.into()));
+                        }
+                    };
+                    let value = &rest[1..1 + len];
+                    self.buffer = &rest[1 + len..];
+                    return Some(Ok((code, value)));
+                }
+            }
+        }
+    }
+}
+
+impl<Buf> Debug for Packet<Buf>
+where
+    Buf: AsRef<[u8]>,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "op: {:?}, xid: {:#x}, flags: {:#x}, ciaddr: {:?}, yiaddr: {:?}, giaddr: {:?}, \
+            chaddr: {:02x?}, message type: {:?}",
+            self.op(),
+            self.xid(),
+            self.flags(),
+            self.ciaddr(),
+            self.yiaddr(),
+            self.giaddr(),
+            self.chaddr(),
+            self.message_type(),
+        )
+    }
+}
+
+impl<Buf> AsRef<[u8]> for Packet<Buf>
+where
+    Buf: AsRef<[u8]>,
+{
+    fn as_ref(&self) -> &[u8] {
+        self.buffer.as_ref()
+    }
+}
+
+impl<Buf> AsMut<[u8]> for Packet<Buf>
+where
+    Buf: AsMut<[u8]>,
+{
+    fn as_mut(&mut self) -> &mut [u8] {
+        self.buffer.as_mut()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use super::{consts, MessageType, Packet};
+
+    #[test]
+    fn options() {
+        let mut bytes = vec![0; consts::OPTIONS_OFFSET];
+        bytes[0] = consts::BOOT_REQUEST;
+        bytes[4..8].copy_from_slice(&[0xde, 0xad, 0xbe, 0xef]);
+        bytes[236..240].copy_from_slice(&consts::MAGIC_COOKIE);
+        bytes.extend_from_slice(&[
+            consts::OPTION_MESSAGE_TYPE,
+            1,
+            3,                  //
+            consts::OPTION_PAD, //
+            consts::OPTION_REQUESTED_ADDR,
+            4,
+            10,
+            0,
+            0,
+            7,                  //
+            consts::OPTION_END, //
+            consts::OPTION_SERVER_ID,
+            4,
+            10,
+            0,
+            0,
+            1,
+        ]);
+
+        let mut packet = Packet::new_checked(bytes.as_mut_slice()).unwrap();
+        packet.set_chaddr(&[2, 0, 0, 0, 0, 1]);
+        assert_eq!(packet.xid(), 0xdeadbeef);
+        assert_eq!(packet.message_type(), Some(MessageType::Request));
+        assert_eq!(packet.requested_addr(), Some(Ipv4Addr::new(10, 0, 0, 7)));
+        // Options after the end option are ignored.
+        assert_eq!(packet.server_id(), None);
+        assert_eq!(packet.client_id(), &[2, 0, 0, 0, 0, 1]);
+        assert_eq!(packet.options().count(), 2);
+
+        let truncated = &bytes[..bytes.len() - 10];
+        assert!(Packet::new_checked(truncated).is_err());
+        bytes[236] = 0;
+        assert!(Packet::new_checked(bytes.as_slice()).is_err());
+    }
+}
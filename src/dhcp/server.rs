@@ -0,0 +1,461 @@
+use std::collections::HashMap;
+use std::net::{Ipv4Addr, SocketAddrV4};
+use std::time::{Duration, Instant};
+
+use crate::dhcp::builder::PacketBuilder;
+use crate::dhcp::error::Error;
+use crate::dhcp::packet::{consts as packet_consts, MessageType, Packet};
+use crate::error::Result;
+use crate::macros::diagnostics::debug;
+use crate::net_device::device::consts::DEFAULT_MTU;
+use crate::net_device::Device;
+use crate::udp::udp_socket::UdpSocket;
+
+pub mod consts {
+    use std::time::Duration;
+
+    pub const DEFAULT_LEASE_TIME: Duration = Duration::from_secs(60 * 60);
+    pub const OFFER_TIMEOUT: Duration = Duration::from_secs(60); // How long an offered address is held for the client
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum LeaseState {
+    /// Offered to the client, which has not requested it yet.
+    Offered,
+    Bound,
+    /// Declined by a client that found it in use, the address is not offered until the lease expires.
+    Declined,
+}
+
+/// An address given to a client.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Lease {
+    pub client_id: Vec<u8>,
+    pub addr: Ipv4Addr,
+    pub state: LeaseState,
+    pub expires_at: Instant,
+}
+
+/// A reply to send to a client.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Reply {
+    pub dest: SocketAddrV4,
+    pub payload: Vec<u8>,
+}
+
+/// A DHCP server (RFC 2131) giving the addresses of a pool to the clients of the network of the interface.
+pub struct Server {
+    server_addr: Ipv4Addr,
+    netmask: Ipv4Addr,
+    router: Option<Ipv4Addr>,
+    dns_servers: Vec<Ipv4Addr>,
+    pool_first: u32,
+    pool_last: u32,
+    lease_time: Duration,
+    /// Addresses reserved by client identifier.
+    reservations: HashMap<Vec<u8>, Ipv4Addr>,
+    leases: HashMap<Ipv4Addr, Lease>,
+}
+
+impl Server {
+    /// Serve the addresses from `pool_first` to `pool_last`, `server_addr` being the address of the interface.
+    pub fn new(server_addr: Ipv4Addr, netmask: Ipv4Addr, pool_first: Ipv4Addr, pool_last: Ipv4Addr) -> Result<Self> {
+        if u32::from(pool_first) > u32::from(pool_last) {
+            return Err(Error::InvalidPool.into());
+        }
+
+        Ok(Self {
+            server_addr,
+            netmask,
+            router: None,
+            dns_servers: vec![],
+            pool_first: pool_first.into(),
+            pool_last: pool_last.into(),
+            lease_time: consts::DEFAULT_LEASE_TIME,
+            reservations: HashMap::new(),
+            leases: HashMap::new(),
+        })
+    }
+
+    pub fn set_router(&mut self, router: Option<Ipv4Addr>) {
+        self.router = router;
+    }
+
+    pub fn set_dns_servers(&mut self, dns_servers: Vec<Ipv4Addr>) {
+        self.dns_servers = dns_servers;
+    }
+
+    pub fn set_lease_time(&mut self, lease_time: Duration) {
+        self.lease_time = lease_time;
+    }
+
+    /// Always give `addr` to the client identified by `client_id`, its client identifier option
+    /// or else its hardware address. The address does not have to be in the pool.
+    pub fn reserve(&mut self, client_id: &[u8], addr: Ipv4Addr) {
+        self.reservations.insert(client_id.to_vec(), addr);
+    }
+
+    pub fn unreserve(&mut self, client_id: &[u8]) -> Option<Ipv4Addr> {
+        self.reservations.remove(client_id)
+    }
+
+    pub fn leases(&self) -> impl Iterator<Item = &Lease> {
+        self.leases.values()
+    }
+
+    pub fn lease(&self, addr: Ipv4Addr) -> Option<&Lease> {
+        self.leases.get(&addr)
+    }
+
+    /// Drop the leases that expired at `now`.
+    pub fn expire(&mut self, now: Instant) {
+        self.leases.retain(|_, lease| lease.expires_at > now);
+    }
+
+    fn in_pool(&self, addr: Ipv4Addr) -> bool {
+        (self.pool_first..=self.pool_last).contains(&u32::from(addr))
+    }
+
+    /// Whether `addr` can be given to the client `client_id`.
+    fn available(&self, addr: Ipv4Addr, client_id: &[u8]) -> bool {
+        if addr.is_unspecified() || addr == self.server_addr {
+            return false;
+        }
+
+        let reserved = self.reservations.iter().find(|(_, reserved)| **reserved == addr);
+        let allowed = match reserved {
+            Some((owner, _)) => owner.as_slice() == client_id,
+            None => self.in_pool(addr),
+        };
+        allowed && self.leases.get(&addr).is_none_or(|lease| lease.client_id == client_id)
+    }
+
+    /// Pick the address of the client: the one it already has, its reservation,
+    /// the one it asked for, or the first free one of the pool.
+    fn choose(&self, client_id: &[u8], requested: Option<Ipv4Addr>) -> Option<Ipv4Addr> {
+        let leased = self
+            .leases
+            .values()
+            .find(|lease| lease.client_id == client_id && lease.state != LeaseState::Declined)
+            .map(|lease| lease.addr);
+        let reserved = self.reservations.get(client_id).copied();
+
+        leased
+            .into_iter()
+            .chain(reserved)
+            .chain(requested)
+            .find(|addr| self.available(*addr, client_id))
+            .or_else(|| {
+                (self.pool_first..=self.pool_last)
+                    .map(Ipv4Addr::from)
+                    .find(|addr| self.available(*addr, client_id))
+            })
+    }
+
+    fn set_lease(&mut self, client_id: &[u8], addr: Ipv4Addr, state: LeaseState, expires_at: Instant) {
+        // A client holds a single address.
+        self.leases
+            .retain(|leased, lease| *leased == addr || lease.client_id != client_id);
+        self.leases.insert(
+            addr,
+            Lease {
+                client_id: client_id.to_vec(),
+                addr,
+                state,
+                expires_at,
+            },
+        );
+    }
+
+    /// Handle a message received on the server port, returns the reply to send if any.
+    pub fn process(&mut self, buffer: &[u8], now: Instant) -> Result<Option<Reply>> {
+        let request = Packet::new_checked(buffer)?;
+        if request.op() != packet_consts::BOOT_REQUEST {
+            return Ok(None);
+        }
+        self.expire(now);
+
+        let client_id = request.client_id().to_vec();
+        let reply = match request.message_type() {
+            Some(MessageType::Discover) => {
+                let addr = match self.choose(&client_id, request.requested_addr()) {
+                    Some(addr) => addr,
+                    None => {
+                        debug!("dhcp pool exhausted, discover from {:02x?} ignored", client_id);
+                        return Ok(None);
+                    }
+                };
+                if self
+                    .leases
+                    .get(&addr)
+                    .is_none_or(|lease| lease.state != LeaseState::Bound)
+                {
+                    self.set_lease(&client_id, addr, LeaseState::Offered, now + consts::OFFER_TIMEOUT);
+                }
+                self.reply(&request, MessageType::Offer, addr)
+            }
+            Some(MessageType::Request) => {
+                if let Some(server_id) = request.server_id() {
+                    if server_id != self.server_addr {
+                        // The client took the offer of another server.
+                        self.leases
+                            .retain(|_, lease| lease.client_id != client_id || lease.state != LeaseState::Offered);
+                        return Ok(None);
+                    }
+                }
+
+                let requested = request
+                    .requested_addr()
+                    .or_else(|| Some(request.ciaddr()).filter(|ciaddr| !ciaddr.is_unspecified()));
+                match requested {
+                    Some(addr) if self.available(addr, &client_id) => {
+                        self.set_lease(&client_id, addr, LeaseState::Bound, now + self.lease_time);
+                        self.reply(&request, MessageType::Ack, addr)
+                    }
+                    _ => self.reply(&request, MessageType::Nak, Ipv4Addr::UNSPECIFIED),
+                }
+            }
+            Some(MessageType::Decline) => {
+                if let Some(addr) = request.requested_addr() {
+                    if self.leases.get(&addr).is_some_and(|lease| lease.client_id == client_id) {
+                        self.set_lease(&[], addr, LeaseState::Declined, now + self.lease_time);
+                    }
+                }
+                return Ok(None);
+            }
+            Some(MessageType::Release) => {
+                let addr = request.ciaddr();
+                if self.leases.get(&addr).is_some_and(|lease| lease.client_id == client_id) {
+                    self.leases.remove(&addr);
+                }
+                return Ok(None);
+            }
+            Some(MessageType::Inform) => self.reply(&request, MessageType::Inform, Ipv4Addr::UNSPECIFIED),
+            _ => return Ok(None),
+        };
+
+        Ok(Some(reply))
+    }
+
+    /// Build the reply of `message_type` giving `addr`, an `Inform` message type builds the answer to an inform.
+    fn reply(&self, request: &Packet<&[u8]>, message_type: MessageType, addr: Ipv4Addr) -> Reply {
+        let inform = message_type == MessageType::Inform;
+        let message_type = if inform { MessageType::Ack } else { message_type };
+
+        let mut builder = PacketBuilder::default()
+            .op(packet_consts::BOOT_REPLY)
+            .xid(request.xid())
+            .flags(request.flags())
+            .giaddr(request.giaddr())
+            .chaddr(request.chaddr())
+            .message_type(message_type)
+            .addr_option(packet_consts::OPTION_SERVER_ID, self.server_addr);
+        if inform {
+            builder = builder.ciaddr(request.ciaddr());
+        }
+
+        if message_type != MessageType::Nak {
+            builder = builder
+                .yiaddr(addr)
+                .addr_option(packet_consts::OPTION_SUBNET_MASK, self.netmask);
+            if !inform {
+                // Renew at half the lease and rebind at seven eighths (RFC 2131 section 4.4.5).
+                let lease_time = self.lease_time.as_secs().min(u32::MAX as u64) as u32;
+                builder = builder
+                    .option(packet_consts::OPTION_LEASE_TIME, lease_time.to_be_bytes().to_vec())
+                    .option(
+                        packet_consts::OPTION_RENEWAL_TIME,
+                        (lease_time / 2).to_be_bytes().to_vec(),
+                    )
+                    .option(
+                        packet_consts::OPTION_REBINDING_TIME,
+                        (lease_time / 8 * 7).to_be_bytes().to_vec(),
+                    );
+            }
+            if let Some(router) = self.router {
+                builder = builder.addr_option(packet_consts::OPTION_ROUTER, router);
+            }
+            if !self.dns_servers.is_empty() {
+                let dns_servers = self.dns_servers.iter().flat_map(|addr| addr.octets()).collect();
+                builder = builder.option(packet_consts::OPTION_DNS_SERVERS, dns_servers);
+            }
+        }
+
+        // Where replies go (RFC 2131 section 4.1), clients without an address are reached by broadcast.
+        let dest = if !request.giaddr().is_unspecified() {
+            SocketAddrV4::new(request.giaddr(), packet_consts::SERVER_PORT)
+        } else if message_type != MessageType::Nak && !request.ciaddr().is_unspecified() {
+            SocketAddrV4::new(request.ciaddr(), packet_consts::CLIENT_PORT)
+        } else {
+            SocketAddrV4::new(Ipv4Addr::BROADCAST, packet_consts::CLIENT_PORT)
+        };
+
+        Reply {
+            dest,
+            payload: builder.build_vec(),
+        }
+    }
+
+    /// Receive a message on `socket`, bound to the server port with broadcast enabled, and answer it.
+    pub fn serve<D: Device>(&mut self, socket: &UdpSocket<D>) -> Result<()> {
+        let mut buf = [0; DEFAULT_MTU];
+        let (len, remote) = socket.recv_from(&mut buf)?;
+
+        match self.process(&buf[..len], Instant::now()) {
+            Ok(Some(reply)) => {
+                socket.send_to(&reply.payload, reply.dest)?;
+            }
+            Ok(None) => {}
+            Err(e) => debug!("dhcp message from {} dropped: {}", remote, e),
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{Ipv4Addr, SocketAddrV4};
+    use std::time::{Duration, Instant};
+
+    use super::{LeaseState, Server};
+    use crate::dhcp::builder::PacketBuilder;
+    use crate::dhcp::packet::{consts, MessageType, Packet};
+
+    const SERVER_ADDR: Ipv4Addr = Ipv4Addr::new(10, 0, 0, 1);
+    const CLIENT: [u8; 6] = [2, 0, 0, 0, 0, 1];
+    const OTHER_CLIENT: [u8; 6] = [2, 0, 0, 0, 0, 2];
+
+    fn request(chaddr: &[u8], message_type: MessageType) -> PacketBuilder {
+        PacketBuilder::default()
+            .xid(42)
+            .chaddr(chaddr)
+            .message_type(message_type)
+    }
+
+    #[test]
+    fn lease() {
+        let mut server = Server::new(
+            SERVER_ADDR,
+            Ipv4Addr::new(255, 255, 255, 0),
+            Ipv4Addr::new(10, 0, 0, 1),
+            Ipv4Addr::new(10, 0, 0, 3),
+        )
+        .unwrap();
+        server.set_dns_servers(vec![SERVER_ADDR]);
+        let now = Instant::now();
+
+        // The server address is skipped, the offer is broadcast.
+        let offer = server
+            .process(&request(&CLIENT, MessageType::Discover).build_vec(), now)
+            .unwrap()
+            .unwrap();
+        assert_eq!(offer.dest, SocketAddrV4::new(Ipv4Addr::BROADCAST, consts::CLIENT_PORT));
+        let packet = Packet::new_checked(offer.payload.as_slice()).unwrap();
+        assert_eq!(packet.message_type(), Some(MessageType::Offer));
+        assert_eq!(packet.xid(), 42);
+        assert_eq!(packet.yiaddr(), Ipv4Addr::new(10, 0, 0, 2));
+        assert_eq!(packet.server_id(), Some(SERVER_ADDR));
+        assert_eq!(packet.option(consts::OPTION_DNS_SERVERS), Some(&[10, 0, 0, 1][..]));
+
+        // The offered address is held for the client.
+        let offer = server
+            .process(&request(&OTHER_CLIENT, MessageType::Discover).build_vec(), now)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            Packet::new_checked(offer.payload.as_slice()).unwrap().yiaddr(),
+            Ipv4Addr::new(10, 0, 0, 3)
+        );
+        let selected = request(&CLIENT, MessageType::Request)
+            .addr_option(consts::OPTION_SERVER_ID, SERVER_ADDR)
+            .addr_option(consts::OPTION_REQUESTED_ADDR, Ipv4Addr::new(10, 0, 0, 2))
+            .build_vec();
+        let ack = server.process(&selected, now).unwrap().unwrap();
+        assert_eq!(
+            Packet::new_checked(ack.payload.as_slice()).unwrap().message_type(),
+            Some(MessageType::Ack)
+        );
+        assert_eq!(
+            server.lease(Ipv4Addr::new(10, 0, 0, 2)).unwrap().state,
+            LeaseState::Bound
+        );
+
+        // The pool is exhausted, another client asking for a taken address is refused.
+        assert!(server
+            .process(&request(&[2, 0, 0, 0, 0, 3], MessageType::Discover).build_vec(), now)
+            .unwrap()
+            .is_none());
+        let taken = request(&OTHER_CLIENT, MessageType::Request)
+            .addr_option(consts::OPTION_REQUESTED_ADDR, Ipv4Addr::new(10, 0, 0, 2))
+            .build_vec();
+        let nak = server.process(&taken, now).unwrap().unwrap();
+        assert_eq!(
+            Packet::new_checked(nak.payload.as_slice()).unwrap().message_type(),
+            Some(MessageType::Nak)
+        );
+
+        // Choosing another server frees the offer, a released lease is freed at once.
+        let elsewhere = request(&OTHER_CLIENT, MessageType::Request)
+            .addr_option(consts::OPTION_SERVER_ID, Ipv4Addr::new(10, 0, 0, 254))
+            .build_vec();
+        assert!(server.process(&elsewhere, now).unwrap().is_none());
+        assert!(server.lease(Ipv4Addr::new(10, 0, 0, 3)).is_none());
+        let release = request(&CLIENT, MessageType::Release)
+            .ciaddr(Ipv4Addr::new(10, 0, 0, 2))
+            .build_vec();
+        assert!(server.process(&release, now).unwrap().is_none());
+        assert_eq!(server.leases().count(), 0);
+
+        // A renewal is unicast, and the lease expires when it is not renewed.
+        let renew = request(&CLIENT, MessageType::Request)
+            .ciaddr(Ipv4Addr::new(10, 0, 0, 2))
+            .build_vec();
+        let ack = server.process(&renew, now).unwrap().unwrap();
+        assert_eq!(
+            ack.dest,
+            SocketAddrV4::new(Ipv4Addr::new(10, 0, 0, 2), consts::CLIENT_PORT)
+        );
+        server.expire(now + Duration::from_secs(3600));
+        assert_eq!(server.leases().count(), 0);
+    }
+
+    #[test]
+    fn reservation() {
+        let mut server = Server::new(
+            SERVER_ADDR,
+            Ipv4Addr::new(255, 255, 255, 0),
+            Ipv4Addr::new(10, 0, 0, 100),
+            Ipv4Addr::new(10, 0, 0, 100),
+        )
+        .unwrap();
+        server.reserve(&CLIENT, Ipv4Addr::new(10, 0, 0, 50));
+        let now = Instant::now();
+
+        let offer = server
+            .process(&request(&CLIENT, MessageType::Discover).build_vec(), now)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            Packet::new_checked(offer.payload.as_slice()).unwrap().yiaddr(),
+            Ipv4Addr::new(10, 0, 0, 50)
+        );
+
+        // The reserved address is not given to other clients.
+        let reserved = request(&OTHER_CLIENT, MessageType::Request)
+            .addr_option(consts::OPTION_REQUESTED_ADDR, Ipv4Addr::new(10, 0, 0, 50))
+            .build_vec();
+        let nak = server.process(&reserved, now).unwrap().unwrap();
+        assert_eq!(
+            Packet::new_checked(nak.payload.as_slice()).unwrap().message_type(),
+            Some(MessageType::Nak)
+        );
+
+        assert!(Server::new(
+            SERVER_ADDR,
+            Ipv4Addr::new(255, 255, 255, 0),
+            Ipv4Addr::new(10, 0, 0, 9),
+            Ipv4Addr::new(10, 0, 0, 8)
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,17 @@
+use core::fmt::{Display, Formatter};
+
+#[derive(Debug)]
+pub enum Error {
+    /// A header a registered dissector could not parse.
+    InvalidHeader,
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Error::InvalidHeader => write!(f, "invalid header"),
+        }
+    }
+}
+
+impl core::error::Error for Error {}
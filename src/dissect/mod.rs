@@ -0,0 +1,598 @@
+//! Dissect raw frames into the layers radish knows, from Ethernet down to the payload,
+//! to print them the way tcpdump does or to look into them in tests.
+
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::{Display, Formatter};
+use core::net::{Ipv4Addr, Ipv6Addr};
+
+use crate::arp::packet::{Operation, Packet as ArpPacket};
+use crate::dissect::error::Error as DissectError;
+use crate::error::{Error, Result};
+use crate::ethernet::frame::{EtherType, Frame, MacAddr};
+use crate::icmpv4::error::Error as Icmpv4Error;
+use crate::icmpv4::packet::{MessageType as Icmpv4MessageType, Packet as Icmpv4Packet};
+use crate::icmpv6::packet::{MessageType as Icmpv6MessageType, Packet as Icmpv6Packet};
+use crate::ipv4::error::Error as Ipv4Error;
+use crate::ipv4::packet::{consts as ipv4_consts, Packet as Ipv4Packet, Protocol};
+use crate::ipv6::packet::{consts as ipv6_consts, Packet as Ipv6Packet};
+use crate::tcp::packet::{Packet as TcpPacket, TcpFlags};
+use crate::udp::packet::Packet as UdpPacket;
+
+pub mod error;
+
+/// A layer of a dissected frame, with the fields worth printing.
+#[derive(Debug)]
+pub enum Layer<'a> {
+    Ethernet {
+        src_addr: MacAddr,
+        dest_addr: MacAddr,
+        /// The VLAN identifiers of the tags, outermost first.
+        vlan_ids: Vec<u16>,
+        ether_type: EtherType,
+    },
+    Arp {
+        operation: Operation,
+        sender_addr: Ipv4Addr,
+        target_addr: Ipv4Addr,
+    },
+    Ipv4 {
+        src_addr: Ipv4Addr,
+        dest_addr: Ipv4Addr,
+        protocol: Protocol,
+        ttl: u8,
+        identification: u16,
+        /// The offset of a fragment in octets.
+        offset: u16,
+        more_fragments: bool,
+        len: usize,
+    },
+    Ipv6 {
+        src_addr: Ipv6Addr,
+        dest_addr: Ipv6Addr,
+        /// The upper-layer protocol, past the extension headers.
+        next_header: Protocol,
+        hop_limit: u8,
+        len: usize,
+    },
+    Tcp {
+        src_port: u16,
+        dest_port: u16,
+        flags: TcpFlags,
+        seq_number: u32,
+        ack_number: u32,
+        window: u16,
+    },
+    Udp {
+        src_port: u16,
+        dest_port: u16,
+        len: usize,
+    },
+    Icmpv4 {
+        r#type: Icmpv4MessageType,
+        code: u8,
+    },
+    Icmpv6 {
+        r#type: Icmpv6MessageType,
+        code: u8,
+    },
+    /// A header of a protocol parsed by a registered dissector.
+    Custom {
+        name: &'static str,
+        summary: String,
+    },
+    /// The octets no layer was dissected from, such as the data of a TCP segment.
+    Payload(&'a [u8]),
+    /// A layer that could not be parsed, the octets are left in the payload that follows.
+    Malformed {
+        name: &'static str,
+        error: Error,
+    },
+}
+
+impl Display for Layer<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Layer::Ethernet {
+                src_addr,
+                dest_addr,
+                vlan_ids,
+                ether_type,
+            } => {
+                write!(f, "Ethernet {} > {}", src_addr, dest_addr)?;
+                for vlan_id in vlan_ids {
+                    write!(f, ", vlan {}", vlan_id)?;
+                }
+                write!(f, ", {:?}", ether_type)
+            }
+            Layer::Arp {
+                operation,
+                sender_addr,
+                target_addr,
+            } => match operation {
+                Operation::Request => write!(f, "ARP who-has {} tell {}", target_addr, sender_addr),
+                Operation::Reply => write!(f, "ARP reply {} to {}", sender_addr, target_addr),
+                Operation::Unknown(operation) => {
+                    write!(f, "ARP operation {} {} > {}", operation, sender_addr, target_addr)
+                }
+            },
+            Layer::Ipv4 {
+                src_addr,
+                dest_addr,
+                protocol,
+                ttl,
+                identification,
+                offset,
+                more_fragments,
+                len,
+            } => {
+                write!(
+                    f,
+                    "IPv4 {} > {}, {:?}, ttl {}, id {}, len {}",
+                    src_addr, dest_addr, protocol, ttl, identification, len
+                )?;
+                if *offset != 0 || *more_fragments {
+                    write!(f, ", offset {}", offset)?;
+                }
+                if *more_fragments {
+                    write!(f, ", more fragments")?;
+                }
+                Ok(())
+            }
+            Layer::Ipv6 {
+                src_addr,
+                dest_addr,
+                next_header,
+                hop_limit,
+                len,
+            } => write!(
+                f,
+                "IPv6 {} > {}, {:?}, hop limit {}, len {}",
+                src_addr, dest_addr, next_header, hop_limit, len
+            ),
+            Layer::Tcp {
+                src_port,
+                dest_port,
+                flags,
+                seq_number,
+                ack_number,
+                window,
+            } => {
+                write!(f, "TCP {} > {}, flags [", src_port, dest_port)?;
+                let letters = [
+                    (TcpFlags::SYN, 'S'),
+                    (TcpFlags::FIN, 'F'),
+                    (TcpFlags::RST, 'R'),
+                    (TcpFlags::PSH, 'P'),
+                    (TcpFlags::URG, 'U'),
+                    (TcpFlags::ECE, 'E'),
+                    (TcpFlags::CWR, 'W'),
+                    (TcpFlags::ACK, '.'),
+                ];
+                for (flag, letter) in letters {
+                    if flags.contains(flag) {
+                        write!(f, "{}", letter)?;
+                    }
+                }
+                write!(f, "], seq {}", seq_number)?;
+                if flags.contains(TcpFlags::ACK) {
+                    write!(f, ", ack {}", ack_number)?;
+                }
+                write!(f, ", win {}", window)
+            }
+            Layer::Udp {
+                src_port,
+                dest_port,
+                len,
+            } => write!(f, "UDP {} > {}, len {}", src_port, dest_port, len),
+            Layer::Icmpv4 { r#type, code } => write!(f, "ICMP {:?}, code {}", r#type, code),
+            Layer::Icmpv6 { r#type, code } => write!(f, "ICMPv6 {:?}, code {}", r#type, code),
+            Layer::Custom { name, summary } => write!(f, "{} {}", name, summary),
+            Layer::Payload(payload) => write!(f, "Payload {} octets", payload.len()),
+            Layer::Malformed { name, error } => write!(f, "{} malformed: {}", name, error),
+        }
+    }
+}
+
+/// The layers of a frame, outermost first, displayed one per line.
+#[derive(Debug)]
+pub struct Dissection<'a> {
+    layers: Vec<Layer<'a>>,
+}
+
+impl<'a> Dissection<'a> {
+    pub fn layers(&self) -> &[Layer<'a>] {
+        &self.layers
+    }
+
+    /// Returns the payload of the innermost layer, empty if there is none.
+    pub fn payload(&self) -> &'a [u8] {
+        match self.layers.last() {
+            Some(Layer::Payload(payload)) => payload,
+            _ => &[],
+        }
+    }
+
+    /// Whether a layer could not be parsed.
+    pub fn is_malformed(&self) -> bool {
+        self.layers.iter().any(|layer| matches!(layer, Layer::Malformed { .. }))
+    }
+
+    fn push(&mut self, layer: Layer<'a>) {
+        self.layers.push(layer);
+    }
+
+    /// Push the layer parsed by `parse`, or else a malformed layer followed by the octets as payload.
+    fn push_with<F>(&mut self, name: &'static str, buffer: &'a [u8], parse: F)
+    where
+        F: FnOnce(&mut Self, &'a [u8]) -> Result<()>,
+    {
+        if let Err(error) = parse(self, buffer) {
+            self.push(Layer::Malformed { name, error });
+            self.push(Layer::Payload(buffer));
+        }
+    }
+}
+
+impl Display for Dissection<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        for (index, layer) in self.layers.iter().enumerate() {
+            if index > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{}", layer)?;
+        }
+        Ok(())
+    }
+}
+
+/// Dissects the header of a protocol radish does not know, such as a private or experimental one.
+pub trait Dissector {
+    /// The name of the protocol, printed before the summary.
+    fn name(&self) -> &'static str;
+
+    /// Parse the header at the start of `buffer`, returning a summary of its fields and its length,
+    /// the octets after it being the payload.
+    fn dissect(&self, buffer: &[u8]) -> Result<(String, usize)>;
+}
+
+/// The dissectors registered for IP protocol numbers and EtherTypes,
+/// taking precedence over the ones of radish.
+#[derive(Default)]
+pub struct Registry {
+    protocols: BTreeMap<u8, Box<dyn Dissector>>,
+    ether_types: BTreeMap<u16, Box<dyn Dissector>>,
+}
+
+impl Registry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Dissect the payloads of IPv4 datagrams and IPv6 packets of `protocol` with `dissector`.
+    pub fn register_protocol(&mut self, protocol: Protocol, dissector: impl Dissector + 'static) {
+        self.protocols.insert(protocol.into(), Box::new(dissector));
+    }
+
+    /// Dissect the payloads of Ethernet frames of `ether_type` with `dissector`.
+    pub fn register_ether_type(&mut self, ether_type: EtherType, dissector: impl Dissector + 'static) {
+        self.ether_types.insert(ether_type.into(), Box::new(dissector));
+    }
+
+    /// Dissect an Ethernet frame.
+    pub fn dissect<'a>(&self, buffer: &'a [u8]) -> Dissection<'a> {
+        let mut dissection = Dissection { layers: Vec::new() };
+        dissection.push_with("Ethernet", buffer, |dissection, buffer| {
+            dissect_ethernet(self, dissection, buffer)
+        });
+        dissection
+    }
+
+    /// Dissect an IP packet of either version, such as one read from a TUN device.
+    pub fn dissect_ip<'a>(&self, buffer: &'a [u8]) -> Dissection<'a> {
+        let mut dissection = Dissection { layers: Vec::new() };
+        dissect_ip_into(self, &mut dissection, buffer);
+        dissection
+    }
+}
+
+/// Dissect an Ethernet frame with the dissectors of radish only.
+pub fn dissect(buffer: &[u8]) -> Dissection<'_> {
+    Registry::new().dissect(buffer)
+}
+
+/// Dissect an IP packet of either version with the dissectors of radish only.
+pub fn dissect_ip(buffer: &[u8]) -> Dissection<'_> {
+    Registry::new().dissect_ip(buffer)
+}
+
+fn dissect_ip_into<'a>(registry: &Registry, dissection: &mut Dissection<'a>, buffer: &'a [u8]) {
+    match buffer.first().map(|octet| octet >> 4) {
+        Some(ipv6_consts::VERSION) => dissection.push_with("IPv6", buffer, |dissection, buffer| {
+            dissect_ipv6(registry, dissection, buffer)
+        }),
+        _ => dissection.push_with("IPv4", buffer, |dissection, buffer| {
+            dissect_ipv4(registry, dissection, buffer)
+        }),
+    }
+}
+
+fn dissect_custom<'a>(dissector: &dyn Dissector, dissection: &mut Dissection<'a>, buffer: &'a [u8]) -> Result<()> {
+    let (summary, header_len) = dissector.dissect(buffer)?;
+    if header_len > buffer.len() {
+        return Err(DissectError::InvalidHeader.into());
+    }
+    dissection.push(Layer::Custom {
+        name: dissector.name(),
+        summary,
+    });
+    dissection.push(Layer::Payload(&buffer[header_len..]));
+    Ok(())
+}
+
+fn dissect_ethernet<'a>(registry: &Registry, dissection: &mut Dissection<'a>, buffer: &'a [u8]) -> Result<()> {
+    let frame = Frame::new_checked(buffer)?;
+    let ether_type = frame.inner_ether_type();
+    dissection.push(Layer::Ethernet {
+        src_addr: frame.src_addr(),
+        dest_addr: frame.dest_addr(),
+        vlan_ids: frame.vlan_tags().iter().map(|tag| tag.vid).collect(),
+        ether_type,
+    });
+
+    let payload = &buffer[buffer.len() - frame.inner_payload().len()..];
+    if let Some(dissector) = registry.ether_types.get(&ether_type.into()) {
+        let dissector = dissector.as_ref();
+        dissection.push_with(dissector.name(), payload, |dissection, buffer| {
+            dissect_custom(dissector, dissection, buffer)
+        });
+        return Ok(());
+    }
+    match ether_type {
+        EtherType::Ipv4 => dissection.push_with("IPv4", payload, |dissection, buffer| {
+            dissect_ipv4(registry, dissection, buffer)
+        }),
+        EtherType::Ipv6 => dissection.push_with("IPv6", payload, |dissection, buffer| {
+            dissect_ipv6(registry, dissection, buffer)
+        }),
+        EtherType::Arp => dissection.push_with("ARP", payload, dissect_arp),
+        _ => dissection.push(Layer::Payload(payload)),
+    }
+    Ok(())
+}
+
+fn dissect_arp<'a>(dissection: &mut Dissection<'a>, buffer: &'a [u8]) -> Result<()> {
+    let packet = ArpPacket::new_checked(buffer)?;
+    dissection.push(Layer::Arp {
+        operation: packet.operation(),
+        sender_addr: packet.sender_protocol_addr(),
+        target_addr: packet.target_protocol_addr(),
+    });
+    Ok(())
+}
+
+fn dissect_ipv4<'a>(registry: &Registry, dissection: &mut Dissection<'a>, buffer: &'a [u8]) -> Result<()> {
+    // The octets after the total length are link layer padding.
+    if buffer.len() < (ipv4_consts::MIN_HEADER_LEN * 4) as usize {
+        return Err(Ipv4Error::InvalidTotalLen.into());
+    }
+    let total_len = Ipv4Packet::new_unchecked(buffer).total_len() as usize;
+    let packet = Ipv4Packet::new_checked(&buffer[..total_len.min(buffer.len())])?;
+    dissection.push(Layer::Ipv4 {
+        src_addr: packet.src_addr(),
+        dest_addr: packet.dest_addr(),
+        protocol: packet.protocol(),
+        ttl: packet.ttl(),
+        identification: packet.identification(),
+        offset: packet.offset() * 8,
+        more_fragments: packet.more_fragments(),
+        len: total_len,
+    });
+
+    let payload = &buffer[packet.header_len() as usize * 4..total_len];
+    if packet.offset() != 0 {
+        // Only the first fragment holds the upper-layer header.
+        dissection.push(Layer::Payload(payload));
+        return Ok(());
+    }
+    dissect_upper_layer(registry, dissection, packet.protocol(), payload);
+    Ok(())
+}
+
+fn dissect_ipv6<'a>(registry: &Registry, dissection: &mut Dissection<'a>, buffer: &'a [u8]) -> Result<()> {
+    let packet = Ipv6Packet::new_checked(buffer)?;
+    let mut headers = packet.extension_headers();
+    if let Some(Err(e)) = headers.find(|header| header.is_err()) {
+        return Err(e);
+    }
+    let (next_header, offset) = headers.upper_layer();
+    dissection.push(Layer::Ipv6 {
+        src_addr: packet.src_addr(),
+        dest_addr: packet.dest_addr(),
+        next_header,
+        hop_limit: packet.hop_limit(),
+        len: ipv6_consts::HEADER_LEN + packet.payload_len() as usize,
+    });
+
+    let payload = &buffer[ipv6_consts::HEADER_LEN + offset..ipv6_consts::HEADER_LEN + packet.payload_len() as usize];
+    dissect_upper_layer(registry, dissection, next_header, payload);
+    Ok(())
+}
+
+fn dissect_upper_layer<'a>(
+    registry: &Registry,
+    dissection: &mut Dissection<'a>,
+    protocol: Protocol,
+    payload: &'a [u8],
+) {
+    if let Some(dissector) = registry.protocols.get(&protocol.into()) {
+        let dissector = dissector.as_ref();
+        dissection.push_with(dissector.name(), payload, |dissection, buffer| {
+            dissect_custom(dissector, dissection, buffer)
+        });
+        return;
+    }
+    match protocol {
+        Protocol::Tcp => dissection.push_with("TCP", payload, dissect_tcp),
+        Protocol::Udp => dissection.push_with("UDP", payload, dissect_udp),
+        Protocol::Icmp => dissection.push_with("ICMP", payload, dissect_icmpv4),
+        Protocol::Icmpv6 => dissection.push_with("ICMPv6", payload, dissect_icmpv6),
+        _ => dissection.push(Layer::Payload(payload)),
+    }
+}
+
+fn dissect_tcp<'a>(dissection: &mut Dissection<'a>, buffer: &'a [u8]) -> Result<()> {
+    let packet = TcpPacket::new_checked(buffer)?;
+    dissection.push(Layer::Tcp {
+        src_port: packet.src_port(),
+        dest_port: packet.dest_port(),
+        flags: packet.flags(),
+        seq_number: packet.seq_number(),
+        ack_number: packet.ack_number(),
+        window: packet.window(),
+    });
+    dissection.push(Layer::Payload(&buffer[packet.data_offset() as usize * 4..]));
+    Ok(())
+}
+
+fn dissect_udp<'a>(dissection: &mut Dissection<'a>, buffer: &'a [u8]) -> Result<()> {
+    let packet = UdpPacket::new_checked(buffer)?;
+    dissection.push(Layer::Udp {
+        src_port: packet.src_port(),
+        dest_port: packet.dest_port(),
+        len: packet.length() as usize,
+    });
+    dissection.push(Layer::Payload(&buffer[buffer.len() - packet.payload().len()..]));
+    Ok(())
+}
+
+fn dissect_icmpv4<'a>(dissection: &mut Dissection<'a>, buffer: &'a [u8]) -> Result<()> {
+    if buffer.len() < 4 {
+        return Err(Icmpv4Error::TruncatedMessage.into());
+    }
+    let packet = Icmpv4Packet::new_unchecked(buffer);
+    dissection.push(Layer::Icmpv4 {
+        r#type: packet.r#type(),
+        code: packet.code(),
+    });
+    dissection.push(Layer::Payload(&buffer[4..]));
+    Ok(())
+}
+
+fn dissect_icmpv6<'a>(dissection: &mut Dissection<'a>, buffer: &'a [u8]) -> Result<()> {
+    let packet = Icmpv6Packet::new_checked(buffer)?;
+    dissection.push(Layer::Icmpv6 {
+        r#type: packet.r#type(),
+        code: packet.code(),
+    });
+    dissection.push(Layer::Payload(&buffer[4..]));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::format;
+    use std::net::Ipv4Addr;
+    use std::string::{String, ToString};
+
+    use super::error::Error as DissectError;
+    use super::{dissect, dissect_ip, Dissector, Layer, Registry};
+    use crate::error::Result;
+    use crate::ethernet::builder::FrameBuilder;
+    use crate::ethernet::frame::{EtherType, MacAddr};
+    use crate::ipv4::builder::PacketBuilder;
+    use crate::ipv4::packet::Protocol;
+    use crate::udp::builder::PacketBuilder as UdpPacketBuilder;
+
+    /// A protocol whose header is its version and the length of its payload.
+    struct Experimental;
+
+    impl Dissector for Experimental {
+        fn name(&self) -> &'static str {
+            "Experimental"
+        }
+
+        fn dissect(&self, buffer: &[u8]) -> Result<(String, usize)> {
+            match buffer {
+                [version, len, payload @ ..] if *len as usize == payload.len() => {
+                    Ok((format!("version {}, len {}", version, len), 2))
+                }
+                _ => Err(DissectError::InvalidHeader.into()),
+            }
+        }
+    }
+
+    #[test]
+    fn dissect_frame() {
+        let (src_addr, dest_addr) = (Ipv4Addr::new(10, 0, 0, 1), Ipv4Addr::new(10, 0, 0, 2));
+        let datagram = UdpPacketBuilder::default()
+            .src_port(5353)
+            .dest_port(53)
+            .src_addr(src_addr)
+            .dest_addr(dest_addr)
+            .payload(b"query".to_vec())
+            .build_vec();
+        let packet = PacketBuilder::default()
+            .identification(7)
+            .ttl(64)
+            .protocol(Protocol::Udp)
+            .src_addr(src_addr)
+            .dest_addr(dest_addr)
+            .payload(datagram)
+            .build();
+        let frame = FrameBuilder::default()
+            .src_addr(MacAddr([2, 0, 0, 0, 0, 1]))
+            .dest_addr(MacAddr([2, 0, 0, 0, 0, 2]))
+            .ether_type(EtherType::Ipv4)
+            .payload(packet.as_ref().to_vec())
+            .build_vec();
+
+        let dissection = dissect(&frame);
+        assert!(!dissection.is_malformed());
+        assert_eq!(dissection.payload(), b"query");
+        assert_eq!(
+            dissection.to_string(),
+            "Ethernet 02:00:00:00:00:01 > 02:00:00:00:00:02, Ipv4\n\
+             IPv4 10.0.0.1 > 10.0.0.2, Udp, ttl 64, id 7, len 33\n\
+             UDP 5353 > 53, len 13\n\
+             Payload 5 octets"
+        );
+
+        // A truncated datagram is left as payload.
+        let dissection = dissect_ip(&packet.as_ref()[..24]);
+        assert!(dissection.is_malformed());
+        assert!(matches!(dissection.layers()[0], Layer::Malformed { name: "IPv4", .. }));
+        assert_eq!(dissection.payload().len(), 24);
+
+        // A registered dissector takes the protocols radish does not know.
+        let experimental = Protocol::Unknown(253);
+        let mut registry = Registry::new();
+        registry.register_protocol(experimental, Experimental);
+        let packet = PacketBuilder::default()
+            .ttl(64)
+            .protocol(experimental)
+            .src_addr(src_addr)
+            .dest_addr(dest_addr)
+            .payload(vec![1, 3, 0xaa, 0xbb, 0xcc])
+            .build();
+        let dissection = registry.dissect_ip(packet.as_ref());
+        assert_eq!(dissection.layers()[1].to_string(), "Experimental version 1, len 3");
+        assert_eq!(dissection.payload(), [0xaa, 0xbb, 0xcc]);
+        assert!(matches!(dissect_ip(packet.as_ref()).layers()[1], Layer::Payload(_)));
+
+        let packet = PacketBuilder::default()
+            .protocol(experimental)
+            .payload(vec![1, 4, 0xaa])
+            .build();
+        let dissection = registry.dissect_ip(packet.as_ref());
+        assert!(matches!(
+            dissection.layers()[1],
+            Layer::Malformed {
+                name: "Experimental",
+                ..
+            }
+        ));
+    }
+}
@@ -0,0 +1,183 @@
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::dns::packet::{consts, write_name, write_record_data, Packet, Question, Rcode, Record};
+
+/// Build a DNS message, names are written uncompressed and must pass `check_name`.
+pub struct PacketBuilder {
+    id: u16,
+    response: bool,
+    authoritative: bool,
+    truncated: bool,
+    recursion_desired: bool,
+    recursion_available: bool,
+    rcode: Rcode,
+    questions: Vec<Question>,
+    answers: Vec<Record>,
+    authorities: Vec<Record>,
+}
+
+impl PacketBuilder {
+    pub fn id(mut self, id: u16) -> Self {
+        self.id = id;
+        self
+    }
+
+    pub fn response(mut self, response: bool) -> Self {
+        self.response = response;
+        self
+    }
+
+    pub fn authoritative(mut self, authoritative: bool) -> Self {
+        self.authoritative = authoritative;
+        self
+    }
+
+    pub fn truncated(mut self, truncated: bool) -> Self {
+        self.truncated = truncated;
+        self
+    }
+
+    pub fn recursion_desired(mut self, recursion_desired: bool) -> Self {
+        self.recursion_desired = recursion_desired;
+        self
+    }
+
+    pub fn recursion_available(mut self, recursion_available: bool) -> Self {
+        self.recursion_available = recursion_available;
+        self
+    }
+
+    pub fn rcode(mut self, rcode: Rcode) -> Self {
+        self.rcode = rcode;
+        self
+    }
+
+    pub fn question(mut self, question: Question) -> Self {
+        self.questions.push(question);
+        self
+    }
+
+    pub fn answer(mut self, answer: Record) -> Self {
+        self.answers.push(answer);
+        self
+    }
+
+    pub fn authority(mut self, authority: Record) -> Self {
+        self.authorities.push(authority);
+        self
+    }
+
+    pub fn build_vec(self) -> Vec<u8> {
+        let mut buffer: Vec<u8> = vec![0; consts::HEADER_LEN];
+
+        for question in self.questions.iter() {
+            write_name(&question.name, &mut buffer);
+            buffer.extend_from_slice(u16::from(question.r#type).to_be_bytes().as_ref());
+            buffer.extend_from_slice(question.class.to_be_bytes().as_ref());
+        }
+        for record in self.answers.iter().chain(self.authorities.iter()) {
+            write_name(&record.name, &mut buffer);
+            buffer.extend_from_slice(u16::from(record.r#type).to_be_bytes().as_ref());
+            buffer.extend_from_slice(record.class.to_be_bytes().as_ref());
+            buffer.extend_from_slice(record.ttl.to_be_bytes().as_ref());
+            write_record_data(&record.data, &mut buffer);
+        }
+
+        let mut packet = Packet::new_unchecked(buffer.as_mut_slice());
+        packet.set_id(self.id);
+        packet.set_response(self.response);
+        packet.set_authoritative(self.authoritative);
+        packet.set_truncated(self.truncated);
+        packet.set_recursion_desired(self.recursion_desired);
+        packet.set_recursion_available(self.recursion_available);
+        packet.set_rcode(self.rcode);
+        packet.set_question_count(self.questions.len() as u16);
+        packet.set_answer_count(self.answers.len() as u16);
+        packet.set_authority_count(self.authorities.len() as u16);
+
+        buffer
+    }
+
+    pub fn build(self) -> Packet<Vec<u8>> {
+        Packet::new_unchecked(self.build_vec())
+    }
+}
+
+impl Default for PacketBuilder {
+    fn default() -> Self {
+        Self {
+            id: 0,
+            response: false,
+            authoritative: false,
+            truncated: false,
+            recursion_desired: true,
+            recursion_available: false,
+            rcode: Rcode::NoError,
+            questions: vec![],
+            answers: vec![],
+            authorities: vec![],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use crate::dns::packet::consts::CLASS_IN;
+    use crate::dns::packet::{Message, Question, Rcode, Record, RecordData, RecordType};
+
+    #[test]
+    fn build() {
+        let question = Question {
+            name: "example.com".to_string(),
+            r#type: RecordType::Mx,
+            class: CLASS_IN,
+        };
+        let answer = Record {
+            name: "example.com".to_string(),
+            r#type: RecordType::Mx,
+            class: CLASS_IN,
+            ttl: 300,
+            data: RecordData::Mx {
+                preference: 10,
+                exchange: "mail.example.com".to_string(),
+            },
+        };
+        let authority = Record {
+            name: "example.com".to_string(),
+            r#type: RecordType::Ns,
+            class: CLASS_IN,
+            ttl: 300,
+            data: RecordData::Ns("ns.example.com".to_string()),
+        };
+
+        let packet = super::PacketBuilder::default()
+            .id(0xbeef)
+            .response(true)
+            .question(question.clone())
+            .answer(answer.clone())
+            .authority(authority.clone())
+            .build();
+        assert_eq!(packet.id(), 0xbeef);
+        assert!(packet.recursion_desired());
+
+        let message = Message::parse(packet.as_ref()).unwrap();
+        assert!(message.is_response);
+        assert_eq!(message.rcode, Rcode::NoError);
+        assert_eq!(message.questions, vec![question]);
+        assert_eq!(message.answers, vec![answer]);
+        assert_eq!(message.authorities, vec![authority]);
+
+        let a = Record {
+            name: "example.com".to_string(),
+            r#type: RecordType::A,
+            class: CLASS_IN,
+            ttl: 0,
+            data: RecordData::A(Ipv4Addr::new(192, 0, 2, 1)),
+        };
+        let packet = super::PacketBuilder::default().answer(a.clone()).build();
+        assert_eq!(Message::parse(packet.as_ref()).unwrap().answers, vec![a]);
+    }
+}
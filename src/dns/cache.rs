@@ -0,0 +1,234 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use crate::dns::packet::{Message, Record, RecordData, RecordType};
+
+pub mod consts {
+    pub const DEFAULT_CAPACITY: usize = 1024; // Answers kept before the ones expiring first are evicted
+    pub const MAX_NEGATIVE_TTL: u32 = 3 * 60 * 60; // RFC 2308 section 5
+}
+
+/// An answer kept in the cache.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Cached {
+    /// The records of the queried type, with their TTLs counting down.
+    Records(Vec<Record>),
+    /// The name exists without records of the queried type.
+    NoData,
+    NameNotFound,
+}
+
+#[derive(Debug)]
+struct Entry {
+    cached: Cached,
+    inserted_at: Instant,
+    expires_at: Instant,
+}
+
+/// A cache of the answers of a resolver, each one kept for the TTL of its records (RFC 1035 section 7.4)
+/// or, for negative answers, the TTL of the SOA record of the zone (RFC 2308).
+#[derive(Debug)]
+pub struct Cache {
+    entries: HashMap<(String, RecordType), Entry>,
+    capacity: usize,
+}
+
+impl Cache {
+    /// Keep up to `capacity` answers, zero disables the cache.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: HashMap::new(),
+            capacity,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        while self.entries.len() > capacity {
+            self.evict();
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    /// Returns the answer cached for `name` and `r#type`, unless it expired.
+    pub fn get(&mut self, name: &str, r#type: RecordType, now: Instant) -> Option<Cached> {
+        let key = key(name, r#type);
+        let entry = self.entries.get(&key)?;
+        if now >= entry.expires_at {
+            self.entries.remove(&key);
+            return None;
+        }
+
+        let age = now.saturating_duration_since(entry.inserted_at).as_secs() as u32;
+        Some(match &entry.cached {
+            Cached::Records(records) => Cached::Records(
+                records
+                    .iter()
+                    .map(|record| Record {
+                        ttl: record.ttl.saturating_sub(age),
+                        ..record.clone()
+                    })
+                    .collect(),
+            ),
+            cached => cached.clone(),
+        })
+    }
+
+    /// Cache the answer for `name` and `r#type` for `ttl` seconds, an answer with a zero TTL is not cached.
+    pub fn insert(&mut self, name: &str, r#type: RecordType, cached: Cached, ttl: u32, now: Instant) {
+        if ttl == 0 || self.capacity == 0 {
+            return;
+        }
+
+        let key = key(name, r#type);
+        if !self.entries.contains_key(&key) && self.entries.len() >= self.capacity {
+            self.entries.retain(|_, entry| entry.expires_at > now);
+            if self.entries.len() >= self.capacity {
+                self.evict();
+            }
+        }
+
+        self.entries.insert(
+            key,
+            Entry {
+                cached,
+                inserted_at: now,
+                expires_at: now + Duration::from_secs(ttl as u64),
+            },
+        );
+    }
+
+    /// Remove the entry expiring first.
+    fn evict(&mut self) {
+        let first = self
+            .entries
+            .iter()
+            .min_by_key(|(_, entry)| entry.expires_at)
+            .map(|(key, _)| key.clone());
+        if let Some(key) = first {
+            self.entries.remove(&key);
+        }
+    }
+}
+
+impl Default for Cache {
+    fn default() -> Self {
+        Self::new(consts::DEFAULT_CAPACITY)
+    }
+}
+
+fn key(name: &str, r#type: RecordType) -> (String, RecordType) {
+    (name.trim_end_matches('.').to_ascii_lowercase(), r#type)
+}
+
+/// Returns how long a negative answer can be cached: the smaller of the TTL and the minimum field
+/// of the SOA record in its authority section (RFC 2308 section 5). Without one it is not cached.
+pub fn negative_ttl(message: &Message) -> Option<u32> {
+    message
+        .authorities
+        .iter()
+        .find_map(|record| match record.data {
+            RecordData::Soa { minimum, .. } => Some(record.ttl.min(minimum)),
+            _ => None,
+        })
+        .map(|ttl| ttl.min(consts::MAX_NEGATIVE_TTL))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+    use std::time::{Duration, Instant};
+
+    use super::{negative_ttl, Cache, Cached};
+    use crate::dns::builder::PacketBuilder;
+    use crate::dns::packet::consts::CLASS_IN;
+    use crate::dns::packet::{Message, Rcode, Record, RecordData, RecordType};
+
+    #[test]
+    fn expiry() {
+        let mut cache = Cache::new(2);
+        let now = Instant::now();
+        let record = Record {
+            name: "example.com".to_string(),
+            r#type: RecordType::A,
+            class: CLASS_IN,
+            ttl: 60,
+            data: RecordData::A(Ipv4Addr::new(192, 0, 2, 1)),
+        };
+
+        cache.insert(
+            "Example.com.",
+            RecordType::A,
+            Cached::Records(vec![record.clone()]),
+            60,
+            now,
+        );
+        cache.insert("example.org", RecordType::A, Cached::NameNotFound, 30, now);
+        cache.insert("example.net", RecordType::A, Cached::NoData, 0, now);
+        assert_eq!(cache.len(), 2);
+
+        match cache.get("example.com", RecordType::A, now + Duration::from_secs(20)) {
+            Some(Cached::Records(records)) => assert_eq!(records[0].ttl, 40),
+            cached => panic!("unexpected {:?}", cached),
+        }
+        assert!(cache.get("example.com", RecordType::Aaaa, now).is_none());
+        assert_eq!(cache.get("example.org", RecordType::A, now), Some(Cached::NameNotFound));
+        assert!(cache
+            .get("example.org", RecordType::A, now + Duration::from_secs(30))
+            .is_none());
+        assert_eq!(cache.len(), 1);
+
+        // A full cache evicts the answer expiring first.
+        cache.insert("example.org", RecordType::A, Cached::NoData, 10, now);
+        cache.insert("example.net", RecordType::A, Cached::NoData, 90, now);
+        assert_eq!(cache.len(), 2);
+        assert!(cache.get("example.org", RecordType::A, now).is_none());
+        assert!(cache.get("example.com", RecordType::A, now).is_some());
+    }
+
+    #[test]
+    fn negative() {
+        let soa = Record {
+            name: "example.com".to_string(),
+            r#type: RecordType::Soa,
+            class: CLASS_IN,
+            ttl: 3600,
+            data: RecordData::Soa {
+                mname: "ns.example.com".to_string(),
+                rname: "hostmaster.example.com".to_string(),
+                serial: 1,
+                refresh: 7200,
+                retry: 900,
+                expire: 1209600,
+                minimum: 300,
+            },
+        };
+        let response = PacketBuilder::default()
+            .response(true)
+            .rcode(Rcode::NameError)
+            .authority(soa)
+            .build_vec();
+        assert_eq!(negative_ttl(&Message::parse(&response).unwrap()), Some(300));
+
+        let response = PacketBuilder::default()
+            .response(true)
+            .rcode(Rcode::NameError)
+            .build_vec();
+        assert_eq!(negative_ttl(&Message::parse(&response).unwrap()), None);
+    }
+}
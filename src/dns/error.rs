@@ -0,0 +1,31 @@
+use core::fmt::{Display, Formatter};
+
+use crate::dns::packet::Rcode;
+
+#[derive(Debug)]
+pub enum Error {
+    InvalidLength,
+    InvalidName,
+    NoServers,
+    /// The name does not exist, as stated by an authoritative server.
+    NameNotFound,
+    /// Every server answered with an error, or did not answer.
+    ErrorResponse(Rcode),
+    /// The answer received over TCP is not the one to the query.
+    UnexpectedResponse,
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Error::InvalidLength => write!(f, "invalid length"),
+            Error::InvalidName => write!(f, "invalid name"),
+            Error::NoServers => write!(f, "no name server configured"),
+            Error::NameNotFound => write!(f, "name not found"),
+            Error::ErrorResponse(rcode) => write!(f, "error response: {:?}", rcode),
+            Error::UnexpectedResponse => write!(f, "unexpected response"),
+        }
+    }
+}
+
+impl core::error::Error for Error {}
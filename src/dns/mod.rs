@@ -0,0 +1,7 @@
+pub mod builder;
+#[cfg(feature = "std")]
+pub mod cache;
+pub mod error;
+pub mod packet;
+#[cfg(feature = "std")]
+pub mod resolver;
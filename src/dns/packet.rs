@@ -0,0 +1,566 @@
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt::{Debug, Formatter};
+use core::net::{Ipv4Addr, Ipv6Addr};
+
+use crate::c_like_enum;
+use crate::dns::error::Error;
+use crate::error::Result;
+
+pub mod consts {
+    pub const HEADER_LEN: usize = 12; // RFC 1035 section 4.1.1
+    pub const PORT: u16 = 53;
+    pub const MAX_UDP_LEN: usize = 512; // RFC 1035 section 4.2.1, without EDNS
+    pub const MAX_NAME_LEN: usize = 255; // RFC 1035 section 2.3.4
+    pub const MAX_LABEL_LEN: usize = 63;
+    pub const MAX_POINTERS: usize = 64; // Compression pointers followed in a name, more is a loop
+    pub const CLASS_IN: u16 = 1;
+}
+
+c_like_enum!(
+    /// Resource record types (RFC 1035 section 3.2.2 and RFC 3596)
+    #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+    pub enum RecordType(u16) {
+        A = 1,
+        Ns = 2,
+        Cname = 5,
+        Soa = 6,
+        Ptr = 12,
+        Mx = 15,
+        Txt = 16,
+        Aaaa = 28,
+    }
+);
+
+c_like_enum!(
+    /// Response codes (RFC 1035 section 4.1.1)
+    #[derive(Debug, Copy, Clone, PartialEq, Eq)]
+    pub enum Rcode(u8) {
+        NoError = 0,
+        FormatError = 1,
+        ServerFailure = 2,
+        NameError = 3,
+        NotImplemented = 4,
+        Refused = 5,
+    }
+);
+
+/// The header of a DNS message.
+pub struct Packet<Buf> {
+    buffer: Buf,
+}
+
+impl<Buf> Packet<Buf>
+where
+    Buf: AsRef<[u8]>,
+{
+    pub fn new_unchecked(buffer: Buf) -> Self {
+        Packet { buffer }
+    }
+
+    pub fn new_checked(buffer: Buf) -> Result<Self> {
+        if buffer.as_ref().len() < consts::HEADER_LEN {
+            return Err(Error::InvalidLength.into());
+        }
+        Ok(Self::new_unchecked(buffer))
+    }
+
+    pub fn id(&self) -> u16 {
+        u16::from_be_bytes([self.buffer.as_ref()[0], self.buffer.as_ref()[1]])
+    }
+
+    pub fn is_response(&self) -> bool {
+        self.buffer.as_ref()[2] & 0x80 != 0
+    }
+
+    pub fn opcode(&self) -> u8 {
+        (self.buffer.as_ref()[2] >> 3) & 0xf
+    }
+
+    pub fn is_authoritative(&self) -> bool {
+        self.buffer.as_ref()[2] & 0x04 != 0
+    }
+
+    /// Whether the message was cut to fit in a UDP datagram.
+    pub fn is_truncated(&self) -> bool {
+        self.buffer.as_ref()[2] & 0x02 != 0
+    }
+
+    pub fn recursion_desired(&self) -> bool {
+        self.buffer.as_ref()[2] & 0x01 != 0
+    }
+
+    pub fn recursion_available(&self) -> bool {
+        self.buffer.as_ref()[3] & 0x80 != 0
+    }
+
+    pub fn rcode(&self) -> Rcode {
+        (self.buffer.as_ref()[3] & 0xf).into()
+    }
+
+    pub fn question_count(&self) -> u16 {
+        u16::from_be_bytes([self.buffer.as_ref()[4], self.buffer.as_ref()[5]])
+    }
+
+    pub fn answer_count(&self) -> u16 {
+        u16::from_be_bytes([self.buffer.as_ref()[6], self.buffer.as_ref()[7]])
+    }
+
+    pub fn authority_count(&self) -> u16 {
+        u16::from_be_bytes([self.buffer.as_ref()[8], self.buffer.as_ref()[9]])
+    }
+
+    pub fn additional_count(&self) -> u16 {
+        u16::from_be_bytes([self.buffer.as_ref()[10], self.buffer.as_ref()[11]])
+    }
+
+    /// Returns the sections following the header.
+    pub fn payload(&self) -> &[u8] {
+        &self.buffer.as_ref()[consts::HEADER_LEN..]
+    }
+}
+
+impl<Buf> Packet<Buf>
+where
+    Buf: AsRef<[u8]> + AsMut<[u8]>,
+{
+    pub fn set_id(&mut self, id: u16) {
+        self.buffer.as_mut()[0..=1].copy_from_slice(id.to_be_bytes().as_ref());
+    }
+
+    pub fn set_response(&mut self, response: bool) {
+        self.set_flag(2, 0x80, response);
+    }
+
+    pub fn set_opcode(&mut self, opcode: u8) {
+        self.buffer.as_mut()[2] = (self.buffer.as_ref()[2] & !0x78) | ((opcode & 0xf) << 3);
+    }
+
+    pub fn set_authoritative(&mut self, authoritative: bool) {
+        self.set_flag(2, 0x04, authoritative);
+    }
+
+    pub fn set_truncated(&mut self, truncated: bool) {
+        self.set_flag(2, 0x02, truncated);
+    }
+
+    pub fn set_recursion_desired(&mut self, recursion_desired: bool) {
+        self.set_flag(2, 0x01, recursion_desired);
+    }
+
+    pub fn set_recursion_available(&mut self, recursion_available: bool) {
+        self.set_flag(3, 0x80, recursion_available);
+    }
+
+    pub fn set_rcode(&mut self, rcode: Rcode) {
+        self.buffer.as_mut()[3] = (self.buffer.as_ref()[3] & !0xf) | (u8::from(rcode) & 0xf);
+    }
+
+    pub fn set_question_count(&mut self, count: u16) {
+        self.buffer.as_mut()[4..=5].copy_from_slice(count.to_be_bytes().as_ref());
+    }
+
+    pub fn set_answer_count(&mut self, count: u16) {
+        self.buffer.as_mut()[6..=7].copy_from_slice(count.to_be_bytes().as_ref());
+    }
+
+    pub fn set_authority_count(&mut self, count: u16) {
+        self.buffer.as_mut()[8..=9].copy_from_slice(count.to_be_bytes().as_ref());
+    }
+
+    pub fn set_additional_count(&mut self, count: u16) {
+        self.buffer.as_mut()[10..=11].copy_from_slice(count.to_be_bytes().as_ref());
+    }
+
+    fn set_flag(&mut self, index: usize, mask: u8, value: bool) {
+        if value {
+            self.buffer.as_mut()[index] |= mask;
+        } else {
+            self.buffer.as_mut()[index] &= !mask;
+        }
+    }
+}
+
+impl<Buf> Debug for Packet<Buf>
+where
+    Buf: AsRef<[u8]>,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "id: {:#x}, response: {:?}, opcode: {:?}, truncated: {:?}, rcode: {:?}, \
+            questions: {:?}, answers: {:?}, authorities: {:?}, additionals: {:?}",
+            self.id(),
+            self.is_response(),
+            self.opcode(),
+            self.is_truncated(),
+            self.rcode(),
+            self.question_count(),
+            self.answer_count(),
+            self.authority_count(),
+            self.additional_count(),
+        )
+    }
+}
+
+impl<Buf> AsRef<[u8]> for Packet<Buf>
+where
+    Buf: AsRef<[u8]>,
+{
+    fn as_ref(&self) -> &[u8] {
+        self.buffer.as_ref()
+    }
+}
+
+impl<Buf> AsMut<[u8]> for Packet<Buf>
+where
+    Buf: AsMut<[u8]>,
+{
+    fn as_mut(&mut self) -> &mut [u8] {
+        self.buffer.as_mut()
+    }
+}
+
+/// An entry of the question section.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Question {
+    /// The queried name, without the trailing dot.
+    pub name: String,
+    pub r#type: RecordType,
+    pub class: u16,
+}
+
+/// The data of a resource record, the names it holds are decompressed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RecordData {
+    A(Ipv4Addr),
+    Aaaa(Ipv6Addr),
+    Ns(String),
+    Cname(String),
+    Ptr(String),
+    Mx {
+        preference: u16,
+        exchange: String,
+    },
+    Soa {
+        mname: String,
+        rname: String,
+        serial: u32,
+        refresh: u32,
+        retry: u32,
+        expire: u32,
+        /// The TTL of negative answers (RFC 2308 section 4).
+        minimum: u32,
+    },
+    Txt(Vec<Vec<u8>>),
+    /// The raw data of a record of another type.
+    Other(Vec<u8>),
+}
+
+/// A resource record of the answer, authority or additional section.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Record {
+    pub name: String,
+    pub r#type: RecordType,
+    pub class: u16,
+    pub ttl: u32,
+    pub data: RecordData,
+}
+
+/// A parsed DNS message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Message {
+    pub id: u16,
+    pub is_response: bool,
+    pub is_truncated: bool,
+    pub rcode: Rcode,
+    pub questions: Vec<Question>,
+    pub answers: Vec<Record>,
+    pub authorities: Vec<Record>,
+    pub additionals: Vec<Record>,
+}
+
+impl Message {
+    pub fn parse(buffer: &[u8]) -> Result<Self> {
+        let packet = Packet::new_checked(buffer)?;
+        let mut offset = consts::HEADER_LEN;
+
+        let mut questions = vec![];
+        for _ in 0..packet.question_count() {
+            let (name, next) = read_name(buffer, offset)?;
+            let fixed = read(buffer, next, 4)?;
+            questions.push(Question {
+                name,
+                r#type: u16::from_be_bytes([fixed[0], fixed[1]]).into(),
+                class: u16::from_be_bytes([fixed[2], fixed[3]]),
+            });
+            offset = next + 4;
+        }
+
+        let mut sections = [vec![], vec![], vec![]];
+        let counts = [
+            packet.answer_count(),
+            packet.authority_count(),
+            packet.additional_count(),
+        ];
+        for (section, count) in sections.iter_mut().zip(counts.iter()) {
+            for _ in 0..*count {
+                let (record, next) = read_record(buffer, offset)?;
+                section.push(record);
+                offset = next;
+            }
+        }
+        let [answers, authorities, additionals] = sections;
+
+        Ok(Self {
+            id: packet.id(),
+            is_response: packet.is_response(),
+            is_truncated: packet.is_truncated(),
+            rcode: packet.rcode(),
+            questions,
+            answers,
+            authorities,
+            additionals,
+        })
+    }
+}
+
+fn read(buffer: &[u8], offset: usize, len: usize) -> Result<&[u8]> {
+    buffer
+        .get(offset..offset + len)
+        .ok_or_else(|| Error::InvalidLength.into())
+}
+
+fn read_u16(buffer: &[u8], offset: usize) -> Result<u16> {
+    let octets = read(buffer, offset, 2)?;
+    Ok(u16::from_be_bytes([octets[0], octets[1]]))
+}
+
+fn read_u32(buffer: &[u8], offset: usize) -> Result<u32> {
+    let octets = read(buffer, offset, 4)?;
+    Ok(u32::from_be_bytes([octets[0], octets[1], octets[2], octets[3]]))
+}
+
+/// Read the possibly compressed name at `offset` (RFC 1035 section 4.1.4),
+/// returns it and the offset following it in the message.
+pub fn read_name(buffer: &[u8], offset: usize) -> Result<(String, usize)> {
+    let mut labels: Vec<String> = vec![];
+    let mut name_len = 0;
+    let mut position = offset;
+    let mut next = None;
+    let mut pointers = 0;
+
+    loop {
+        let len = *buffer.get(position).ok_or(Error::InvalidLength)? as usize;
+        match len & 0xc0 {
+            0x00 if len == 0 => {
+                return Ok((labels.join("."), next.unwrap_or(position + 1)));
+            }
+            0x00 => {
+                let label = read(buffer, position + 1, len)?;
+                name_len += len + 1;
+                if name_len >= consts::MAX_NAME_LEN {
+                    return Err(Error::InvalidName.into());
+                }
+                labels.push(String::from_utf8_lossy(label).into_owned());
+                position += len + 1;
+            }
+            0xc0 => {
+                pointers += 1;
+                if pointers > consts::MAX_POINTERS {
+                    return Err(Error::InvalidName.into());
+                }
+                next.get_or_insert(position + 2);
+                position = (read_u16(buffer, position)? & 0x3fff) as usize;
+            }
+            _ => return Err(Error::InvalidName.into()),
+        }
+    }
+}
+
+/// Check that `name` can be encoded: labels of 1 to 63 octets, 255 octets at most once encoded.
+pub fn check_name(name: &str) -> Result<()> {
+    let name = name.strip_suffix('.').unwrap_or(name);
+    if name.is_empty() {
+        return Ok(());
+    }
+    if name.len() + 2 > consts::MAX_NAME_LEN
+        || name
+            .split('.')
+            .any(|label| label.is_empty() || label.len() > consts::MAX_LABEL_LEN)
+    {
+        return Err(Error::InvalidName.into());
+    }
+    Ok(())
+}
+
+/// Append `name` to `buffer`, uncompressed. The name must pass `check_name`.
+pub fn write_name(name: &str, buffer: &mut Vec<u8>) {
+    let name = name.strip_suffix('.').unwrap_or(name);
+    for label in name.split('.').filter(|label| !label.is_empty()) {
+        buffer.push(label.len() as u8);
+        buffer.extend_from_slice(label.as_bytes());
+    }
+    buffer.push(0);
+}
+
+fn read_record(buffer: &[u8], offset: usize) -> Result<(Record, usize)> {
+    let (name, offset) = read_name(buffer, offset)?;
+    let r#type: RecordType = read_u16(buffer, offset)?.into();
+    let class = read_u16(buffer, offset + 2)?;
+    let ttl = read_u32(buffer, offset + 4)?;
+    let data_len = read_u16(buffer, offset + 8)? as usize;
+    let start = offset + 10;
+    let data = read(buffer, start, data_len)?;
+
+    let data = match r#type {
+        RecordType::A if data_len == 4 => RecordData::A(Ipv4Addr::new(data[0], data[1], data[2], data[3])),
+        RecordType::Aaaa if data_len == 16 => {
+            let mut octets = [0; 16];
+            octets.copy_from_slice(data);
+            RecordData::Aaaa(Ipv6Addr::from(octets))
+        }
+        RecordType::Ns => RecordData::Ns(read_name(buffer, start)?.0),
+        RecordType::Cname => RecordData::Cname(read_name(buffer, start)?.0),
+        RecordType::Ptr => RecordData::Ptr(read_name(buffer, start)?.0),
+        RecordType::Mx => RecordData::Mx {
+            preference: read_u16(buffer, start)?,
+            exchange: read_name(buffer, start + 2)?.0,
+        },
+        RecordType::Soa => {
+            let (mname, next) = read_name(buffer, start)?;
+            let (rname, next) = read_name(buffer, next)?;
+            RecordData::Soa {
+                mname,
+                rname,
+                serial: read_u32(buffer, next)?,
+                refresh: read_u32(buffer, next + 4)?,
+                retry: read_u32(buffer, next + 8)?,
+                expire: read_u32(buffer, next + 12)?,
+                minimum: read_u32(buffer, next + 16)?,
+            }
+        }
+        RecordType::Txt => {
+            let mut strings = vec![];
+            let mut position = 0;
+            while position < data_len {
+                let len = data[position] as usize;
+                strings.push(read(data, position + 1, len)?.to_vec());
+                position += len + 1;
+            }
+            RecordData::Txt(strings)
+        }
+        RecordType::A | RecordType::Aaaa => return Err(Error::InvalidLength.into()),
+        _ => RecordData::Other(data.to_vec()),
+    };
+
+    Ok((
+        Record {
+            name,
+            r#type,
+            class,
+            ttl,
+            data,
+        },
+        start + data_len,
+    ))
+}
+
+/// Append the data of a record to `buffer`, preceded by its length.
+pub fn write_record_data(data: &RecordData, buffer: &mut Vec<u8>) {
+    let mut encoded = vec![];
+    match data {
+        RecordData::A(addr) => encoded.extend_from_slice(addr.octets().as_ref()),
+        RecordData::Aaaa(addr) => encoded.extend_from_slice(addr.octets().as_ref()),
+        RecordData::Ns(name) | RecordData::Cname(name) | RecordData::Ptr(name) => write_name(name, &mut encoded),
+        RecordData::Mx { preference, exchange } => {
+            encoded.extend_from_slice(preference.to_be_bytes().as_ref());
+            write_name(exchange, &mut encoded);
+        }
+        RecordData::Soa {
+            mname,
+            rname,
+            serial,
+            refresh,
+            retry,
+            expire,
+            minimum,
+        } => {
+            write_name(mname, &mut encoded);
+            write_name(rname, &mut encoded);
+            for value in [serial, refresh, retry, expire, minimum].iter() {
+                encoded.extend_from_slice(value.to_be_bytes().as_ref());
+            }
+        }
+        RecordData::Txt(strings) => {
+            for string in strings {
+                encoded.push(string.len() as u8);
+                encoded.extend_from_slice(string);
+            }
+        }
+        RecordData::Other(data) => encoded.extend_from_slice(data),
+    }
+
+    buffer.extend_from_slice((encoded.len() as u16).to_be_bytes().as_ref());
+    buffer.append(&mut encoded);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use super::{check_name, Message, Packet, Rcode, RecordData, RecordType};
+
+    #[test]
+    fn parse() {
+        let bytes: Vec<u8> = vec![
+            // header: response, recursion desired and available, 1 question and 2 answers
+            0x12, 0x34, 0x81, 0x80, 0x00, 0x01, 0x00, 0x02, 0x00, 0x00, 0x00, 0x00, //
+            // question: www.example.com A IN
+            0x03, b'w', b'w', b'w', 0x07, b'e', b'x', b'a', b'm', b'p', b'l', b'e', 0x03, b'c', b'o', b'm',
+            0x00, //
+            0x00, 0x01, 0x00, 0x01, //
+            // answer: www.example.com CNAME example.com, compressed
+            0xc0, 0x0c, 0x00, 0x05, 0x00, 0x01, 0x00, 0x00, 0x0e, 0x10, 0x00, 0x02, 0xc0, 0x10, //
+            // answer: example.com A 93.184.216.34
+            0xc0, 0x10, 0x00, 0x01, 0x00, 0x01, 0x00, 0x00, 0x00, 0x3c, 0x00, 0x04, 93, 184, 216, 34,
+        ];
+
+        let packet = Packet::new_checked(bytes.as_slice()).unwrap();
+        assert_eq!(packet.id(), 0x1234);
+        assert!(packet.is_response());
+        assert!(packet.recursion_desired());
+        assert!(packet.recursion_available());
+        assert!(!packet.is_truncated());
+        assert_eq!(packet.rcode(), Rcode::NoError);
+
+        let message = Message::parse(&bytes).unwrap();
+        assert_eq!(message.questions[0].name, "www.example.com");
+        assert_eq!(message.questions[0].r#type, RecordType::A);
+        assert_eq!(message.answers.len(), 2);
+        assert_eq!(message.answers[0].data, RecordData::Cname("example.com".to_string()));
+        assert_eq!(message.answers[0].ttl, 3600);
+        assert_eq!(message.answers[1].name, "example.com");
+        assert_eq!(message.answers[1].data, RecordData::A(Ipv4Addr::new(93, 184, 216, 34)));
+
+        assert!(Message::parse(&bytes[..bytes.len() - 1]).is_err());
+        assert!(Packet::new_checked(&bytes[..11]).is_err());
+
+        // A compression pointer to itself loops forever.
+        let mut looping = bytes[..12].to_vec();
+        looping.extend_from_slice(&[0xc0, 0x0c, 0x00, 0x01, 0x00, 0x01]);
+        looping[7] = 0;
+        assert!(Message::parse(&looping).is_err());
+    }
+
+    #[test]
+    fn name() {
+        assert!(check_name("example.com").is_ok());
+        assert!(check_name("example.com.").is_ok());
+        assert!(check_name("").is_ok());
+        assert!(check_name("example..com").is_err());
+        assert!(check_name(&"a".repeat(64)).is_err());
+        assert!(check_name(&["a"; 128].join(".")).is_err());
+    }
+}
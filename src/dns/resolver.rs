@@ -0,0 +1,486 @@
+use std::collections::hash_map::RandomState;
+use std::hash::BuildHasher;
+use std::io::{Error as IOError, ErrorKind, Read, Write};
+use std::net::{Ipv4Addr, SocketAddrV4};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::dns::builder::PacketBuilder;
+use crate::dns::cache::{negative_ttl, Cache, Cached};
+use crate::dns::error::Error;
+use crate::dns::packet::consts::{CLASS_IN, MAX_UDP_LEN};
+use crate::dns::packet::{check_name, Message, Question, Rcode, Record, RecordData, RecordType};
+use crate::error::{Error as RadishError, Result};
+use crate::ipv4::interface::Interface;
+use crate::macros::diagnostics::debug;
+use crate::net_device::tun::TunDevice;
+use crate::net_device::Device;
+use crate::tcp::stream::TcpStream;
+use crate::udp::udp_socket::UdpSocket;
+
+pub mod consts {
+    use std::time::Duration;
+
+    pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5); // Same as the resolv.conf defaults
+    pub const DEFAULT_ATTEMPTS: usize = 2;
+    pub const MAX_CNAME_CHAIN: usize = 8; // Aliases followed in an answer
+}
+
+/// A stub resolver, which sends queries to recursive name servers through the UDP sockets of an interface
+/// and caches their answers, on the clock of the interface. A truncated answer is queried again over TCP.
+pub struct Resolver<D: Device = TunDevice> {
+    interface: Arc<Mutex<Interface<D>>>,
+    servers: Vec<SocketAddrV4>,
+    timeout: Duration,
+    attempts: usize,
+    /// Randomizes the query IDs, which with the random source port guards against spoofed answers.
+    secret: RandomState,
+    queries: u64,
+    cache: Cache,
+}
+
+impl<D: Device> Resolver<D> {
+    /// Query `servers` in order, such as 192.0.2.53:53.
+    pub fn new(interface: &Arc<Mutex<Interface<D>>>, servers: Vec<SocketAddrV4>) -> Self {
+        Self {
+            interface: interface.clone(),
+            servers,
+            timeout: consts::DEFAULT_TIMEOUT,
+            attempts: consts::DEFAULT_ATTEMPTS,
+            secret: RandomState::new(),
+            queries: 0,
+            cache: Cache::default(),
+        }
+    }
+
+    pub fn servers(&self) -> &[SocketAddrV4] {
+        &self.servers
+    }
+
+    /// How long to wait for the answer of a server before querying the next one.
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+
+    /// How many times every server is queried before giving up.
+    pub fn set_attempts(&mut self, attempts: usize) {
+        self.attempts = attempts;
+    }
+
+    pub fn cache(&self) -> &Cache {
+        &self.cache
+    }
+
+    pub fn cache_mut(&mut self) -> &mut Cache {
+        &mut self.cache
+    }
+
+    fn now(&self) -> Instant {
+        self.interface.lock().unwrap().now()
+    }
+
+    /// Returns the time left until `deadline`, failing with `TimedOut` once it has passed.
+    fn remaining(&self, deadline: Instant) -> Result<Duration> {
+        match deadline.saturating_duration_since(self.now()) {
+            Duration::ZERO => Err(IOError::from(ErrorKind::TimedOut).into()),
+            remaining => Ok(remaining),
+        }
+    }
+
+    fn next_id(&mut self) -> u16 {
+        self.queries += 1;
+        self.secret.hash_one((self.queries, Instant::now())) as u16
+    }
+
+    /// Resolve the records of `r#type` for `name`, following the aliases of the answer.
+    /// An empty list means the name exists without records of this type.
+    pub fn query(&mut self, name: &str, r#type: RecordType) -> Result<Vec<Record>> {
+        match self.cache.get(name, r#type, self.now()) {
+            Some(Cached::Records(records)) => return Ok(records),
+            Some(Cached::NoData) => return Ok(vec![]),
+            Some(Cached::NameNotFound) => return Err(Error::NameNotFound.into()),
+            None => {}
+        }
+
+        let message = self.exchange(name, r#type)?;
+        let now = self.now();
+        if message.rcode == Rcode::NameError {
+            if let Some(ttl) = negative_ttl(&message) {
+                self.cache.insert(name, r#type, Cached::NameNotFound, ttl, now);
+            }
+            return Err(Error::NameNotFound.into());
+        }
+
+        let (records, chain_ttl) = records(&message, name, r#type);
+        if records.is_empty() {
+            if let Some(ttl) = negative_ttl(&message) {
+                self.cache.insert(name, r#type, Cached::NoData, ttl, now);
+            }
+        } else {
+            let ttl = records
+                .iter()
+                .map(|record| record.ttl)
+                .min()
+                .unwrap_or(0)
+                .min(chain_ttl);
+            self.cache
+                .insert(name, r#type, Cached::Records(records.clone()), ttl, now);
+        }
+        Ok(records)
+    }
+
+    /// Resolve the IPv4 addresses of `name`.
+    pub fn lookup_ipv4(&mut self, name: &str) -> Result<Vec<Ipv4Addr>> {
+        let records = self.query(name, RecordType::A)?;
+        Ok(records
+            .into_iter()
+            .filter_map(|record| match record.data {
+                RecordData::A(addr) => Some(addr),
+                _ => None,
+            })
+            .collect())
+    }
+
+    /// Send the query to the servers until one of them answers it, returns the answer,
+    /// which may state that the name does not exist.
+    pub(crate) fn exchange(&mut self, name: &str, r#type: RecordType) -> Result<Message> {
+        check_name(name)?;
+        if self.servers.is_empty() {
+            return Err(Error::NoServers.into());
+        }
+
+        let question = Question {
+            name: name.trim_end_matches('.').to_string(),
+            r#type,
+            class: CLASS_IN,
+        };
+        let mut socket = UdpSocket::bind(&self.interface, Ipv4Addr::UNSPECIFIED, 0)?;
+        let mut last_error: Option<RadishError> = None;
+
+        for _ in 0..self.attempts {
+            for server in self.servers.clone() {
+                let id = self.next_id();
+                let query = PacketBuilder::default().id(id).question(question.clone()).build_vec();
+                socket.send_to(&query, server)?;
+
+                let deadline = self.now() + self.timeout;
+                let answered = match self.wait_answer(&mut socket, server, id, &question, deadline) {
+                    Ok(message) if message.is_truncated => {
+                        debug!("truncated answer from name server {}, retrying over tcp", server);
+                        self.exchange_tcp(server, &question, deadline)
+                    }
+                    answered => answered,
+                };
+
+                match answered {
+                    Ok(message) => match message.rcode {
+                        Rcode::NoError | Rcode::NameError => return Ok(message),
+                        rcode => {
+                            debug!("name server {} answered {:?}", server, rcode);
+                            last_error = Some(Error::ErrorResponse(rcode).into());
+                        }
+                    },
+                    Err(e) => {
+                        debug!("no answer from name server {}: {}", server, e);
+                        last_error = Some(e);
+                    }
+                }
+            }
+        }
+
+        Err(last_error.unwrap_or_else(|| IOError::from(ErrorKind::TimedOut).into()))
+    }
+
+    /// Send the query over a TCP connection to `server`, each message prefixed with its length
+    /// (RFC 1035 section 4.2.2). The exchange fails with `TimedOut` once `deadline` has passed.
+    fn exchange_tcp(&mut self, server: SocketAddrV4, question: &Question, deadline: Instant) -> Result<Message> {
+        let id = self.next_id();
+        let query = PacketBuilder::default().id(id).question(question.clone()).build_vec();
+        let mut framed = (query.len() as u16).to_be_bytes().to_vec();
+        framed.extend_from_slice(&query);
+
+        let mut stream = TcpStream::connect_timeout(&self.interface, server, self.remaining(deadline)?)?;
+        stream.set_write_timeout(Some(self.remaining(deadline)?));
+        stream.write_all(&framed)?;
+
+        let mut len = [0; 2];
+        stream.set_read_timeout(Some(self.remaining(deadline)?));
+        stream.read_exact(&mut len)?;
+        let mut response = vec![0; u16::from_be_bytes(len) as usize];
+        stream.set_read_timeout(Some(self.remaining(deadline)?));
+        stream.read_exact(&mut response)?;
+
+        answer(&response, id, question).ok_or_else(|| Error::UnexpectedResponse.into())
+    }
+
+    /// Receive datagrams until the answer to the query `id` arrives from `server`, or `deadline` passes
+    /// on the clock of the interface.
+    fn wait_answer(
+        &self,
+        socket: &mut UdpSocket<D>,
+        server: SocketAddrV4,
+        id: u16,
+        question: &Question,
+        deadline: Instant,
+    ) -> Result<Message> {
+        let mut buf = [0; MAX_UDP_LEN];
+
+        loop {
+            socket.set_read_timeout(Some(self.remaining(deadline)?));
+
+            let (len, sender) = socket.recv_from(&mut buf)?;
+            if sender != server.into() {
+                continue;
+            }
+            if let Some(message) = answer(&buf[..len], id, question) {
+                return Ok(message);
+            }
+        }
+    }
+}
+
+/// Parse `response`, returns it if it answers the query `id` about `question`.
+fn answer(response: &[u8], id: u16, question: &Question) -> Option<Message> {
+    let message = match Message::parse(response) {
+        Ok(message) => message,
+        Err(e) => {
+            debug!("dns response dropped: {}", e);
+            return None;
+        }
+    };
+
+    let matches = message.is_response
+        && message.id == id
+        && message.questions.len() == 1
+        && message.questions[0].r#type == question.r#type
+        && message.questions[0].class == question.class
+        && message.questions[0].name.eq_ignore_ascii_case(&question.name);
+    if matches {
+        Some(message)
+    } else {
+        None
+    }
+}
+
+/// Returns the answers of `r#type` for `name`, following the CNAME records of the answer,
+/// and the smallest TTL of the CNAME records followed.
+fn records(message: &Message, name: &str, r#type: RecordType) -> (Vec<Record>, u32) {
+    let mut name = name.trim_end_matches('.').to_string();
+    let mut chain_ttl = u32::MAX;
+
+    for _ in 0..=consts::MAX_CNAME_CHAIN {
+        let owned: Vec<&Record> = message
+            .answers
+            .iter()
+            .filter(|record| record.name.eq_ignore_ascii_case(&name))
+            .collect();
+
+        let found: Vec<Record> = owned
+            .iter()
+            .filter(|record| record.r#type == r#type)
+            .map(|record| (*record).clone())
+            .collect();
+        if !found.is_empty() {
+            return (found, chain_ttl);
+        }
+
+        match owned.iter().find_map(|record| match &record.data {
+            RecordData::Cname(target) => Some((target.clone(), record.ttl)),
+            _ => None,
+        }) {
+            Some((target, ttl)) => {
+                name = target;
+                chain_ttl = chain_ttl.min(ttl);
+            }
+            None => break,
+        }
+    }
+
+    (vec![], chain_ttl)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{ErrorKind, Read, Write};
+    use std::net::{Ipv4Addr, SocketAddrV4};
+    use std::sync::{mpsc, Arc, Mutex};
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    use super::{answer, records, Resolver};
+    use crate::clock::{Clock, MockClock};
+    use crate::dns::builder::PacketBuilder;
+    use crate::dns::packet::consts::CLASS_IN;
+    use crate::dns::packet::{Message, Question, Record, RecordData, RecordType};
+    use crate::ipv4::interface::Interface;
+    use crate::ipv4::reassembly::Reassembler;
+    use crate::net_device::channel::ChannelDevice;
+    use crate::tcp::listener::TcpListener;
+    use crate::udp::udp_socket::UdpSocket;
+
+    const CLIENT_ADDR: Ipv4Addr = Ipv4Addr::new(10, 0, 0, 1);
+    const SERVER_ADDR: Ipv4Addr = Ipv4Addr::new(10, 0, 0, 2);
+    const ADDR: Ipv4Addr = Ipv4Addr::new(192, 0, 2, 1);
+
+    fn interface(device: ChannelDevice, addr: Ipv4Addr) -> Arc<Mutex<Interface<ChannelDevice>>> {
+        let mut interface = Interface::new(device, Reassembler::default());
+        interface.set_ip_addr(addr);
+        Arc::new(Mutex::new(interface))
+    }
+
+    fn record(name: &str, r#type: RecordType, data: RecordData) -> Record {
+        Record {
+            name: name.to_string(),
+            r#type,
+            class: CLASS_IN,
+            ttl: 300,
+            data,
+        }
+    }
+
+    #[test]
+    fn answer_matching() {
+        let question = Question {
+            name: "www.example.com".to_string(),
+            r#type: RecordType::A,
+            class: CLASS_IN,
+        };
+        let response = PacketBuilder::default()
+            .id(7)
+            .response(true)
+            .question(Question {
+                name: "WWW.Example.com".to_string(),
+                ..question.clone()
+            })
+            .answer(record(
+                "www.example.com",
+                RecordType::Cname,
+                RecordData::Cname("web.example.com".to_string()),
+            ))
+            .answer(record(
+                "web.example.com",
+                RecordType::Cname,
+                RecordData::Cname("example.com".to_string()),
+            ))
+            .answer(record(
+                "example.com",
+                RecordType::A,
+                RecordData::A(Ipv4Addr::new(192, 0, 2, 1)),
+            ))
+            .answer(record(
+                "example.com",
+                RecordType::A,
+                RecordData::A(Ipv4Addr::new(192, 0, 2, 2)),
+            ))
+            .build_vec();
+
+        // Answers to another query, or queries, are ignored.
+        assert!(answer(&response, 8, &question).is_none());
+        assert!(answer(&response[..response.len() - 1], 7, &question).is_none());
+        let query = PacketBuilder::default().id(7).question(question.clone()).build_vec();
+        assert!(answer(&query, 7, &question).is_none());
+        let other = Question {
+            r#type: RecordType::Aaaa,
+            ..question.clone()
+        };
+        assert!(answer(&response, 7, &other).is_none());
+
+        let message = answer(&response, 7, &question).unwrap();
+        let (found, chain_ttl) = records(&message, "www.example.com.", RecordType::A);
+        assert_eq!(found.len(), 2);
+        assert_eq!(found[1].data, RecordData::A(Ipv4Addr::new(192, 0, 2, 2)));
+        assert_eq!(chain_ttl, 300);
+        assert_eq!(records(&message, "www.example.com", RecordType::Cname).0.len(), 1);
+        assert!(records(&message, "www.example.com", RecordType::Mx).0.is_empty());
+    }
+
+    #[test]
+    fn truncated() {
+        let (client_device, server_device) = ChannelDevice::pair();
+        let client = interface(client_device, CLIENT_ADDR);
+        let server = interface(server_device, SERVER_ADDR);
+        // Far from the system time, so entries cached at the system time would have expired.
+        let clock = MockClock::new();
+        clock.advance(Duration::from_secs(3600));
+        client.lock().unwrap().set_clock(Arc::new(clock.clone()));
+
+        let socket = UdpSocket::bind(&server, SERVER_ADDR, 53).unwrap();
+        let listener = TcpListener::bind(&server, 53).unwrap();
+        let name_server = thread::spawn(move || {
+            let mut buf = [0; 512];
+            let (len, remote) = socket.recv_from(&mut buf).unwrap();
+            let query = Message::parse(&buf[..len]).unwrap();
+            let truncated = PacketBuilder::default()
+                .id(query.id)
+                .response(true)
+                .truncated(true)
+                .question(query.questions[0].clone())
+                .build_vec();
+            socket.send_to(&truncated, remote).unwrap();
+
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut len = [0; 2];
+            stream.read_exact(&mut len).unwrap();
+            let mut query = vec![0; u16::from_be_bytes(len) as usize];
+            stream.read_exact(&mut query).unwrap();
+            let query = Message::parse(&query).unwrap();
+            let response = PacketBuilder::default()
+                .id(query.id)
+                .response(true)
+                .question(query.questions[0].clone())
+                .answer(record("example.com", RecordType::A, RecordData::A(ADDR)))
+                .build_vec();
+            let mut framed = (response.len() as u16).to_be_bytes().to_vec();
+            framed.extend_from_slice(&response);
+            stream.write_all(&framed).unwrap();
+        });
+
+        let mut resolver = Resolver::new(&client, vec![SocketAddrV4::new(SERVER_ADDR, 53)]);
+        assert_eq!(resolver.lookup_ipv4("example.com").unwrap(), vec![ADDR]);
+        name_server.join().unwrap();
+
+        // The answer is cached on the clock of the interface.
+        let cache = resolver.cache_mut();
+        assert!(cache.get("example.com", RecordType::A, clock.now()).is_some());
+        clock.advance(Duration::from_secs(301));
+        assert!(cache.get("example.com", RecordType::A, clock.now()).is_none());
+    }
+
+    #[test]
+    fn tcp_timeout() {
+        let (client_device, server_device) = ChannelDevice::pair();
+        let client = interface(client_device, CLIENT_ADDR);
+        let server = interface(server_device, SERVER_ADDR);
+
+        let socket = UdpSocket::bind(&server, SERVER_ADDR, 53).unwrap();
+        let listener = TcpListener::bind(&server, 53).unwrap();
+        let (done, wait_done) = mpsc::channel::<()>();
+        let name_server = thread::spawn(move || {
+            let mut buf = [0; 512];
+            let (len, remote) = socket.recv_from(&mut buf).unwrap();
+            let query = Message::parse(&buf[..len]).unwrap();
+            let truncated = PacketBuilder::default()
+                .id(query.id)
+                .response(true)
+                .truncated(true)
+                .question(query.questions[0].clone())
+                .build_vec();
+            socket.send_to(&truncated, remote).unwrap();
+
+            // The connection is accepted, but the query is never answered.
+            let (_stream, _) = listener.accept().unwrap();
+            wait_done.recv().unwrap();
+        });
+
+        let mut resolver = Resolver::new(&client, vec![SocketAddrV4::new(SERVER_ADDR, 53)]);
+        resolver.set_attempts(1);
+        resolver.set_timeout(Duration::from_millis(300));
+        let started = Instant::now();
+        let e = resolver.lookup_ipv4("example.com").unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::TimedOut);
+        assert!(started.elapsed() < Duration::from_secs(5));
+
+        done.send(()).unwrap();
+        name_server.join().unwrap();
+    }
+}
@@ -1 +1,210 @@
-pub type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+use core::fmt::{Display, Formatter};
+#[cfg(feature = "std")]
+use std::io::{Error as IOError, ErrorKind};
+
+use crate::{arp, compose, dhcp, dissect, dns, ethernet, icmpv4, icmpv6, ipv4, ipv6, lldp, mld, ndp, tcp, tftp, udp};
+#[cfg(feature = "std")]
+use crate::{net_device, pcap};
+
+pub type Result<T> = core::result::Result<T, Error>;
+
+macro_rules! error_enum {
+    ($($(#[$meta:meta])* $variant:ident($error:ty),)+) => {
+        /// An error of radish, telling which module failed so it can be matched on,
+        /// such as `Error::Ipv4(ipv4::error::Error::TryAgainLater)`.
+        #[derive(Debug)]
+        pub enum Error {
+            $($(#[$meta])* $variant($error),)+
+            /// An interface or device name containing a nul octet, which the system calls cannot take.
+            InvalidName,
+        }
+
+        $(
+            $(#[$meta])*
+            impl From<$error> for Error {
+                fn from(e: $error) -> Self {
+                    Error::$variant(e)
+                }
+            }
+        )+
+
+        impl Display for Error {
+            fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+                match self {
+                    $($(#[$meta])* Error::$variant(e) => write!(f, "{}", e),)+
+                    Error::InvalidName => write!(f, "invalid name"),
+                }
+            }
+        }
+
+        impl core::error::Error for Error {
+            fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+                match self {
+                    $($(#[$meta])* Error::$variant(e) => Some(e),)+
+                    Error::InvalidName => None,
+                }
+            }
+        }
+    };
+}
+
+error_enum! {
+    Arp(arp::error::Error),
+    Compose(compose::error::Error),
+    Dhcp(dhcp::error::Error),
+    Dissect(dissect::error::Error),
+    Dns(dns::error::Error),
+    Ethernet(ethernet::error::Error),
+    Icmpv4(icmpv4::error::Error),
+    Icmpv6(icmpv6::error::Error),
+    Ipv4(ipv4::error::Error),
+    Ipv6(ipv6::error::Error),
+    Lldp(lldp::error::Error),
+    Mld(mld::error::Error),
+    Ndp(ndp::error::Error),
+    #[cfg(feature = "std")]
+    NetDevice(net_device::error::Error),
+    #[cfg(feature = "std")]
+    Pcap(pcap::error::Error),
+    Tcp(tcp::error::Error),
+    Tftp(tftp::error::Error),
+    Udp(udp::error::Error),
+    #[cfg(feature = "std")]
+    Io(IOError),
+}
+
+#[cfg(feature = "std")]
+impl Error {
+    /// The kind of I/O error it stands for, such as `WouldBlock` for a datagram waiting for its other fragments.
+    /// An I/O error keeps its kind, malformed packets are `InvalidData` and bad arguments `InvalidInput`.
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Error::Io(e) => e.kind(),
+            Error::Ipv4(ipv4::error::Error::TryAgainLater) => ErrorKind::WouldBlock,
+            Error::Tcp(tcp::error::Error::AddressInUse) | Error::Udp(udp::error::Error::AddressInUse) => {
+                ErrorKind::AddrInUse
+            }
+            Error::Tcp(tcp::error::Error::InvalidAddress) | Error::Udp(udp::error::Error::InvalidAddress) => {
+                ErrorKind::AddrNotAvailable
+            }
+            Error::Tcp(tcp::error::Error::UnsupportedOption) | Error::Udp(udp::error::Error::UnsupportedOption) => {
+                ErrorKind::Unsupported
+            }
+            Error::Udp(udp::error::Error::PortUnreachable) => ErrorKind::ConnectionRefused,
+            Error::Udp(udp::error::Error::BroadcastDenied) => ErrorKind::PermissionDenied,
+            Error::Udp(udp::error::Error::QueueFull) => ErrorKind::Other,
+            Error::Udp(
+                udp::error::Error::InvalidPort | udp::error::Error::NotMember | udp::error::Error::MessageTooLong,
+            ) => ErrorKind::InvalidInput,
+            Error::Dns(dns::error::Error::NameNotFound) => ErrorKind::NotFound,
+            Error::Dns(dns::error::Error::NoServers) => ErrorKind::InvalidInput,
+            Error::Ipv4(ipv4::error::Error::NonFragmentablePacket) => ErrorKind::InvalidInput,
+            Error::NetDevice(_) | Error::InvalidName => ErrorKind::InvalidInput,
+            _ => ErrorKind::InvalidData,
+        }
+    }
+}
+
+/// An I/O error is given back as is, others are wrapped in one of their kind,
+/// so the socket types can implement the I/O traits of std.
+#[cfg(feature = "std")]
+impl From<Error> for IOError {
+    fn from(e: Error) -> Self {
+        match e {
+            Error::Io(e) => e,
+            e => IOError::new(e.kind(), e),
+        }
+    }
+}
+
+impl From<alloc::ffi::NulError> for Error {
+    fn from(_: alloc::ffi::NulError) -> Self {
+        Error::InvalidName
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use std::io::{Error as IOError, ErrorKind};
+
+    use super::Error;
+    use crate::dns::error::Error as DnsError;
+    use crate::ipv4::error::Error as Ipv4Error;
+    use crate::net_device::error::Error as NetDeviceError;
+    use crate::tcp::error::Error as TcpError;
+    use crate::udp::error::Error as UdpError;
+
+    #[test]
+    fn kind() {
+        let errors = [
+            (Error::from(Ipv4Error::TryAgainLater), ErrorKind::WouldBlock),
+            (Error::from(TcpError::AddressInUse), ErrorKind::AddrInUse),
+            (Error::from(UdpError::AddressInUse), ErrorKind::AddrInUse),
+            (Error::from(TcpError::InvalidAddress), ErrorKind::AddrNotAvailable),
+            (Error::from(UdpError::InvalidAddress), ErrorKind::AddrNotAvailable),
+            (Error::from(TcpError::UnsupportedOption), ErrorKind::Unsupported),
+            (Error::from(UdpError::UnsupportedOption), ErrorKind::Unsupported),
+            (Error::from(UdpError::PortUnreachable), ErrorKind::ConnectionRefused),
+            (Error::from(UdpError::BroadcastDenied), ErrorKind::PermissionDenied),
+            (Error::from(UdpError::QueueFull), ErrorKind::Other),
+            (Error::from(UdpError::InvalidPort), ErrorKind::InvalidInput),
+            (Error::from(UdpError::NotMember), ErrorKind::InvalidInput),
+            (Error::from(UdpError::MessageTooLong), ErrorKind::InvalidInput),
+            (Error::from(DnsError::NameNotFound), ErrorKind::NotFound),
+            (Error::from(DnsError::NoServers), ErrorKind::InvalidInput),
+            (Error::from(Ipv4Error::NonFragmentablePacket), ErrorKind::InvalidInput),
+            (Error::from(NetDeviceError::InvalidMtu), ErrorKind::InvalidInput),
+            (Error::InvalidName, ErrorKind::InvalidInput),
+            (Error::from(Ipv4Error::InvalidChecksum), ErrorKind::InvalidData),
+            (Error::from(DnsError::InvalidLength), ErrorKind::InvalidData),
+        ];
+        for (e, kind) in errors {
+            assert_eq!(e.kind(), kind, "{:?}", e);
+
+            // The error wrapped in an I/O one keeps its kind and can be matched on again.
+            let message = e.to_string();
+            let e = IOError::from(e);
+            assert_eq!(e.kind(), kind);
+            assert_eq!(e.to_string(), message);
+            assert!(e.get_ref().is_some_and(|inner| inner.is::<Error>()));
+        }
+    }
+
+    #[test]
+    fn from_io_error() {
+        for kind in [
+            ErrorKind::WouldBlock,
+            ErrorKind::AddrInUse,
+            ErrorKind::AddrNotAvailable,
+            ErrorKind::Unsupported,
+            ErrorKind::TimedOut,
+        ] {
+            let e = Error::from(IOError::from(kind));
+            assert!(matches!(e, Error::Io(_)));
+            assert_eq!(e.kind(), kind);
+            assert_eq!(IOError::from(e).kind(), kind);
+        }
+
+        let e: IOError = Error::from(IOError::new(ErrorKind::WouldBlock, "no packet")).into();
+        assert_eq!(e.kind(), ErrorKind::WouldBlock);
+        assert_eq!(e.to_string(), "no packet");
+        assert!(e.get_ref().is_some_and(|inner| !inner.is::<Error>()));
+    }
+
+    #[test]
+    fn into_io_error() {
+        let e: IOError = Error::from(Ipv4Error::TryAgainLater).into();
+        assert_eq!(e.kind(), ErrorKind::WouldBlock);
+
+        let e: IOError = Error::from(UdpError::PortUnreachable).into();
+        assert_eq!(e.kind(), ErrorKind::ConnectionRefused);
+        assert_eq!(e.to_string(), "port unreachable");
+
+        // A device error comes back as it was raised.
+        let e: IOError = Error::from(IOError::from_raw_os_error(libc::EIO)).into();
+        assert_eq!(e.raw_os_error(), Some(libc::EIO));
+
+        let e: IOError = Error::from(Ipv4Error::InvalidChecksum).into();
+        assert_eq!(e.kind(), ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,95 @@
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::ethernet::frame::{consts, EtherType, Frame, MacAddr};
+use crate::ethernet::vlan::Tag;
+
+pub struct FrameBuilder {
+    dest_addr: MacAddr,
+    src_addr: MacAddr,
+    ether_type: EtherType,
+    vlan_tags: Vec<Tag>,
+    payload: Vec<u8>,
+}
+
+impl FrameBuilder {
+    pub fn dest_addr(mut self, dest_addr: MacAddr) -> Self {
+        self.dest_addr = dest_addr;
+        self
+    }
+
+    pub fn src_addr(mut self, src_addr: MacAddr) -> Self {
+        self.src_addr = src_addr;
+        self
+    }
+
+    pub fn ether_type(mut self, ether_type: EtherType) -> Self {
+        self.ether_type = ether_type;
+        self
+    }
+
+    /// Add a VLAN tag inside the ones added before, so an S-tag is added before the C-tag.
+    pub fn vlan_tag(mut self, tag: Tag) -> Self {
+        self.vlan_tags.push(tag);
+        self
+    }
+
+    pub fn payload(mut self, payload: Vec<u8>) -> Self {
+        self.payload = payload;
+        self
+    }
+
+    /// Build the frame, without padding: a TAP device or raw socket pads short frames when sending them.
+    pub fn build_vec(mut self) -> Vec<u8> {
+        let mut buffer: Vec<u8> = vec![0; 2 * consts::ADDR_LEN];
+        for tag in self.vlan_tags.iter() {
+            buffer.extend_from_slice(&tag.to_bytes());
+        }
+        buffer.extend_from_slice(&u16::from(self.ether_type).to_be_bytes());
+        buffer.append(&mut self.payload);
+
+        let mut frame = Frame::new_unchecked(buffer.as_mut_slice());
+        frame.set_dest_addr(self.dest_addr);
+        frame.set_src_addr(self.src_addr);
+
+        buffer
+    }
+
+    pub fn build(self) -> Frame<Vec<u8>> {
+        Frame::new_unchecked(self.build_vec())
+    }
+}
+
+impl Default for FrameBuilder {
+    fn default() -> Self {
+        Self {
+            dest_addr: MacAddr::BROADCAST,
+            src_addr: MacAddr::UNSPECIFIED,
+            ether_type: EtherType::Ipv4,
+            vlan_tags: vec![],
+            payload: vec![],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FrameBuilder;
+    use crate::ethernet::frame::{consts, EtherType, MacAddr};
+
+    #[test]
+    fn build() {
+        let frame = FrameBuilder::default()
+            .dest_addr(MacAddr([0x02, 0, 0, 0, 0, 0x02]))
+            .src_addr(MacAddr([0x02, 0, 0, 0, 0, 0x01]))
+            .ether_type(EtherType::Arp)
+            .payload(vec![1, 2, 3])
+            .build();
+
+        assert_eq!(frame.as_ref().len(), consts::HEADER_LEN + 3);
+        assert_eq!(frame.dest_addr(), MacAddr([0x02, 0, 0, 0, 0, 0x02]));
+        assert_eq!(frame.src_addr(), MacAddr([0x02, 0, 0, 0, 0, 0x01]));
+        assert_eq!(frame.ether_type(), EtherType::Arp);
+        assert_eq!(frame.payload(), &[1, 2, 3]);
+    }
+}
@@ -0,0 +1,22 @@
+use core::fmt::{Display, Formatter};
+
+#[derive(Debug)]
+pub enum Error {
+    InvalidLength,
+    InvalidAddress,
+    InvalidVlanTag,
+    InvalidFcs,
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Error::InvalidLength => write!(f, "invalid length"),
+            Error::InvalidAddress => write!(f, "invalid address"),
+            Error::InvalidVlanTag => write!(f, "invalid vlan tag"),
+            Error::InvalidFcs => write!(f, "invalid frame check sequence"),
+        }
+    }
+}
+
+impl core::error::Error for Error {}
@@ -0,0 +1,94 @@
+use alloc::vec::Vec;
+
+use crate::error::Result;
+use crate::ethernet::error::Error;
+use crate::ethernet::frame::consts as frame_consts;
+
+pub mod consts {
+    pub const FCS_LEN: usize = 4;
+    pub const POLYNOMIAL: u32 = 0xedb8_8320; // IEEE 802.3 CRC-32, bit reversed
+    pub const RESIDUE: u32 = 0x2144_df1c; // The CRC of a frame followed by its valid FCS
+}
+
+/// The CRC of every octet value, so the CRC is computed an octet at a time.
+const TABLE: [u32; 256] = table();
+
+const fn table() -> [u32; 256] {
+    let mut table = [0; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ consts::POLYNOMIAL
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+}
+
+/// Compute the CRC-32 of IEEE 802.3 section 3.2.9, which is the frame check sequence of `data`.
+pub fn crc32(data: &[u8]) -> u32 {
+    !data
+        .iter()
+        .fold(!0, |crc, octet| (crc >> 8) ^ TABLE[((crc as u8) ^ octet) as usize])
+}
+
+/// Append the frame check sequence to the frame in `buffer`, least significant octet first.
+pub fn append_fcs(buffer: &mut Vec<u8>) {
+    let fcs = crc32(buffer);
+    buffer.extend_from_slice(&fcs.to_le_bytes());
+}
+
+/// Whether the frame in `buffer` ends with a valid frame check sequence.
+pub fn verify_fcs(buffer: &[u8]) -> bool {
+    buffer.len() >= frame_consts::HEADER_LEN + consts::FCS_LEN && crc32(buffer) == consts::RESIDUE
+}
+
+/// Verify and remove the frame check sequence, such as the one of frames captured with it.
+pub fn strip_fcs(buffer: &mut Vec<u8>) -> Result<()> {
+    if buffer.len() < frame_consts::HEADER_LEN + consts::FCS_LEN {
+        return Err(Error::InvalidLength.into());
+    }
+    if !verify_fcs(buffer) {
+        return Err(Error::InvalidFcs.into());
+    }
+    buffer.truncate(buffer.len() - consts::FCS_LEN);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{append_fcs, crc32, strip_fcs, verify_fcs};
+    use crate::ethernet::builder::FrameBuilder;
+    use crate::ethernet::frame::{EtherType, MacAddr};
+
+    #[test]
+    fn fcs() {
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+
+        let frame = FrameBuilder::default()
+            .src_addr(MacAddr([0x02, 0, 0, 0, 0, 0x01]))
+            .ether_type(EtherType::Arp)
+            .payload(vec![0; 46])
+            .build_vec();
+        let mut bytes = frame.clone();
+        append_fcs(&mut bytes);
+        assert_eq!(bytes.len(), frame.len() + 4);
+        assert!(verify_fcs(&bytes));
+
+        bytes[20] ^= 0x01;
+        assert!(!verify_fcs(&bytes));
+        assert!(strip_fcs(&mut bytes).is_err());
+        bytes[20] ^= 0x01;
+        strip_fcs(&mut bytes).unwrap();
+        assert_eq!(bytes, frame);
+        assert!(strip_fcs(&mut vec![0; 17]).is_err());
+    }
+}
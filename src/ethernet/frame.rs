@@ -0,0 +1,303 @@
+use alloc::vec::Vec;
+use core::convert::TryInto;
+use core::fmt::{Debug, Display, Formatter};
+use core::net::{Ipv4Addr, Ipv6Addr};
+use core::str::FromStr;
+
+use crate::c_like_enum;
+use crate::error::Result;
+use crate::ethernet::error::Error;
+use crate::ethernet::vlan::{consts as vlan_consts, Tag};
+
+pub mod consts {
+    pub const HEADER_LEN: usize = 14; // Destination and source addresses and EtherType
+    pub const ADDR_LEN: usize = 6;
+    pub const MIN_PAYLOAD_LEN: usize = 46; // IEEE 802.3, shorter payloads are padded
+    pub const MAX_PAYLOAD_LEN: usize = 1500;
+}
+
+c_like_enum!(
+    /// The protocol of the payload of a frame (IEEE 802 numbers)
+    #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+    pub enum EtherType(u16) {
+        Ipv4 = 0x0800,
+        Arp = 0x0806,
+        Vlan = 0x8100,
+        Ipv6 = 0x86dd,
+        QinQ = 0x88a8, // IEEE 802.1ad
+        Lldp = 0x88cc, // IEEE 802.1AB
+    }
+);
+
+impl EtherType {
+    /// Whether this is the Tag Protocol Identifier of a VLAN tag rather than the protocol of the payload.
+    pub fn is_vlan_tag(&self) -> bool {
+        matches!(self, EtherType::Vlan | EtherType::QinQ)
+    }
+}
+
+/// A 48 bits MAC address.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Default)]
+pub struct MacAddr(pub [u8; consts::ADDR_LEN]);
+
+impl MacAddr {
+    pub const BROADCAST: MacAddr = MacAddr([0xff; consts::ADDR_LEN]);
+    pub const UNSPECIFIED: MacAddr = MacAddr([0; consts::ADDR_LEN]);
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let octets: [u8; consts::ADDR_LEN] = bytes.try_into().map_err(|_| Error::InvalidAddress)?;
+        Ok(MacAddr(octets))
+    }
+
+    /// Returns the address an IPv4 multicast group is sent to (RFC 1112 section 6.4).
+    pub fn from_ipv4_multicast(group: Ipv4Addr) -> Self {
+        let octets = group.octets();
+        MacAddr([0x01, 0x00, 0x5e, octets[1] & 0x7f, octets[2], octets[3]])
+    }
+
+    /// Returns the address an IPv6 multicast group is sent to (RFC 2464 section 7).
+    pub fn from_ipv6_multicast(group: Ipv6Addr) -> Self {
+        let octets = group.octets();
+        MacAddr([0x33, 0x33, octets[12], octets[13], octets[14], octets[15]])
+    }
+
+    pub fn octets(&self) -> [u8; consts::ADDR_LEN] {
+        self.0
+    }
+
+    pub fn is_broadcast(&self) -> bool {
+        *self == Self::BROADCAST
+    }
+
+    /// Whether the group bit is set, broadcast included.
+    pub fn is_multicast(&self) -> bool {
+        self.0[0] & 0x01 != 0
+    }
+
+    pub fn is_unicast(&self) -> bool {
+        !self.is_multicast()
+    }
+
+    /// Whether the address was assigned locally rather than by the manufacturer.
+    pub fn is_local(&self) -> bool {
+        self.0[0] & 0x02 != 0
+    }
+
+    pub fn is_unspecified(&self) -> bool {
+        *self == Self::UNSPECIFIED
+    }
+}
+
+impl Display for MacAddr {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        let [a, b, c, d, e, g] = self.0;
+        write!(f, "{:02x}:{:02x}:{:02x}:{:02x}:{:02x}:{:02x}", a, b, c, d, e, g)
+    }
+}
+
+impl Debug for MacAddr {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        Display::fmt(self, f)
+    }
+}
+
+impl FromStr for MacAddr {
+    type Err = Error;
+
+    /// Parse six hexadecimal octets separated by colons or hyphens.
+    fn from_str(s: &str) -> core::result::Result<Self, Self::Err> {
+        let mut octets = [0; consts::ADDR_LEN];
+        let mut parts = s.split([':', '-']);
+        for octet in octets.iter_mut() {
+            let part = parts.next().ok_or(Error::InvalidAddress)?;
+            if part.is_empty() || part.len() > 2 {
+                return Err(Error::InvalidAddress);
+            }
+            *octet = u8::from_str_radix(part, 16).map_err(|_| Error::InvalidAddress)?;
+        }
+        if parts.next().is_some() {
+            return Err(Error::InvalidAddress);
+        }
+        Ok(MacAddr(octets))
+    }
+}
+
+/// An Ethernet II frame, without its preamble and frame check sequence.
+/// The EtherType of a tagged frame is the one of its outermost VLAN tag.
+pub struct Frame<Buf> {
+    buffer: Buf,
+}
+
+impl<Buf> Frame<Buf>
+where
+    Buf: AsRef<[u8]>,
+{
+    pub fn new_unchecked(buffer: Buf) -> Self {
+        Frame { buffer }
+    }
+
+    pub fn new_checked(buffer: Buf) -> Result<Self> {
+        let frame = Self::new_unchecked(buffer);
+        if frame.buffer.as_ref().len() < consts::HEADER_LEN {
+            return Err(Error::InvalidLength.into());
+        }
+        if frame.buffer.as_ref().len() < consts::HEADER_LEN + frame.tags_len() {
+            return Err(Error::InvalidLength.into());
+        }
+        Ok(frame)
+    }
+
+    /// Returns the length of the VLAN tags, counting a truncated tag as whole.
+    fn tags_len(&self) -> usize {
+        let buffer = self.buffer.as_ref();
+        let mut offset = 2 * consts::ADDR_LEN;
+        while buffer.len() >= offset + 2
+            && EtherType::from(u16::from_be_bytes([buffer[offset], buffer[offset + 1]])).is_vlan_tag()
+        {
+            offset += vlan_consts::TAG_LEN;
+        }
+        offset - 2 * consts::ADDR_LEN
+    }
+
+    pub fn dest_addr(&self) -> MacAddr {
+        MacAddr(self.buffer.as_ref()[0..6].try_into().unwrap())
+    }
+
+    pub fn src_addr(&self) -> MacAddr {
+        MacAddr(self.buffer.as_ref()[6..12].try_into().unwrap())
+    }
+
+    pub fn ether_type(&self) -> EtherType {
+        u16::from_be_bytes([self.buffer.as_ref()[12], self.buffer.as_ref()[13]]).into()
+    }
+
+    /// Returns the payload, including the padding of short frames.
+    pub fn payload(&self) -> &[u8] {
+        &self.buffer.as_ref()[consts::HEADER_LEN..]
+    }
+
+    /// Returns the VLAN tags, outermost first: the S-tag then the C-tag of a QinQ frame.
+    pub fn vlan_tags(&self) -> Vec<Tag> {
+        let tags = &self.buffer.as_ref()[2 * consts::ADDR_LEN..2 * consts::ADDR_LEN + self.tags_len()];
+        tags.chunks_exact(vlan_consts::TAG_LEN)
+            .map(|tag| Tag::parse(tag).unwrap())
+            .collect()
+    }
+
+    /// Returns the EtherType following the VLAN tags, which is the protocol of the payload.
+    pub fn inner_ether_type(&self) -> EtherType {
+        let offset = 2 * consts::ADDR_LEN + self.tags_len();
+        u16::from_be_bytes([self.buffer.as_ref()[offset], self.buffer.as_ref()[offset + 1]]).into()
+    }
+
+    /// Returns the payload following the VLAN tags.
+    pub fn inner_payload(&self) -> &[u8] {
+        &self.buffer.as_ref()[consts::HEADER_LEN + self.tags_len()..]
+    }
+}
+
+impl<Buf> Frame<Buf>
+where
+    Buf: AsRef<[u8]> + AsMut<[u8]>,
+{
+    pub fn set_dest_addr(&mut self, dest_addr: MacAddr) {
+        self.buffer.as_mut()[0..6].copy_from_slice(dest_addr.0.as_ref());
+    }
+
+    pub fn set_src_addr(&mut self, src_addr: MacAddr) {
+        self.buffer.as_mut()[6..12].copy_from_slice(src_addr.0.as_ref());
+    }
+
+    pub fn set_ether_type(&mut self, ether_type: EtherType) {
+        self.buffer.as_mut()[12..=13].copy_from_slice(u16::from(ether_type).to_be_bytes().as_ref());
+    }
+
+    pub fn payload_mut(&mut self) -> &mut [u8] {
+        &mut self.buffer.as_mut()[consts::HEADER_LEN..]
+    }
+
+    pub fn set_payload(&mut self, payload: &[u8]) {
+        self.payload_mut()[..payload.len()].copy_from_slice(payload);
+    }
+}
+
+impl<Buf> Debug for Frame<Buf>
+where
+    Buf: AsRef<[u8]>,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "destination address: {}, source address: {}, ether type: {:?}, payload length: {:?}",
+            self.dest_addr(),
+            self.src_addr(),
+            self.ether_type(),
+            self.payload().len(),
+        )
+    }
+}
+
+impl<Buf> AsRef<[u8]> for Frame<Buf>
+where
+    Buf: AsRef<[u8]>,
+{
+    fn as_ref(&self) -> &[u8] {
+        self.buffer.as_ref()
+    }
+}
+
+impl<Buf> AsMut<[u8]> for Frame<Buf>
+where
+    Buf: AsMut<[u8]>,
+{
+    fn as_mut(&mut self) -> &mut [u8] {
+        self.buffer.as_mut()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use super::{EtherType, Frame, MacAddr};
+
+    #[test]
+    fn new_checked() {
+        let mut bytes: Vec<u8> = vec![
+            // destination and source addresses
+            0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x02, 0x00, 0x00, 0x00, 0x00, 0x01, //
+            // ether type
+            0x08, 0x06, //
+            // payload
+            0x00, 0x01,
+        ];
+
+        let frame = Frame::new_checked(bytes.as_slice()).expect("a valid frame");
+        assert!(frame.dest_addr().is_broadcast());
+        assert_eq!(frame.src_addr().to_string(), "02:00:00:00:00:01");
+        assert_eq!(frame.ether_type(), EtherType::Arp);
+        assert_eq!(frame.payload(), &[0x00, 0x01]);
+        assert!(Frame::new_checked(&bytes[..13]).is_err());
+
+        let mut frame = Frame::new_unchecked(bytes.as_mut_slice());
+        frame.set_dest_addr(MacAddr::from_ipv4_multicast(Ipv4Addr::new(224, 128, 0, 251)));
+        frame.set_ether_type(EtherType::Ipv6);
+        frame.set_payload(&[0xbe, 0xef]);
+        assert_eq!(frame.dest_addr(), MacAddr([0x01, 0x00, 0x5e, 0x00, 0x00, 0xfb]));
+        assert!(frame.dest_addr().is_multicast());
+        assert_eq!(frame.ether_type(), EtherType::Ipv6);
+        assert_eq!(frame.payload(), &[0xbe, 0xef]);
+    }
+
+    #[test]
+    fn mac_addr() {
+        let addr: MacAddr = "02-00-5E-10-00-01".parse().unwrap();
+        assert_eq!(addr, MacAddr([0x02, 0x00, 0x5e, 0x10, 0x00, 0x01]));
+        assert!(addr.is_unicast());
+        assert!(addr.is_local());
+        assert!("02:00:5e:10:00".parse::<MacAddr>().is_err());
+        assert!("02:00:5e:10:00:01:02".parse::<MacAddr>().is_err());
+        assert!("02:00:5e:10:00:0g".parse::<MacAddr>().is_err());
+        assert!(MacAddr::from_bytes(&[0; 5]).is_err());
+    }
+}
@@ -0,0 +1,5 @@
+pub mod builder;
+pub mod error;
+pub mod fcs;
+pub mod frame;
+pub mod vlan;
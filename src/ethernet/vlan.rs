@@ -0,0 +1,141 @@
+use alloc::vec::Vec;
+
+use crate::error::Result;
+use crate::ethernet::error::Error;
+use crate::ethernet::frame::{consts as frame_consts, EtherType};
+
+pub mod consts {
+    pub const TAG_LEN: usize = 4; // TPID and TCI
+    pub const MAX_VID: u16 = 4094; // IEEE 802.1Q, 4095 is reserved
+}
+
+/// A VLAN tag: a C-tag of IEEE 802.1Q, or an S-tag of IEEE 802.1ad which a provider stacks
+/// in front of the C-tag of a customer frame (QinQ).
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Tag {
+    /// The Tag Protocol Identifier, `Vlan` for a C-tag and `QinQ` for an S-tag.
+    pub tpid: EtherType,
+    /// The Priority Code Point.
+    pub pcp: u8,
+    /// The Drop Eligible Indicator.
+    pub dei: bool,
+    /// The VLAN identifier, zero for a priority tag.
+    pub vid: u16,
+}
+
+impl Tag {
+    /// A customer VLAN tag.
+    pub fn customer(vid: u16) -> Self {
+        Self {
+            tpid: EtherType::Vlan,
+            pcp: 0,
+            dei: false,
+            vid,
+        }
+    }
+
+    /// A service VLAN tag, the outer tag of a QinQ frame.
+    pub fn service(vid: u16) -> Self {
+        Self {
+            tpid: EtherType::QinQ,
+            ..Self::customer(vid)
+        }
+    }
+
+    pub fn parse(buffer: &[u8]) -> Result<Self> {
+        if buffer.len() < consts::TAG_LEN {
+            return Err(Error::InvalidLength.into());
+        }
+        let tpid: EtherType = u16::from_be_bytes([buffer[0], buffer[1]]).into();
+        if !tpid.is_vlan_tag() {
+            return Err(Error::InvalidVlanTag.into());
+        }
+        let tci = u16::from_be_bytes([buffer[2], buffer[3]]);
+        Ok(Self {
+            tpid,
+            pcp: (tci >> 13) as u8,
+            dei: tci & 0x1000 != 0,
+            vid: tci & 0x0fff,
+        })
+    }
+
+    pub fn to_bytes(&self) -> [u8; consts::TAG_LEN] {
+        let tci = (self.pcp as u16 & 0x07) << 13 | (self.dei as u16) << 12 | (self.vid & 0x0fff);
+        let [tpid_high, tpid_low] = u16::from(self.tpid).to_be_bytes();
+        let [tci_high, tci_low] = tci.to_be_bytes();
+        [tpid_high, tpid_low, tci_high, tci_low]
+    }
+}
+
+/// Push `tag` onto the frame in `buffer` as its outermost tag, such as an S-tag onto a frame with a C-tag.
+pub fn push_tag(buffer: &mut Vec<u8>, tag: Tag) -> Result<()> {
+    if buffer.len() < frame_consts::HEADER_LEN {
+        return Err(Error::InvalidLength.into());
+    }
+    if !tag.tpid.is_vlan_tag() || tag.vid > consts::MAX_VID {
+        return Err(Error::InvalidVlanTag.into());
+    }
+    let offset = 2 * frame_consts::ADDR_LEN;
+    buffer.splice(offset..offset, tag.to_bytes());
+    Ok(())
+}
+
+/// Pop the outermost tag of the frame in `buffer`, returns none when the frame is untagged.
+pub fn pop_tag(buffer: &mut Vec<u8>) -> Result<Option<Tag>> {
+    if buffer.len() < frame_consts::HEADER_LEN {
+        return Err(Error::InvalidLength.into());
+    }
+    let offset = 2 * frame_consts::ADDR_LEN;
+    if !EtherType::from(u16::from_be_bytes([buffer[offset], buffer[offset + 1]])).is_vlan_tag() {
+        return Ok(None);
+    }
+    if buffer.len() < frame_consts::HEADER_LEN + consts::TAG_LEN {
+        return Err(Error::InvalidLength.into());
+    }
+    let tag = Tag::parse(&buffer[offset..])?;
+    buffer.drain(offset..offset + consts::TAG_LEN);
+    Ok(Some(tag))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{pop_tag, push_tag, Tag};
+    use crate::ethernet::builder::FrameBuilder;
+    use crate::ethernet::frame::{EtherType, Frame};
+
+    #[test]
+    fn stack() {
+        let mut bytes = FrameBuilder::default()
+            .ether_type(EtherType::Ipv6)
+            .vlan_tag(Tag::customer(100))
+            .payload(vec![1, 2, 3])
+            .build_vec();
+        let service = Tag {
+            pcp: 5,
+            dei: true,
+            ..Tag::service(42)
+        };
+        push_tag(&mut bytes, service).unwrap();
+        assert_eq!(&bytes[12..18], &[0x88, 0xa8, 0xb0, 0x2a, 0x81, 0x00]);
+
+        let frame = Frame::new_checked(bytes.as_slice()).unwrap();
+        assert_eq!(frame.ether_type(), EtherType::QinQ);
+        assert_eq!(frame.vlan_tags(), vec![service, Tag::customer(100)]);
+        assert_eq!(frame.inner_ether_type(), EtherType::Ipv6);
+        assert_eq!(frame.inner_payload(), &[1, 2, 3]);
+
+        assert_eq!(pop_tag(&mut bytes).unwrap(), Some(service));
+        assert_eq!(pop_tag(&mut bytes).unwrap(), Some(Tag::customer(100)));
+        assert_eq!(pop_tag(&mut bytes).unwrap(), None);
+        let frame = Frame::new_checked(bytes.as_slice()).unwrap();
+        assert!(frame.vlan_tags().is_empty());
+        assert_eq!(frame.inner_payload(), frame.payload());
+
+        assert!(push_tag(&mut bytes, Tag::customer(4095)).is_err());
+        // A tagged frame too short for the EtherType following the tag.
+        let mut truncated = vec![0; 12];
+        truncated.extend_from_slice(&[0x81, 0x00, 0x00, 0x64]);
+        assert!(Frame::new_checked(truncated.as_slice()).is_err());
+        assert!(pop_tag(&mut truncated).is_err());
+    }
+}
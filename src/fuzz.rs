@@ -0,0 +1,176 @@
+//! Arbitrary packet representations for fuzzing and property testing code consuming radish.
+//!
+//! They are drawn valid, so that emitting and parsing one gives it back, which the round trips check.
+
+use std::net::Ipv6Addr;
+
+use arbitrary::{Arbitrary, Result as ArbitraryResult, Unstructured};
+
+use crate::checksum::IpAddress;
+use crate::error::Result;
+use crate::ipv4::packet::Protocol;
+use crate::ipv6::packet::Packet as Ipv6Packet;
+use crate::ipv6::repr::Repr as Ipv6Repr;
+use crate::tcp::packet::{Packet as TcpPacket, TcpFlags};
+use crate::tcp::repr::{Control, Repr as TcpRepr};
+
+pub mod consts {
+    pub const MAX_PAYLOAD_LEN: usize = 1500; // The payload described by a representation, kept to a frame
+    pub const MAX_TCP_HEADER_LEN: usize = 60; // The data offset counts at most 15 words of 32 bits
+    pub const MAX_FLOW_LABEL: u32 = 0xfffff; // The flow label takes 20 bits
+    pub const MAX_WINDOW_SCALE: u8 = 14; // A larger shift is taken as 14 (RFC 7323 section 2.3)
+}
+
+impl<'a> Arbitrary<'a> for Protocol {
+    fn arbitrary(u: &mut Unstructured<'a>) -> ArbitraryResult<Self> {
+        Ok(Protocol::from(u8::arbitrary(u)?))
+    }
+}
+
+impl<'a> Arbitrary<'a> for Control {
+    fn arbitrary(u: &mut Unstructured<'a>) -> ArbitraryResult<Self> {
+        Ok(*u.choose(&[Control::None, Control::Syn, Control::Fin, Control::Rst])?)
+    }
+}
+
+/// A segment with non-zero ports, whose options fit in the header: SACK ranges are dropped until they do.
+impl<'a> Arbitrary<'a> for TcpRepr {
+    fn arbitrary(u: &mut Unstructured<'a>) -> ArbitraryResult<Self> {
+        let mut repr = TcpRepr {
+            src_port: u.int_in_range(1..=u16::MAX)?,
+            dest_port: u.int_in_range(1..=u16::MAX)?,
+            control: Control::arbitrary(u)?,
+            flags: TcpFlags::from_bits_truncate(u16::arbitrary(u)?)
+                - (TcpFlags::SYN | TcpFlags::FIN | TcpFlags::RST | TcpFlags::ACK),
+            seq_number: u32::arbitrary(u)?,
+            ack_number: Option::arbitrary(u)?,
+            window: u16::arbitrary(u)?,
+            urgent_pointer: u16::arbitrary(u)?,
+            max_seg_size: Option::arbitrary(u)?,
+            window_scale: if bool::arbitrary(u)? {
+                Some(u.int_in_range(0..=consts::MAX_WINDOW_SCALE)?)
+            } else {
+                None
+            },
+            sack_permitted: bool::arbitrary(u)?,
+            sack_ranges: [None; 3],
+            timestamps: Option::arbitrary(u)?,
+            payload_len: u.int_in_range(0..=consts::MAX_PAYLOAD_LEN)?,
+        };
+
+        // Parsing fills the ranges in order, so they are drawn as a prefix.
+        for index in 0..u.int_in_range(0..=repr.sack_ranges.len())? {
+            repr.sack_ranges[index] = Some(<(u32, u32)>::arbitrary(u)?);
+        }
+        while repr.header_len() > consts::MAX_TCP_HEADER_LEN {
+            let last = repr.sack_ranges.iter().rposition(Option::is_some).unwrap();
+            repr.sack_ranges[last] = None;
+        }
+
+        Ok(repr)
+    }
+}
+
+/// A packet from a source address that is not multicast, carrying a payload of at most a frame.
+impl<'a> Arbitrary<'a> for Ipv6Repr {
+    fn arbitrary(u: &mut Unstructured<'a>) -> ArbitraryResult<Self> {
+        let mut src_addr = Ipv6Addr::arbitrary(u)?;
+        if src_addr.is_multicast() {
+            let mut octets = src_addr.octets();
+            octets[0] = 0xfe;
+            src_addr = Ipv6Addr::from(octets);
+        }
+
+        Ok(Ipv6Repr {
+            src_addr,
+            dest_addr: Ipv6Addr::arbitrary(u)?,
+            next_header: Protocol::arbitrary(u)?,
+            payload_len: u.int_in_range(0..=consts::MAX_PAYLOAD_LEN)?,
+            hop_limit: u8::arbitrary(u)?,
+            traffic_class: u8::arbitrary(u)?,
+            flow_label: u.int_in_range(0..=consts::MAX_FLOW_LABEL)?,
+        })
+    }
+}
+
+/// Emit `repr` with a zeroed payload and parse it back, which gives `repr` again if radish is right.
+pub fn tcp_round_trip<A: IpAddress>(repr: &TcpRepr, src_addr: A, dest_addr: A) -> Result<TcpRepr> {
+    let mut buffer = vec![0; repr.buffer_len()];
+    repr.emit(&mut TcpPacket::new_unchecked(&mut buffer[..]), src_addr, dest_addr);
+    TcpRepr::parse(&TcpPacket::new_checked(&buffer[..])?, src_addr, dest_addr)
+}
+
+/// Emit `repr` with a zeroed payload and parse it back, which gives `repr` again if radish is right.
+pub fn ipv6_round_trip(repr: &Ipv6Repr) -> Result<Ipv6Repr> {
+    let mut buffer = vec![0; repr.buffer_len()];
+    repr.emit(&mut Ipv6Packet::new_unchecked(&mut buffer[..]));
+    Ipv6Repr::parse(&Ipv6Packet::new_checked(&buffer[..])?)
+}
+
+/// The arbitrary representations as proptest strategies, drawn from random octets.
+#[cfg(feature = "proptest")]
+pub mod strategy {
+    use std::fmt::Debug;
+
+    use arbitrary::{Arbitrary, Unstructured};
+    use proptest::arbitrary::any;
+    use proptest::collection::vec;
+    use proptest::strategy::Strategy;
+
+    use crate::ipv6::repr::Repr as Ipv6Repr;
+    use crate::tcp::repr::Repr as TcpRepr;
+
+    pub mod consts {
+        pub const MAX_SEED_LEN: usize = 128; // Octets to draw a representation from, enough for every field
+    }
+
+    pub fn from_arbitrary<T>() -> impl Strategy<Value = T>
+    where
+        T: for<'a> Arbitrary<'a> + Debug,
+    {
+        vec(any::<u8>(), 0..consts::MAX_SEED_LEN).prop_filter_map("not enough octets", |seed| {
+            T::arbitrary(&mut Unstructured::new(&seed)).ok()
+        })
+    }
+
+    pub fn tcp_repr() -> impl Strategy<Value = TcpRepr> {
+        from_arbitrary()
+    }
+
+    pub fn ipv6_repr() -> impl Strategy<Value = Ipv6Repr> {
+        from_arbitrary()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use arbitrary::{Arbitrary, Unstructured};
+
+    use super::{ipv6_round_trip, tcp_round_trip};
+    use crate::ipv6::repr::Repr as Ipv6Repr;
+    use crate::tcp::repr::Repr as TcpRepr;
+
+    #[test]
+    fn round_trip() {
+        let src_addr = Ipv4Addr::new(192, 168, 233, 233);
+        let dest_addr = Ipv4Addr::new(192, 168, 233, 234);
+
+        // Seeds from a linear congruential generator, so a failure can be replayed.
+        let mut state = 1u32;
+        let mut seed = [0; 128];
+        for _ in 0..1000 {
+            for octet in seed.iter_mut() {
+                state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                *octet = (state >> 24) as u8;
+            }
+
+            let repr = TcpRepr::arbitrary(&mut Unstructured::new(&seed)).unwrap();
+            assert_eq!(tcp_round_trip(&repr, src_addr, dest_addr).unwrap(), repr);
+
+            let repr = Ipv6Repr::arbitrary(&mut Unstructured::new(&seed)).unwrap();
+            assert_eq!(ipv6_round_trip(&repr).unwrap(), repr);
+        }
+    }
+}
@@ -0,0 +1,444 @@
+//! Lock protocol behavior in against captures: the frames of an input capture are fed one by one
+//! through an interface or a handler, and what it emits is compared with an expected capture.
+//!
+//! The expected capture is usually written once by `Golden::bless` and checked in next to the
+//! input one. Fields that legitimately change between runs, such as the IPv4 identification,
+//! are ignored by the comparison as told by a `Tolerance`.
+
+use std::fmt::{Display, Formatter};
+use std::fs::File;
+use std::io::{BufReader, Read, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::clock::MockClock;
+use crate::dissect::{dissect, dissect_ip};
+use crate::error::Result;
+use crate::ethernet::frame::{consts as ethernet_consts, EtherType};
+use crate::ipv4::handler::Handler;
+use crate::ipv4::interface::Interface;
+use crate::ipv4::packet::{Packet as Ipv4Packet, Protocol};
+use crate::ipv4::reassembly::Reassembler;
+use crate::ipv6::packet::consts as ipv6_consts;
+use crate::net_device::channel::ChannelDevice;
+use crate::net_device::Device;
+use crate::pcap::{LinkType, Reader, Record, Writer};
+
+/// What the frames of a capture are fed through.
+pub trait Target {
+    /// Feed a frame received at `now`, returning the frames emitted in response, in order.
+    fn feed(&mut self, now: Instant, frame: &[u8]) -> Result<Vec<Vec<u8>>>;
+}
+
+/// A handler is fed the frames as they are, its reply being the only frame emitted.
+impl<H: Handler> Target for H {
+    fn feed(&mut self, now: Instant, frame: &[u8]) -> Result<Vec<Vec<u8>>> {
+        Ok(self.process(now, frame).into_iter().collect())
+    }
+}
+
+/// An interface on one end of a channel, fed from the other end, whose clock follows the timestamps
+/// of the capture.
+///
+/// The interface carries IP packets, so the captures replayed through it are of the `Raw` link type,
+/// as the ones taken on a TUN device.
+pub struct InterfaceTarget {
+    interface: Arc<Mutex<Interface<ChannelDevice>>>,
+    peer: ChannelDevice,
+    clock: MockClock,
+}
+
+impl InterfaceTarget {
+    pub fn new() -> Self {
+        let (device, mut peer) = ChannelDevice::pair();
+        peer.set_nonblocking(true);
+
+        let clock = MockClock::new();
+        let mut interface = Interface::new(device, Reassembler::default());
+        interface.set_clock(Arc::new(clock.clone()));
+
+        Self {
+            interface: Arc::new(Mutex::new(interface)),
+            peer,
+            clock,
+        }
+    }
+
+    /// The interface under test, to set its addresses, bind sockets or register handlers on.
+    pub fn interface(&self) -> &Arc<Mutex<Interface<ChannelDevice>>> {
+        &self.interface
+    }
+}
+
+impl Default for InterfaceTarget {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Target for InterfaceTarget {
+    fn feed(&mut self, now: Instant, frame: &[u8]) -> Result<Vec<Vec<u8>>> {
+        self.clock.set(now);
+        self.peer.transmit(frame)?;
+        self.interface.lock().unwrap().poll(now)?;
+
+        let mut frames = vec![];
+        let mut buf = vec![0; self.peer.mtu()];
+        while let Ok(len) = self.peer.receive(&mut buf) {
+            frames.push(buf[..len].to_vec());
+        }
+        Ok(frames)
+    }
+}
+
+/// The fields left out of the comparison of emitted frames with expected ones.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct Tolerance {
+    /// Ignore the identification of IPv4 headers, which depends on what was sent before.
+    pub identification: bool,
+    /// Ignore the IPv4 header checksum and those of TCP, UDP, ICMP and ICMPv6, which follow from the
+    /// fields ignored.
+    pub checksums: bool,
+}
+
+impl Tolerance {
+    /// Ignore every field the stack may legitimately change between runs.
+    pub const LENIENT: Tolerance = Tolerance {
+        identification: true,
+        checksums: true,
+    };
+}
+
+/// A difference between the frames emitted and the expected ones.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Mismatch {
+    /// The frame at `index` of the expected ones was not emitted.
+    Missing { index: usize, expected: Vec<u8> },
+    /// The frame at `index` of the emitted ones was not expected.
+    Unexpected { index: usize, emitted: Vec<u8> },
+    /// The frames at `index` differ, from the octet at `offset`.
+    Different {
+        index: usize,
+        offset: usize,
+        emitted: Vec<u8>,
+        expected: Vec<u8>,
+    },
+}
+
+/// A mismatch with the link type of its frames, to print them layer by layer.
+pub struct MismatchDisplay<'a> {
+    mismatch: &'a Mismatch,
+    link_type: LinkType,
+}
+
+impl Mismatch {
+    pub fn display(&self, link_type: LinkType) -> MismatchDisplay<'_> {
+        MismatchDisplay {
+            mismatch: self,
+            link_type,
+        }
+    }
+}
+
+impl Display for MismatchDisplay<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let layers = |frame: &[u8]| match self.link_type {
+            LinkType::Ethernet => dissect(frame).to_string(),
+            _ => dissect_ip(frame).to_string(),
+        };
+
+        match self.mismatch {
+            Mismatch::Missing { index, expected } => {
+                write!(f, "frame #{} missing, expected:\n{}", index + 1, layers(expected))
+            }
+            Mismatch::Unexpected { index, emitted } => {
+                write!(f, "frame #{} unexpected, emitted:\n{}", index + 1, layers(emitted))
+            }
+            Mismatch::Different {
+                index,
+                offset,
+                emitted,
+                expected,
+            } => write!(
+                f,
+                "frame #{} differs from octet {}, emitted:\n{}\nexpected:\n{}",
+                index + 1,
+                offset,
+                layers(emitted),
+                layers(expected)
+            ),
+        }
+    }
+}
+
+/// An input capture and the frames expected to be emitted when it is replayed.
+pub struct Golden {
+    link_type: LinkType,
+    input: Vec<Record>,
+    expected: Vec<Record>,
+    tolerance: Tolerance,
+}
+
+impl Golden {
+    pub fn new<R: Read, E: Read>(input: Reader<R>, expected: Reader<E>) -> Result<Self> {
+        Ok(Self {
+            link_type: input.link_type(),
+            input: input.collect::<Result<_>>()?,
+            expected: expected.collect::<Result<_>>()?,
+            tolerance: Tolerance::default(),
+        })
+    }
+
+    /// Load the input and expected captures from files, in the pcap format or in pcapng.
+    pub fn open(input: impl AsRef<Path>, expected: impl AsRef<Path>) -> Result<Self> {
+        let input = Reader::new(BufReader::new(File::open(input)?))?;
+        let expected = Reader::new(BufReader::new(File::open(expected)?))?;
+        Self::new(input, expected)
+    }
+
+    pub fn tolerance(mut self, tolerance: Tolerance) -> Self {
+        self.tolerance = tolerance;
+        self
+    }
+
+    /// Feed every input frame through `target`, returning the frames emitted with the timestamp
+    /// of the input frame that caused them.
+    pub fn replay(&self, target: &mut impl Target) -> Result<Vec<Record>> {
+        let start = Instant::now();
+        let first = self.input.first().map_or(Duration::ZERO, |record| record.timestamp);
+
+        let mut emitted = vec![];
+        for record in &self.input {
+            let now = start + record.timestamp.saturating_sub(first);
+            for data in target.feed(now, &record.data)? {
+                emitted.push(Record {
+                    timestamp: record.timestamp,
+                    link_type: self.link_type,
+                    original_len: data.len(),
+                    data,
+                });
+            }
+        }
+        Ok(emitted)
+    }
+
+    /// Replay the input through `target` and write what it emits, to be used as the expected capture
+    /// once reviewed.
+    pub fn bless<W: Write>(&self, target: &mut impl Target, writer: &mut Writer<W>) -> Result<()> {
+        for record in self.replay(target)? {
+            writer.write_record(&record)?;
+        }
+        writer.flush()
+    }
+
+    /// Replay the input through `target`, returning how what it emits differs from the expected frames.
+    pub fn check(&self, target: &mut impl Target) -> Result<Vec<Mismatch>> {
+        let emitted = self.replay(target)?;
+        let mut mismatches = vec![];
+
+        for index in 0..emitted.len().max(self.expected.len()) {
+            match (emitted.get(index), self.expected.get(index)) {
+                (Some(emitted), Some(expected)) => {
+                    let normalized = self.normalize(&emitted.data);
+                    let expected_normalized = self.normalize(&expected.data);
+                    if normalized != expected_normalized {
+                        let offset = normalized
+                            .iter()
+                            .zip(expected_normalized.iter())
+                            .position(|(a, b)| a != b)
+                            .unwrap_or(normalized.len().min(expected_normalized.len()));
+                        mismatches.push(Mismatch::Different {
+                            index,
+                            offset,
+                            emitted: emitted.data.clone(),
+                            expected: expected.data.clone(),
+                        });
+                    }
+                }
+                (Some(emitted), None) => mismatches.push(Mismatch::Unexpected {
+                    index,
+                    emitted: emitted.data.clone(),
+                }),
+                (None, Some(expected)) => mismatches.push(Mismatch::Missing {
+                    index,
+                    expected: expected.data.clone(),
+                }),
+                (None, None) => unreachable!(),
+            }
+        }
+        Ok(mismatches)
+    }
+
+    /// Replay the input through `target` and panic, printing every mismatch, unless it emits the
+    /// expected frames.
+    pub fn assert(&self, target: &mut impl Target) {
+        let mismatches = self.check(target).expect("replay the input capture");
+        if !mismatches.is_empty() {
+            let report: Vec<String> = mismatches
+                .iter()
+                .map(|mismatch| mismatch.display(self.link_type).to_string())
+                .collect();
+            panic!(
+                "{} mismatches with the expected capture:\n\n{}",
+                mismatches.len(),
+                report.join("\n\n")
+            );
+        }
+    }
+
+    /// Returns the frame with the fields ignored by the tolerance zeroed.
+    fn normalize(&self, frame: &[u8]) -> Vec<u8> {
+        let mut frame = frame.to_vec();
+        let offset = match self.link_type {
+            LinkType::Ethernet => {
+                let mut offset = 2 * ethernet_consts::ADDR_LEN;
+                // Skip the VLAN tags, 4 octets each, to the EtherType of the payload.
+                while let Some(ether_type) = frame.get(offset..offset + 2) {
+                    let ether_type = EtherType::from(u16::from_be_bytes([ether_type[0], ether_type[1]]));
+                    if !ether_type.is_vlan_tag() {
+                        break;
+                    }
+                    offset += 4;
+                }
+                offset + 2
+            }
+            LinkType::Raw | LinkType::Ipv4 | LinkType::Ipv6 => 0,
+            _ => return frame,
+        };
+
+        if let Some(packet) = frame.get_mut(offset..) {
+            self.normalize_ip(packet);
+        }
+        frame
+    }
+
+    fn normalize_ip(&self, packet: &mut [u8]) {
+        let (protocol, upper) = match packet.first().map(|octet| octet >> 4) {
+            Some(4) => {
+                let Ok(mut datagram) = Ipv4Packet::new_checked(&mut *packet) else {
+                    return;
+                };
+                if self.tolerance.identification {
+                    datagram.set_identification(0);
+                }
+                if self.tolerance.checksums {
+                    datagram.set_checksum(0);
+                }
+                if datagram.offset() != 0 {
+                    return; // Only the first fragment carries the upper-layer header.
+                }
+                let header_len = datagram.header_len() as usize * 4;
+                (datagram.protocol(), header_len)
+            }
+            Some(ipv6_consts::VERSION) if packet.len() >= ipv6_consts::HEADER_LEN => {
+                (Protocol::from(packet[6]), ipv6_consts::HEADER_LEN)
+            }
+            _ => return,
+        };
+
+        // The offset of the checksum in the upper-layer header.
+        let offset = match protocol {
+            Protocol::Tcp => 16,
+            Protocol::Udp => 6,
+            Protocol::Icmp | Protocol::Icmpv6 => 2,
+            _ => return,
+        };
+        if self.tolerance.checksums {
+            if let Some(checksum) = packet.get_mut(upper + offset..upper + offset + 2) {
+                checksum.fill(0);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+    use std::time::Duration;
+
+    use super::{Golden, InterfaceTarget, Mismatch, Tolerance};
+    use crate::compose::{Ipv4Layer, UdpLayer};
+    use crate::icmpv4::builder::ErrorBuilder;
+    use crate::icmpv4::packet::{DestinationUnreachablePacketCode, ErrorMessage};
+    use crate::ipv4::packet::Protocol;
+    use crate::pcap::consts::DEFAULT_SNAPLEN;
+    use crate::pcap::{Format, LinkType, Reader, Writer};
+
+    fn capture(frames: &[&[u8]]) -> Vec<u8> {
+        let mut writer = Writer::new(vec![], Format::Pcap, LinkType::Raw, DEFAULT_SNAPLEN).unwrap();
+        for (index, frame) in frames.iter().enumerate() {
+            writer.write(Duration::from_secs(index as u64), frame).unwrap();
+        }
+        writer.into_inner()
+    }
+
+    #[test]
+    fn golden() {
+        let (ip_addr, peer_addr) = (Ipv4Addr::new(10, 0, 0, 1), Ipv4Addr::new(10, 0, 0, 2));
+
+        // A datagram to a closed port is answered by a port unreachable error.
+        let datagram =
+            (Ipv4Layer::new(peer_addr, ip_addr).identification(0x1234) / UdpLayer::new(5000, 9) / b"hi".to_vec())
+                .build()
+                .unwrap();
+        let error = ErrorBuilder::default()
+            .message(ErrorMessage::Unreachable(
+                DestinationUnreachablePacketCode::PortUnreachable,
+            ))
+            .datagram(&datagram)
+            .build_vec();
+        let reply = (Ipv4Layer::new(ip_addr, peer_addr).protocol(Protocol::Icmp) / error)
+            .build()
+            .unwrap();
+
+        let input = capture(&[&datagram]);
+        let expected = capture(&[&reply]);
+        let golden = |tolerance| {
+            Golden::new(
+                Reader::new(input.as_slice()).unwrap(),
+                Reader::new(expected.as_slice()).unwrap(),
+            )
+            .unwrap()
+            .tolerance(tolerance)
+        };
+        let target = || {
+            let target = InterfaceTarget::new();
+            target.interface().lock().unwrap().set_ip_addr(ip_addr);
+            target
+        };
+
+        // The interface numbers the datagrams it sends itself, which the expected one does not.
+        golden(Tolerance::LENIENT).assert(&mut target());
+        let mismatches = golden(Tolerance::default()).check(&mut target()).unwrap();
+        assert!(matches!(
+            mismatches.as_slice(),
+            [Mismatch::Different {
+                index: 0,
+                offset: 5,
+                ..
+            }]
+        ));
+
+        // Blessing writes what is emitted, which then matches as it is.
+        let mut writer = Writer::new(vec![], Format::Pcap, LinkType::Raw, DEFAULT_SNAPLEN).unwrap();
+        golden(Tolerance::default()).bless(&mut target(), &mut writer).unwrap();
+        let blessed = writer.into_inner();
+        let golden = Golden::new(
+            Reader::new(input.as_slice()).unwrap(),
+            Reader::new(blessed.as_slice()).unwrap(),
+        )
+        .unwrap();
+        golden.assert(&mut target());
+
+        // Nothing is emitted for an input the interface ignores.
+        let mismatches = Golden::new(
+            Reader::new(expected.as_slice()).unwrap(),
+            Reader::new(expected.as_slice()).unwrap(),
+        )
+        .unwrap()
+        .check(&mut target())
+        .unwrap();
+        assert!(matches!(mismatches.as_slice(), [Mismatch::Missing { index: 0, .. }]));
+    }
+}
@@ -0,0 +1,164 @@
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::checksum::checksum;
+use crate::icmpv4::packet::{
+    consts, DestinationUnreachablePacketCode, EchoAndEchoReplyPacket, ErrorMessage, MessageType, Packet,
+    TimeExceededPacketCode,
+};
+
+/// Build an ICMP error message about a received datagram.
+pub struct ErrorBuilder {
+    message: ErrorMessage,
+    datagram: Vec<u8>,
+}
+
+impl ErrorBuilder {
+    pub fn message(mut self, message: ErrorMessage) -> Self {
+        self.message = message;
+        self
+    }
+
+    /// The datagram the message is about, its IPv4 header and the first 8 octets of its payload are quoted.
+    pub fn datagram(mut self, datagram: &[u8]) -> Self {
+        let header_len = datagram.first().map_or(0, |octet| ((octet & 0xf) * 4) as usize);
+        let quoted_len = datagram.len().min(header_len + consts::QUOTED_PAYLOAD_LEN);
+        self.datagram = datagram[..quoted_len].to_vec();
+        self
+    }
+
+    pub fn build_vec(mut self) -> Vec<u8> {
+        let mut buffer: Vec<u8> = vec![0; consts::ERROR_HEADER_LEN];
+        buffer.append(&mut self.datagram);
+
+        let (r#type, code) = match self.message {
+            ErrorMessage::Unreachable(code) => (MessageType::DestinationUnreachable, code.into()),
+            ErrorMessage::FragmentationNeeded(mtu) => {
+                buffer[6..8].copy_from_slice(&mtu.to_be_bytes());
+                (
+                    MessageType::DestinationUnreachable,
+                    DestinationUnreachablePacketCode::FragmentationNeededAndDfSet.into(),
+                )
+            }
+            ErrorMessage::TimeExceeded(code) => (MessageType::TimeExceeded, code.into()),
+        };
+
+        let mut packet = Packet::new_unchecked(buffer.as_mut_slice());
+        packet.set_type(r#type);
+        packet.set_code(code);
+        let checksum = checksum(packet.as_ref());
+        packet.set_checksum(checksum);
+
+        buffer
+    }
+
+    pub fn build(self) -> Packet<Vec<u8>> {
+        Packet::new_unchecked(self.build_vec())
+    }
+}
+
+impl Default for ErrorBuilder {
+    fn default() -> Self {
+        Self {
+            message: ErrorMessage::TimeExceeded(TimeExceededPacketCode::TtlExceededInTransit),
+            datagram: vec![],
+        }
+    }
+}
+
+/// Build an echo request, or the reply to one.
+#[derive(Default)]
+pub struct EchoBuilder {
+    reply: bool,
+    identifier: u16,
+    sequence_number: u16,
+    payload: Vec<u8>,
+}
+
+impl EchoBuilder {
+    pub fn reply(mut self, reply: bool) -> Self {
+        self.reply = reply;
+        self
+    }
+
+    pub fn identifier(mut self, identifier: u16) -> Self {
+        self.identifier = identifier;
+        self
+    }
+
+    pub fn sequence_number(mut self, sequence_number: u16) -> Self {
+        self.sequence_number = sequence_number;
+        self
+    }
+
+    pub fn payload(mut self, payload: Vec<u8>) -> Self {
+        self.payload = payload;
+        self
+    }
+
+    pub fn build_vec(mut self) -> Vec<u8> {
+        let mut buffer: Vec<u8> = vec![0; consts::ECHO_HEADER_LEN];
+        buffer.append(&mut self.payload);
+
+        let mut packet = EchoAndEchoReplyPacket::new_unchecked(buffer.as_mut_slice());
+        packet.set_type(if self.reply {
+            MessageType::EchoReply
+        } else {
+            MessageType::Echo
+        });
+        packet.set_code(0);
+        packet.set_identifier(self.identifier);
+        packet.set_sequence_number(self.sequence_number);
+        let checksum = checksum(packet.as_ref());
+        packet.set_checksum(checksum);
+
+        buffer
+    }
+
+    pub fn build(self) -> EchoAndEchoReplyPacket<Vec<u8>> {
+        EchoAndEchoReplyPacket::new_unchecked(self.build_vec())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use crate::checksum::checksum;
+    use crate::icmpv4::packet::{DestinationUnreachablePacketCode, ErrorMessage};
+    use crate::ipv4::builder::PacketBuilder;
+    use crate::ipv4::packet::Protocol;
+
+    #[test]
+    fn build() {
+        let datagram = PacketBuilder::default()
+            .protocol(Protocol::Udp)
+            .src_addr(Ipv4Addr::new(192, 168, 233, 234))
+            .dest_addr(Ipv4Addr::new(192, 168, 233, 233))
+            .payload((0..32).collect())
+            .build_vec();
+
+        let message = ErrorMessage::Unreachable(DestinationUnreachablePacketCode::PortUnreachable);
+        let buffer = super::ErrorBuilder::default()
+            .message(message)
+            .datagram(&datagram)
+            .build_vec();
+
+        assert_eq!(buffer.len(), 8 + 20 + 8);
+        assert_eq!(checksum(&buffer), 0);
+        let (parsed, quoted) = ErrorMessage::parse(&buffer).unwrap().unwrap();
+        assert_eq!(parsed, message);
+        assert_eq!(quoted.src_addr, Ipv4Addr::new(192, 168, 233, 234));
+        assert_eq!(quoted.protocol, Protocol::Udp);
+        assert_eq!(quoted.payload, &datagram[20..28]);
+
+        let buffer = super::ErrorBuilder::default()
+            .message(ErrorMessage::FragmentationNeeded(576))
+            .datagram(&datagram)
+            .build_vec();
+        assert_eq!(
+            ErrorMessage::parse(&buffer).unwrap().unwrap().0,
+            ErrorMessage::FragmentationNeeded(576)
+        );
+    }
+}
@@ -1,16 +1,18 @@
-use std::fmt::{Display, Formatter};
+use core::fmt::{Display, Formatter};
 
 #[derive(Debug)]
 pub enum Error {
     InvalidMessageType,
+    TruncatedMessage,
 }
 
 impl Display for Error {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
         match self {
             Error::InvalidMessageType => write!(f, "invalid message type"),
+            Error::TruncatedMessage => write!(f, "truncated message"),
         }
     }
 }
 
-impl std::error::Error for Error {}
+impl core::error::Error for Error {}
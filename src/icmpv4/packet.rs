@@ -1,10 +1,20 @@
-use std::convert::{TryFrom, TryInto};
-use std::fmt::{Debug, Formatter};
-use std::ops::{Deref, DerefMut};
+use core::convert::{TryFrom, TryInto};
+use core::fmt::{Debug, Formatter};
+use core::net::Ipv4Addr;
+use core::ops::{Deref, DerefMut};
+#[cfg(feature = "std")]
+use std::io::ErrorKind;
 
 use crate::c_like_enum;
 use crate::error::Result;
 use crate::icmpv4::error::Error;
+use crate::ipv4::packet::{consts as ipv4_consts, Packet as Ipv4Packet, Protocol};
+
+pub mod consts {
+    pub const ERROR_HEADER_LEN: usize = 8; // Type, code, checksum and the 4 octets before the quoted datagram
+    pub const QUOTED_PAYLOAD_LEN: usize = 8; // RFC 792, at least the first 64 bits of the original payload
+    pub const ECHO_HEADER_LEN: usize = 8; // Type, code, checksum, identifier and sequence number
+}
 
 c_like_enum!(
     /// ICMP message types defined in RFC 792
@@ -74,7 +84,7 @@ impl<Buf> Debug for Packet<Buf>
 where
     Buf: AsRef<[u8]>,
 {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
         write!(
             f,
             "type: {:?}, code: {:?}, checksum: {:#x}",
@@ -130,6 +140,9 @@ where
     }
 
     pub fn new_checked(buffer: Buf) -> Result<Self> {
+        if buffer.as_ref().len() < consts::ERROR_HEADER_LEN {
+            return Err(Error::TruncatedMessage.into());
+        }
         let unchecked = Self::new_unchecked(buffer);
 
         match unchecked.packet.try_into() {
@@ -142,9 +155,18 @@ where
         self.packet.buffer.as_ref()[1].into()
     }
 
+    /// Returns the MTU of the next hop of a Fragmentation Needed message, zero if the router did not set it (RFC 1191).
+    pub fn next_hop_mtu(&self) -> u16 {
+        u16::from_be_bytes([self.packet.buffer.as_ref()[6], self.packet.buffer.as_ref()[7]])
+    }
+
     pub fn payload(&self) -> &[u8] {
         &self.packet.buffer.as_ref()[8..]
     }
+
+    pub fn quoted(&self) -> Result<Quoted<'_>> {
+        Quoted::parse(self.payload())
+    }
 }
 
 impl<Buf> Deref for DestinationUnreachablePacket<Buf>
@@ -173,7 +195,7 @@ where
 {
     type Error = Error;
 
-    fn try_from(value: Packet<Buf>) -> std::result::Result<Self, Self::Error> {
+    fn try_from(value: Packet<Buf>) -> core::result::Result<Self, Self::Error> {
         let packet = Self::new_unchecked(value.buffer);
 
         if packet.r#type() != MessageType::DestinationUnreachable {
@@ -207,6 +229,9 @@ where
     }
 
     pub fn new_checked(buffer: Buf) -> Result<Self> {
+        if buffer.as_ref().len() < consts::ERROR_HEADER_LEN {
+            return Err(Error::TruncatedMessage.into());
+        }
         let unchecked = Self::new_unchecked(buffer);
 
         match unchecked.packet.try_into() {
@@ -222,6 +247,10 @@ where
     pub fn payload(&self) -> &[u8] {
         &self.packet.buffer.as_ref()[8..]
     }
+
+    pub fn quoted(&self) -> Result<Quoted<'_>> {
+        Quoted::parse(self.payload())
+    }
 }
 
 impl<Buf> Deref for TimeExceededPacket<Buf>
@@ -250,7 +279,7 @@ where
 {
     type Error = Error;
 
-    fn try_from(value: Packet<Buf>) -> std::result::Result<Self, Self::Error> {
+    fn try_from(value: Packet<Buf>) -> core::result::Result<Self, Self::Error> {
         let packet = Self::new_unchecked(value.buffer);
 
         if packet.r#type() != MessageType::TimeExceeded {
@@ -261,6 +290,113 @@ where
     }
 }
 
+/// The datagram quoted by an error message: its IPv4 header and the beginning of its payload.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Quoted<'a> {
+    pub src_addr: Ipv4Addr,
+    pub dest_addr: Ipv4Addr,
+    pub protocol: Protocol,
+    pub payload: &'a [u8],
+}
+
+impl<'a> Quoted<'a> {
+    /// Parse the payload of an error message, which must quote at least 8 octets of the original payload.
+    pub fn parse(buffer: &'a [u8]) -> Result<Self> {
+        if buffer.len() < (ipv4_consts::MIN_HEADER_LEN * 4) as usize {
+            return Err(Error::TruncatedMessage.into());
+        }
+
+        // The total length describes the original datagram, only the header is checked.
+        let packet = Ipv4Packet::new_unchecked(buffer);
+        packet.check_version()?;
+        let header_len = (packet.header_len() * 4) as usize;
+        if packet.header_len() < ipv4_consts::MIN_HEADER_LEN || buffer.len() < header_len + consts::QUOTED_PAYLOAD_LEN {
+            return Err(Error::TruncatedMessage.into());
+        }
+
+        Ok(Self {
+            src_addr: packet.src_addr(),
+            dest_addr: packet.dest_addr(),
+            protocol: packet.protocol(),
+            payload: &buffer[header_len..],
+        })
+    }
+
+    /// Returns the source and destination ports, the first four octets of both TCP and UDP headers.
+    pub fn ports(&self) -> (u16, u16) {
+        (
+            u16::from_be_bytes([self.payload[0], self.payload[1]]),
+            u16::from_be_bytes([self.payload[2], self.payload[3]]),
+        )
+    }
+}
+
+/// An error reported by an ICMP message about a datagram sent by a transport protocol.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ErrorMessage {
+    Unreachable(DestinationUnreachablePacketCode),
+    /// A router needs smaller datagrams, with the MTU of its next hop (RFC 1191).
+    FragmentationNeeded(u16),
+    TimeExceeded(TimeExceededPacketCode),
+}
+
+impl ErrorMessage {
+    /// Parse an error message and the datagram it quotes, returns `None` for other message types.
+    pub fn parse(buffer: &[u8]) -> Result<Option<(Self, Quoted<'_>)>> {
+        if buffer.len() < consts::ERROR_HEADER_LEN {
+            return Err(Error::TruncatedMessage.into());
+        }
+
+        match Packet::new_unchecked(buffer).r#type() {
+            MessageType::DestinationUnreachable => {
+                let packet = DestinationUnreachablePacket::new_checked(buffer)?;
+                let message = match packet.code() {
+                    DestinationUnreachablePacketCode::FragmentationNeededAndDfSet => {
+                        ErrorMessage::FragmentationNeeded(packet.next_hop_mtu())
+                    }
+                    code => ErrorMessage::Unreachable(code),
+                };
+                Ok(Some((message, Quoted::parse(&buffer[consts::ERROR_HEADER_LEN..])?)))
+            }
+            MessageType::TimeExceeded => {
+                let packet = TimeExceededPacket::new_checked(buffer)?;
+                Ok(Some((
+                    ErrorMessage::TimeExceeded(packet.code()),
+                    Quoted::parse(&buffer[consts::ERROR_HEADER_LEN..])?,
+                )))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Returns the error reported to the application of the socket the message is about.
+    #[cfg(feature = "std")]
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            ErrorMessage::Unreachable(DestinationUnreachablePacketCode::NetUnreachable) => {
+                ErrorKind::NetworkUnreachable
+            }
+            ErrorMessage::Unreachable(
+                DestinationUnreachablePacketCode::ProtocolUnreachable
+                | DestinationUnreachablePacketCode::PortUnreachable,
+            ) => ErrorKind::ConnectionRefused,
+            _ => ErrorKind::HostUnreachable,
+        }
+    }
+
+    /// Whether the error is permanent and aborts the connection, rather than being reported
+    /// if the connection fails for another reason (RFC 1122 section 4.2.3.9).
+    pub fn is_hard(&self) -> bool {
+        matches!(
+            self,
+            ErrorMessage::Unreachable(
+                DestinationUnreachablePacketCode::ProtocolUnreachable
+                    | DestinationUnreachablePacketCode::PortUnreachable
+            )
+        )
+    }
+}
+
 // TODO: support other ICMP message types
 
 pub struct EchoAndEchoReplyPacket<Buf> {
@@ -346,7 +482,7 @@ where
 {
     type Error = Error;
 
-    fn try_from(value: Packet<Buf>) -> std::result::Result<Self, Self::Error> {
+    fn try_from(value: Packet<Buf>) -> core::result::Result<Self, Self::Error> {
         let packet = Self::new_unchecked(value.buffer);
 
         if packet.r#type() != MessageType::Echo && packet.r#type() != MessageType::EchoReply {
@@ -356,3 +492,40 @@ where
         Ok(packet)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use super::{DestinationUnreachablePacketCode, ErrorMessage};
+    use crate::ipv4::packet::Protocol;
+
+    #[test]
+    fn error_message() {
+        let bytes: Vec<u8> = vec![
+            // icmp header, fragmentation needed with a next-hop MTU of 1400
+            0x03, 0x04, 0x00, 0x00, 0x00, 0x00, 0x05, 0x78, //
+            // quoted ip header
+            0x45, 0x00, 0x05, 0xdc, 0x00, 0x01, 0x40, 0x00, 0x40, 0x06, 0x00, 0x00, 0xc0, 0xa8, 0xe9, 0xe9, 0xc0, 0xa8,
+            0xe9, 0xea, //
+            // quoted tcp ports and sequence number
+            0x00, 0x50, 0x9c, 0x40, 0x00, 0x00, 0x03, 0xe8,
+        ];
+
+        let (message, quoted) = ErrorMessage::parse(&bytes).unwrap().expect("an error message");
+        assert_eq!(message, ErrorMessage::FragmentationNeeded(1400));
+        assert!(!message.is_hard());
+        assert_eq!(quoted.src_addr, Ipv4Addr::new(192, 168, 233, 233));
+        assert_eq!(quoted.dest_addr, Ipv4Addr::new(192, 168, 233, 234));
+        assert_eq!(quoted.protocol, Protocol::Tcp);
+        assert_eq!(quoted.ports(), (80, 40000));
+        assert_eq!(quoted.payload[4..8], [0x00, 0x00, 0x03, 0xe8]);
+
+        assert!(ErrorMessage::parse(&bytes[..bytes.len() - 1]).is_err());
+        assert!(ErrorMessage::Unreachable(DestinationUnreachablePacketCode::PortUnreachable).is_hard());
+
+        let mut echo = bytes.clone();
+        echo[0] = 8;
+        assert!(ErrorMessage::parse(&echo).unwrap().is_none());
+    }
+}
@@ -0,0 +1,89 @@
+use std::time::{Duration, Instant};
+
+pub mod consts {
+    use std::time::Duration;
+
+    pub const DEFAULT_BURST: u32 = 50; // Messages sent back to back before the rate applies
+    pub const DEFAULT_INTERVAL: Duration = Duration::from_millis(1); // One message per interval, 1000 per second
+}
+
+/// A token bucket limiting the rate of the ICMP error messages sent (RFC 1812 section 4.3.2.8).
+#[derive(Debug, Clone)]
+pub struct RateLimiter {
+    burst: u32,
+    interval: Duration,
+    tokens: u32,
+    /// When the last token was added.
+    refilled_at: Option<Instant>,
+}
+
+impl RateLimiter {
+    /// Allow `burst` messages at once, then one message per `interval`. A zero interval disables the limit.
+    pub fn new(burst: u32, interval: Duration) -> Self {
+        Self {
+            burst,
+            interval,
+            tokens: burst,
+            refilled_at: None,
+        }
+    }
+
+    /// Whether a message can be sent at `now`, taking a token if it can.
+    pub fn allow(&mut self, now: Instant) -> bool {
+        if self.interval == Duration::ZERO {
+            return true;
+        }
+
+        let refilled_at = *self.refilled_at.get_or_insert(now);
+        let elapsed = now.saturating_duration_since(refilled_at);
+        let refill = (elapsed.as_nanos() / self.interval.as_nanos()).min(self.burst as u128) as u32;
+        if refill > 0 {
+            self.tokens = (self.tokens + refill).min(self.burst);
+            self.refilled_at = Some(if self.tokens == self.burst {
+                now
+            } else {
+                refilled_at + self.interval * refill
+            });
+        }
+
+        if self.tokens == 0 {
+            return false;
+        }
+        self.tokens -= 1;
+        true
+    }
+}
+
+impl Default for RateLimiter {
+    fn default() -> Self {
+        Self::new(consts::DEFAULT_BURST, consts::DEFAULT_INTERVAL)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::RateLimiter;
+
+    #[test]
+    fn allow() {
+        let mut limiter = RateLimiter::new(2, Duration::from_secs(1));
+        let now = Instant::now();
+
+        assert!(limiter.allow(now));
+        assert!(limiter.allow(now));
+        assert!(!limiter.allow(now + Duration::from_millis(999)));
+        assert!(limiter.allow(now + Duration::from_secs(1)));
+        assert!(!limiter.allow(now + Duration::from_secs(1)));
+
+        // The bucket never holds more than the burst.
+        let later = now + Duration::from_secs(60);
+        assert!(limiter.allow(later));
+        assert!(limiter.allow(later));
+        assert!(!limiter.allow(later));
+
+        let mut unlimited = RateLimiter::new(0, Duration::ZERO);
+        assert!(unlimited.allow(now));
+    }
+}
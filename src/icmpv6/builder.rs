@@ -0,0 +1,205 @@
+use alloc::vec;
+use alloc::vec::Vec;
+use core::net::Ipv6Addr;
+
+use crate::icmpv6::packet::{consts, ErrorMessage, MessageType, Packet, TimeExceededCode};
+
+/// Build an ICMPv6 error message about a received packet, sent from `src_addr` to `dest_addr`.
+pub struct ErrorBuilder {
+    message: ErrorMessage,
+    src_addr: Ipv6Addr,
+    dest_addr: Ipv6Addr,
+    datagram: Vec<u8>,
+}
+
+impl ErrorBuilder {
+    pub fn message(mut self, message: ErrorMessage) -> Self {
+        self.message = message;
+        self
+    }
+
+    /// Source address used by the pseudo-header checksum.
+    pub fn src_addr(mut self, src_addr: Ipv6Addr) -> Self {
+        self.src_addr = src_addr;
+        self
+    }
+
+    /// Destination address used by the pseudo-header checksum.
+    pub fn dest_addr(mut self, dest_addr: Ipv6Addr) -> Self {
+        self.dest_addr = dest_addr;
+        self
+    }
+
+    /// The packet the message is about, quoted as much as the minimum MTU allows (RFC 4443 section 3).
+    pub fn datagram(mut self, datagram: &[u8]) -> Self {
+        let quoted_len = datagram.len().min(consts::MAX_ERROR_LEN - consts::HEADER_LEN);
+        self.datagram = datagram[..quoted_len].to_vec();
+        self
+    }
+
+    pub fn build_vec(mut self) -> Vec<u8> {
+        let mut buffer: Vec<u8> = vec![0; consts::HEADER_LEN];
+        buffer.append(&mut self.datagram);
+
+        let (r#type, code, body) = match self.message {
+            ErrorMessage::Unreachable(code) => (MessageType::DestinationUnreachable, code.into(), 0),
+            ErrorMessage::PacketTooBig(mtu) => (MessageType::PacketTooBig, 0, mtu),
+            ErrorMessage::TimeExceeded(code) => (MessageType::TimeExceeded, code.into(), 0),
+            ErrorMessage::ParameterProblem(code, pointer) => (MessageType::ParameterProblem, code.into(), pointer),
+        };
+        buffer[4..8].copy_from_slice(&body.to_be_bytes());
+
+        let mut packet = Packet::new_unchecked(buffer.as_mut_slice());
+        packet.set_type(r#type);
+        packet.set_code(code);
+        packet.fill_checksum(self.src_addr, self.dest_addr);
+
+        buffer
+    }
+
+    pub fn build(self) -> Packet<Vec<u8>> {
+        Packet::new_unchecked(self.build_vec())
+    }
+}
+
+impl Default for ErrorBuilder {
+    fn default() -> Self {
+        Self {
+            message: ErrorMessage::TimeExceeded(TimeExceededCode::HopLimitExceeded),
+            src_addr: Ipv6Addr::UNSPECIFIED,
+            dest_addr: Ipv6Addr::UNSPECIFIED,
+            datagram: vec![],
+        }
+    }
+}
+
+/// Build an echo request, or the reply to one.
+pub struct EchoBuilder {
+    reply: bool,
+    identifier: u16,
+    sequence_number: u16,
+    src_addr: Ipv6Addr,
+    dest_addr: Ipv6Addr,
+    payload: Vec<u8>,
+}
+
+impl EchoBuilder {
+    pub fn reply(mut self, reply: bool) -> Self {
+        self.reply = reply;
+        self
+    }
+
+    pub fn identifier(mut self, identifier: u16) -> Self {
+        self.identifier = identifier;
+        self
+    }
+
+    pub fn sequence_number(mut self, sequence_number: u16) -> Self {
+        self.sequence_number = sequence_number;
+        self
+    }
+
+    /// Source address used by the pseudo-header checksum.
+    pub fn src_addr(mut self, src_addr: Ipv6Addr) -> Self {
+        self.src_addr = src_addr;
+        self
+    }
+
+    /// Destination address used by the pseudo-header checksum.
+    pub fn dest_addr(mut self, dest_addr: Ipv6Addr) -> Self {
+        self.dest_addr = dest_addr;
+        self
+    }
+
+    pub fn payload(mut self, payload: Vec<u8>) -> Self {
+        self.payload = payload;
+        self
+    }
+
+    pub fn build_vec(mut self) -> Vec<u8> {
+        let mut buffer: Vec<u8> = vec![0; consts::HEADER_LEN];
+        buffer[4..6].copy_from_slice(&self.identifier.to_be_bytes());
+        buffer[6..8].copy_from_slice(&self.sequence_number.to_be_bytes());
+        buffer.append(&mut self.payload);
+
+        let mut packet = Packet::new_unchecked(buffer.as_mut_slice());
+        packet.set_type(if self.reply {
+            MessageType::EchoReply
+        } else {
+            MessageType::EchoRequest
+        });
+        packet.set_code(0);
+        packet.fill_checksum(self.src_addr, self.dest_addr);
+
+        buffer
+    }
+
+    pub fn build(self) -> Packet<Vec<u8>> {
+        Packet::new_unchecked(self.build_vec())
+    }
+}
+
+impl Default for EchoBuilder {
+    fn default() -> Self {
+        Self {
+            reply: false,
+            identifier: 0,
+            sequence_number: 0,
+            src_addr: Ipv6Addr::UNSPECIFIED,
+            dest_addr: Ipv6Addr::UNSPECIFIED,
+            payload: vec![],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv6Addr;
+
+    use super::{EchoBuilder, ErrorBuilder};
+    use crate::icmpv6::packet::{consts, DestinationUnreachableCode, EchoAndEchoReplyPacket, ErrorMessage};
+    use crate::ipv4::packet::Protocol;
+    use crate::ipv6::builder::PacketBuilder;
+
+    #[test]
+    fn build() {
+        let src_addr: Ipv6Addr = "2001:db8::1".parse().unwrap();
+        let dest_addr: Ipv6Addr = "2001:db8::2".parse().unwrap();
+        let datagram = PacketBuilder::default()
+            .next_header(Protocol::Udp)
+            .src_addr(dest_addr)
+            .dest_addr(src_addr)
+            .payload((0..2000).map(|octet| octet as u8).collect())
+            .build_vec();
+
+        let message = ErrorMessage::Unreachable(DestinationUnreachableCode::PortUnreachable);
+        let packet = ErrorBuilder::default()
+            .message(message)
+            .src_addr(src_addr)
+            .dest_addr(dest_addr)
+            .datagram(&datagram)
+            .build();
+
+        assert_eq!(packet.as_ref().len(), consts::MAX_ERROR_LEN);
+        assert!(packet.verify_checksum(src_addr, dest_addr));
+        let (parsed, quoted) = ErrorMessage::parse(packet.as_ref()).unwrap().unwrap();
+        assert_eq!(parsed, message);
+        assert_eq!(quoted.src_addr, dest_addr);
+        assert_eq!(quoted.next_header, Protocol::Udp);
+        assert_eq!(&quoted.payload[..8], &datagram[40..48]);
+
+        let echo = EchoBuilder::default()
+            .reply(true)
+            .identifier(7)
+            .sequence_number(9)
+            .src_addr(src_addr)
+            .dest_addr(dest_addr)
+            .payload(b"ping".to_vec())
+            .build_vec();
+        let echo = EchoAndEchoReplyPacket::new_checked(echo.as_slice()).unwrap();
+        assert!(echo.is_reply());
+        assert_eq!((echo.identifier(), echo.sequence_number()), (7, 9));
+        assert_eq!(echo.payload(), b"ping");
+        assert!(echo.verify_checksum(src_addr, dest_addr));
+    }
+}
@@ -0,0 +1,18 @@
+use core::fmt::{Display, Formatter};
+
+#[derive(Debug)]
+pub enum Error {
+    InvalidMessageType,
+    TruncatedMessage,
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Error::InvalidMessageType => write!(f, "invalid message type"),
+            Error::TruncatedMessage => write!(f, "truncated message"),
+        }
+    }
+}
+
+impl core::error::Error for Error {}
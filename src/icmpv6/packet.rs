@@ -0,0 +1,514 @@
+use core::convert::{TryFrom, TryInto};
+use core::fmt::{Debug, Formatter};
+use core::net::Ipv6Addr;
+use core::ops::{Deref, DerefMut};
+#[cfg(feature = "std")]
+use std::io::ErrorKind;
+
+use crate::c_like_enum;
+use crate::checksum::pseudo_header_checksum;
+use crate::error::Result;
+use crate::icmpv6::error::Error;
+use crate::ipv4::packet::Protocol;
+use crate::ipv6::extension::ExtensionHeaders;
+use crate::ipv6::packet::{consts as ipv6_consts, Packet as Ipv6Packet};
+
+pub mod consts {
+    pub const HEADER_LEN: usize = 8; // Type, code, checksum and the 4 octets of the message body
+    pub const QUOTED_PAYLOAD_LEN: usize = 8; // Enough for the ports of the quoted upper-layer header
+    pub const MAX_ERROR_LEN: usize = 1280 - 40; // RFC 4443 section 2.4, an error must not exceed the minimum MTU
+}
+
+c_like_enum!(
+    /// ICMPv6 message types defined in RFC 4443, and the ones of Neighbor Discovery and Multicast Listener Discovery
+    #[derive(Debug, Copy, Clone, PartialEq, Eq)]
+    pub enum MessageType(u8) {
+        DestinationUnreachable = 1,
+        PacketTooBig = 2,
+        TimeExceeded = 3,
+        ParameterProblem = 4,
+        EchoRequest = 128,
+        EchoReply = 129,
+        MulticastListenerQuery = 130, // RFC 2710
+        MulticastListenerReport = 131,
+        MulticastListenerDone = 132,
+        RouterSolicitation = 133, // RFC 4861
+        RouterAdvertisement = 134,
+        NeighborSolicitation = 135,
+        NeighborAdvertisement = 136,
+        Redirect = 137,
+        MulticastListenerReportV2 = 143, // RFC 3810
+    }
+);
+
+impl MessageType {
+    /// Whether this is an error message, whose type has the high-order bit clear (RFC 4443 section 2.1).
+    pub fn is_error(&self) -> bool {
+        u8::from(*self) < 128
+    }
+}
+
+pub struct Packet<Buf> {
+    buffer: Buf,
+}
+
+impl<Buf> Packet<Buf>
+where
+    Buf: AsRef<[u8]>,
+{
+    pub fn new_unchecked(buffer: Buf) -> Self {
+        Packet { buffer }
+    }
+
+    pub fn new_checked(buffer: Buf) -> Result<Self> {
+        if buffer.as_ref().len() < consts::HEADER_LEN {
+            return Err(Error::TruncatedMessage.into());
+        }
+        Ok(Self::new_unchecked(buffer))
+    }
+
+    pub fn r#type(&self) -> MessageType {
+        self.buffer.as_ref()[0].into()
+    }
+
+    pub fn code(&self) -> u8 {
+        self.buffer.as_ref()[1]
+    }
+
+    pub fn checksum(&self) -> u16 {
+        u16::from_be_bytes([self.buffer.as_ref()[2], self.buffer.as_ref()[3]])
+    }
+
+    /// Returns the 4 octets after the checksum, whose meaning depends on the message type.
+    fn body(&self) -> u32 {
+        let buffer = self.buffer.as_ref();
+        u32::from_be_bytes([buffer[4], buffer[5], buffer[6], buffer[7]])
+    }
+
+    pub fn payload(&self) -> &[u8] {
+        &self.buffer.as_ref()[4..]
+    }
+
+    /// Validate the checksum, which unlike ICMPv4 covers the IPv6 pseudo-header (RFC 4443 section 2.3).
+    pub fn verify_checksum(&self, src_addr: Ipv6Addr, dest_addr: Ipv6Addr) -> bool {
+        let buffer = self.buffer.as_ref();
+        pseudo_header_checksum(src_addr, dest_addr, Protocol::Icmpv6, buffer.len(), buffer) == 0
+    }
+}
+
+impl<Buf> Packet<Buf>
+where
+    Buf: AsRef<[u8]> + AsMut<[u8]>,
+{
+    pub fn set_type(&mut self, r#type: MessageType) {
+        self.buffer.as_mut()[0] = r#type.into();
+    }
+
+    pub fn set_code(&mut self, code: u8) {
+        self.buffer.as_mut()[1] = code;
+    }
+
+    pub fn set_checksum(&mut self, checksum: u16) {
+        self.buffer.as_mut()[2..=3].copy_from_slice(checksum.to_be_bytes().as_ref());
+    }
+
+    /// Compute and fill the checksum, using the IPv6 pseudo-header.
+    pub fn fill_checksum(&mut self, src_addr: Ipv6Addr, dest_addr: Ipv6Addr) {
+        self.set_checksum(0);
+        let buffer = self.buffer.as_ref();
+        let checksum = pseudo_header_checksum(src_addr, dest_addr, Protocol::Icmpv6, buffer.len(), buffer);
+        self.set_checksum(checksum);
+    }
+}
+
+impl<Buf> Debug for Packet<Buf>
+where
+    Buf: AsRef<[u8]>,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "type: {:?}, code: {:?}, checksum: {:#x}",
+            self.r#type(),
+            self.code(),
+            self.checksum(),
+        )
+    }
+}
+
+impl<Buf> AsRef<[u8]> for Packet<Buf>
+where
+    Buf: AsRef<[u8]>,
+{
+    fn as_ref(&self) -> &[u8] {
+        self.buffer.as_ref()
+    }
+}
+
+impl<Buf> AsMut<[u8]> for Packet<Buf>
+where
+    Buf: AsMut<[u8]>,
+{
+    fn as_mut(&mut self) -> &mut [u8] {
+        self.buffer.as_mut()
+    }
+}
+
+/// Define a view of the messages of `$types` over `Packet`, checked by `new_checked` and `TryFrom`.
+macro_rules! message_packet {
+    ($(#[$meta:meta])* $name:ident, $($types:ident)|+) => {
+        $(#[$meta])*
+        pub struct $name<Buf> {
+            packet: Packet<Buf>,
+        }
+
+        impl<Buf> $name<Buf>
+        where
+            Buf: AsRef<[u8]>,
+        {
+            pub fn new_unchecked(buffer: Buf) -> Self {
+                $name {
+                    packet: Packet { buffer },
+                }
+            }
+
+            pub fn new_checked(buffer: Buf) -> Result<Self> {
+                let packet = Packet::new_checked(buffer)?;
+                match packet.try_into() {
+                    Ok(packet) => Ok(packet),
+                    Err(err) => Err(err.into()),
+                }
+            }
+        }
+
+        impl<Buf> Deref for $name<Buf>
+        where
+            Buf: AsRef<[u8]>,
+        {
+            type Target = Packet<Buf>;
+
+            fn deref(&self) -> &Self::Target {
+                &self.packet
+            }
+        }
+
+        impl<Buf> DerefMut for $name<Buf>
+        where
+            Buf: AsRef<[u8]> + AsMut<[u8]>,
+        {
+            fn deref_mut(&mut self) -> &mut Self::Target {
+                &mut self.packet
+            }
+        }
+
+        impl<Buf> TryFrom<Packet<Buf>> for $name<Buf>
+        where
+            Buf: AsRef<[u8]>,
+        {
+            type Error = Error;
+
+            fn try_from(value: Packet<Buf>) -> core::result::Result<Self, Self::Error> {
+                let packet = Self::new_unchecked(value.buffer);
+
+                if !matches!(packet.r#type(), $(MessageType::$types)|+) {
+                    return Err(Error::InvalidMessageType);
+                }
+
+                Ok(packet)
+            }
+        }
+    };
+}
+
+c_like_enum!(
+    #[derive(Debug, Copy, Clone, PartialEq, Eq)]
+    pub enum DestinationUnreachableCode(u8) {
+        NoRoute = 0,
+        AdministrativelyProhibited = 1,
+        BeyondScope = 2,
+        AddressUnreachable = 3,
+        PortUnreachable = 4,
+        SourcePolicyFailed = 5,
+        RejectRoute = 6,
+    }
+);
+
+message_packet!(DestinationUnreachablePacket, DestinationUnreachable);
+
+impl<Buf> DestinationUnreachablePacket<Buf>
+where
+    Buf: AsRef<[u8]>,
+{
+    pub fn code(&self) -> DestinationUnreachableCode {
+        self.packet.buffer.as_ref()[1].into()
+    }
+
+    /// Returns the quoted packet.
+    pub fn payload(&self) -> &[u8] {
+        &self.packet.buffer.as_ref()[consts::HEADER_LEN..]
+    }
+}
+
+message_packet!(PacketTooBigPacket, PacketTooBig);
+
+impl<Buf> PacketTooBigPacket<Buf>
+where
+    Buf: AsRef<[u8]>,
+{
+    /// Returns the MTU of the next hop, IPv6 routers do not fragment (RFC 8201).
+    pub fn mtu(&self) -> u32 {
+        self.body()
+    }
+
+    pub fn payload(&self) -> &[u8] {
+        &self.packet.buffer.as_ref()[consts::HEADER_LEN..]
+    }
+}
+
+c_like_enum!(
+    #[derive(Debug, Copy, Clone, PartialEq, Eq)]
+    pub enum TimeExceededCode(u8) {
+        HopLimitExceeded = 0,
+        FragmentReassemblyTimeExceeded = 1,
+    }
+);
+
+message_packet!(TimeExceededPacket, TimeExceeded);
+
+impl<Buf> TimeExceededPacket<Buf>
+where
+    Buf: AsRef<[u8]>,
+{
+    pub fn code(&self) -> TimeExceededCode {
+        self.packet.buffer.as_ref()[1].into()
+    }
+
+    pub fn payload(&self) -> &[u8] {
+        &self.packet.buffer.as_ref()[consts::HEADER_LEN..]
+    }
+}
+
+c_like_enum!(
+    #[derive(Debug, Copy, Clone, PartialEq, Eq)]
+    pub enum ParameterProblemCode(u8) {
+        ErroneousHeaderField = 0,
+        UnrecognizedNextHeader = 1,
+        UnrecognizedOption = 2,
+    }
+);
+
+message_packet!(ParameterProblemPacket, ParameterProblem);
+
+impl<Buf> ParameterProblemPacket<Buf>
+where
+    Buf: AsRef<[u8]>,
+{
+    pub fn code(&self) -> ParameterProblemCode {
+        self.packet.buffer.as_ref()[1].into()
+    }
+
+    /// Returns the offset of the erroneous octet in the quoted packet.
+    pub fn pointer(&self) -> u32 {
+        self.body()
+    }
+
+    pub fn payload(&self) -> &[u8] {
+        &self.packet.buffer.as_ref()[consts::HEADER_LEN..]
+    }
+}
+
+message_packet!(EchoAndEchoReplyPacket, EchoRequest | EchoReply);
+
+impl<Buf> EchoAndEchoReplyPacket<Buf>
+where
+    Buf: AsRef<[u8]>,
+{
+    pub fn is_reply(&self) -> bool {
+        self.r#type() == MessageType::EchoReply
+    }
+
+    pub fn is_request(&self) -> bool {
+        self.r#type() == MessageType::EchoRequest
+    }
+
+    pub fn identifier(&self) -> u16 {
+        (self.body() >> 16) as u16
+    }
+
+    pub fn sequence_number(&self) -> u16 {
+        self.body() as u16
+    }
+
+    pub fn payload(&self) -> &[u8] {
+        &self.packet.buffer.as_ref()[consts::HEADER_LEN..]
+    }
+}
+
+impl<Buf> EchoAndEchoReplyPacket<Buf>
+where
+    Buf: AsRef<[u8]> + AsMut<[u8]>,
+{
+    pub fn set_identifier(&mut self, identifier: u16) {
+        self.packet.buffer.as_mut()[4..=5].copy_from_slice(identifier.to_be_bytes().as_ref());
+    }
+
+    pub fn set_sequence_number(&mut self, sequence_number: u16) {
+        self.packet.buffer.as_mut()[6..=7].copy_from_slice(sequence_number.to_be_bytes().as_ref());
+    }
+}
+
+/// The packet quoted by an error message: its IPv6 header and the beginning of its upper-layer payload,
+/// after the extension headers.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Quoted<'a> {
+    pub src_addr: Ipv6Addr,
+    pub dest_addr: Ipv6Addr,
+    pub next_header: Protocol,
+    pub payload: &'a [u8],
+}
+
+impl<'a> Quoted<'a> {
+    /// Parse the payload of an error message, which must quote at least 8 octets of the upper-layer payload.
+    pub fn parse(buffer: &'a [u8]) -> Result<Self> {
+        if buffer.len() < ipv6_consts::HEADER_LEN {
+            return Err(Error::TruncatedMessage.into());
+        }
+
+        // The payload length describes the original packet, only the header is checked.
+        let packet = Ipv6Packet::new_unchecked(buffer);
+        packet.check_version()?;
+        let quoted = &buffer[ipv6_consts::HEADER_LEN..];
+        let mut headers = ExtensionHeaders::new(packet.next_header(), quoted);
+        for header in headers.by_ref() {
+            header?;
+        }
+        let (next_header, offset) = headers.upper_layer();
+        if quoted.len() < offset + consts::QUOTED_PAYLOAD_LEN {
+            return Err(Error::TruncatedMessage.into());
+        }
+
+        Ok(Self {
+            src_addr: packet.src_addr(),
+            dest_addr: packet.dest_addr(),
+            next_header,
+            payload: &quoted[offset..],
+        })
+    }
+
+    /// Returns the source and destination ports, the first four octets of both TCP and UDP headers.
+    pub fn ports(&self) -> (u16, u16) {
+        (
+            u16::from_be_bytes([self.payload[0], self.payload[1]]),
+            u16::from_be_bytes([self.payload[2], self.payload[3]]),
+        )
+    }
+}
+
+/// An error reported by an ICMPv6 message about a packet sent by a transport protocol.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ErrorMessage {
+    Unreachable(DestinationUnreachableCode),
+    /// A router needs smaller packets, with the MTU of its next hop (RFC 8201).
+    PacketTooBig(u32),
+    TimeExceeded(TimeExceededCode),
+    /// The octet at the pointer of the quoted packet could not be processed.
+    ParameterProblem(ParameterProblemCode, u32),
+}
+
+impl ErrorMessage {
+    /// Parse an error message and the packet it quotes, returns `None` for informational messages.
+    pub fn parse(buffer: &[u8]) -> Result<Option<(Self, Quoted<'_>)>> {
+        let packet = Packet::new_checked(buffer)?;
+
+        let message = match packet.r#type() {
+            MessageType::DestinationUnreachable => {
+                ErrorMessage::Unreachable(DestinationUnreachablePacket::new_checked(buffer)?.code())
+            }
+            MessageType::PacketTooBig => ErrorMessage::PacketTooBig(PacketTooBigPacket::new_checked(buffer)?.mtu()),
+            MessageType::TimeExceeded => ErrorMessage::TimeExceeded(TimeExceededPacket::new_checked(buffer)?.code()),
+            MessageType::ParameterProblem => {
+                let packet = ParameterProblemPacket::new_checked(buffer)?;
+                ErrorMessage::ParameterProblem(packet.code(), packet.pointer())
+            }
+            _ => return Ok(None),
+        };
+        Ok(Some((message, Quoted::parse(&buffer[consts::HEADER_LEN..])?)))
+    }
+
+    /// Returns the error reported to the application of the socket the message is about.
+    #[cfg(feature = "std")]
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            ErrorMessage::Unreachable(DestinationUnreachableCode::NoRoute) => ErrorKind::NetworkUnreachable,
+            ErrorMessage::Unreachable(DestinationUnreachableCode::PortUnreachable)
+            | ErrorMessage::ParameterProblem(ParameterProblemCode::UnrecognizedNextHeader, _) => {
+                ErrorKind::ConnectionRefused
+            }
+            ErrorMessage::Unreachable(
+                DestinationUnreachableCode::AdministrativelyProhibited
+                | DestinationUnreachableCode::SourcePolicyFailed
+                | DestinationUnreachableCode::RejectRoute,
+            ) => ErrorKind::PermissionDenied,
+            _ => ErrorKind::HostUnreachable,
+        }
+    }
+
+    /// Whether the error is permanent and aborts the connection, like the hard errors of ICMPv4.
+    pub fn is_hard(&self) -> bool {
+        matches!(
+            self,
+            ErrorMessage::Unreachable(DestinationUnreachableCode::PortUnreachable)
+                | ErrorMessage::ParameterProblem(ParameterProblemCode::UnrecognizedNextHeader, _)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv6Addr;
+
+    use super::{DestinationUnreachableCode, EchoAndEchoReplyPacket, ErrorMessage, Packet};
+    use crate::ipv4::packet::Protocol;
+
+    #[test]
+    fn error_message() {
+        let mut bytes: Vec<u8> = vec![
+            // icmpv6 header, packet too big with an MTU of 1400
+            0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x05, 0x78, //
+            // quoted ipv6 header, a fragment header follows
+            0x60, 0x00, 0x00, 0x00, 0x05, 0xa0, 0x2c, 0x40, //
+            0x20, 0x01, 0x0d, 0xb8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x01, //
+            0x20, 0x01, 0x0d, 0xb8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x02, //
+            // quoted fragment header
+            0x06, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x2a, //
+            // quoted tcp ports and sequence number
+            0x00, 0x50, 0x9c, 0x40, 0x00, 0x00, 0x03, 0xe8,
+        ];
+
+        let src_addr: Ipv6Addr = "2001:db8::fe".parse().unwrap();
+        let dest_addr: Ipv6Addr = "2001:db8::1".parse().unwrap();
+        Packet::new_unchecked(bytes.as_mut_slice()).fill_checksum(src_addr, dest_addr);
+        let packet = Packet::new_checked(bytes.as_slice()).unwrap();
+        assert!(packet.verify_checksum(src_addr, dest_addr));
+        assert!(!packet.verify_checksum(dest_addr, "2001:db8::3".parse().unwrap()));
+
+        let (message, quoted) = ErrorMessage::parse(&bytes).unwrap().expect("an error message");
+        assert_eq!(message, ErrorMessage::PacketTooBig(1400));
+        assert!(!message.is_hard());
+        assert_eq!(quoted.src_addr, "2001:db8::1".parse::<Ipv6Addr>().unwrap());
+        assert_eq!(quoted.next_header, Protocol::Tcp);
+        assert_eq!(quoted.ports(), (80, 40000));
+
+        assert!(ErrorMessage::parse(&bytes[..bytes.len() - 1]).is_err());
+        assert!(ErrorMessage::Unreachable(DestinationUnreachableCode::PortUnreachable).is_hard());
+
+        let mut echo = bytes.clone();
+        echo[0] = 128;
+        assert!(ErrorMessage::parse(&echo).unwrap().is_none());
+        let echo = EchoAndEchoReplyPacket::new_checked(echo.as_slice()).unwrap();
+        assert!(echo.is_request());
+        assert_eq!(echo.identifier(), 0);
+        assert_eq!(echo.sequence_number(), 1400);
+        assert!(EchoAndEchoReplyPacket::new_checked(bytes.as_slice()).is_err());
+    }
+}
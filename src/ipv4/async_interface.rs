@@ -0,0 +1,75 @@
+use std::io::ErrorKind;
+use std::os::unix::io::{AsRawFd, RawFd};
+
+use tokio::io::unix::AsyncFd;
+
+use crate::error::{Error, Result};
+use crate::ipv4::interface::Interface;
+use crate::ipv4::packet::Packet;
+use crate::net_device::tun::TunDevice;
+use crate::net_device::Device;
+use crate::pool::PooledBuffer;
+
+fn is_would_block(e: &Error) -> bool {
+    matches!(e, Error::Io(e) if e.kind() == ErrorKind::WouldBlock)
+}
+
+/// An interface driven by tokio tasks, which wait for its device to be ready instead of blocking.
+///
+/// The device must be in non-blocking mode, such as a `TunDevice` after `set_nonblocking(true)`.
+/// Packets looped back to the interface are only received once the device is readable too.
+pub struct AsyncInterface<D: Device + AsRawFd = TunDevice> {
+    interface: Interface<D>,
+    fd: AsyncFd<RawFd>,
+}
+
+impl<D: Device + AsRawFd> AsyncInterface<D> {
+    /// Register the device of `interface` with the tokio reactor. It must be called within a tokio runtime.
+    pub fn new(interface: Interface<D>) -> Result<Self> {
+        let fd = AsyncFd::new(interface.device().as_raw_fd())?;
+        Ok(Self { interface, fd })
+    }
+
+    pub fn interface(&self) -> &Interface<D> {
+        &self.interface
+    }
+
+    pub fn interface_mut(&mut self) -> &mut Interface<D> {
+        &mut self.interface
+    }
+
+    /// Receive an IPv4 datagram like `Interface::receive`, waiting for the device to be readable.
+    pub async fn recv(&mut self) -> Result<Packet<PooledBuffer>> {
+        loop {
+            let mut guard = self.fd.readable().await?;
+            match self.interface.receive() {
+                Err(e) if is_would_block(&e) => guard.clear_ready(),
+                result => return result,
+            }
+        }
+    }
+
+    /// Send an IPv4 datagram like `Interface::send`, waiting for the device to be writable.
+    /// The fragments of a datagram sent before the device would block are sent again.
+    pub async fn send(&mut self, packet: Packet<&[u8]>) -> Result<usize> {
+        let octets: &[u8] = packet.as_ref();
+        loop {
+            let mut guard = self.fd.writable().await?;
+            match self.interface.send(Packet::new_unchecked(octets)) {
+                Err(e) if is_would_block(&e) => guard.clear_ready(),
+                result => return result,
+            }
+        }
+    }
+
+    /// Poll the interface like `Interface::poll` once the device is readable, so the sockets make progress.
+    pub async fn poll(&mut self) -> Result<()> {
+        loop {
+            let mut guard = self.fd.readable().await?;
+            match self.interface.poll(self.interface.now()) {
+                Err(e) if is_would_block(&e) => guard.clear_ready(),
+                result => return result,
+            }
+        }
+    }
+}
@@ -1,7 +1,11 @@
-use std::net::Ipv4Addr;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::net::Ipv4Addr;
 
 use crate::checksum::checksum;
 use crate::ipv4::packet::{consts, Packet, Protocol};
+#[cfg(feature = "std")]
+use crate::pool::{BufferPool, PooledBuffer};
 
 pub struct PacketBuilder {
     version: u8,
@@ -86,18 +90,39 @@ impl PacketBuilder {
     }
 
     pub fn build_vec(mut self) -> Vec<u8> {
-        if self.total_len == 0 {
-            self.total_len = ((self.header_len * 4) as usize + self.payload.len()) as u16;
-        }
-
         let mut buffer: Vec<u8> = vec![0; (self.header_len * 4) as usize];
         buffer.append(&mut self.payload);
+        self.emit(&mut buffer);
+        buffer
+    }
+
+    pub fn build(self) -> Packet<Vec<u8>> {
+        Packet::new_unchecked(self.build_vec())
+    }
+
+    /// Build the packet into a buffer checked out of `pool`, instead of allocating it like `build`.
+    #[cfg(feature = "std")]
+    pub fn build_in(self, pool: &BufferPool) -> Packet<PooledBuffer> {
+        let header_len = (self.header_len * 4) as usize;
+        let mut buffer = pool.checkout(header_len + self.payload.len());
+        buffer.as_mut()[header_len..].copy_from_slice(&self.payload);
+        self.emit(buffer.as_mut());
+        Packet::new_unchecked(buffer)
+    }
+
+    /// Write the header in front of the payload already in `buffer`, the payload set on the builder being ignored.
+    /// The total length defaults to the length of `buffer`.
+    pub fn emit(&self, buffer: &mut [u8]) {
+        let total_len = match self.total_len {
+            0 => buffer.len() as u16,
+            total_len => total_len,
+        };
 
-        let mut packet = Packet::new_unchecked(buffer.as_mut_slice());
+        let mut packet = Packet::new_unchecked(buffer);
         packet.set_version(self.version);
         packet.set_header_len(self.header_len);
         packet.set_tos(self.tos);
-        packet.set_total_len(self.total_len);
+        packet.set_total_len(total_len);
         packet.set_identification(self.identification);
         packet.set_flags(self.flags);
         packet.set_offset(self.offset);
@@ -107,15 +132,10 @@ impl PacketBuilder {
         packet.set_src_addr(self.src_addr);
         packet.set_dest_addr(self.dest_addr);
 
+        // The header checksum covers the header only (RFC 791 section 3.1).
         if self.checksum == 0 {
-            packet.set_checksum(checksum(packet.as_ref()));
+            packet.set_checksum(checksum(&packet.as_ref()[..(self.header_len * 4) as usize]));
         }
-
-        buffer
-    }
-
-    pub fn build(self) -> Packet<Vec<u8>> {
-        Packet::new_unchecked(self.build_vec())
     }
 }
 
@@ -143,6 +163,7 @@ impl Default for PacketBuilder {
 mod tests {
     use std::net::Ipv4Addr;
 
+    use crate::checksum::checksum;
     use crate::ipv4::packet::{consts, Protocol};
 
     #[test]
@@ -182,4 +203,20 @@ mod tests {
         assert_eq!(packet.dest_addr(), dest_addr);
         assert_eq!(packet.payload(), expected_payload.clone());
     }
+
+    #[test]
+    fn header_checksum() {
+        let packet = super::PacketBuilder::default()
+            .ttl(64)
+            .protocol(Protocol::Udp)
+            .src_addr(Ipv4Addr::new(192, 168, 233, 233))
+            .dest_addr(Ipv4Addr::new(192, 168, 233, 234))
+            .payload((1..=32).collect())
+            .build();
+
+        // The header checksum covers the header only, not the payload (RFC 791 section 3.1).
+        let header_len = (packet.header_len() * 4) as usize;
+        assert_eq!(checksum(&packet.as_ref()[..header_len]), 0);
+        assert_ne!(checksum(packet.as_ref()), 0);
+    }
 }
@@ -1,4 +1,4 @@
-use std::fmt::{Display, Formatter};
+use core::fmt::{Display, Formatter};
 
 #[derive(Debug)]
 pub enum Error {
@@ -9,10 +9,12 @@ pub enum Error {
     InvalidOptionLen,
     NonFragmentablePacket,
     TryAgainLater,
+    DatagramTooLarge,
+    TooManyFragments,
 }
 
 impl Display for Error {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
         match self {
             Error::InvalidVersion => write!(f, "invalid version"),
             Error::InvalidHeaderLen => write!(f, "invalid header length"),
@@ -21,8 +23,10 @@ impl Display for Error {
             Error::InvalidOptionLen => write!(f, "invalid option length"),
             Error::NonFragmentablePacket => write!(f, "non-fragmentable packet"),
             Error::TryAgainLater => write!(f, "try again later"),
+            Error::DatagramTooLarge => write!(f, "datagram too large"),
+            Error::TooManyFragments => write!(f, "too many fragments"),
         }
     }
 }
 
-impl std::error::Error for Error {}
+impl core::error::Error for Error {}
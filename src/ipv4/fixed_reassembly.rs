@@ -0,0 +1,205 @@
+use core::net::Ipv4Addr;
+
+use crate::checksum::checksum;
+use crate::error::Result;
+use crate::ipv4::error::Error;
+use crate::ipv4::packet::{Packet, Protocol};
+use crate::macros::diagnostics::debug;
+
+pub mod consts {
+    pub const MAX_HEADER_LEN: usize = 60; // The header length counts at most 15 words of 32 bits
+    pub const MAX_RANGES: usize = 8; // Disjoint ranges of a datagram received before it is given up
+}
+
+/// The fields telling the fragments of a datagram from those of another (RFC 791 section 3.2)
+type DatagramKey = (u16, Protocol, Ipv4Addr, Ipv4Addr);
+
+/// Reassembles a datagram at a time in `N` octets, header included, so no heap is needed.
+///
+/// A fragment of another datagram replaces the one being reassembled. Having no clock,
+/// it leaves giving a datagram up after the reassembly timeout to the caller, by calling `clear`.
+pub struct FixedReassembler<const N: usize> {
+    key: Option<DatagramKey>,
+    /// The payload is stored after room for the longest header, the header being copied right before it.
+    storage: [u8; N],
+    /// The length of the header of the first fragment, zero until it is received
+    header_len: usize,
+    /// The octets of the payload received, as sorted `[start, end)` ranges neither overlapping nor touching
+    ranges: [(usize, usize); consts::MAX_RANGES],
+    range_count: usize,
+    /// Known once the last fragment is received
+    payload_len: Option<usize>,
+}
+
+impl<const N: usize> FixedReassembler<N> {
+    pub fn new() -> Self {
+        Self {
+            key: None,
+            storage: [0; N],
+            header_len: 0,
+            ranges: [(0, 0); consts::MAX_RANGES],
+            range_count: 0,
+            payload_len: None,
+        }
+    }
+
+    /// Give up the datagram being reassembled.
+    pub fn clear(&mut self) {
+        self.key = None;
+        self.header_len = 0;
+        self.range_count = 0;
+        self.payload_len = None;
+    }
+
+    /// Add `fragment` to its datagram, returns the datagram once every fragment is received.
+    /// A datagram longer than `N` octets with the longest header, or arriving in too many pieces, is given up.
+    pub fn reassemble<Buf>(&mut self, fragment: &Packet<Buf>) -> Result<Option<Packet<&[u8]>>>
+    where
+        Buf: AsRef<[u8]>,
+    {
+        let key = (
+            fragment.identification(),
+            fragment.protocol(),
+            fragment.src_addr(),
+            fragment.dest_addr(),
+        );
+        if self.key != Some(key) {
+            if let Some((identification, _, src_addr, _)) = self.key {
+                debug!(
+                    "datagram {} from {} given up for a fragment of another",
+                    identification, src_addr
+                );
+            }
+            self.clear();
+            self.key = Some(key);
+        }
+
+        let payload = fragment.payload();
+        let start = fragment.offset() as usize * 8;
+        let end = start + payload.len();
+        if consts::MAX_HEADER_LEN + end > N {
+            self.clear();
+            return Err(Error::DatagramTooLarge.into());
+        }
+
+        if start == 0 {
+            let header_len = fragment.header_len() as usize * 4;
+            self.storage[consts::MAX_HEADER_LEN - header_len..consts::MAX_HEADER_LEN]
+                .copy_from_slice(&fragment.as_ref()[..header_len]);
+            self.header_len = header_len;
+        }
+        if !fragment.more_fragments() {
+            self.payload_len = Some(end);
+        }
+        self.storage[consts::MAX_HEADER_LEN + start..consts::MAX_HEADER_LEN + end].copy_from_slice(payload);
+        if !self.insert_range(start, end) {
+            self.clear();
+            return Err(Error::TooManyFragments.into());
+        }
+
+        let payload_len = match self.payload_len {
+            Some(len) if self.header_len > 0 && self.range_count == 1 && self.ranges[0] == (0, len) => len,
+            _ => return Ok(None),
+        };
+
+        let header_len = self.header_len;
+        self.clear();
+
+        let datagram = &mut self.storage[consts::MAX_HEADER_LEN - header_len..consts::MAX_HEADER_LEN + payload_len];
+        let mut packet = Packet::new_unchecked(&mut *datagram);
+        packet.set_total_len((header_len + payload_len) as u16);
+        packet.set_more_fragments(false);
+        packet.set_offset(0);
+        packet.set_checksum(0);
+        let header_checksum = checksum(&packet.as_ref()[..header_len]);
+        packet.set_checksum(header_checksum);
+
+        Ok(Some(Packet::new_unchecked(&*datagram)))
+    }
+
+    /// Merge `[start, end)` with the ranges it overlaps or touches, returns false if there are too many ranges.
+    fn insert_range(&mut self, mut start: usize, mut end: usize) -> bool {
+        let mut ranges = [(0, 0); consts::MAX_RANGES];
+        let mut count = 0;
+        let mut inserted = false;
+
+        for &(first, last) in &self.ranges[..self.range_count] {
+            if last < start {
+                // Before the new range
+            } else if first > end {
+                if !inserted {
+                    if count == consts::MAX_RANGES {
+                        return false;
+                    }
+                    ranges[count] = (start, end);
+                    count += 1;
+                    inserted = true;
+                }
+            } else {
+                start = start.min(first);
+                end = end.max(last);
+                continue;
+            }
+
+            if count == consts::MAX_RANGES {
+                return false;
+            }
+            ranges[count] = (first, last);
+            count += 1;
+        }
+
+        if !inserted {
+            if count == consts::MAX_RANGES {
+                return false;
+            }
+            ranges[count] = (start, end);
+            count += 1;
+        }
+
+        self.ranges = ranges;
+        self.range_count = count;
+        true
+    }
+}
+
+impl<const N: usize> Default for FixedReassembler<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use super::FixedReassembler;
+    use crate::ipv4::builder::PacketBuilder;
+    use crate::ipv4::packet::Protocol;
+
+    #[test]
+    fn reassemble() {
+        let datagram = PacketBuilder::default()
+            .identification(0x1001)
+            .ttl(64)
+            .protocol(Protocol::Udp)
+            .src_addr(Ipv4Addr::new(192, 168, 233, 233))
+            .dest_addr(Ipv4Addr::new(192, 168, 233, 234))
+            .payload((0..100).collect())
+            .build();
+        let fragments: Vec<_> = datagram.fragments(68).collect();
+        assert_eq!(fragments.len(), 3);
+
+        let mut reassembler = FixedReassembler::<256>::new();
+        assert!(reassembler.reassemble(&fragments[2]).unwrap().is_none());
+        assert!(reassembler.reassemble(&fragments[0]).unwrap().is_none());
+        // A duplicate changes nothing.
+        assert!(reassembler.reassemble(&fragments[0]).unwrap().is_none());
+        let reassembled = reassembler.reassemble(&fragments[1]).unwrap().unwrap();
+        assert_eq!(reassembled.as_ref(), datagram.as_ref());
+
+        // Too long for the storage once room is left for the longest header
+        let mut reassembler = FixedReassembler::<128>::new();
+        assert!(reassembler.reassemble(&fragments[0]).unwrap().is_none());
+        assert!(reassembler.reassemble(&fragments[2]).is_err());
+    }
+}
@@ -1,12 +1,17 @@
-use crate::ipv4::builder::PacketBuilder;
-use crate::ipv4::packet::consts::MIN_HEADER_LEN;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::checksum::checksum;
+use crate::ipv4::packet::consts::{MIN_HEADER_LEN, VERSION};
 use crate::ipv4::packet::Packet;
+#[cfg(feature = "std")]
+use crate::pool::{BufferPool, PooledBuffer};
 
 impl<Buf> Packet<Buf>
 where
     Buf: AsRef<[u8]>,
 {
-    pub fn fragments(&self, mtu: usize) -> FragmentIterator {
+    pub fn fragments(&self, mtu: usize) -> FragmentIterator<'_> {
         let total_len = self.total_len() as usize;
         FragmentIterator::new(&self.as_ref()[..total_len], mtu)
     }
@@ -29,24 +34,20 @@ impl<'buf> FragmentIterator<'buf> {
             mtu,
         }
     }
-}
-
-impl<'buf> Iterator for FragmentIterator<'buf> {
-    type Item = Packet<Vec<u8>>;
 
-    /// Returns next fragment, without ip options currently.
-    fn next(&mut self) -> Option<Self::Item> {
+    /// Write the next fragment into `buf`, which must hold the MTU, instead of allocating it like `next`.
+    pub fn next_into<'a>(&mut self, buf: &'a mut [u8]) -> Option<Packet<&'a mut [u8]>> {
         if self.cursor >= self.buffer.len() {
             return None;
         }
 
         let min_header_bytes_len = (MIN_HEADER_LEN * 4) as usize;
         let remaining_bytes_len = self.buffer.len() - self.cursor;
-        let is_last = remaining_bytes_len < (self.mtu - min_header_bytes_len);
+        let is_last = remaining_bytes_len <= (self.mtu - min_header_bytes_len);
 
         let nfb = (self.mtu - min_header_bytes_len) / 8; // number of fragment blocks
         let payload_len = if is_last { remaining_bytes_len } else { nfb * 8 };
-        let payload = self.buffer[self.cursor..(self.cursor + payload_len)].to_vec();
+        let payload = &self.buffer[self.cursor..(self.cursor + payload_len)];
 
         let origin_packet = Packet::new_unchecked(self.buffer);
 
@@ -56,23 +57,49 @@ impl<'buf> Iterator for FragmentIterator<'buf> {
         let real_header_bytes_len = origin_packet.header_len() as usize * 4;
         let fragment_offset = origin_packet.offset() as usize + (self.cursor - real_header_bytes_len) / 8;
 
-        let fragment_vec = PacketBuilder::default()
-            .header_len(MIN_HEADER_LEN)
-            .tos(origin_packet.tos())
-            .total_len(((MIN_HEADER_LEN * 4) as usize + payload_len) as u16)
-            .identification(origin_packet.identification())
-            .flags(flags)
-            .offset(fragment_offset as u16)
-            .ttl(origin_packet.ttl())
-            .protocol(origin_packet.protocol())
-            .src_addr(origin_packet.src_addr())
-            .dest_addr(origin_packet.dest_addr())
-            .payload(payload)
-            .build_vec();
+        let buf = &mut buf[..min_header_bytes_len + payload_len];
+        buf[..min_header_bytes_len].fill(0);
+        buf[min_header_bytes_len..].copy_from_slice(payload);
+
+        let mut fragment = Packet::new_unchecked(buf);
+        fragment.set_version(VERSION);
+        fragment.set_header_len(MIN_HEADER_LEN);
+        fragment.set_tos(origin_packet.tos());
+        fragment.set_total_len((min_header_bytes_len + payload_len) as u16);
+        fragment.set_identification(origin_packet.identification());
+        fragment.set_flags(flags);
+        fragment.set_offset(fragment_offset as u16);
+        fragment.set_ttl(origin_packet.ttl());
+        fragment.set_protocol(origin_packet.protocol());
+        fragment.set_src_addr(origin_packet.src_addr());
+        fragment.set_dest_addr(origin_packet.dest_addr());
+        let header_checksum = checksum(&fragment.as_ref()[..min_header_bytes_len]);
+        fragment.set_checksum(header_checksum);
 
         self.cursor += payload_len;
 
-        Some(Packet::new_unchecked(fragment_vec))
+        Some(fragment)
+    }
+
+    /// Write the next fragment into a buffer checked out of `pool`, instead of allocating it like `next`.
+    #[cfg(feature = "std")]
+    pub fn next_in(&mut self, pool: &BufferPool) -> Option<Packet<PooledBuffer>> {
+        let mut buf = pool.checkout(self.mtu);
+        let len = self.next_into(buf.as_mut())?.as_ref().len();
+        buf.truncate(len);
+        Some(Packet::new_unchecked(buf))
+    }
+}
+
+impl<'buf> Iterator for FragmentIterator<'buf> {
+    type Item = Packet<Vec<u8>>;
+
+    /// Returns next fragment, without ip options currently.
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut buf = vec![0; self.mtu];
+        let len = self.next_into(&mut buf)?.as_ref().len();
+        buf.truncate(len);
+        Some(Packet::new_unchecked(buf))
     }
 }
 
@@ -111,7 +138,7 @@ mod tests {
         let second_fragment = iterator.next().unwrap();
         let third_fragment = iterator.next().unwrap();
 
-        assert_eq!(iterator.next().is_none(), true);
+        assert!(iterator.next().is_none());
         assert_eq!(iterator.mtu, min_mtu);
         assert_eq!(iterator.cursor, (payload_len + header_len * 4) as usize);
 
@@ -130,4 +157,34 @@ mod tests {
         assert_eq!(third_fragment.offset(), 12);
         assert_eq!(third_fragment.payload(), (96..100).collect::<Vec<u8>>().as_slice());
     }
+
+    #[test]
+    fn fragment_exact_fit() {
+        let min_mtu = 68;
+        let payload: Vec<u8> = (0..96).collect();
+
+        let origin_packet = PacketBuilder::default()
+            .identification(0x1001)
+            .ttl(64)
+            .protocol(Protocol::Udp)
+            .src_addr(Ipv4Addr::new(192, 168, 233, 233))
+            .dest_addr(Ipv4Addr::new(192, 168, 233, 234))
+            .payload(payload)
+            .build();
+
+        let fragments: Vec<_> = origin_packet.fragments(min_mtu).collect();
+
+        assert_eq!(fragments.len(), 2);
+        assert!(fragments[0].more_fragments());
+        assert!(!fragments[1].more_fragments());
+        assert_eq!(fragments[1].payload(), (48..96).collect::<Vec<u8>>().as_slice());
+
+        // The same fragments written into a buffer of the stack
+        let mut iterator = origin_packet.fragments(min_mtu);
+        let mut buf = [0; 68];
+        for fragment in &fragments {
+            assert_eq!(iterator.next_into(&mut buf).unwrap().as_ref(), fragment.as_ref());
+        }
+        assert!(iterator.next_into(&mut buf).is_none());
+    }
 }
@@ -0,0 +1,80 @@
+use std::time::Instant;
+
+/// Handles the packets of a protocol the interface does not implement itself, such as a private
+/// or experimental one, registered on the interface by IP protocol number or by EtherType.
+pub trait Handler: Send {
+    /// Handle a packet received at `now`, returning the packet to send back, if any.
+    ///
+    /// A handler registered for a protocol number gets the whole IP packet, once reassembled,
+    /// and replies with an IP packet of either version. One registered for an EtherType gets the packet
+    /// as read from the device, and its reply is transmitted as is.
+    fn process(&mut self, now: Instant, packet: &[u8]) -> Option<Vec<u8>>;
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+    use std::time::Instant;
+
+    use super::Handler;
+    use crate::ipv4::builder::PacketBuilder;
+    use crate::ipv4::interface::Interface;
+    use crate::ipv4::packet::{Packet, Protocol};
+    use crate::ipv4::reassembly::Reassembler;
+    use crate::net_device::channel::ChannelDevice;
+    use crate::net_device::Device;
+
+    const EXPERIMENTAL: Protocol = Protocol::Unknown(253); // RFC 3692
+
+    /// Replies to every datagram with its payload reversed.
+    struct Reverse;
+
+    impl Handler for Reverse {
+        fn process(&mut self, _now: Instant, packet: &[u8]) -> Option<Vec<u8>> {
+            let packet = Packet::new_unchecked(packet);
+            let reply = PacketBuilder::default()
+                .ttl(64)
+                .protocol(packet.protocol())
+                .src_addr(packet.dest_addr())
+                .dest_addr(packet.src_addr())
+                .payload(packet.payload().iter().rev().copied().collect())
+                .build_vec();
+            Some(reply)
+        }
+    }
+
+    #[test]
+    fn handler() {
+        let (device, mut peer) = ChannelDevice::pair();
+        peer.set_nonblocking(true);
+        let (ip_addr, peer_addr) = (Ipv4Addr::new(10, 0, 0, 1), Ipv4Addr::new(10, 0, 0, 2));
+        let mut interface = Interface::new(device, Reassembler::default());
+        interface.set_ip_addr(ip_addr);
+
+        let datagram = PacketBuilder::default()
+            .ttl(64)
+            .protocol(EXPERIMENTAL)
+            .src_addr(peer_addr)
+            .dest_addr(ip_addr)
+            .payload(b"abc".to_vec())
+            .build_vec();
+
+        // Without a handler the datagram is dropped.
+        peer.transmit(&datagram).unwrap();
+        interface.poll(Instant::now()).unwrap();
+        let mut buf = [0; 64];
+        assert!(peer.receive(&mut buf).is_err());
+
+        interface.register_handler(EXPERIMENTAL, Reverse);
+        peer.transmit(&datagram).unwrap();
+        interface.poll(Instant::now()).unwrap();
+        let len = peer.receive(&mut buf).unwrap();
+        let reply = Packet::new_checked(&buf[..len]).unwrap();
+        assert_eq!(reply.protocol(), EXPERIMENTAL);
+        assert_eq!(reply.dest_addr(), peer_addr);
+        assert_eq!(reply.payload(), b"cba");
+
+        assert!(interface.unregister_handler(EXPERIMENTAL));
+        assert!(!interface.unregister_handler(EXPERIMENTAL));
+    }
+}
@@ -1,71 +1,1741 @@
-use std::error::Error as StdError;
-use std::io::{Error as IOError, Read, Write};
-
-use log::error;
+use std::collections::{HashMap, VecDeque};
+use std::io::ErrorKind;
+use std::iter;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use crate::checksum::checksum;
-use crate::error::Result;
+use crate::clock::Clock;
+use crate::error::{Error, Result};
+use crate::ethernet::frame::EtherType;
+use crate::icmpv4::builder::ErrorBuilder;
+use crate::icmpv4::packet::{DestinationUnreachablePacketCode, ErrorMessage, TimeExceededPacketCode};
+use crate::icmpv4::rate_limit::RateLimiter;
+use crate::icmpv6::builder::ErrorBuilder as Icmpv6ErrorBuilder;
+use crate::icmpv6::packet::{
+    DestinationUnreachableCode, ErrorMessage as Icmpv6ErrorMessage, MessageType as Icmpv6MessageType,
+    Packet as Icmpv6Packet, TimeExceededCode,
+};
+use crate::ipv4::builder::PacketBuilder;
 use crate::ipv4::error::Error as Ipv4Error;
-use crate::ipv4::packet::Packet;
+use crate::ipv4::handler::Handler;
+use crate::ipv4::packet::consts::MIN_HEADER_LEN;
+use crate::ipv4::packet::{Packet, Protocol};
 use crate::ipv4::reassembly::Reassembler;
+use crate::ipv4::verify::verify_header;
+use crate::ipv6::autoconf::{Autoconf, Changes};
+use crate::ipv6::builder::PacketBuilder as Ipv6PacketBuilder;
+use crate::ipv6::error::Error as Ipv6Error;
+use crate::ipv6::packet::{consts as ipv6_consts, Packet as Ipv6Packet};
+use crate::ipv6::reassembly::Reassembler as Ipv6Reassembler;
+use crate::ipv6::repr::Repr as Ipv6Repr;
+use crate::ipv6::tunnel::Tunnel;
+use crate::macros::diagnostics::{debug, enter_span, error, trace};
+use crate::metrics::{InterfaceMetrics, Registry};
+use crate::middlebox::mangle::{
+    clamp_ipv6_mss, clamp_mss, copy_clamped, copy_clamped_ipv6, fill_header_checksum, Middlebox,
+};
+use crate::mld::error::Error as MldError;
+use crate::mld::listener::Listener;
+use crate::mld::packet::Message as MldMessage;
+use crate::ndp::advertiser::Advertiser;
+use crate::ndp::packet::consts::{ALL_NODES, ALL_ROUTERS};
+use crate::ndp::packet::{solicited_node, Message as NdpMessage, RouterAdvert};
+use crate::net_device::device::{consts as device_consts, Buffer};
 use crate::net_device::tun::TunDevice;
+use crate::net_device::Device;
+use crate::pool::{BufferPool, PooledBuffer};
+use crate::tcp::connection::Segment;
+use crate::tcp::error::Error as TcpError;
+use crate::tcp::packet::consts::MIN_HEADER_LEN as TCP_MIN_HEADER_LEN;
+use crate::tcp::socket::SocketSet;
+use crate::udp::builder::PacketBuilder as UdpPacketBuilder;
+use crate::udp::error::Error as UdpError;
+use crate::udp::socket::{Datagram, SocketSet as UdpSocketSet};
 
 pub mod consts {
-    pub const DEFAULT_MTU: usize = 1500; // Default Maximum Transmission Unit
+    use std::net::Ipv4Addr;
+    use std::time::Duration;
+
+    pub const DEFAULT_TTL: u8 = 64; // Default Time To Live of locally generated datagrams
+    pub const LOOPBACK_QUEUE_LEN: usize = 64; // Packets looped back and not received yet, more are dropped
+    pub const ALL_HOSTS_GROUP: Ipv4Addr = Ipv4Addr::new(224, 0, 0, 1); // RFC 1112 section 4, joined by every host
+    pub const MAX_POLL_WAIT: Duration = Duration::from_millis(100); // Longest a blocking socket holds the interface waiting
+    pub const MIN_MSS: u16 = 88; // Smallest MSS SYN segments are clamped to whatever the MTU, the one of Linux
+}
+
+/// How the interface clamps the MSS option of TCP SYN segments passing through it.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum MssClamp {
+    Disabled,
+    /// Clamp to the MTU minus the minimum IP and TCP header lengths, and the 6in4 header of IPv6 packets
+    /// sent through a tunnel.
+    ToMtu,
+    /// Clamp to a fixed value.
+    Fixed(u16),
 }
 
 /// The interface provided by the ipv4 module to the upper layers.
-/// Since we build the ipv4 module based on TUN device,
-/// we do not consider the scenario when it is used as a gateway currently.
-pub struct Interface {
-    device: TunDevice,
+/// It is built for a host on a TUN device, though it can act as a router through `forward`,
+/// which sends packets of other hosts out of it and reports those out of hops with ICMP errors.
+/// Any device carrying IP packets will do, the TUN device is only the default one.
+///
+/// The interface is dual-stack: packets read from the device are told apart by their version,
+/// and sockets bind to addresses of either family. A TUN device carries no link layer,
+/// so neither ARP nor Neighbor Discovery runs on it, though the prefixes of router advertisements
+/// received are used to configure addresses.
+pub struct Interface<D: Device = TunDevice> {
+    device: D,
     reassembler: Reassembler,
+    ipv6_reassembler: Ipv6Reassembler,
+    middlebox: Option<Middlebox>,
+    mss_clamp: MssClamp,
+    identification: u16,
+    ip_addr: Ipv4Addr,
+    netmask: Ipv4Addr,
+    ipv6_addrs: Vec<Ipv6Addr>,
+    /// Configures IPv6 addresses from the prefixes of router advertisements.
+    autoconf: Autoconf,
+    /// The 6in4 tunnel IPv6 packets are sent through, instead of the device itself.
+    tunnel: Option<Tunnel>,
+    /// Packets sent to the interface itself, received before anything is read from the device.
+    loopback: VecDeque<Vec<u8>>,
+    /// The multicast groups joined, with the number of sockets that joined each one.
+    groups: HashMap<IpAddr, usize>,
+    /// Reports the IPv6 groups joined to the multicast routers on the link.
+    mld: Listener,
+    /// Sends router advertisements, when the interface is the router of a downstream link.
+    advertiser: Option<Advertiser>,
+    icmp_limiter: RateLimiter,
+    sockets: SocketSet,
+    udp_sockets: UdpSocketSet,
+    /// The handlers of the IP protocols the interface does not implement itself, by protocol number.
+    handlers: HashMap<Protocol, Box<dyn Handler>>,
+    /// The handlers of the packets the device reports of other EtherTypes than IP.
+    ether_type_handlers: HashMap<EtherType, Box<dyn Handler>>,
+    /// The buffers packets are received into and datagrams built in, reused from one to the next.
+    pool: BufferPool,
+    metrics: InterfaceMetrics,
+    /// The time the blocking sockets drive the interface at, the one of the reassembler.
+    clock: Arc<dyn Clock>,
 }
 
-impl Interface {
-    pub fn new(device: TunDevice, reassembler: Reassembler) -> Self {
-        Self { device, reassembler }
+impl<D: Device> Interface<D> {
+    pub fn new(device: D, reassembler: Reassembler) -> Self {
+        Self {
+            device,
+            clock: reassembler.clock().clone(),
+            reassembler,
+            ipv6_reassembler: Ipv6Reassembler::new(),
+            middlebox: None,
+            mss_clamp: MssClamp::Disabled,
+            identification: 0,
+            ip_addr: Ipv4Addr::UNSPECIFIED,
+            netmask: Ipv4Addr::BROADCAST,
+            ipv6_addrs: vec![],
+            autoconf: Autoconf::default(),
+            tunnel: None,
+            loopback: VecDeque::new(),
+            groups: HashMap::new(),
+            mld: Listener::new(),
+            advertiser: None,
+            icmp_limiter: RateLimiter::default(),
+            sockets: SocketSet::new(),
+            udp_sockets: UdpSocketSet::new(),
+            handlers: HashMap::new(),
+            ether_type_handlers: HashMap::new(),
+            pool: BufferPool::default(),
+            metrics: InterfaceMetrics::register(&Registry::new()),
+        }
+    }
+
+    pub fn pool(&self) -> &BufferPool {
+        &self.pool
+    }
+
+    /// Use `pool`, such as one shared with other interfaces, for the buffers of the interface.
+    pub fn set_pool(&mut self, pool: BufferPool) {
+        self.pool = pool;
+    }
+
+    /// Returns the reassembler holding the fragments of incomplete IPv4 datagrams.
+    pub fn reassembler(&self) -> &Reassembler {
+        &self.reassembler
+    }
+
+    pub fn clock(&self) -> &Arc<dyn Clock> {
+        &self.clock
+    }
+
+    /// Keep time by `clock`, such as a mock one in tests, for the interface and its reassembler.
+    pub fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        self.reassembler.set_clock(clock.clone());
+        self.clock = clock;
+    }
+
+    /// Returns the time by the clock of the interface.
+    pub fn now(&self) -> Instant {
+        self.clock.now()
+    }
+
+    pub fn metrics(&self) -> &InterfaceMetrics {
+        &self.metrics
+    }
+
+    /// Count into `registry` from now on, such as one shared with the device and other interfaces.
+    pub fn set_metrics(&mut self, registry: &Registry) {
+        self.metrics = InterfaceMetrics::register(registry);
+    }
+
+    pub fn device(&self) -> &D {
+        &self.device
+    }
+
+    pub fn device_mut(&mut self) -> &mut D {
+        &mut self.device
+    }
+
+    /// Returns the IPv4 address used as the source of locally initiated connections.
+    pub fn ip_addr(&self) -> Ipv4Addr {
+        self.ip_addr
+    }
+
+    /// Set the address of the interface, which should be the one configured on the TUN device.
+    pub fn set_ip_addr(&mut self, ip_addr: Ipv4Addr) {
+        self.ip_addr = ip_addr;
+    }
+
+    pub fn netmask(&self) -> Ipv4Addr {
+        self.netmask
+    }
+
+    /// Set the netmask of the network the interface is on, which gives its subnet broadcast address.
+    pub fn set_netmask(&mut self, netmask: Ipv4Addr) {
+        self.netmask = netmask;
+    }
+
+    /// Returns the IPv6 addresses of the interface.
+    pub fn ipv6_addrs(&self) -> &[Ipv6Addr] {
+        &self.ipv6_addrs
+    }
+
+    /// Add an IPv6 address, which should be configured on the TUN device as well.
+    /// Its solicited-node multicast group is joined along with it.
+    pub fn add_ipv6_addr(&mut self, addr: Ipv6Addr) {
+        if !self.ipv6_addrs.contains(&addr) {
+            self.ipv6_addrs.push(addr);
+            self.join_multicast_group(solicited_node(addr));
+        }
+    }
+
+    /// Remove an IPv6 address, returns whether the interface had it.
+    pub fn remove_ipv6_addr(&mut self, addr: Ipv6Addr) -> bool {
+        let len = self.ipv6_addrs.len();
+        self.ipv6_addrs.retain(|ipv6_addr| *ipv6_addr != addr);
+        if self.ipv6_addrs.len() == len {
+            return false;
+        }
+        self.leave_multicast_group(solicited_node(addr));
+        true
+    }
+
+    pub fn autoconf(&self) -> &Autoconf {
+        &self.autoconf
+    }
+
+    pub fn autoconf_mut(&mut self) -> &mut Autoconf {
+        &mut self.autoconf
+    }
+
+    /// Send router advertisements with the advertiser, or stop sending them.
+    /// They are sent from a link-local address, so the interface needs one.
+    /// The all-routers group, which router solicitations are sent to, is joined while advertising.
+    pub fn set_router_advertiser(&mut self, advertiser: Option<Advertiser>) {
+        match (self.advertiser.is_some(), advertiser.is_some()) {
+            (false, true) => {
+                self.join_multicast_group(ALL_ROUTERS);
+            }
+            (true, false) => {
+                self.leave_multicast_group(ALL_ROUTERS);
+            }
+            _ => {}
+        }
+        self.advertiser = advertiser;
+    }
+
+    pub fn router_advertiser_mut(&mut self) -> Option<&mut Advertiser> {
+        self.advertiser.as_mut()
+    }
+
+    /// Configure addresses from the autonomous prefixes of a router advertisement.
+    pub fn process_router_advert(&mut self, advert: &RouterAdvert, now: Instant) {
+        for prefix in advert.prefixes.iter() {
+            let changes = self.autoconf.process_prefix(prefix, now);
+            self.apply_autoconf(changes);
+        }
+    }
+
+    fn apply_autoconf(&mut self, changes: Changes) {
+        for addr in changes.added {
+            self.add_ipv6_addr(addr);
+        }
+        for addr in changes.removed {
+            self.remove_ipv6_addr(addr);
+        }
+    }
+
+    /// Returns the address of the interface to send to `dest_addr` from, of the same family.
+    /// An IPv6 address of the same scope is preferred, link-local or not (RFC 6724 section 5 rule 2),
+    /// then one which is not deprecated (rule 3), then a temporary one (rule 7)
+    /// unless temporary addresses are disabled.
+    pub fn source_addr(&self, dest_addr: IpAddr) -> Option<IpAddr> {
+        match dest_addr {
+            dest_addr if dest_addr.is_loopback() => Some(dest_addr),
+            IpAddr::V4(_) => Some(self.ip_addr.into()).filter(|addr: &IpAddr| !addr.is_unspecified()),
+            IpAddr::V6(dest_addr) => {
+                let link_local = dest_addr.is_unicast_link_local() || (dest_addr.segments()[0] & 0xff0f) == 0xff02;
+                let prefer_temporary = self.autoconf.temporary_addrs();
+                self.ipv6_addrs
+                    .iter()
+                    .min_by_key(|addr| {
+                        let address = self.autoconf.addr(**addr);
+                        (
+                            addr.is_unicast_link_local() != link_local,
+                            address.is_some_and(|address| address.deprecated),
+                            prefer_temporary && !address.is_some_and(|address| address.temporary),
+                        )
+                    })
+                    .map(|addr| (*addr).into())
+            }
+        }
+    }
+
+    /// Whether packets to `addr` are looped back instead of being written to the device:
+    /// loopback addresses and the addresses of the interface itself.
+    pub fn is_loopback(&self, addr: impl Into<IpAddr>) -> bool {
+        match addr.into() {
+            IpAddr::V4(addr) => addr.is_loopback() || (!addr.is_unspecified() && addr == self.ip_addr),
+            IpAddr::V6(addr) => addr.is_loopback() || self.ipv6_addrs.contains(&addr),
+        }
+    }
+
+    /// Queue a packet sent to the interface itself, to be received by the next `poll`.
+    fn loop_back(&mut self, packet: &[u8]) {
+        if self.loopback.len() >= consts::LOOPBACK_QUEUE_LEN {
+            debug!("loopback queue full, packet dropped");
+            return;
+        }
+        self.loopback.push_back(packet.to_vec());
+    }
+
+    /// Whether `addr` is the limited broadcast address or the broadcast address of the subnet.
+    pub fn is_broadcast(&self, addr: Ipv4Addr) -> bool {
+        let netmask = u32::from(self.netmask);
+        addr.is_broadcast() || (netmask != u32::MAX && u32::from(addr) == u32::from(self.ip_addr) | !netmask)
+    }
+
+    /// Join the multicast `group` on behalf of a socket, returns whether the interface was not a member yet.
+    ///
+    /// The interface stays a member until every socket that joined the group left it,
+    /// this is the membership IGMP reports on. MLD reports the IPv6 groups.
+    pub fn join_multicast_group(&mut self, group: impl Into<IpAddr>) -> bool {
+        let group = group.into();
+        let members = self.groups.entry(group).or_insert(0);
+        *members += 1;
+        if *members > 1 {
+            return false;
+        }
+        if let IpAddr::V6(group) = group {
+            self.mld.join(group);
+        }
+        true
+    }
+
+    /// Leave the multicast `group` on behalf of a socket, returns whether the interface is no longer a member.
+    pub fn leave_multicast_group(&mut self, group: impl Into<IpAddr>) -> bool {
+        let group = group.into();
+        match self.groups.get_mut(&group) {
+            Some(members) if *members > 1 => {
+                *members -= 1;
+                false
+            }
+            Some(_) => {
+                self.groups.remove(&group);
+                if let IpAddr::V6(group) = group {
+                    self.mld.leave(group);
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Whether datagrams sent to the multicast `group` are received,
+    /// the all-hosts group and the all-nodes group are always joined.
+    pub fn is_multicast_member(&self, group: impl Into<IpAddr>) -> bool {
+        let group = group.into();
+        group == consts::ALL_HOSTS_GROUP || group == ALL_NODES || self.groups.contains_key(&group)
+    }
+
+    /// Limit the rate of the ICMP error messages sent by the interface.
+    pub fn set_icmp_rate_limiter(&mut self, icmp_limiter: RateLimiter) {
+        self.icmp_limiter = icmp_limiter;
+    }
+
+    /// Returns the TCP sockets bound to the interface.
+    pub fn sockets(&self) -> &SocketSet {
+        &self.sockets
+    }
+
+    pub fn sockets_mut(&mut self) -> &mut SocketSet {
+        &mut self.sockets
+    }
+
+    /// Returns the UDP sockets bound to the interface.
+    pub fn udp_sockets(&self) -> &UdpSocketSet {
+        &self.udp_sockets
+    }
+
+    pub fn udp_sockets_mut(&mut self) -> &mut UdpSocketSet {
+        &mut self.udp_sockets
+    }
+
+    /// Hand the IPv4 datagrams and IPv6 packets of `protocol` to `handler`, instead of the interface
+    /// handling them itself or dropping them.
+    pub fn register_handler(&mut self, protocol: Protocol, handler: impl Handler + 'static) {
+        self.handlers.insert(protocol, Box::new(handler));
+    }
+
+    /// Returns whether a handler was registered for `protocol`.
+    pub fn unregister_handler(&mut self, protocol: Protocol) -> bool {
+        self.handlers.remove(&protocol).is_some()
+    }
+
+    /// Hand the packets the device reports of `ether_type` to `handler`, instead of dropping them.
+    pub fn register_ether_type_handler(&mut self, ether_type: EtherType, handler: impl Handler + 'static) {
+        self.ether_type_handlers.insert(ether_type, Box::new(handler));
+    }
+
+    /// Returns whether a handler was registered for `ether_type`.
+    pub fn unregister_ether_type_handler(&mut self, ether_type: EtherType) -> bool {
+        self.ether_type_handlers.remove(&ether_type).is_some()
+    }
+
+    /// Run IPv6 over an IPv4 path: packets are sent through the 6in4 `tunnel`,
+    /// and the ones it carries back are received as if read from the device.
+    pub fn set_tunnel(&mut self, tunnel: Option<Tunnel>) {
+        self.tunnel = tunnel;
+    }
+
+    pub fn tunnel(&self) -> Option<Tunnel> {
+        self.tunnel
+    }
+
+    /// Install a middlebox hook, which mangles every IPv4 datagram sent, received or forwarded by the interface.
+    pub fn set_middlebox(&mut self, middlebox: Option<Middlebox>) {
+        self.middlebox = middlebox;
+    }
+
+    /// Configure MSS clamping of TCP SYN segments of either version, sent, received and forwarded,
+    /// which avoids PMTUD blackholes when the path MTU is smaller than the peer expects.
+    pub fn set_mss_clamp(&mut self, mss_clamp: MssClamp) {
+        self.mss_clamp = mss_clamp;
+    }
+
+    /// The MSS SYN segments are clamped to, behind IP headers of `ip_header_len` octets.
+    fn clamped_mss(&self, ip_header_len: usize) -> Option<u16> {
+        match self.mss_clamp {
+            MssClamp::Disabled => None,
+            MssClamp::ToMtu => {
+                let headers_len = ip_header_len + (TCP_MIN_HEADER_LEN * 4) as usize;
+                let mss = self.device.mtu().saturating_sub(headers_len).min(u16::MAX as usize) as u16;
+                Some(mss.max(consts::MIN_MSS))
+            }
+            MssClamp::Fixed(mss) => Some(mss),
+        }
+    }
+
+    /// The IP headers in front of the TCP segments of IPv6 packets, with the 6in4 one through a tunnel.
+    fn ipv6_headers_len(&self) -> usize {
+        let tunnel_header_len = self.tunnel.map_or(0, |_| (MIN_HEADER_LEN * 4) as usize);
+        ipv6_consts::HEADER_LEN + tunnel_header_len
+    }
+
+    /// Clamp the MSS of an IPv6 packet, returns `None` when the packet is left as is.
+    fn clamp_ipv6(&self, packet: &[u8]) -> Option<Vec<u8>> {
+        let mss = self.clamped_mss(self.ipv6_headers_len())?;
+        copy_clamped_ipv6(packet, mss).map(Ipv6Packet::into_inner)
+    }
+
+    /// Apply the middlebox hook and MSS clamping, returns `None` when the packet is left as is.
+    /// The packet is only copied when it is rewritten.
+    fn rewrite(&mut self, packet: &[u8]) -> Option<Packet<Vec<u8>>> {
+        let mangled = self.middlebox.as_mut().and_then(|middlebox| middlebox.process(packet));
+        let mss = match self.clamped_mss((MIN_HEADER_LEN * 4) as usize) {
+            Some(mss) => mss,
+            None => return mangled,
+        };
+
+        match mangled {
+            Some(mut mangled) => {
+                clamp_mss(&mut mangled, mss);
+                Some(mangled)
+            }
+            None => copy_clamped(packet, mss),
+        }
+    }
+
+    /// Whether a datagram of `len` octets to `dest_addr` goes to the device as built,
+    /// being neither looped back, rewritten nor fragmented.
+    fn transmits_in_place(&self, dest_addr: Ipv4Addr, len: usize) -> bool {
+        !self.is_loopback(dest_addr)
+            && self.middlebox.is_none()
+            && self.mss_clamp == MssClamp::Disabled
+            && len <= self.device.mtu()
     }
 
     pub fn send(&mut self, packet: Packet<&[u8]>) -> Result<usize> {
+        let mangled = self.rewrite(packet.as_ref());
+        let packet = match mangled.as_ref() {
+            Some(mangled) => Packet::new_unchecked(mangled.as_ref()),
+            None => packet,
+        };
         let octets = packet.as_ref();
+        enter_span!("send", dest_addr = %packet.dest_addr(), len = octets.len());
 
-        if octets.len() > consts::DEFAULT_MTU {
+        if self.is_loopback(packet.dest_addr()) {
+            trace!("datagram looped back");
+            self.loop_back(octets);
+            Ok(octets.len())
+        } else if octets.len() > self.device.mtu() {
             if packet.dont_fragment() {
+                debug!("datagram dropped: longer than the MTU and not to be fragmented");
+                self.metrics.datagrams_dropped.inc();
                 Err(Ipv4Error::NonFragmentablePacket.into())
             } else {
-                for fragment in packet.fragments(consts::DEFAULT_MTU) {
-                    let map_err_fn = |e: IOError| -> Box<dyn StdError> { e.into() };
-                    self.device.write(fragment.as_ref()).map_err(map_err_fn)?;
-                }
+                let mut iterator = packet.fragments(self.device.mtu());
+                let fragments: Vec<_> = iter::from_fn(|| iterator.next_in(&self.pool)).collect();
+                let fragments: Vec<&[u8]> = fragments.iter().map(|fragment| fragment.as_ref()).collect();
+                trace!("datagram fragmented into {} fragments", fragments.len());
+                self.device.transmit_batch(&fragments)?;
+                self.metrics.fragments_transmitted.add(fragments.len() as u64);
+                self.metrics.packets_transmitted.add(fragments.len() as u64);
                 Ok(octets.len())
             }
         } else {
-            self.device.write(octets).map_err(|e| e.into())
+            self.transmit(octets)?;
+            trace!("datagram transmitted");
+            Ok(octets.len())
         }
     }
 
-    pub fn receive(&mut self) -> Result<Packet<Vec<u8>>> {
-        let mut buf: Vec<u8> = vec![0; consts::DEFAULT_MTU];
-        let read_byte_number = self.device.read(buf.as_mut_slice())?;
-        buf.resize(read_byte_number, 0);
+    /// Send a packet of either version routed from another interface out of this one, the egress one,
+    /// so its SYN segments are clamped to the MTU of this device. Its time to live or hop limit is
+    /// decremented, and the packet is dropped when that runs out. Its source is then told with an ICMP
+    /// Time Exceeded error (RFC 1812 section 5.3.1, RFC 4443 section 3.3) sent out of this interface too,
+    /// from its own address, which the host routes back to the source through a TUN device.
+    pub fn forward(&mut self, packet: &[u8]) -> Result<()> {
+        let now = self.now();
+        if packet.first().map(|octet| octet >> 4) == Some(ipv6_consts::VERSION) {
+            let mut packet = Ipv6Packet::new_checked(packet.to_vec())?;
+            if packet.hop_limit() <= 1 {
+                debug!("forwarded packet to {} dropped: hop limit exceeded", packet.dest_addr());
+                self.metrics.datagrams_dropped.inc();
+                if let Some(IpAddr::V6(src_addr)) = self.source_addr(packet.src_addr().into()) {
+                    let message = Icmpv6ErrorMessage::TimeExceeded(TimeExceededCode::HopLimitExceeded);
+                    self.send_icmpv6_error(now, message, src_addr, &Ipv6Packet::new_unchecked(packet.as_ref()))?;
+                }
+                return Ok(());
+            }
+            packet.set_hop_limit(packet.hop_limit() - 1);
+            self.send_ipv6(packet.as_ref())
+        } else {
+            let mut packet = Packet::new_checked(packet.to_vec())?;
+            if packet.ttl() <= 1 {
+                debug!(
+                    "forwarded datagram to {} dropped: time to live exceeded",
+                    packet.dest_addr()
+                );
+                self.metrics.datagrams_dropped.inc();
+                if let Some(IpAddr::V4(src_addr)) = self.source_addr(packet.src_addr().into()) {
+                    let message = ErrorMessage::TimeExceeded(TimeExceededPacketCode::TtlExceededInTransit);
+                    self.send_icmp_error(now, message, src_addr, &packet)?;
+                }
+                return Ok(());
+            }
+            packet.set_ttl(packet.ttl() - 1);
+            fill_header_checksum(&mut packet);
+            self.send(Packet::new_unchecked(packet.as_ref())).map(|_| ())
+        }
+    }
 
-        let packet = Packet::new_checked(buf)?;
-        let checksum_value = checksum(&packet.as_ref()[..(packet.header_len() * 4) as usize]);
+    fn transmit(&mut self, packet: &[u8]) -> Result<()> {
+        self.device.transmit(packet)?;
+        self.metrics.packets_transmitted.inc();
+        Ok(())
+    }
+
+    /// Read a packet of either version, looped back packets first and then from the device.
+    fn read(&mut self) -> Result<PooledBuffer> {
+        if let Some(packet) = self.loopback.pop_front() {
+            return Ok(PooledBuffer::from(packet));
+        }
+
+        let buffer = self.device.receive_token(&self.pool)?.into_buffer();
+        self.metrics.packets_received.inc();
+        Ok(buffer)
+    }
+
+    /// Read the packets queued, the looped back ones if any and else a batch from the device.
+    fn read_batch(&mut self) -> Result<Vec<Buffer>> {
+        if !self.loopback.is_empty() {
+            return Ok(self.loopback.drain(..).map(Buffer::from).collect());
+        }
+
+        let mtu = self.device.mtu();
+        let mut bufs: Vec<_> = (0..device_consts::BATCH_LEN)
+            .map(|_| Buffer::from_pool(self.pool.checkout(mtu)))
+            .collect();
+        let received = self.device.recv_batch(&mut bufs)?;
+        trace!("{} packets received from the device", received);
+        self.metrics.packets_received.add(received as u64);
+        bufs.truncate(received);
+        Ok(bufs)
+    }
+
+    /// Receive an IPv4 datagram, reassembled from its fragments.
+    pub fn receive(&mut self) -> Result<Packet<PooledBuffer>> {
+        let buf = self.read()?;
+        self.receive_ipv4(buf)
+    }
 
-        if packet.checksum() != checksum_value {
+    fn receive_ipv4(&mut self, buf: PooledBuffer) -> Result<Packet<PooledBuffer>> {
+        let packet = Packet::new_checked(buf)?;
+        if !verify_header(&packet) {
             error!("Invalid checksum, ip packet dropped.");
+            self.metrics.checksum_errors.inc();
             return Err(Ipv4Error::InvalidChecksum.into());
         }
 
         // If the packet is a whole datagram, return it directly.
-        if packet.offset() == 0 && !packet.more_fragments() {
+        let datagram = if packet.offset() == 0 && !packet.more_fragments() {
             self.reassembler.release(packet.datagram_id());
-            return Ok(packet);
+            packet
+        } else {
+            // Fragments wait for the others out of the pool.
+            let fragment = Packet::new_unchecked(packet.into_inner().into_vec());
+            self.metrics.fragments_received.inc();
+            let datagram = self.reassembler.reassemble(fragment);
+            self.metrics.reassembly_pending.set(self.reassembler.len() as i64);
+            let datagram = datagram.ok_or(Ipv4Error::TryAgainLater)?;
+            self.metrics.datagrams_reassembled.inc();
+            Packet::new_unchecked(PooledBuffer::from(datagram.into_inner()))
+        };
+
+        match self.rewrite(datagram.as_ref()) {
+            Some(rewritten) => Ok(Packet::new_unchecked(PooledBuffer::from(rewritten.into_inner()))),
+            None => Ok(datagram),
+        }
+    }
+
+    /// Receive a batch of datagrams, hand TCP segments and UDP datagrams to the sockets and send whatever the sockets have to send.
+    /// Call it whenever the device is readable or a socket timer expires.
+    ///
+    /// Every datagram of the batch is processed even if one fails, and the sockets send even if
+    /// receiving failed, such as with `WouldBlock` on a non-blocking device. The first failure is returned.
+    pub fn poll(&mut self, now: Instant) -> Result<()> {
+        enter_span!("poll");
+        self.reassembler.poll();
+        self.ipv6_reassembler.poll(now);
+
+        let mut result = Ok(());
+        match self.read_batch() {
+            Ok(bufs) => {
+                for buf in bufs {
+                    if let Err(e) = self.process(now, buf) {
+                        result = result.and(Err(e));
+                    }
+                }
+            }
+            Err(e) => result = Err(e),
+        }
+
+        let dispatched = self.dispatch(now);
+        self.metrics.reassembly_pending.set(self.reassembler.len() as i64);
+        self.metrics.tcp_connections.set(self.sockets.handles().count() as i64);
+        result.and(dispatched)
+    }
+
+    /// Returns when a timer of the TCP connections expires, by the clock of the interface.
+    pub fn poll_at(&self) -> Option<Instant> {
+        self.sockets.poll_at()
+    }
+
+    /// Wait up to `timeout` for a packet, or until `poll_at` if sooner, then poll the interface.
+    /// Unlike `poll` it never waits longer, and a device with nothing to receive is not a failure,
+    /// so the timers run even while the peer is silent.
+    pub fn poll_wait(&mut self, timeout: Duration) -> Result<()> {
+        let now = self.now();
+        let timeout = self
+            .poll_at()
+            .map_or(timeout, |at| timeout.min(at.saturating_duration_since(now)));
+
+        if !self.loopback.is_empty() || self.device.wait(timeout)? {
+            return match self.poll(self.now()) {
+                Err(Error::Io(e)) if e.kind() == ErrorKind::WouldBlock => Ok(()),
+                result => result,
+            };
+        }
+        self.dispatch(self.now())
+    }
+
+    /// Process a packet of either version read from the device,
+    /// told apart by the protocol the device reports or else by the version of the packet.
+    fn process(&mut self, now: Instant, buf: Buffer) -> Result<()> {
+        let is_ipv6 = match buf.protocol() {
+            Some(EtherType::Ipv6) => true,
+            Some(EtherType::Ipv4) => false,
+            Some(protocol) => {
+                let reply = match self.ether_type_handlers.get_mut(&protocol) {
+                    Some(handler) => handler.process(now, buf.packet()),
+                    None => {
+                        debug!("packet of protocol {:?} dropped", protocol);
+                        return Ok(());
+                    }
+                };
+                if let Some(reply) = reply {
+                    self.transmit(&reply)?;
+                }
+                return Ok(());
+            }
+            None => buf.packet().first().map(|octet| octet >> 4) == Some(ipv6_consts::VERSION),
+        };
+        if is_ipv6 {
+            return self.process_ipv6(now, buf.packet());
+        }
+
+        match self.receive_ipv4(buf.into_pooled()) {
+            Ok(datagram) => self.process_ipv4(now, datagram),
+            Err(e) => {
+                if !matches!(e, Error::Ipv4(Ipv4Error::TryAgainLater)) {
+                    debug!("datagram dropped: {}", e);
+                    self.metrics.datagrams_dropped.inc();
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Hand the TCP segment, UDP datagram or ICMP message of an IPv4 datagram to the sockets.
+    /// Datagrams to addresses or groups the interface does not have are dropped, though any unicast one
+    /// is accepted before the interface has an address, as while it is configured through DHCP.
+    fn process_ipv4(&mut self, now: Instant, datagram: Packet<PooledBuffer>) -> Result<()> {
+        let dest_addr = datagram.dest_addr();
+        if dest_addr.is_multicast() && !self.is_multicast_member(dest_addr) {
+            trace!("datagram to {} dropped: group not joined", dest_addr);
+            self.metrics.datagrams_dropped.inc();
+            return Ok(());
+        }
+        let local = self.ip_addr.is_unspecified() || self.is_loopback(dest_addr) || self.is_broadcast(dest_addr);
+        if !dest_addr.is_multicast() && !local {
+            trace!("datagram to {} dropped: not an address of the interface", dest_addr);
+            self.metrics.datagrams_dropped.inc();
+            return Ok(());
+        }
+        trace!(
+            "datagram of {:?} from {} to {} accepted",
+            datagram.protocol(),
+            datagram.src_addr(),
+            dest_addr
+        );
+
+        if let Some(handler) = self.handlers.get_mut(&datagram.protocol()) {
+            if let Some(reply) = handler.process(now, datagram.as_ref()) {
+                self.send_reply(&reply)?;
+            }
+            return Ok(());
+        }
+
+        match datagram.protocol() {
+            Protocol::Tcp => {
+                self.metrics.tcp_segments_received.inc();
+                match self
+                    .sockets
+                    .process(now, datagram.src_addr(), datagram.dest_addr(), datagram.payload())
+                {
+                    Ok(Some(reply)) => self.send_segment(&reply)?,
+                    Ok(None) => {}
+                    Err(e) => {
+                        debug!("tcp segment dropped: {}", e);
+                        self.metrics.tcp_segments_dropped.inc();
+                    }
+                }
+            }
+            Protocol::Udp => {
+                self.metrics.udp_datagrams_received.inc();
+                let result = if dest_addr.is_multicast() || self.is_broadcast(dest_addr) {
+                    self.udp_sockets
+                        .process_multicast(datagram.src_addr(), dest_addr, datagram.payload())
+                        .map(|_| ())
+                } else {
+                    self.udp_sockets
+                        .process(datagram.src_addr(), dest_addr, datagram.payload())
+                        .map(|_| ())
+                };
+                match result {
+                    // Only unicast datagrams get an error (RFC 1122 section 4.1.3.1).
+                    Err(e)
+                        if matches!(e, Error::Udp(UdpError::PortUnreachable))
+                            && !dest_addr.is_multicast()
+                            && !self.is_broadcast(dest_addr) =>
+                    {
+                        let message = ErrorMessage::Unreachable(DestinationUnreachablePacketCode::PortUnreachable);
+                        self.send_icmp_error(now, message, dest_addr, &datagram)?;
+                    }
+                    Err(e) => debug!("udp datagram dropped: {}", e),
+                    Ok(()) => {}
+                }
+            }
+            Protocol::Icmp => self.process_icmp(datagram.payload()),
+            Protocol::Ipv6 => match self.tunnel.map(|tunnel| tunnel.decapsulate(&datagram)) {
+                Some(Ok(packet)) => self.process_ipv6(now, packet)?,
+                Some(Err(e)) => debug!("6in4 datagram dropped: {}", e),
+                None => {}
+            },
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    /// Hand the TCP segment or UDP datagram of an IPv6 packet to the sockets, once reassembled
+    /// and past its extension headers. Packets to addresses or groups the interface does not have are dropped.
+    fn process_ipv6(&mut self, now: Instant, buffer: &[u8]) -> Result<()> {
+        let packet = Ipv6Packet::new_unchecked(buffer);
+        let dest_addr = match Ipv6Repr::parse(&packet) {
+            Ok(repr) => repr.dest_addr,
+            Err(e) => {
+                debug!("ipv6 packet dropped: {}", e);
+                return Ok(());
+            }
+        };
+        let local = if dest_addr.is_multicast() {
+            self.is_multicast_member(dest_addr)
+        } else {
+            dest_addr.is_loopback() || self.ipv6_addrs.contains(&dest_addr)
+        };
+        if !local {
+            return Ok(());
         }
 
-        self.reassembler
-            .reassemble(packet)
-            .ok_or_else(|| Ipv4Error::TryAgainLater.into())
+        let buffer = match self.ipv6_reassembler.reassemble(&packet, now) {
+            Ok(Some(buffer)) => buffer,
+            Ok(None) => return Ok(()),
+            Err(e) => {
+                debug!("ipv6 fragment dropped: {}", e);
+                return Ok(());
+            }
+        };
+        let buffer = match self.clamped_mss(self.ipv6_headers_len()) {
+            Some(mss) => {
+                let mut packet = Ipv6Packet::new_unchecked(buffer);
+                clamp_ipv6_mss(&mut packet, mss);
+                packet.into_inner()
+            }
+            None => buffer,
+        };
+        let packet = Ipv6Packet::new_unchecked(buffer.as_slice());
+        let mut headers = packet.extension_headers();
+        if let Some(Err(e)) = headers.find(|header| header.is_err()) {
+            debug!("ipv6 packet dropped: {}", e);
+            return Ok(());
+        }
+        let (protocol, offset) = headers.upper_layer();
+        let (src_addr, payload) = (packet.src_addr(), &packet.payload()[offset..]);
+
+        if let Some(handler) = self.handlers.get_mut(&protocol) {
+            if let Some(reply) = handler.process(now, packet.as_ref()) {
+                self.send_reply(&reply)?;
+            }
+            return Ok(());
+        }
+
+        match protocol {
+            Protocol::Tcp => {
+                self.metrics.tcp_segments_received.inc();
+                match self.sockets.process(now, src_addr, dest_addr, payload) {
+                    Ok(Some(reply)) => self.send_segment(&reply)?,
+                    Ok(None) => {}
+                    Err(e) => {
+                        debug!("tcp segment dropped: {}", e);
+                        self.metrics.tcp_segments_dropped.inc();
+                    }
+                }
+            }
+            Protocol::Udp => {
+                self.metrics.udp_datagrams_received.inc();
+                let result = if dest_addr.is_multicast() {
+                    self.udp_sockets
+                        .process_multicast(src_addr, dest_addr, payload)
+                        .map(|_| ())
+                } else {
+                    self.udp_sockets.process(src_addr, dest_addr, payload).map(|_| ())
+                };
+                match result {
+                    // No error is sent about a packet to a multicast address (RFC 4443 section 2.4).
+                    Err(e) if matches!(e, Error::Udp(UdpError::PortUnreachable)) && !dest_addr.is_multicast() => {
+                        let message = Icmpv6ErrorMessage::Unreachable(DestinationUnreachableCode::PortUnreachable);
+                        self.send_icmpv6_error(now, message, dest_addr, &packet)?;
+                    }
+                    Err(e) => debug!("udp datagram dropped: {}", e),
+                    Ok(()) => {}
+                }
+            }
+            Protocol::Icmpv6 => self.process_icmpv6(now, src_addr, dest_addr, packet.hop_limit(), payload),
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    /// Handle the ICMPv6 messages the interface takes part in: Multicast Listener Discovery,
+    /// router advertisements whose prefixes addresses are configured from,
+    /// and router solicitations when it advertises itself.
+    fn process_icmpv6(&mut self, now: Instant, src_addr: Ipv6Addr, dest_addr: Ipv6Addr, hop_limit: u8, message: &[u8]) {
+        let r#type = match Icmpv6Packet::new_checked(message) {
+            Ok(packet) => packet.r#type(),
+            Err(e) => {
+                debug!("icmpv6 message dropped: {}", e);
+                return;
+            }
+        };
+
+        if matches!(
+            r#type,
+            Icmpv6MessageType::RouterAdvertisement | Icmpv6MessageType::RouterSolicitation
+        ) {
+            match NdpMessage::parse(src_addr, dest_addr, hop_limit, message) {
+                Ok(NdpMessage::RouterAdvert(advert)) => self.process_router_advert(&advert, now),
+                Ok(NdpMessage::RouterSolicit { .. }) => {
+                    if let Some(advertiser) = self.advertiser.as_mut() {
+                        advertiser.process_solicit(now);
+                    }
+                }
+                Ok(_) => {}
+                Err(e) => debug!("neighbor discovery message dropped: {}", e),
+            }
+            return;
+        }
+        match MldMessage::parse(src_addr, dest_addr, hop_limit, message) {
+            Ok(message) => self.mld.process(&message, now),
+            Err(Error::Mld(MldError::InvalidMessageType)) => {}
+            Err(e) => debug!("mld message dropped: {}", e),
+        }
+    }
+
+    /// Deliver an ICMP error to the socket that sent the quoted datagram.
+    fn process_icmp(&mut self, message: &[u8]) {
+        if checksum(message) != 0 {
+            debug!("icmp message dropped: invalid checksum");
+            return;
+        }
+
+        match ErrorMessage::parse(message) {
+            Ok(Some((message, quoted))) => match quoted.protocol {
+                Protocol::Tcp => self.sockets.process_icmp(message, &quoted),
+                Protocol::Udp => self.udp_sockets.process_icmp(message, &quoted),
+                _ => {}
+            },
+            Ok(None) => {}
+            Err(e) => debug!("icmp message dropped: {}", e),
+        }
+    }
+
+    /// Send an ICMP error message from `src_addr` about a received datagram, unless the rate limiter holds it back
+    /// or the datagram is one no error may be sent about (RFC 1122 section 3.2.2).
+    fn send_icmp_error<Buf: AsRef<[u8]>>(
+        &mut self,
+        now: Instant,
+        message: ErrorMessage,
+        src_addr: Ipv4Addr,
+        datagram: &Packet<Buf>,
+    ) -> Result<()> {
+        let dest_addr = datagram.src_addr();
+        if dest_addr.is_unspecified() || dest_addr.is_multicast() || self.is_broadcast(dest_addr) {
+            return Ok(());
+        }
+        if !self.icmp_limiter.allow(now) {
+            debug!("icmp error to {} rate limited", dest_addr);
+            self.metrics.icmp_errors_rate_limited.inc();
+            return Ok(());
+        }
+
+        let payload = ErrorBuilder::default()
+            .message(message)
+            .datagram(datagram.as_ref())
+            .build_vec();
+
+        self.identification = self.identification.wrapping_add(1);
+        let packet = PacketBuilder::default()
+            .identification(self.identification)
+            .ttl(consts::DEFAULT_TTL)
+            .protocol(Protocol::Icmp)
+            .src_addr(src_addr)
+            .dest_addr(dest_addr)
+            .payload(payload)
+            .build_in(&self.pool);
+
+        self.send(Packet::new_unchecked(packet.as_ref()))?;
+        self.metrics.icmp_errors_sent.inc();
+        Ok(())
+    }
+
+    /// Send an ICMPv6 error message from `src_addr` about a received packet, unless the rate limiter holds it back
+    /// or the packet came from the unspecified address.
+    fn send_icmpv6_error(
+        &mut self,
+        now: Instant,
+        message: Icmpv6ErrorMessage,
+        src_addr: Ipv6Addr,
+        packet: &Ipv6Packet<&[u8]>,
+    ) -> Result<()> {
+        let dest_addr = packet.src_addr();
+        if dest_addr.is_unspecified() {
+            return Ok(());
+        }
+        if !self.icmp_limiter.allow(now) {
+            debug!("icmpv6 error to {} rate limited", dest_addr);
+            self.metrics.icmp_errors_rate_limited.inc();
+            return Ok(());
+        }
+
+        let payload = Icmpv6ErrorBuilder::default()
+            .message(message)
+            .src_addr(src_addr)
+            .dest_addr(dest_addr)
+            .datagram(packet.as_ref())
+            .build_vec();
+        let packet = Ipv6PacketBuilder::default()
+            .next_header(Protocol::Icmpv6)
+            .hop_limit(ipv6_consts::DEFAULT_HOP_LIMIT)
+            .src_addr(src_addr)
+            .dest_addr(dest_addr)
+            .payload(payload)
+            .build_vec();
+
+        self.send_ipv6(&packet)?;
+        self.metrics.icmp_errors_sent.inc();
+        Ok(())
+    }
+
+    /// Send the IP packet of either version a handler replied with.
+    fn send_reply(&mut self, packet: &[u8]) -> Result<()> {
+        match packet.first().map(|octet| octet >> 4) {
+            Some(ipv6_consts::VERSION) => self.send_ipv6(packet),
+            _ => self.send(Packet::new_checked(packet)?).map(|_| ()),
+        }
+    }
+
+    /// Send an IPv6 packet, which is not fragmented: the upper layers keep to the MTU.
+    /// Through a tunnel, the encapsulating datagram is fragmented when it does not fit.
+    fn send_ipv6(&mut self, packet: &[u8]) -> Result<()> {
+        let clamped = self.clamp_ipv6(packet);
+        let packet = clamped.as_deref().unwrap_or(packet);
+        let dest_addr = Ipv6Packet::new_unchecked(packet).dest_addr();
+        if self.is_loopback(dest_addr) {
+            self.loop_back(packet);
+            return Ok(());
+        }
+        if packet.len() > self.device.mtu() {
+            return Err(Ipv6Error::PacketTooBig.into());
+        }
+        match self.tunnel {
+            Some(tunnel) => {
+                self.identification = self.identification.wrapping_add(1);
+                let datagram = tunnel.encapsulate(self.identification, packet);
+                self.send(Packet::new_unchecked(datagram.as_slice()))?;
+            }
+            None => self.transmit(packet)?,
+        }
+        Ok(())
+    }
+
+    /// Send the segments queued by the sockets and the MLD reports due, without reading from the device.
+    /// Autoconfigured addresses are renewed and expired as well.
+    pub fn dispatch(&mut self, now: Instant) -> Result<()> {
+        for segment in self.sockets.dispatch(now) {
+            self.send_segment(&segment)?;
+        }
+
+        let changes = self.autoconf.poll(now);
+        self.apply_autoconf(changes);
+
+        // Reports are sent from a link-local address, or the unspecified one before there is any
+        // (RFC 3810 section 5.2.13).
+        let src_addr = self
+            .ipv6_addrs
+            .iter()
+            .copied()
+            .find(Ipv6Addr::is_unicast_link_local)
+            .unwrap_or(Ipv6Addr::UNSPECIFIED);
+        for (dest_addr, message) in self.mld.poll(now) {
+            self.send_ipv6(&message.build_packet(src_addr, dest_addr))?;
+        }
+
+        // Router advertisements must come from a link-local address (RFC 4861 section 6.1.2).
+        if !src_addr.is_unspecified() {
+            if let Some(message) = self.advertiser.as_mut().and_then(|advertiser| advertiser.poll(now)) {
+                self.send_ipv6(&message.build_packet(src_addr, ALL_NODES))?;
+            }
+        }
+        Ok(())
+    }
+
+    pub(crate) fn send_segment(&mut self, segment: &Segment) -> Result<()> {
+        self.metrics.tcp_segments_transmitted.inc();
+        match (segment.src_addr, segment.dest_addr) {
+            (IpAddr::V4(src_addr), IpAddr::V4(dest_addr)) => {
+                self.identification = self.identification.wrapping_add(1);
+
+                let builder = PacketBuilder::default()
+                    .identification(self.identification)
+                    .tos(segment.tos)
+                    .ttl(segment.ttl.unwrap_or(consts::DEFAULT_TTL))
+                    .protocol(Protocol::Tcp)
+                    .src_addr(src_addr)
+                    .dest_addr(dest_addr);
+
+                let header_len = (MIN_HEADER_LEN * 4) as usize;
+                let len = header_len + segment.buffer_len();
+                if self.transmits_in_place(dest_addr, len) {
+                    // Built straight into the buffer the device transmits from
+                    self.device.transmit_token(&self.pool).consume(len, |buffer| {
+                        segment.emit(&mut buffer[header_len..]);
+                        builder.emit(buffer);
+                    })?;
+                    self.metrics.packets_transmitted.inc();
+                    return Ok(());
+                }
+
+                let datagram = builder.payload(segment.build_vec()).build_in(&self.pool);
+                self.send(Packet::new_unchecked(datagram.as_ref()))?;
+                Ok(())
+            }
+            (IpAddr::V6(src_addr), IpAddr::V6(dest_addr)) => {
+                let packet = Ipv6PacketBuilder::default()
+                    .traffic_class(segment.tos)
+                    .hop_limit(segment.ttl.unwrap_or(ipv6_consts::DEFAULT_HOP_LIMIT))
+                    .next_header(Protocol::Tcp)
+                    .src_addr(src_addr)
+                    .dest_addr(dest_addr)
+                    .payload(segment.build_vec())
+                    .build_vec();
+
+                self.send_ipv6(&packet)
+            }
+            _ => Err(TcpError::InvalidAddress.into()),
+        }
+    }
+
+    pub(crate) fn send_datagram(&mut self, datagram: &Datagram) -> Result<()> {
+        self.metrics.udp_datagrams_transmitted.inc();
+        let src_addr = match datagram.src_addr.ip() {
+            src_addr if src_addr.is_unspecified() => self.source_addr(datagram.dest_addr.ip()).unwrap_or(src_addr),
+            src_addr => src_addr,
+        };
+        let payload = UdpPacketBuilder::default()
+            .src_port(datagram.src_addr.port())
+            .dest_port(datagram.dest_addr.port())
+            .src_addr(src_addr)
+            .dest_addr(datagram.dest_addr.ip())
+            .payload(datagram.payload.clone())
+            .build_vec();
+
+        match (src_addr, datagram.dest_addr.ip()) {
+            (IpAddr::V4(src_addr), IpAddr::V4(dest_addr)) => {
+                self.identification = self.identification.wrapping_add(1);
+
+                let packet = PacketBuilder::default()
+                    .identification(self.identification)
+                    .tos(datagram.tos)
+                    .ttl(datagram.ttl.unwrap_or(consts::DEFAULT_TTL))
+                    .protocol(Protocol::Udp)
+                    .src_addr(src_addr)
+                    .dest_addr(dest_addr)
+                    .payload(payload)
+                    .build_in(&self.pool);
+
+                self.send(Packet::new_unchecked(packet.as_ref()))?;
+                Ok(())
+            }
+            (IpAddr::V6(src_addr), IpAddr::V6(dest_addr)) => {
+                let packet = Ipv6PacketBuilder::default()
+                    .traffic_class(datagram.tos)
+                    .hop_limit(datagram.ttl.unwrap_or(ipv6_consts::DEFAULT_HOP_LIMIT))
+                    .next_header(Protocol::Udp)
+                    .src_addr(src_addr)
+                    .dest_addr(dest_addr)
+                    .payload(payload)
+                    .build_vec();
+
+                self.send_ipv6(&packet)
+            }
+            _ => Err(UdpError::InvalidAddress.into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::ErrorKind;
+    use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use super::{consts, Interface, MssClamp};
+    use crate::checksum::checksum;
+    use crate::clock::MockClock;
+    use crate::icmpv4::packet::{TimeExceededPacket, TimeExceededPacketCode};
+    use crate::icmpv6::packet::{TimeExceededCode, TimeExceededPacket as Icmpv6TimeExceededPacket};
+    use crate::ipv4::builder::PacketBuilder;
+    use crate::ipv4::packet::{Packet, Protocol};
+    use crate::ipv4::reassembly::Reassembler;
+    use crate::ipv6::builder::PacketBuilder as Ipv6PacketBuilder;
+    use crate::ipv6::packet::Packet as Ipv6Packet;
+    use crate::middlebox::mangle::{Mangle, Middlebox};
+    use crate::net_device::channel::ChannelDevice;
+    use crate::net_device::fault::{FaultDevice, Faults};
+    use crate::net_device::Device;
+    use crate::tcp::builder::PacketBuilder as TcpPacketBuilder;
+    use crate::tcp::connection::State;
+    use crate::tcp::packet::{Packet as TcpPacket, TcpOption};
+    use crate::udp::builder::PacketBuilder as UdpPacketBuilder;
+
+    const ADDR: Ipv4Addr = Ipv4Addr::new(10, 0, 0, 1);
+    const PEER_ADDR: Ipv4Addr = Ipv4Addr::new(10, 0, 0, 2);
+    const ADDR_V6: Ipv6Addr = Ipv6Addr::new(0xfd00, 0, 0, 0, 0, 0, 0, 1);
+    const PEER_ADDR_V6: Ipv6Addr = Ipv6Addr::new(0xfd00, 0, 0, 0, 0, 0, 0, 2);
+
+    fn interface() -> (Interface<ChannelDevice>, ChannelDevice) {
+        let (device, mut peer) = ChannelDevice::pair();
+        peer.set_nonblocking(true);
+        let mut interface = Interface::new(device, Reassembler::default());
+        interface.set_ip_addr(ADDR);
+        (interface, peer)
+    }
+
+    /// A TCP segment from port 40000 of the peer to port 80 of the interface.
+    fn segment(seq_number: u32, ack_number: Option<u32>, syn: bool, fin: bool, payload: &[u8]) -> Vec<u8> {
+        let segment = TcpPacketBuilder::default()
+            .src_port(40000)
+            .dest_port(80)
+            .seq_number(seq_number)
+            .ack_number(ack_number.unwrap_or(0))
+            .ack(ack_number.is_some())
+            .syn(syn)
+            .fin(fin)
+            .window(65535)
+            .src_addr(PEER_ADDR)
+            .dest_addr(ADDR)
+            .payload(payload.to_vec())
+            .build_vec();
+        PacketBuilder::default()
+            .ttl(64)
+            .protocol(Protocol::Tcp)
+            .src_addr(PEER_ADDR)
+            .dest_addr(ADDR)
+            .payload(segment)
+            .build_vec()
+    }
+
+    /// A datagram or packet of the version of the addresses carrying `segment`.
+    fn datagram(src_addr: IpAddr, dest_addr: IpAddr, segment: Vec<u8>) -> Vec<u8> {
+        match (src_addr, dest_addr) {
+            (IpAddr::V4(src_addr), IpAddr::V4(dest_addr)) => PacketBuilder::default()
+                .ttl(64)
+                .protocol(Protocol::Tcp)
+                .src_addr(src_addr)
+                .dest_addr(dest_addr)
+                .payload(segment)
+                .build_vec(),
+            (IpAddr::V6(src_addr), IpAddr::V6(dest_addr)) => Ipv6PacketBuilder::default()
+                .next_header(Protocol::Tcp)
+                .hop_limit(64)
+                .src_addr(src_addr)
+                .dest_addr(dest_addr)
+                .payload(segment)
+                .build_vec(),
+            _ => unreachable!(),
+        }
+    }
+
+    /// A SYN segment from port 40000 to port 80 announcing `mss`.
+    fn syn(src_addr: IpAddr, dest_addr: IpAddr, mss: u16) -> Vec<u8> {
+        TcpPacketBuilder::default()
+            .src_port(40000)
+            .dest_port(80)
+            .seq_number(1000)
+            .syn(true)
+            .window(65535)
+            .option(TcpOption::MaxSegmentSize(mss))
+            .src_addr(src_addr)
+            .dest_addr(dest_addr)
+            .build_vec()
+    }
+
+    fn mss(segment: &TcpPacket<Vec<u8>>) -> TcpOption<'_> {
+        segment.options().next().unwrap().unwrap()
+    }
+
+    type LossyInterface = Interface<FaultDevice<ChannelDevice>>;
+
+    /// Two interfaces on the ends of a channel losing `loss` of the packets each way, on the same clock.
+    fn lossy_pair(loss: f64) -> (LossyInterface, LossyInterface, MockClock) {
+        let (left, right) = ChannelDevice::pair();
+        let clock = MockClock::new();
+        let end = |mut device: ChannelDevice, addr, seed| {
+            device.set_nonblocking(true);
+            let faults = Faults {
+                loss,
+                ..Faults::default()
+            };
+            let mut interface = Interface::new(FaultDevice::new(device, faults, seed), Reassembler::default());
+            interface.set_ip_addr(addr);
+            interface.set_clock(Arc::new(clock.clone()));
+            interface
+        };
+        (end(left, ADDR, 1), end(right, PEER_ADDR, 2), clock)
+    }
+
+    /// Let both interfaces receive what the other sent, then move the clock on.
+    fn exchange(a: &mut LossyInterface, b: &mut LossyInterface, clock: &MockClock) {
+        for interface in [a, b] {
+            match interface.poll(interface.now()) {
+                Err(e) if e.kind() == ErrorKind::WouldBlock => {}
+                result => result.unwrap(),
+            }
+        }
+        clock.advance(Duration::from_millis(10));
+    }
+
+    /// The TCP segments the interface sent to the peer, over either version.
+    /// The MLD reports sent once there is an IPv6 address are skipped.
+    fn sent(peer: &mut ChannelDevice) -> Vec<TcpPacket<Vec<u8>>> {
+        let mut buf = [0; 1500];
+        let mut segments = vec![];
+        while let Ok(len) = peer.receive(&mut buf) {
+            let payload = if buf[0] >> 4 == 6 {
+                let packet = Ipv6Packet::new_checked(&buf[..len]).unwrap();
+                if packet.next_header() != Protocol::Tcp {
+                    continue;
+                }
+                packet.payload().to_vec()
+            } else {
+                let datagram = Packet::new_checked(&buf[..len]).unwrap();
+                assert_eq!(datagram.protocol(), Protocol::Tcp);
+                datagram.payload().to_vec()
+            };
+            segments.push(TcpPacket::new_checked(payload).unwrap());
+        }
+        segments
+    }
+
+    #[test]
+    fn receive() {
+        let (mut interface, mut peer) = interface();
+        let datagram = PacketBuilder::default()
+            .ttl(64)
+            .protocol(Protocol::Udp)
+            .src_addr(PEER_ADDR)
+            .dest_addr(ADDR)
+            .payload((0..32).collect())
+            .build_vec();
+
+        // A datagram built by the stack passes its own header checksum check.
+        peer.transmit(&datagram).unwrap();
+        let received = interface.receive().unwrap();
+        assert_eq!(received.as_ref(), datagram.as_slice());
+
+        let mut corrupted = datagram.clone();
+        corrupted[8] -= 1; // TTL
+        peer.transmit(&corrupted).unwrap();
+        assert!(interface.receive().is_err());
+        assert_eq!(interface.metrics().checksum_errors.get(), 1);
+    }
+
+    #[test]
+    fn destination() {
+        let (mut interface, mut peer) = interface();
+        interface.set_netmask(Ipv4Addr::new(255, 255, 255, 0));
+        let socket = interface.udp_sockets_mut().bind((Ipv4Addr::UNSPECIFIED, 7)).unwrap();
+        let now = interface.now();
+
+        let other_host = Ipv4Addr::new(10, 0, 0, 3);
+        let broadcast = Ipv4Addr::new(10, 0, 0, 255);
+        for dest_addr in [ADDR, other_host, broadcast] {
+            let datagram = UdpPacketBuilder::default()
+                .src_port(40000)
+                .dest_port(7)
+                .src_addr(PEER_ADDR)
+                .dest_addr(dest_addr)
+                .payload(dest_addr.octets().to_vec())
+                .build_vec();
+            let datagram = PacketBuilder::default()
+                .ttl(64)
+                .protocol(Protocol::Udp)
+                .src_addr(PEER_ADDR)
+                .dest_addr(dest_addr)
+                .payload(datagram)
+                .build_vec();
+            peer.transmit(&datagram).unwrap();
+            interface.poll(now).unwrap();
+        }
+
+        // The datagram to another host reaches no socket, and no error is sent about it.
+        let socket = interface.udp_sockets_mut().get_mut(socket).unwrap();
+        let mut buf = [0; 16];
+        assert_eq!(socket.recv_from(&mut buf).unwrap().0, 4);
+        assert_eq!(buf[..4], ADDR.octets());
+        assert_eq!(socket.recv_from(&mut buf).unwrap().0, 4);
+        assert_eq!(buf[..4], broadcast.octets());
+        assert!(socket.recv_from(&mut buf).is_none());
+        assert!(peer.receive(&mut buf).is_err());
+        assert_eq!(interface.metrics().datagrams_dropped.get(), 1);
+    }
+
+    #[test]
+    fn loopback() {
+        let (mut interface, mut peer) = interface();
+        let now = interface.now();
+
+        for addr in [IpAddr::from(Ipv4Addr::LOCALHOST), IpAddr::from(Ipv6Addr::LOCALHOST)] {
+            let server = interface.udp_sockets_mut().bind((addr, 7)).unwrap();
+            let client = interface.udp_sockets_mut().bind((addr, 0)).unwrap();
+            let datagram = interface
+                .udp_sockets()
+                .get(client)
+                .unwrap()
+                .send_to(b"ping", (addr, 7), false)
+                .unwrap();
+            interface.send_datagram(&datagram).unwrap();
+            interface.poll(now).unwrap();
+
+            let mut buf = [0; 16];
+            let socket = interface.udp_sockets_mut().get_mut(server).unwrap();
+            let (len, remote) = socket.recv_from(&mut buf).unwrap();
+            assert_eq!(&buf[..len], b"ping");
+            assert_eq!(remote, datagram.src_addr);
+        }
+
+        // Nothing was written to the device.
+        assert!(peer.receive(&mut [0; 1500]).is_err());
+        assert_eq!(interface.metrics().packets_transmitted.get(), 0);
+    }
+
+    #[test]
+    fn tcp() {
+        let (mut interface, mut peer) = interface();
+        let listener = interface.sockets_mut().bind((ADDR, 80), 1).unwrap();
+        let now = interface.now();
+
+        peer.transmit(&segment(1000, None, true, false, &[])).unwrap();
+        interface.poll(now).unwrap();
+        let syn_ack = sent(&mut peer).pop().unwrap();
+        assert!(syn_ack.syn() && syn_ack.ack());
+        assert_eq!(syn_ack.ack_number(), 1001);
+        let seq_number = syn_ack.seq_number() + 1;
+
+        peer.transmit(&segment(1001, Some(seq_number), false, false, b"hello"))
+            .unwrap();
+        interface.poll(now).unwrap();
+        let handle = interface.sockets_mut().accept(listener).unwrap();
+        let connection = interface.sockets_mut().get_mut(handle).unwrap();
+        let mut buf = [0; 16];
+        assert_eq!(connection.recv(&mut buf), 5);
+        assert_eq!(&buf[..5], b"hello");
+
+        // Nothing to receive from a non-blocking device, the reply is sent all the same.
+        connection.send(b"bye");
+        interface.device_mut().set_nonblocking(true);
+        let e = interface.poll(now).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::WouldBlock);
+        let reply = sent(&mut peer).pop().unwrap();
+        assert_eq!(reply.payload(), b"bye");
+        assert_eq!(reply.ack_number(), 1006);
+
+        peer.transmit(&segment(1006, Some(seq_number + 3), false, true, &[]))
+            .unwrap();
+        interface.poll(now).unwrap();
+        let ack = sent(&mut peer).pop().unwrap();
+        assert_eq!(ack.ack_number(), 1007);
+        assert_eq!(interface.sockets().get(handle).unwrap().state(), State::CloseWait);
+    }
+
+    #[test]
+    fn mss_clamp() {
+        // The MTU minus the IPv4 and TCP headers, then minus the IPv6 and TCP headers.
+        let ends = [
+            (IpAddr::from(ADDR), IpAddr::from(PEER_ADDR), 1240),
+            (IpAddr::from(ADDR_V6), IpAddr::from(PEER_ADDR_V6), 1220),
+        ];
+        for (addr, peer_addr, clamped) in ends {
+            let (mut interface, mut peer) = interface();
+            interface.add_ipv6_addr(ADDR_V6);
+            interface.device_mut().set_mtu(1280);
+            interface.set_mss_clamp(MssClamp::ToMtu);
+            let now = interface.now();
+
+            let listener = interface.sockets_mut().bind((addr, 80), 1).unwrap();
+            peer.transmit(&datagram(peer_addr, addr, syn(peer_addr, addr, 1460)))
+                .unwrap();
+            interface.poll(now).unwrap();
+
+            // The MSS the interface announces is clamped on the way out.
+            let syn_ack = sent(&mut peer).pop().unwrap();
+            assert_eq!(mss(&syn_ack), TcpOption::MaxSegmentSize(clamped));
+
+            let ack = TcpPacketBuilder::default()
+                .src_port(40000)
+                .dest_port(80)
+                .seq_number(1001)
+                .ack_number(syn_ack.seq_number() + 1)
+                .ack(true)
+                .window(65535)
+                .src_addr(peer_addr)
+                .dest_addr(addr)
+                .build_vec();
+            peer.transmit(&datagram(peer_addr, addr, ack)).unwrap();
+            interface.poll(now).unwrap();
+
+            // The MSS of the peer is clamped on the way in, so the segments sent fit the MTU.
+            let handle = interface.sockets_mut().accept(listener).unwrap();
+            interface.sockets_mut().get_mut(handle).unwrap().send(&[0; 2000]);
+            interface.dispatch(now).unwrap();
+            let segments = sent(&mut peer);
+            assert_eq!(segments[0].payload().len(), clamped as usize);
+        }
+
+        // Neither a tiny nor a huge MTU wraps around.
+        let (mut interface, _peer) = interface();
+        interface.set_mss_clamp(MssClamp::ToMtu);
+        interface.device_mut().set_mtu(20);
+        assert_eq!(interface.clamped_mss(20), Some(consts::MIN_MSS));
+        interface.device_mut().set_mtu(100_000);
+        assert_eq!(interface.clamped_mss(20), Some(u16::MAX));
+    }
+
+    #[test]
+    fn forward() {
+        let (mut interface, mut peer) = interface();
+        interface.device_mut().set_mtu(1280);
+        interface.set_mss_clamp(MssClamp::ToMtu);
+        let mut buf = [0; 1500];
+
+        // Between other hosts, through the interface as their router.
+        let src_addr = IpAddr::from(Ipv4Addr::new(192, 168, 0, 2));
+        let dest_addr = IpAddr::from(Ipv4Addr::new(172, 16, 0, 2));
+        interface
+            .forward(&datagram(src_addr, dest_addr, syn(src_addr, dest_addr, 1460)))
+            .unwrap();
+        let len = peer.receive(&mut buf).unwrap();
+        let forwarded = Packet::new_checked(&buf[..len]).unwrap();
+        assert_eq!(forwarded.ttl(), 63);
+        assert_eq!(checksum(&buf[..20]), 0);
+        let segment = TcpPacket::new_checked(forwarded.payload().to_vec()).unwrap();
+        assert_eq!(mss(&segment), TcpOption::MaxSegmentSize(1240));
+        assert!(segment.verify_checksum(src_addr, dest_addr));
+
+        let src_addr = IpAddr::from(Ipv6Addr::new(0xfd01, 0, 0, 0, 0, 0, 0, 2));
+        let dest_addr = IpAddr::from(Ipv6Addr::new(0xfd02, 0, 0, 0, 0, 0, 0, 2));
+        interface
+            .forward(&datagram(src_addr, dest_addr, syn(src_addr, dest_addr, 1440)))
+            .unwrap();
+        let len = peer.receive(&mut buf).unwrap();
+        let forwarded = Ipv6Packet::new_checked(&buf[..len]).unwrap();
+        assert_eq!(forwarded.hop_limit(), 63);
+        let segment = TcpPacket::new_checked(forwarded.payload().to_vec()).unwrap();
+        assert_eq!(mss(&segment), TcpOption::MaxSegmentSize(1220));
+        assert!(segment.verify_checksum(src_addr, dest_addr));
+    }
+
+    #[test]
+    fn forward_time_exceeded() {
+        let (mut interface, mut peer) = interface();
+        let mut buf = [0; 1500];
+
+        // A datagram out of hops goes no further, its source is told from the address of the interface.
+        let src_addr = Ipv4Addr::new(192, 168, 0, 2);
+        let dest_addr = Ipv4Addr::new(172, 16, 0, 2);
+        let mut expiring = datagram(
+            src_addr.into(),
+            dest_addr.into(),
+            syn(src_addr.into(), dest_addr.into(), 1460),
+        );
+        expiring[8] = 1; // time to live
+        expiring[10..12].fill(0);
+        let header_checksum = checksum(&expiring[..20]);
+        expiring[10..12].copy_from_slice(&header_checksum.to_be_bytes());
+        interface.forward(&expiring).unwrap();
+
+        let len = peer.receive(&mut buf).unwrap();
+        let error = Packet::new_checked(&buf[..len]).unwrap();
+        assert_eq!(error.protocol(), Protocol::Icmp);
+        assert_eq!(error.src_addr(), ADDR);
+        assert_eq!(error.dest_addr(), src_addr);
+        let message = TimeExceededPacket::new_checked(error.payload()).unwrap();
+        assert_eq!(message.code(), TimeExceededPacketCode::TtlExceededInTransit);
+        assert_eq!(checksum(error.payload()), 0);
+        // The header of the datagram and the first 8 octets of its payload are quoted.
+        assert_eq!(message.payload(), &expiring[..28]);
+        assert!(peer.receive(&mut buf).is_err());
+        assert_eq!(interface.metrics().datagrams_dropped.get(), 1);
+        assert_eq!(interface.metrics().icmp_errors_sent.get(), 1);
+    }
+
+    #[test]
+    fn forward_hop_limit_exceeded() {
+        let (mut interface, mut peer) = interface();
+        let mut buf = [0; 1500];
+        interface.add_ipv6_addr(ADDR_V6);
+        while peer.receive(&mut buf).is_ok() {}
+
+        // A packet out of hops goes no further, its source is told from the address of the interface.
+        let src_addr = Ipv6Addr::new(0xfd01, 0, 0, 0, 0, 0, 0, 2);
+        let dest_addr = Ipv6Addr::new(0xfd02, 0, 0, 0, 0, 0, 0, 2);
+        let mut expiring = datagram(
+            src_addr.into(),
+            dest_addr.into(),
+            syn(src_addr.into(), dest_addr.into(), 1440),
+        );
+        expiring[7] = 1; // hop limit
+        interface.forward(&expiring).unwrap();
+
+        let len = peer.receive(&mut buf).unwrap();
+        let error = Ipv6Packet::new_checked(&buf[..len]).unwrap();
+        assert_eq!(error.next_header(), Protocol::Icmpv6);
+        assert_eq!(error.src_addr(), ADDR_V6);
+        assert_eq!(error.dest_addr(), src_addr);
+        let message = Icmpv6TimeExceededPacket::new_checked(error.payload()).unwrap();
+        assert!(message.verify_checksum(ADDR_V6, src_addr));
+        assert_eq!(message.code(), TimeExceededCode::HopLimitExceeded);
+        // The whole packet fits in the error, so it is quoted whole.
+        assert_eq!(message.payload(), &expiring[..]);
+        assert!(peer.receive(&mut buf).is_err());
+        assert_eq!(interface.metrics().datagrams_dropped.get(), 1);
+        assert_eq!(interface.metrics().icmp_errors_sent.get(), 1);
+    }
+
+    #[test]
+    fn forward_through_middlebox() {
+        let (mut interface, mut peer) = interface();
+        let middlebox = Middlebox::with_seed(7)
+            .rule(Mangle::StripOptions)
+            .rule(Mangle::RandomizeIdentification);
+        interface.set_middlebox(Some(middlebox));
+
+        let mut datagram = PacketBuilder::default()
+            .header_len(6)
+            .identification(0x1234)
+            .ttl(64)
+            .protocol(Protocol::Udp)
+            .src_addr(Ipv4Addr::new(192, 168, 0, 2))
+            .dest_addr(Ipv4Addr::new(172, 16, 0, 2))
+            .payload(vec![1, 2, 3, 4])
+            .build_vec();
+        datagram[20..24].copy_from_slice(&[1, 1, 1, 0]); // NOP, NOP, NOP, End
+        interface.forward(&datagram).unwrap();
+
+        let mut buf = [0; 64];
+        let len = peer.receive(&mut buf).unwrap();
+        let forwarded = Packet::new_checked(&buf[..len]).unwrap();
+        assert_eq!(forwarded.header_len(), 5);
+        assert_eq!(forwarded.ttl(), 63);
+        assert_ne!(forwarded.identification(), 0x1234);
+        assert_eq!(forwarded.payload(), &[1, 2, 3, 4]);
+        assert_eq!(checksum(&buf[..20]), 0);
+    }
+
+    #[test]
+    fn simultaneous_open() {
+        let (mut a, mut b, clock) = lossy_pair(0.0);
+        let now = a.now();
+        let a_addr = SocketAddr::from((ADDR, 40000));
+        let b_addr = SocketAddr::from((PEER_ADDR, 40001));
+        let a_handle = a.sockets_mut().connect(now, a_addr, b_addr).unwrap();
+        let b_handle = b.sockets_mut().connect(now, b_addr, a_addr).unwrap();
+        a.dispatch(now).unwrap();
+        b.dispatch(now).unwrap();
+
+        // The SYNs cross, both ends go through SYN-RECEIVED to ESTABLISHED without waiting for a retransmission.
+        for _ in 0..3 {
+            exchange(&mut a, &mut b, &clock);
+        }
+        assert_eq!(a.sockets().get(a_handle).unwrap().state(), State::Established);
+        assert_eq!(b.sockets().get(b_handle).unwrap().state(), State::Established);
+
+        a.sockets_mut().get_mut(a_handle).unwrap().send(b"ping");
+        for _ in 0..10 {
+            exchange(&mut a, &mut b, &clock);
+        }
+        let mut buf = [0; 16];
+        assert_eq!(b.sockets_mut().get_mut(b_handle).unwrap().recv(&mut buf), 4);
+        assert_eq!(&buf[..4], b"ping");
+    }
+
+    #[test]
+    fn lossy_transfer() {
+        let (mut client, mut server, clock) = lossy_pair(0.05);
+        let listener = server.sockets_mut().bind((PEER_ADDR, 80), 1).unwrap();
+        let now = client.now();
+        let client_handle = client
+            .sockets_mut()
+            .connect(now, (ADDR, 40000), (PEER_ADDR, 80))
+            .unwrap();
+        client.dispatch(now).unwrap();
+
+        let data: Vec<u8> = (0..256 * 1024).map(|i: usize| (i % 251) as u8).collect();
+        let mut sent = 0;
+        let mut received = vec![];
+        let mut server_handle = None;
+        let mut buf = [0; 4096];
+        for _ in 0..100_000 {
+            exchange(&mut client, &mut server, &clock);
+            if server_handle.is_none() {
+                server_handle = server.sockets_mut().accept(listener);
+            }
+
+            let connection = client.sockets_mut().get_mut(client_handle).unwrap();
+            sent += connection.send(&data[sent..]);
+            if let Some(handle) = server_handle {
+                let connection = server.sockets_mut().get_mut(handle).unwrap();
+                let len = connection.recv(&mut buf);
+                received.extend_from_slice(&buf[..len]);
+            }
+            if received.len() == data.len() {
+                break;
+            }
+        }
+        assert!(received == data, "{} of {} octets received", received.len(), data.len());
+        assert!(client.device().metrics().lost.get() > 0);
+        assert!(server.device().metrics().lost.get() > 0);
+
+        // The client closes first and waits in TIME-WAIT, the server closes once it got the FIN.
+        let server_handle = server_handle.unwrap();
+        client.sockets_mut().get_mut(client_handle).unwrap().close();
+        for _ in 0..100_000 {
+            exchange(&mut client, &mut server, &clock);
+            let connection = server.sockets_mut().get_mut(server_handle).unwrap();
+            if connection.state() == State::CloseWait {
+                connection.close();
+            }
+            if client.sockets().get(client_handle).unwrap().state() == State::TimeWait
+                && server.sockets().get(server_handle).unwrap().state() == State::Closed
+            {
+                break;
+            }
+        }
+        assert_eq!(client.sockets().get(client_handle).unwrap().state(), State::TimeWait);
+        assert_eq!(server.sockets().get(server_handle).unwrap().state(), State::Closed);
     }
 }
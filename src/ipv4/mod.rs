@@ -1,6 +1,15 @@
+#[cfg(feature = "tokio")]
+pub mod async_interface;
 pub mod builder;
 pub mod error;
+pub mod fixed_reassembly;
 pub mod fragmentation;
+#[cfg(feature = "std")]
+pub mod handler;
+#[cfg(feature = "std")]
 pub mod interface;
 pub mod packet;
 pub mod reassembly;
+pub mod verify;
+
+pub use crate::ipv4::verify::verify;
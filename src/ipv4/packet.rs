@@ -1,6 +1,6 @@
-use std::fmt::{Debug, Formatter};
-use std::net::Ipv4Addr;
-use std::option::Option as StdOption;
+use core::fmt::{Debug, Formatter};
+use core::net::Ipv4Addr;
+use core::option::Option as StdOption;
 
 use crate::c_like_enum;
 use crate::error::Result;
@@ -13,11 +13,22 @@ pub mod consts {
 
 c_like_enum!(
     /// assigned internet protocol numbers defined in RFC 790 and other RFCs
-    #[derive(Debug, Copy, Clone, PartialEq, Eq)]
+    #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+    #[cfg_attr(feature = "defmt", derive(defmt::Format))]
     pub enum Protocol(u8) {
+        HopByHop = 0,
         Icmp = 1,
         Tcp = 6,
         Udp = 17,
+        Ipv6 = 41,
+        Routing = 43,
+        Fragment = 44,
+        Esp = 50,
+        Ah = 51,
+        Icmpv6 = 58,
+        NoNextHeader = 59,
+        DestinationOptions = 60,
+        UdpLite = 136,
     }
 );
 
@@ -40,6 +51,10 @@ where
         Ok(packet)
     }
 
+    pub fn into_inner(self) -> Buf {
+        self.buffer
+    }
+
     pub fn check_version(&self) -> Result<()> {
         if self.version() != consts::VERSION {
             return Err(Error::InvalidVersion.into());
@@ -132,7 +147,7 @@ where
         &self.buffer.as_ref()[header_bytes_len..]
     }
 
-    pub fn options(&self) -> OptionIterator {
+    pub fn options(&self) -> OptionIterator<'_> {
         let header_bytes_len: usize = (self.header_len() * 4) as usize;
         OptionIterator::new(&self.buffer.as_ref()[20..header_bytes_len])
     }
@@ -228,7 +243,7 @@ impl<Buf> Debug for Packet<Buf>
 where
     Buf: AsRef<[u8]>,
 {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
         write!(
             f,
             "version: {:?}, header length: {:?}, total length: {:?}, source address: {:?}, destination address: {:?}, type of service: {:#x}, identification: {:#x}, flags: {:#b}, fragment offset: {:#x}, time to live: {:?}, protocol: {:?}, header checksum: {:#x}",
@@ -476,8 +491,8 @@ mod tests {
         assert_eq!(packet.total_len(), 120);
         assert_eq!(packet.identification(), 0x102c);
         assert_eq!(packet.flags(), 0b000);
-        assert_eq!(packet.dont_fragment(), false);
-        assert_eq!(packet.more_fragments(), false);
+        assert!(!packet.dont_fragment());
+        assert!(!packet.more_fragments());
         assert_eq!(packet.offset(), 0);
         assert_eq!(packet.ttl(), 64);
         assert_eq!(packet.protocol(), super::Protocol::Icmp);
@@ -492,20 +507,20 @@ mod tests {
             .expect("some result")
             .expect("a valid ipv4 option");
 
-        assert_eq!(timestamp_option.r#type().copied(), false);
+        assert!(!timestamp_option.r#type().copied());
         assert_eq!(
             timestamp_option.r#type().class(),
             super::OptionClass::DebuggingAndMeasurement
         );
         assert_eq!(timestamp_option.r#type().number(), 0b00000100);
         assert_eq!(timestamp_option.length(), Some(36));
-        assert_eq!(option_iterator.next().is_none(), true);
+        assert!(option_iterator.next().is_none());
     }
 
     #[test]
     fn setter() {
         let header_len = super::consts::MIN_HEADER_LEN as usize;
-        let payload_len = 8 as usize;
+        let payload_len = 8usize;
         let total_len = header_len * 4 + payload_len;
 
         let buffer: Vec<u8> = vec![0; total_len];
@@ -530,10 +545,10 @@ mod tests {
         assert_eq!(packet.flags(), 0b000);
 
         packet.set_dont_fragment(true);
-        assert_eq!(packet.dont_fragment(), true);
+        assert!(packet.dont_fragment());
 
         packet.set_more_fragments(true);
-        assert_eq!(packet.more_fragments(), true);
+        assert!(packet.more_fragments());
         assert_eq!(packet.flags(), 0b011);
 
         packet.set_offset(40);
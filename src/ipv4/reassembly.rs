@@ -1,11 +1,15 @@
-use std::collections::HashMap;
-use std::sync::{Arc, Mutex};
-
-use chrono::Duration;
-use timer::{Guard, Timer};
-
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::ops::DerefMut;
+
+use crate::clock::Clock;
+#[cfg(feature = "std")]
+use crate::clock::SystemClock;
 use crate::ipv4::builder::PacketBuilder;
 use crate::ipv4::packet::Packet;
+use crate::macros::diagnostics::{debug, trace};
+use crate::time::{Duration, Instant};
 
 mod consts {
     pub const DEFAULT_TLB: u8 = 15; // Default Timer Lower Bound
@@ -21,6 +25,15 @@ struct IncompleteDatagram {
 }
 
 impl IncompleteDatagram {
+    fn new(now: Instant) -> Self {
+        Self {
+            reassembly_timer: ReassemblyTimer::new(now),
+            holes: vec![HoleDescriptor::default()],
+            fragments: Vec::new(),
+            total_data_len: 0,
+        }
+    }
+
     /// Insert fragment into the incomplete datagram.
     /// This is a simple but inefficient implementation of RFC 815.
     pub fn insert(&mut self, fragment: Packet<Vec<u8>>) {
@@ -97,7 +110,7 @@ impl IncompleteDatagram {
             "`total_data_len` should be equal to payload length."
         );
 
-        let first_fragment = self.fragments.get(0)?;
+        let first_fragment = self.fragments.first()?;
 
         let datagram = PacketBuilder::default()
             .header_len(first_fragment.header_len())
@@ -117,30 +130,24 @@ impl IncompleteDatagram {
     }
 }
 
-impl Default for IncompleteDatagram {
-    fn default() -> Self {
-        Self {
-            reassembly_timer: ReassemblyTimer::default(),
-            holes: vec![HoleDescriptor::default()],
-            fragments: Vec::new(),
-            total_data_len: 0,
-        }
-    }
-}
-
-/// A timer used to manage reassembly timeout.
+/// A timer used to manage reassembly timeout, restarted by every fragment.
 struct ReassemblyTimer {
     timeout: u8,
-    guard: Option<Guard>,
+    deadline: Instant,
 }
 
-impl Default for ReassemblyTimer {
-    fn default() -> Self {
+impl ReassemblyTimer {
+    fn new(now: Instant) -> Self {
         Self {
             timeout: consts::DEFAULT_TLB,
-            guard: None,
+            deadline: now,
         }
     }
+
+    fn restart(&mut self, now: Instant, ttl: u8) {
+        self.timeout = self.timeout.max(ttl);
+        self.deadline = now + Duration::from_secs(self.timeout as u64);
+    }
 }
 
 /// A HoleDescriptor represents an area that has not been filled in the datagram.
@@ -167,6 +174,13 @@ impl Default for HoleDescriptor {
 /// The id of the datagram being reassembled.
 type DatagramId = u128;
 
+/// The datagrams being reassembled, behind a mutex with `std` and a cell without, as `alloc` has neither
+/// a hash map nor a mutex.
+#[cfg(feature = "std")]
+type DatagramMap = std::sync::Mutex<std::collections::HashMap<DatagramId, IncompleteDatagram>>;
+#[cfg(not(feature = "std"))]
+type DatagramMap = core::cell::RefCell<alloc::collections::BTreeMap<DatagramId, IncompleteDatagram>>;
+
 impl<Buf> Packet<Buf>
 where
     Buf: AsRef<[u8]>,
@@ -192,64 +206,113 @@ where
 }
 
 /// Reassembler is used to reconstruct complete datagram from fragments.
+///
+/// Datagrams time out by the clock of the reassembler, checked whenever it is used.
 pub struct Reassembler {
-    /// A timer used to execute timed tasks.
-    task_timer: Timer,
-    /// A hash map to store datagrams being reassembled.
-    datagram_map: Arc<Mutex<HashMap<DatagramId, IncompleteDatagram>>>,
+    /// The clock reassembly timeouts are measured by.
+    clock: Arc<dyn Clock>,
+    /// A map to store datagrams being reassembled.
+    datagram_map: DatagramMap,
 }
 
 impl Reassembler {
+    pub fn with_clock(clock: Arc<dyn Clock>) -> Self {
+        Self {
+            clock,
+            datagram_map: DatagramMap::default(),
+        }
+    }
+
+    #[cfg(feature = "std")]
+    fn datagram_map(&self) -> impl DerefMut<Target = std::collections::HashMap<DatagramId, IncompleteDatagram>> + '_ {
+        self.datagram_map.lock().unwrap()
+    }
+
+    #[cfg(not(feature = "std"))]
+    fn datagram_map(
+        &self,
+    ) -> impl DerefMut<Target = alloc::collections::BTreeMap<DatagramId, IncompleteDatagram>> + '_ {
+        self.datagram_map.borrow_mut()
+    }
+
+    pub fn clock(&self) -> &Arc<dyn Clock> {
+        &self.clock
+    }
+
+    pub fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        self.clock = clock;
+    }
+
+    /// Returns the number of datagrams being reassembled.
+    pub fn len(&self) -> usize {
+        self.poll();
+        self.datagram_map().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
     /// Discard the datagram that is being reassembled.
     pub fn release(&self, datagram_id: DatagramId) {
-        self.datagram_map.lock().unwrap().remove(&datagram_id);
+        self.datagram_map().remove(&datagram_id);
+    }
+
+    /// Discard the datagrams whose reassembly timed out.
+    pub fn poll(&self) {
+        let now = self.clock.now();
+        self.datagram_map().retain(|datagram_id, datagram| {
+            let expired = datagram.reassembly_timer.deadline <= now;
+            if expired {
+                debug!("reassembly of datagram {:x} timed out", datagram_id);
+            }
+            !expired
+        });
     }
 
     /// Reassemble fragments.
     pub fn reassemble(&self, fragment: Packet<Vec<u8>>) -> Option<Packet<Vec<u8>>> {
+        self.poll();
+        let now = self.clock.now();
         let ttl = fragment.ttl();
         let datagram_id = fragment.datagram_id();
 
-        let mut datagram_map = self.datagram_map.lock().unwrap();
+        let mut datagram_map = self.datagram_map();
+        if !datagram_map.contains_key(&datagram_id) {
+            trace!("reassembly of datagram {:x} started", datagram_id);
+        }
         let datagram = datagram_map
             .entry(datagram_id)
-            .or_insert_with(IncompleteDatagram::default);
+            .or_insert_with(|| IncompleteDatagram::new(now));
 
         datagram.insert(fragment);
-
-        let timeout = datagram.reassembly_timer.timeout.max(ttl);
-        let cloned_datagram_map = self.datagram_map.clone();
-        let guard = self
-            .task_timer
-            .schedule_with_delay(Duration::seconds(timeout as i64), move || {
-                cloned_datagram_map.lock().unwrap().remove(&datagram_id);
-            });
-
-        datagram.reassembly_timer.timeout = timeout;
-        datagram.reassembly_timer.guard = Some(guard);
-
-        datagram.complete().map(|complete_datagram| {
+        datagram.reassembly_timer.restart(now, ttl);
+
+        datagram.complete().inspect(|datagram| {
+            trace!(
+                "datagram {:x} of {} octets reassembled",
+                datagram_id,
+                datagram.as_ref().len()
+            );
             datagram_map.remove(&datagram_id);
-            complete_datagram
         })
     }
 }
 
+#[cfg(feature = "std")]
 impl Default for Reassembler {
     fn default() -> Self {
-        Self {
-            task_timer: Timer::new(),
-            datagram_map: Arc::new(Mutex::new(HashMap::new())),
-        }
+        Self::with_clock(Arc::new(SystemClock))
     }
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "std"))]
 mod tests {
     use std::net::Ipv4Addr;
-    use std::thread::sleep;
+    use std::sync::Arc;
     use std::time::Duration;
 
+    use crate::clock::{Clock, MockClock};
     use crate::ipv4::builder::PacketBuilder;
     use crate::ipv4::packet::consts::MIN_HEADER_LEN;
     use crate::ipv4::packet::{Packet, Protocol};
@@ -294,8 +357,8 @@ mod tests {
 
         let reassembler = Reassembler::default();
 
-        assert_eq!(reassembler.reassemble(second).is_none(), true);
-        assert_eq!(reassembler.reassemble(third).is_none(), true);
+        assert!(reassembler.reassemble(second).is_none());
+        assert!(reassembler.reassemble(third).is_none());
 
         let datagram = reassembler.reassemble(first).unwrap();
 
@@ -313,7 +376,8 @@ mod tests {
         let third = fragments.remove(0);
 
         let datagram_id = first.datagram_id();
-        let reassembler = Reassembler::default();
+        let clock = MockClock::new();
+        let reassembler = Reassembler::with_clock(Arc::new(clock.clone()));
 
         reassembler.reassemble(third);
 
@@ -322,15 +386,22 @@ mod tests {
             let incomplete_datagram = datagram_map.get(&datagram_id).unwrap();
 
             assert_eq!(incomplete_datagram.reassembly_timer.timeout, TTL);
-            assert_eq!(incomplete_datagram.reassembly_timer.guard.is_some(), true);
+            assert_eq!(
+                incomplete_datagram.reassembly_timer.deadline,
+                clock.now() + Duration::from_secs(TTL as u64)
+            );
             assert_eq!(incomplete_datagram.total_data_len, payload_len as usize);
         }
 
-        sleep(Duration::new((TTL + 1) as u64, 0)); // Wait for timeout.
+        clock.advance(Duration::from_secs(TTL as u64 - 1));
+        assert_eq!(reassembler.len(), 1);
+
+        clock.advance(Duration::from_secs(1)); // Time out.
+        reassembler.poll();
 
         {
             let datagram_map = reassembler.datagram_map.lock().unwrap();
-            assert_eq!(datagram_map.contains_key(&datagram_id), false);
+            assert!(!datagram_map.contains_key(&datagram_id));
         }
     }
 }
@@ -0,0 +1,158 @@
+use crate::checksum::checksum;
+use crate::ipv4::packet::{Packet, Protocol};
+use crate::tcp::packet::{consts as tcp_consts, Packet as TcpPacket};
+use crate::udp::packet::Packet as UdpPacket;
+
+/// The outcome of checking the checksum of one layer of a packet.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Check {
+    Valid,
+    Invalid,
+    /// The layer has no checksum the packet alone can verify, such as the payload of a fragment.
+    Skipped,
+}
+
+/// The layer whose checksum failed.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Layer {
+    Ipv4,
+    Tcp,
+    Udp,
+    Icmp,
+}
+
+/// The checksums of an IPv4 packet and of the TCP segment, UDP datagram or ICMP message it carries.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Report {
+    pub protocol: Protocol,
+    pub header: Check,
+    pub payload: Check,
+}
+
+impl Report {
+    pub fn is_valid(&self) -> bool {
+        self.header != Check::Invalid && self.payload != Check::Invalid
+    }
+
+    /// The lowest layer whose checksum failed, if any.
+    pub fn failed_layer(&self) -> Option<Layer> {
+        if self.header == Check::Invalid {
+            return Some(Layer::Ipv4);
+        }
+        if self.payload != Check::Invalid {
+            return None;
+        }
+        match self.protocol {
+            Protocol::Tcp => Some(Layer::Tcp),
+            Protocol::Udp => Some(Layer::Udp),
+            Protocol::Icmp => Some(Layer::Icmp),
+            _ => None,
+        }
+    }
+}
+
+/// Whether the header checksum of `packet` is valid, the checksum of a header with a valid checksum field being zero.
+pub fn verify_header<T: AsRef<[u8]>>(packet: &Packet<T>) -> bool {
+    checksum(&packet.as_ref()[..(packet.header_len() * 4) as usize]) == 0
+}
+
+/// Verify the header checksum of `packet`, then the checksum of the TCP segment, UDP datagram or ICMP message it carries,
+/// using the pseudo-header of its addresses for TCP and UDP.
+///
+/// The payload of a fragment is skipped, its checksum covering the whole datagram,
+/// and so is a payload too short for the header of its protocol.
+pub fn verify<T: AsRef<[u8]>>(packet: &Packet<T>) -> Report {
+    let header = match verify_header(packet) {
+        true => Check::Valid,
+        false => Check::Invalid,
+    };
+
+    let payload = packet.payload();
+    let payload = if packet.offset() != 0 || packet.more_fragments() {
+        Check::Skipped
+    } else {
+        let (src_addr, dest_addr) = (packet.src_addr(), packet.dest_addr());
+        let valid = match packet.protocol() {
+            Protocol::Tcp if payload.len() >= tcp_consts::MIN_HEADER_LEN as usize * 4 => {
+                Some(TcpPacket::new_unchecked(payload).verify_checksum(src_addr, dest_addr))
+            }
+            Protocol::Udp => match UdpPacket::new_checked(payload) {
+                Ok(datagram) => Some(datagram.verify_checksum(src_addr, dest_addr)),
+                Err(_) => None,
+            },
+            Protocol::Icmp if !payload.is_empty() => {
+                // ICMP has no pseudo-header (RFC 792).
+                Some(checksum(payload) == 0)
+            }
+            _ => None,
+        };
+        match valid {
+            Some(true) => Check::Valid,
+            Some(false) => Check::Invalid,
+            None => Check::Skipped,
+        }
+    };
+
+    Report {
+        protocol: packet.protocol(),
+        header,
+        payload,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use super::{Check, Layer};
+    use crate::ipv4::builder::PacketBuilder;
+    use crate::ipv4::packet::Protocol;
+    use crate::udp::builder::PacketBuilder as UdpPacketBuilder;
+
+    #[test]
+    fn verify() {
+        let src_addr = Ipv4Addr::new(192, 168, 233, 234);
+        let dest_addr = Ipv4Addr::new(192, 168, 233, 233);
+        let datagram = UdpPacketBuilder::default()
+            .src_port(54321)
+            .dest_port(53)
+            .src_addr(src_addr)
+            .dest_addr(dest_addr)
+            .payload(vec![1, 2, 3, 4, 5])
+            .build_vec();
+        let builder = || {
+            PacketBuilder::default()
+                .protocol(Protocol::Udp)
+                .src_addr(src_addr)
+                .dest_addr(dest_addr)
+                .payload(datagram.clone())
+        };
+
+        let report = super::verify(&builder().build());
+        assert_eq!((report.header, report.payload), (Check::Valid, Check::Valid));
+        assert!(report.is_valid());
+        assert_eq!(report.failed_layer(), None);
+
+        // A datagram checksummed with other addresses
+        let report = super::verify(&builder().src_addr(Ipv4Addr::new(10, 0, 0, 1)).build());
+        assert_eq!((report.header, report.payload), (Check::Valid, Check::Invalid));
+        assert_eq!(report.failed_layer(), Some(Layer::Udp));
+
+        let report = super::verify(&builder().checksum(0xbeef).build());
+        assert_eq!(report.failed_layer(), Some(Layer::Ipv4));
+
+        // The first fragment of a datagram, whose checksum covers the fragments to come
+        let report = super::verify(&builder().flags(0b001).build());
+        assert_eq!((report.header, report.payload), (Check::Valid, Check::Skipped));
+        assert!(report.is_valid());
+
+        let mut message = vec![8, 0, 0, 0, 0, 1, 0, 1];
+        let checksum = crate::checksum::checksum(&message);
+        message[2..4].copy_from_slice(&checksum.to_be_bytes());
+        let report = super::verify(&builder().protocol(Protocol::Icmp).payload(message.clone()).build());
+        assert_eq!(report.payload, Check::Valid);
+        message[7] = 2;
+        let report = super::verify(&builder().protocol(Protocol::Icmp).payload(message).build());
+        assert_eq!(report.failed_layer(), Some(Layer::Icmp));
+    }
+}
@@ -0,0 +1,329 @@
+use std::collections::hash_map::RandomState;
+use std::hash::BuildHasher;
+use std::net::Ipv6Addr;
+use std::time::{Duration, Instant};
+
+use crate::ndp::packet::PrefixInfo;
+
+pub mod consts {
+    use std::time::Duration;
+
+    pub const PREFIX_LEN: u8 = 64; // Interface identifiers are 64 bits long (RFC 4291 section 2.5.1)
+    pub const MIN_VALID_LIFETIME: Duration = Duration::from_secs(2 * 60 * 60); // RFC 4862 section 5.5.3 e)
+    pub const MAX_PREFIXES: usize = 16; // Prefixes autoconfigured at once, more advertised ones are ignored
+
+    // Default values of RFC 8981 section 3.8
+    pub const TEMP_VALID_LIFETIME: Duration = Duration::from_secs(2 * 24 * 60 * 60);
+    pub const TEMP_PREFERRED_LIFETIME: Duration = Duration::from_secs(24 * 60 * 60);
+    pub const REGEN_ADVANCE: Duration = Duration::from_secs(5);
+    pub const MAX_DESYNC_FACTOR: Duration = Duration::from_millis(TEMP_PREFERRED_LIFETIME.as_millis() as u64 * 2 / 5);
+}
+
+/// An autoconfigured address, which is deprecated once its preferred lifetime ends
+/// and removed once its valid lifetime does.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Address {
+    pub addr: Ipv6Addr,
+    /// A temporary address (RFC 8981), whose interface identifier is random and changes over time.
+    pub temporary: bool,
+    pub created: Instant,
+    pub preferred_until: Instant,
+    pub valid_until: Instant,
+    /// Whether the preferred lifetime ended, new communications should not use the address.
+    pub deprecated: bool,
+}
+
+impl Address {
+    fn prefix(&self) -> u64 {
+        (u128::from(self.addr) >> 64) as u64
+    }
+}
+
+/// The addresses added to and removed from the interface.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Changes {
+    pub added: Vec<Ipv6Addr>,
+    pub removed: Vec<Ipv6Addr>,
+}
+
+/// Stateless address autoconfiguration (RFC 4862 section 5.5.3) from the prefixes routers advertise,
+/// along with the temporary addresses of RFC 8981 which keep a host from being tracked across networks
+/// by its interface identifier.
+///
+/// Each prefix gets a public address, from the stable interface identifier, and a temporary address
+/// which is regenerated shortly before its preferred lifetime ends.
+#[derive(Debug)]
+pub struct Autoconf {
+    interface_id: u64,
+    temporary_addrs: bool,
+    addrs: Vec<Address>,
+    /// Shortens the preferred lifetime of temporary addresses, so hosts do not regenerate them all at once.
+    desync_factor: Duration,
+    random_state: RandomState,
+    random_counter: u64,
+}
+
+impl Autoconf {
+    /// Create the autoconfiguration of the public addresses made from `interface_id`, temporary addresses are enabled.
+    pub fn new(interface_id: u64) -> Self {
+        let mut autoconf = Self {
+            interface_id,
+            temporary_addrs: true,
+            addrs: vec![],
+            desync_factor: Duration::ZERO,
+            random_state: RandomState::new(),
+            random_counter: 0,
+        };
+        let max = consts::MAX_DESYNC_FACTOR.as_millis() as u64;
+        autoconf.desync_factor = Duration::from_millis(autoconf.random() % max);
+        autoconf
+    }
+
+    pub fn interface_id(&self) -> u64 {
+        self.interface_id
+    }
+
+    /// Whether temporary addresses are generated, the ones already generated are kept until they expire.
+    pub fn set_temporary_addrs(&mut self, enabled: bool) {
+        self.temporary_addrs = enabled;
+    }
+
+    pub fn temporary_addrs(&self) -> bool {
+        self.temporary_addrs
+    }
+
+    pub fn addrs(&self) -> &[Address] {
+        &self.addrs
+    }
+
+    pub fn addr(&self, addr: Ipv6Addr) -> Option<&Address> {
+        self.addrs.iter().find(|address| address.addr == addr)
+    }
+
+    /// Apply a prefix of a router advertisement: a new autonomous prefix gets its addresses,
+    /// and the lifetimes of the addresses of a known one are updated.
+    pub fn process_prefix(&mut self, prefix: &PrefixInfo, now: Instant) -> Changes {
+        let mut changes = Changes::default();
+        let network = (u128::from(prefix.prefix) >> 64) as u64;
+        if !prefix.autonomous
+            || prefix.prefix_len != consts::PREFIX_LEN
+            || prefix.preferred_lifetime > prefix.valid_lifetime
+            || prefix.prefix.is_unicast_link_local()
+            || prefix.prefix.is_multicast()
+        {
+            return changes;
+        }
+        let valid = Duration::from_secs(prefix.valid_lifetime as u64);
+        let preferred = Duration::from_secs(prefix.preferred_lifetime as u64);
+
+        let public = match self
+            .addrs
+            .iter_mut()
+            .find(|address| !address.temporary && address.prefix() == network)
+        {
+            Some(public) => {
+                public.preferred_until = now + preferred;
+                // A short valid lifetime is only trusted down to two hours, so an unauthenticated
+                // advertisement can not make the addresses go away at once (RFC 4862 section 5.5.3 e).
+                let remaining = public.valid_until.saturating_duration_since(now);
+                if valid > consts::MIN_VALID_LIFETIME || valid > remaining {
+                    public.valid_until = now + valid;
+                } else if remaining > consts::MIN_VALID_LIFETIME {
+                    public.valid_until = now + consts::MIN_VALID_LIFETIME;
+                }
+                public.deprecated = public.preferred_until <= now;
+                *public
+            }
+            None => {
+                let prefixes = self.addrs.iter().filter(|address| !address.temporary).count();
+                if valid.is_zero() || prefixes >= consts::MAX_PREFIXES {
+                    return changes;
+                }
+                let public = Address {
+                    addr: Ipv6Addr::from((network as u128) << 64 | self.interface_id as u128),
+                    temporary: false,
+                    created: now,
+                    preferred_until: now + preferred,
+                    valid_until: now + valid,
+                    deprecated: preferred.is_zero(),
+                };
+                self.addrs.push(public);
+                changes.added.push(public.addr);
+                if let Some(addr) = self.create_temporary(&public, now) {
+                    changes.added.push(addr);
+                }
+                return changes;
+            }
+        };
+
+        // Temporary addresses never outlive the prefix (RFC 8981 section 3.4).
+        for address in self
+            .addrs
+            .iter_mut()
+            .filter(|address| address.temporary && address.prefix() == network)
+        {
+            address.valid_until = public.valid_until.min(address.created + consts::TEMP_VALID_LIFETIME);
+            address.preferred_until = public
+                .preferred_until
+                .min(address.created + consts::TEMP_PREFERRED_LIFETIME - self.desync_factor);
+            address.deprecated = address.preferred_until <= now;
+        }
+        changes
+    }
+
+    /// Remove the addresses whose valid lifetime ended, deprecate the ones whose preferred lifetime did,
+    /// and generate a new temporary address when the one of a prefix is about to be deprecated.
+    pub fn poll(&mut self, now: Instant) -> Changes {
+        let mut changes = Changes::default();
+        self.addrs.retain(|address| {
+            if address.valid_until <= now {
+                changes.removed.push(address.addr);
+            }
+            address.valid_until > now
+        });
+        for address in self.addrs.iter_mut() {
+            address.deprecated = address.preferred_until <= now;
+        }
+
+        if self.temporary_addrs {
+            let regenerate: Vec<Address> = self
+                .addrs
+                .iter()
+                .filter(|public| !public.temporary && !public.deprecated)
+                .filter(|public| {
+                    !self.addrs.iter().any(|address| {
+                        address.temporary
+                            && address.prefix() == public.prefix()
+                            && address.preferred_until > now + consts::REGEN_ADVANCE
+                    })
+                })
+                .copied()
+                .collect();
+            for public in regenerate.iter() {
+                if let Some(addr) = self.create_temporary(public, now) {
+                    changes.added.push(addr);
+                }
+            }
+        }
+        changes
+    }
+
+    /// Generate a temporary address for the prefix of the `public` address, unless temporary addresses
+    /// are disabled or the prefix is not preferred long enough for it to be used (RFC 8981 section 3.4).
+    fn create_temporary(&mut self, public: &Address, now: Instant) -> Option<Ipv6Addr> {
+        let preferred_until = public
+            .preferred_until
+            .min(now + consts::TEMP_PREFERRED_LIFETIME - self.desync_factor);
+        if !self.temporary_addrs || preferred_until <= now + consts::REGEN_ADVANCE {
+            return None;
+        }
+
+        let interface_id = loop {
+            let interface_id = self.random();
+            if !reserved(interface_id) && interface_id != self.interface_id {
+                break interface_id;
+            }
+        };
+        let address = Address {
+            addr: Ipv6Addr::from((public.prefix() as u128) << 64 | interface_id as u128),
+            temporary: true,
+            created: now,
+            preferred_until,
+            valid_until: public.valid_until.min(now + consts::TEMP_VALID_LIFETIME),
+            deprecated: false,
+        };
+        self.addrs.push(address);
+        Some(address.addr)
+    }
+
+    fn random(&mut self) -> u64 {
+        self.random_counter += 1;
+        self.random_state.hash_one(self.random_counter)
+    }
+}
+
+impl Default for Autoconf {
+    /// Autoconfiguration with a random interface identifier, which is stable for as long as it lives.
+    fn default() -> Self {
+        let interface_id = loop {
+            let interface_id = RandomState::new().hash_one(0u64);
+            if !reserved(interface_id) {
+                break interface_id;
+            }
+        };
+        Self::new(interface_id)
+    }
+}
+
+/// Whether `interface_id` is reserved (RFC 5453), the subnet-router anycast one and the reserved anycast ones.
+fn reserved(interface_id: u64) -> bool {
+    interface_id == 0 || interface_id >= 0xfdff_ffff_ffff_ff80
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv6Addr;
+    use std::time::Instant;
+
+    use super::{consts, Autoconf};
+    use crate::ndp::packet::PrefixInfo;
+
+    #[test]
+    fn temporary() {
+        let now = Instant::now();
+        let mut autoconf = Autoconf::new(0x0211_22ff_fe33_4455);
+        let mut prefix = PrefixInfo {
+            prefix: Ipv6Addr::new(0x2001, 0xdb8, 0, 1, 0, 0, 0, 0),
+            prefix_len: 64,
+            on_link: true,
+            autonomous: true,
+            valid_lifetime: 30 * 24 * 60 * 60,
+            preferred_lifetime: 7 * 24 * 60 * 60,
+        };
+
+        let changes = autoconf.process_prefix(&prefix, now);
+        let public = Ipv6Addr::new(0x2001, 0xdb8, 0, 1, 0x0211, 0x22ff, 0xfe33, 0x4455);
+        assert_eq!(changes.added.len(), 2);
+        assert_eq!(changes.added[0], public);
+        let temporary = *autoconf.addr(changes.added[1]).unwrap();
+        assert!(temporary.temporary);
+        assert_eq!(temporary.addr.segments()[..4], public.segments()[..4]);
+        assert_eq!(temporary.valid_until, now + consts::TEMP_VALID_LIFETIME);
+        assert!(temporary.preferred_until <= now + consts::TEMP_PREFERRED_LIFETIME);
+        assert!(temporary.preferred_until > now + consts::TEMP_PREFERRED_LIFETIME - consts::MAX_DESYNC_FACTOR);
+        assert_eq!(autoconf.poll(now), Default::default());
+
+        // A new temporary address is generated shortly before the current one is deprecated.
+        let regen = temporary.preferred_until - consts::REGEN_ADVANCE;
+        let changes = autoconf.poll(regen);
+        assert_eq!(changes.added.len(), 1);
+        assert!(changes.removed.is_empty());
+        assert!(autoconf.poll(temporary.preferred_until).added.is_empty());
+        assert!(autoconf.addr(temporary.addr).unwrap().deprecated);
+        assert_eq!(autoconf.poll(temporary.valid_until).removed, vec![temporary.addr]);
+
+        // A short valid lifetime only brings the addresses down to two hours.
+        prefix.valid_lifetime = 60;
+        prefix.preferred_lifetime = 0;
+        let later = temporary.valid_until;
+        autoconf.process_prefix(&prefix, later);
+        let address = autoconf.addr(public).unwrap();
+        assert!(address.deprecated);
+        assert_eq!(address.valid_until, later + consts::MIN_VALID_LIFETIME);
+        assert!(autoconf.addrs().iter().all(|address| address.deprecated));
+        assert!(autoconf.poll(later).added.is_empty());
+        assert!(autoconf
+            .poll(later + consts::MIN_VALID_LIFETIME)
+            .removed
+            .contains(&public));
+        assert!(autoconf.addrs().is_empty());
+
+        prefix.autonomous = false;
+        prefix.valid_lifetime = 60;
+        assert_eq!(autoconf.process_prefix(&prefix, later), Default::default());
+
+        autoconf.set_temporary_addrs(false);
+        prefix.autonomous = true;
+        assert_eq!(autoconf.process_prefix(&prefix, later).added, vec![public]);
+    }
+}
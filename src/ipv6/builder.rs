@@ -0,0 +1,162 @@
+use alloc::vec;
+use alloc::vec::Vec;
+use core::net::Ipv6Addr;
+
+use crate::ipv4::packet::Protocol;
+use crate::ipv6::packet::{consts, Packet};
+use crate::ipv6::repr::Repr;
+
+pub struct PacketBuilder {
+    traffic_class: u8,
+    flow_label: u32,
+    payload_len: Option<u16>,
+    next_header: Protocol,
+    hop_limit: u8,
+    src_addr: Ipv6Addr,
+    dest_addr: Ipv6Addr,
+    checksum_offset: Option<usize>,
+    payload: Vec<u8>,
+}
+
+impl PacketBuilder {
+    pub fn traffic_class(mut self, traffic_class: u8) -> Self {
+        self.traffic_class = traffic_class;
+        self
+    }
+
+    pub fn flow_label(mut self, flow_label: u32) -> Self {
+        self.flow_label = flow_label;
+        self
+    }
+
+    /// Set the payload length instead of computing it from the payload.
+    pub fn payload_len(mut self, payload_len: u16) -> Self {
+        self.payload_len = Some(payload_len);
+        self
+    }
+
+    pub fn next_header(mut self, next_header: Protocol) -> Self {
+        self.next_header = next_header;
+        self
+    }
+
+    pub fn hop_limit(mut self, hop_limit: u8) -> Self {
+        self.hop_limit = hop_limit;
+        self
+    }
+
+    pub fn src_addr(mut self, src_addr: Ipv6Addr) -> Self {
+        self.src_addr = src_addr;
+        self
+    }
+
+    pub fn dest_addr(mut self, dest_addr: Ipv6Addr) -> Self {
+        self.dest_addr = dest_addr;
+        self
+    }
+
+    /// Fill the checksum of the upper-layer packet carried as payload, found at `offset` of it,
+    /// such as 6 for UDP or 16 for TCP, using the pseudo-header of this packet.
+    /// The next header must be the upper-layer protocol.
+    pub fn upper_layer_checksum(mut self, offset: usize) -> Self {
+        self.checksum_offset = Some(offset);
+        self
+    }
+
+    pub fn payload(mut self, payload: Vec<u8>) -> Self {
+        self.payload = payload;
+        self
+    }
+
+    pub fn build_vec(mut self) -> Vec<u8> {
+        let repr = Repr {
+            src_addr: self.src_addr,
+            dest_addr: self.dest_addr,
+            next_header: self.next_header,
+            payload_len: self.payload_len.map_or(self.payload.len(), usize::from),
+            hop_limit: self.hop_limit,
+            traffic_class: self.traffic_class,
+            flow_label: self.flow_label,
+        };
+
+        if let Some(offset) = self.checksum_offset {
+            self.payload[offset..offset + 2].fill(0);
+            // Zero is sent as all ones, UDP over IPv6 must carry a checksum (RFC 8200 section 8.1).
+            let checksum = match repr.pseudo_header_checksum(&self.payload) {
+                0 => 0xffff,
+                checksum => checksum,
+            };
+            self.payload[offset..offset + 2].copy_from_slice(checksum.to_be_bytes().as_ref());
+        }
+
+        let mut buffer: Vec<u8> = vec![0; consts::HEADER_LEN];
+        buffer.append(&mut self.payload);
+
+        let mut packet = Packet::new_unchecked(buffer.as_mut_slice());
+        repr.emit(&mut packet);
+
+        buffer
+    }
+
+    pub fn build(self) -> Packet<Vec<u8>> {
+        Packet::new_unchecked(self.build_vec())
+    }
+}
+
+impl Default for PacketBuilder {
+    fn default() -> Self {
+        Self {
+            traffic_class: 0,
+            flow_label: 0,
+            payload_len: None,
+            next_header: Protocol::NoNextHeader,
+            hop_limit: consts::DEFAULT_HOP_LIMIT,
+            src_addr: Ipv6Addr::UNSPECIFIED,
+            dest_addr: Ipv6Addr::UNSPECIFIED,
+            checksum_offset: None,
+            payload: vec![],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv6Addr;
+
+    use super::PacketBuilder;
+    use crate::checksum::pseudo_header_checksum;
+    use crate::ipv4::packet::Protocol;
+    use crate::udp::builder::PacketBuilder as UdpPacketBuilder;
+
+    #[test]
+    fn build() {
+        let src_addr: Ipv6Addr = "2001:db8::1".parse().unwrap();
+        let dest_addr: Ipv6Addr = "2001:db8::2".parse().unwrap();
+        let datagram = UdpPacketBuilder::default()
+            .src_port(5353)
+            .dest_port(5353)
+            .payload(b"hello".to_vec())
+            .build_vec();
+
+        let packet = PacketBuilder::default()
+            .next_header(Protocol::Udp)
+            .hop_limit(255)
+            .src_addr(src_addr)
+            .dest_addr(dest_addr)
+            .upper_layer_checksum(6)
+            .payload(datagram.clone())
+            .build();
+
+        assert_eq!(packet.version(), 6);
+        assert_eq!(packet.payload_len() as usize, datagram.len());
+        assert_eq!(packet.next_header(), Protocol::Udp);
+        assert_eq!(packet.hop_limit(), 255);
+        assert_eq!(packet.src_addr(), src_addr);
+        assert_eq!(packet.dest_addr(), dest_addr);
+        assert_eq!(&packet.payload()[..6], &datagram[..6]);
+        assert_eq!(
+            pseudo_header_checksum(src_addr, dest_addr, Protocol::Udp, datagram.len(), packet.payload()),
+            0
+        );
+    }
+}
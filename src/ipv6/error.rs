@@ -0,0 +1,30 @@
+use core::fmt::{Display, Formatter};
+
+#[derive(Debug)]
+pub enum Error {
+    InvalidVersion,
+    InvalidPayloadLen,
+    InvalidAddress,
+    InvalidExtensionHeader,
+    InvalidFragment,
+    TooManyDatagrams,
+    PacketTooBig,
+    NotEncapsulated,
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Error::InvalidVersion => write!(f, "invalid version"),
+            Error::InvalidPayloadLen => write!(f, "invalid payload length"),
+            Error::InvalidAddress => write!(f, "invalid address"),
+            Error::InvalidExtensionHeader => write!(f, "invalid extension header"),
+            Error::InvalidFragment => write!(f, "invalid fragment"),
+            Error::TooManyDatagrams => write!(f, "too many datagrams being reassembled"),
+            Error::PacketTooBig => write!(f, "packet too big"),
+            Error::NotEncapsulated => write!(f, "not an encapsulated packet"),
+        }
+    }
+}
+
+impl core::error::Error for Error {}
@@ -0,0 +1,267 @@
+use crate::error::Result;
+use crate::ipv4::packet::Protocol;
+use crate::ipv6::error::Error;
+
+pub mod consts {
+    pub const OPTION_PAD1: u8 = 0; // RFC 8200 section 4.2
+    pub const OPTION_PADN: u8 = 1;
+    pub const OPTION_ROUTER_ALERT: u8 = 5; // RFC 2711
+    pub const FRAGMENT_HEADER_LEN: usize = 8;
+}
+
+/// An extension header (RFC 8200 section 4), borrowing from the payload of the packet.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ExtensionHeader<'a> {
+    HopByHop(Options<'a>),
+    Routing {
+        routing_type: u8,
+        segments_left: u8,
+        /// The data specific to the routing type.
+        data: &'a [u8],
+    },
+    Fragment {
+        /// The offset of the fragment, in 8-octet units.
+        offset: u16,
+        more_fragments: bool,
+        identification: u32,
+    },
+    DestinationOptions(Options<'a>),
+    /// An Authentication Header (RFC 4302), its length counted in 4-octet units.
+    Ah {
+        spi: u32,
+        sequence: u32,
+        icv: &'a [u8],
+    },
+    /// An Encapsulating Security Payload (RFC 4303), what follows is encrypted.
+    Esp {
+        spi: u32,
+    },
+}
+
+/// An iterator over the extension headers of a packet, following the next header chain.
+/// It stops at the upper-layer header, at an unknown one, after an error, or after a header
+/// beyond which the payload cannot be read: an encrypted payload or a fragment other than the first.
+pub struct ExtensionHeaders<'a> {
+    next_header: Protocol,
+    buffer: &'a [u8],
+    offset: usize,
+    done: bool,
+}
+
+impl<'a> ExtensionHeaders<'a> {
+    /// Walk the headers of `payload`, the first one being `next_header`.
+    pub fn new(next_header: Protocol, payload: &'a [u8]) -> Self {
+        Self {
+            next_header,
+            buffer: payload,
+            offset: 0,
+            done: false,
+        }
+    }
+
+    /// Returns the header after the ones walked so far and its offset in the payload,
+    /// which is the upper-layer header once the iterator is exhausted without error.
+    pub fn upper_layer(&self) -> (Protocol, usize) {
+        (self.next_header, self.offset)
+    }
+
+    fn fail(&mut self) -> Option<Result<ExtensionHeader<'a>>> {
+        self.done = true;
+        Some(Err(Error::InvalidExtensionHeader.into()))
+    }
+}
+
+impl<'a> Iterator for ExtensionHeaders<'a> {
+    type Item = Result<ExtensionHeader<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let rest = &self.buffer[self.offset..];
+
+        let len = match self.next_header {
+            Protocol::HopByHop | Protocol::Routing | Protocol::DestinationOptions => {
+                rest.get(1).map(|len| (*len as usize + 1) * 8)
+            }
+            Protocol::Fragment => Some(consts::FRAGMENT_HEADER_LEN),
+            Protocol::Ah => rest.get(1).map(|len| (*len as usize + 2) * 4),
+            Protocol::Esp => {
+                self.done = true;
+                return match rest {
+                    [a, b, c, d, ..] => Some(Ok(ExtensionHeader::Esp {
+                        spi: u32::from_be_bytes([*a, *b, *c, *d]),
+                    })),
+                    _ => self.fail(),
+                };
+            }
+            _ => {
+                self.done = true;
+                return None;
+            }
+        };
+        let header = match len {
+            Some(len) if len <= rest.len() => &rest[..len],
+            _ => return self.fail(),
+        };
+
+        // The hop-by-hop options only follow the IPv6 header (RFC 8200 section 4.3).
+        if self.next_header == Protocol::HopByHop && self.offset != 0 {
+            return self.fail();
+        }
+
+        let extension = match self.next_header {
+            Protocol::HopByHop => ExtensionHeader::HopByHop(Options::new(&header[2..])),
+            Protocol::DestinationOptions => ExtensionHeader::DestinationOptions(Options::new(&header[2..])),
+            Protocol::Routing => ExtensionHeader::Routing {
+                routing_type: header[2],
+                segments_left: header[3],
+                data: &header[4..],
+            },
+            Protocol::Fragment => {
+                let offset = u16::from_be_bytes([header[2], header[3]]);
+                let extension = ExtensionHeader::Fragment {
+                    offset: offset >> 3,
+                    more_fragments: offset & 0x1 == 1,
+                    identification: u32::from_be_bytes([header[4], header[5], header[6], header[7]]),
+                };
+                // Only the first fragment holds the headers that follow.
+                if offset >> 3 != 0 {
+                    self.done = true;
+                }
+                extension
+            }
+            Protocol::Ah => {
+                if header.len() < 12 {
+                    return self.fail();
+                }
+                ExtensionHeader::Ah {
+                    spi: u32::from_be_bytes([header[4], header[5], header[6], header[7]]),
+                    sequence: u32::from_be_bytes([header[8], header[9], header[10], header[11]]),
+                    icv: &header[12..],
+                }
+            }
+            _ => unreachable!(),
+        };
+
+        self.next_header = header[0].into();
+        self.offset += header.len();
+        Some(Ok(extension))
+    }
+}
+
+/// The options of a hop-by-hop or destination options header, padding excluded.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Options<'a> {
+    buffer: &'a [u8],
+}
+
+impl<'a> Options<'a> {
+    fn new(buffer: &'a [u8]) -> Self {
+        Self { buffer }
+    }
+}
+
+impl<'a> Iterator for Options<'a> {
+    /// The option type, whose two highest bits tell what to do when it is unknown, and its data.
+    type Item = Result<(u8, &'a [u8])>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let (option_type, rest) = self.buffer.split_first()?;
+            if *option_type == consts::OPTION_PAD1 {
+                self.buffer = rest;
+                continue;
+            }
+
+            let len = match rest.first() {
+                Some(len) if rest.len() > *len as usize => *len as usize,
+                _ => {
+                    self.buffer = &[];
+                    return Some(Err(Error::InvalidExtensionHeader.into()));
+                }
+            };
+            let data = &rest[1..1 + len];
+            self.buffer = &rest[1 + len..];
+            if *option_type != consts::OPTION_PADN {
+                return Some(Ok((*option_type, data)));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ExtensionHeader, ExtensionHeaders};
+    use crate::ipv4::packet::Protocol;
+
+    #[test]
+    fn chain() {
+        let payload: Vec<u8> = vec![
+            // hop-by-hop options: router alert, then padding
+            60, 0, 0x05, 0x02, 0x00, 0x00, 0x01, 0x00, //
+            // destination options: pad1 and padn only
+            43, 0, 0x00, 0x01, 0x03, 0x00, 0x00, 0x00, //
+            // routing: type 4, 1 segment left
+            44, 0, 4, 1, 0, 0, 0, 0, //
+            // fragment: offset 0, more fragments
+            17, 0, 0x00, 0x01, 0x12, 0x34, 0x56, 0x78, //
+            // udp header
+            0x14, 0xe9, 0x14, 0xe9, 0x00, 0x08, 0x00, 0x00,
+        ];
+
+        let mut headers = ExtensionHeaders::new(Protocol::HopByHop, &payload);
+        match headers.next().unwrap().unwrap() {
+            ExtensionHeader::HopByHop(options) => {
+                let options: Vec<_> = options.map(Result::unwrap).collect();
+                assert_eq!(options, vec![(0x05, &[0x00, 0x00][..])]);
+            }
+            header => panic!("unexpected {:?}", header),
+        }
+        match headers.next().unwrap().unwrap() {
+            ExtensionHeader::DestinationOptions(options) => assert_eq!(options.count(), 0),
+            header => panic!("unexpected {:?}", header),
+        }
+        assert!(matches!(
+            headers.next().unwrap().unwrap(),
+            ExtensionHeader::Routing {
+                routing_type: 4,
+                segments_left: 1,
+                ..
+            }
+        ));
+        assert_eq!(
+            headers.next().unwrap().unwrap(),
+            ExtensionHeader::Fragment {
+                offset: 0,
+                more_fragments: true,
+                identification: 0x12345678
+            }
+        );
+        assert!(headers.next().is_none());
+        assert_eq!(headers.upper_layer(), (Protocol::Udp, 32));
+
+        // A truncated header ends the walk with an error.
+        let mut headers = ExtensionHeaders::new(Protocol::HopByHop, &payload[..4]);
+        assert!(headers.next().unwrap().is_err());
+        assert!(headers.next().is_none());
+
+        // A hop-by-hop header after another one is malformed.
+        let mut misplaced = payload[8..].to_vec();
+        misplaced[0] = 0;
+        let mut headers = ExtensionHeaders::new(Protocol::DestinationOptions, &misplaced);
+        assert!(headers.next().unwrap().is_ok());
+        assert!(headers.next().unwrap().is_err());
+        assert!(headers.next().is_none());
+
+        // Nothing is read after a later fragment or an encrypted payload.
+        let mut later = payload[24..].to_vec();
+        later[3] = 0x09;
+        let mut headers = ExtensionHeaders::new(Protocol::Fragment, &later);
+        assert!(headers.next().unwrap().is_ok());
+        assert!(headers.next().is_none());
+        let mut headers = ExtensionHeaders::new(Protocol::Esp, &[0, 0, 0x10, 0x01, 0xff]);
+        assert_eq!(headers.next().unwrap().unwrap(), ExtensionHeader::Esp { spi: 0x1001 });
+        assert!(headers.next().is_none());
+    }
+}
@@ -0,0 +1,11 @@
+#[cfg(feature = "std")]
+pub mod autoconf;
+pub mod builder;
+pub mod error;
+pub mod extension;
+pub mod packet;
+#[cfg(feature = "std")]
+pub mod reassembly;
+pub mod repr;
+#[cfg(feature = "std")]
+pub mod tunnel;
@@ -0,0 +1,241 @@
+use core::convert::TryInto;
+use core::fmt::{Debug, Formatter};
+use core::net::Ipv6Addr;
+
+use crate::error::Result;
+use crate::ipv4::packet::Protocol;
+use crate::ipv6::error::Error;
+use crate::ipv6::extension::ExtensionHeaders;
+
+pub mod consts {
+    pub const VERSION: u8 = 6;
+    pub const HEADER_LEN: usize = 40; // RFC 8200 section 3
+    pub const MIN_MTU: usize = 1280; // RFC 8200 section 5, every link must carry it
+    pub const DEFAULT_HOP_LIMIT: u8 = 64;
+}
+
+/// An IPv6 packet (RFC 8200), its next header being the first extension header or the upper-layer protocol.
+pub struct Packet<Buf> {
+    buffer: Buf,
+}
+
+impl<Buf> Packet<Buf>
+where
+    Buf: AsRef<[u8]>,
+{
+    pub fn new_unchecked(buffer: Buf) -> Self {
+        Packet { buffer }
+    }
+
+    pub fn new_checked(buffer: Buf) -> Result<Self> {
+        let packet = Self::new_unchecked(buffer);
+        packet.check_len()?;
+        packet.check_version()?;
+        Ok(packet)
+    }
+
+    pub fn into_inner(self) -> Buf {
+        self.buffer
+    }
+
+    pub fn check_version(&self) -> Result<()> {
+        if self.version() != consts::VERSION {
+            return Err(Error::InvalidVersion.into());
+        }
+        Ok(())
+    }
+
+    /// The payload length must fit in the buffer, octets after it are link layer padding.
+    pub fn check_len(&self) -> Result<()> {
+        let buf_len = self.buffer.as_ref().len();
+        if buf_len < consts::HEADER_LEN || consts::HEADER_LEN + self.payload_len() as usize > buf_len {
+            return Err(Error::InvalidPayloadLen.into());
+        }
+        Ok(())
+    }
+
+    pub fn version(&self) -> u8 {
+        self.buffer.as_ref()[0] >> 4
+    }
+
+    pub fn traffic_class(&self) -> u8 {
+        let buffer = self.buffer.as_ref();
+        (buffer[0] << 4) | (buffer[1] >> 4)
+    }
+
+    pub fn flow_label(&self) -> u32 {
+        let buffer = self.buffer.as_ref();
+        u32::from_be_bytes([0, buffer[1] & 0x0f, buffer[2], buffer[3]])
+    }
+
+    /// Returns the length of the extension headers and the upper-layer payload.
+    pub fn payload_len(&self) -> u16 {
+        u16::from_be_bytes([self.buffer.as_ref()[4], self.buffer.as_ref()[5]])
+    }
+
+    pub fn next_header(&self) -> Protocol {
+        self.buffer.as_ref()[6].into()
+    }
+
+    pub fn hop_limit(&self) -> u8 {
+        self.buffer.as_ref()[7]
+    }
+
+    pub fn src_addr(&self) -> Ipv6Addr {
+        let octets: [u8; 16] = self.buffer.as_ref()[8..24].try_into().unwrap();
+        Ipv6Addr::from(octets)
+    }
+
+    pub fn dest_addr(&self) -> Ipv6Addr {
+        let octets: [u8; 16] = self.buffer.as_ref()[24..40].try_into().unwrap();
+        Ipv6Addr::from(octets)
+    }
+
+    pub fn payload(&self) -> &[u8] {
+        &self.buffer.as_ref()[consts::HEADER_LEN..consts::HEADER_LEN + self.payload_len() as usize]
+    }
+
+    /// Returns an iterator over the extension headers, ending at the upper-layer header.
+    pub fn extension_headers(&self) -> ExtensionHeaders<'_> {
+        ExtensionHeaders::new(self.next_header(), self.payload())
+    }
+}
+
+impl<Buf> Packet<Buf>
+where
+    Buf: AsRef<[u8]> + AsMut<[u8]>,
+{
+    pub fn set_version(&mut self, version: u8) {
+        self.buffer.as_mut()[0] = (self.buffer.as_mut()[0] & 0x0f) | (version << 4);
+    }
+
+    pub fn set_traffic_class(&mut self, traffic_class: u8) {
+        let buffer = self.buffer.as_mut();
+        buffer[0] = (buffer[0] & 0xf0) | (traffic_class >> 4);
+        buffer[1] = (buffer[1] & 0x0f) | (traffic_class << 4);
+    }
+
+    /// Set the 20 bits flow label, higher bits are ignored.
+    pub fn set_flow_label(&mut self, flow_label: u32) {
+        let be_bytes = flow_label.to_be_bytes();
+        let buffer = self.buffer.as_mut();
+        buffer[1] = (buffer[1] & 0xf0) | (be_bytes[1] & 0x0f);
+        buffer[2] = be_bytes[2];
+        buffer[3] = be_bytes[3];
+    }
+
+    pub fn set_payload_len(&mut self, payload_len: u16) {
+        self.buffer.as_mut()[4..=5].copy_from_slice(payload_len.to_be_bytes().as_ref());
+    }
+
+    pub fn set_next_header(&mut self, next_header: Protocol) {
+        self.buffer.as_mut()[6] = next_header.into();
+    }
+
+    pub fn set_hop_limit(&mut self, hop_limit: u8) {
+        self.buffer.as_mut()[7] = hop_limit;
+    }
+
+    pub fn set_src_addr(&mut self, src_addr: Ipv6Addr) {
+        self.buffer.as_mut()[8..24].copy_from_slice(src_addr.octets().as_ref());
+    }
+
+    pub fn set_dest_addr(&mut self, dest_addr: Ipv6Addr) {
+        self.buffer.as_mut()[24..40].copy_from_slice(dest_addr.octets().as_ref());
+    }
+
+    pub fn payload_mut(&mut self) -> &mut [u8] {
+        let payload_len = self.payload_len() as usize;
+        &mut self.buffer.as_mut()[consts::HEADER_LEN..consts::HEADER_LEN + payload_len]
+    }
+
+    pub fn set_payload(&mut self, payload: &[u8]) {
+        self.payload_mut()[..payload.len()].copy_from_slice(payload);
+    }
+}
+
+impl<Buf> Debug for Packet<Buf>
+where
+    Buf: AsRef<[u8]>,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "version: {:?}, traffic class: {:#x}, flow label: {:#x}, payload length: {:?}, next header: {:?}, hop limit: {:?}, source address: {:?}, destination address: {:?}",
+            self.version(),
+            self.traffic_class(),
+            self.flow_label(),
+            self.payload_len(),
+            self.next_header(),
+            self.hop_limit(),
+            self.src_addr(),
+            self.dest_addr(),
+        )
+    }
+}
+
+impl<Buf> AsRef<[u8]> for Packet<Buf>
+where
+    Buf: AsRef<[u8]>,
+{
+    fn as_ref(&self) -> &[u8] {
+        self.buffer.as_ref()
+    }
+}
+
+impl<Buf> AsMut<[u8]> for Packet<Buf>
+where
+    Buf: AsMut<[u8]>,
+{
+    fn as_mut(&mut self) -> &mut [u8] {
+        self.buffer.as_mut()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv6Addr;
+
+    use super::Packet;
+    use crate::ipv4::packet::Protocol;
+
+    #[test]
+    fn new_checked() {
+        let mut bytes: Vec<u8> = vec![
+            // version, traffic class and flow label
+            0x6b, 0x81, 0x23, 0x45, //
+            // payload length, next header and hop limit
+            0x00, 0x02, 0x11, 0x40, //
+            // source address
+            0x20, 0x01, 0x0d, 0xb8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x01, //
+            // destination address
+            0x20, 0x01, 0x0d, 0xb8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x02, //
+            // payload
+            0xbe, 0xef, //
+            // padding of the frame
+            0x00,
+        ];
+
+        let packet = Packet::new_checked(bytes.as_slice()).expect("a valid ipv6 packet");
+        assert_eq!(packet.version(), 6);
+        assert_eq!(packet.traffic_class(), 0xb8);
+        assert_eq!(packet.flow_label(), 0x12345);
+        assert_eq!(packet.payload_len(), 2);
+        assert_eq!(packet.next_header(), Protocol::Udp);
+        assert_eq!(packet.hop_limit(), 64);
+        assert_eq!(packet.src_addr(), "2001:db8::1".parse::<Ipv6Addr>().unwrap());
+        assert_eq!(packet.dest_addr(), "2001:db8::2".parse::<Ipv6Addr>().unwrap());
+        assert_eq!(packet.payload(), &[0xbe, 0xef]);
+        assert!(Packet::new_checked(&bytes[..41]).is_err());
+
+        let mut packet = Packet::new_unchecked(bytes.as_mut_slice());
+        packet.set_traffic_class(0x2e);
+        packet.set_flow_label(0xfff00001);
+        assert_eq!(packet.version(), 6);
+        assert_eq!(packet.traffic_class(), 0x2e);
+        assert_eq!(packet.flow_label(), 1);
+
+        bytes[0] = 0x45;
+        assert!(Packet::new_checked(bytes.as_slice()).is_err());
+    }
+}
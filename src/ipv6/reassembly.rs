@@ -0,0 +1,329 @@
+use std::collections::{BTreeMap, HashMap};
+use std::net::Ipv6Addr;
+use std::time::Instant;
+
+use crate::error::Result;
+use crate::ipv4::packet::Protocol;
+use crate::ipv6::error::Error;
+use crate::ipv6::extension::{consts as extension_consts, ExtensionHeader};
+use crate::ipv6::packet::{consts as packet_consts, Packet};
+
+pub mod consts {
+    use std::time::Duration;
+
+    pub const REASSEMBLY_TIMEOUT: Duration = Duration::from_secs(60); // RFC 8200 section 4.5
+    pub const MAX_DATAGRAMS: usize = 64; // Datagrams reassembled at once, fragments of new ones are dropped past it
+    pub const MAX_PAYLOAD_LEN: usize = 65535; // Jumbograms are not supported
+}
+
+/// The source address, destination address and identification of the datagram being reassembled.
+type DatagramId = (Ipv6Addr, Ipv6Addr, u32);
+
+/// The datagram being reassembled.
+struct IncompleteDatagram {
+    /// The IPv6 header and the extension headers preceding the fragment header, taken from the first fragment.
+    unfragmentable: Option<Vec<u8>>,
+    /// The offset in `unfragmentable` of the next header field that pointed to the fragment header.
+    next_header_field: usize,
+    /// The header following the fragment header of the first fragment.
+    next_header: Protocol,
+    /// The data of the fragments by offset in octets.
+    fragments: BTreeMap<usize, Vec<u8>>,
+    /// The length of the fragmentable part, known once the last fragment arrived.
+    total_len: Option<usize>,
+    /// The datagram is dropped at this deadline, counted from its first fragment to arrive.
+    deadline: Instant,
+}
+
+impl IncompleteDatagram {
+    fn new(deadline: Instant) -> Self {
+        Self {
+            unfragmentable: None,
+            next_header_field: 0,
+            next_header: Protocol::NoNextHeader,
+            fragments: BTreeMap::new(),
+            total_len: None,
+            deadline,
+        }
+    }
+
+    /// Insert the data of a fragment starting at `offset`, fails when it overlaps another fragment (RFC 5722)
+    /// or when it goes past the end of the datagram. An exact duplicate is ignored.
+    fn insert(&mut self, offset: usize, data: &[u8], more_fragments: bool) -> Result<()> {
+        let end = offset + data.len();
+
+        if self
+            .fragments
+            .get(&offset)
+            .is_some_and(|fragment| fragment.as_slice() == data)
+        {
+            return Ok(());
+        }
+        let overlaps_previous = self
+            .fragments
+            .range(..offset)
+            .next_back()
+            .is_some_and(|(first, fragment)| first + fragment.len() > offset);
+        let overlaps_next = self
+            .fragments
+            .range(offset..)
+            .next()
+            .is_some_and(|(first, _)| *first < end);
+        if overlaps_previous || overlaps_next {
+            return Err(Error::InvalidFragment.into());
+        }
+
+        if !more_fragments {
+            if self.total_len.is_some_and(|total_len| total_len != end) {
+                return Err(Error::InvalidFragment.into());
+            }
+            self.total_len = Some(end);
+        }
+        if self
+            .total_len
+            .is_some_and(|total_len| end > total_len || self.last_end() > total_len)
+        {
+            return Err(Error::InvalidFragment.into());
+        }
+
+        self.fragments.insert(offset, data.to_vec());
+        Ok(())
+    }
+
+    fn last_end(&self) -> usize {
+        self.fragments
+            .iter()
+            .next_back()
+            .map_or(0, |(first, fragment)| first + fragment.len())
+    }
+
+    /// Returns the reassembled packet once every fragment arrived.
+    fn complete(&self) -> Result<Option<Vec<u8>>> {
+        let (unfragmentable, total_len) = match (&self.unfragmentable, self.total_len) {
+            (Some(unfragmentable), Some(total_len)) => (unfragmentable, total_len),
+            _ => return Ok(None),
+        };
+
+        let mut end = 0;
+        for (first, fragment) in self.fragments.iter() {
+            if *first != end {
+                return Ok(None);
+            }
+            end += fragment.len();
+        }
+        if end != total_len {
+            return Ok(None);
+        }
+
+        let payload_len = unfragmentable.len() - packet_consts::HEADER_LEN + total_len;
+        if payload_len > consts::MAX_PAYLOAD_LEN {
+            return Err(Error::InvalidFragment.into());
+        }
+
+        let mut buffer = unfragmentable.clone();
+        for fragment in self.fragments.values() {
+            buffer.extend_from_slice(fragment);
+        }
+        buffer[self.next_header_field] = self.next_header.into();
+
+        let mut packet = Packet::new_unchecked(buffer.as_mut_slice());
+        packet.set_payload_len(payload_len as u16);
+        Ok(Some(buffer))
+    }
+}
+
+/// Reassembler is used to reconstruct complete packets from the fragments of RFC 8200 section 4.5.
+///
+/// Timeouts are driven by the time given to `poll`, where the IPv4 reassembler reads its own clock.
+pub struct Reassembler {
+    datagrams: HashMap<DatagramId, IncompleteDatagram>,
+}
+
+impl Reassembler {
+    pub fn new() -> Self {
+        Self {
+            datagrams: HashMap::new(),
+        }
+    }
+
+    /// Returns the number of datagrams being reassembled.
+    pub fn len(&self) -> usize {
+        self.datagrams.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Reassemble the fragment `packet`, returns the whole packet without its fragment header once every
+    /// fragment arrived. A packet without a fragment header, or an atomic fragment (RFC 6946),
+    /// is returned right away.
+    ///
+    /// A malformed fragment fails, and so does one overlapping another fragment, which also drops
+    /// the datagram it belongs to.
+    pub fn reassemble<Buf>(&mut self, packet: &Packet<Buf>, now: Instant) -> Result<Option<Vec<u8>>>
+    where
+        Buf: AsRef<[u8]>,
+    {
+        self.poll(now);
+
+        let mut headers = packet.extension_headers();
+        // The IPv6 header holds the first next header field, extension headers hold it in their first octet.
+        let mut next_header_field = 6;
+        let (header_offset, offset, more_fragments, identification) = loop {
+            let (_, header_offset) = headers.upper_layer();
+            match headers.next() {
+                Some(Ok(ExtensionHeader::Fragment {
+                    offset,
+                    more_fragments,
+                    identification,
+                })) => break (header_offset, offset as usize * 8, more_fragments, identification),
+                Some(Ok(_)) => next_header_field = packet_consts::HEADER_LEN + header_offset,
+                Some(Err(e)) => return Err(e),
+                None => {
+                    return Ok(Some(
+                        packet.as_ref()[..packet_consts::HEADER_LEN + packet.payload().len()].to_vec(),
+                    ))
+                }
+            }
+        };
+
+        let data = &packet.payload()[header_offset + extension_consts::FRAGMENT_HEADER_LEN..];
+        // Every fragment but the last is a multiple of 8 octets long (RFC 8200 section 4.5).
+        if (more_fragments && !data.len().is_multiple_of(8)) || offset + data.len() > consts::MAX_PAYLOAD_LEN {
+            return Err(Error::InvalidFragment.into());
+        }
+        let fragment_header = packet_consts::HEADER_LEN + header_offset;
+        let next_header: Protocol = packet.as_ref()[fragment_header].into();
+
+        if offset == 0 && !more_fragments {
+            let mut buffer = packet.as_ref()[..fragment_header].to_vec();
+            buffer.extend_from_slice(data);
+            buffer[next_header_field] = next_header.into();
+            let payload_len = (buffer.len() - packet_consts::HEADER_LEN) as u16;
+            Packet::new_unchecked(buffer.as_mut_slice()).set_payload_len(payload_len);
+            return Ok(Some(buffer));
+        }
+
+        let id = (packet.src_addr(), packet.dest_addr(), identification);
+        if !self.datagrams.contains_key(&id) && self.datagrams.len() >= consts::MAX_DATAGRAMS {
+            return Err(Error::TooManyDatagrams.into());
+        }
+        let datagram = self
+            .datagrams
+            .entry(id)
+            .or_insert_with(|| IncompleteDatagram::new(now + consts::REASSEMBLY_TIMEOUT));
+
+        if let Err(e) = datagram.insert(offset, data, more_fragments) {
+            self.datagrams.remove(&id);
+            return Err(e);
+        }
+        if offset == 0 {
+            datagram.unfragmentable = Some(packet.as_ref()[..fragment_header].to_vec());
+            datagram.next_header_field = next_header_field;
+            datagram.next_header = next_header;
+        }
+
+        let complete = datagram.complete();
+        if !matches!(complete, Ok(None)) {
+            self.datagrams.remove(&id);
+        }
+        complete
+    }
+
+    /// Drop the datagrams whose reassembly timed out.
+    pub fn poll(&mut self, now: Instant) {
+        self.datagrams.retain(|_, datagram| datagram.deadline > now);
+    }
+}
+
+impl Default for Reassembler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv6Addr;
+    use std::time::{Duration, Instant};
+
+    use super::{consts, Reassembler};
+    use crate::ipv4::packet::Protocol;
+    use crate::ipv6::builder::PacketBuilder;
+    use crate::ipv6::packet::Packet;
+
+    const SRC_ADDR: Ipv6Addr = Ipv6Addr::new(0xfd00, 0, 0, 0, 0, 0, 0, 1);
+    const DEST_ADDR: Ipv6Addr = Ipv6Addr::new(0xfd00, 0, 0, 0, 0, 0, 0, 2);
+
+    fn fragment(offset: usize, more_fragments: bool, data: &[u8]) -> Packet<Vec<u8>> {
+        let offset = (offset as u16) | more_fragments as u16;
+        let mut payload = vec![Protocol::Udp.into(), 0];
+        payload.extend_from_slice(offset.to_be_bytes().as_ref());
+        payload.extend_from_slice(0x1001u32.to_be_bytes().as_ref());
+        payload.extend_from_slice(data);
+
+        PacketBuilder::default()
+            .next_header(Protocol::Fragment)
+            .src_addr(SRC_ADDR)
+            .dest_addr(DEST_ADDR)
+            .payload(payload)
+            .build()
+    }
+
+    #[test]
+    fn reassemble() {
+        let data: Vec<u8> = (0..40).collect();
+        let now = Instant::now();
+        let mut reassembler = Reassembler::new();
+
+        assert_eq!(
+            reassembler.reassemble(&fragment(32, false, &data[32..]), now).unwrap(),
+            None
+        );
+        assert_eq!(
+            reassembler.reassemble(&fragment(16, true, &data[16..32]), now).unwrap(),
+            None
+        );
+        assert_eq!(
+            reassembler.reassemble(&fragment(16, true, &data[16..32]), now).unwrap(),
+            None
+        );
+        let buffer = reassembler
+            .reassemble(&fragment(0, true, &data[..16]), now)
+            .unwrap()
+            .unwrap();
+        let packet = Packet::new_checked(buffer.as_slice()).unwrap();
+        assert_eq!(packet.next_header(), Protocol::Udp);
+        assert_eq!(packet.payload(), data.as_slice());
+        assert!(reassembler.is_empty());
+
+        let atomic = reassembler
+            .reassemble(&fragment(0, false, &data), now)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            Packet::new_checked(atomic.as_slice()).unwrap().payload(),
+            data.as_slice()
+        );
+
+        // Overlapping fragments drop the whole datagram.
+        assert_eq!(
+            reassembler.reassemble(&fragment(0, true, &data[..16]), now).unwrap(),
+            None
+        );
+        assert!(reassembler.reassemble(&fragment(8, true, &data[8..24]), now).is_err());
+        assert!(reassembler.is_empty());
+
+        assert!(reassembler.reassemble(&fragment(0, true, &data[..12]), now).is_err());
+
+        assert_eq!(
+            reassembler.reassemble(&fragment(0, true, &data[..16]), now).unwrap(),
+            None
+        );
+        reassembler.poll(now + consts::REASSEMBLY_TIMEOUT - Duration::from_secs(1));
+        assert_eq!(reassembler.len(), 1);
+        reassembler.poll(now + consts::REASSEMBLY_TIMEOUT);
+        assert!(reassembler.is_empty());
+    }
+}
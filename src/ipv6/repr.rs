@@ -0,0 +1,128 @@
+use core::net::Ipv6Addr;
+
+use crate::checksum::pseudo_header_checksum;
+use crate::error::Result;
+use crate::ipv4::packet::Protocol;
+use crate::ipv6::error::Error;
+use crate::ipv6::packet::{consts, Packet};
+
+/// A high-level representation of an IPv6 header, extension headers being part of the payload.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Repr {
+    pub src_addr: Ipv6Addr,
+    pub dest_addr: Ipv6Addr,
+    pub next_header: Protocol,
+    pub payload_len: usize,
+    pub hop_limit: u8,
+    pub traffic_class: u8,
+    pub flow_label: u32,
+}
+
+impl Repr {
+    /// Parse and validate a packet, a multicast source address is invalid (RFC 4291 section 2.7).
+    pub fn parse<Buf>(packet: &Packet<Buf>) -> Result<Self>
+    where
+        Buf: AsRef<[u8]>,
+    {
+        packet.check_len()?;
+        packet.check_version()?;
+
+        if packet.src_addr().is_multicast() {
+            return Err(Error::InvalidAddress.into());
+        }
+
+        Ok(Repr {
+            src_addr: packet.src_addr(),
+            dest_addr: packet.dest_addr(),
+            next_header: packet.next_header(),
+            payload_len: packet.payload_len() as usize,
+            hop_limit: packet.hop_limit(),
+            traffic_class: packet.traffic_class(),
+            flow_label: packet.flow_label(),
+        })
+    }
+
+    /// Returns the length of the whole packet in octets.
+    pub fn buffer_len(&self) -> usize {
+        consts::HEADER_LEN + self.payload_len
+    }
+
+    /// Emit the header into `packet`.
+    ///
+    /// The buffer must be at least `buffer_len()` octets long, and the payload no longer than
+    /// 65535 octets: jumbograms (RFC 2675) are not supported.
+    pub fn emit<Buf>(&self, packet: &mut Packet<Buf>)
+    where
+        Buf: AsRef<[u8]> + AsMut<[u8]>,
+    {
+        packet.set_version(consts::VERSION);
+        packet.set_traffic_class(self.traffic_class);
+        packet.set_flow_label(self.flow_label);
+        packet.set_payload_len(self.payload_len as u16);
+        packet.set_next_header(self.next_header);
+        packet.set_hop_limit(self.hop_limit);
+        packet.set_src_addr(self.src_addr);
+        packet.set_dest_addr(self.dest_addr);
+    }
+
+    /// Returns the checksum of the upper-layer packet `data` with the pseudo-header of this packet,
+    /// to fill into a zeroed checksum field, or zero when verifying a filled one.
+    pub fn pseudo_header_checksum(&self, data: &[u8]) -> u16 {
+        pseudo_header_checksum(self.src_addr, self.dest_addr, self.next_header, data.len(), data)
+    }
+}
+
+impl Default for Repr {
+    fn default() -> Self {
+        Self {
+            src_addr: Ipv6Addr::UNSPECIFIED,
+            dest_addr: Ipv6Addr::UNSPECIFIED,
+            next_header: Protocol::NoNextHeader,
+            payload_len: 0,
+            hop_limit: consts::DEFAULT_HOP_LIMIT,
+            traffic_class: 0,
+            flow_label: 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv6Addr;
+
+    use super::Repr;
+    use crate::ipv4::packet::Protocol;
+    use crate::ipv6::packet::Packet;
+
+    #[test]
+    fn emit_and_parse() {
+        let payload = [0xaa, 0xbb, 0xcc];
+        let repr = Repr {
+            src_addr: "fe80::1".parse().unwrap(),
+            dest_addr: "ff02::1".parse().unwrap(),
+            next_header: Protocol::Udp,
+            payload_len: payload.len(),
+            hop_limit: 255,
+            traffic_class: 0x10,
+            flow_label: 0xabcde,
+        };
+
+        let mut buffer = vec![0; repr.buffer_len()];
+        let mut packet = Packet::new_unchecked(buffer.as_mut_slice());
+        repr.emit(&mut packet);
+        packet.set_payload(&payload);
+
+        let packet = Packet::new_checked(buffer.as_slice()).unwrap();
+        assert_eq!(packet.payload(), &payload);
+        assert_eq!(Repr::parse(&packet).unwrap(), repr);
+
+        let spoofed = Repr {
+            src_addr: Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 0, 1),
+            ..repr
+        };
+        let mut buffer = vec![0; spoofed.buffer_len()];
+        spoofed.emit(&mut Packet::new_unchecked(buffer.as_mut_slice()));
+        assert!(Repr::parse(&Packet::new_unchecked(buffer.as_slice())).is_err());
+    }
+}
@@ -0,0 +1,92 @@
+use std::net::Ipv4Addr;
+
+use crate::error::Result;
+use crate::ipv4::builder::PacketBuilder;
+use crate::ipv4::interface::consts::DEFAULT_TTL;
+use crate::ipv4::packet::{Packet, Protocol};
+use crate::ipv6::error::Error;
+use crate::ipv6::packet::consts;
+
+/// A configured 6in4 tunnel (RFC 4213 section 3), which carries IPv6 packets as the payload
+/// of IPv4 datagrams of protocol 41 between two IPv4 endpoints, such as a tunnel broker.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Tunnel {
+    pub local_addr: Ipv4Addr,
+    pub remote_addr: Ipv4Addr,
+    /// The TTL of the encapsulating datagrams (RFC 4213 section 3.3).
+    pub ttl: u8,
+}
+
+impl Tunnel {
+    pub fn new(local_addr: Ipv4Addr, remote_addr: Ipv4Addr) -> Self {
+        Self {
+            local_addr,
+            remote_addr,
+            ttl: DEFAULT_TTL,
+        }
+    }
+
+    /// Encapsulate the IPv6 `packet` in an IPv4 datagram to the remote endpoint.
+    /// The Don't Fragment flag is left clear, so packets larger than the IPv4 path MTU are fragmented
+    /// (RFC 4213 section 3.2).
+    pub fn encapsulate(&self, identification: u16, packet: &[u8]) -> Vec<u8> {
+        PacketBuilder::default()
+            .identification(identification)
+            .ttl(self.ttl)
+            .protocol(Protocol::Ipv6)
+            .src_addr(self.local_addr)
+            .dest_addr(self.remote_addr)
+            .payload(packet.to_vec())
+            .build_vec()
+    }
+
+    /// Returns the IPv6 packet carried by `datagram`. Datagrams which do not come from the remote endpoint
+    /// are dropped, so no one else can inject packets into the tunnel (RFC 4213 section 3.6).
+    pub fn decapsulate<'a, Buf>(&self, datagram: &'a Packet<Buf>) -> Result<&'a [u8]>
+    where
+        Buf: AsRef<[u8]>,
+    {
+        if datagram.protocol() != Protocol::Ipv6 {
+            return Err(Error::NotEncapsulated.into());
+        }
+        if datagram.src_addr() != self.remote_addr || datagram.dest_addr() != self.local_addr {
+            return Err(Error::InvalidAddress.into());
+        }
+
+        let packet = datagram.payload();
+        if packet.first().map(|octet| octet >> 4) != Some(consts::VERSION) {
+            return Err(Error::InvalidVersion.into());
+        }
+        Ok(packet)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    use super::Tunnel;
+    use crate::ipv4::packet::{Packet, Protocol};
+    use crate::ipv6::builder::PacketBuilder;
+
+    #[test]
+    fn encapsulate() {
+        let tunnel = Tunnel::new(Ipv4Addr::new(192, 0, 2, 1), Ipv4Addr::new(198, 51, 100, 1));
+        let packet = PacketBuilder::default()
+            .src_addr(Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 2))
+            .dest_addr(Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1))
+            .payload(vec![1, 2, 3])
+            .build_vec();
+
+        let bytes = tunnel.encapsulate(7, &packet);
+        let datagram = Packet::new_checked(bytes.as_slice()).unwrap();
+        assert_eq!(datagram.protocol(), Protocol::Ipv6);
+        assert!(!datagram.dont_fragment());
+        assert_eq!(datagram.dest_addr(), tunnel.remote_addr);
+
+        // The remote endpoint decapsulates what this one sends.
+        let remote = Tunnel::new(tunnel.remote_addr, tunnel.local_addr);
+        assert_eq!(remote.decapsulate(&datagram).unwrap(), packet.as_slice());
+        assert!(tunnel.decapsulate(&datagram).is_err());
+    }
+}
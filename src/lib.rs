@@ -1,7 +1,45 @@
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+extern crate alloc;
+
+pub mod arp;
+#[cfg(feature = "std")]
+pub mod capabilities;
 pub mod checksum;
+pub mod clock;
+pub mod compose;
+pub mod dhcp;
+pub mod dissect;
+pub mod dns;
 pub mod error;
+pub mod ethernet;
+#[cfg(feature = "arbitrary")]
+pub mod fuzz;
+#[cfg(feature = "std")]
+pub mod golden;
 pub mod icmpv4;
+pub mod icmpv6;
 pub mod ipv4;
+pub mod ipv6;
+pub mod lldp;
 pub mod macros;
+#[cfg(feature = "std")]
+pub mod metrics;
+#[cfg(feature = "std")]
+pub mod middlebox;
+pub mod mld;
+pub mod ndp;
+#[cfg(feature = "std")]
 pub mod net_device;
+pub mod options;
+#[cfg(feature = "std")]
+pub mod pcap;
+#[cfg(feature = "std")]
+pub mod pool;
 pub mod tcp;
+pub mod tftp;
+pub mod time;
+pub mod udp;
+
+#[cfg(feature = "std")]
+pub use crate::capabilities::capabilities;
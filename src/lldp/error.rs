@@ -0,0 +1,19 @@
+use core::fmt::{Display, Formatter};
+
+#[derive(Debug)]
+pub enum Error {
+    InvalidLength,
+    /// A mandatory TLV is missing, out of order, or malformed.
+    InvalidTlv,
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Error::InvalidLength => write!(f, "invalid length"),
+            Error::InvalidTlv => write!(f, "invalid tlv"),
+        }
+    }
+}
+
+impl core::error::Error for Error {}
@@ -0,0 +1,192 @@
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::error::Result;
+use crate::ethernet::builder::FrameBuilder;
+use crate::ethernet::frame::{EtherType, MacAddr};
+use crate::lldp::error::Error;
+
+pub mod consts {
+    use crate::ethernet::frame::MacAddr;
+
+    // The nearest bridge group address, which bridges do not forward (IEEE 802.1AB section 7.1)
+    pub const MULTICAST_ADDR: MacAddr = MacAddr([0x01, 0x80, 0xc2, 0x00, 0x00, 0x0e]);
+    pub const DEFAULT_TTL: u16 = 120; // The default transmit interval of 30 seconds times the hold multiplier of 4
+
+    // TLV types of IEEE 802.1AB section 8.4
+    pub const TLV_END: u8 = 0;
+    pub const TLV_CHASSIS_ID: u8 = 1;
+    pub const TLV_PORT_ID: u8 = 2;
+    pub const TLV_TTL: u8 = 3;
+    pub const TLV_SYSTEM_NAME: u8 = 5;
+    pub const MAX_TLV_LEN: usize = 511; // The length field is 9 bits long
+
+    pub const CHASSIS_ID_MAC_ADDR: u8 = 4;
+    pub const PORT_ID_MAC_ADDR: u8 = 3;
+    pub const PORT_ID_INTERFACE_NAME: u8 = 5;
+}
+
+/// A chassis or port identifier, whose subtype tells how to read the identifier.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Id {
+    pub subtype: u8,
+    pub id: Vec<u8>,
+}
+
+impl Id {
+    pub fn chassis_mac_addr(mac_addr: MacAddr) -> Self {
+        Self {
+            subtype: consts::CHASSIS_ID_MAC_ADDR,
+            id: mac_addr.octets().to_vec(),
+        }
+    }
+
+    pub fn port_interface_name(name: &str) -> Self {
+        Self {
+            subtype: consts::PORT_ID_INTERFACE_NAME,
+            id: name.as_bytes().to_vec(),
+        }
+    }
+
+    fn parse(value: &[u8]) -> Result<Self> {
+        // The identifier is 1 to 255 octets long (IEEE 802.1AB sections 8.5.2 and 8.5.3).
+        match value.split_first() {
+            Some((subtype, id)) if !id.is_empty() && id.len() <= 255 => Ok(Self {
+                subtype: *subtype,
+                id: id.to_vec(),
+            }),
+            _ => Err(Error::InvalidTlv.into()),
+        }
+    }
+}
+
+/// An LLDP data unit, with the mandatory TLVs and the system name (IEEE 802.1AB section 8).
+/// The TLVs not understood are ignored.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Lldpdu {
+    pub chassis_id: Id,
+    pub port_id: Id,
+    /// How many seconds the information is valid, zero tells neighbors to forget it at once.
+    pub ttl: u16,
+    pub system_name: Option<String>,
+}
+
+impl Lldpdu {
+    /// Parse the payload of an LLDP frame, which starts with the chassis ID, port ID and TTL TLVs in this order.
+    pub fn parse(mut buffer: &[u8]) -> Result<Self> {
+        let mut next_tlv = || -> Result<Option<(u8, &[u8])>> {
+            if buffer.is_empty() {
+                return Ok(None);
+            }
+            if buffer.len() < 2 {
+                return Err(Error::InvalidLength.into());
+            }
+            let header = u16::from_be_bytes([buffer[0], buffer[1]]);
+            let (r#type, len) = ((header >> 9) as u8, (header & 0x01ff) as usize);
+            if buffer.len() < 2 + len {
+                return Err(Error::InvalidLength.into());
+            }
+            let value = &buffer[2..2 + len];
+            buffer = &buffer[2 + len..];
+            Ok(Some((r#type, value)))
+        };
+        let mut mandatory = |r#type: u8| -> Result<&[u8]> {
+            match next_tlv()? {
+                Some((tlv_type, value)) if tlv_type == r#type => Ok(value),
+                _ => Err(Error::InvalidTlv.into()),
+            }
+        };
+
+        let chassis_id = Id::parse(mandatory(consts::TLV_CHASSIS_ID)?)?;
+        let port_id = Id::parse(mandatory(consts::TLV_PORT_ID)?)?;
+        let ttl = match mandatory(consts::TLV_TTL)? {
+            [high, low, ..] => u16::from_be_bytes([*high, *low]),
+            _ => return Err(Error::InvalidTlv.into()),
+        };
+
+        let mut system_name = None;
+        // The End TLV may be missing from the last octets of a frame, trailing padding is ignored past it.
+        while let Some((r#type, value)) = next_tlv()? {
+            match r#type {
+                consts::TLV_END => break,
+                consts::TLV_SYSTEM_NAME => system_name = Some(String::from_utf8_lossy(value).into_owned()),
+                _ => {}
+            }
+        }
+
+        Ok(Self {
+            chassis_id,
+            port_id,
+            ttl,
+            system_name,
+        })
+    }
+
+    pub fn build_vec(&self) -> Vec<u8> {
+        let mut buffer = vec![];
+        let mut push_tlv = |r#type: u8, value: &[u8]| {
+            let len = value.len().min(consts::MAX_TLV_LEN);
+            buffer.extend_from_slice(&((r#type as u16) << 9 | len as u16).to_be_bytes());
+            buffer.extend_from_slice(&value[..len]);
+        };
+
+        let id = |id: &Id| [&[id.subtype], id.id.as_slice()].concat();
+        push_tlv(consts::TLV_CHASSIS_ID, &id(&self.chassis_id));
+        push_tlv(consts::TLV_PORT_ID, &id(&self.port_id));
+        push_tlv(consts::TLV_TTL, &self.ttl.to_be_bytes());
+        if let Some(system_name) = &self.system_name {
+            push_tlv(consts::TLV_SYSTEM_NAME, system_name.as_bytes());
+        }
+        push_tlv(consts::TLV_END, &[]);
+        buffer
+    }
+
+    /// Build the frame announcing the LLDP data unit from `src_addr` to the nearest bridge group address.
+    pub fn build_frame(&self, src_addr: MacAddr) -> Vec<u8> {
+        FrameBuilder::default()
+            .dest_addr(consts::MULTICAST_ADDR)
+            .src_addr(src_addr)
+            .ether_type(EtherType::Lldp)
+            .payload(self.build_vec())
+            .build_vec()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{consts, Id, Lldpdu};
+    use crate::ethernet::frame::{EtherType, Frame, MacAddr};
+
+    #[test]
+    fn parse() {
+        let mac_addr = MacAddr([0x02, 0, 0, 0, 0, 0x01]);
+        let lldpdu = Lldpdu {
+            chassis_id: Id::chassis_mac_addr(mac_addr),
+            port_id: Id::port_interface_name("tap0"),
+            ttl: consts::DEFAULT_TTL,
+            system_name: Some("radish".to_string()),
+        };
+        let bytes = lldpdu.build_vec();
+        assert_eq!(&bytes[..9], &[0x02, 0x07, 0x04, 0x02, 0, 0, 0, 0, 0x01]);
+        assert_eq!(&bytes[bytes.len() - 2..], &[0, 0]);
+
+        let frame = lldpdu.build_frame(mac_addr);
+        let frame = Frame::new_checked(frame.as_slice()).unwrap();
+        assert_eq!(frame.dest_addr(), consts::MULTICAST_ADDR);
+        assert_eq!(frame.ether_type(), EtherType::Lldp);
+        assert_eq!(Lldpdu::parse(frame.payload()).unwrap(), lldpdu);
+
+        // Padding past the End TLV, and unknown TLVs, are ignored.
+        let mut padded = bytes.clone();
+        padded.extend_from_slice(&[0; 8]);
+        assert_eq!(Lldpdu::parse(&padded).unwrap(), lldpdu);
+        let mut unknown = bytes[..bytes.len() - 2].to_vec();
+        unknown.extend_from_slice(&[0xfe, 0x02, 0xaa, 0xbb]);
+        assert_eq!(Lldpdu::parse(&unknown).unwrap(), lldpdu);
+
+        // The chassis ID must come first.
+        assert!(Lldpdu::parse(&bytes[9..]).is_err());
+        assert!(Lldpdu::parse(&bytes[..12]).is_err());
+    }
+}
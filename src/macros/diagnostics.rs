@@ -0,0 +1,78 @@
+//! The diagnostics of radish, sent through `defmt` with the `defmt` feature, through `tracing`
+//! with the `tracing` feature, and through `log` otherwise.
+//!
+//! Their arguments, such as I/O errors, need not implement `defmt::Format`,
+//! so with `defmt` a message is formatted on the target before it is sent to the probe.
+//! Spans are only entered with `tracing`, the other backends having none.
+
+#[cfg(not(any(feature = "defmt", feature = "tracing")))]
+#[allow(unused_imports)]
+pub(crate) use log::{debug, error, info, trace, warn};
+
+#[cfg(all(feature = "tracing", not(feature = "defmt")))]
+#[allow(unused_imports)]
+pub(crate) use tracing::{debug, error, info, trace, warn};
+
+/// Enter a span of the `DEBUG` level until the end of the enclosing block.
+#[cfg(all(feature = "tracing", not(feature = "defmt")))]
+#[allow(unused_macros)]
+macro_rules! enter_span {
+    ($($arg:tt)+) => {
+        let _span = ::tracing::debug_span!($($arg)+).entered();
+    };
+}
+
+#[cfg(not(all(feature = "tracing", not(feature = "defmt"))))]
+#[allow(unused_macros)]
+macro_rules! enter_span {
+    ($($arg:tt)+) => {};
+}
+
+#[allow(unused_imports)]
+pub(crate) use enter_span;
+
+#[cfg(feature = "defmt")]
+#[allow(unused_macros)]
+macro_rules! defmt_trace {
+    ($($arg:tt)+) => {
+        ::defmt::trace!("{=str}", ::alloc::format!($($arg)+).as_str())
+    };
+}
+
+#[cfg(feature = "defmt")]
+#[allow(unused_macros)]
+macro_rules! defmt_debug {
+    ($($arg:tt)+) => {
+        ::defmt::debug!("{=str}", ::alloc::format!($($arg)+).as_str())
+    };
+}
+
+#[cfg(feature = "defmt")]
+#[allow(unused_macros)]
+macro_rules! defmt_info {
+    ($($arg:tt)+) => {
+        ::defmt::info!("{=str}", ::alloc::format!($($arg)+).as_str())
+    };
+}
+
+#[cfg(feature = "defmt")]
+#[allow(unused_macros)]
+macro_rules! defmt_warn {
+    ($($arg:tt)+) => {
+        ::defmt::warn!("{=str}", ::alloc::format!($($arg)+).as_str())
+    };
+}
+
+#[cfg(feature = "defmt")]
+#[allow(unused_macros)]
+macro_rules! defmt_error {
+    ($($arg:tt)+) => {
+        ::defmt::error!("{=str}", ::alloc::format!($($arg)+).as_str())
+    };
+}
+
+#[cfg(feature = "defmt")]
+#[allow(unused_imports)]
+pub(crate) use {
+    defmt_debug as debug, defmt_error as error, defmt_info as info, defmt_trace as trace, defmt_warn as warn,
+};
@@ -1 +1,2 @@
 pub mod c_like_enum;
+pub(crate) mod diagnostics;
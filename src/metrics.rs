@@ -0,0 +1,297 @@
+//! Counters and gauges of every subsystem, registered in one registry so they can be read together,
+//! as a snapshot or encoded in the Prometheus text format.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// A value that only goes up, such as the number of packets received.
+#[derive(Debug, Clone, Default)]
+pub struct Counter(Arc<AtomicU64>);
+
+impl Counter {
+    pub fn inc(&self) {
+        self.add(1);
+    }
+
+    pub fn add(&self, n: u64) {
+        self.0.fetch_add(n, Ordering::Relaxed);
+    }
+
+    pub fn get(&self) -> u64 {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// A value that goes up and down, such as the number of datagrams being reassembled.
+#[derive(Debug, Clone, Default)]
+pub struct Gauge(Arc<AtomicI64>);
+
+impl Gauge {
+    pub fn set(&self, value: i64) {
+        self.0.store(value, Ordering::Relaxed);
+    }
+
+    pub fn inc(&self) {
+        self.0.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn dec(&self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    pub fn get(&self) -> i64 {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Metric {
+    Counter(Counter),
+    Gauge(Gauge),
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Kind {
+    Counter,
+    Gauge,
+}
+
+/// The value of a metric when the snapshot was taken.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Sample {
+    pub name: &'static str,
+    pub help: &'static str,
+    pub kind: Kind,
+    pub value: i64,
+}
+
+/// The metrics of the subsystems sharing it, a clone sharing the metrics of the original.
+///
+/// Registering a name twice returns the metric registered first,
+/// so the subsystems given the same registry add to the same counters.
+#[derive(Debug, Clone, Default)]
+pub struct Registry {
+    metrics: Arc<Mutex<BTreeMap<&'static str, (&'static str, Metric)>>>,
+}
+
+impl Registry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register the counter `name`, described by `help`, or returns it if already registered.
+    ///
+    /// # Panics
+    /// If `name` is registered as a gauge.
+    pub fn counter(&self, name: &'static str, help: &'static str) -> Counter {
+        let mut metrics = self.metrics.lock().unwrap();
+        match &metrics
+            .entry(name)
+            .or_insert_with(|| (help, Metric::Counter(Counter::default())))
+            .1
+        {
+            Metric::Counter(counter) => counter.clone(),
+            Metric::Gauge(_) => panic!("metric {} registered as a gauge", name),
+        }
+    }
+
+    /// Register the gauge `name`, described by `help`, or returns it if already registered.
+    ///
+    /// # Panics
+    /// If `name` is registered as a counter.
+    pub fn gauge(&self, name: &'static str, help: &'static str) -> Gauge {
+        let mut metrics = self.metrics.lock().unwrap();
+        match &metrics
+            .entry(name)
+            .or_insert_with(|| (help, Metric::Gauge(Gauge::default())))
+            .1
+        {
+            Metric::Gauge(gauge) => gauge.clone(),
+            Metric::Counter(_) => panic!("metric {} registered as a counter", name),
+        }
+    }
+
+    /// Returns the values of the metrics, ordered by name.
+    pub fn snapshot(&self) -> Snapshot {
+        let metrics = self.metrics.lock().unwrap();
+        let samples = metrics
+            .iter()
+            .map(|(name, (help, metric))| {
+                let (kind, value) = match metric {
+                    Metric::Counter(counter) => (Kind::Counter, counter.get() as i64),
+                    Metric::Gauge(gauge) => (Kind::Gauge, gauge.get()),
+                };
+                Sample {
+                    name,
+                    help,
+                    kind,
+                    value,
+                }
+            })
+            .collect();
+        Snapshot { samples }
+    }
+}
+
+/// The values of the metrics of a registry at a point in time.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snapshot {
+    samples: Vec<Sample>,
+}
+
+impl Snapshot {
+    pub fn samples(&self) -> &[Sample] {
+        &self.samples
+    }
+
+    /// Returns the value of the metric `name`, if registered.
+    pub fn get(&self, name: &str) -> Option<i64> {
+        self.samples
+            .iter()
+            .find(|sample| sample.name == name)
+            .map(|sample| sample.value)
+    }
+
+    /// Encode the samples in the Prometheus text exposition format.
+    pub fn to_prometheus(&self) -> String {
+        let mut text = String::new();
+        for sample in &self.samples {
+            let kind = match sample.kind {
+                Kind::Counter => "counter",
+                Kind::Gauge => "gauge",
+            };
+            let _ = writeln!(text, "# HELP {} {}", sample.name, sample.help);
+            let _ = writeln!(text, "# TYPE {} {}", sample.name, kind);
+            let _ = writeln!(text, "{} {}", sample.name, sample.value);
+        }
+        text
+    }
+}
+
+/// The metrics of an interface, covering the device, reassembly, ICMP and TCP.
+#[derive(Debug, Clone)]
+pub struct InterfaceMetrics {
+    pub packets_received: Counter,
+    pub packets_transmitted: Counter,
+    pub datagrams_dropped: Counter,
+    pub checksum_errors: Counter,
+    pub fragments_transmitted: Counter,
+    pub fragments_received: Counter,
+    pub datagrams_reassembled: Counter,
+    pub reassembly_pending: Gauge,
+    pub icmp_errors_sent: Counter,
+    pub icmp_errors_rate_limited: Counter,
+    pub tcp_segments_received: Counter,
+    pub tcp_segments_transmitted: Counter,
+    pub tcp_segments_dropped: Counter,
+    pub tcp_connections: Gauge,
+    pub udp_datagrams_received: Counter,
+    pub udp_datagrams_transmitted: Counter,
+}
+
+impl InterfaceMetrics {
+    pub fn register(registry: &Registry) -> Self {
+        Self {
+            packets_received: registry.counter("radish_packets_received_total", "Packets read from the device"),
+            packets_transmitted: registry.counter(
+                "radish_packets_transmitted_total",
+                "Packets written to the device, fragments included",
+            ),
+            datagrams_dropped: registry.counter("radish_datagrams_dropped_total", "IPv4 datagrams dropped"),
+            checksum_errors: registry.counter(
+                "radish_checksum_errors_total",
+                "IPv4 datagrams dropped for their header checksum",
+            ),
+            fragments_transmitted: registry.counter(
+                "radish_fragments_transmitted_total",
+                "IPv4 fragments of datagrams longer than the MTU",
+            ),
+            fragments_received: registry.counter("radish_fragments_received_total", "IPv4 fragments received"),
+            datagrams_reassembled: registry.counter(
+                "radish_datagrams_reassembled_total",
+                "IPv4 datagrams reassembled from their fragments",
+            ),
+            reassembly_pending: registry.gauge(
+                "radish_reassembly_pending",
+                "IPv4 datagrams waiting for the rest of their fragments",
+            ),
+            icmp_errors_sent: registry.counter("radish_icmp_errors_sent_total", "ICMP error messages sent"),
+            icmp_errors_rate_limited: registry.counter(
+                "radish_icmp_errors_rate_limited_total",
+                "ICMP error messages held back by the rate limiter",
+            ),
+            tcp_segments_received: registry.counter("radish_tcp_segments_received_total", "TCP segments received"),
+            tcp_segments_transmitted: registry
+                .counter("radish_tcp_segments_transmitted_total", "TCP segments transmitted"),
+            tcp_segments_dropped: registry.counter(
+                "radish_tcp_segments_dropped_total",
+                "TCP segments dropped by the sockets",
+            ),
+            tcp_connections: registry.gauge("radish_tcp_connections", "TCP connections held by the sockets"),
+            udp_datagrams_received: registry.counter("radish_udp_datagrams_received_total", "UDP datagrams received"),
+            udp_datagrams_transmitted: registry
+                .counter("radish_udp_datagrams_transmitted_total", "UDP datagrams transmitted"),
+        }
+    }
+}
+
+/// The faults a `FaultDevice` introduced, in packets affected by each.
+#[derive(Debug, Clone)]
+pub struct FaultMetrics {
+    pub transmitted: Counter,
+    pub lost: Counter,
+    pub duplicated: Counter,
+    pub corrupted: Counter,
+    pub reordered: Counter,
+}
+
+impl FaultMetrics {
+    pub fn register(registry: &Registry) -> Self {
+        Self {
+            transmitted: registry.counter(
+                "radish_fault_transmitted_total",
+                "Packets transmitted through the faulty device",
+            ),
+            lost: registry.counter("radish_fault_lost_total", "Packets lost by the faulty device"),
+            duplicated: registry.counter(
+                "radish_fault_duplicated_total",
+                "Packets sent twice by the faulty device",
+            ),
+            corrupted: registry.counter("radish_fault_corrupted_total", "Packets corrupted by the faulty device"),
+            reordered: registry.counter("radish_fault_reordered_total", "Packets reordered by the faulty device"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{InterfaceMetrics, Registry};
+
+    #[test]
+    fn registry() {
+        let registry = Registry::new();
+        let first = InterfaceMetrics::register(&registry);
+        let second = InterfaceMetrics::register(&registry.clone());
+
+        // Both interfaces count into the same metrics.
+        first.packets_received.inc();
+        second.packets_received.add(2);
+        first.reassembly_pending.set(3);
+        second.reassembly_pending.dec();
+
+        let snapshot = registry.snapshot();
+        assert_eq!(snapshot.get("radish_packets_received_total"), Some(3));
+        assert_eq!(snapshot.get("radish_reassembly_pending"), Some(2));
+        assert_eq!(snapshot.get("radish_unknown"), None);
+
+        let text = snapshot.to_prometheus();
+        assert!(text.contains(
+            "# HELP radish_packets_received_total Packets read from the device\n\
+             # TYPE radish_packets_received_total counter\n\
+             radish_packets_received_total 3\n"
+        ));
+        assert!(text.contains("# TYPE radish_reassembly_pending gauge\nradish_reassembly_pending 2\n"));
+    }
+}
@@ -0,0 +1,358 @@
+use std::ops::Range;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::checksum::{checksum, IpAddress};
+use crate::ipv4::packet::consts::MIN_HEADER_LEN;
+use crate::ipv4::packet::{Packet, Protocol};
+use crate::ipv6::extension::ExtensionHeader;
+use crate::ipv6::packet::consts::HEADER_LEN as IPV6_HEADER_LEN;
+use crate::ipv6::packet::Packet as Ipv6Packet;
+use crate::tcp::packet::Packet as TcpPacket;
+
+/// A rewrite applied to packets passing through a middlebox.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Mangle {
+    /// Overwrite the time to live.
+    SetTtl(u8),
+    /// Clamp the MSS option of TCP SYN segments to the given value.
+    ClampMss(u16),
+    /// Remove all IPv4 options.
+    StripOptions,
+    /// Replace the identification with a pseudo-random value.
+    RandomizeIdentification,
+}
+
+/// A chain of mangle rules emulating common middlebox behaviors.
+pub struct Middlebox {
+    rules: Vec<Mangle>,
+    random_state: u32,
+}
+
+impl Middlebox {
+    pub fn new() -> Self {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.subsec_nanos())
+            .unwrap_or(0);
+
+        Self::with_seed(nanos)
+    }
+
+    /// Create a middlebox with a fixed seed, so randomized rewrites are reproducible.
+    pub fn with_seed(seed: u32) -> Self {
+        Self {
+            rules: vec![],
+            random_state: seed | 1, // xorshift state must be nonzero
+        }
+    }
+
+    pub fn rule(mut self, rule: Mangle) -> Self {
+        self.rules.push(rule);
+        self
+    }
+
+    pub fn rules(&self) -> &[Mangle] {
+        &self.rules
+    }
+
+    /// Apply all rules in order to a copy of `packet`, its header checksum recomputed afterwards.
+    /// Returns `None` when no rule rewrites it, the packet is only copied once one does.
+    pub fn process(&mut self, packet: &[u8]) -> Option<Packet<Vec<u8>>> {
+        let mut rewritten: Option<Packet<Vec<u8>>> = None;
+
+        for rule in &self.rules {
+            let current = Packet::new_unchecked(rewritten.as_ref().map_or(packet, |rewritten| rewritten.as_ref()));
+            match *rule {
+                Mangle::SetTtl(ttl) => {
+                    if current.ttl() != ttl {
+                        rewritten.get_or_insert_with(|| copy(packet)).set_ttl(ttl);
+                    }
+                }
+                Mangle::ClampMss(mss) => match rewritten.as_mut() {
+                    Some(rewritten) => {
+                        clamp_mss(rewritten, mss);
+                    }
+                    None => rewritten = copy_clamped(packet, mss),
+                },
+                Mangle::StripOptions => {
+                    if current.header_len() > MIN_HEADER_LEN {
+                        rewritten = Some(strip_options(rewritten.unwrap_or_else(|| copy(packet))));
+                    }
+                }
+                Mangle::RandomizeIdentification => {
+                    let identification = next_random(&mut self.random_state) as u16;
+                    rewritten
+                        .get_or_insert_with(|| copy(packet))
+                        .set_identification(identification);
+                }
+            }
+        }
+
+        let mut rewritten = rewritten?;
+        fill_header_checksum(&mut rewritten);
+        Some(rewritten)
+    }
+}
+
+fn copy(packet: &[u8]) -> Packet<Vec<u8>> {
+    Packet::new_unchecked(packet.to_vec())
+}
+
+/// Xorshift32, good enough to make identifications unpredictable for tests.
+fn next_random(state: &mut u32) -> u32 {
+    let mut x = *state;
+    x ^= x << 13;
+    x ^= x >> 17;
+    x ^= x << 5;
+    *state = x;
+    x
+}
+
+impl Default for Middlebox {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Clamp the MSS option of a TCP SYN segment carried by `packet`, recomputing the TCP checksum.
+/// Returns whether the segment was modified. The IPv4 header checksum is left untouched.
+pub fn clamp_mss(packet: &mut Packet<Vec<u8>>, mss: u16) -> bool {
+    match syn_segment(packet.as_ref()) {
+        Some(segment) => {
+            let (src_addr, dest_addr) = (packet.src_addr(), packet.dest_addr());
+            clamp_segment_mss(&mut packet.as_mut()[segment], src_addr, dest_addr, mss)
+        }
+        None => false,
+    }
+}
+
+/// Clamp the MSS option of a TCP SYN segment carried by an IPv6 `packet`, past its extension headers,
+/// recomputing the TCP checksum. Returns whether the segment was modified.
+/// Fragments are left untouched, as the checksum covers the whole segment.
+pub fn clamp_ipv6_mss(packet: &mut Ipv6Packet<Vec<u8>>, mss: u16) -> bool {
+    match ipv6_syn_segment(packet.as_ref()) {
+        Some(segment) => {
+            let (src_addr, dest_addr) = (packet.src_addr(), packet.dest_addr());
+            clamp_segment_mss(&mut packet.as_mut()[segment], src_addr, dest_addr, mss)
+        }
+        None => false,
+    }
+}
+
+/// Returns a copy of the IPv4 `packet` with the MSS of its TCP SYN segment clamped to `mss`,
+/// or `None` when it is left as is. Only SYN segments are copied.
+pub fn copy_clamped(packet: &[u8], mss: u16) -> Option<Packet<Vec<u8>>> {
+    syn_segment(packet)?;
+    let mut packet = copy(packet);
+    if clamp_mss(&mut packet, mss) {
+        Some(packet)
+    } else {
+        None
+    }
+}
+
+/// Returns a copy of the IPv6 `packet` with the MSS of its TCP SYN segment clamped to `mss`,
+/// or `None` when it is left as is. Only SYN segments are copied.
+pub fn copy_clamped_ipv6(packet: &[u8], mss: u16) -> Option<Ipv6Packet<Vec<u8>>> {
+    ipv6_syn_segment(packet)?;
+    let mut packet = Ipv6Packet::new_unchecked(packet.to_vec());
+    if clamp_ipv6_mss(&mut packet, mss) {
+        Some(packet)
+    } else {
+        None
+    }
+}
+
+/// Returns where the TCP SYN segment carried whole by an IPv4 datagram lies in it.
+fn syn_segment(buffer: &[u8]) -> Option<Range<usize>> {
+    if buffer.len() < (MIN_HEADER_LEN * 4) as usize {
+        return None;
+    }
+    let packet = Packet::new_unchecked(buffer);
+    if packet.protocol() != Protocol::Tcp || packet.offset() != 0 || packet.more_fragments() {
+        return None;
+    }
+
+    let total_len = packet.total_len() as usize;
+    let header_bytes_len = (packet.header_len() * 4) as usize;
+    if total_len > buffer.len() || header_bytes_len > total_len {
+        return None;
+    }
+
+    syn(buffer, header_bytes_len..total_len)
+}
+
+/// Returns where the TCP SYN segment of an IPv6 packet lies in it, past the extension headers.
+fn ipv6_syn_segment(buffer: &[u8]) -> Option<Range<usize>> {
+    let packet = Ipv6Packet::new_checked(buffer).ok()?;
+    let mut headers = packet.extension_headers();
+    if headers.any(|header| matches!(header, Ok(ExtensionHeader::Fragment { .. }) | Err(_))) {
+        return None;
+    }
+    let (protocol, offset) = headers.upper_layer();
+    if protocol != Protocol::Tcp {
+        return None;
+    }
+
+    syn(
+        buffer,
+        IPV6_HEADER_LEN + offset..IPV6_HEADER_LEN + packet.payload_len() as usize,
+    )
+}
+
+fn syn(buffer: &[u8], segment: Range<usize>) -> Option<Range<usize>> {
+    match TcpPacket::new_checked(&buffer[segment.clone()]) {
+        Ok(packet) if packet.syn() => Some(segment),
+        _ => None,
+    }
+}
+
+fn clamp_segment_mss<A: IpAddress>(segment: &mut [u8], src_addr: A, dest_addr: A, mss: u16) -> bool {
+    let mut segment = TcpPacket::new_unchecked(segment);
+
+    let options_end = (segment.data_offset() * 4) as usize;
+    let mut cursor = 20;
+    let mut modified = false;
+
+    while cursor < options_end {
+        let options = &mut segment.as_mut()[cursor..options_end];
+        match options[0] {
+            0 => break,
+            1 => cursor += 1,
+            kind => {
+                let length = match options.get(1) {
+                    Some(&length) if length >= 2 && length as usize <= options.len() => length as usize,
+                    _ => break,
+                };
+                if kind == 2 && length == 4 {
+                    let current = u16::from_be_bytes([options[2], options[3]]);
+                    if current > mss {
+                        options[2..4].copy_from_slice(mss.to_be_bytes().as_ref());
+                        modified = true;
+                    }
+                }
+                cursor += length;
+            }
+        }
+    }
+
+    if modified {
+        segment.fill_checksum(src_addr, dest_addr);
+    }
+
+    modified
+}
+
+/// Remove all IPv4 options, shrinking the header to its minimum length.
+pub fn strip_options(packet: Packet<Vec<u8>>) -> Packet<Vec<u8>> {
+    let header_bytes_len = (packet.header_len() * 4) as usize;
+    let min_header_bytes_len = (MIN_HEADER_LEN * 4) as usize;
+
+    if header_bytes_len <= min_header_bytes_len {
+        return packet;
+    }
+
+    let total_len = (packet.total_len() as usize).min(packet.as_ref().len());
+    let mut buffer = packet.as_ref()[..min_header_bytes_len].to_vec();
+    buffer.extend_from_slice(&packet.as_ref()[header_bytes_len..total_len]);
+
+    let mut stripped = Packet::new_unchecked(buffer);
+    stripped.set_header_len(MIN_HEADER_LEN);
+    stripped.set_total_len((total_len - (header_bytes_len - min_header_bytes_len)) as u16);
+    stripped
+}
+
+/// Recompute the IPv4 header checksum.
+pub fn fill_header_checksum(packet: &mut Packet<Vec<u8>>) {
+    packet.set_checksum(0);
+    let checksum_value = checksum(&packet.as_ref()[..(packet.header_len() * 4) as usize]);
+    packet.set_checksum(checksum_value);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use super::{Mangle, Middlebox};
+    use crate::checksum::checksum;
+    use crate::ipv4::builder::PacketBuilder;
+    use crate::ipv4::packet::{Packet, Protocol};
+    use crate::tcp::builder::PacketBuilder as TcpPacketBuilder;
+    use crate::tcp::packet::{Packet as TcpPacket, TcpOption};
+
+    const SRC_ADDR: Ipv4Addr = Ipv4Addr::new(192, 168, 233, 233);
+    const DEST_ADDR: Ipv4Addr = Ipv4Addr::new(192, 168, 233, 234);
+
+    fn syn_packet(mss: u16) -> Packet<Vec<u8>> {
+        let segment = TcpPacketBuilder::default()
+            .src_port(40000)
+            .dest_port(80)
+            .syn(true)
+            .option(TcpOption::MaxSegmentSize(mss))
+            .src_addr(SRC_ADDR)
+            .dest_addr(DEST_ADDR)
+            .build_vec();
+
+        PacketBuilder::default()
+            .ttl(64)
+            .protocol(Protocol::Tcp)
+            .src_addr(SRC_ADDR)
+            .dest_addr(DEST_ADDR)
+            .payload(segment)
+            .build()
+    }
+
+    #[test]
+    fn clamp_mss() {
+        let mut middlebox = Middlebox::new().rule(Mangle::ClampMss(1400)).rule(Mangle::SetTtl(1));
+        let packet = middlebox.process(syn_packet(1460).as_ref()).unwrap();
+
+        assert_eq!(packet.ttl(), 1);
+        assert_eq!(checksum(&packet.as_ref()[..20]), 0);
+
+        let segment = TcpPacket::new_checked(packet.payload()).unwrap();
+        assert_eq!(
+            segment.options().next().unwrap().unwrap(),
+            TcpOption::MaxSegmentSize(1400)
+        );
+        assert!(segment.verify_checksum(SRC_ADDR, DEST_ADDR));
+
+        // A smaller MSS is left untouched.
+        let packet = middlebox.process(syn_packet(536).as_ref()).unwrap();
+        let segment = TcpPacket::new_checked(packet.payload()).unwrap();
+        assert_eq!(
+            segment.options().next().unwrap().unwrap(),
+            TcpOption::MaxSegmentSize(536)
+        );
+
+        // Nor is a packet copied when no rule rewrites it.
+        let mut middlebox = Middlebox::new().rule(Mangle::ClampMss(1400)).rule(Mangle::SetTtl(64));
+        assert!(middlebox.process(syn_packet(536).as_ref()).is_none());
+    }
+
+    #[test]
+    fn strip_options_and_randomize_identification() {
+        let mut buffer = PacketBuilder::default()
+            .header_len(6)
+            .identification(0x1234)
+            .ttl(64)
+            .protocol(Protocol::Udp)
+            .src_addr(SRC_ADDR)
+            .dest_addr(DEST_ADDR)
+            .payload(vec![1, 2, 3, 4])
+            .build_vec();
+        buffer[20..24].copy_from_slice(&[1, 1, 1, 0]); // NOP, NOP, NOP, End
+
+        let mut middlebox = Middlebox::with_seed(42)
+            .rule(Mangle::StripOptions)
+            .rule(Mangle::RandomizeIdentification);
+        let packet = middlebox.process(&buffer).unwrap();
+
+        assert_eq!(packet.header_len(), 5);
+        assert_eq!(packet.total_len(), 24);
+        assert_eq!(packet.payload(), &[1, 2, 3, 4]);
+        assert_ne!(packet.identification(), 0x1234);
+        assert!(Packet::new_checked(packet.as_ref()).is_ok());
+        assert_eq!(checksum(&packet.as_ref()[..20]), 0);
+    }
+}
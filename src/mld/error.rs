@@ -0,0 +1,25 @@
+use core::fmt::{Display, Formatter};
+
+#[derive(Debug)]
+pub enum Error {
+    InvalidLength,
+    InvalidMessageType,
+    InvalidChecksum,
+    /// Multicast Listener Discovery messages must be sent with a hop limit of 1, they never leave the link.
+    InvalidHopLimit,
+    InvalidAddress,
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Error::InvalidLength => write!(f, "invalid length"),
+            Error::InvalidMessageType => write!(f, "invalid message type"),
+            Error::InvalidChecksum => write!(f, "invalid checksum"),
+            Error::InvalidHopLimit => write!(f, "invalid hop limit"),
+            Error::InvalidAddress => write!(f, "invalid address"),
+        }
+    }
+}
+
+impl core::error::Error for Error {}
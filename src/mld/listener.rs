@@ -0,0 +1,300 @@
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::hash::BuildHasher;
+use std::net::Ipv6Addr;
+use std::time::{Duration, Instant};
+
+use crate::mld::packet::{consts as packet_consts, AddressRecord, Message, RecordType};
+use crate::ndp::packet::consts::{ALL_NODES, ALL_ROUTERS};
+
+pub mod consts {
+    use std::time::Duration;
+
+    // Protocol constants of RFC 3810 section 9
+    pub const ROBUSTNESS: u8 = 2;
+    pub const QUERY_INTERVAL: Duration = Duration::from_secs(125);
+    pub const QUERY_RESPONSE_INTERVAL: Duration = Duration::from_secs(10);
+    pub const UNSOLICITED_REPORT_INTERVAL: Duration = Duration::from_secs(1);
+}
+
+/// The version of the protocol the listener speaks, which follows the oldest querier on the link.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Version {
+    V1,
+    V2,
+}
+
+/// A change of the membership of a group, reported more than once in case a report is lost.
+#[derive(Debug)]
+struct StateChange {
+    group: Ipv6Addr,
+    joined: bool,
+    retransmissions: u8,
+    /// When the change is reported next, right away when none.
+    next: Option<Instant>,
+}
+
+/// The multicast listener part of Multicast Listener Discovery for hosts (RFC 3810 section 6),
+/// falling back to MLDv1 while an MLDv1 querier is present (RFC 3810 section 8.2).
+///
+/// Groups are listened to from any source, so every record is an exclude record with no sources.
+#[derive(Debug)]
+pub struct Listener {
+    /// The groups listened to, with when the report answering a query for them is due.
+    groups: HashMap<Ipv6Addr, Option<Instant>>,
+    changes: Vec<StateChange>,
+    /// When the report answering an MLDv2 general query is due.
+    general_report: Option<Instant>,
+    /// The host speaks MLDv1 until then, the Older Version Querier Present timeout.
+    v1_querier_until: Option<Instant>,
+    robustness: u8,
+    random_state: RandomState,
+    random_counter: u64,
+}
+
+impl Listener {
+    pub fn new() -> Self {
+        Self {
+            groups: HashMap::new(),
+            changes: Vec::new(),
+            general_report: None,
+            v1_querier_until: None,
+            robustness: consts::ROBUSTNESS,
+            random_state: RandomState::new(),
+            random_counter: 0,
+        }
+    }
+
+    pub fn version(&self, now: Instant) -> Version {
+        match self.v1_querier_until {
+            Some(until) if until > now => Version::V1,
+            _ => Version::V2,
+        }
+    }
+
+    pub fn is_member(&self, group: Ipv6Addr) -> bool {
+        self.groups.contains_key(&group)
+    }
+
+    /// Start listening to `group`, which is reported right away, returns false when it was already listened to.
+    pub fn join(&mut self, group: Ipv6Addr) -> bool {
+        if self.groups.insert(group, None).is_some() {
+            return false;
+        }
+        self.change(group, true);
+        true
+    }
+
+    /// Stop listening to `group`, returns false when it was not listened to.
+    pub fn leave(&mut self, group: Ipv6Addr) -> bool {
+        if self.groups.remove(&group).is_none() {
+            return false;
+        }
+        self.change(group, false);
+        true
+    }
+
+    fn change(&mut self, group: Ipv6Addr, joined: bool) {
+        if !reportable(group) {
+            return;
+        }
+        // A newer change of the group replaces the pending one.
+        self.changes.retain(|change| change.group != group);
+        self.changes.push(StateChange {
+            group,
+            joined,
+            retransmissions: self.robustness,
+            next: None,
+        });
+    }
+
+    /// Handle a message received on the link, a query schedules the reports answering it
+    /// after a random delay, and an MLDv1 report of another listener suppresses ours.
+    pub fn process(&mut self, message: &Message, now: Instant) {
+        match message {
+            Message::Query {
+                max_response_delay,
+                group,
+                v2,
+            } => {
+                match v2 {
+                    Some(v2) if v2.robustness != 0 => self.robustness = v2.robustness,
+                    Some(_) => {}
+                    None => {
+                        let timeout = consts::QUERY_INTERVAL * self.robustness as u32 + consts::QUERY_RESPONSE_INTERVAL;
+                        self.v1_querier_until = Some(now + timeout);
+                    }
+                }
+
+                let at = now + self.random_delay(*max_response_delay);
+                let earliest = |due: &mut Option<Instant>| *due = Some(due.map_or(at, |due| due.min(at)));
+                if !group.is_unspecified() {
+                    if let Some(due) = self.groups.get_mut(group) {
+                        earliest(due);
+                    }
+                } else if self.version(now) == Version::V2 {
+                    earliest(&mut self.general_report);
+                } else {
+                    self.groups.values_mut().for_each(earliest);
+                }
+            }
+            Message::Report { group } if self.version(now) == Version::V1 => {
+                if let Some(due) = self.groups.get_mut(group) {
+                    *due = None;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Returns the messages due at `now`, with the address they are sent to.
+    pub fn poll(&mut self, now: Instant) -> Vec<(Ipv6Addr, Message)> {
+        let version = self.version(now);
+        let mut messages = Vec::new();
+        let mut records = Vec::new();
+        let mut record = |group, record_type, messages: &mut Vec<(Ipv6Addr, Message)>| match version {
+            Version::V2 => records.push(AddressRecord {
+                record_type,
+                group,
+                sources: vec![],
+            }),
+            Version::V1 if record_type == RecordType::ChangeToInclude => {
+                messages.push((ALL_ROUTERS, Message::Done { group }))
+            }
+            Version::V1 => messages.push((group, Message::Report { group })),
+        };
+
+        let mut changes = std::mem::take(&mut self.changes);
+        for change in changes
+            .iter_mut()
+            .filter(|change| change.next.is_none_or(|next| next <= now))
+        {
+            let record_type = if change.joined {
+                RecordType::ChangeToExclude
+            } else {
+                RecordType::ChangeToInclude
+            };
+            record(change.group, record_type, &mut messages);
+            change.retransmissions -= 1;
+            change.next = Some(now + self.random_delay(consts::UNSOLICITED_REPORT_INTERVAL));
+        }
+        changes.retain(|change| change.retransmissions > 0);
+        self.changes = changes;
+
+        let general = self.general_report.is_some_and(|due| due <= now);
+        if general {
+            self.general_report = None;
+        }
+        for (group, due) in self.groups.iter_mut() {
+            if general || due.is_some_and(|due| due <= now) {
+                *due = None;
+                if reportable(*group) {
+                    record(*group, RecordType::ModeIsExclude, &mut messages);
+                }
+            }
+        }
+
+        if !records.is_empty() {
+            messages.push((packet_consts::ALL_MLDV2_ROUTERS, Message::ReportV2(records)));
+        }
+        messages
+    }
+
+    /// Returns a random delay up to `max`, so the listeners on the link do not answer a query at once.
+    fn random_delay(&mut self, max: Duration) -> Duration {
+        self.random_counter += 1;
+        let random = self.random_state.hash_one(self.random_counter);
+        Duration::from_millis(random % (max.as_millis() as u64 + 1))
+    }
+}
+
+impl Default for Listener {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Whether membership of `group` is reported, which is never the case of the all-nodes address
+/// nor of interface-local groups (RFC 3810 section 6).
+fn reportable(group: Ipv6Addr) -> bool {
+    group != ALL_NODES && group.segments()[0] & 0x000f > 1
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv6Addr;
+    use std::time::{Duration, Instant};
+
+    use super::{consts, Listener, Version};
+    use crate::mld::packet::{consts as packet_consts, AddressRecord, Message, QueryV2, RecordType};
+    use crate::ndp::packet::consts::{ALL_NODES, ALL_ROUTERS};
+
+    const GROUP: Ipv6Addr = Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 0, 0xfb);
+
+    fn record(record_type: RecordType) -> Message {
+        Message::ReportV2(vec![AddressRecord {
+            record_type,
+            group: GROUP,
+            sources: vec![],
+        }])
+    }
+
+    #[test]
+    fn report() {
+        let now = Instant::now();
+        let mut listener = Listener::new();
+        assert!(listener.join(ALL_NODES));
+        assert!(listener.poll(now).is_empty());
+
+        // A join is reported right away, and once more within the unsolicited report interval.
+        assert!(listener.join(GROUP));
+        assert!(!listener.join(GROUP));
+        let joined = (packet_consts::ALL_MLDV2_ROUTERS, record(RecordType::ChangeToExclude));
+        assert_eq!(listener.poll(now), vec![joined.clone()]);
+        let later = now + consts::UNSOLICITED_REPORT_INTERVAL;
+        assert_eq!(listener.poll(later), vec![joined]);
+        assert!(listener.poll(later).is_empty());
+
+        // A general query is answered with the state of every reportable group.
+        let delay = Duration::from_secs(1);
+        let query = Message::Query {
+            max_response_delay: delay,
+            group: Ipv6Addr::UNSPECIFIED,
+            v2: Some(QueryV2 {
+                suppress: false,
+                robustness: 2,
+                query_interval: consts::QUERY_INTERVAL,
+                sources: vec![],
+            }),
+        };
+        listener.process(&query, later);
+        assert_eq!(
+            listener.poll(later + delay),
+            vec![(packet_consts::ALL_MLDV2_ROUTERS, record(RecordType::ModeIsExclude))]
+        );
+
+        // An MLDv1 query switches the listener to MLDv1 until the querier is gone.
+        let query = Message::Query {
+            max_response_delay: delay,
+            group: GROUP,
+            v2: None,
+        };
+        listener.process(&query, later);
+        assert_eq!(listener.version(later), Version::V1);
+        listener.process(&Message::Report { group: GROUP }, later);
+        assert!(listener.poll(later + delay).is_empty());
+        listener.process(&query, later);
+        assert_eq!(
+            listener.poll(later + delay),
+            vec![(GROUP, Message::Report { group: GROUP })]
+        );
+
+        assert!(listener.leave(GROUP));
+        assert_eq!(
+            listener.poll(later),
+            vec![(ALL_ROUTERS, Message::Done { group: GROUP })]
+        );
+        assert!(!listener.is_member(GROUP));
+        assert_eq!(listener.version(later + consts::QUERY_INTERVAL * 3), Version::V2);
+    }
+}
@@ -0,0 +1,4 @@
+pub mod error;
+#[cfg(feature = "std")]
+pub mod listener;
+pub mod packet;
@@ -0,0 +1,366 @@
+use alloc::vec;
+use alloc::vec::Vec;
+use core::convert::TryInto;
+use core::net::Ipv6Addr;
+use core::time::Duration;
+
+use crate::c_like_enum;
+use crate::error::Result;
+use crate::icmpv6::packet::{MessageType, Packet as Icmpv6Packet};
+use crate::ipv4::packet::Protocol;
+use crate::ipv6::builder::PacketBuilder;
+use crate::ipv6::extension::consts as extension_consts;
+use crate::mld::error::Error;
+
+pub mod consts {
+    use core::net::Ipv6Addr;
+
+    pub const HOP_LIMIT: u8 = 1; // RFC 3810 section 5, messages never leave the link
+    pub const ALL_MLDV2_ROUTERS: Ipv6Addr = Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 0, 0x16); // RFC 3810 section 5.2.14
+    pub const V1_LEN: usize = 24; // RFC 2710 section 3
+    pub const V2_QUERY_LEN: usize = 28; // RFC 3810 section 5.1
+    pub const FLAG_SUPPRESS: u8 = 0x08;
+}
+
+c_like_enum!(
+    /// Multicast address record types (RFC 3810 section 5.2.12)
+    #[derive(Debug, Copy, Clone, PartialEq, Eq)]
+    pub enum RecordType(u8) {
+        ModeIsInclude = 1,
+        ModeIsExclude = 2,
+        ChangeToInclude = 3,
+        ChangeToExclude = 4,
+        AllowNewSources = 5,
+        BlockOldSources = 6,
+    }
+);
+
+/// The fields an MLDv2 query adds to an MLDv1 one (RFC 3810 section 5.1).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QueryV2 {
+    /// Routers do not update their timers on hearing the query.
+    pub suppress: bool,
+    /// The Querier's Robustness Variable, zero when it is above 7.
+    pub robustness: u8,
+    pub query_interval: Duration,
+    /// The sources of a group-and-source specific query.
+    pub sources: Vec<Ipv6Addr>,
+}
+
+/// The state of a multicast address, or a change of it, in an MLDv2 report (RFC 3810 section 5.2.4).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AddressRecord {
+    pub record_type: RecordType,
+    pub group: Ipv6Addr,
+    pub sources: Vec<Ipv6Addr>,
+}
+
+/// A Multicast Listener Discovery message of RFC 2710 (MLDv1) or RFC 3810 (MLDv2).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Message {
+    /// A general query when `group` is unspecified, MLDv1 queries have no `v2` part.
+    Query {
+        max_response_delay: Duration,
+        group: Ipv6Addr,
+        v2: Option<QueryV2>,
+    },
+    Report {
+        group: Ipv6Addr,
+    },
+    Done {
+        group: Ipv6Addr,
+    },
+    ReportV2(Vec<AddressRecord>),
+}
+
+impl Message {
+    /// Parse and validate the ICMPv6 message `buffer` received from `src_addr` to `dest_addr`
+    /// with `hop_limit`, following the validation rules of RFC 3810 sections 5.1.14 and 5.2.13.
+    pub fn parse(src_addr: Ipv6Addr, dest_addr: Ipv6Addr, hop_limit: u8, buffer: &[u8]) -> Result<Self> {
+        let packet = Icmpv6Packet::new_checked(buffer)?;
+        let r#type = packet.r#type();
+        if !matches!(
+            r#type,
+            MessageType::MulticastListenerQuery
+                | MessageType::MulticastListenerReport
+                | MessageType::MulticastListenerDone
+                | MessageType::MulticastListenerReportV2
+        ) {
+            return Err(Error::InvalidMessageType.into());
+        }
+        if hop_limit != consts::HOP_LIMIT {
+            return Err(Error::InvalidHopLimit.into());
+        }
+        if !packet.verify_checksum(src_addr, dest_addr) {
+            return Err(Error::InvalidChecksum.into());
+        }
+        // Reports may come from hosts without a link-local address yet, queries only from routers.
+        if !src_addr.is_unicast_link_local()
+            && (r#type == MessageType::MulticastListenerQuery || !src_addr.is_unspecified())
+        {
+            return Err(Error::InvalidAddress.into());
+        }
+
+        if r#type == MessageType::MulticastListenerReportV2 {
+            return Ok(Message::ReportV2(parse_records(buffer)?));
+        }
+
+        if buffer.len() < consts::V1_LEN {
+            return Err(Error::InvalidLength.into());
+        }
+        let group = addr(&buffer[8..24]);
+        let message = match r#type {
+            MessageType::MulticastListenerQuery => {
+                let code = u16::from_be_bytes([buffer[4], buffer[5]]);
+                // A query of 24 octets is an MLDv1 one, any other length below 28 is invalid (RFC 3810 section 8.1).
+                let v2 = match buffer.len() {
+                    consts::V1_LEN => None,
+                    len if len < consts::V2_QUERY_LEN => return Err(Error::InvalidLength.into()),
+                    len => {
+                        let sources = u16::from_be_bytes([buffer[26], buffer[27]]) as usize;
+                        if len < consts::V2_QUERY_LEN + sources * 16 {
+                            return Err(Error::InvalidLength.into());
+                        }
+                        Some(QueryV2 {
+                            suppress: buffer[24] & consts::FLAG_SUPPRESS != 0,
+                            robustness: buffer[24] & 0x07,
+                            query_interval: Duration::from_secs(decode_float(buffer[25] as u32, 8) as u64),
+                            sources: addrs(&buffer[consts::V2_QUERY_LEN..], sources),
+                        })
+                    }
+                };
+                Message::Query {
+                    // MLDv1 carries the delay in milliseconds, MLDv2 encodes larger ones as floating point.
+                    max_response_delay: Duration::from_millis(match v2 {
+                        Some(_) => decode_float(code as u32, 16) as u64,
+                        None => code as u64,
+                    }),
+                    group,
+                    v2,
+                }
+            }
+            MessageType::MulticastListenerReport => Message::Report { group },
+            _ => Message::Done { group },
+        };
+        Ok(message)
+    }
+
+    pub fn r#type(&self) -> MessageType {
+        match self {
+            Message::Query { .. } => MessageType::MulticastListenerQuery,
+            Message::Report { .. } => MessageType::MulticastListenerReport,
+            Message::Done { .. } => MessageType::MulticastListenerDone,
+            Message::ReportV2(_) => MessageType::MulticastListenerReportV2,
+        }
+    }
+
+    /// Build the ICMPv6 message sent from `src_addr` to `dest_addr`, with its checksum.
+    pub fn build_vec(&self, src_addr: Ipv6Addr, dest_addr: Ipv6Addr) -> Vec<u8> {
+        let mut buffer: Vec<u8> = vec![0; 8];
+        buffer[0] = self.r#type().into();
+
+        match self {
+            Message::Query {
+                max_response_delay,
+                group,
+                v2,
+            } => {
+                let delay = max_response_delay.as_millis().min(u32::MAX as u128) as u32;
+                let code = match v2 {
+                    Some(_) => encode_float(delay, 16),
+                    None => delay.min(u16::MAX as u32),
+                };
+                buffer[4..6].copy_from_slice(&(code as u16).to_be_bytes());
+                buffer.extend_from_slice(&group.octets());
+                if let Some(v2) = v2 {
+                    let robustness = if v2.robustness > 7 { 0 } else { v2.robustness };
+                    let interval = v2.query_interval.as_secs().min(u32::MAX as u64) as u32;
+                    buffer.push(if v2.suppress { consts::FLAG_SUPPRESS } else { 0 } | robustness);
+                    buffer.push(encode_float(interval, 8) as u8);
+                    buffer.extend_from_slice(&(v2.sources.len() as u16).to_be_bytes());
+                    for source in v2.sources.iter() {
+                        buffer.extend_from_slice(&source.octets());
+                    }
+                }
+            }
+            Message::Report { group } | Message::Done { group } => buffer.extend_from_slice(&group.octets()),
+            Message::ReportV2(records) => {
+                buffer[6..8].copy_from_slice(&(records.len() as u16).to_be_bytes());
+                for record in records.iter() {
+                    buffer.extend_from_slice(&[record.record_type.into(), 0]);
+                    buffer.extend_from_slice(&(record.sources.len() as u16).to_be_bytes());
+                    buffer.extend_from_slice(&record.group.octets());
+                    for source in record.sources.iter() {
+                        buffer.extend_from_slice(&source.octets());
+                    }
+                }
+            }
+        }
+
+        Icmpv6Packet::new_unchecked(buffer.as_mut_slice()).fill_checksum(src_addr, dest_addr);
+        buffer
+    }
+
+    /// Build the IPv6 packet carrying the message, with a hop limit of 1 and the Router Alert option
+    /// routers need to look at messages for groups they do not listen to (RFC 3810 section 5).
+    pub fn build_packet(&self, src_addr: Ipv6Addr, dest_addr: Ipv6Addr) -> Vec<u8> {
+        let mut payload = vec![
+            Protocol::Icmpv6.into(),
+            0,
+            extension_consts::OPTION_ROUTER_ALERT,
+            2,
+            0,
+            0,
+            extension_consts::OPTION_PADN,
+            0,
+        ];
+        payload.extend_from_slice(&self.build_vec(src_addr, dest_addr));
+
+        PacketBuilder::default()
+            .next_header(Protocol::HopByHop)
+            .hop_limit(consts::HOP_LIMIT)
+            .src_addr(src_addr)
+            .dest_addr(dest_addr)
+            .payload(payload)
+            .build_vec()
+    }
+}
+
+fn parse_records(buffer: &[u8]) -> Result<Vec<AddressRecord>> {
+    if buffer.len() < 8 {
+        return Err(Error::InvalidLength.into());
+    }
+    let count = u16::from_be_bytes([buffer[6], buffer[7]]);
+    let mut rest = &buffer[8..];
+    let mut records = Vec::with_capacity(count as usize);
+
+    for _ in 0..count {
+        if rest.len() < 20 {
+            return Err(Error::InvalidLength.into());
+        }
+        let sources = u16::from_be_bytes([rest[2], rest[3]]) as usize;
+        // The auxiliary data length is in units of 32-bit words.
+        let len = 20 + sources * 16 + rest[1] as usize * 4;
+        if rest.len() < len {
+            return Err(Error::InvalidLength.into());
+        }
+        records.push(AddressRecord {
+            record_type: rest[0].into(),
+            group: addr(&rest[4..20]),
+            sources: addrs(&rest[20..], sources),
+        });
+        rest = &rest[len..];
+    }
+    Ok(records)
+}
+
+fn addr(buffer: &[u8]) -> Ipv6Addr {
+    let octets: [u8; 16] = buffer[..16].try_into().unwrap();
+    Ipv6Addr::from(octets)
+}
+
+fn addrs(buffer: &[u8], count: usize) -> Vec<Ipv6Addr> {
+    buffer.chunks_exact(16).take(count).map(addr).collect()
+}
+
+/// Decode the `bits` long Maximum Response Code or QQIC, which past half their range hold
+/// a 3-bit exponent and a mantissa (RFC 3810 sections 5.1.3 and 5.1.9).
+fn decode_float(code: u32, bits: u32) -> u32 {
+    let mant_bits = bits - 4;
+    if code < 1 << (bits - 1) {
+        return code;
+    }
+    let exp = (code >> mant_bits) & 0x07;
+    let mant = code & ((1 << mant_bits) - 1);
+    (mant | 1 << mant_bits) << (exp + 3)
+}
+
+/// The inverse of `decode_float`, rounding down, and saturating at the largest value.
+fn encode_float(value: u32, bits: u32) -> u32 {
+    let mant_bits = bits - 4;
+    if value < 1 << (bits - 1) {
+        return value;
+    }
+    for exp in 0..8 {
+        let mant = value >> (exp + 3);
+        if mant < 1 << (mant_bits + 1) {
+            return 1 << (bits - 1) | exp << mant_bits | (mant & ((1 << mant_bits) - 1));
+        }
+    }
+    (1 << bits) - 1
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv6Addr;
+    use std::time::Duration;
+
+    use super::{consts, decode_float, encode_float, AddressRecord, Message, QueryV2, RecordType};
+    use crate::ipv6::extension::{consts as extension_consts, ExtensionHeader};
+    use crate::ipv6::packet::Packet;
+
+    const ROUTER: Ipv6Addr = Ipv6Addr::new(0xfe80, 0, 0, 0, 0, 0, 0, 1);
+    const HOST: Ipv6Addr = Ipv6Addr::new(0xfe80, 0, 0, 0, 0, 0, 0, 2);
+    const GROUP: Ipv6Addr = Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 0, 0xfb);
+
+    #[test]
+    fn parse() {
+        let all_nodes = Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 0, 1);
+        let query = Message::Query {
+            max_response_delay: Duration::from_secs(10),
+            group: Ipv6Addr::UNSPECIFIED,
+            v2: None,
+        };
+        let bytes = query.build_vec(ROUTER, all_nodes);
+        assert_eq!(bytes.len(), consts::V1_LEN);
+        assert_eq!(Message::parse(ROUTER, all_nodes, 1, &bytes).unwrap(), query);
+        assert!(Message::parse(ROUTER, all_nodes, 255, &bytes).is_err());
+        assert!(Message::parse(Ipv6Addr::UNSPECIFIED, all_nodes, 1, &bytes).is_err());
+
+        let query = Message::Query {
+            max_response_delay: Duration::from_millis(40960),
+            group: GROUP,
+            v2: Some(QueryV2 {
+                suppress: true,
+                robustness: 2,
+                query_interval: Duration::from_secs(125),
+                sources: vec![ROUTER],
+            }),
+        };
+        let bytes = query.build_vec(ROUTER, GROUP);
+        assert_eq!(bytes.len(), consts::V2_QUERY_LEN + 16);
+        assert_eq!(Message::parse(ROUTER, GROUP, 1, &bytes).unwrap(), query);
+        assert!(Message::parse(ROUTER, GROUP, 1, &bytes[..26]).is_err());
+
+        let report = Message::ReportV2(vec![AddressRecord {
+            record_type: RecordType::ChangeToExclude,
+            group: GROUP,
+            sources: vec![],
+        }]);
+        let bytes = report.build_packet(HOST, consts::ALL_MLDV2_ROUTERS);
+        let packet = Packet::new_checked(bytes.as_slice()).unwrap();
+        assert_eq!(packet.hop_limit(), consts::HOP_LIMIT);
+        let mut headers = packet.extension_headers();
+        match headers.next() {
+            Some(Ok(ExtensionHeader::HopByHop(mut options))) => {
+                assert_eq!(
+                    options.next().unwrap().unwrap(),
+                    (extension_consts::OPTION_ROUTER_ALERT, [0, 0].as_ref())
+                );
+            }
+            header => panic!("unexpected {:?}", header),
+        }
+        let (_, offset) = headers.upper_layer();
+        let message = &packet.payload()[offset..];
+        assert_eq!(
+            Message::parse(HOST, consts::ALL_MLDV2_ROUTERS, 1, message).unwrap(),
+            report
+        );
+        assert!(Message::parse(HOST, consts::ALL_MLDV2_ROUTERS, 1, &message[..message.len() - 1]).is_err());
+
+        assert_eq!(decode_float(0x8000, 16), 32768);
+        assert_eq!(decode_float(0xffff, 16), 8387584);
+        assert_eq!(encode_float(125, 8), 125);
+        assert_eq!(decode_float(encode_float(300, 8), 8), 288);
+    }
+}
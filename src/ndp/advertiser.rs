@@ -0,0 +1,174 @@
+use std::collections::hash_map::RandomState;
+use std::hash::BuildHasher;
+use std::time::{Duration, Instant};
+
+use crate::ndp::packet::{Message, RouterAdvert};
+
+pub mod consts {
+    use std::time::Duration;
+
+    // Router constants of RFC 4861 section 10
+    pub const MAX_INITIAL_RTR_ADVERT_INTERVAL: Duration = Duration::from_secs(16);
+    pub const MAX_INITIAL_RTR_ADVERTISEMENTS: usize = 3;
+    pub const MIN_DELAY_BETWEEN_RAS: Duration = Duration::from_secs(3);
+    pub const MAX_RA_DELAY_TIME: Duration = Duration::from_millis(500);
+
+    // Default values of the interface variables of RFC 4861 section 6.2.1
+    pub const MAX_RTR_ADV_INTERVAL: Duration = Duration::from_secs(600);
+    pub const MIN_RTR_ADV_INTERVAL: Duration = Duration::from_secs(200);
+}
+
+/// Sends the router advertisements of RFC 4861 section 6.2, unsolicited ones at random intervals
+/// and, shortly after, answers to router solicitations. Advertisements are multicast to all nodes.
+#[derive(Debug)]
+pub struct Advertiser {
+    advert: RouterAdvert,
+    min_interval: Duration,
+    max_interval: Duration,
+    /// When the next advertisement is sent, right away when none.
+    next: Option<Instant>,
+    last_sent: Option<Instant>,
+    /// The first advertisements are sent more often, so hosts learn about the router sooner.
+    initial_left: usize,
+    random_state: RandomState,
+    random_counter: u64,
+}
+
+impl Advertiser {
+    /// Create an advertiser of `advert`, whose router lifetime should be zero
+    /// unless the router is to be a default router.
+    pub fn new(advert: RouterAdvert) -> Self {
+        Self {
+            advert,
+            min_interval: consts::MIN_RTR_ADV_INTERVAL,
+            max_interval: consts::MAX_RTR_ADV_INTERVAL,
+            next: None,
+            last_sent: None,
+            initial_left: consts::MAX_INITIAL_RTR_ADVERTISEMENTS,
+            random_state: RandomState::new(),
+            random_counter: 0,
+        }
+    }
+
+    pub fn advert(&self) -> &RouterAdvert {
+        &self.advert
+    }
+
+    /// Change what is advertised, the change is advertised right away as if the router had just started.
+    pub fn set_advert(&mut self, advert: RouterAdvert) {
+        self.advert = advert;
+        self.next = None;
+        self.initial_left = consts::MAX_INITIAL_RTR_ADVERTISEMENTS;
+    }
+
+    /// Set the range of the intervals between unsolicited advertisements, `max` is clamped to 4 to 1800 seconds
+    /// and `min` to 3 seconds up to three quarters of `max` (RFC 4861 section 6.2.1).
+    pub fn set_interval(&mut self, min: Duration, max: Duration) {
+        self.max_interval = max.clamp(Duration::from_secs(4), Duration::from_secs(1800));
+        self.min_interval = min.clamp(Duration::from_secs(3), self.max_interval * 3 / 4);
+    }
+
+    /// Handle a router solicitation, which is answered within a short random delay, though no sooner than
+    /// the minimum delay after the previous advertisement (RFC 4861 section 6.2.6).
+    pub fn process_solicit(&mut self, now: Instant) {
+        let mut at = now + self.random_delay(Duration::ZERO, consts::MAX_RA_DELAY_TIME);
+        if let Some(last_sent) = self.last_sent {
+            at = at.max(last_sent + consts::MIN_DELAY_BETWEEN_RAS);
+        }
+        if self.next.is_some_and(|next| next > at) {
+            self.next = Some(at);
+        }
+    }
+
+    /// Returns the advertisement to send at `now` if it is due, and schedules the next one.
+    pub fn poll(&mut self, now: Instant) -> Option<Message> {
+        if self.next.is_some_and(|next| next > now) {
+            return None;
+        }
+
+        let mut interval = self.random_delay(self.min_interval, self.max_interval);
+        if self.initial_left > 0 {
+            self.initial_left -= 1;
+            interval = interval.min(consts::MAX_INITIAL_RTR_ADVERT_INTERVAL);
+        }
+        self.next = Some(now + interval);
+        self.last_sent = Some(now);
+        Some(Message::RouterAdvert(self.advert.clone()))
+    }
+
+    /// Returns the advertisement to send when the router stops advertising, with a zero router lifetime
+    /// so hosts stop using it as a default router (RFC 4861 section 6.2.5).
+    pub fn shutdown(&self) -> Message {
+        Message::RouterAdvert(RouterAdvert {
+            router_lifetime: 0,
+            ..self.advert.clone()
+        })
+    }
+
+    /// Returns a random delay between `min` and `max`, so routers on the link do not synchronize.
+    fn random_delay(&mut self, min: Duration, max: Duration) -> Duration {
+        self.random_counter += 1;
+        let random = self.random_state.hash_one(self.random_counter);
+        min + Duration::from_millis(random % ((max - min).as_millis() as u64 + 1))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv6Addr;
+    use std::time::{Duration, Instant};
+
+    use super::{consts, Advertiser};
+    use crate::ndp::packet::{Message, PrefixInfo, RouterAdvert};
+
+    #[test]
+    fn advertise() {
+        let advert = RouterAdvert {
+            hop_limit: 64,
+            router_lifetime: 1800,
+            mtu: Some(1480),
+            prefixes: vec![PrefixInfo {
+                prefix: Ipv6Addr::new(0x2001, 0xdb8, 0, 1, 0, 0, 0, 0),
+                prefix_len: 64,
+                on_link: true,
+                autonomous: true,
+                valid_lifetime: 86400,
+                preferred_lifetime: 14400,
+            }],
+            ..Default::default()
+        };
+        let mut advertiser = Advertiser::new(advert.clone());
+        let mut now = Instant::now();
+
+        // The first advertisements are no more than 16 seconds apart.
+        for _ in 0..consts::MAX_INITIAL_RTR_ADVERTISEMENTS {
+            assert_eq!(advertiser.poll(now), Some(Message::RouterAdvert(advert.clone())));
+            assert_eq!(advertiser.poll(now), None);
+            now += consts::MAX_INITIAL_RTR_ADVERT_INTERVAL;
+        }
+
+        // The following ones are between the minimum and maximum intervals apart.
+        assert!(advertiser.poll(now).is_some());
+        assert_eq!(
+            advertiser.poll(now + consts::MIN_RTR_ADV_INTERVAL - Duration::from_secs(1)),
+            None
+        );
+        now += consts::MAX_RTR_ADV_INTERVAL;
+        assert!(advertiser.poll(now).is_some());
+
+        // A solicitation is answered shortly, though not right after the previous advertisement.
+        advertiser.process_solicit(now);
+        assert_eq!(advertiser.poll(now + consts::MAX_RA_DELAY_TIME), None);
+        now += consts::MIN_DELAY_BETWEEN_RAS;
+        assert!(advertiser.poll(now).is_some());
+        now += Duration::from_secs(10);
+        advertiser.process_solicit(now);
+        assert!(advertiser.poll(now + consts::MAX_RA_DELAY_TIME).is_some());
+
+        advertiser.set_interval(Duration::from_secs(1), Duration::from_secs(10));
+        match advertiser.shutdown() {
+            Message::RouterAdvert(advert) => assert_eq!(advert.router_lifetime, 0),
+            message => panic!("unexpected {:?}", message),
+        }
+    }
+}
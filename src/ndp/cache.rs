@@ -0,0 +1,523 @@
+use std::collections::{HashMap, VecDeque};
+use std::net::Ipv6Addr;
+use std::time::{Duration, Instant};
+
+use crate::ethernet::frame::MacAddr;
+use crate::macros::diagnostics::debug;
+use crate::ndp::packet::{consts as packet_consts, solicited_node, Message, RouterAdvert};
+
+pub mod consts {
+    use std::time::Duration;
+
+    // Protocol constants of RFC 4861 section 10
+    pub const REACHABLE_TIME: Duration = Duration::from_secs(30);
+    pub const RETRANS_TIMER: Duration = Duration::from_secs(1);
+    pub const DELAY_FIRST_PROBE_TIME: Duration = Duration::from_secs(5);
+    pub const MAX_MULTICAST_SOLICIT: usize = 3;
+    pub const MAX_UNICAST_SOLICIT: usize = 3;
+    pub const MAX_PENDING: usize = 16; // Packets queued per unresolved address, the oldest ones are dropped
+}
+
+/// The reachability state of a neighbor (RFC 4861 section 7.3.2).
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum State {
+    /// Solicitations were multicast, the packets to the neighbor wait for an advertisement.
+    Incomplete,
+    /// The neighbor was recently confirmed to be reachable.
+    Reachable,
+    /// The link-layer address may be outdated, it is used until a packet is sent.
+    Stale,
+    /// A packet was sent to a stale neighbor, upper layers have some time to confirm it.
+    Delay,
+    /// Unicast solicitations are sent to confirm the neighbor.
+    Probe,
+}
+
+#[derive(Debug)]
+struct Entry {
+    state: State,
+    /// Only unknown while incomplete.
+    mac_addr: Option<MacAddr>,
+    is_router: bool,
+    queue: VecDeque<Vec<u8>>,
+    solicitations: usize,
+    /// When a reachable entry becomes stale, a delayed one is probed, or the next solicitation is sent.
+    timer: Instant,
+}
+
+/// A Neighbor Solicitation to send.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Solicitation {
+    pub target: Ipv6Addr,
+    /// The address to unicast a probe to, none to multicast the solicitation to the solicited-node address.
+    pub mac_addr: Option<MacAddr>,
+}
+
+/// The outcome of a Neighbor Discovery message handled by the cache.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Processed {
+    /// The IPv6 packet answering a solicitation for one of our addresses.
+    pub reply: Option<Vec<u8>>,
+    /// The packets which waited for the neighbor to be resolved, now to be sent to it.
+    pub flushed: Vec<Vec<u8>>,
+    /// A router advertisement, whose prefixes, MTU and hop limit are for the interface to apply.
+    pub router_advert: Option<RouterAdvert>,
+}
+
+/// A cache of the link-layer addresses of IPv6 neighbors tracking their reachability,
+/// and the list of default routers, as Neighbor Discovery does for hosts (RFC 4861).
+#[derive(Debug)]
+pub struct NeighborCache {
+    entries: HashMap<Ipv6Addr, Entry>,
+    /// The default routers, with the time they expire at.
+    routers: Vec<(Ipv6Addr, Instant)>,
+    reachable_time: Duration,
+    retrans_timer: Duration,
+}
+
+impl NeighborCache {
+    pub fn new() -> Self {
+        Self {
+            entries: HashMap::new(),
+            routers: vec![],
+            reachable_time: consts::REACHABLE_TIME,
+            retrans_timer: consts::RETRANS_TIMER,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Forget every neighbor and router, dropping the queued packets.
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.routers.clear();
+    }
+
+    /// How long a neighbor is reachable after a confirmation, as advertised by the routers.
+    pub fn reachable_time(&self) -> Duration {
+        self.reachable_time
+    }
+
+    /// The time between solicitations, as advertised by the routers.
+    pub fn retrans_timer(&self) -> Duration {
+        self.retrans_timer
+    }
+
+    pub fn state(&self, addr: Ipv6Addr) -> Option<State> {
+        self.entries.get(&addr).map(|entry| entry.state)
+    }
+
+    /// Returns the link-layer address to send a packet to `addr` at `now`, if resolved.
+    /// Sending to a stale neighbor starts the delay before it is probed (RFC 4861 section 7.3.3).
+    pub fn lookup(&mut self, addr: Ipv6Addr, now: Instant) -> Option<MacAddr> {
+        let entry = self.entries.get_mut(&addr)?;
+        if entry.state == State::Reachable && entry.timer <= now {
+            entry.state = State::Stale;
+        }
+        if entry.state == State::Stale {
+            entry.state = State::Delay;
+            entry.timer = now + consts::DELAY_FIRST_PROBE_TIME;
+        }
+        entry.mac_addr
+    }
+
+    /// Queue `packet` until `addr` is resolved, returns whether a solicitation for it must be sent now.
+    pub fn enqueue(&mut self, addr: Ipv6Addr, packet: Vec<u8>, now: Instant) -> bool {
+        if let Some(entry) = self.entries.get_mut(&addr) {
+            if entry.state == State::Incomplete {
+                if entry.queue.len() >= consts::MAX_PENDING {
+                    entry.queue.pop_front();
+                }
+                entry.queue.push_back(packet);
+                return false;
+            }
+        }
+
+        self.entries.insert(
+            addr,
+            Entry {
+                state: State::Incomplete,
+                mac_addr: None,
+                is_router: false,
+                queue: VecDeque::from(vec![packet]),
+                solicitations: 1,
+                timer: now + self.retrans_timer,
+            },
+        );
+        true
+    }
+
+    /// Confirm that `addr` is reachable, as upper layers do when they see forward progress,
+    /// such as new acknowledgments of a TCP connection (RFC 4861 section 7.3.1).
+    pub fn confirm(&mut self, addr: Ipv6Addr, now: Instant) {
+        if let Some(entry) = self.entries.get_mut(&addr) {
+            if entry.mac_addr.is_some() {
+                entry.state = State::Reachable;
+                entry.timer = now + self.reachable_time;
+            }
+        }
+    }
+
+    /// Returns the default router to send off-link packets to, preferring the ones known
+    /// to be reachable (RFC 4861 section 6.3.6).
+    pub fn default_router(&self, now: Instant) -> Option<Ipv6Addr> {
+        let mut routers = self
+            .routers
+            .iter()
+            .filter(|(_, expires_at)| *expires_at > now)
+            .map(|(addr, _)| *addr);
+        let first = routers.clone().next();
+        routers
+            .find(|addr| matches!(self.state(*addr), Some(state) if state != State::Incomplete))
+            .or(first)
+    }
+
+    /// Run the timers of the entries, returns the solicitations to send.
+    /// A neighbor unanswered after all its solicitations is dropped with its queued packets.
+    pub fn poll(&mut self, now: Instant) -> Vec<Solicitation> {
+        let mut solicitations = vec![];
+        let retrans_timer = self.retrans_timer;
+        self.entries.retain(|addr, entry| {
+            if entry.timer > now {
+                return true;
+            }
+            match entry.state {
+                State::Reachable => entry.state = State::Stale,
+                State::Stale => {}
+                State::Delay => {
+                    entry.state = State::Probe;
+                    entry.solicitations = 0;
+                }
+                State::Incomplete | State::Probe => {
+                    let max = match entry.state {
+                        State::Incomplete => consts::MAX_MULTICAST_SOLICIT,
+                        _ => consts::MAX_UNICAST_SOLICIT,
+                    };
+                    if entry.solicitations >= max {
+                        debug!(
+                            "ndp: {} unreachable, {} queued packets dropped",
+                            addr,
+                            entry.queue.len()
+                        );
+                        return false;
+                    }
+                }
+            }
+
+            if matches!(entry.state, State::Incomplete | State::Probe) {
+                entry.solicitations += 1;
+                entry.timer = now + retrans_timer;
+                solicitations.push(Solicitation {
+                    target: *addr,
+                    mac_addr: entry.mac_addr,
+                });
+            }
+            true
+        });
+        self.routers.retain(|(_, expires_at)| *expires_at > now);
+        solicitations
+    }
+
+    /// Handle a Neighbor Discovery message received from `src_addr` by the interface at `addrs`
+    /// and `mac_addr`, as a host does: router solicitations are ignored.
+    pub fn process(
+        &mut self,
+        message: &Message,
+        src_addr: Ipv6Addr,
+        addrs: &[Ipv6Addr],
+        mac_addr: MacAddr,
+        now: Instant,
+    ) -> Processed {
+        let mut processed = Processed::default();
+        match message {
+            Message::RouterSolicit { .. } => {}
+            Message::RouterAdvert(advert) => {
+                if let Some(source_link_addr) = advert.source_link_addr {
+                    processed.flushed = self.learn(src_addr, source_link_addr, now);
+                }
+                if let Some(entry) = self.entries.get_mut(&src_addr) {
+                    entry.is_router = true;
+                }
+                self.routers.retain(|(router, _)| *router != src_addr);
+                if advert.router_lifetime != 0 {
+                    let expires_at = now + Duration::from_secs(advert.router_lifetime as u64);
+                    self.routers.push((src_addr, expires_at));
+                }
+                if advert.reachable_time != 0 {
+                    self.reachable_time = Duration::from_millis(advert.reachable_time as u64);
+                }
+                if advert.retrans_timer != 0 {
+                    self.retrans_timer = Duration::from_millis(advert.retrans_timer as u64);
+                }
+                processed.router_advert = Some(advert.clone());
+            }
+            Message::NeighborSolicit {
+                target,
+                source_link_addr,
+            } => {
+                if !addrs.contains(target) {
+                    return processed;
+                }
+                match source_link_addr {
+                    Some(source_link_addr) if !src_addr.is_unspecified() => {
+                        processed.flushed = self.learn(src_addr, *source_link_addr, now);
+                    }
+                    _ => {}
+                }
+
+                // Duplicate address detection probes are answered to all nodes (RFC 4861 section 7.2.4).
+                let (dest_addr, solicited) = if src_addr.is_unspecified() {
+                    (packet_consts::ALL_NODES, false)
+                } else {
+                    (src_addr, true)
+                };
+                let advert = Message::NeighborAdvert {
+                    target: *target,
+                    router: false,
+                    solicited,
+                    r#override: true,
+                    target_link_addr: Some(mac_addr),
+                };
+                processed.reply = Some(advert.build_packet(*target, dest_addr));
+            }
+            Message::NeighborAdvert {
+                target,
+                router,
+                solicited,
+                r#override,
+                target_link_addr,
+            } => {
+                if addrs.contains(target) {
+                    debug!("ndp: address {} also claimed by {:?}", target, target_link_addr);
+                    return processed;
+                }
+                processed.flushed = self.advertised(*target, *target_link_addr, *router, *solicited, *r#override, now);
+            }
+        }
+        processed
+    }
+
+    /// Learn the link-layer address of `addr` from a solicitation or a router advertisement,
+    /// a new or changed address is stale until confirmed (RFC 4861 section 7.2.3).
+    fn learn(&mut self, addr: Ipv6Addr, mac_addr: MacAddr, now: Instant) -> Vec<Vec<u8>> {
+        let entry = self.entries.entry(addr).or_insert_with(|| Entry {
+            state: State::Stale,
+            mac_addr: Some(mac_addr),
+            is_router: false,
+            queue: VecDeque::new(),
+            solicitations: 0,
+            timer: now,
+        });
+        if entry.mac_addr != Some(mac_addr) {
+            entry.mac_addr = Some(mac_addr);
+            entry.state = State::Stale;
+        }
+        entry.queue.drain(..).collect()
+    }
+
+    /// Update the entry of `target` from a Neighbor Advertisement (RFC 4861 section 7.2.5),
+    /// returns the packets which waited for it.
+    fn advertised(
+        &mut self,
+        target: Ipv6Addr,
+        target_link_addr: Option<MacAddr>,
+        router: bool,
+        solicited: bool,
+        r#override: bool,
+        now: Instant,
+    ) -> Vec<Vec<u8>> {
+        let reachable_time = self.reachable_time;
+        // Advertisements for unknown neighbors are not learnt.
+        let entry = match self.entries.get_mut(&target) {
+            Some(entry) => entry,
+            None => return vec![],
+        };
+
+        let reachable = |entry: &mut Entry| {
+            entry.state = State::Reachable;
+            entry.timer = now + reachable_time;
+        };
+        if entry.state == State::Incomplete {
+            let mac_addr = match target_link_addr {
+                Some(mac_addr) => mac_addr,
+                None => return vec![],
+            };
+            entry.mac_addr = Some(mac_addr);
+            if solicited {
+                reachable(entry);
+            } else {
+                entry.state = State::Stale;
+            }
+            entry.is_router = router;
+            return entry.queue.drain(..).collect();
+        }
+
+        let changed = target_link_addr.is_some() && target_link_addr != entry.mac_addr;
+        if changed && !r#override {
+            // Keep the known address, but check it again before relying on it.
+            if entry.state == State::Reachable {
+                entry.state = State::Stale;
+            }
+            return vec![];
+        }
+        if changed {
+            entry.mac_addr = target_link_addr;
+        }
+        if solicited {
+            reachable(entry);
+        } else if changed {
+            entry.state = State::Stale;
+        }
+        let was_router = entry.is_router;
+        entry.is_router = router;
+        if was_router && !router {
+            self.routers.retain(|(addr, _)| *addr != target);
+        }
+        vec![]
+    }
+}
+
+impl Default for NeighborCache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Build the IPv6 packet soliciting the link-layer address of `target`, multicast to its
+/// solicited-node address, or unicast to probe a neighbor already known.
+pub fn solicitation(src_addr: Ipv6Addr, mac_addr: MacAddr, target: Ipv6Addr, probe: bool) -> Vec<u8> {
+    let dest_addr = if probe { target } else { solicited_node(target) };
+    Message::NeighborSolicit {
+        target,
+        source_link_addr: Some(mac_addr),
+    }
+    .build_packet(src_addr, dest_addr)
+}
+
+/// Build the IPv6 packet asking the routers to advertise themselves, sent when the interface starts.
+/// Without an address yet, `src_addr` is unspecified and the link-layer address is left out.
+pub fn router_solicitation(src_addr: Ipv6Addr, mac_addr: MacAddr) -> Vec<u8> {
+    Message::RouterSolicit {
+        source_link_addr: Some(mac_addr).filter(|_| !src_addr.is_unspecified()),
+    }
+    .build_packet(src_addr, packet_consts::ALL_ROUTERS)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv6Addr;
+    use std::time::Instant;
+
+    use super::{consts, solicitation, NeighborCache, Solicitation, State};
+    use crate::ethernet::frame::MacAddr;
+    use crate::ipv6::packet::Packet;
+    use crate::ndp::packet::{Message, RouterAdvert};
+
+    const ADDR: Ipv6Addr = Ipv6Addr::new(0xfe80, 0, 0, 0, 0, 0, 0, 1);
+    const MAC_ADDR: MacAddr = MacAddr([0x02, 0, 0, 0, 0, 0x01]);
+    const NEIGHBOR: Ipv6Addr = Ipv6Addr::new(0xfe80, 0, 0, 0, 0, 0, 0, 2);
+    const NEIGHBOR_MAC: MacAddr = MacAddr([0x02, 0, 0, 0, 0, 0x02]);
+
+    fn advert(solicited: bool, r#override: bool, target_link_addr: MacAddr) -> Message {
+        Message::NeighborAdvert {
+            target: NEIGHBOR,
+            router: false,
+            solicited,
+            r#override,
+            target_link_addr: Some(target_link_addr),
+        }
+    }
+
+    #[test]
+    fn reachability() {
+        let mut cache = NeighborCache::default();
+        let now = Instant::now();
+
+        assert!(cache.enqueue(NEIGHBOR, vec![1], now));
+        assert!(!cache.enqueue(NEIGHBOR, vec![2], now));
+        assert_eq!(cache.lookup(NEIGHBOR, now), None);
+        assert_eq!(cache.state(NEIGHBOR), Some(State::Incomplete));
+
+        let processed = cache.process(&advert(true, true, NEIGHBOR_MAC), NEIGHBOR, &[ADDR], MAC_ADDR, now);
+        assert_eq!(processed.flushed, vec![vec![1], vec![2]]);
+        assert_eq!(cache.state(NEIGHBOR), Some(State::Reachable));
+
+        // Unconfirmed for a while, the neighbor is probed once a packet is sent to it.
+        let later = now + consts::REACHABLE_TIME;
+        assert!(cache.poll(later).is_empty());
+        assert_eq!(cache.state(NEIGHBOR), Some(State::Stale));
+        assert_eq!(cache.lookup(NEIGHBOR, later), Some(NEIGHBOR_MAC));
+        assert_eq!(cache.state(NEIGHBOR), Some(State::Delay));
+        let later = later + consts::DELAY_FIRST_PROBE_TIME;
+        let probe = Solicitation {
+            target: NEIGHBOR,
+            mac_addr: Some(NEIGHBOR_MAC),
+        };
+        assert_eq!(cache.poll(later), vec![probe]);
+        assert_eq!(cache.state(NEIGHBOR), Some(State::Probe));
+
+        // An unsolicited advertisement without override does not replace the address.
+        let other_mac = MacAddr([0x02, 0, 0, 0, 0, 0x03]);
+        cache.process(&advert(false, false, other_mac), NEIGHBOR, &[ADDR], MAC_ADDR, later);
+        assert_eq!(cache.lookup(NEIGHBOR, later), Some(NEIGHBOR_MAC));
+        cache.process(&advert(true, false, NEIGHBOR_MAC), NEIGHBOR, &[ADDR], MAC_ADDR, later);
+        assert_eq!(cache.state(NEIGHBOR), Some(State::Reachable));
+
+        // Probes left unanswered drop the neighbor.
+        cache.confirm(NEIGHBOR, now);
+        cache.lookup(NEIGHBOR, later);
+        let mut later = later + consts::DELAY_FIRST_PROBE_TIME;
+        for _ in 0..consts::MAX_UNICAST_SOLICIT {
+            assert_eq!(cache.poll(later), vec![probe]);
+            later += consts::RETRANS_TIMER;
+        }
+        assert!(cache.poll(later).is_empty());
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn solicit() {
+        let mut cache = NeighborCache::default();
+        let now = Instant::now();
+
+        let bytes = solicitation(NEIGHBOR, NEIGHBOR_MAC, ADDR, false);
+        let packet = Packet::new_checked(bytes.as_slice()).unwrap();
+        let message = Message::parse(NEIGHBOR, packet.dest_addr(), packet.hop_limit(), packet.payload()).unwrap();
+        let processed = cache.process(&message, NEIGHBOR, &[ADDR], MAC_ADDR, now);
+        let reply = processed.reply.unwrap();
+        let reply = Packet::new_checked(reply.as_slice()).unwrap();
+        assert_eq!((reply.src_addr(), reply.dest_addr()), (ADDR, NEIGHBOR));
+        assert_eq!(
+            Message::parse(ADDR, NEIGHBOR, reply.hop_limit(), reply.payload()).unwrap(),
+            Message::NeighborAdvert {
+                target: ADDR,
+                router: false,
+                solicited: true,
+                r#override: true,
+                target_link_addr: Some(MAC_ADDR),
+            }
+        );
+        // The solicitor is learnt, stale until confirmed.
+        assert_eq!(cache.state(NEIGHBOR), Some(State::Stale));
+
+        let advert = Message::RouterAdvert(RouterAdvert {
+            router_lifetime: 60,
+            reachable_time: 10000,
+            source_link_addr: Some(NEIGHBOR_MAC),
+            ..RouterAdvert::default()
+        });
+        let processed = cache.process(&advert, NEIGHBOR, &[ADDR], MAC_ADDR, now);
+        assert!(processed.router_advert.is_some());
+        assert_eq!(cache.default_router(now), Some(NEIGHBOR));
+        assert_eq!(cache.reachable_time().as_millis(), 10000);
+        let advert = Message::RouterAdvert(RouterAdvert::default());
+        cache.process(&advert, NEIGHBOR, &[ADDR], MAC_ADDR, now);
+        assert_eq!(cache.default_router(now), None);
+    }
+}
@@ -0,0 +1,29 @@
+use core::fmt::{Display, Formatter};
+
+#[derive(Debug)]
+pub enum Error {
+    InvalidLength,
+    InvalidMessageType,
+    InvalidCode,
+    InvalidChecksum,
+    /// Neighbor Discovery messages must be sent with a hop limit of 255, so they can not come from off-link.
+    InvalidHopLimit,
+    InvalidOption,
+    InvalidAddress,
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Error::InvalidLength => write!(f, "invalid length"),
+            Error::InvalidMessageType => write!(f, "invalid message type"),
+            Error::InvalidCode => write!(f, "invalid code"),
+            Error::InvalidChecksum => write!(f, "invalid checksum"),
+            Error::InvalidHopLimit => write!(f, "invalid hop limit"),
+            Error::InvalidOption => write!(f, "invalid option"),
+            Error::InvalidAddress => write!(f, "invalid address"),
+        }
+    }
+}
+
+impl core::error::Error for Error {}
@@ -0,0 +1,6 @@
+#[cfg(feature = "std")]
+pub mod advertiser;
+#[cfg(feature = "std")]
+pub mod cache;
+pub mod error;
+pub mod packet;
@@ -0,0 +1,394 @@
+use alloc::vec;
+use alloc::vec::Vec;
+use core::convert::TryInto;
+use core::net::Ipv6Addr;
+
+use crate::error::Result;
+use crate::ethernet::frame::MacAddr;
+use crate::icmpv6::packet::{MessageType, Packet as Icmpv6Packet};
+use crate::ipv4::packet::Protocol;
+use crate::ipv6::builder::PacketBuilder;
+use crate::ndp::error::Error;
+
+pub mod consts {
+    use core::net::Ipv6Addr;
+
+    pub const HOP_LIMIT: u8 = 255; // RFC 4861 section 3.1, a lower one means the message was forwarded
+    pub const ALL_NODES: Ipv6Addr = Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 0, 1);
+    pub const ALL_ROUTERS: Ipv6Addr = Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 0, 2);
+
+    // Option types of RFC 4861 section 4.6
+    pub const OPTION_SOURCE_LINK_ADDR: u8 = 1;
+    pub const OPTION_TARGET_LINK_ADDR: u8 = 2;
+    pub const OPTION_PREFIX_INFO: u8 = 3;
+    pub const OPTION_MTU: u8 = 5;
+
+    pub const FLAG_MANAGED: u8 = 0x80;
+    pub const FLAG_OTHER_CONFIG: u8 = 0x40;
+    pub const FLAG_ROUTER: u8 = 0x80;
+    pub const FLAG_SOLICITED: u8 = 0x40;
+    pub const FLAG_OVERRIDE: u8 = 0x20;
+    pub const FLAG_ON_LINK: u8 = 0x80;
+    pub const FLAG_AUTONOMOUS: u8 = 0x40;
+}
+
+/// A prefix advertised by a router (RFC 4861 section 4.6.2), lifetimes are in seconds
+/// and all ones means infinity.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct PrefixInfo {
+    pub prefix: Ipv6Addr,
+    pub prefix_len: u8,
+    /// The addresses of the prefix are on the link, and reached without a router.
+    pub on_link: bool,
+    /// The prefix can be used for stateless address autoconfiguration.
+    pub autonomous: bool,
+    pub valid_lifetime: u32,
+    pub preferred_lifetime: u32,
+}
+
+/// A Router Advertisement (RFC 4861 section 4.2), times are in milliseconds but the router lifetime
+/// in seconds. Zero leaves a value unspecified.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RouterAdvert {
+    pub hop_limit: u8,
+    pub managed: bool,
+    pub other_config: bool,
+    /// How long the router can be a default router, zero if it is not one.
+    pub router_lifetime: u16,
+    pub reachable_time: u32,
+    pub retrans_timer: u32,
+    pub source_link_addr: Option<MacAddr>,
+    pub mtu: Option<u32>,
+    pub prefixes: Vec<PrefixInfo>,
+}
+
+/// A Neighbor Discovery message, options not understood are ignored (RFC 4861 section 4.6).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Message {
+    RouterSolicit {
+        source_link_addr: Option<MacAddr>,
+    },
+    RouterAdvert(RouterAdvert),
+    NeighborSolicit {
+        target: Ipv6Addr,
+        source_link_addr: Option<MacAddr>,
+    },
+    NeighborAdvert {
+        target: Ipv6Addr,
+        router: bool,
+        solicited: bool,
+        r#override: bool,
+        target_link_addr: Option<MacAddr>,
+    },
+}
+
+#[derive(Default)]
+struct Options {
+    source_link_addr: Option<MacAddr>,
+    target_link_addr: Option<MacAddr>,
+    mtu: Option<u32>,
+    prefixes: Vec<PrefixInfo>,
+}
+
+impl Message {
+    /// Parse and validate the ICMPv6 message `buffer` received from `src_addr` to `dest_addr`
+    /// with `hop_limit`, following the validation rules of RFC 4861 sections 6.1 and 7.1.
+    pub fn parse(src_addr: Ipv6Addr, dest_addr: Ipv6Addr, hop_limit: u8, buffer: &[u8]) -> Result<Self> {
+        let packet = Icmpv6Packet::new_checked(buffer)?;
+        if hop_limit != consts::HOP_LIMIT {
+            return Err(Error::InvalidHopLimit.into());
+        }
+        if !packet.verify_checksum(src_addr, dest_addr) {
+            return Err(Error::InvalidChecksum.into());
+        }
+        if packet.code() != 0 {
+            return Err(Error::InvalidCode.into());
+        }
+
+        let options_offset = match packet.r#type() {
+            MessageType::RouterSolicitation => 8,
+            MessageType::RouterAdvertisement => 16,
+            MessageType::NeighborSolicitation | MessageType::NeighborAdvertisement => 24,
+            _ => return Err(Error::InvalidMessageType.into()),
+        };
+        if buffer.len() < options_offset {
+            return Err(Error::InvalidLength.into());
+        }
+        let options = Options::parse(&buffer[options_offset..])?;
+        let target = || {
+            let octets: [u8; 16] = buffer[8..24].try_into().unwrap();
+            Ipv6Addr::from(octets)
+        };
+
+        let message = match packet.r#type() {
+            MessageType::RouterSolicitation => {
+                if src_addr.is_unspecified() && options.source_link_addr.is_some() {
+                    return Err(Error::InvalidOption.into());
+                }
+                Message::RouterSolicit {
+                    source_link_addr: options.source_link_addr,
+                }
+            }
+            MessageType::RouterAdvertisement => {
+                if !src_addr.is_unicast_link_local() {
+                    return Err(Error::InvalidAddress.into());
+                }
+                Message::RouterAdvert(RouterAdvert {
+                    hop_limit: buffer[4],
+                    managed: buffer[5] & consts::FLAG_MANAGED != 0,
+                    other_config: buffer[5] & consts::FLAG_OTHER_CONFIG != 0,
+                    router_lifetime: u16::from_be_bytes([buffer[6], buffer[7]]),
+                    reachable_time: u32::from_be_bytes(buffer[8..12].try_into().unwrap()),
+                    retrans_timer: u32::from_be_bytes(buffer[12..16].try_into().unwrap()),
+                    source_link_addr: options.source_link_addr,
+                    mtu: options.mtu,
+                    prefixes: options.prefixes,
+                })
+            }
+            MessageType::NeighborSolicitation => {
+                let target = target();
+                if target.is_multicast() {
+                    return Err(Error::InvalidAddress.into());
+                }
+                // Duplicate address detection probes, sent from the unspecified address.
+                if src_addr.is_unspecified()
+                    && (dest_addr != solicited_node(target) || options.source_link_addr.is_some())
+                {
+                    return Err(Error::InvalidAddress.into());
+                }
+                Message::NeighborSolicit {
+                    target,
+                    source_link_addr: options.source_link_addr,
+                }
+            }
+            _ => {
+                let target = target();
+                let solicited = buffer[4] & consts::FLAG_SOLICITED != 0;
+                if target.is_multicast() || (dest_addr.is_multicast() && solicited) {
+                    return Err(Error::InvalidAddress.into());
+                }
+                Message::NeighborAdvert {
+                    target,
+                    router: buffer[4] & consts::FLAG_ROUTER != 0,
+                    solicited,
+                    r#override: buffer[4] & consts::FLAG_OVERRIDE != 0,
+                    target_link_addr: options.target_link_addr,
+                }
+            }
+        };
+        Ok(message)
+    }
+
+    pub fn r#type(&self) -> MessageType {
+        match self {
+            Message::RouterSolicit { .. } => MessageType::RouterSolicitation,
+            Message::RouterAdvert(_) => MessageType::RouterAdvertisement,
+            Message::NeighborSolicit { .. } => MessageType::NeighborSolicitation,
+            Message::NeighborAdvert { .. } => MessageType::NeighborAdvertisement,
+        }
+    }
+
+    /// Build the ICMPv6 message sent from `src_addr` to `dest_addr`, with its checksum.
+    pub fn build_vec(&self, src_addr: Ipv6Addr, dest_addr: Ipv6Addr) -> Vec<u8> {
+        let mut buffer: Vec<u8> = vec![0; 8];
+        buffer[0] = self.r#type().into();
+
+        match self {
+            Message::RouterSolicit { source_link_addr } => {
+                push_link_addr(&mut buffer, consts::OPTION_SOURCE_LINK_ADDR, *source_link_addr);
+            }
+            Message::RouterAdvert(advert) => {
+                buffer[4] = advert.hop_limit;
+                buffer[5] =
+                    flag(advert.managed, consts::FLAG_MANAGED) | flag(advert.other_config, consts::FLAG_OTHER_CONFIG);
+                buffer[6..8].copy_from_slice(&advert.router_lifetime.to_be_bytes());
+                buffer.extend_from_slice(&advert.reachable_time.to_be_bytes());
+                buffer.extend_from_slice(&advert.retrans_timer.to_be_bytes());
+                push_link_addr(&mut buffer, consts::OPTION_SOURCE_LINK_ADDR, advert.source_link_addr);
+                if let Some(mtu) = advert.mtu {
+                    buffer.extend_from_slice(&[consts::OPTION_MTU, 1, 0, 0]);
+                    buffer.extend_from_slice(&mtu.to_be_bytes());
+                }
+                for prefix in advert.prefixes.iter() {
+                    buffer.extend_from_slice(&[
+                        consts::OPTION_PREFIX_INFO,
+                        4,
+                        prefix.prefix_len,
+                        flag(prefix.on_link, consts::FLAG_ON_LINK) | flag(prefix.autonomous, consts::FLAG_AUTONOMOUS),
+                    ]);
+                    buffer.extend_from_slice(&prefix.valid_lifetime.to_be_bytes());
+                    buffer.extend_from_slice(&prefix.preferred_lifetime.to_be_bytes());
+                    buffer.extend_from_slice(&[0; 4]);
+                    buffer.extend_from_slice(&prefix.prefix.octets());
+                }
+            }
+            Message::NeighborSolicit {
+                target,
+                source_link_addr,
+            } => {
+                buffer.extend_from_slice(&target.octets());
+                push_link_addr(&mut buffer, consts::OPTION_SOURCE_LINK_ADDR, *source_link_addr);
+            }
+            Message::NeighborAdvert {
+                target,
+                router,
+                solicited,
+                r#override,
+                target_link_addr,
+            } => {
+                buffer[4] = flag(*router, consts::FLAG_ROUTER)
+                    | flag(*solicited, consts::FLAG_SOLICITED)
+                    | flag(*r#override, consts::FLAG_OVERRIDE);
+                buffer.extend_from_slice(&target.octets());
+                push_link_addr(&mut buffer, consts::OPTION_TARGET_LINK_ADDR, *target_link_addr);
+            }
+        }
+
+        Icmpv6Packet::new_unchecked(buffer.as_mut_slice()).fill_checksum(src_addr, dest_addr);
+        buffer
+    }
+
+    /// Build the IPv6 packet carrying the message, with the hop limit Neighbor Discovery requires.
+    pub fn build_packet(&self, src_addr: Ipv6Addr, dest_addr: Ipv6Addr) -> Vec<u8> {
+        PacketBuilder::default()
+            .next_header(Protocol::Icmpv6)
+            .hop_limit(consts::HOP_LIMIT)
+            .src_addr(src_addr)
+            .dest_addr(dest_addr)
+            .payload(self.build_vec(src_addr, dest_addr))
+            .build_vec()
+    }
+}
+
+impl Options {
+    fn parse(mut buffer: &[u8]) -> Result<Self> {
+        let mut options = Options::default();
+        while !buffer.is_empty() {
+            // The length counts units of 8 octets, type and length included, and zero is invalid.
+            let len = match buffer {
+                [_, len, ..] if *len != 0 && buffer.len() >= *len as usize * 8 => *len as usize * 8,
+                _ => return Err(Error::InvalidOption.into()),
+            };
+            let (option, rest) = buffer.split_at(len);
+            buffer = rest;
+
+            match option[0] {
+                consts::OPTION_SOURCE_LINK_ADDR => options.source_link_addr = Some(MacAddr::from_bytes(&option[2..8])?),
+                consts::OPTION_TARGET_LINK_ADDR => options.target_link_addr = Some(MacAddr::from_bytes(&option[2..8])?),
+                consts::OPTION_MTU => options.mtu = Some(u32::from_be_bytes(option[4..8].try_into().unwrap())),
+                consts::OPTION_PREFIX_INFO => {
+                    if len != 32 {
+                        return Err(Error::InvalidOption.into());
+                    }
+                    let prefix: [u8; 16] = option[16..32].try_into().unwrap();
+                    options.prefixes.push(PrefixInfo {
+                        prefix: Ipv6Addr::from(prefix),
+                        prefix_len: option[2],
+                        on_link: option[3] & consts::FLAG_ON_LINK != 0,
+                        autonomous: option[3] & consts::FLAG_AUTONOMOUS != 0,
+                        valid_lifetime: u32::from_be_bytes(option[4..8].try_into().unwrap()),
+                        preferred_lifetime: u32::from_be_bytes(option[8..12].try_into().unwrap()),
+                    });
+                }
+                _ => {}
+            }
+        }
+        Ok(options)
+    }
+}
+
+fn flag(set: bool, flag: u8) -> u8 {
+    if set {
+        flag
+    } else {
+        0
+    }
+}
+
+fn push_link_addr(buffer: &mut Vec<u8>, r#type: u8, mac_addr: Option<MacAddr>) {
+    if let Some(mac_addr) = mac_addr {
+        buffer.extend_from_slice(&[r#type, 1]);
+        buffer.extend_from_slice(&mac_addr.octets());
+    }
+}
+
+/// Returns the solicited-node multicast address of `addr`, which Neighbor Solicitations
+/// for it are sent to (RFC 4291 section 2.7.1).
+pub fn solicited_node(addr: Ipv6Addr) -> Ipv6Addr {
+    let octets = addr.octets();
+    Ipv6Addr::new(
+        0xff02,
+        0,
+        0,
+        0,
+        0,
+        1,
+        0xff00 | octets[13] as u16,
+        u16::from_be_bytes([octets[14], octets[15]]),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv6Addr;
+
+    use super::{consts, solicited_node, Message, PrefixInfo, RouterAdvert};
+    use crate::ethernet::frame::MacAddr;
+
+    const ROUTER: Ipv6Addr = Ipv6Addr::new(0xfe80, 0, 0, 0, 0, 0, 0, 1);
+    const ROUTER_MAC: MacAddr = MacAddr([0x02, 0, 0, 0, 0, 0x01]);
+
+    #[test]
+    fn parse() {
+        let advert = Message::RouterAdvert(RouterAdvert {
+            hop_limit: 64,
+            other_config: true,
+            router_lifetime: 1800,
+            reachable_time: 30000,
+            source_link_addr: Some(ROUTER_MAC),
+            mtu: Some(1480),
+            prefixes: vec![PrefixInfo {
+                prefix: "2001:db8::".parse().unwrap(),
+                prefix_len: 64,
+                on_link: true,
+                autonomous: true,
+                valid_lifetime: 86400,
+                preferred_lifetime: 14400,
+            }],
+            ..RouterAdvert::default()
+        });
+        let bytes = advert.build_vec(ROUTER, consts::ALL_NODES);
+        assert_eq!(bytes.len(), 16 + 8 + 8 + 32);
+        assert_eq!(Message::parse(ROUTER, consts::ALL_NODES, 255, &bytes).unwrap(), advert);
+        // Forwarded, or sent from a global address.
+        assert!(Message::parse(ROUTER, consts::ALL_NODES, 254, &bytes).is_err());
+        let global: Ipv6Addr = "2001:db8::1".parse().unwrap();
+        assert!(Message::parse(global, consts::ALL_NODES, 255, &bytes).is_err());
+
+        // A duplicate address detection probe.
+        let target: Ipv6Addr = "fe80::200:ff:fe12:3456".parse().unwrap();
+        let dest = solicited_node(target);
+        assert_eq!(dest, "ff02::1:ff12:3456".parse::<Ipv6Addr>().unwrap());
+        let probe = Message::NeighborSolicit {
+            target,
+            source_link_addr: None,
+        };
+        let bytes = probe.build_vec(Ipv6Addr::UNSPECIFIED, dest);
+        assert_eq!(Message::parse(Ipv6Addr::UNSPECIFIED, dest, 255, &bytes).unwrap(), probe);
+        assert!(Message::parse(Ipv6Addr::UNSPECIFIED, consts::ALL_NODES, 255, &bytes).is_err());
+
+        let mut bytes = Message::NeighborAdvert {
+            target,
+            router: false,
+            solicited: true,
+            r#override: true,
+            target_link_addr: Some(ROUTER_MAC),
+        }
+        .build_vec(target, ROUTER);
+        assert!(Message::parse(target, ROUTER, 255, &bytes).is_ok());
+        assert!(Message::parse(target, consts::ALL_NODES, 255, &bytes).is_err());
+        // An option with a zero length.
+        bytes[25] = 0;
+        assert!(Message::parse(target, ROUTER, 255, &bytes).is_err());
+    }
+}
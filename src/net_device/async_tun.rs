@@ -0,0 +1,71 @@
+use std::io::{Read, Result as IOResult, Write};
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+
+use tokio::io::unix::AsyncFd;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+use crate::error::Result;
+use crate::net_device::tun::TunDevice;
+
+/// A tun device read and written by tokio tasks, which wait for the device to be ready instead of blocking.
+#[derive(Debug)]
+pub struct AsyncTunDevice {
+    inner: AsyncFd<TunDevice>,
+}
+
+impl AsyncTunDevice {
+    /// Register `device` with the tokio reactor, in non-blocking mode. It must be called within a tokio runtime.
+    pub fn new(device: TunDevice) -> Result<Self> {
+        device.set_nonblocking(true)?;
+        Ok(Self {
+            inner: AsyncFd::new(device)?,
+        })
+    }
+
+    pub fn get_ref(&self) -> &TunDevice {
+        self.inner.get_ref()
+    }
+
+    /// Deregister the device, which is left in non-blocking mode.
+    pub fn into_inner(self) -> TunDevice {
+        self.inner.into_inner()
+    }
+}
+
+impl AsyncRead for AsyncTunDevice {
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<IOResult<()>> {
+        let this = self.get_mut();
+        loop {
+            let mut guard = ready!(this.inner.poll_read_ready_mut(cx))?;
+            match guard.try_io(|inner| inner.get_mut().read(buf.initialize_unfilled())) {
+                Ok(result) => {
+                    buf.advance(result?);
+                    return Poll::Ready(Ok(()));
+                }
+                Err(_would_block) => continue,
+            }
+        }
+    }
+}
+
+impl AsyncWrite for AsyncTunDevice {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<IOResult<usize>> {
+        let this = self.get_mut();
+        loop {
+            let mut guard = ready!(this.inner.poll_write_ready_mut(cx))?;
+            match guard.try_io(|inner| inner.get_mut().write(buf)) {
+                Ok(result) => return Poll::Ready(result),
+                Err(_would_block) => continue,
+            }
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<IOResult<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<IOResult<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
@@ -0,0 +1,256 @@
+use std::io::{Error as IOError, ErrorKind};
+use std::sync::mpsc::{channel, Receiver, RecvError, RecvTimeoutError, Sender, TryRecvError};
+use std::time::Duration;
+
+use crate::capabilities::Offload;
+use crate::error::Result;
+use crate::ethernet::frame::consts::HEADER_LEN;
+use crate::net_device::device::{consts, Buffer, Device, DeviceCapabilities, Medium};
+
+/// A virtual device connected to another one in the same process, like the two ends of a cable:
+/// packets transmitted on one end are received on the other, in order and unchanged.
+///
+/// Two interfaces on the ends of a pair talk to each other without any device of the kernel,
+/// such as a client and a server in a test.
+#[derive(Debug)]
+pub struct ChannelDevice {
+    sender: Sender<Vec<u8>>,
+    receiver: Receiver<Vec<u8>>,
+    /// The packet that arrived while waiting, received before the others.
+    waiting: Option<Vec<u8>>,
+    medium: Medium,
+    mtu: usize,
+    nonblocking: bool,
+}
+
+impl ChannelDevice {
+    /// Create two connected devices carrying IP packets, like TUN devices.
+    pub fn pair() -> (Self, Self) {
+        Self::pair_with_medium(Medium::Ip)
+    }
+
+    /// Create two connected devices carrying packets of `medium`, Ethernet frames like TAP devices do.
+    pub fn pair_with_medium(medium: Medium) -> (Self, Self) {
+        let (sender, peer_receiver) = channel();
+        let (peer_sender, receiver) = channel();
+        let end = |sender, receiver| Self {
+            sender,
+            receiver,
+            waiting: None,
+            medium,
+            mtu: consts::DEFAULT_MTU,
+            nonblocking: false,
+        };
+        (end(sender, receiver), end(peer_sender, peer_receiver))
+    }
+
+    /// Set the MTU of this end, larger packets are refused when transmitted.
+    pub fn set_mtu(&mut self, mtu: usize) {
+        self.mtu = mtu;
+    }
+
+    /// In non-blocking mode `receive` fails with `WouldBlock` instead of waiting for the other end,
+    /// so a single thread can drive the interfaces on both ends.
+    pub fn set_nonblocking(&mut self, nonblocking: bool) {
+        self.nonblocking = nonblocking;
+    }
+}
+
+fn copy_packet(packet: &[u8], buf: &mut [u8]) -> Result<usize> {
+    if packet.len() > buf.len() {
+        return Err(IOError::new(ErrorKind::InvalidData, "packet truncated").into());
+    }
+    buf[..packet.len()].copy_from_slice(packet);
+    Ok(packet.len())
+}
+
+impl Device for ChannelDevice {
+    fn receive(&mut self, buf: &mut [u8]) -> Result<usize> {
+        let packet = if let Some(packet) = self.waiting.take() {
+            packet
+        } else if self.nonblocking {
+            self.receiver.try_recv().map_err(|e| match e {
+                TryRecvError::Empty => IOError::from(ErrorKind::WouldBlock),
+                TryRecvError::Disconnected => IOError::from(ErrorKind::NotConnected),
+            })?
+        } else {
+            self.receiver
+                .recv()
+                .map_err(|RecvError| IOError::from(ErrorKind::NotConnected))?
+        };
+
+        copy_packet(&packet, buf)
+    }
+
+    fn recv_batch(&mut self, bufs: &mut [Buffer]) -> Result<usize> {
+        let mut received = 0;
+        for buf in bufs.iter_mut() {
+            // Only the first packet is waited for, a failure after it ends the batch.
+            let len = if received == 0 {
+                self.receive(buf.as_mut_slice())?
+            } else {
+                match self
+                    .receiver
+                    .try_recv()
+                    .map(|packet| copy_packet(&packet, buf.as_mut_slice()))
+                {
+                    Ok(Ok(len)) => len,
+                    _ => break,
+                }
+            };
+
+            buf.set_len(len);
+            received += 1;
+        }
+        Ok(received)
+    }
+
+    fn wait(&mut self, timeout: Duration) -> Result<bool> {
+        if self.waiting.is_none() {
+            self.waiting = match self.receiver.recv_timeout(timeout) {
+                Ok(packet) => Some(packet),
+                Err(RecvTimeoutError::Timeout) => return Ok(false),
+                Err(RecvTimeoutError::Disconnected) => return Err(IOError::from(ErrorKind::NotConnected).into()),
+            };
+        }
+        Ok(true)
+    }
+
+    fn transmit(&mut self, packet: &[u8]) -> Result<()> {
+        let max_len = match self.medium {
+            Medium::Ip => self.mtu,
+            Medium::Ethernet => HEADER_LEN + self.mtu,
+        };
+        if packet.len() > max_len {
+            return Err(IOError::new(ErrorKind::InvalidInput, "packet larger than the MTU").into());
+        }
+
+        // Like a cable with nothing plugged in on the other end, packets are lost once the peer is gone.
+        let _ = self.sender.send(packet.to_vec());
+        Ok(())
+    }
+
+    fn mtu(&self) -> usize {
+        self.mtu
+    }
+
+    fn capabilities(&self) -> DeviceCapabilities {
+        DeviceCapabilities {
+            medium: self.medium,
+            offload: Offload::default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{Ipv4Addr, SocketAddr};
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    use super::ChannelDevice;
+    use crate::ipv4::interface::Interface;
+    use crate::ipv4::reassembly::Reassembler;
+    use crate::metrics::Registry;
+    use crate::net_device::device::Buffer;
+    use crate::net_device::Device;
+    use crate::pool::BufferPool;
+    use crate::udp::udp_socket::UdpSocket;
+
+    #[test]
+    fn pair() {
+        let (client_device, server_device) = ChannelDevice::pair();
+        let client_addr = Ipv4Addr::new(10, 0, 0, 1);
+        let server_addr = Ipv4Addr::new(10, 0, 0, 2);
+
+        let client = Arc::new(Mutex::new(Interface::new(client_device, Reassembler::default())));
+        client.lock().unwrap().set_ip_addr(client_addr);
+        let server = Arc::new(Mutex::new(Interface::new(server_device, Reassembler::default())));
+        server.lock().unwrap().set_ip_addr(server_addr);
+        let registry = Registry::new();
+        client.lock().unwrap().set_metrics(&registry);
+        server.lock().unwrap().set_metrics(&registry);
+
+        let client_socket = UdpSocket::bind(&client, client_addr, 0).unwrap();
+        let server_socket = UdpSocket::bind(&server, server_addr, 7).unwrap();
+        let mut buf = [0; 64];
+
+        client_socket.send_to(b"ping", (server_addr, 7)).unwrap();
+        let (len, remote) = server_socket.recv_from(&mut buf).unwrap();
+        assert_eq!(&buf[..len], b"ping");
+        assert_eq!(remote, client_socket.local_addr());
+
+        server_socket.send_to(b"pong", remote).unwrap();
+        let (len, remote) = client_socket.recv_from(&mut buf).unwrap();
+        assert_eq!(&buf[..len], b"pong");
+        assert_eq!(remote, SocketAddr::from((server_addr, 7)));
+
+        // Both interfaces count into the registry they share.
+        let snapshot = registry.snapshot();
+        assert_eq!(snapshot.get("radish_udp_datagrams_transmitted_total"), Some(2));
+        assert_eq!(snapshot.get("radish_udp_datagrams_received_total"), Some(2));
+        assert_eq!(snapshot.get("radish_packets_transmitted_total"), Some(2));
+
+        // Nothing is waiting on either end.
+        let (mut left, mut right) = ChannelDevice::pair();
+        left.set_nonblocking(true);
+        assert!(left.receive(&mut buf).is_err());
+        right.set_mtu(2);
+        assert!(right.transmit(&[1, 2, 3]).is_err());
+        right.transmit(&[1, 2]).unwrap();
+        assert_eq!(left.receive(&mut buf).unwrap(), 2);
+    }
+
+    #[test]
+    fn recv_batch() {
+        let (mut left, mut right) = ChannelDevice::pair();
+        left.transmit_batch(&[b"one", b"two", b"three"]).unwrap();
+
+        let mut bufs = vec![Buffer::new(8); 2];
+        assert_eq!(right.recv_batch(&mut bufs).unwrap(), 2);
+        assert_eq!(bufs[0].packet(), b"one");
+        assert_eq!(bufs[1].packet(), b"two");
+
+        // Only the packets already queued are received after the first one.
+        let mut bufs = vec![Buffer::new(8); 4];
+        assert_eq!(right.recv_batch(&mut bufs).unwrap(), 1);
+        assert_eq!(bufs[0].packet(), b"three");
+    }
+
+    #[test]
+    fn wait() {
+        let (mut left, mut right) = ChannelDevice::pair();
+        right.set_nonblocking(true);
+        assert!(!right.wait(Duration::from_millis(1)).unwrap());
+
+        // The packet waited for is received first, even by a non-blocking end.
+        left.transmit_batch(&[b"one", b"two"]).unwrap();
+        assert!(right.wait(Duration::from_millis(1)).unwrap());
+        let mut bufs = vec![Buffer::new(8); 4];
+        assert_eq!(right.recv_batch(&mut bufs).unwrap(), 2);
+        assert_eq!(bufs[0].packet(), b"one");
+        assert_eq!(bufs[1].packet(), b"two");
+    }
+
+    #[test]
+    fn tokens() {
+        let (mut left, mut right) = ChannelDevice::pair();
+        let pool = BufferPool::new(2);
+
+        let built = left
+            .transmit_token(&pool)
+            .consume(3, |buffer| buffer.copy_from_slice(b"abc"));
+        assert!(built.is_ok());
+        assert!(left.transmit_token(&pool).consume(1501, |_| ()).is_err());
+
+        let token = right.receive_token(&pool).unwrap();
+        let len = token.consume(|packet| {
+            packet.reverse();
+            assert_eq!(packet, b"cba");
+            packet.len()
+        });
+        assert_eq!(len, 3);
+        // A single buffer served every token and went back to the pool.
+        assert_eq!(pool.available(), 1);
+    }
+}
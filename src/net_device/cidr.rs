@@ -0,0 +1,97 @@
+use std::fmt::{Display, Formatter};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::str::FromStr;
+
+use crate::net_device::error::Error;
+
+/// An address with the length of its network prefix, such as `192.168.233.233/24`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Cidr {
+    addr: IpAddr,
+    prefix_len: u8,
+}
+
+impl Cidr {
+    /// Fails if `prefix_len` is longer than the address.
+    pub fn new(addr: IpAddr, prefix_len: u8) -> Result<Self, Error> {
+        let max_len = match addr {
+            IpAddr::V4(_) => 32,
+            IpAddr::V6(_) => 128,
+        };
+        if prefix_len > max_len {
+            return Err(Error::InvalidCidr);
+        }
+        Ok(Self { addr, prefix_len })
+    }
+
+    pub fn addr(&self) -> IpAddr {
+        self.addr
+    }
+
+    pub fn prefix_len(&self) -> u8 {
+        self.prefix_len
+    }
+
+    /// The netmask of the prefix, `255.255.255.0` for a `/24` one.
+    pub fn netmask(&self) -> IpAddr {
+        match self.addr {
+            IpAddr::V4(_) => {
+                let mask = u32::MAX.checked_shl(32 - self.prefix_len as u32).unwrap_or(0);
+                IpAddr::V4(Ipv4Addr::from(mask))
+            }
+            IpAddr::V6(_) => {
+                let mask = u128::MAX.checked_shl(128 - self.prefix_len as u32).unwrap_or(0);
+                IpAddr::V6(Ipv6Addr::from(mask))
+            }
+        }
+    }
+}
+
+impl Display for Cidr {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", self.addr, self.prefix_len)
+    }
+}
+
+impl FromStr for Cidr {
+    type Err = Error;
+
+    /// Parse an address of either version followed by a slash and the prefix length.
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let (addr, prefix_len) = s.split_once('/').ok_or(Error::InvalidCidr)?;
+        let addr = addr.parse().map_err(|_| Error::InvalidCidr)?;
+        let prefix_len = prefix_len.parse().map_err(|_| Error::InvalidCidr)?;
+        Self::new(addr, prefix_len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+    use super::Cidr;
+
+    #[test]
+    fn from_str() {
+        let cidr: Cidr = "192.168.233.233/24".parse().unwrap();
+        assert_eq!(cidr.addr(), IpAddr::V4(Ipv4Addr::new(192, 168, 233, 233)));
+        assert_eq!(cidr.prefix_len(), 24);
+        assert_eq!(cidr.netmask(), IpAddr::V4(Ipv4Addr::new(255, 255, 255, 0)));
+        assert_eq!(cidr.to_string(), "192.168.233.233/24");
+
+        let cidr: Cidr = "fd00::1/64".parse().unwrap();
+        assert_eq!(
+            cidr.netmask(),
+            IpAddr::V6(Ipv6Addr::new(0xffff, 0xffff, 0xffff, 0xffff, 0, 0, 0, 0))
+        );
+        assert_eq!(
+            "10.0.0.1/0".parse::<Cidr>().unwrap().netmask(),
+            IpAddr::V4(Ipv4Addr::UNSPECIFIED)
+        );
+
+        assert!("192.168.233.233".parse::<Cidr>().is_err());
+        assert!("192.168.233.233/33".parse::<Cidr>().is_err());
+        assert!("fd00::1/129".parse::<Cidr>().is_err());
+        assert!("tun-radish/24".parse::<Cidr>().is_err());
+    }
+}
@@ -0,0 +1,242 @@
+use std::io::{Error as IOError, ErrorKind};
+use std::os::unix::io::RawFd;
+use std::time::{Duration, Instant};
+
+use libc::{c_int, c_short, poll, pollfd, POLLIN};
+
+use crate::capabilities::Offload;
+use crate::error::Result;
+use crate::ethernet::frame::EtherType;
+use crate::net_device::error::Error;
+use crate::pool::{BufferPool, PooledBuffer};
+
+pub mod consts {
+    pub const DEFAULT_MTU: usize = 1500; // The MTU of Ethernet, which TUN and TAP devices start with
+    pub const BATCH_LEN: usize = 16; // Packets the interface receives from the device at a time
+}
+
+/// What a device carries: IP packets, like a TUN device, or Ethernet frames, like a TAP device.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Medium {
+    Ip,
+    Ethernet,
+}
+
+/// What the stack should know about a device before sending through it.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct DeviceCapabilities {
+    pub medium: Medium,
+    /// Work the device does for the stack, such as computing checksums.
+    pub offload: Offload,
+}
+
+/// A buffer of a batch, holding a packet once one is received into it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Buffer {
+    data: PooledBuffer,
+    len: usize,
+    protocol: Option<EtherType>,
+}
+
+impl Buffer {
+    /// An empty buffer able to hold a packet of `capacity` octets.
+    pub fn new(capacity: usize) -> Self {
+        Self::from_pool(PooledBuffer::from(vec![0; capacity]))
+    }
+
+    /// An empty buffer of a pool, returned to it once dropped.
+    pub fn from_pool(data: PooledBuffer) -> Self {
+        Self {
+            data,
+            len: 0,
+            protocol: None,
+        }
+    }
+
+    /// The whole buffer, for the device to receive a packet into.
+    pub fn as_mut_slice(&mut self) -> &mut [u8] {
+        self.data.as_mut()
+    }
+
+    /// Set the length of the packet received, which is at most the capacity.
+    pub fn set_len(&mut self, len: usize) {
+        self.len = len.min(self.data.len());
+    }
+
+    /// The protocol of the packet, if the device tells it rather than leaving it to the version of the packet.
+    pub fn protocol(&self) -> Option<EtherType> {
+        self.protocol
+    }
+
+    pub fn set_protocol(&mut self, protocol: Option<EtherType>) {
+        self.protocol = protocol;
+    }
+
+    pub fn packet(&self) -> &[u8] {
+        &self.data.as_ref()[..self.len]
+    }
+
+    pub fn into_packet(self) -> Vec<u8> {
+        self.into_pooled().into_vec()
+    }
+
+    /// The packet, in the buffer of the pool it came from.
+    pub fn into_pooled(mut self) -> PooledBuffer {
+        self.data.truncate(self.len);
+        self.data
+    }
+}
+
+impl From<Vec<u8>> for Buffer {
+    /// A buffer holding `packet`, of unknown protocol.
+    fn from(packet: Vec<u8>) -> Self {
+        Self {
+            len: packet.len(),
+            data: PooledBuffer::from(packet),
+            protocol: None,
+        }
+    }
+}
+
+/// A packet received by a device, parsed in the buffer it was received into.
+#[derive(Debug)]
+pub struct RxToken {
+    buffer: PooledBuffer,
+}
+
+impl RxToken {
+    /// Hand the packet to `f`, which may rewrite it in place.
+    pub fn consume<R, F>(mut self, f: F) -> R
+    where
+        F: FnOnce(&mut [u8]) -> R,
+    {
+        f(self.buffer.as_mut())
+    }
+
+    /// Keep the buffer holding the packet, such as for a fragment waiting for the others.
+    pub fn into_buffer(self) -> PooledBuffer {
+        self.buffer
+    }
+}
+
+/// The right to transmit a packet through a device, built in place in a buffer of the MTU.
+pub struct TxToken<'a, D: Device> {
+    device: &'a mut D,
+    buffer: PooledBuffer,
+}
+
+impl<'a, D: Device> TxToken<'a, D> {
+    /// Let `f` build a packet of `len` octets, then transmit it.
+    /// The buffer is not zeroed, `f` is expected to write every octet of it.
+    pub fn consume<R, F>(mut self, len: usize, f: F) -> Result<R>
+    where
+        F: FnOnce(&mut [u8]) -> R,
+    {
+        if len > self.buffer.len() {
+            return Err(Error::PacketTooLong.into());
+        }
+
+        self.buffer.truncate(len);
+        let result = f(self.buffer.as_mut());
+        self.device.transmit(self.buffer.as_ref())?;
+        Ok(result)
+    }
+}
+
+/// A device the interface receives packets from and transmits packets through.
+///
+/// A packet is read or written whole: `receive` returns the length of one packet,
+/// and `transmit` either sends all of `packet` or fails.
+pub trait Device {
+    /// Receive a packet into `buf`, returns its length.
+    fn receive(&mut self, buf: &mut [u8]) -> Result<usize>;
+
+    /// Transmit `packet`, which is no longer than the MTU.
+    fn transmit(&mut self, packet: &[u8]) -> Result<()>;
+
+    /// Receive up to `bufs.len()` packets, returns how many were received.
+    /// The first packet is waited for like by `receive`, the others are only received if already queued.
+    ///
+    /// By default a single packet is received, devices able to tell whether more are queued receive more.
+    fn recv_batch(&mut self, bufs: &mut [Buffer]) -> Result<usize> {
+        match bufs.first_mut() {
+            Some(buf) => {
+                let len = self.receive(buf.as_mut_slice())?;
+                buf.set_len(len);
+                Ok(1)
+            }
+            None => Ok(0),
+        }
+    }
+
+    /// Wait up to `timeout` for a packet to receive, returns whether one arrived.
+    /// The interface waits this way so it can run its timers while no packet arrives.
+    ///
+    /// By default a packet is assumed to be there, and `receive` waits for it instead.
+    fn wait(&mut self, _timeout: Duration) -> Result<bool> {
+        Ok(true)
+    }
+
+    /// Transmit `packets` in order, stopping at the first one failing.
+    fn transmit_batch(&mut self, packets: &[&[u8]]) -> Result<()> {
+        packets.iter().try_for_each(|packet| self.transmit(packet))
+    }
+
+    /// The largest packet the device transmits, without the link layer header.
+    fn mtu(&self) -> usize;
+
+    fn capabilities(&self) -> DeviceCapabilities;
+
+    /// Receive a packet into a buffer of `pool`, to be parsed where it was received.
+    fn receive_token(&mut self, pool: &BufferPool) -> Result<RxToken> {
+        let mut buffer = pool.checkout(self.mtu());
+        let len = self.receive(buffer.as_mut())?;
+        buffer.truncate(len);
+        Ok(RxToken { buffer })
+    }
+
+    /// Check out a buffer of `pool` for a packet to be built in and transmitted from.
+    fn transmit_token(&mut self, pool: &BufferPool) -> TxToken<'_, Self>
+    where
+        Self: Sized,
+    {
+        let buffer = pool.checkout(self.mtu());
+        TxToken { device: self, buffer }
+    }
+}
+
+/// Wait up to `timeout` for `fd` to become readable, returns whether it did.
+pub(crate) fn wait_readable(fd: RawFd, timeout: Duration) -> Result<bool> {
+    match wait_fd(fd, POLLIN, Some(timeout)) {
+        Ok(()) => Ok(true),
+        Err(e) if e.kind() == ErrorKind::TimedOut => Ok(false),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Wait until `fd` has one of the poll(2) `events`, failing with `TimedOut` once `timeout` elapses.
+/// Without a timeout it returns right away, and the read or write that follows blocks instead.
+pub(crate) fn wait_fd(fd: RawFd, events: c_short, timeout: Option<Duration>) -> std::io::Result<()> {
+    let deadline = match timeout {
+        Some(timeout) => Instant::now() + timeout,
+        None => return Ok(()),
+    };
+
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        // Round up, so a timeout shorter than a millisecond does not turn into a busy loop.
+        let millis = remaining.as_nanos().div_ceil(1_000_000).min(c_int::MAX as u128) as c_int;
+        let mut pollfd = pollfd { fd, events, revents: 0 };
+
+        match unsafe { poll(&mut pollfd, 1, millis) } {
+            0 => return Err(IOError::from(ErrorKind::TimedOut)),
+            n if n > 0 => return Ok(()),
+            _ => {
+                let err = IOError::last_os_error();
+                if err.kind() != ErrorKind::Interrupted {
+                    return Err(err);
+                }
+            }
+        }
+    }
+}
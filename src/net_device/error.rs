@@ -3,12 +3,22 @@ use std::fmt::{Display, Formatter};
 #[derive(Debug)]
 pub enum Error {
     NameTooLong,
+    InvalidCidr,
+    InvalidAddress,
+    InvalidMtu,
+    InvalidNetmask,
+    PacketTooLong,
 }
 
 impl Display for Error {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
             Error::NameTooLong => write!(f, "device name too long"),
+            Error::InvalidCidr => write!(f, "invalid CIDR notation"),
+            Error::InvalidAddress => write!(f, "invalid address"),
+            Error::InvalidMtu => write!(f, "invalid MTU"),
+            Error::InvalidNetmask => write!(f, "invalid netmask"),
+            Error::PacketTooLong => write!(f, "packet longer than the MTU"),
         }
     }
 }
@@ -0,0 +1,263 @@
+use std::time::{Duration, Instant};
+
+use crate::error::Result;
+use crate::metrics::{FaultMetrics, Registry};
+use crate::net_device::device::{Buffer, Device, DeviceCapabilities};
+
+/// The faults a `FaultDevice` introduces, rates are probabilities from 0 to 1.
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub struct Faults {
+    /// How often a packet is lost.
+    pub loss: f64,
+    /// How often a packet is sent twice.
+    pub duplication: f64,
+    /// How often a bit of a packet is flipped.
+    pub corruption: f64,
+    /// How often a packet is held back and sent after the next one.
+    pub reordering: f64,
+    /// How long every packet is delayed.
+    pub latency: Duration,
+    /// The most a packet is delayed on top of the latency, which reorders packets as well.
+    pub jitter: Duration,
+    /// Packets larger than the MTU of the path are lost, as through a router that does not report
+    /// they are too big, though the device still advertises its own MTU.
+    pub path_mtu: Option<usize>,
+}
+
+/// A device that transmits through another one with the faults of a bad network,
+/// so retransmissions, reassembly timeouts and path MTU discovery can be tested.
+///
+/// Faults are drawn from a generator seeded by the caller, the same seed and packets give the same faults.
+/// Only transmitted packets are affected, wrap both ends of a link to affect both directions.
+/// Delayed packets are transmitted by `poll`, and by `transmit` and `receive` once they are due.
+#[derive(Debug)]
+pub struct FaultDevice<D: Device> {
+    device: D,
+    faults: Faults,
+    random: u64,
+    /// The packet held back to be sent after the next one.
+    held: Option<Vec<u8>>,
+    /// Delayed packets, ordered by when they are due.
+    delayed: Vec<(Instant, Vec<u8>)>,
+    metrics: FaultMetrics,
+}
+
+impl<D: Device> FaultDevice<D> {
+    pub fn new(device: D, faults: Faults, seed: u64) -> Self {
+        Self {
+            device,
+            faults,
+            // The generator would be stuck on a zero state.
+            random: seed.max(1),
+            held: None,
+            delayed: vec![],
+            metrics: FaultMetrics::register(&Registry::new()),
+        }
+    }
+
+    pub fn faults(&self) -> &Faults {
+        &self.faults
+    }
+
+    pub fn set_faults(&mut self, faults: Faults) {
+        self.faults = faults;
+    }
+
+    /// Returns how many packets were affected by each fault.
+    pub fn metrics(&self) -> &FaultMetrics {
+        &self.metrics
+    }
+
+    /// Count the faults into `registry` from now on, such as the one of the interface above the device.
+    pub fn set_metrics(&mut self, registry: &Registry) {
+        self.metrics = FaultMetrics::register(registry);
+    }
+
+    pub fn inner(&self) -> &D {
+        &self.device
+    }
+
+    pub fn inner_mut(&mut self) -> &mut D {
+        &mut self.device
+    }
+
+    /// Transmit the delayed packets due at `now`.
+    pub fn poll(&mut self, now: Instant) -> Result<()> {
+        let due = self.delayed.iter().take_while(|(at, _)| *at <= now).count();
+        for (_, packet) in self.delayed.drain(..due).collect::<Vec<_>>() {
+            self.device.transmit(&packet)?;
+        }
+        Ok(())
+    }
+
+    /// Returns when the next delayed packet is due, if any.
+    pub fn poll_at(&self) -> Option<Instant> {
+        self.delayed.first().map(|(at, _)| *at)
+    }
+
+    /// A xorshift generator, small and deterministic.
+    fn next_random(&mut self) -> u64 {
+        self.random ^= self.random << 13;
+        self.random ^= self.random >> 7;
+        self.random ^= self.random << 17;
+        self.random
+    }
+
+    fn chance(&mut self, rate: f64) -> bool {
+        rate > 0.0 && (self.next_random() as f64 / u64::MAX as f64) < rate
+    }
+
+    /// Transmit `packet` after the latency and a random jitter, right away when there is neither.
+    fn schedule(&mut self, now: Instant, packet: Vec<u8>) -> Result<()> {
+        let jitter = self.faults.jitter.as_nanos() as u64;
+        let delay = match jitter {
+            0 => self.faults.latency,
+            _ => self.faults.latency + Duration::from_nanos(self.next_random() % (jitter + 1)),
+        };
+        self.metrics.transmitted.inc();
+        if delay.is_zero() {
+            return self.device.transmit(&packet);
+        }
+
+        let at = now + delay;
+        let index = self.delayed.iter().take_while(|(due, _)| *due <= at).count();
+        self.delayed.insert(index, (at, packet));
+        Ok(())
+    }
+}
+
+impl<D: Device> Device for FaultDevice<D> {
+    fn receive(&mut self, buf: &mut [u8]) -> Result<usize> {
+        self.poll(Instant::now())?;
+        self.device.receive(buf)
+    }
+
+    fn recv_batch(&mut self, bufs: &mut [Buffer]) -> Result<usize> {
+        self.poll(Instant::now())?;
+        self.device.recv_batch(bufs)
+    }
+
+    /// Wait for a packet, or until a delayed one is due to be transmitted.
+    fn wait(&mut self, timeout: Duration) -> Result<bool> {
+        let now = Instant::now();
+        self.poll(now)?;
+        let timeout = self
+            .poll_at()
+            .map_or(timeout, |at| timeout.min(at.saturating_duration_since(now)));
+        self.device.wait(timeout)
+    }
+
+    fn transmit(&mut self, packet: &[u8]) -> Result<()> {
+        let now = Instant::now();
+        self.poll(now)?;
+
+        if self.faults.path_mtu.is_some_and(|mtu| packet.len() > mtu) || self.chance(self.faults.loss) {
+            self.metrics.lost.inc();
+            return Ok(());
+        }
+
+        let mut packet = packet.to_vec();
+        if !packet.is_empty() && self.chance(self.faults.corruption) {
+            let bit = self.next_random() as usize % (packet.len() * 8);
+            packet[bit / 8] ^= 1 << (bit % 8);
+            self.metrics.corrupted.inc();
+        }
+
+        let copies = if self.chance(self.faults.duplication) {
+            self.metrics.duplicated.inc();
+            2
+        } else {
+            1
+        };
+        for _ in 0..copies {
+            if self.held.is_none() && self.chance(self.faults.reordering) {
+                self.held = Some(packet.clone());
+                self.metrics.reordered.inc();
+                continue;
+            }
+            self.schedule(now, packet.clone())?;
+            if let Some(held) = self.held.take() {
+                self.schedule(now, held)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn mtu(&self) -> usize {
+        self.device.mtu()
+    }
+
+    fn capabilities(&self) -> DeviceCapabilities {
+        self.device.capabilities()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::{FaultDevice, Faults};
+    use crate::net_device::channel::ChannelDevice;
+    use crate::net_device::Device;
+
+    fn receive_all(device: &mut ChannelDevice) -> Vec<Vec<u8>> {
+        let mut buf = [0; 64];
+        let mut packets = vec![];
+        while let Ok(len) = device.receive(&mut buf) {
+            packets.push(buf[..len].to_vec());
+        }
+        packets
+    }
+
+    #[test]
+    fn faults() {
+        let (left, mut right) = ChannelDevice::pair();
+        right.set_nonblocking(true);
+        let faults = Faults {
+            loss: 0.3,
+            ..Default::default()
+        };
+        let mut device = FaultDevice::new(left, faults, 42);
+
+        for i in 0..100u8 {
+            device.transmit(&[i]).unwrap();
+        }
+        let received = receive_all(&mut right);
+        assert!(device.metrics().lost.get() > 0);
+        assert_eq!(received.len() as u64, 100 - device.metrics().lost.get());
+        assert!(received.windows(2).all(|pair| pair[0] < pair[1]));
+
+        // Every packet is corrupted and sent twice, the first copy held back behind the second.
+        device.set_faults(Faults {
+            duplication: 1.0,
+            corruption: 1.0,
+            reordering: 1.0,
+            ..Default::default()
+        });
+        device.transmit(&[0; 4]).unwrap();
+        let received = receive_all(&mut right);
+        assert_eq!(received.len(), 2);
+        assert!(received
+            .iter()
+            .all(|packet| packet.iter().map(|octet| octet.count_ones()).sum::<u32>() == 1));
+
+        // Packets are delayed and, with jitter, may arrive out of order.
+        device.set_faults(Faults {
+            latency: Duration::from_secs(10),
+            jitter: Duration::from_secs(1),
+            path_mtu: Some(8),
+            ..Default::default()
+        });
+        device.transmit(&[1]).unwrap();
+        device.transmit(&[2]).unwrap();
+        device.transmit(&[0; 9]).unwrap();
+        assert!(receive_all(&mut right).is_empty());
+        let now = Instant::now();
+        assert!(device.poll_at().unwrap() >= now + Duration::from_secs(9));
+        device.poll(now + Duration::from_secs(12)).unwrap();
+        let mut received = receive_all(&mut right);
+        received.sort();
+        assert_eq!(received, vec![vec![1], vec![2]]);
+        assert_eq!(device.poll_at(), None);
+    }
+}
@@ -7,7 +7,6 @@ use crate::error::Result;
 use crate::net_device::error::Error;
 
 /// Data structure defined in <net/if.h>
-
 #[repr(C)]
 pub struct InterfaceRequest {
     pub name: InterfaceName,
@@ -77,4 +76,12 @@ pub mod consts {
     pub const TUNSETPERSIST: c_ulong = 0x400454cb;
     pub const TUNSETOWNER: c_ulong = 0x400454cc;
     pub const TUNSETGROUP: c_ulong = 0x400454ce;
+    pub const TUNGETIFF: c_ulong = 0x800454d2;
+    pub const TUNGETSNDBUF: c_ulong = 0x800454d3;
+    pub const TUNSETSNDBUF: c_ulong = 0x400454d4;
+
+    pub const PACKET_INFO_LEN: usize = 4; // struct tun_pi, flags and protocol
+    pub const TUN_PKT_STRIP: u16 = 0x0001; // The frame did not fit in the read buffer
+    pub const ARPHRD_ETHER: u16 = 1;
+    pub const IPV6_PREFIX_LEN: u8 = 64; // The prefix length of an ipv6 address set without a netmask
 }
@@ -1,3 +1,22 @@
+#[cfg(feature = "tokio")]
+pub mod async_tun;
+pub mod channel;
+pub mod cidr;
+pub mod device;
 pub mod error;
+pub mod fault;
 pub mod r#if;
+#[cfg(target_os = "linux")]
+pub mod raw_socket;
+#[cfg(any(target_os = "linux", target_os = "freebsd", target_os = "openbsd"))]
+pub mod reopen;
+#[cfg(target_os = "linux")]
+pub mod route;
+#[cfg(target_os = "linux")]
+pub mod tap;
+#[cfg(any(target_os = "linux", target_os = "freebsd", target_os = "openbsd"))]
 pub mod tun;
+#[cfg(any(target_os = "linux", target_os = "freebsd", target_os = "openbsd"))]
+pub mod tun_builder;
+
+pub use crate::net_device::device::Device;
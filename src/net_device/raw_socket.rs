@@ -0,0 +1,321 @@
+use std::convert::TryInto;
+use std::io::{Read, Write};
+use std::mem::size_of;
+use std::os::unix::io::{AsFd, AsRawFd, BorrowedFd, RawFd};
+use std::time::Duration;
+
+use libc::{
+    bind, c_int, c_ushort, close, ioctl, read, setsockopt, sock_filter, sock_fprog, sockaddr, sockaddr_ll, socket,
+    socklen_t, write, AF_PACKET, BPF_ABS, BPF_H, BPF_JEQ, BPF_JMP, BPF_K, BPF_LD, BPF_RET, ETH_P_ALL, SIOCGIFHWADDR,
+    SIOCGIFINDEX, SIOCGIFMTU, SOCK_RAW, SOL_PACKET, SOL_SOCKET, SO_ATTACH_FILTER, SO_DETACH_FILTER,
+};
+
+use crate::capabilities::Offload;
+use crate::error::Result;
+use crate::ethernet::frame::{EtherType, MacAddr};
+use crate::macros::diagnostics::error;
+use crate::net_device::device::{wait_readable, Device, DeviceCapabilities, Medium};
+use crate::net_device::r#if::InterfaceRequest;
+
+pub mod consts {
+    use libc::c_int;
+
+    pub const PACKET_ADD_MEMBERSHIP: c_int = 1; // <linux/if_packet.h>
+    pub const PACKET_DROP_MEMBERSHIP: c_int = 2;
+    pub const PACKET_MR_PROMISC: u16 = 1; // A membership receiving every frame of the interface
+}
+
+/// `struct packet_mreq` defined in <linux/if_packet.h>
+#[repr(C)]
+struct PacketMembership {
+    ifindex: c_int,
+    kind: u16,
+    addr_len: u16,
+    addr: [u8; 8],
+}
+
+/// A classic BPF program accepting the frames of `ether_types` only, to attach with `attach_filter`.
+/// It holds at most 254 EtherTypes, the offset of a jump being a single octet.
+pub fn ether_type_filter(ether_types: &[EtherType]) -> Vec<sock_filter> {
+    assert!(ether_types.len() < 255, "too many EtherTypes for a jump offset");
+    let count = ether_types.len() as u8;
+
+    // Load the EtherType, jump to the accepting return once it matches, else fall through to the rejecting one.
+    let mut program = vec![sock_filter {
+        code: (BPF_LD | BPF_H | BPF_ABS) as u16,
+        jt: 0,
+        jf: 0,
+        k: 12,
+    }];
+    for (i, ether_type) in ether_types.iter().enumerate() {
+        program.push(sock_filter {
+            code: (BPF_JMP | BPF_JEQ | BPF_K) as u16,
+            jt: count - i as u8,
+            jf: 0,
+            k: u16::from(*ether_type) as u32,
+        });
+    }
+    program.push(sock_filter {
+        code: (BPF_RET | BPF_K) as u16,
+        jt: 0,
+        jf: 0,
+        k: 0,
+    });
+    program.push(sock_filter {
+        code: (BPF_RET | BPF_K) as u16,
+        jt: 0,
+        jf: 0,
+        k: u32::MAX,
+    });
+    program
+}
+
+/// A packet(7) socket bound to a network interface of the host, such as a physical NIC,
+/// reading and writing whole Ethernet frames.
+///
+/// The kernel keeps handling the traffic of the interface too, so the stack should use addresses
+/// the host does not. Opening the socket needs the `CAP_NET_RAW` capability.
+#[derive(Debug)]
+pub struct RawSocketDevice {
+    fd: RawFd,
+    name: String,
+    ifindex: c_int,
+    mtu: usize,
+}
+
+impl RawSocketDevice {
+    /// Open a raw socket receiving every frame of the interface `name`
+    pub fn new(name: &str) -> Result<Self> {
+        let protocol = (ETH_P_ALL as u16).to_be();
+        let fd = unsafe { socket(AF_PACKET, SOCK_RAW, protocol as c_int) };
+        if fd < 0 {
+            error!("Failed to create a packet socket.");
+            return Err(std::io::Error::last_os_error().into());
+        }
+
+        match Self::bind(fd, name, protocol) {
+            Ok((ifindex, mtu)) => Ok(Self {
+                fd,
+                name: name.to_string(),
+                ifindex,
+                mtu,
+            }),
+            Err(e) => {
+                if unsafe { close(fd) } < 0 {
+                    error!("Failed to close RawSocketDevice file descriptor.");
+                }
+                Err(e)
+            }
+        }
+    }
+
+    /// Bind the socket to the interface `name`, returns its index and MTU
+    fn bind(fd: RawFd, name: &str, protocol: u16) -> Result<(c_int, usize)> {
+        let mut request = InterfaceRequest::new(name)?;
+        if unsafe { ioctl(fd, SIOCGIFINDEX, &mut request) } < 0 {
+            error!("Failed to get the index of interface '{}'.", name);
+            return Err(std::io::Error::last_os_error().into());
+        }
+        let ifindex = unsafe { request.union.value };
+
+        if unsafe { ioctl(fd, SIOCGIFMTU, &mut request) } < 0 {
+            error!("Failed to get the MTU of interface '{}'.", name);
+            return Err(std::io::Error::last_os_error().into());
+        }
+        let mtu = unsafe { request.union.mtu } as usize;
+
+        let addr = sockaddr_ll {
+            sll_family: AF_PACKET as u16,
+            sll_protocol: protocol,
+            sll_ifindex: ifindex,
+            sll_hatype: 0,
+            sll_pkttype: 0,
+            sll_halen: 0,
+            sll_addr: [0; 8],
+        };
+        let result = unsafe {
+            bind(
+                fd,
+                (&addr as *const sockaddr_ll).cast::<sockaddr>(),
+                size_of::<sockaddr_ll>() as socklen_t,
+            )
+        };
+        if result < 0 {
+            error!("Failed to bind the packet socket to interface '{}'.", name);
+            return Err(std::io::Error::last_os_error().into());
+        }
+
+        Ok((ifindex, mtu))
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn ifindex(&self) -> c_int {
+        self.ifindex
+    }
+
+    /// In promiscuous mode the interface receives every frame on the link, not only the ones sent to it.
+    /// The mode ends with the socket, and other sockets asking for it keep it on.
+    pub fn set_promiscuous(&self, promiscuous: bool) -> Result<&Self> {
+        let membership = PacketMembership {
+            ifindex: self.ifindex,
+            kind: consts::PACKET_MR_PROMISC,
+            addr_len: 0,
+            addr: [0; 8],
+        };
+        let option = if promiscuous {
+            consts::PACKET_ADD_MEMBERSHIP
+        } else {
+            consts::PACKET_DROP_MEMBERSHIP
+        };
+
+        let result = unsafe {
+            setsockopt(
+                self.fd,
+                SOL_PACKET,
+                option,
+                (&membership as *const PacketMembership).cast(),
+                size_of::<PacketMembership>() as socklen_t,
+            )
+        };
+        if result < 0 {
+            error!("Failed to set promiscuous mode: {}.", promiscuous);
+            return Err(std::io::Error::last_os_error().into());
+        }
+
+        Ok(self)
+    }
+
+    /// Attach a classic BPF program, such as one of `ether_type_filter`, so the kernel drops the frames it rejects
+    /// before they are queued to the socket. It replaces the program attached before.
+    pub fn attach_filter(&self, program: &[sock_filter]) -> Result<&Self> {
+        let program = sock_fprog {
+            len: program.len() as c_ushort,
+            filter: program.as_ptr() as *mut sock_filter,
+        };
+
+        let result = unsafe {
+            setsockopt(
+                self.fd,
+                SOL_SOCKET,
+                SO_ATTACH_FILTER,
+                (&program as *const sock_fprog).cast(),
+                size_of::<sock_fprog>() as socklen_t,
+            )
+        };
+        if result < 0 {
+            error!("Failed to attach a filter of {} instructions.", program.len);
+            return Err(std::io::Error::last_os_error().into());
+        }
+
+        Ok(self)
+    }
+
+    /// Detach the BPF program, the socket receives every frame again
+    pub fn detach_filter(&self) -> Result<&Self> {
+        // The value is ignored, but the kernel refuses options shorter than an int.
+        let unused: c_int = 0;
+        let result = unsafe {
+            setsockopt(
+                self.fd,
+                SOL_SOCKET,
+                SO_DETACH_FILTER,
+                (&unused as *const c_int).cast(),
+                size_of::<c_int>() as socklen_t,
+            )
+        };
+        if result < 0 {
+            error!("Failed to detach the filter.");
+            return Err(std::io::Error::last_os_error().into());
+        }
+
+        Ok(self)
+    }
+
+    /// Get the hardware address of the interface
+    pub fn hardware_addr(&self) -> Result<MacAddr> {
+        let mut request = InterfaceRequest::new(&self.name)?;
+
+        let result = unsafe { ioctl(self.fd, SIOCGIFHWADDR, &mut request) };
+        if result < 0 {
+            error!("Failed to get hardware address.");
+            return Err(std::io::Error::last_os_error().into());
+        }
+
+        let data = unsafe { request.union.mac_addr.sa_data };
+        let octets: Vec<u8> = data[..6].iter().map(|octet| *octet as u8).collect();
+        Ok(MacAddr(octets.as_slice().try_into().unwrap()))
+    }
+}
+
+impl Device for RawSocketDevice {
+    fn receive(&mut self, buf: &mut [u8]) -> Result<usize> {
+        Ok(self.read(buf)?)
+    }
+
+    fn wait(&mut self, timeout: Duration) -> Result<bool> {
+        wait_readable(self.fd, timeout)
+    }
+
+    fn transmit(&mut self, frame: &[u8]) -> Result<()> {
+        Ok(self.write_all(frame)?)
+    }
+
+    fn mtu(&self) -> usize {
+        self.mtu
+    }
+
+    fn capabilities(&self) -> DeviceCapabilities {
+        DeviceCapabilities {
+            medium: Medium::Ethernet,
+            offload: Offload::default(),
+        }
+    }
+}
+
+impl Read for RawSocketDevice {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = unsafe { read(self.fd, buf.as_mut_ptr().cast(), buf.len()) };
+        if n < 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        Ok(n as usize)
+    }
+}
+
+impl Write for RawSocketDevice {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let n = unsafe { write(self.fd, buf.as_ptr().cast(), buf.len()) };
+        if n < 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        Ok(n as usize)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl AsRawFd for RawSocketDevice {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd
+    }
+}
+
+impl AsFd for RawSocketDevice {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        // The descriptor stays open as long as the device.
+        unsafe { BorrowedFd::borrow_raw(self.fd) }
+    }
+}
+
+impl Drop for RawSocketDevice {
+    fn drop(&mut self) {
+        if unsafe { close(self.fd) } < 0 {
+            error!("Failed to close RawSocketDevice file descriptor.");
+        }
+    }
+}
@@ -0,0 +1,283 @@
+use std::collections::VecDeque;
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::time::Duration;
+
+use libc::{EBADF, EIO, ENODEV, ENXIO};
+#[cfg(target_os = "linux")]
+use libc::{EBADFD, EFAULT};
+
+use crate::error::{Error, Result};
+use crate::macros::diagnostics::{info, warn};
+use crate::net_device::device::{Buffer, Device, DeviceCapabilities};
+use crate::net_device::tun::TunDevice;
+use crate::net_device::tun_builder::TunDeviceBuilder;
+
+pub mod consts {
+    use std::time::Duration;
+
+    pub const MAX_ATTEMPTS: u32 = 5; // Attempts to reopen a device before giving up
+    pub const RETRY_DELAY: Duration = Duration::from_millis(200); // Between two attempts
+}
+
+/// How persistently a device is reopened.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Policy {
+    pub max_attempts: u32,
+    pub retry_delay: Duration,
+}
+
+impl Default for Policy {
+    fn default() -> Self {
+        Self {
+            max_attempts: consts::MAX_ATTEMPTS,
+            retry_delay: consts::RETRY_DELAY,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event {
+    /// The device failed and was opened again after `attempts` attempts.
+    Reopened { attempts: u32 },
+    /// Every attempt to open the device again failed, the error is returned to the interface.
+    GaveUp { attempts: u32 },
+}
+
+/// Whether the device is gone rather than the packet at fault, such as the interface having been deleted
+fn is_gone(e: &Error) -> bool {
+    let errno = match e {
+        Error::Io(e) => e.raw_os_error(),
+        _ => None,
+    };
+    errno.is_some_and(|errno| {
+        // The buffers are valid, so EFAULT comes from a TUN device detached from its deleted interface.
+        #[cfg(target_os = "linux")]
+        if errno == EBADFD || errno == EFAULT {
+            return true;
+        }
+        matches!(errno, EBADF | EIO | ENODEV | ENXIO)
+    })
+}
+
+/// A device opened again, by name or however `open` does it, once it fails with EBADF, EBADFD, EFAULT, EIO, ENODEV or ENXIO,
+/// as a TUN device does once its interface is deleted.
+/// The packet being received or transmitted is then retried on the new device,
+/// so the interface and its sockets survive the interface being deleted and created again.
+///
+/// Opening blocks the interface between attempts. The descriptor of the device changes when it is reopened,
+/// so it does not suit an `AsyncInterface`, which registers the descriptor once.
+pub struct ReopenDevice<D: Device = TunDevice> {
+    device: D,
+    open: Box<dyn FnMut() -> Result<D> + Send>,
+    policy: Policy,
+    events: VecDeque<Event>,
+}
+
+impl<D: Device> ReopenDevice<D> {
+    /// Open the device with `open`, which is called again whenever the device is gone.
+    /// It should configure the device too, as a new interface has no addresses.
+    pub fn new(mut open: impl FnMut() -> Result<D> + Send + 'static, policy: Policy) -> Result<Self> {
+        Ok(Self {
+            device: open()?,
+            open: Box::new(open),
+            policy,
+            events: VecDeque::new(),
+        })
+    }
+
+    pub fn device(&self) -> &D {
+        &self.device
+    }
+
+    pub fn device_mut(&mut self) -> &mut D {
+        &mut self.device
+    }
+
+    pub fn policy(&self) -> Policy {
+        self.policy
+    }
+
+    pub fn set_policy(&mut self, policy: Policy) {
+        self.policy = policy;
+    }
+
+    /// Returns the earliest event not returned yet.
+    pub fn poll_event(&mut self) -> Option<Event> {
+        self.events.pop_front()
+    }
+
+    fn reopen(&mut self) -> Result<()> {
+        let mut attempts = 0;
+        loop {
+            attempts += 1;
+            match (self.open)() {
+                Ok(device) => {
+                    info!("device reopened after {} attempts", attempts);
+                    self.device = device;
+                    self.events.push_back(Event::Reopened { attempts });
+                    return Ok(());
+                }
+                Err(e) if attempts >= self.policy.max_attempts => {
+                    warn!("device not reopened, giving up: {}", e);
+                    self.events.push_back(Event::GaveUp { attempts });
+                    return Err(e);
+                }
+                Err(e) => {
+                    warn!("device not reopened, retrying: {}", e);
+                    std::thread::sleep(self.policy.retry_delay);
+                }
+            }
+        }
+    }
+}
+
+impl ReopenDevice<TunDevice> {
+    /// Build a tun device with `builder`, which builds it again by name, with its address and MTU, once it is gone.
+    pub fn tun(builder: TunDeviceBuilder, policy: Policy) -> Result<Self> {
+        Self::new(move || builder.clone().build(), policy)
+    }
+}
+
+impl<D: Device> Device for ReopenDevice<D> {
+    fn receive(&mut self, buf: &mut [u8]) -> Result<usize> {
+        match self.device.receive(buf) {
+            Err(e) if is_gone(&e) => {
+                self.reopen()?;
+                self.device.receive(buf)
+            }
+            result => result,
+        }
+    }
+
+    fn recv_batch(&mut self, bufs: &mut [Buffer]) -> Result<usize> {
+        match self.device.recv_batch(bufs) {
+            Err(e) if is_gone(&e) => {
+                self.reopen()?;
+                self.device.recv_batch(bufs)
+            }
+            result => result,
+        }
+    }
+
+    fn wait(&mut self, timeout: Duration) -> Result<bool> {
+        match self.device.wait(timeout) {
+            Err(e) if is_gone(&e) => {
+                self.reopen()?;
+                self.device.wait(timeout)
+            }
+            result => result,
+        }
+    }
+
+    fn transmit(&mut self, packet: &[u8]) -> Result<()> {
+        match self.device.transmit(packet) {
+            Err(e) if is_gone(&e) => {
+                self.reopen()?;
+                self.device.transmit(packet)
+            }
+            result => result,
+        }
+    }
+
+    fn mtu(&self) -> usize {
+        self.device.mtu()
+    }
+
+    fn capabilities(&self) -> DeviceCapabilities {
+        self.device.capabilities()
+    }
+}
+
+impl<D: Device + AsRawFd> AsRawFd for ReopenDevice<D> {
+    fn as_raw_fd(&self) -> RawFd {
+        self.device.as_raw_fd()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Error as IOError;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use libc::{EIO, ENODEV};
+
+    use super::{Event, Policy, ReopenDevice};
+    use crate::error::Result;
+    use crate::net_device::device::{Device, DeviceCapabilities, Medium};
+
+    /// A device whose first generation fails with EIO, like one whose interface was deleted
+    struct Flaky {
+        generation: u32,
+    }
+
+    impl Device for Flaky {
+        fn receive(&mut self, buf: &mut [u8]) -> Result<usize> {
+            if self.generation == 0 {
+                return Err(IOError::from_raw_os_error(EIO).into());
+            }
+            buf[0] = self.generation as u8;
+            Ok(1)
+        }
+
+        fn transmit(&mut self, _packet: &[u8]) -> Result<()> {
+            Err(IOError::from(std::io::ErrorKind::InvalidInput).into())
+        }
+
+        fn mtu(&self) -> usize {
+            1500
+        }
+
+        fn capabilities(&self) -> DeviceCapabilities {
+            DeviceCapabilities {
+                medium: Medium::Ip,
+                offload: Default::default(),
+            }
+        }
+    }
+
+    #[test]
+    fn reopen() {
+        let policy = Policy {
+            max_attempts: 3,
+            retry_delay: Duration::ZERO,
+        };
+        let opened = Arc::new(AtomicU32::new(0));
+        let counter = opened.clone();
+        let mut device = ReopenDevice::new(
+            move || {
+                // The first reopening attempt fails too.
+                match counter.fetch_add(1, Ordering::SeqCst) {
+                    1 => Err(IOError::from_raw_os_error(EIO).into()),
+                    n => Ok(Flaky { generation: n }),
+                }
+            },
+            policy,
+        )
+        .unwrap();
+
+        let mut buf = [0; 1];
+        assert_eq!(device.receive(&mut buf).unwrap(), 1);
+        assert_eq!(buf[0], 2);
+        assert_eq!(device.poll_event(), Some(Event::Reopened { attempts: 2 }));
+        assert_eq!(device.poll_event(), None);
+
+        // Errors about the packet do not reopen the device.
+        assert!(device.transmit(&[0]).is_err());
+        assert_eq!(opened.load(Ordering::SeqCst), 3);
+
+        // The interface is never created again.
+        let mut opened = false;
+        let mut device = ReopenDevice::new(
+            move || match std::mem::replace(&mut opened, true) {
+                false => Ok(Flaky { generation: 0 }),
+                true => Err(IOError::from_raw_os_error(ENODEV).into()),
+            },
+            policy,
+        )
+        .unwrap();
+        assert!(device.receive(&mut buf).is_err());
+        assert_eq!(device.poll_event(), Some(Event::GaveUp { attempts: 3 }));
+    }
+}
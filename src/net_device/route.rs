@@ -0,0 +1,151 @@
+use std::ffi::CString;
+use std::mem::transmute;
+use std::net::Ipv4Addr;
+
+use libc::{
+    c_char, c_short, c_uchar, c_ulong, c_ushort, close, in_addr, ioctl, sockaddr, sockaddr_in, socket, AF_INET,
+    RTF_GATEWAY, RTF_HOST, RTF_UP, SIOCADDRT, SIOCDELRT, SOCK_DGRAM,
+};
+
+use crate::error::Result;
+use crate::macros::diagnostics::error;
+
+/// `struct rtentry` defined in <net/route.h>, which the libc crate does not provide for glibc
+#[repr(C)]
+struct RouteEntry {
+    pad1: c_ulong,
+    dst: sockaddr,
+    gateway: sockaddr,
+    genmask: sockaddr,
+    flags: c_ushort,
+    pad2: c_short,
+    pad3: c_ulong,
+    tos: c_uchar,
+    class: c_uchar,
+    #[cfg(target_pointer_width = "64")]
+    pad4: [c_short; 3],
+    #[cfg(not(target_pointer_width = "64"))]
+    pad4: c_short,
+    metric: c_short,
+    dev: *mut c_char,
+    mtu: c_ulong,
+    window: c_ulong,
+    irtt: c_ushort,
+}
+
+fn sockaddr(addr: Ipv4Addr) -> sockaddr {
+    unsafe {
+        transmute::<sockaddr_in, sockaddr>(sockaddr_in {
+            sin_family: AF_INET as u16,
+            sin_port: 0,
+            sin_addr: in_addr {
+                s_addr: u32::from(addr).to_be(),
+            },
+            sin_zero: [0; 8],
+        })
+    }
+}
+
+/// An IPv4 route of the kernel, sending the packets to `destination/netmask` through an interface,
+/// so the host reaches the stack behind a TUN device like `ip route add` would set it up.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Route {
+    destination: Ipv4Addr,
+    netmask: Ipv4Addr,
+    gateway: Option<Ipv4Addr>,
+    metric: u16,
+}
+
+impl Route {
+    /// A route to the hosts of `destination/netmask`, which are on the link of the interface
+    pub fn new(destination: Ipv4Addr, netmask: Ipv4Addr) -> Self {
+        Self {
+            destination,
+            netmask,
+            gateway: None,
+            metric: 0,
+        }
+    }
+
+    /// Send the packets to `gateway` instead, which is on the link of the interface
+    pub fn gateway(mut self, gateway: Ipv4Addr) -> Self {
+        self.gateway = Some(gateway);
+        self
+    }
+
+    /// Set the metric of the route, the kernel prefers the routes of lower metrics.
+    /// Metrics above 32766 are clamped, the ioctl taking a short one.
+    pub fn metric(mut self, metric: u16) -> Self {
+        self.metric = metric;
+        self
+    }
+
+    /// Install the route through the interface `name`, such as the name of a `TunDevice`
+    pub fn add(&self, name: &str) -> Result<()> {
+        self.request(name, SIOCADDRT).inspect_err(|_| {
+            error!(
+                "Failed to add route: {}/{} dev {}.",
+                self.destination, self.netmask, name
+            );
+        })
+    }
+
+    /// Remove the route through the interface `name`
+    pub fn delete(&self, name: &str) -> Result<()> {
+        self.request(name, SIOCDELRT).inspect_err(|_| {
+            error!(
+                "Failed to delete route: {}/{} dev {}.",
+                self.destination, self.netmask, name
+            );
+        })
+    }
+
+    fn request(&self, name: &str, request: c_ulong) -> Result<()> {
+        let name = CString::new(name)?;
+
+        let mut flags = RTF_UP;
+        if self.gateway.is_some() {
+            flags |= RTF_GATEWAY;
+        }
+        if self.netmask == Ipv4Addr::BROADCAST {
+            flags |= RTF_HOST;
+        }
+
+        let destination = u32::from(self.destination) & u32::from(self.netmask);
+        let entry = RouteEntry {
+            pad1: 0,
+            dst: sockaddr(Ipv4Addr::from(destination)),
+            gateway: sockaddr(self.gateway.unwrap_or(Ipv4Addr::UNSPECIFIED)),
+            genmask: sockaddr(self.netmask),
+            flags,
+            pad2: 0,
+            pad3: 0,
+            tos: 0,
+            class: 0,
+            pad4: Default::default(),
+            // The kernel stores the metric minus one, like route(8) we add it back.
+            metric: self.metric.min(c_short::MAX as u16 - 1) as c_short + 1,
+            dev: name.as_ptr() as *mut c_char,
+            mtu: 0,
+            window: 0,
+            irtt: 0,
+        };
+
+        let socket_fd = unsafe { socket(AF_INET, SOCK_DGRAM, 0) };
+        if socket_fd < 0 {
+            error!("Failed to create a socket.");
+            return Err(std::io::Error::last_os_error().into());
+        }
+
+        let result = unsafe { ioctl(socket_fd, request, &entry) };
+        let err = std::io::Error::last_os_error();
+        if unsafe { close(socket_fd) } < 0 {
+            error!("Failed to close route socket file descriptor.");
+        }
+
+        if result < 0 {
+            return Err(err.into());
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,171 @@
+use std::convert::TryInto;
+use std::io::{Error as IOError, ErrorKind, Read, Write};
+use std::ops::Deref;
+use std::os::unix::io::{AsFd, AsRawFd, BorrowedFd, RawFd};
+use std::time::Duration;
+
+use libc::{c_short, ioctl, IFF_NO_PI, IFF_TAP, SIOCGIFHWADDR, SIOCSIFHWADDR};
+
+use crate::capabilities::Offload;
+use crate::error::Result;
+use crate::ethernet::frame::consts::{HEADER_LEN, MAX_PAYLOAD_LEN};
+use crate::ethernet::frame::MacAddr;
+use crate::macros::diagnostics::error;
+use crate::net_device::device::{Device, DeviceCapabilities, Medium};
+use crate::net_device::r#if::{consts, InterfaceRequest};
+use crate::net_device::tun::TunDevice;
+
+/// A tap device, reading and writing Ethernet frames.
+/// Its configuration, such as addresses and flags, goes through the underlying tun device.
+#[derive(Debug)]
+pub struct TapDevice {
+    device: TunDevice,
+    /// Whether frames are prefixed with the `tun_pi` header of the kernel.
+    packet_info: bool,
+}
+
+impl TapDevice {
+    /// Create a new tap device, or connect to a tap device that already exists
+    pub fn new(name: &str) -> Result<Self> {
+        Ok(Self {
+            device: TunDevice::open(name, (IFF_TAP | IFF_NO_PI) as c_short)?,
+            packet_info: false,
+        })
+    }
+
+    /// Open the tap device with packet information, the kernel then reports frames
+    /// truncated by a too small read buffer instead of silently cutting them
+    pub fn with_packet_info(name: &str) -> Result<Self> {
+        Ok(Self {
+            device: TunDevice::open(name, IFF_TAP as c_short)?,
+            packet_info: true,
+        })
+    }
+
+    pub fn packet_info(&self) -> bool {
+        self.packet_info
+    }
+
+    /// Get the hardware address of the kernel side of the device
+    pub fn hardware_addr(&self) -> Result<MacAddr> {
+        let mut request = InterfaceRequest::new(self.device.name())?;
+
+        let result = unsafe { ioctl(self.device.socket_fd(), SIOCGIFHWADDR, &mut request) };
+        if result < 0 {
+            error!("Failed to get hardware address.");
+            return Err(std::io::Error::last_os_error().into());
+        }
+
+        let data = unsafe { request.union.mac_addr.sa_data };
+        let octets: Vec<u8> = data[..6].iter().map(|octet| *octet as u8).collect();
+        Ok(MacAddr(octets.as_slice().try_into().unwrap()))
+    }
+
+    /// Set the hardware address of the kernel side of the device
+    pub fn set_hardware_addr(&self, mac_addr: MacAddr) -> Result<&Self> {
+        let mut request = InterfaceRequest::new(self.device.name())?;
+        unsafe {
+            request.union.mac_addr.sa_family = consts::ARPHRD_ETHER;
+            for (data, octet) in request.union.mac_addr.sa_data.iter_mut().zip(mac_addr.octets()) {
+                *data = octet as _;
+            }
+        }
+
+        let result = unsafe { ioctl(self.device.socket_fd(), SIOCSIFHWADDR, &request) };
+        if result < 0 {
+            error!("Failed to set hardware address: {}.", mac_addr);
+            return Err(std::io::Error::last_os_error().into());
+        }
+
+        Ok(self)
+    }
+}
+
+impl Deref for TapDevice {
+    type Target = TunDevice;
+
+    fn deref(&self) -> &TunDevice {
+        &self.device
+    }
+}
+
+impl Device for TapDevice {
+    fn receive(&mut self, buf: &mut [u8]) -> Result<usize> {
+        Ok(self.read(buf)?)
+    }
+
+    fn wait(&mut self, timeout: Duration) -> Result<bool> {
+        self.device.wait(timeout)
+    }
+
+    fn transmit(&mut self, frame: &[u8]) -> Result<()> {
+        Ok(self.write_all(frame)?)
+    }
+
+    fn mtu(&self) -> usize {
+        Device::mtu(&self.device)
+    }
+
+    fn capabilities(&self) -> DeviceCapabilities {
+        DeviceCapabilities {
+            medium: Medium::Ethernet,
+            offload: Offload::default(),
+        }
+    }
+}
+
+impl AsRawFd for TapDevice {
+    fn as_raw_fd(&self) -> RawFd {
+        self.device.as_raw_fd()
+    }
+}
+
+impl AsFd for TapDevice {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.device.as_fd()
+    }
+}
+
+impl Read for TapDevice {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if !self.packet_info {
+            return self.device.read(buf);
+        }
+
+        let mut frame = vec![0; consts::PACKET_INFO_LEN + buf.len().max(HEADER_LEN + MAX_PAYLOAD_LEN)];
+        let n = self.device.read(&mut frame)?;
+        if n < consts::PACKET_INFO_LEN {
+            return Err(IOError::new(ErrorKind::InvalidData, "missing packet information"));
+        }
+
+        let flags = u16::from_ne_bytes([frame[0], frame[1]]);
+        let len = n - consts::PACKET_INFO_LEN;
+        if flags & consts::TUN_PKT_STRIP != 0 || len > buf.len() {
+            return Err(IOError::new(ErrorKind::InvalidData, "frame truncated"));
+        }
+        buf[..len].copy_from_slice(&frame[consts::PACKET_INFO_LEN..n]);
+        Ok(len)
+    }
+}
+
+impl Write for TapDevice {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if !self.packet_info {
+            return self.device.write(buf);
+        }
+
+        // The protocol of the packet information is the EtherType of the frame.
+        let protocol = buf.get(12..HEADER_LEN).unwrap_or(&[0, 0]);
+        let mut frame = Vec::with_capacity(consts::PACKET_INFO_LEN + buf.len());
+        frame.extend_from_slice(&[0, 0]);
+        frame.extend_from_slice(protocol);
+        frame.extend_from_slice(buf);
+
+        let n = self.device.write(&frame)?;
+        Ok(n.saturating_sub(consts::PACKET_INFO_LEN))
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
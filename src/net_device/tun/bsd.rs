@@ -0,0 +1,246 @@
+use std::cell::Cell;
+use std::ffi::CString;
+use std::io::{Error as IOError, ErrorKind, IoSlice, IoSliceMut};
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::os::unix::io::RawFd;
+
+use libc::{c_int, ioctl, iovec, open, readv, writev, AF_INET, AF_INET6, O_RDWR};
+
+use crate::error::Result;
+use crate::ethernet::frame::EtherType;
+use crate::macros::diagnostics::error;
+use crate::net_device::r#if::InterfaceRequest;
+use crate::net_device::tun::TunDevice;
+
+/// The BSD ioctls, whose numbers encode the size of their argument
+pub mod consts {
+    use libc::c_ulong;
+
+    pub const SIOCSIFFLAGS: c_ulong = 0x8020_6910; // _IOW('i', 16, struct ifreq)
+    pub const SIOCIFDESTROY: c_ulong = 0x8020_6979; // _IOW('i', 121, struct ifreq)
+    #[cfg(target_os = "freebsd")]
+    pub const SIOCAIFADDR: c_ulong = 0x8044_692b; // _IOW('i', 43, struct in_aliasreq)
+    #[cfg(not(target_os = "freebsd"))]
+    pub const SIOCAIFADDR: c_ulong = 0x8040_691a; // _IOW('i', 26, struct ifaliasreq)
+    #[cfg(target_os = "freebsd")]
+    pub const SIOCGIFMTU: c_ulong = 0xc020_6933; // _IOWR('i', 51, struct ifreq)
+    #[cfg(not(target_os = "freebsd"))]
+    pub const SIOCGIFMTU: c_ulong = 0xc020_697e; // _IOWR('i', 126, struct ifreq)
+    #[cfg(target_os = "freebsd")]
+    pub const SIOCSIFMTU: c_ulong = 0x8020_6934; // _IOW('i', 52, struct ifreq)
+    #[cfg(not(target_os = "freebsd"))]
+    pub const SIOCSIFMTU: c_ulong = 0x8020_697f; // _IOW('i', 127, struct ifreq)
+    #[cfg(target_os = "freebsd")]
+    pub const TUNSIFHEAD: c_ulong = 0x8004_7460; // _IOW('t', 96, int), prefix packets with their family
+
+    pub const FAMILY_LEN: usize = 4; // The address family prefixing every packet, in network byte order
+}
+
+/// `struct sockaddr_in` of BSD, which starts with its length
+#[repr(C)]
+#[derive(Copy, Clone)]
+struct SockaddrIn {
+    len: u8,
+    family: u8,
+    port: u16,
+    addr: [u8; 4],
+    zero: [u8; 8],
+}
+
+impl SockaddrIn {
+    fn new(addr: Ipv4Addr) -> Self {
+        Self {
+            len: std::mem::size_of::<Self>() as u8,
+            family: AF_INET as u8,
+            port: 0,
+            addr: addr.octets(),
+            zero: [0; 8],
+        }
+    }
+}
+
+/// `struct in_aliasreq` of `SIOCAIFADDR`, which sets an address with its netmask
+#[repr(C)]
+struct AliasRequest {
+    name: [u8; 16],
+    addr: SockaddrIn,
+    dest_addr: SockaddrIn,
+    mask: SockaddrIn,
+    #[cfg(target_os = "freebsd")]
+    vhid: c_int,
+}
+
+/// The BSD part of a `TunDevice`
+#[derive(Debug)]
+pub(super) struct State {
+    /// The address and netmask are set together, so the one set last is kept for the other.
+    ipv4_addr: Cell<Ipv4Addr>,
+    ipv4_netmask: Cell<Ipv4Addr>,
+}
+
+impl State {
+    /// OpenBSD always prefixes packets with their family, FreeBSD does once asked to
+    pub(super) fn new(fd: RawFd) -> Result<Self> {
+        #[cfg(target_os = "freebsd")]
+        {
+            let on: c_int = 1;
+            if unsafe { ioctl(fd, consts::TUNSIFHEAD, &on) } < 0 {
+                return Err(std::io::Error::last_os_error().into());
+            }
+        }
+        #[cfg(not(target_os = "freebsd"))]
+        let _ = fd;
+
+        Ok(Self {
+            ipv4_addr: Cell::new(Ipv4Addr::UNSPECIFIED),
+            ipv4_netmask: Cell::new(Ipv4Addr::BROADCAST),
+        })
+    }
+}
+
+impl TunDevice {
+    /// Open the tun device `name`, which is `tun` followed by its unit number such as `tun0`
+    pub fn new(name: &str) -> Result<Self> {
+        let fd = unsafe { open(CString::new(format!("/dev/{}", name))?.as_ptr(), O_RDWR) };
+        if fd < 0 {
+            error!("Failed to open '/dev/{}'.", name);
+            return Err(std::io::Error::last_os_error().into());
+        }
+
+        Self::from_fd(fd, name.to_string())
+    }
+
+    /// Persist current tun device.
+    /// FreeBSD keeps tun interfaces once closed, OpenBSD only keeps the ones made by `ifconfig tunN create`.
+    pub fn persist(&self) -> Result<&Self> {
+        if cfg!(target_os = "freebsd") {
+            Ok(self)
+        } else {
+            Err(IOError::from(ErrorKind::Unsupported).into())
+        }
+    }
+
+    /// Delete current tun device
+    pub fn delete(&self) -> Result<&Self> {
+        let request = InterfaceRequest::new(&self.name)?;
+        let result = unsafe { ioctl(self.socket_fd, consts::SIOCIFDESTROY, &request) };
+        if result < 0 {
+            return Err(std::io::Error::last_os_error().into());
+        }
+        Ok(self)
+    }
+
+    /// Set ipv4 address
+    pub(super) fn ipv4_address(&self, ipv4_addr: Ipv4Addr) -> Result<&Self> {
+        self.state.ipv4_addr.set(ipv4_addr);
+        self.set_ipv4_alias()
+    }
+
+    /// Set ipv4 netmask
+    pub(super) fn ipv4_netmask(&self, netmask: Ipv4Addr) -> Result<&Self> {
+        self.state.ipv4_netmask.set(netmask);
+        self.set_ipv4_alias()
+    }
+
+    /// Ipv6 addresses and netmasks are not supported on the BSDs yet
+    pub(super) fn ipv6_address(&self, _ipv6_addr: Ipv6Addr) -> Result<&Self> {
+        Err(IOError::from(ErrorKind::Unsupported).into())
+    }
+
+    pub(super) fn ipv6_netmask(&self, _netmask: Ipv6Addr) -> Result<&Self> {
+        Err(IOError::from(ErrorKind::Unsupported).into())
+    }
+
+    /// Add the ipv4 address with its netmask, a tun interface is point-to-point so the address is its peer too
+    fn set_ipv4_alias(&self) -> Result<&Self> {
+        let request = InterfaceRequest::new(&self.name)?;
+        let addr = SockaddrIn::new(self.state.ipv4_addr.get());
+        let alias = AliasRequest {
+            name: unsafe { request.name.name },
+            addr,
+            dest_addr: addr,
+            mask: SockaddrIn::new(self.state.ipv4_netmask.get()),
+            #[cfg(target_os = "freebsd")]
+            vhid: 0,
+        };
+
+        let result = unsafe { ioctl(self.socket_fd, consts::SIOCAIFADDR, &alias) };
+        if result < 0 {
+            error!(
+                "Failed to set ipv4 address: {}/{}.",
+                self.state.ipv4_addr.get(),
+                self.state.ipv4_netmask.get()
+            );
+            return Err(std::io::Error::last_os_error().into());
+        }
+
+        Ok(self)
+    }
+
+    /// Read a packet without waiting for the read timeout
+    pub(super) fn read_packet(&self, buf: &mut [u8]) -> std::io::Result<(usize, Option<EtherType>)> {
+        self.readv_packet(&mut [IoSliceMut::new(buf)])
+    }
+
+    /// Read a packet scattered over `bufs` without waiting for the read timeout, reading its family prefix apart
+    pub(super) fn readv_packet(&self, bufs: &mut [IoSliceMut<'_>]) -> std::io::Result<(usize, Option<EtherType>)> {
+        let mut family = [0u8; consts::FAMILY_LEN];
+        let mut iovecs = vec![iovec {
+            iov_base: family.as_mut_ptr().cast(),
+            iov_len: family.len(),
+        }];
+        iovecs.extend(bufs.iter_mut().map(|buf| iovec {
+            iov_base: buf.as_mut_ptr().cast(),
+            iov_len: buf.len(),
+        }));
+
+        let n = unsafe { readv(self.fd, iovecs.as_ptr(), iovecs.len() as c_int) };
+        if n < 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        let len = (n as usize)
+            .checked_sub(consts::FAMILY_LEN)
+            .ok_or_else(|| IOError::new(ErrorKind::InvalidData, "missing address family"))?;
+
+        let protocol = match u32::from_be_bytes(family) as c_int {
+            AF_INET => Some(EtherType::Ipv4),
+            AF_INET6 => Some(EtherType::Ipv6),
+            _ => None,
+        };
+        Ok((len, protocol))
+    }
+
+    /// Write a packet without waiting for the write timeout, prefixed with its family
+    pub(super) fn write_packet(&self, buf: &[u8]) -> std::io::Result<usize> {
+        self.writev_packet(&[IoSlice::new(buf)])
+    }
+
+    /// Write a packet gathered from `bufs` without waiting for the write timeout, with a single writev(2)
+    /// also writing the family prefix
+    pub(super) fn writev_packet(&self, bufs: &[IoSlice<'_>]) -> std::io::Result<usize> {
+        let first = bufs.iter().find(|buf| !buf.is_empty()).map(|buf| buf[0]);
+        let family = (family(first) as u32).to_be_bytes();
+        let mut iovecs = vec![iovec {
+            iov_base: family.as_ptr() as *mut _,
+            iov_len: family.len(),
+        }];
+        iovecs.extend(bufs.iter().map(|buf| iovec {
+            iov_base: buf.as_ptr() as *mut _,
+            iov_len: buf.len(),
+        }));
+
+        let n = unsafe { writev(self.fd, iovecs.as_ptr(), iovecs.len() as c_int) };
+        if n < 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        Ok((n as usize).saturating_sub(consts::FAMILY_LEN))
+    }
+}
+
+/// The address family prefixing a packet starting with `first_octet`
+fn family(first_octet: Option<u8>) -> c_int {
+    match first_octet.map(|octet| octet >> 4) {
+        Some(6) => AF_INET6,
+        _ => AF_INET,
+    }
+}
@@ -0,0 +1,508 @@
+use std::cell::Cell;
+use std::convert::TryFrom;
+use std::ffi::{CStr, CString};
+use std::io::{Error as IOError, ErrorKind, IoSlice, IoSliceMut};
+use std::mem::transmute;
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::os::unix::io::{AsRawFd, IntoRawFd, OwnedFd, RawFd};
+
+use libc::{
+    c_int, c_short, c_ulong, close, gid_t, in6_addr, in6_ifreq, in_addr, ioctl, iovec, open, read, readv, sockaddr,
+    sockaddr_in, socket, uid_t, write, writev, AF_INET, AF_INET6, IFF_NO_PI, IFF_TUN, IFF_UP, O_RDWR, SIOCDIFADDR,
+    SIOCGIFFLAGS, SIOCGIFINDEX, SIOCGIFTXQLEN, SIOCSIFADDR, SIOCSIFNAME, SIOCSIFNETMASK, SIOCSIFTXQLEN, SOCK_DGRAM,
+};
+
+use crate::error::Result;
+use crate::ethernet::frame::EtherType;
+use crate::macros::diagnostics::error;
+use crate::net_device::error::Error;
+use crate::net_device::r#if::{consts, InterfaceRequest};
+use crate::net_device::tun::TunDevice;
+
+/// The Linux part of a `TunDevice`
+#[derive(Debug)]
+pub(super) struct State {
+    /// Whether packets are prefixed with the `tun_pi` header of the kernel, only for tun devices,
+    /// as `TapDevice` handles the header of its frames itself.
+    packet_info: bool,
+    /// The ipv6 address and prefix length are set together, so the one set last is kept for the other.
+    ipv6_addr: Cell<Option<Ipv6Addr>>,
+    ipv6_prefix_len: Cell<u8>,
+}
+
+impl State {
+    pub(super) fn new(_fd: RawFd) -> Result<Self> {
+        Ok(Self {
+            packet_info: false,
+            ipv6_addr: Cell::new(None),
+            ipv6_prefix_len: Cell::new(consts::IPV6_PREFIX_LEN),
+        })
+    }
+}
+
+impl TunDevice {
+    /// Create a new tun device, or connect to a tun device that already exists
+    pub fn new(name: &str) -> Result<Self> {
+        Self::open(name, (IFF_TUN | IFF_NO_PI) as c_short)
+    }
+
+    /// Create a new tun device reading and writing packets prefixed with the `tun_pi` header of the kernel,
+    /// which tells the protocol of a packet and reports packets truncated by a too small read buffer
+    pub fn with_packet_info(name: &str) -> Result<Self> {
+        let mut device = Self::open(name, IFF_TUN as c_short)?;
+        device.state.packet_info = true;
+        Ok(device)
+    }
+
+    /// Open the device `name` with the `IFF_*` mode flags of `TUNSETIFF`
+    pub(crate) fn open(name: &str, flags: c_short) -> Result<Self> {
+        let mut request = InterfaceRequest::new(name)?;
+        request.union.flags = flags;
+
+        let fd = unsafe { open(CString::new("/dev/net/tun")?.as_ptr(), O_RDWR) };
+        if fd < 0 {
+            error!("Failed to open '/dev/net/tun'.");
+            return Err(std::io::Error::last_os_error().into());
+        }
+
+        let result = unsafe { ioctl(fd, consts::TUNSETIFF, &request) };
+        if result < 0 {
+            if unsafe { close(fd) } < 0 {
+                error!("Failed to close TunDevice file descriptor.");
+            }
+            return Err(std::io::Error::last_os_error().into());
+        }
+
+        Self::from_fd(fd, interface_name(&request))
+    }
+
+    pub fn packet_info(&self) -> bool {
+        self.state.packet_info
+    }
+
+    pub(crate) fn socket_fd(&self) -> RawFd {
+        self.socket_fd
+    }
+
+    /// Persist current tun device
+    pub fn persist(&self) -> Result<&Self> {
+        let result = unsafe { ioctl(self.fd, consts::TUNSETPERSIST, 1) };
+        if result < 0 {
+            return Err(std::io::Error::last_os_error().into());
+        }
+        Ok(self)
+    }
+
+    /// Rename current tun device. The kernel only renames interfaces that are down,
+    /// so an interface that is up is brought down first and up again once renamed
+    pub fn rename(&mut self, new_name: &str) -> Result<&Self> {
+        let mut request = InterfaceRequest::new(&self.name)?;
+        let result = unsafe { ioctl(self.socket_fd, SIOCGIFFLAGS, &mut request) };
+        if result < 0 {
+            error!("Failed to get flags.");
+            return Err(std::io::Error::last_os_error().into());
+        }
+        let flags = unsafe { request.union.flags };
+        let up = flags & IFF_UP as c_short != 0;
+        if up {
+            self.flags(flags & !(IFF_UP as c_short))?;
+        }
+
+        let mut request = InterfaceRequest::new(&self.name)?;
+        request.union.new_name = unsafe { InterfaceRequest::new(new_name)?.name.name };
+        let result = unsafe { ioctl(self.socket_fd, SIOCSIFNAME, &request) };
+        if result < 0 {
+            error!("Failed to rename '{}' to '{}'.", self.name, new_name);
+            let err = Err(std::io::Error::last_os_error().into());
+            if up {
+                self.flags(flags)?;
+            }
+            return err;
+        }
+
+        self.name = new_name.to_string();
+        if up {
+            self.flags(flags)?;
+        }
+        Ok(self)
+    }
+
+    /// Set the user owning current tun device, who may then open it without privileges
+    pub fn owner(&self, uid: uid_t) -> Result<&Self> {
+        let result = unsafe { ioctl(self.fd, consts::TUNSETOWNER, uid as c_ulong) };
+        if result < 0 {
+            error!("Failed to set owner: {}.", uid);
+            return Err(std::io::Error::last_os_error().into());
+        }
+        Ok(self)
+    }
+
+    /// Set the group owning current tun device, whose members may then open it without privileges
+    pub fn group(&self, gid: gid_t) -> Result<&Self> {
+        let result = unsafe { ioctl(self.fd, consts::TUNSETGROUP, gid as c_ulong) };
+        if result < 0 {
+            error!("Failed to set group: {}.", gid);
+            return Err(std::io::Error::last_os_error().into());
+        }
+        Ok(self)
+    }
+
+    /// Delete current tun device
+    pub fn delete(&self) -> Result<&Self> {
+        let result = unsafe { ioctl(self.fd, consts::TUNSETPERSIST, 0) };
+        if result < 0 {
+            return Err(std::io::Error::last_os_error().into());
+        }
+        Ok(self)
+    }
+
+    /// Set ipv4 address
+    pub(super) fn ipv4_address(&self, ipv4_addr: Ipv4Addr) -> Result<&Self> {
+        let mut request = InterfaceRequest::new(&self.name)?;
+        request.union.addr = unsafe {
+            transmute::<sockaddr_in, sockaddr>(sockaddr_in {
+                sin_family: AF_INET as u16,
+                sin_port: 0,
+                sin_addr: in_addr {
+                    s_addr: u32::from(ipv4_addr).to_be(),
+                },
+                sin_zero: [0; 8],
+            })
+        };
+
+        let result = unsafe { ioctl(self.socket_fd, SIOCSIFADDR, &request) };
+        if result < 0 {
+            error!("Failed to set ipv4 address: {}.", ipv4_addr);
+            return Err(std::io::Error::last_os_error().into());
+        }
+
+        Ok(self)
+    }
+
+    /// Set ipv6 address, with the prefix length of the netmask set so far or the default one.
+    /// The address set before is removed, an interface may have several ipv6 addresses but the device manages one.
+    pub(super) fn ipv6_address(&self, ipv6_addr: Ipv6Addr) -> Result<&Self> {
+        if let Some(old_addr) = self.state.ipv6_addr.get() {
+            self.ipv6_request(SIOCDIFADDR, old_addr)?;
+            self.state.ipv6_addr.set(None);
+        }
+
+        self.ipv6_request(SIOCSIFADDR, ipv6_addr).inspect_err(|_| {
+            error!(
+                "Failed to set ipv6 address: {}/{}.",
+                ipv6_addr,
+                self.state.ipv6_prefix_len.get()
+            );
+        })?;
+        self.state.ipv6_addr.set(Some(ipv6_addr));
+        Ok(self)
+    }
+
+    /// Add or remove `ipv6_addr` with the prefix length kept, through an ipv6 socket
+    fn ipv6_request(&self, request: c_ulong, ipv6_addr: Ipv6Addr) -> Result<()> {
+        let mut index_request = InterfaceRequest::new(&self.name)?;
+        if unsafe { ioctl(self.socket_fd, SIOCGIFINDEX, &mut index_request) } < 0 {
+            error!("Failed to get the index of interface '{}'.", self.name);
+            return Err(std::io::Error::last_os_error().into());
+        }
+
+        let address_request = in6_ifreq {
+            ifr6_addr: in6_addr {
+                s6_addr: ipv6_addr.octets(),
+            },
+            ifr6_prefixlen: self.state.ipv6_prefix_len.get() as u32,
+            ifr6_ifindex: unsafe { index_request.union.value },
+        };
+
+        let socket_fd = unsafe { socket(AF_INET6, SOCK_DGRAM, 0) };
+        if socket_fd < 0 {
+            error!("Failed to create an ipv6 socket.");
+            return Err(std::io::Error::last_os_error().into());
+        }
+        let result = unsafe { ioctl(socket_fd, request, &address_request) };
+        let err = std::io::Error::last_os_error();
+        if unsafe { close(socket_fd) } < 0 {
+            error!("Failed to close TunDevice ipv6 socket file descriptor.");
+        }
+
+        if result < 0 {
+            return Err(err.into());
+        }
+        Ok(())
+    }
+
+    /// Set ipv4 netmask
+    pub(super) fn ipv4_netmask(&self, netmask: Ipv4Addr) -> Result<&Self> {
+        let mut request = InterfaceRequest::new(&self.name)?;
+        request.union.netmask = unsafe {
+            transmute::<sockaddr_in, sockaddr>(sockaddr_in {
+                sin_family: AF_INET as u16,
+                sin_port: 0,
+                sin_addr: in_addr {
+                    s_addr: u32::from(netmask).to_be(),
+                },
+                sin_zero: [0; 8],
+            })
+        };
+
+        let result = unsafe { ioctl(self.socket_fd, SIOCSIFNETMASK, &request) };
+        if result < 0 {
+            error!("Failed to set ipv4 netmask: {}.", netmask);
+            return Err(std::io::Error::last_os_error().into());
+        }
+
+        Ok(self)
+    }
+
+    /// Set ipv6 netmask, which must be a contiguous prefix. The address set is added again with the new prefix length.
+    pub(super) fn ipv6_netmask(&self, netmask: Ipv6Addr) -> Result<&Self> {
+        let mask = u128::from(netmask);
+        if mask.leading_ones() != mask.count_ones() {
+            error!("Failed to set ipv6 netmask: {}.", netmask);
+            return Err(Error::InvalidNetmask.into());
+        }
+
+        self.state.ipv6_prefix_len.set(mask.leading_ones() as u8);
+        match self.state.ipv6_addr.get() {
+            Some(ipv6_addr) => self.ipv6_address(ipv6_addr),
+            None => Ok(self),
+        }
+    }
+
+    /// Get the size of the send buffer, the bytes of written packets the kernel holds before writes block
+    pub fn send_buffer(&self) -> Result<usize> {
+        let mut size: c_int = 0;
+        let result = unsafe { ioctl(self.fd, consts::TUNGETSNDBUF, &mut size) };
+        if result < 0 {
+            error!("Failed to get send buffer size.");
+            return Err(std::io::Error::last_os_error().into());
+        }
+        Ok(size as usize)
+    }
+
+    /// Set the size of the send buffer, in bytes
+    pub fn set_send_buffer(&self, size: usize) -> Result<&Self> {
+        let size = size.min(c_int::MAX as usize) as c_int;
+        let result = unsafe { ioctl(self.fd, consts::TUNSETSNDBUF, &size) };
+        if result < 0 {
+            error!("Failed to set send buffer size: {}.", size);
+            return Err(std::io::Error::last_os_error().into());
+        }
+        Ok(self)
+    }
+
+    /// Get the transmit queue length, the packets the kernel queues for the device to read before dropping them
+    pub fn tx_queue_len(&self) -> Result<usize> {
+        let mut request = InterfaceRequest::new(&self.name)?;
+
+        let result = unsafe { ioctl(self.socket_fd, SIOCGIFTXQLEN, &mut request) };
+        if result < 0 {
+            error!("Failed to get transmit queue length.");
+            return Err(std::io::Error::last_os_error().into());
+        }
+
+        Ok(unsafe { request.union.value } as usize)
+    }
+
+    /// Set the transmit queue length, raise it when the stack reads in bursts slower than the kernel sends
+    pub fn set_tx_queue_len(&self, len: usize) -> Result<&Self> {
+        let mut request = InterfaceRequest::new(&self.name)?;
+        request.union.value = len.min(c_int::MAX as usize) as c_int;
+
+        let result = unsafe { ioctl(self.socket_fd, SIOCSIFTXQLEN, &request) };
+        if result < 0 {
+            error!("Failed to set transmit queue length: {}.", len);
+            return Err(std::io::Error::last_os_error().into());
+        }
+
+        Ok(self)
+    }
+
+    /// Read a packet without waiting for the read timeout
+    pub(super) fn read_packet(&self, buf: &mut [u8]) -> std::io::Result<(usize, Option<EtherType>)> {
+        if self.state.packet_info {
+            return self.readv_packet(&mut [IoSliceMut::new(buf)]);
+        }
+
+        let n = unsafe { read(self.fd, buf.as_mut_ptr().cast(), buf.len()) };
+        if n < 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        Ok((n as usize, None))
+    }
+
+    /// Read a packet scattered over `bufs` without waiting for the read timeout, reading its packet information apart
+    pub(super) fn readv_packet(&self, bufs: &mut [IoSliceMut<'_>]) -> std::io::Result<(usize, Option<EtherType>)> {
+        let mut info = [0u8; consts::PACKET_INFO_LEN];
+        let n = if self.state.packet_info {
+            let mut iovecs = vec![iovec {
+                iov_base: info.as_mut_ptr().cast(),
+                iov_len: info.len(),
+            }];
+            iovecs.extend(bufs.iter_mut().map(|buf| iovec {
+                iov_base: buf.as_mut_ptr().cast(),
+                iov_len: buf.len(),
+            }));
+            unsafe { readv(self.fd, iovecs.as_ptr(), iovecs.len() as c_int) }
+        } else {
+            // An `IoSliceMut` has the layout of an `iovec`.
+            unsafe { readv(self.fd, bufs.as_ptr().cast::<iovec>(), bufs.len() as c_int) }
+        };
+        if n < 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        if !self.state.packet_info {
+            return Ok((n as usize, None));
+        }
+
+        let (len, protocol) = parse_packet_info(&info, n as usize)?;
+        Ok((len, Some(protocol)))
+    }
+
+    /// Write a packet without waiting for the write timeout
+    pub(super) fn write_packet(&self, buf: &[u8]) -> std::io::Result<usize> {
+        if self.state.packet_info {
+            return self.writev_packet(&[IoSlice::new(buf)]);
+        }
+
+        let n = unsafe { write(self.fd, buf.as_ptr().cast(), buf.len()) };
+        if n < 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        Ok(n as usize)
+    }
+
+    /// Write a packet gathered from `bufs`, prefixed with packet information in that mode
+    pub(super) fn writev_packet(&self, bufs: &[IoSlice<'_>]) -> std::io::Result<usize> {
+        if !self.state.packet_info {
+            // An `IoSlice` has the layout of an `iovec`.
+            let n = unsafe { writev(self.fd, bufs.as_ptr().cast::<iovec>(), bufs.len() as c_int) };
+            if n < 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+            return Ok(n as usize);
+        }
+
+        let info = packet_info(bufs.iter().find(|buf| !buf.is_empty()).map(|buf| buf[0]))?;
+        let mut iovecs = vec![iovec {
+            iov_base: info.as_ptr() as *mut _,
+            iov_len: info.len(),
+        }];
+        iovecs.extend(bufs.iter().map(|buf| iovec {
+            iov_base: buf.as_ptr() as *mut _,
+            iov_len: buf.len(),
+        }));
+
+        let n = unsafe { writev(self.fd, iovecs.as_ptr(), iovecs.len() as c_int) };
+        if n < 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        Ok((n as usize).saturating_sub(consts::PACKET_INFO_LEN))
+    }
+}
+
+/// Parse the packet information read along with a packet, `read` octets in all.
+/// Returns the length of the packet and its protocol, or an error when the kernel truncated the packet.
+fn parse_packet_info(info: &[u8; consts::PACKET_INFO_LEN], read: usize) -> std::io::Result<(usize, EtherType)> {
+    let len = read
+        .checked_sub(consts::PACKET_INFO_LEN)
+        .ok_or_else(|| IOError::new(ErrorKind::InvalidData, "missing packet information"))?;
+
+    let flags = u16::from_ne_bytes([info[0], info[1]]);
+    if flags & consts::TUN_PKT_STRIP != 0 {
+        return Err(IOError::new(ErrorKind::InvalidData, "packet truncated"));
+    }
+    Ok((len, EtherType::from(u16::from_be_bytes([info[2], info[3]]))))
+}
+
+/// The packet information to write before a packet starting with `first_octet`,
+/// whose protocol is told by the version of the packet
+fn packet_info(first_octet: Option<u8>) -> std::io::Result<[u8; consts::PACKET_INFO_LEN]> {
+    let protocol = match first_octet.map(|octet| octet >> 4) {
+        Some(4) => EtherType::Ipv4,
+        Some(6) => EtherType::Ipv6,
+        _ => return Err(IOError::new(ErrorKind::InvalidInput, "not an IP packet")),
+    };
+    let [high, low] = u16::from(protocol).to_be_bytes();
+    Ok([0, 0, high, low])
+}
+
+/// The name of the interface a `TUNSETIFF` or `TUNGETIFF` request is about
+fn interface_name(request: &InterfaceRequest) -> String {
+    unsafe { CStr::from_ptr(request.name.name.as_ptr().cast()) }
+        .to_string_lossy()
+        .into_owned()
+}
+
+impl TryFrom<OwnedFd> for TunDevice {
+    type Error = crate::error::Error;
+
+    /// Take over an open tun device, asking the kernel which interface it is attached to
+    /// and whether it reads packet information. A tap device is refused.
+    fn try_from(fd: OwnedFd) -> Result<Self> {
+        let mut request = InterfaceRequest::new("")?;
+        if unsafe { ioctl(fd.as_raw_fd(), consts::TUNGETIFF, &mut request) } < 0 {
+            error!("Failed to get the interface of a tun device file descriptor.");
+            return Err(std::io::Error::last_os_error().into());
+        }
+
+        let flags = unsafe { request.union.flags } as c_int;
+        if flags & IFF_TUN == 0 {
+            error!(
+                "Failed to take over a tun device file descriptor with flags: {:#x}.",
+                flags
+            );
+            return Err(IOError::new(ErrorKind::InvalidInput, "not a tun device").into());
+        }
+
+        let mut device = Self::from_fd(fd.into_raw_fd(), interface_name(&request))?;
+        device.state.packet_info = flags & IFF_NO_PI == 0;
+        Ok(device)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::ErrorKind;
+
+    use super::{packet_info, parse_packet_info};
+    use crate::ethernet::frame::EtherType;
+    use crate::net_device::r#if::consts;
+
+    #[test]
+    fn parse() {
+        assert_eq!(
+            parse_packet_info(&[0, 0, 0x08, 0x00], 24).unwrap(),
+            (20, EtherType::Ipv4)
+        );
+        assert_eq!(
+            parse_packet_info(&[0, 0, 0x86, 0xdd], 44).unwrap(),
+            (40, EtherType::Ipv6)
+        );
+        assert_eq!(
+            parse_packet_info(&[0, 0, 0x12, 0x34], 4).unwrap(),
+            (0, EtherType::Unknown(0x1234))
+        );
+
+        let [high, low] = consts::TUN_PKT_STRIP.to_ne_bytes();
+        let err = parse_packet_info(&[high, low, 0x08, 0x00], 24).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+
+        let err = parse_packet_info(&[0; 4], 3).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn build() {
+        assert_eq!(packet_info(Some(0x45)).unwrap(), [0, 0, 0x08, 0x00]);
+        assert_eq!(packet_info(Some(0x60)).unwrap(), [0, 0, 0x86, 0xdd]);
+        assert_eq!(packet_info(Some(0x50)).unwrap_err().kind(), ErrorKind::InvalidInput);
+        assert_eq!(packet_info(None).unwrap_err().kind(), ErrorKind::InvalidInput);
+
+        // What is written is read back.
+        for (first_octet, protocol) in [(0x45, EtherType::Ipv4), (0x60, EtherType::Ipv6)] {
+            let info = packet_info(Some(first_octet)).unwrap();
+            assert_eq!(parse_packet_info(&info, 24).unwrap(), (20, protocol));
+        }
+    }
+}
@@ -0,0 +1,305 @@
+use std::cell::Cell;
+use std::io::{IoSlice, IoSliceMut, Read, Write};
+use std::net::IpAddr;
+use std::os::unix::io::{AsFd, AsRawFd, BorrowedFd, RawFd};
+use std::time::Duration;
+
+use libc::{
+    c_int, c_short, close, fcntl, ioctl, socket, AF_INET, F_GETFL, F_SETFL, O_NONBLOCK, POLLIN, POLLOUT, SOCK_DGRAM,
+};
+#[cfg(target_os = "linux")]
+use libc::{SIOCGIFMTU, SIOCSIFFLAGS, SIOCSIFMTU};
+
+use crate::capabilities::Offload;
+use crate::error::Result;
+use crate::ethernet::frame::EtherType;
+use crate::macros::diagnostics::error;
+use crate::net_device::device::{
+    consts as device_consts, wait_fd, wait_readable, Buffer, Device, DeviceCapabilities, Medium,
+};
+use crate::net_device::r#if::InterfaceRequest;
+
+#[cfg(any(target_os = "freebsd", target_os = "openbsd"))]
+mod bsd;
+#[cfg(target_os = "linux")]
+mod linux;
+
+#[cfg(any(target_os = "freebsd", target_os = "openbsd"))]
+use self::bsd::consts::{SIOCGIFMTU, SIOCSIFFLAGS, SIOCSIFMTU};
+#[cfg(any(target_os = "freebsd", target_os = "openbsd"))]
+use self::bsd::State;
+#[cfg(target_os = "linux")]
+use self::linux::State;
+
+#[cfg(any(target_os = "freebsd", target_os = "openbsd"))]
+pub use self::bsd::consts;
+
+/// A tun device, `/dev/net/tun` on Linux and `/dev/tunN` on FreeBSD and OpenBSD.
+///
+/// The ioctls and the framing of packets are up to the platform modules, the BSDs prefix packets
+/// with their address family and the device adds and strips it, so it reads and writes bare IP packets everywhere.
+#[derive(Debug)]
+pub struct TunDevice {
+    fd: RawFd,
+    name: String,
+    socket_fd: RawFd,
+    /// The MTU of the interface, read when the device is opened and updated by `set_mtu`.
+    mtu: usize,
+    read_timeout: Option<Duration>,
+    write_timeout: Option<Duration>,
+    cleanup_on_drop: Cell<bool>,
+    state: State,
+}
+
+impl TunDevice {
+    /// Take over the tun device `fd` attached to the interface `name`, such as one passed by a privileged parent.
+    ///
+    /// # Safety
+    ///
+    /// `fd` must be an open tun device owned by nobody else, the device closes it once dropped.
+    pub unsafe fn from_raw_fd(fd: RawFd, name: &str) -> Result<Self> {
+        Self::from_fd(fd, name.to_string())
+    }
+
+    /// Make a device of the open descriptor `fd`, which is closed if it fails
+    fn from_fd(fd: RawFd, name: String) -> Result<Self> {
+        let opened = State::new(fd).and_then(|state| {
+            let socket_fd = unsafe { socket(AF_INET, SOCK_DGRAM, 0) };
+            if socket_fd < 0 {
+                error!("Failed to create a socket.");
+                return Err(std::io::Error::last_os_error().into());
+            }
+            Ok((state, socket_fd))
+        });
+        let (state, socket_fd) = match opened {
+            Ok(opened) => opened,
+            Err(err) => {
+                if unsafe { close(fd) } < 0 {
+                    error!("Failed to close TunDevice file descriptor.");
+                }
+                return Err(err);
+            }
+        };
+
+        let mut device = Self {
+            fd,
+            name,
+            socket_fd,
+            mtu: device_consts::DEFAULT_MTU,
+            read_timeout: None,
+            write_timeout: None,
+            cleanup_on_drop: Cell::new(false),
+            state,
+        };
+        device.mtu = device.mtu()?;
+        Ok(device)
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Set the active flag word of current tun device
+    pub fn flags(&self, flags: c_short) -> Result<&Self> {
+        let mut request = InterfaceRequest::new(&self.name)?;
+        request.union.flags = flags;
+
+        let result = unsafe { ioctl(self.socket_fd, SIOCSIFFLAGS, &request) };
+        if result < 0 {
+            error!("Failed to set flags: {}.", flags);
+            return Err(std::io::Error::last_os_error().into());
+        }
+
+        Ok(self)
+    }
+
+    /// Delete the interface when the device is dropped, even if it was persisted,
+    /// so tests and short-lived tools leave no stale interfaces behind
+    pub fn cleanup_on_drop(&self, cleanup: bool) -> &Self {
+        self.cleanup_on_drop.set(cleanup);
+        self
+    }
+
+    /// In non-blocking mode reads and writes fail with `WouldBlock` instead of waiting for the device
+    pub fn set_nonblocking(&self, nonblocking: bool) -> Result<&Self> {
+        let flags = unsafe { fcntl(self.fd, F_GETFL) };
+        if flags < 0 {
+            return Err(std::io::Error::last_os_error().into());
+        }
+
+        let flags = if nonblocking {
+            flags | O_NONBLOCK
+        } else {
+            flags & !O_NONBLOCK
+        };
+        if unsafe { fcntl(self.fd, F_SETFL, flags) } < 0 {
+            error!("Failed to set non-blocking mode: {}.", nonblocking);
+            return Err(std::io::Error::last_os_error().into());
+        }
+
+        Ok(self)
+    }
+
+    /// Make reads fail with `TimedOut` when no packet arrived within `read_timeout`
+    pub fn set_read_timeout(&mut self, read_timeout: Option<Duration>) {
+        self.read_timeout = read_timeout;
+    }
+
+    /// Make writes fail with `TimedOut` when the device did not take the packet within `write_timeout`
+    pub fn set_write_timeout(&mut self, write_timeout: Option<Duration>) {
+        self.write_timeout = write_timeout;
+    }
+
+    /// Set ip address
+    pub fn address(&self, ip_addr: IpAddr) -> Result<&Self> {
+        match ip_addr {
+            IpAddr::V4(v4) => self.ipv4_address(v4),
+            IpAddr::V6(v6) => self.ipv6_address(v6),
+        }
+    }
+
+    /// Set netmask
+    pub fn netmask(&self, netmask: IpAddr) -> Result<&Self> {
+        match netmask {
+            IpAddr::V4(v4) => self.ipv4_netmask(v4),
+            IpAddr::V6(v6) => self.ipv6_netmask(v6),
+        }
+    }
+
+    /// Get the MTU of current tun device
+    pub fn mtu(&self) -> Result<usize> {
+        let mut request = InterfaceRequest::new(&self.name)?;
+
+        let result = unsafe { ioctl(self.socket_fd, SIOCGIFMTU, &mut request) };
+        if result < 0 {
+            error!("Failed to get MTU.");
+            return Err(std::io::Error::last_os_error().into());
+        }
+
+        Ok(unsafe { request.union.mtu } as usize)
+    }
+
+    /// Set the MTU of current tun device, which the interface sizes its buffers and fragments by
+    pub fn set_mtu(&mut self, mtu: usize) -> Result<&Self> {
+        let mut request = InterfaceRequest::new(&self.name)?;
+        request.union.mtu = mtu as c_int;
+
+        let result = unsafe { ioctl(self.socket_fd, SIOCSIFMTU, &request) };
+        if result < 0 {
+            error!("Failed to set MTU: {}.", mtu);
+            return Err(std::io::Error::last_os_error().into());
+        }
+
+        self.mtu = mtu;
+        Ok(self)
+    }
+
+    /// Read a packet with its protocol, which Linux only tells in packet information mode
+    pub fn read_with_protocol(&mut self, buf: &mut [u8]) -> std::io::Result<(usize, Option<EtherType>)> {
+        wait_fd(self.fd, POLLIN, self.read_timeout)?;
+        self.read_packet(buf)
+    }
+}
+
+impl Device for TunDevice {
+    fn receive(&mut self, buf: &mut [u8]) -> Result<usize> {
+        Ok(self.read(buf)?)
+    }
+
+    fn recv_batch(&mut self, bufs: &mut [Buffer]) -> Result<usize> {
+        let mut received = 0;
+        for buf in bufs.iter_mut() {
+            // Only the first packet is waited for, a failure after it ends the batch.
+            let (len, protocol) = if received == 0 {
+                self.read_with_protocol(buf.as_mut_slice())?
+            } else {
+                match wait_fd(self.fd, POLLIN, Some(Duration::ZERO)).and_then(|_| self.read_packet(buf.as_mut_slice()))
+                {
+                    Ok(packet) => packet,
+                    Err(_) => break,
+                }
+            };
+
+            buf.set_len(len);
+            buf.set_protocol(protocol);
+            received += 1;
+        }
+        Ok(received)
+    }
+
+    fn wait(&mut self, timeout: Duration) -> Result<bool> {
+        wait_readable(self.fd, timeout)
+    }
+
+    fn transmit(&mut self, packet: &[u8]) -> Result<()> {
+        Ok(self.write_all(packet)?)
+    }
+
+    fn mtu(&self) -> usize {
+        self.mtu
+    }
+
+    fn capabilities(&self) -> DeviceCapabilities {
+        DeviceCapabilities {
+            medium: Medium::Ip,
+            offload: Offload::default(),
+        }
+    }
+}
+
+impl Read for TunDevice {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        wait_fd(self.fd, POLLIN, self.read_timeout)?;
+        Ok(self.read_packet(buf)?.0)
+    }
+
+    /// Read a packet scattered over `bufs`, with a single readv(2).
+    fn read_vectored(&mut self, bufs: &mut [IoSliceMut<'_>]) -> std::io::Result<usize> {
+        wait_fd(self.fd, POLLIN, self.read_timeout)?;
+        Ok(self.readv_packet(bufs)?.0)
+    }
+}
+
+impl Write for TunDevice {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        wait_fd(self.fd, POLLOUT, self.write_timeout)?;
+        self.write_packet(buf)
+    }
+
+    /// Write a packet gathered from `bufs`, with a single writev(2).
+    fn write_vectored(&mut self, bufs: &[IoSlice<'_>]) -> std::io::Result<usize> {
+        wait_fd(self.fd, POLLOUT, self.write_timeout)?;
+        self.writev_packet(bufs)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl AsRawFd for TunDevice {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd
+    }
+}
+
+impl AsFd for TunDevice {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        // The descriptor stays open as long as the device.
+        unsafe { BorrowedFd::borrow_raw(self.fd) }
+    }
+}
+
+impl Drop for TunDevice {
+    fn drop(&mut self) {
+        if self.cleanup_on_drop.get() && self.delete().is_err() {
+            error!("Failed to delete tun device '{}'.", self.name);
+        }
+        if unsafe { close(self.fd) } < 0 {
+            error!("Failed to close TunDevice file descriptor.");
+        }
+        if unsafe { close(self.socket_fd) } < 0 {
+            error!("Failed to close TunDevice socket file descriptor.");
+        }
+    }
+}
@@ -0,0 +1,144 @@
+use libc::{c_short, IFF_UP, IFNAMSIZ};
+
+use crate::error::Result;
+use crate::net_device::cidr::Cidr;
+use crate::net_device::error::Error;
+use crate::net_device::tun::TunDevice;
+
+pub mod consts {
+    pub const MIN_MTU: usize = 68; // RFC 791, every IPv4 host must forward a datagram of 68 octets
+    pub const MAX_MTU: usize = 65535; // The largest IPv4 datagram
+}
+
+/// Configure a tun device in one go, instead of calling `new`, `address`, `netmask`, `flags` and `persist` in order.
+///
+/// The configuration is validated before the device is opened, and the device is only persisted
+/// once every other step succeeded, so a failed build leaves no interface behind.
+#[derive(Debug, Clone)]
+pub struct TunDeviceBuilder {
+    name: String,
+    address: Option<Cidr>,
+    mtu: Option<usize>,
+    up: bool,
+    persist: bool,
+    cleanup_on_drop: bool,
+}
+
+impl TunDeviceBuilder {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            address: None,
+            mtu: None,
+            up: false,
+            persist: false,
+            cleanup_on_drop: false,
+        }
+    }
+
+    /// Set the address and netmask of the interface, such as `"192.168.233.233/24".parse()?`
+    pub fn address(mut self, address: Cidr) -> Self {
+        self.address = Some(address);
+        self
+    }
+
+    pub fn mtu(mut self, mtu: usize) -> Self {
+        self.mtu = Some(mtu);
+        self
+    }
+
+    /// Bring the interface up
+    pub fn up(mut self) -> Self {
+        self.up = true;
+        self
+    }
+
+    /// Keep the interface once the device is closed
+    pub fn persist(mut self) -> Self {
+        self.persist = true;
+        self
+    }
+
+    /// Delete the interface when the device is dropped, see `TunDevice::cleanup_on_drop`
+    pub fn cleanup_on_drop(mut self) -> Self {
+        self.cleanup_on_drop = true;
+        self
+    }
+
+    /// Check the configuration without touching the system
+    pub fn validate(&self) -> Result<()> {
+        if self.name.len() >= IFNAMSIZ {
+            return Err(Error::NameTooLong.into());
+        }
+
+        if let Some(address) = self.address {
+            let addr = address.addr();
+            if addr.is_unspecified() || addr.is_multicast() {
+                return Err(Error::InvalidAddress.into());
+            }
+        }
+
+        if let Some(mtu) = self.mtu {
+            if !(consts::MIN_MTU..=consts::MAX_MTU).contains(&mtu) {
+                return Err(Error::InvalidMtu.into());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Validate the configuration, then open and configure the device
+    pub fn build(self) -> Result<TunDevice> {
+        self.validate()?;
+
+        let mut device = TunDevice::new(&self.name)?;
+        device.cleanup_on_drop(self.cleanup_on_drop);
+        if let Some(mtu) = self.mtu {
+            device.set_mtu(mtu)?;
+        }
+        match self.address {
+            // An ipv4 netmask is set on the address, while an ipv6 address is added with the prefix length set before.
+            Some(address) if address.addr().is_ipv4() => {
+                device.address(address.addr())?.netmask(address.netmask())?;
+            }
+            Some(address) => {
+                device.netmask(address.netmask())?.address(address.addr())?;
+            }
+            None => {}
+        }
+        if self.up {
+            device.flags(IFF_UP as c_short)?;
+        }
+        if self.persist {
+            device.persist()?;
+        }
+
+        Ok(device)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TunDeviceBuilder;
+
+    #[test]
+    fn validate() {
+        let builder = TunDeviceBuilder::new("tun-radish").address("192.168.233.233/24".parse().unwrap());
+        assert!(builder.clone().up().persist().validate().is_ok());
+        assert!(builder.clone().mtu(1280).validate().is_ok());
+        assert!(builder.clone().mtu(67).validate().is_err());
+        assert!(builder.mtu(65536).validate().is_err());
+
+        assert!(TunDeviceBuilder::new("tun-radish")
+            .address("0.0.0.0/24".parse().unwrap())
+            .validate()
+            .is_err());
+        assert!(TunDeviceBuilder::new("tun-radish")
+            .address("224.0.0.1/4".parse().unwrap())
+            .validate()
+            .is_err());
+        assert!(TunDeviceBuilder::new("tun-radish-too-long").validate().is_err());
+        // Nothing is opened when the configuration is invalid.
+        assert!(TunDeviceBuilder::new("tun-radish").mtu(0).build().is_err());
+    }
+}
@@ -0,0 +1,187 @@
+use alloc::vec;
+use alloc::vec::Vec;
+use core::time::Duration;
+
+pub mod consts {
+    use core::time::Duration;
+
+    pub const DEFAULT_KEEP_ALIVE_IDLE: Duration = Duration::from_secs(2 * 60 * 60); // RFC 1122 section 4.2.3.6
+    pub const DEFAULT_KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(75);
+    pub const DEFAULT_KEEP_ALIVE_PROBES: u32 = 9;
+}
+
+/// The keep-alive parameters of a TCP connection (RFC 1122 section 4.2.3.6).
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct KeepAlive {
+    /// How long the connection stays idle before the first probe.
+    pub idle: Duration,
+    /// The time between unanswered probes.
+    pub interval: Duration,
+    /// Unanswered probes after which the connection is dropped.
+    pub probes: u32,
+}
+
+impl Default for KeepAlive {
+    fn default() -> Self {
+        Self {
+            idle: consts::DEFAULT_KEEP_ALIVE_IDLE,
+            interval: consts::DEFAULT_KEEP_ALIVE_INTERVAL,
+            probes: consts::DEFAULT_KEEP_ALIVE_PROBES,
+        }
+    }
+}
+
+/// An option of a TCP or UDP socket.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SocketOption {
+    SendBufferSize(usize),
+    RecvBufferSize(usize),
+    /// Disable Nagle's algorithm.
+    NoDelay(bool),
+    KeepAlive(Option<KeepAlive>),
+    /// How long dropping a stream waits for the queued data to be acknowledged,
+    /// zero resets the connection instead of closing it.
+    Linger(Option<Duration>),
+    Ttl(u8),
+    Tos(u8),
+    /// Bind a port still used by connections that are not listening, such as connections in TIME-WAIT.
+    ReuseAddress(bool),
+    /// Let several listeners bind the same address, each one gets a share of the incoming connections.
+    ReusePort(bool),
+}
+
+impl SocketOption {
+    /// Whether the option belongs to the connection, rather than to the socket or listener owning it.
+    pub fn is_connection_option(&self) -> bool {
+        !matches!(
+            self,
+            SocketOption::Linger(_) | SocketOption::ReuseAddress(_) | SocketOption::ReusePort(_)
+        )
+    }
+}
+
+/// The options a socket is opened with, set with chained setters.
+///
+/// Options left unset keep the defaults of the socket.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SocketConfig {
+    send_buffer_size: Option<usize>,
+    recv_buffer_size: Option<usize>,
+    nodelay: Option<bool>,
+    keep_alive: Option<Option<KeepAlive>>,
+    linger: Option<Option<Duration>>,
+    ttl: Option<u8>,
+    tos: Option<u8>,
+    reuse_address: bool,
+    reuse_port: bool,
+}
+
+impl SocketConfig {
+    pub fn send_buffer_size(mut self, send_buffer_size: usize) -> Self {
+        self.send_buffer_size = Some(send_buffer_size);
+        self
+    }
+
+    pub fn recv_buffer_size(mut self, recv_buffer_size: usize) -> Self {
+        self.recv_buffer_size = Some(recv_buffer_size);
+        self
+    }
+
+    pub fn nodelay(mut self, nodelay: bool) -> Self {
+        self.nodelay = Some(nodelay);
+        self
+    }
+
+    pub fn keep_alive(mut self, keep_alive: Option<KeepAlive>) -> Self {
+        self.keep_alive = Some(keep_alive);
+        self
+    }
+
+    pub fn linger(mut self, linger: Option<Duration>) -> Self {
+        self.linger = Some(linger);
+        self
+    }
+
+    pub fn ttl(mut self, ttl: u8) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    pub fn tos(mut self, tos: u8) -> Self {
+        self.tos = Some(tos);
+        self
+    }
+
+    pub fn reuse_address(mut self, reuse_address: bool) -> Self {
+        self.reuse_address = reuse_address;
+        self
+    }
+
+    pub fn reuse_port(mut self, reuse_port: bool) -> Self {
+        self.reuse_port = reuse_port;
+        self
+    }
+
+    pub fn is_reuse_address(&self) -> bool {
+        self.reuse_address
+    }
+
+    pub fn is_reuse_port(&self) -> bool {
+        self.reuse_port
+    }
+
+    /// Returns the linger time set, `None` when dropping a stream closes it in the background.
+    pub fn linger_time(&self) -> Option<Duration> {
+        self.linger.flatten()
+    }
+
+    /// Returns the options that were set.
+    pub fn options(&self) -> Vec<SocketOption> {
+        let mut options = vec![];
+        options.extend(self.send_buffer_size.map(SocketOption::SendBufferSize));
+        options.extend(self.recv_buffer_size.map(SocketOption::RecvBufferSize));
+        options.extend(self.nodelay.map(SocketOption::NoDelay));
+        options.extend(self.keep_alive.map(SocketOption::KeepAlive));
+        options.extend(self.linger.map(SocketOption::Linger));
+        options.extend(self.ttl.map(SocketOption::Ttl));
+        options.extend(self.tos.map(SocketOption::Tos));
+        if self.reuse_address {
+            options.push(SocketOption::ReuseAddress(true));
+        }
+        if self.reuse_port {
+            options.push(SocketOption::ReusePort(true));
+        }
+        options
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{KeepAlive, SocketConfig, SocketOption};
+
+    #[test]
+    fn config() {
+        let config = SocketConfig::default()
+            .nodelay(true)
+            .keep_alive(Some(KeepAlive::default()))
+            .linger(Some(Duration::from_secs(5)))
+            .ttl(32)
+            .reuse_port(true);
+
+        assert_eq!(
+            config.options(),
+            vec![
+                SocketOption::NoDelay(true),
+                SocketOption::KeepAlive(Some(KeepAlive::default())),
+                SocketOption::Linger(Some(Duration::from_secs(5))),
+                SocketOption::Ttl(32),
+                SocketOption::ReusePort(true),
+            ]
+        );
+        assert_eq!(config.linger_time(), Some(Duration::from_secs(5)));
+        assert!(!config.is_reuse_address());
+        assert!(SocketConfig::default().options().is_empty());
+    }
+}
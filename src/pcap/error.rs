@@ -0,0 +1,26 @@
+use std::fmt::{Display, Formatter};
+
+#[derive(Debug)]
+pub enum Error {
+    InvalidMagic,
+    UnsupportedVersion,
+    InvalidBlockLen,
+    InvalidTimestampResolution,
+    MissingInterface,
+    UnknownInterface,
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::InvalidMagic => write!(f, "invalid magic number"),
+            Error::UnsupportedVersion => write!(f, "unsupported version"),
+            Error::InvalidBlockLen => write!(f, "invalid block length"),
+            Error::InvalidTimestampResolution => write!(f, "invalid timestamp resolution"),
+            Error::MissingInterface => write!(f, "missing interface description"),
+            Error::UnknownInterface => write!(f, "unknown interface"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
@@ -0,0 +1,85 @@
+//! Reading and writing capture files, in the pcap format or in pcapng, independent of any device.
+//!
+//! Records carry a timestamp since the UNIX epoch, the link type of their data,
+//! and their length before being truncated to the snapshot length.
+
+use std::time::Duration;
+
+use crate::c_like_enum;
+use crate::net_device::device::Medium;
+
+pub mod error;
+pub mod reader;
+pub mod writer;
+
+pub use crate::pcap::reader::Reader;
+pub use crate::pcap::writer::Writer;
+
+pub mod consts {
+    pub const PCAP_MAGIC_MICROS: u32 = 0xa1b2c3d4; // Timestamps in microseconds
+    pub const PCAP_MAGIC_NANOS: u32 = 0xa1b23c4d; // Timestamps in nanoseconds
+    pub const PCAP_VERSION_MAJOR: u16 = 2;
+    pub const PCAP_VERSION_MINOR: u16 = 4;
+    pub const PCAP_HEADER_LEN: usize = 24;
+    pub const PCAP_RECORD_HEADER_LEN: usize = 16;
+
+    pub const PCAPNG_BYTE_ORDER_MAGIC: u32 = 0x1a2b3c4d;
+    pub const PCAPNG_VERSION_MAJOR: u16 = 1;
+    pub const PCAPNG_VERSION_MINOR: u16 = 0;
+    pub const PCAPNG_MIN_BLOCK_LEN: usize = 12; // Type and length, then the length again
+    pub const PCAPNG_MAX_BLOCK_LEN: usize = 16 * 1024 * 1024; // Refused beyond, as Wireshark does
+
+    pub const DEFAULT_SNAPLEN: u32 = 262144; // The one of tcpdump
+    pub const DEFAULT_TIMESTAMP_UNITS: u64 = 1_000_000; // Microseconds, without an if_tsresol option
+
+    pub mod block_type {
+        pub const SECTION_HEADER: u32 = 0x0a0d0d0a;
+        pub const INTERFACE_DESCRIPTION: u32 = 0x00000001;
+        pub const SIMPLE_PACKET: u32 = 0x00000003;
+        pub const ENHANCED_PACKET: u32 = 0x00000006;
+    }
+
+    pub mod option_code {
+        pub const END_OF_OPTIONS: u16 = 0;
+        pub const IF_TSRESOL: u16 = 9;
+    }
+}
+
+c_like_enum!(
+    /// The link-layer header type of the records (tcpdump.org LINKTYPE_ values)
+    #[derive(Debug, Copy, Clone, PartialEq, Eq)]
+    pub enum LinkType(u32) {
+        Null = 0,
+        Ethernet = 1,
+        Raw = 101, // An IPv4 or IPv6 packet, told apart by its version
+        LinuxSll = 113,
+        Ipv4 = 228,
+        Ipv6 = 229,
+    }
+);
+
+impl From<Medium> for LinkType {
+    fn from(medium: Medium) -> Self {
+        match medium {
+            Medium::Ip => LinkType::Raw,
+            Medium::Ethernet => LinkType::Ethernet,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Format {
+    Pcap,
+    Pcapng,
+}
+
+/// A captured packet.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Record {
+    /// Since the UNIX epoch
+    pub timestamp: Duration,
+    pub link_type: LinkType,
+    /// The length of the packet on the wire, `data` being truncated to the snapshot length
+    pub original_len: usize,
+    pub data: Vec<u8>,
+}
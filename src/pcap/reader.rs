@@ -0,0 +1,380 @@
+use std::io::{ErrorKind, Read};
+use std::time::Duration;
+
+use crate::error::Result;
+use crate::pcap::consts::{self, block_type, option_code};
+use crate::pcap::error::Error;
+use crate::pcap::{Format, LinkType, Record};
+
+/// An interface described in a pcapng section.
+#[derive(Debug, Copy, Clone)]
+struct Interface {
+    link_type: LinkType,
+    snaplen: u32,
+    /// Timestamp ticks per second
+    units: u64,
+}
+
+/// Reads the records of a capture file, in the pcap format or in pcapng, in either byte order.
+pub struct Reader<R: Read> {
+    inner: R,
+    format: Format,
+    big_endian: bool,
+    /// The single interface of a pcap file, those of the current section of a pcapng one
+    interfaces: Vec<Interface>,
+}
+
+impl<R: Read> Reader<R> {
+    /// Read the file header from `inner`, up to the first interface description of a pcapng file.
+    pub fn new(mut inner: R) -> Result<Self> {
+        let mut magic = [0; 4];
+        inner.read_exact(&mut magic)?;
+
+        let mut reader = Reader {
+            inner,
+            format: Format::Pcap,
+            big_endian: false,
+            interfaces: vec![],
+        };
+
+        if u32::from_ne_bytes(magic) == block_type::SECTION_HEADER {
+            reader.format = Format::Pcapng;
+            reader.read_section_header()?;
+            while reader.interfaces.is_empty() {
+                let mut block_type = [0; 4];
+                if !reader.read_or_eof(&mut block_type)? {
+                    return Err(Error::MissingInterface.into());
+                }
+                if u32::from_ne_bytes(block_type) == block_type::SECTION_HEADER {
+                    reader.read_section_header()?;
+                } else if reader.read_block_of(block_type)?.is_some() {
+                    return Err(Error::UnknownInterface.into());
+                }
+            }
+            return Ok(reader);
+        }
+
+        let units = match (u32::from_le_bytes(magic), u32::from_be_bytes(magic)) {
+            (consts::PCAP_MAGIC_MICROS, _) => 1_000_000,
+            (consts::PCAP_MAGIC_NANOS, _) => 1_000_000_000,
+            (_, consts::PCAP_MAGIC_MICROS) => {
+                reader.big_endian = true;
+                1_000_000
+            }
+            (_, consts::PCAP_MAGIC_NANOS) => {
+                reader.big_endian = true;
+                1_000_000_000
+            }
+            _ => return Err(Error::InvalidMagic.into()),
+        };
+
+        let mut header = [0; consts::PCAP_HEADER_LEN - 4];
+        reader.inner.read_exact(&mut header)?;
+        if reader.u16_at(&header, 0) != consts::PCAP_VERSION_MAJOR {
+            return Err(Error::UnsupportedVersion.into());
+        }
+        reader.interfaces.push(Interface {
+            link_type: LinkType::from(reader.u32_at(&header, 16)),
+            snaplen: reader.u32_at(&header, 12),
+            units,
+        });
+
+        Ok(reader)
+    }
+
+    pub fn format(&self) -> Format {
+        self.format
+    }
+
+    /// Returns the link type of the file, the one of the first interface for pcapng.
+    pub fn link_type(&self) -> LinkType {
+        self.interfaces[0].link_type
+    }
+
+    /// Returns the snapshot length of the file, the one of the first interface for pcapng.
+    pub fn snaplen(&self) -> u32 {
+        self.interfaces[0].snaplen
+    }
+
+    /// Read the next record, returns `None` at the end of the file.
+    pub fn next_record(&mut self) -> Result<Option<Record>> {
+        match self.format {
+            Format::Pcap => self.read_pcap_record(),
+            Format::Pcapng => loop {
+                let mut block_type = [0; 4];
+                if !self.read_or_eof(&mut block_type)? {
+                    return Ok(None);
+                }
+                if u32::from_ne_bytes(block_type) == block_type::SECTION_HEADER {
+                    self.read_section_header()?;
+                    continue;
+                }
+                if let Some(record) = self.read_block_of(block_type)? {
+                    return Ok(Some(record));
+                }
+            },
+        }
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    fn read_pcap_record(&mut self) -> Result<Option<Record>> {
+        let mut header = [0; consts::PCAP_RECORD_HEADER_LEN];
+        if !self.read_or_eof(&mut header)? {
+            return Ok(None);
+        }
+
+        let interface = self.interfaces[0];
+        let secs = self.u32_at(&header, 0) as u64;
+        let fraction = self.u32_at(&header, 4) as u64;
+        let captured_len = self.u32_at(&header, 8) as usize;
+        let original_len = self.u32_at(&header, 12) as usize;
+
+        let mut data = vec![0; captured_len];
+        self.inner.read_exact(&mut data)?;
+
+        Ok(Some(Record {
+            timestamp: Duration::from_secs(secs) + to_duration(fraction, interface.units),
+            link_type: interface.link_type,
+            original_len,
+            data,
+        }))
+    }
+
+    /// Read the rest of a pcapng block other than a section header, returns a packet if it is one.
+    fn read_block_of(&mut self, block_type: [u8; 4]) -> Result<Option<Record>> {
+        let block_type = self.u32_at(&block_type, 0);
+        let body = self.read_block_body()?;
+
+        match block_type {
+            block_type::INTERFACE_DESCRIPTION => {
+                if body.len() < 8 {
+                    return Err(Error::InvalidBlockLen.into());
+                }
+                let mut interface = Interface {
+                    link_type: LinkType::from(self.u16_at(&body, 0) as u32),
+                    snaplen: self.u32_at(&body, 4),
+                    units: consts::DEFAULT_TIMESTAMP_UNITS,
+                };
+                if let Some(resolution) = self.option(&body[8..], option_code::IF_TSRESOL) {
+                    let resolution = *resolution.first().ok_or(Error::InvalidTimestampResolution)?;
+                    let units = if resolution & 0x80 == 0 {
+                        10u64.checked_pow(resolution as u32)
+                    } else {
+                        2u64.checked_pow((resolution & 0x7f) as u32)
+                    };
+                    interface.units = units.ok_or(Error::InvalidTimestampResolution)?;
+                }
+                self.interfaces.push(interface);
+                Ok(None)
+            }
+            block_type::ENHANCED_PACKET => {
+                if body.len() < 20 {
+                    return Err(Error::InvalidBlockLen.into());
+                }
+                let interface = *self
+                    .interfaces
+                    .get(self.u32_at(&body, 0) as usize)
+                    .ok_or(Error::UnknownInterface)?;
+                let ticks = ((self.u32_at(&body, 4) as u64) << 32) | self.u32_at(&body, 8) as u64;
+                let captured_len = self.u32_at(&body, 12) as usize;
+                let original_len = self.u32_at(&body, 16) as usize;
+                let data = body.get(20..20 + captured_len).ok_or(Error::InvalidBlockLen)?;
+
+                Ok(Some(Record {
+                    timestamp: Duration::from_secs(ticks / interface.units)
+                        + to_duration(ticks % interface.units, interface.units),
+                    link_type: interface.link_type,
+                    original_len,
+                    data: data.to_vec(),
+                }))
+            }
+            block_type::SIMPLE_PACKET => {
+                if body.len() < 4 {
+                    return Err(Error::InvalidBlockLen.into());
+                }
+                let interface = *self.interfaces.first().ok_or(Error::UnknownInterface)?;
+                let original_len = self.u32_at(&body, 0) as usize;
+                let captured_len = original_len.min(interface.snaplen as usize).min(body.len() - 4);
+
+                Ok(Some(Record {
+                    timestamp: Duration::ZERO,
+                    link_type: interface.link_type,
+                    original_len,
+                    data: body[4..4 + captured_len].to_vec(),
+                }))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Read a section header block, its type being already read, which sets the byte order of the section.
+    fn read_section_header(&mut self) -> Result<()> {
+        let mut block_len = [0; 4];
+        self.inner.read_exact(&mut block_len)?;
+        let mut byte_order_magic = [0; 4];
+        self.inner.read_exact(&mut byte_order_magic)?;
+
+        self.big_endian = match (
+            u32::from_le_bytes(byte_order_magic),
+            u32::from_be_bytes(byte_order_magic),
+        ) {
+            (consts::PCAPNG_BYTE_ORDER_MAGIC, _) => false,
+            (_, consts::PCAPNG_BYTE_ORDER_MAGIC) => true,
+            _ => return Err(Error::InvalidMagic.into()),
+        };
+        self.interfaces.clear();
+
+        let block_len = self.checked_block_len(block_len)?;
+        if block_len < consts::PCAPNG_MIN_BLOCK_LEN + 16 {
+            return Err(Error::InvalidBlockLen.into());
+        }
+        let mut rest = vec![0; block_len - consts::PCAPNG_MIN_BLOCK_LEN];
+        self.inner.read_exact(&mut rest)?;
+        if self.u16_at(&rest, 0) != consts::PCAPNG_VERSION_MAJOR {
+            return Err(Error::UnsupportedVersion.into());
+        }
+
+        Ok(())
+    }
+
+    /// Read the length, body and trailing length of a block whose type is already read.
+    fn read_block_body(&mut self) -> Result<Vec<u8>> {
+        let mut block_len = [0; 4];
+        self.inner.read_exact(&mut block_len)?;
+        let block_len = self.checked_block_len(block_len)?;
+
+        let mut body = vec![0; block_len - consts::PCAPNG_MIN_BLOCK_LEN + 4];
+        self.inner.read_exact(&mut body)?;
+        body.truncate(block_len - consts::PCAPNG_MIN_BLOCK_LEN);
+        Ok(body)
+    }
+
+    fn checked_block_len(&self, block_len: [u8; 4]) -> Result<usize> {
+        let block_len = self.u32_at(&block_len, 0) as usize;
+        if block_len < consts::PCAPNG_MIN_BLOCK_LEN
+            || !block_len.is_multiple_of(4)
+            || block_len > consts::PCAPNG_MAX_BLOCK_LEN
+        {
+            return Err(Error::InvalidBlockLen.into());
+        }
+        Ok(block_len)
+    }
+
+    /// Returns the value of the option `code` among `options`, if present.
+    fn option<'a>(&self, mut options: &'a [u8], code: u16) -> Option<&'a [u8]> {
+        while options.len() >= 4 {
+            let option = self.u16_at(options, 0);
+            let len = self.u16_at(options, 2) as usize;
+            if option == option_code::END_OF_OPTIONS {
+                return None;
+            }
+            let value = options.get(4..4 + len)?;
+            if option == code {
+                return Some(value);
+            }
+            options = options.get(4 + len.div_ceil(4) * 4..)?;
+        }
+        None
+    }
+
+    /// Fill `buffer`, returns false at the end of the file, before any octet.
+    fn read_or_eof(&mut self, buffer: &mut [u8]) -> Result<bool> {
+        let mut filled = 0;
+        while filled < buffer.len() {
+            match self.inner.read(&mut buffer[filled..]) {
+                Ok(0) if filled == 0 => return Ok(false),
+                Ok(0) => return Err(std::io::Error::from(ErrorKind::UnexpectedEof).into()),
+                Ok(n) => filled += n,
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Ok(true)
+    }
+
+    fn u16_at(&self, buffer: &[u8], offset: usize) -> u16 {
+        let octets = [buffer[offset], buffer[offset + 1]];
+        if self.big_endian {
+            u16::from_be_bytes(octets)
+        } else {
+            u16::from_le_bytes(octets)
+        }
+    }
+
+    fn u32_at(&self, buffer: &[u8], offset: usize) -> u32 {
+        let octets = [
+            buffer[offset],
+            buffer[offset + 1],
+            buffer[offset + 2],
+            buffer[offset + 3],
+        ];
+        if self.big_endian {
+            u32::from_be_bytes(octets)
+        } else {
+            u32::from_le_bytes(octets)
+        }
+    }
+}
+
+impl<R: Read> Iterator for Reader<R> {
+    type Item = Result<Record>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_record().transpose()
+    }
+}
+
+/// Returns the duration of `ticks` of `1 / units` second.
+fn to_duration(ticks: u64, units: u64) -> Duration {
+    let nanos = ticks as u128 * 1_000_000_000 / units as u128;
+    Duration::new((nanos / 1_000_000_000) as u64, (nanos % 1_000_000_000) as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::Reader;
+    use crate::pcap::writer::Writer;
+    use crate::pcap::{Format, LinkType};
+
+    #[test]
+    fn write_and_read() {
+        for format in [Format::Pcap, Format::Pcapng] {
+            let mut writer = Writer::new(vec![], format, LinkType::Raw, 8).unwrap();
+            writer
+                .write(Duration::new(1_700_000_000, 123_456_000), &[0x45; 20])
+                .unwrap();
+            writer.write(Duration::new(1_700_000_001, 0), &[0x60, 0, 0, 0]).unwrap();
+
+            let file = writer.into_inner();
+            let mut reader = Reader::new(file.as_slice()).unwrap();
+            assert_eq!(reader.format(), format);
+            assert_eq!(reader.link_type(), LinkType::Raw);
+            assert_eq!(reader.snaplen(), 8);
+
+            let record = reader.next().unwrap().unwrap();
+            assert_eq!(record.timestamp, Duration::new(1_700_000_000, 123_456_000));
+            assert_eq!(record.original_len, 20);
+            assert_eq!(record.data, [0x45; 8]);
+
+            let record = reader.next().unwrap().unwrap();
+            assert_eq!(record.timestamp, Duration::new(1_700_000_001, 0));
+            assert_eq!(record.data, [0x60, 0, 0, 0]);
+            assert!(reader.next().is_none());
+        }
+
+        // A big-endian pcap file with timestamps in nanoseconds
+        let mut file = vec![
+            0xa1, 0xb2, 0x3c, 0x4d, 0, 2, 0, 4, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0xff, 0xff, 0, 0, 0, 1,
+        ];
+        file.extend_from_slice(&[0, 0, 0, 1, 0, 0, 0, 5, 0, 0, 0, 2, 0, 0, 0, 2, 0xaa, 0xbb]);
+        let record = Reader::new(file.as_slice()).unwrap().next().unwrap().unwrap();
+        assert_eq!(record.timestamp, Duration::new(1, 5));
+        assert_eq!(record.link_type, LinkType::Ethernet);
+        assert_eq!(record.data, [0xaa, 0xbb]);
+    }
+}
@@ -0,0 +1,132 @@
+use std::io::Write;
+use std::time::Duration;
+
+use crate::error::Result;
+use crate::pcap::consts::{self, block_type, option_code};
+use crate::pcap::{Format, LinkType, Record};
+
+/// Writes records into a capture file, in little-endian with timestamps in microseconds.
+///
+/// A pcapng file holds a single section describing a single interface.
+pub struct Writer<W: Write> {
+    inner: W,
+    format: Format,
+    link_type: LinkType,
+    snaplen: u32,
+}
+
+impl<W: Write> Writer<W> {
+    /// Write the file header into `inner`, the records being truncated to `snaplen` octets.
+    pub fn new(mut inner: W, format: Format, link_type: LinkType, snaplen: u32) -> Result<Self> {
+        match format {
+            Format::Pcap => {
+                let mut header = Vec::with_capacity(consts::PCAP_HEADER_LEN);
+                header.extend_from_slice(&consts::PCAP_MAGIC_MICROS.to_le_bytes());
+                header.extend_from_slice(&consts::PCAP_VERSION_MAJOR.to_le_bytes());
+                header.extend_from_slice(&consts::PCAP_VERSION_MINOR.to_le_bytes());
+                header.extend_from_slice(&0i32.to_le_bytes()); // thiszone
+                header.extend_from_slice(&0u32.to_le_bytes()); // sigfigs
+                header.extend_from_slice(&snaplen.to_le_bytes());
+                header.extend_from_slice(&u32::from(link_type).to_le_bytes());
+                inner.write_all(&header)?;
+            }
+            Format::Pcapng => {
+                let mut body = vec![];
+                body.extend_from_slice(&consts::PCAPNG_BYTE_ORDER_MAGIC.to_le_bytes());
+                body.extend_from_slice(&consts::PCAPNG_VERSION_MAJOR.to_le_bytes());
+                body.extend_from_slice(&consts::PCAPNG_VERSION_MINOR.to_le_bytes());
+                body.extend_from_slice(&(-1i64).to_le_bytes()); // Section length not known
+                write_block(&mut inner, block_type::SECTION_HEADER, &body)?;
+
+                let mut body = vec![];
+                body.extend_from_slice(&(u32::from(link_type) as u16).to_le_bytes());
+                body.extend_from_slice(&0u16.to_le_bytes());
+                body.extend_from_slice(&snaplen.to_le_bytes());
+                body.extend_from_slice(&option_code::END_OF_OPTIONS.to_le_bytes());
+                body.extend_from_slice(&0u16.to_le_bytes());
+                write_block(&mut inner, block_type::INTERFACE_DESCRIPTION, &body)?;
+            }
+        }
+
+        Ok(Writer {
+            inner,
+            format,
+            link_type,
+            snaplen,
+        })
+    }
+
+    pub fn format(&self) -> Format {
+        self.format
+    }
+
+    pub fn link_type(&self) -> LinkType {
+        self.link_type
+    }
+
+    pub fn snaplen(&self) -> u32 {
+        self.snaplen
+    }
+
+    /// Write a packet of the link type of the file, as captured at `timestamp` since the UNIX epoch.
+    pub fn write(&mut self, timestamp: Duration, data: &[u8]) -> Result<()> {
+        self.write_packet(timestamp, data.len(), data)
+    }
+
+    /// Write a record read from another file, keeping its original length.
+    /// Its link type is expected to be the one of the file.
+    pub fn write_record(&mut self, record: &Record) -> Result<()> {
+        self.write_packet(record.timestamp, record.original_len, &record.data)
+    }
+
+    fn write_packet(&mut self, timestamp: Duration, original_len: usize, data: &[u8]) -> Result<()> {
+        let data = &data[..data.len().min(self.snaplen as usize)];
+        let original_len = original_len.max(data.len()) as u32;
+
+        match self.format {
+            Format::Pcap => {
+                let mut header = Vec::with_capacity(consts::PCAP_RECORD_HEADER_LEN);
+                header.extend_from_slice(&(timestamp.as_secs() as u32).to_le_bytes());
+                header.extend_from_slice(&timestamp.subsec_micros().to_le_bytes());
+                header.extend_from_slice(&(data.len() as u32).to_le_bytes());
+                header.extend_from_slice(&original_len.to_le_bytes());
+                self.inner.write_all(&header)?;
+                self.inner.write_all(data)?;
+            }
+            Format::Pcapng => {
+                let micros = timestamp.as_micros() as u64;
+                let mut body = Vec::with_capacity(20 + data.len() + 3);
+                body.extend_from_slice(&0u32.to_le_bytes()); // The only interface
+                body.extend_from_slice(&((micros >> 32) as u32).to_le_bytes());
+                body.extend_from_slice(&(micros as u32).to_le_bytes());
+                body.extend_from_slice(&(data.len() as u32).to_le_bytes());
+                body.extend_from_slice(&original_len.to_le_bytes());
+                body.extend_from_slice(data);
+                body.resize(body.len().div_ceil(4) * 4, 0);
+                write_block(&mut self.inner, block_type::ENHANCED_PACKET, &body)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> Result<()> {
+        self.inner.flush()?;
+        Ok(())
+    }
+
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+/// Write a pcapng block around `body`, which is already padded to 32 bits.
+fn write_block<W: Write>(inner: &mut W, block_type: u32, body: &[u8]) -> Result<()> {
+    let block_len = ((consts::PCAPNG_MIN_BLOCK_LEN + body.len()) as u32).to_le_bytes();
+
+    inner.write_all(&block_type.to_le_bytes())?;
+    inner.write_all(&block_len)?;
+    inner.write_all(body)?;
+    inner.write_all(&block_len)?;
+    Ok(())
+}
@@ -0,0 +1,163 @@
+//! Packet buffers checked out of a pool and returned to it when dropped,
+//! so receiving and sending packets allocates nothing once the pool is warm.
+
+use std::fmt::{Debug, Formatter};
+use std::sync::{Arc, Mutex};
+
+pub mod consts {
+    pub const DEFAULT_CAPACITY: usize = 64; // Buffers kept for reuse, those returned beyond are freed
+}
+
+#[derive(Debug)]
+struct Free {
+    buffers: Vec<Vec<u8>>,
+    capacity: usize,
+}
+
+/// A pool of packet buffers, usually of the MTU, shared by its clones.
+///
+/// A buffer is allocated when none is free, so checking out never fails,
+/// and up to `capacity` of those returned are kept.
+#[derive(Debug, Clone)]
+pub struct BufferPool {
+    free: Arc<Mutex<Free>>,
+}
+
+impl BufferPool {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            free: Arc::new(Mutex::new(Free {
+                buffers: Vec::with_capacity(capacity),
+                capacity,
+            })),
+        }
+    }
+
+    /// Returns the number of buffers free for reuse.
+    pub fn available(&self) -> usize {
+        self.free.lock().unwrap().buffers.len()
+    }
+
+    /// Check out a buffer of `len` octets, holding whatever its previous user left in it.
+    pub fn checkout(&self, len: usize) -> PooledBuffer {
+        let mut data = self.free.lock().unwrap().buffers.pop().unwrap_or_default();
+        data.resize(len, 0);
+        PooledBuffer {
+            data,
+            pool: Some(self.free.clone()),
+        }
+    }
+}
+
+impl Default for BufferPool {
+    fn default() -> Self {
+        Self::new(consts::DEFAULT_CAPACITY)
+    }
+}
+
+/// A packet buffer returned to its pool when dropped, usable as the buffer of a packet.
+///
+/// A buffer made from a `Vec` belongs to no pool, and is freed as usual.
+#[derive(Clone)]
+pub struct PooledBuffer {
+    data: Vec<u8>,
+    pool: Option<Arc<Mutex<Free>>>,
+}
+
+impl PooledBuffer {
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    /// Shorten the buffer to `len` octets, such as the length of the packet received into it.
+    pub fn truncate(&mut self, len: usize) {
+        self.data.truncate(len);
+    }
+
+    /// Take the octets out of the pool, for a packet kept longer than the pool should wait for it.
+    pub fn into_vec(mut self) -> Vec<u8> {
+        self.pool = None;
+        std::mem::take(&mut self.data)
+    }
+}
+
+impl From<Vec<u8>> for PooledBuffer {
+    fn from(data: Vec<u8>) -> Self {
+        Self { data, pool: None }
+    }
+}
+
+impl AsRef<[u8]> for PooledBuffer {
+    fn as_ref(&self) -> &[u8] {
+        &self.data
+    }
+}
+
+impl AsMut<[u8]> for PooledBuffer {
+    fn as_mut(&mut self) -> &mut [u8] {
+        &mut self.data
+    }
+}
+
+impl PartialEq for PooledBuffer {
+    fn eq(&self, other: &Self) -> bool {
+        self.data == other.data
+    }
+}
+
+impl Eq for PooledBuffer {}
+
+impl Debug for PooledBuffer {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        self.data.fmt(f)
+    }
+}
+
+impl Drop for PooledBuffer {
+    fn drop(&mut self) {
+        if let Some(pool) = self.pool.take() {
+            let mut free = pool.lock().unwrap();
+            if free.buffers.len() < free.capacity {
+                free.buffers.push(std::mem::take(&mut self.data));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BufferPool, PooledBuffer};
+
+    #[test]
+    fn checkout_and_return() {
+        let pool = BufferPool::new(1);
+
+        let mut first = pool.checkout(1500);
+        first.as_mut()[0] = 0x45;
+        first.truncate(20);
+        let address = first.as_ref().as_ptr();
+        drop(first);
+        assert_eq!(pool.available(), 1);
+
+        // The storage is reused, grown back to the length asked for.
+        let second = pool.checkout(1500);
+        assert_eq!(second.as_ref().as_ptr(), address);
+        assert_eq!(second.len(), 1500);
+        assert_eq!(pool.available(), 0);
+
+        // Only as many buffers as the capacity are kept.
+        let third = pool.checkout(1500);
+        drop(second);
+        drop(third);
+        assert_eq!(pool.available(), 1);
+
+        // Taken out of the pool, or never in it
+        assert_eq!(pool.checkout(4).into_vec().len(), 4);
+        drop(PooledBuffer::from(vec![0; 4]));
+        assert_eq!(pool.available(), 0);
+    }
+}
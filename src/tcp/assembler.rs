@@ -0,0 +1,140 @@
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// A receive-side buffer for out-of-order segments.
+///
+/// Data is placed at offsets relative to the next expected sequence number,
+/// overlapping and adjacent ranges are merged so each octet is stored once.
+#[derive(Debug, Clone, Default)]
+pub struct Assembler {
+    /// Non-overlapping, non-adjacent ranges sorted by offset.
+    ranges: Vec<(usize, Vec<u8>)>,
+}
+
+impl Assembler {
+    /// Returns the number of octets held.
+    pub fn len(&self) -> usize {
+        self.ranges.iter().map(|(_, data)| data.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+
+    /// Returns the `(start, end)` offsets of the ranges held, in order.
+    pub fn ranges(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.ranges.iter().map(|(offset, data)| (*offset, offset + data.len()))
+    }
+
+    /// Store `data` received at `offset`, merging it with the ranges it overlaps or touches.
+    pub fn insert(&mut self, offset: usize, data: &[u8]) {
+        if data.is_empty() {
+            return;
+        }
+
+        let end = offset + data.len();
+        let first = self
+            .ranges
+            .iter()
+            .position(|(start, stored)| start + stored.len() >= offset)
+            .unwrap_or(self.ranges.len());
+        let last = self
+            .ranges
+            .iter()
+            .rposition(|(start, _)| *start <= end)
+            .map_or(0, |index| index + 1);
+
+        if first >= last {
+            self.ranges.insert(first, (offset, data.to_vec()));
+            return;
+        }
+
+        let merged_start = offset.min(self.ranges[first].0);
+        let merged_end = end.max(self.ranges[last - 1].0 + self.ranges[last - 1].1.len());
+
+        let mut merged = vec![0; merged_end - merged_start];
+        merged[offset - merged_start..end - merged_start].copy_from_slice(data);
+        for (start, stored) in self.ranges.drain(first..last) {
+            merged[start - merged_start..start - merged_start + stored.len()].copy_from_slice(&stored);
+        }
+
+        self.ranges.insert(first, (merged_start, merged));
+    }
+
+    /// Take the data that became contiguous with the next expected sequence number.
+    pub fn pop_front(&mut self) -> Option<Vec<u8>> {
+        if self.ranges.first()?.0 != 0 {
+            return None;
+        }
+
+        let (_, data) = self.ranges.remove(0);
+        self.shift(data.len());
+        Some(data)
+    }
+
+    /// Move the origin forward after `len` octets were received in order, dropping what they covered.
+    pub fn advance(&mut self, len: usize) {
+        if len == 0 {
+            return;
+        }
+
+        self.ranges.retain(|(start, data)| start + data.len() > len);
+        if let Some((start, data)) = self.ranges.first_mut() {
+            if *start < len {
+                data.drain(..len - *start);
+                *start = len;
+            }
+        }
+        self.shift(len);
+    }
+
+    fn shift(&mut self, len: usize) {
+        for (start, _) in self.ranges.iter_mut() {
+            *start -= len;
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.ranges.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Assembler;
+
+    #[test]
+    fn merge() {
+        let mut assembler = Assembler::default();
+
+        assembler.insert(10, b"klm");
+        assembler.insert(4, b"ef");
+        assembler.insert(13, b"nop");
+        assert_eq!(assembler.ranges().collect::<Vec<_>>(), vec![(4, 6), (10, 16)]);
+
+        assembler.insert(5, b"fghijk");
+        assert_eq!(assembler.ranges().collect::<Vec<_>>(), vec![(4, 16)]);
+        assert_eq!(assembler.len(), 12);
+        assert!(assembler.pop_front().is_none());
+
+        assembler.insert(0, b"abcd");
+        assert_eq!(assembler.pop_front().unwrap(), b"abcdefghijklmnop");
+        assert!(assembler.is_empty());
+    }
+
+    #[test]
+    fn advance() {
+        let mut assembler = Assembler::default();
+
+        assembler.insert(2, b"cd");
+        assembler.insert(6, b"ghi");
+        assembler.advance(3);
+        assert_eq!(assembler.ranges().collect::<Vec<_>>(), vec![(0, 1), (3, 6)]);
+
+        assert_eq!(assembler.pop_front().unwrap(), b"d");
+        assert_eq!(assembler.ranges().collect::<Vec<_>>(), vec![(2, 5)]);
+
+        assembler.advance(10);
+        assert!(assembler.is_empty());
+    }
+}
@@ -0,0 +1,263 @@
+use alloc::vec;
+use alloc::vec::Vec;
+use core::net::{IpAddr, Ipv4Addr};
+
+use crate::tcp::packet::{consts, Packet, TcpOption};
+
+pub struct PacketBuilder {
+    src_port: u16,
+    dest_port: u16,
+    seq_number: u32,
+    ack_number: u32,
+    data_offset: u8,
+    urg: bool,
+    ack: bool,
+    psh: bool,
+    rst: bool,
+    syn: bool,
+    fin: bool,
+    window: u16,
+    checksum: u16,
+    urgent_pointer: u16,
+    options: Vec<u8>,
+    src_addr: IpAddr,
+    dest_addr: IpAddr,
+    payload: Vec<u8>,
+}
+
+impl PacketBuilder {
+    pub fn src_port(mut self, src_port: u16) -> Self {
+        self.src_port = src_port;
+        self
+    }
+
+    pub fn dest_port(mut self, dest_port: u16) -> Self {
+        self.dest_port = dest_port;
+        self
+    }
+
+    pub fn seq_number(mut self, seq_number: u32) -> Self {
+        self.seq_number = seq_number;
+        self
+    }
+
+    pub fn ack_number(mut self, ack_number: u32) -> Self {
+        self.ack_number = ack_number;
+        self
+    }
+
+    /// Set the header length in 32-bit words, raised when building to fit the minimum header and the options.
+    pub fn data_offset(mut self, data_offset: u8) -> Self {
+        self.data_offset = data_offset;
+        self
+    }
+
+    pub fn urg(mut self, urg: bool) -> Self {
+        self.urg = urg;
+        self
+    }
+
+    pub fn ack(mut self, ack: bool) -> Self {
+        self.ack = ack;
+        self
+    }
+
+    pub fn psh(mut self, psh: bool) -> Self {
+        self.psh = psh;
+        self
+    }
+
+    pub fn rst(mut self, rst: bool) -> Self {
+        self.rst = rst;
+        self
+    }
+
+    pub fn syn(mut self, syn: bool) -> Self {
+        self.syn = syn;
+        self
+    }
+
+    pub fn fin(mut self, fin: bool) -> Self {
+        self.fin = fin;
+        self
+    }
+
+    pub fn window(mut self, window: u16) -> Self {
+        self.window = window;
+        self
+    }
+
+    pub fn checksum(mut self, checksum: u16) -> Self {
+        self.checksum = checksum;
+        self
+    }
+
+    pub fn urgent_pointer(mut self, urgent_pointer: u16) -> Self {
+        self.urgent_pointer = urgent_pointer;
+        self
+    }
+
+    /// Append an option, options are padded to a multiple of 4 octets when building.
+    pub fn option(mut self, option: TcpOption) -> Self {
+        option.emit(&mut self.options);
+        self
+    }
+
+    /// Source address used by the pseudo-header checksum.
+    pub fn src_addr(mut self, src_addr: impl Into<IpAddr>) -> Self {
+        self.src_addr = src_addr.into();
+        self
+    }
+
+    /// Destination address used by the pseudo-header checksum.
+    pub fn dest_addr(mut self, dest_addr: impl Into<IpAddr>) -> Self {
+        self.dest_addr = dest_addr.into();
+        self
+    }
+
+    pub fn payload(mut self, payload: Vec<u8>) -> Self {
+        self.payload = payload;
+        self
+    }
+
+    pub fn build_vec(mut self) -> Vec<u8> {
+        while !self.options.len().is_multiple_of(4) {
+            self.options.push(0); // Pad with End of Option List.
+        }
+
+        // An offset too small for the options would have them overwrite the payload.
+        let min_data_offset = consts::MIN_HEADER_LEN + (self.options.len() / 4) as u8;
+        self.data_offset = self.data_offset.max(min_data_offset);
+
+        let mut buffer: Vec<u8> = vec![0; self.data_offset as usize * 4];
+        buffer.append(&mut self.payload);
+
+        let mut packet = Packet::new_unchecked(buffer.as_mut_slice());
+        packet.set_src_port(self.src_port);
+        packet.set_dest_port(self.dest_port);
+        packet.set_seq_number(self.seq_number);
+        packet.set_ack_number(self.ack_number);
+        packet.set_data_offset(self.data_offset);
+        packet.set_urg(self.urg);
+        packet.set_ack(self.ack);
+        packet.set_psh(self.psh);
+        packet.set_rst(self.rst);
+        packet.set_syn(self.syn);
+        packet.set_fin(self.fin);
+        packet.set_window(self.window);
+        packet.set_urgent_pointer(self.urgent_pointer);
+        packet.set_options(self.options.as_slice());
+        packet.set_checksum(self.checksum);
+
+        if self.checksum == 0 {
+            packet.fill_checksum(self.src_addr, self.dest_addr);
+        }
+
+        buffer
+    }
+
+    pub fn build(self) -> Packet<Vec<u8>> {
+        Packet::new_unchecked(self.build_vec())
+    }
+}
+
+impl Default for PacketBuilder {
+    fn default() -> Self {
+        Self {
+            src_port: 0,
+            dest_port: 0,
+            seq_number: 0,
+            ack_number: 0,
+            data_offset: 0,
+            urg: false,
+            ack: false,
+            psh: false,
+            rst: false,
+            syn: false,
+            fin: false,
+            window: 0,
+            checksum: 0,
+            urgent_pointer: 0,
+            options: vec![],
+            src_addr: Ipv4Addr::UNSPECIFIED.into(),
+            dest_addr: Ipv4Addr::UNSPECIFIED.into(),
+            payload: vec![],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use crate::tcp::packet::{consts, TcpOption};
+
+    #[test]
+    fn build() {
+        let src_addr = Ipv4Addr::new(192, 168, 233, 233);
+        let dest_addr = Ipv4Addr::new(192, 168, 233, 234);
+        let payload = vec![1, 2, 3, 4, 5];
+
+        let packet = super::PacketBuilder::default()
+            .src_port(49307)
+            .dest_port(3000)
+            .seq_number(0x57162308)
+            .ack_number(0x60822590)
+            .ack(true)
+            .syn(true)
+            .window(0xffff)
+            .option(TcpOption::MaxSegmentSize(1460))
+            .option(TcpOption::WindowScale(7))
+            .src_addr(src_addr)
+            .dest_addr(dest_addr)
+            .payload(payload.clone())
+            .build();
+
+        assert_eq!(packet.src_port(), 49307);
+        assert_eq!(packet.dest_port(), 3000);
+        assert_eq!(packet.seq_number(), 0x57162308);
+        assert_eq!(packet.ack_number(), 0x60822590);
+        assert_eq!(packet.data_offset(), consts::MIN_HEADER_LEN + 2);
+        assert!(packet.ack());
+        assert!(packet.syn());
+        assert!(!packet.fin());
+        assert_eq!(packet.window(), 0xffff);
+        assert_eq!(packet.payload(), payload.as_slice());
+        assert!(packet.verify_checksum(src_addr, dest_addr));
+        assert!(!packet.verify_checksum(dest_addr, Ipv4Addr::new(10, 0, 0, 1)));
+
+        let options: Vec<TcpOption> = packet.options().map(|option| option.unwrap()).collect();
+        assert_eq!(
+            options,
+            vec![TcpOption::MaxSegmentSize(1460), TcpOption::WindowScale(7)]
+        );
+    }
+
+    #[test]
+    fn data_offset() {
+        let payload = vec![1, 2, 3, 4, 5];
+        let build = |data_offset| {
+            super::PacketBuilder::default()
+                .src_port(49307)
+                .dest_port(3000)
+                .data_offset(data_offset)
+                .option(TcpOption::MaxSegmentSize(1460))
+                .option(TcpOption::WindowScale(7))
+                .payload(payload.clone())
+                .build()
+        };
+
+        // An offset leaving no room for the options, or below the minimum header, is raised to fit them.
+        for data_offset in [1, 5, 6] {
+            let packet = build(data_offset);
+            assert_eq!(packet.data_offset(), consts::MIN_HEADER_LEN + 2);
+            assert_eq!(packet.options().count(), 2);
+            assert_eq!(packet.payload(), payload.as_slice());
+        }
+
+        // A larger one is kept, the options padded up to it.
+        let packet = build(10);
+        assert_eq!(packet.data_offset(), 10);
+        assert_eq!(packet.payload(), payload.as_slice());
+    }
+}
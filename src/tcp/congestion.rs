@@ -0,0 +1,143 @@
+use core::fmt::Debug;
+
+use crate::time::Instant;
+
+/// A congestion control algorithm, which limits the data a connection keeps in flight.
+///
+/// The connection detects losses and drives fast recovery, the algorithm only sizes the window.
+pub trait CongestionControl: Debug + Send {
+    /// Returns the congestion window in octets.
+    fn cwnd(&self) -> usize;
+
+    /// Set the maximum segment size used to size the window, once the handshake negotiated it.
+    fn set_mss(&mut self, mss: usize);
+
+    /// `acked` octets of new data were acknowledged, `in_recovery` tells if fast recovery is in progress.
+    fn on_ack(&mut self, now: Instant, acked: usize, in_recovery: bool);
+
+    /// A loss was detected by duplicate ACKs while `in_flight` octets were outstanding.
+    fn on_loss(&mut self, now: Instant, in_flight: usize);
+
+    /// An additional duplicate ACK arrived during fast recovery.
+    fn on_dup_ack(&mut self, _now: Instant) {}
+
+    /// All data outstanding when the loss was detected is acknowledged.
+    fn on_recovery_end(&mut self, _now: Instant) {}
+
+    /// The retransmission timer expired while `in_flight` octets were outstanding.
+    fn on_rto(&mut self, now: Instant, in_flight: usize);
+
+    /// Whether the window is still growing exponentially, pacing is faster then.
+    fn in_slow_start(&self) -> bool {
+        false
+    }
+}
+
+/// The NewReno algorithm of RFC 5681 and RFC 6582.
+#[derive(Debug, Clone)]
+pub struct NewReno {
+    mss: usize,
+    cwnd: usize,
+    ssthresh: usize,
+}
+
+impl NewReno {
+    pub fn new(mss: usize) -> Self {
+        Self {
+            mss,
+            cwnd: Self::initial_window(mss),
+            ssthresh: usize::MAX,
+        }
+    }
+
+    /// The initial window of RFC 5681 section 3.1.
+    fn initial_window(mss: usize) -> usize {
+        (4 * mss).min((2 * mss).max(4380))
+    }
+
+    pub fn ssthresh(&self) -> usize {
+        self.ssthresh
+    }
+}
+
+impl CongestionControl for NewReno {
+    fn cwnd(&self) -> usize {
+        self.cwnd
+    }
+
+    fn set_mss(&mut self, mss: usize) {
+        self.mss = mss;
+        self.cwnd = Self::initial_window(mss);
+    }
+
+    fn on_ack(&mut self, _now: Instant, acked: usize, in_recovery: bool) {
+        if in_recovery {
+            // Partial ACK, deflate the window by the data acknowledged and add back one segment.
+            self.cwnd = self.cwnd.saturating_sub(acked) + self.mss;
+        } else if self.cwnd < self.ssthresh {
+            // Slow start
+            self.cwnd += acked.min(self.mss);
+        } else {
+            // Congestion avoidance
+            self.cwnd += (self.mss * self.mss / self.cwnd).max(1);
+        }
+    }
+
+    fn on_loss(&mut self, _now: Instant, in_flight: usize) {
+        self.ssthresh = (in_flight / 2).max(2 * self.mss);
+        self.cwnd = self.ssthresh + 3 * self.mss;
+    }
+
+    fn on_dup_ack(&mut self, _now: Instant) {
+        self.cwnd += self.mss;
+    }
+
+    fn on_recovery_end(&mut self, _now: Instant) {
+        self.cwnd = self.ssthresh;
+    }
+
+    fn on_rto(&mut self, _now: Instant, in_flight: usize) {
+        self.ssthresh = (in_flight / 2).max(2 * self.mss);
+        self.cwnd = self.mss;
+    }
+
+    fn in_slow_start(&self) -> bool {
+        self.cwnd < self.ssthresh
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use std::time::Instant;
+
+    use super::{CongestionControl, NewReno};
+
+    #[test]
+    fn new_reno() {
+        let now = Instant::now();
+        let mut reno = NewReno::new(1000);
+        assert_eq!(reno.cwnd(), 4000);
+
+        reno.on_ack(now, 1000, false);
+        reno.on_ack(now, 3000, false);
+        assert_eq!(reno.cwnd(), 6000);
+
+        reno.on_loss(now, 6000);
+        assert_eq!(reno.ssthresh(), 3000);
+        assert_eq!(reno.cwnd(), 6000);
+
+        reno.on_dup_ack(now);
+        assert_eq!(reno.cwnd(), 7000);
+        reno.on_ack(now, 2000, true);
+        assert_eq!(reno.cwnd(), 6000);
+
+        reno.on_recovery_end(now);
+        assert_eq!(reno.cwnd(), 3000);
+        reno.on_ack(now, 1000, false);
+        assert_eq!(reno.cwnd(), 3333);
+
+        reno.on_rto(now, 3000);
+        assert_eq!(reno.ssthresh(), 2000);
+        assert_eq!(reno.cwnd(), 1000);
+    }
+}
@@ -0,0 +1,2260 @@
+use alloc::boxed::Box;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+use crate::error::Result;
+use crate::icmpv4::packet::ErrorMessage;
+use crate::macros::diagnostics::debug;
+use crate::options::{KeepAlive, SocketOption};
+use crate::tcp::assembler::Assembler;
+use crate::tcp::congestion::{CongestionControl, NewReno};
+use crate::tcp::error::Error;
+use crate::tcp::packet::{Packet, TcpFlags};
+use crate::tcp::repr::{Control, Repr};
+use crate::tcp::retransmit::{consts as retransmit_consts, RetransmitQueue, RttEstimator};
+use crate::tcp::ring_buffer::RingBuffer;
+use crate::tcp::seq::SeqNumber;
+use crate::time::{Duration, Instant};
+
+pub mod consts {
+    use core::time::Duration;
+
+    pub const DEFAULT_MSS: usize = 536; // Default send MSS when the peer does not announce one (RFC 1122)
+    pub const LOCAL_MSS: usize = 1460; // Default MTU minus the minimum IPv4 and TCP headers
+    pub const LOCAL_MSS_V6: usize = 1440; // Default MTU minus the IPv6 and minimum TCP headers
+    pub const DEFAULT_BUFFER_SIZE: usize = 65535;
+    pub const MAX_WINDOW: usize = 65535; // Largest window without window scaling
+    pub const MAX_WINDOW_SHIFT: u8 = 14; // Largest window scale shift (RFC 7323 section 2.3)
+    pub const PAWS_IDLE_TIMEOUT: Duration = Duration::from_secs(24 * 24 * 60 * 60); // RFC 7323 section 5.5
+    pub const TIME_WAIT_TIMEOUT: Duration = Duration::from_secs(60); // 2 * MSL
+    pub const DUP_ACK_THRESHOLD: u32 = 3; // Duplicate ACKs triggering a fast retransmit (RFC 5681)
+    pub const PACING_GAIN: f64 = 1.25; // Pacing rate over cwnd / srtt, leaving room for ACK compression
+    pub const MIN_MTU: u16 = 68; // Smallest MTU a Fragmentation Needed message is believed for (RFC 1191)
+    pub const HEADER_OVERHEAD: u16 = 40; // Minimum IPv4 and TCP headers
+}
+
+/// Connection states defined in RFC 793.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum State {
+    Closed,
+    Listen,
+    SynSent,
+    SynReceived,
+    Established,
+    FinWait1,
+    FinWait2,
+    CloseWait,
+    Closing,
+    LastAck,
+    TimeWait,
+}
+
+/// A segment to be transmitted, together with the addresses of its pseudo-header.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Segment {
+    pub src_addr: IpAddr,
+    pub dest_addr: IpAddr,
+    pub repr: Repr,
+    pub payload: Vec<u8>,
+    /// The TTL of the datagram, the default of the interface when unset.
+    pub ttl: Option<u8>,
+    pub tos: u8,
+}
+
+impl Segment {
+    /// Serialize the segment, filling the checksum.
+    pub fn build_vec(&self) -> Vec<u8> {
+        let mut buffer = vec![0; self.buffer_len()];
+        self.emit(&mut buffer);
+        buffer
+    }
+
+    /// Returns the length of the serialized segment in octets.
+    pub fn buffer_len(&self) -> usize {
+        self.repr().buffer_len()
+    }
+
+    /// Serialize the segment into `buffer`, which is `buffer_len()` octets long, filling the checksum.
+    pub fn emit(&self, buffer: &mut [u8]) {
+        let repr = self.repr();
+        buffer[repr.header_len()..].copy_from_slice(self.payload.as_slice());
+
+        repr.emit(&mut Packet::new_unchecked(buffer), self.src_addr, self.dest_addr);
+    }
+
+    fn repr(&self) -> Repr {
+        Repr {
+            payload_len: self.payload.len(),
+            ..self.repr
+        }
+    }
+}
+
+/// Returns the MSS announced on connections from `local_addr`.
+pub(crate) fn local_mss(local_addr: IpAddr) -> usize {
+    match local_addr {
+        IpAddr::V4(_) => consts::LOCAL_MSS,
+        IpAddr::V6(_) => consts::LOCAL_MSS_V6,
+    }
+}
+
+/// Returns the unspecified address of the family of `addr`.
+fn unspecified(addr: IpAddr) -> IpAddr {
+    match addr {
+        IpAddr::V4(_) => Ipv4Addr::UNSPECIFIED.into(),
+        IpAddr::V6(_) => Ipv6Addr::UNSPECIFIED.into(),
+    }
+}
+
+/// Returns the RST segment answering `repr`, which was received from `remote_addr` (RFC 793 page 36).
+pub fn reset_reply(local_addr: IpAddr, remote_addr: IpAddr, repr: &Repr) -> Segment {
+    let mut reply = Repr {
+        src_port: repr.dest_port,
+        dest_port: repr.src_port,
+        control: Control::Rst,
+        ..Repr::default()
+    };
+
+    match repr.ack_number {
+        Some(ack_number) => reply.seq_number = ack_number,
+        None => reply.ack_number = Some((SeqNumber(repr.seq_number) + repr.segment_len()).into()),
+    }
+
+    Segment {
+        src_addr: local_addr,
+        dest_addr: remote_addr,
+        repr: reply,
+        payload: vec![],
+        ttl: None,
+        tos: 0,
+    }
+}
+
+/// The Transmission Control Block of a single connection.
+///
+/// The connection is driven by events: `on_segment` for arriving segments, `on_timer` for timeouts,
+/// and `poll_transmit` which returns the segments that should be sent next.
+#[derive(Debug)]
+pub struct Connection {
+    state: State,
+    local: SocketAddr,
+    remote: SocketAddr,
+    passive: bool,
+    reset: bool,
+    timed_out: bool,
+    /// The last ICMP error about the connection, the one that closed it if it was hard.
+    icmp_error: Option<ErrorMessage>,
+
+    iss: SeqNumber,
+    snd_una: SeqNumber,
+    snd_nxt: SeqNumber,
+    /// The highest sequence number sent, `snd_nxt` goes back to `snd_una` on a retransmission timeout.
+    snd_max: SeqNumber,
+    snd_wnd: usize,
+    snd_wl1: SeqNumber,
+    snd_wl2: SeqNumber,
+    remote_mss: usize,
+
+    irs: SeqNumber,
+    rcv_nxt: SeqNumber,
+    /// The sequence number of a FIN received ahead of some data.
+    remote_fin: Option<SeqNumber>,
+    assembler: Assembler,
+    rcv_wnd_advertised: usize,
+
+    /// Whether both ends sent the Window Scale option (RFC 7323).
+    window_scaling: bool,
+    snd_wnd_shift: u8,
+    rcv_wnd_shift: u8,
+
+    /// Whether both ends sent the Timestamps option (RFC 7323).
+    timestamps: bool,
+    /// The instant at which the TSval clock started, it ticks every millisecond.
+    ts_epoch: Option<Instant>,
+    ts_recent: u32,
+    ts_recent_age: Option<Instant>,
+    last_ack_sent: SeqNumber,
+
+    nodelay: bool,
+    /// Whether new segments are spread over the round-trip time instead of sent in bursts.
+    pacing: bool,
+    pacing_next: Option<Instant>,
+
+    tx_buffer: RingBuffer,
+    rx_buffer: RingBuffer,
+    /// Whether the application stopped reading, data received from then on is acknowledged and dropped.
+    rx_shutdown: bool,
+
+    fin_queued: bool,
+    fin_seq: Option<SeqNumber>,
+    ack_pending: bool,
+    time_wait_deadline: Option<Instant>,
+
+    rtt: RttEstimator,
+    retransmit: RetransmitQueue,
+    rto_deadline: Option<Instant>,
+    retransmissions: u32,
+
+    /// The persist timer, running while the peer advertises a zero window (RFC 1122 section 4.2.2.17).
+    persist_deadline: Option<Instant>,
+    persist_backoff: u32,
+    probe_pending: bool,
+
+    keep_alive: Option<KeepAlive>,
+    keep_alive_deadline: Option<Instant>,
+    /// Keep-alive probes sent since the peer was last heard from.
+    keep_alive_probes: u32,
+    keep_alive_pending: bool,
+
+    ttl: Option<u8>,
+    tos: u8,
+
+    congestion: Box<dyn CongestionControl>,
+    dup_acks: u32,
+    /// The highest sequence number sent when fast recovery started (RFC 6582).
+    recover: Option<SeqNumber>,
+    fast_retransmit: bool,
+
+    /// Whether both ends sent SACK-Permitted (RFC 2018).
+    sack_enabled: bool,
+    /// Merged ranges above `snd_una` the peer reported as received.
+    sacked: Vec<(SeqNumber, SeqNumber)>,
+    /// Where the search for the next hole to retransmit resumes during recovery.
+    rexmit_next: SeqNumber,
+    /// The start of the out-of-order data received last, reported in the first SACK block.
+    sack_recent: Option<SeqNumber>,
+}
+
+impl Connection {
+    fn new(state: State, local: SocketAddr, remote: SocketAddr, iss: SeqNumber) -> Self {
+        Self {
+            state,
+            local,
+            remote,
+            passive: state == State::Listen,
+            reset: false,
+            timed_out: false,
+            icmp_error: None,
+            iss,
+            snd_una: iss,
+            snd_nxt: iss,
+            snd_max: iss,
+            snd_wnd: 0,
+            snd_wl1: SeqNumber(0),
+            snd_wl2: SeqNumber(0),
+            remote_mss: consts::DEFAULT_MSS,
+            irs: SeqNumber(0),
+            rcv_nxt: SeqNumber(0),
+            remote_fin: None,
+            assembler: Assembler::default(),
+            rcv_wnd_advertised: 0,
+            window_scaling: false,
+            snd_wnd_shift: 0,
+            rcv_wnd_shift: 0,
+            timestamps: false,
+            ts_epoch: None,
+            ts_recent: 0,
+            ts_recent_age: None,
+            last_ack_sent: SeqNumber(0),
+            nodelay: false,
+            pacing: false,
+            pacing_next: None,
+            tx_buffer: RingBuffer::with_capacity(consts::DEFAULT_BUFFER_SIZE),
+            rx_buffer: RingBuffer::with_capacity(consts::DEFAULT_BUFFER_SIZE),
+            rx_shutdown: false,
+            fin_queued: false,
+            fin_seq: None,
+            ack_pending: false,
+            time_wait_deadline: None,
+            rtt: RttEstimator::default(),
+            retransmit: RetransmitQueue::default(),
+            rto_deadline: None,
+            retransmissions: 0,
+            persist_deadline: None,
+            persist_backoff: 0,
+            probe_pending: false,
+            keep_alive: None,
+            keep_alive_deadline: None,
+            keep_alive_probes: 0,
+            keep_alive_pending: false,
+            ttl: None,
+            tos: 0,
+            congestion: Box::new(NewReno::new(consts::DEFAULT_MSS)),
+            dup_acks: 0,
+            recover: None,
+            fast_retransmit: false,
+            sack_enabled: false,
+            sacked: vec![],
+            rexmit_next: iss,
+            sack_recent: None,
+        }
+    }
+
+    /// Passive open, waiting for a SYN on `local`.
+    pub fn listen(local: SocketAddr, iss: SeqNumber) -> Self {
+        Self::new(State::Listen, local, SocketAddr::new(unspecified(local.ip()), 0), iss)
+    }
+
+    /// Active open, the SYN is sent by the next `poll_transmit`.
+    pub fn connect(local: SocketAddr, remote: SocketAddr, iss: SeqNumber) -> Self {
+        Self::new(State::SynSent, local, remote, iss)
+    }
+
+    /// Passive open completed with a SYN cookie: the SYN-ACK carrying `iss` was sent without keeping state,
+    /// so the connection starts in SYN-RECEIVED without any of the options a cookie cannot encode.
+    pub(crate) fn from_cookie(
+        local: SocketAddr,
+        remote: SocketAddr,
+        iss: SeqNumber,
+        irs: SeqNumber,
+        remote_mss: u16,
+    ) -> Self {
+        let mut connection = Self::new(State::SynReceived, local, remote, iss);
+        connection.passive = true;
+        connection.snd_nxt = iss + 1;
+        connection.snd_max = iss + 1;
+        connection.irs = irs;
+        connection.rcv_nxt = irs + 1;
+        connection.last_ack_sent = irs + 1;
+        connection.snd_wl1 = irs;
+        connection.snd_wl2 = iss;
+        connection.remote_mss = remote_mss as usize;
+        connection.congestion.set_mss(connection.remote_mss);
+        connection
+    }
+
+    pub fn state(&self) -> State {
+        self.state
+    }
+
+    fn set_state(&mut self, state: State) {
+        if state != self.state {
+            debug!(
+                "tcp {} <-> {}: {:?} -> {:?}",
+                self.local, self.remote, self.state, state
+            );
+        }
+        self.state = state;
+    }
+
+    pub fn local(&self) -> SocketAddr {
+        self.local
+    }
+
+    pub fn remote(&self) -> SocketAddr {
+        self.remote
+    }
+
+    /// Whether the connection was closed by a RST.
+    pub fn was_reset(&self) -> bool {
+        self.reset
+    }
+
+    /// Whether the connection was dropped after too many retransmission timeouts.
+    pub fn was_timed_out(&self) -> bool {
+        self.timed_out
+    }
+
+    /// Returns the last ICMP error reported about the connection.
+    pub fn icmp_error(&self) -> Option<ErrorMessage> {
+        self.icmp_error
+    }
+
+    /// Replace the congestion control algorithm, NewReno by default.
+    pub fn set_congestion_control(&mut self, mut congestion: Box<dyn CongestionControl>) {
+        congestion.set_mss(self.remote_mss);
+        self.congestion = congestion;
+    }
+
+    pub fn congestion_control(&self) -> &dyn CongestionControl {
+        self.congestion.as_ref()
+    }
+
+    /// Returns the round-trip time estimator of the connection.
+    pub fn rtt(&self) -> &RttEstimator {
+        &self.rtt
+    }
+
+    pub(crate) fn set_iss(&mut self, iss: SeqNumber) {
+        if self.state == State::Listen {
+            self.iss = iss;
+            self.snd_una = iss;
+            self.snd_nxt = iss;
+            self.snd_max = iss;
+        }
+    }
+
+    /// Whether the application may still queue data.
+    pub fn may_send(&self) -> bool {
+        matches!(
+            self.state,
+            State::SynSent | State::SynReceived | State::Established | State::CloseWait
+        ) && !self.fin_queued
+    }
+
+    /// Whether more data may still arrive from the peer.
+    pub fn may_recv(&self) -> bool {
+        matches!(
+            self.state,
+            State::SynSent | State::SynReceived | State::Established | State::FinWait1 | State::FinWait2
+        )
+    }
+
+    /// Queue data for transmission, returns the number of octets accepted.
+    pub fn send(&mut self, data: &[u8]) -> usize {
+        if !self.may_send() {
+            return 0;
+        }
+
+        self.tx_buffer.enqueue_slice(data)
+    }
+
+    /// Read received data in order, returns the number of octets copied.
+    pub fn recv(&mut self, buf: &mut [u8]) -> usize {
+        let len = self.rx_buffer.dequeue_slice(buf);
+
+        // Send a window update once the window may be moved.
+        if len > 0 && self.may_recv() && self.advertised_window() > self.offered_window() {
+            self.ack_pending = true;
+        }
+        len
+    }
+
+    pub fn nodelay(&self) -> bool {
+        self.nodelay
+    }
+
+    /// Disable Nagle's algorithm, so small segments are sent without waiting for outstanding data to be acknowledged.
+    pub fn set_nodelay(&mut self, nodelay: bool) {
+        self.nodelay = nodelay;
+    }
+
+    pub fn pacing(&self) -> bool {
+        self.pacing
+    }
+
+    /// Enable pacing, which sends a congestion window worth of data evenly over a round-trip time.
+    pub fn set_pacing(&mut self, pacing: bool) {
+        self.pacing = pacing;
+        self.pacing_next = None;
+    }
+
+    pub fn keep_alive(&self) -> Option<KeepAlive> {
+        self.keep_alive
+    }
+
+    /// Probe the peer once the connection was idle for a while, and drop the connection when it stops answering.
+    pub fn set_keep_alive(&mut self, keep_alive: Option<KeepAlive>) {
+        self.keep_alive = keep_alive;
+        self.keep_alive_deadline = None;
+        self.keep_alive_probes = 0;
+        self.keep_alive_pending = false;
+    }
+
+    pub fn ttl(&self) -> Option<u8> {
+        self.ttl
+    }
+
+    pub fn set_ttl(&mut self, ttl: u8) {
+        self.ttl = Some(ttl);
+    }
+
+    pub fn tos(&self) -> u8 {
+        self.tos
+    }
+
+    pub fn set_tos(&mut self, tos: u8) {
+        self.tos = tos;
+    }
+
+    /// Apply an option of the connection itself, options of the socket layer are rejected.
+    pub fn set_option(&mut self, option: SocketOption) -> Result<()> {
+        if !option.is_connection_option() {
+            return Err(Error::UnsupportedOption.into());
+        }
+
+        match option {
+            SocketOption::SendBufferSize(size) => self.set_send_buffer_size(size),
+            SocketOption::RecvBufferSize(size) => self.set_recv_buffer_size(size),
+            SocketOption::NoDelay(nodelay) => self.set_nodelay(nodelay),
+            SocketOption::KeepAlive(keep_alive) => self.set_keep_alive(keep_alive),
+            SocketOption::Ttl(ttl) => self.set_ttl(ttl),
+            SocketOption::Tos(tos) => self.set_tos(tos),
+            SocketOption::Linger(_) | SocketOption::ReuseAddress(_) | SocketOption::ReusePort(_) => {}
+        }
+        Ok(())
+    }
+
+    pub fn send_buffer_size(&self) -> usize {
+        self.tx_buffer.capacity()
+    }
+
+    /// Resize the send buffer, which never drops below the data it holds.
+    pub fn set_send_buffer_size(&mut self, size: usize) {
+        self.tx_buffer.resize(size);
+    }
+
+    pub fn recv_buffer_size(&self) -> usize {
+        self.rx_buffer.capacity()
+    }
+
+    /// Resize the receive buffer, which bounds the advertised window.
+    /// Shrinking it below the window already advertised is not allowed (RFC 793 section 3.7).
+    pub fn set_recv_buffer_size(&mut self, size: usize) {
+        let advertised = match self.state {
+            State::Closed | State::Listen | State::SynSent => 0,
+            _ => self.rcv_wnd(),
+        };
+        self.rx_buffer.resize(size.max(self.rx_buffer.len() + advertised));
+    }
+
+    /// Returns the number of octets ready to be read.
+    pub fn recv_queue(&self) -> usize {
+        self.rx_buffer.len()
+    }
+
+    /// Returns the number of octets queued but not yet acknowledged.
+    pub fn send_queue(&self) -> usize {
+        self.tx_buffer.len()
+    }
+
+    /// Close the sending direction, a FIN is sent once all queued data is out.
+    /// Data may still be received until the peer closes its direction as well.
+    pub fn close(&mut self) {
+        match self.state {
+            State::Listen | State::SynSent => self.set_state(State::Closed),
+            State::SynReceived | State::Established => {
+                self.fin_queued = true;
+                self.set_state(State::FinWait1);
+            }
+            State::CloseWait => {
+                self.fin_queued = true;
+                self.set_state(State::LastAck);
+            }
+            _ => {}
+        }
+    }
+
+    /// Close the receiving direction: data not read yet is dropped, and so is the data received later,
+    /// which is still acknowledged so the peer can finish sending.
+    pub fn shutdown_read(&mut self) {
+        self.rx_shutdown = true;
+        self.rx_buffer.clear();
+    }
+
+    /// Abort the connection, returns the RST to be sent if the connection was synchronized.
+    pub fn abort(&mut self) -> Option<Segment> {
+        let synchronized = !matches!(self.state, State::Closed | State::Listen | State::SynSent);
+        let segment = if synchronized {
+            let mut repr = self.repr(Control::Rst, self.snd_nxt);
+            repr.ack_number = None;
+            repr.window = 0;
+            Some(self.segment(repr, vec![]))
+        } else {
+            None
+        };
+
+        self.set_state(State::Closed);
+        self.tx_buffer.clear();
+        self.stop_retransmit_timer();
+        segment
+    }
+
+    /// Handle an ICMP error about the segment starting at `seq` (RFC 1122 section 4.2.3.9):
+    /// a hard error aborts the connection, a Fragmentation Needed message lowers the MSS
+    /// and sends the outstanding data again, other errors are only recorded.
+    pub fn on_icmp_error(&mut self, message: ErrorMessage, seq: SeqNumber) {
+        // Only errors about data in flight are believed (RFC 5927 section 4.1).
+        if matches!(self.state, State::Closed | State::Listen | State::TimeWait)
+            || seq < self.snd_una
+            || seq >= self.snd_max
+        {
+            return;
+        }
+
+        match message {
+            ErrorMessage::FragmentationNeeded(mtu) => {
+                if mtu < consts::MIN_MTU || (mtu - consts::HEADER_OVERHEAD) as usize >= self.remote_mss {
+                    return;
+                }
+                self.remote_mss = (mtu - consts::HEADER_OVERHEAD) as usize;
+                self.congestion.set_mss(self.remote_mss);
+                // Not a congestion signal, the window is kept (RFC 1191 section 6.5).
+                if !matches!(self.state, State::SynSent | State::SynReceived) {
+                    self.sacked.clear();
+                    self.retransmit.clear();
+                    self.snd_nxt = self.snd_una;
+                }
+            }
+            message if message.is_hard() => {
+                self.set_state(State::Closed);
+                self.icmp_error = Some(message);
+                self.tx_buffer.clear();
+                self.stop_retransmit_timer();
+                self.persist_deadline = None;
+            }
+            message => self.icmp_error = Some(message),
+        }
+    }
+
+    fn reset(&mut self) {
+        self.set_state(State::Closed);
+        self.reset = true;
+        self.tx_buffer.clear();
+        self.stop_retransmit_timer();
+        self.persist_deadline = None;
+    }
+
+    fn stop_retransmit_timer(&mut self) {
+        self.retransmit.clear();
+        self.rto_deadline = None;
+    }
+
+    /// Returns the receive window from the free space of the receive buffer.
+    fn rcv_wnd(&self) -> usize {
+        self.rx_buffer.window().min(consts::MAX_WINDOW << self.rcv_wnd_shift)
+    }
+
+    /// Returns what is left of the window advertised last.
+    fn offered_window(&self) -> usize {
+        let right_edge = self.last_ack_sent + self.rcv_wnd_advertised;
+        if right_edge > self.rcv_nxt {
+            right_edge - self.rcv_nxt
+        } else {
+            0
+        }
+    }
+
+    /// Returns the window to advertise. Receiver-side silly window syndrome avoidance keeps the right edge
+    /// in place until it can move by a full segment or half the buffer (RFC 1122 section 4.2.3.3).
+    fn advertised_window(&self) -> usize {
+        let available = self.rcv_wnd();
+        let offered = self.offered_window().min(available);
+        let threshold = local_mss(self.local.ip()).min(self.rx_buffer.capacity() / 2);
+
+        if available >= offered + threshold {
+            available
+        } else {
+            offered
+        }
+    }
+
+    /// Returns the sequence number of the first octet in the transmit buffer.
+    fn data_start(&self) -> SeqNumber {
+        if self.snd_una == self.iss {
+            self.iss + 1 // The SYN is not acknowledged yet.
+        } else {
+            self.snd_una
+        }
+    }
+
+    /// Returns the number of octets of the transmit buffer that were already sent.
+    fn sent_data_len(&self) -> usize {
+        let data_end = match self.fin_seq {
+            Some(fin_seq) => fin_seq.min(self.snd_nxt),
+            None => self.snd_nxt,
+        };
+        let data_start = self.data_start();
+
+        if data_end > data_start {
+            (data_end - data_start).min(self.tx_buffer.len())
+        } else {
+            0
+        }
+    }
+
+    /// Returns the number of octets that may still be sent, limited by both the peer and the congestion window.
+    fn available_window(&self) -> usize {
+        let window_end = self.snd_una + self.snd_wnd.min(self.congestion.cwnd());
+        if window_end > self.snd_nxt {
+            window_end - self.snd_nxt
+        } else {
+            0
+        }
+    }
+
+    fn fin_acked(&self) -> bool {
+        self.fin_seq.map(|fin_seq| self.snd_una > fin_seq).unwrap_or(false)
+    }
+
+    fn repr(&self, control: Control, seq_number: SeqNumber) -> Repr {
+        Repr {
+            src_port: self.local.port(),
+            dest_port: self.remote.port(),
+            control,
+            seq_number: seq_number.into(),
+            ack_number: Some(self.rcv_nxt.into()),
+            window: (self.advertised_window() >> self.rcv_wnd_shift) as u16,
+            sack_ranges: self.sack_blocks(),
+            ..Repr::default()
+        }
+    }
+
+    /// Returns the SACK blocks describing the out-of-order data held, the most recent one first (RFC 2018 section 4).
+    fn sack_blocks(&self) -> [Option<(u32, u32)>; 3] {
+        let mut blocks = [None; 3];
+        if !self.sack_enabled {
+            return blocks;
+        }
+
+        let mut ranges: Vec<(SeqNumber, SeqNumber)> = self
+            .assembler
+            .ranges()
+            .map(|(start, end)| (self.rcv_nxt + start, self.rcv_nxt + end))
+            .collect();
+        if let Some(recent) = self.sack_recent {
+            if let Some(index) = ranges.iter().position(|(start, end)| *start <= recent && recent < *end) {
+                let range = ranges.remove(index);
+                ranges.insert(0, range);
+            }
+        }
+
+        for (block, (start, end)) in blocks.iter_mut().zip(ranges) {
+            *block = Some((start.into(), end.into()));
+        }
+        blocks
+    }
+
+    /// Record the SACK blocks of an acceptable ACK in the scoreboard.
+    fn process_sack(&mut self, repr: &Repr) {
+        if !self.sack_enabled {
+            return;
+        }
+
+        for (start, end) in repr.sack_ranges.iter().flatten() {
+            let (start, end) = (SeqNumber(*start), SeqNumber(*end));
+            if start >= end || start < self.snd_una || end > self.snd_max {
+                continue;
+            }
+
+            let mut merged = (start, end);
+            self.sacked.retain(|(other_start, other_end)| {
+                if *other_start > merged.1 || *other_end < merged.0 {
+                    return true;
+                }
+                merged = (merged.0.min(*other_start), merged.1.max(*other_end));
+                false
+            });
+
+            let index = self.sacked.iter().position(|(other_start, _)| *other_start > merged.0);
+            self.sacked.insert(index.unwrap_or(self.sacked.len()), merged);
+        }
+    }
+
+    /// Returns the next range that was sent, not sacked, and lies below sacked data, starting from `from`.
+    fn next_hole(&self, from: SeqNumber) -> Option<(SeqNumber, SeqNumber)> {
+        let mut start = from;
+
+        for (sacked_start, sacked_end) in self.sacked.iter() {
+            if start < *sacked_start {
+                return Some((start, *sacked_start));
+            }
+            start = start.max(*sacked_end);
+        }
+        None
+    }
+
+    fn segment(&mut self, mut repr: Repr, payload: Vec<u8>) -> Segment {
+        repr.payload_len = payload.len();
+        if let Some(ack_number) = repr.ack_number {
+            self.last_ack_sent = SeqNumber(ack_number);
+        }
+        self.rcv_wnd_advertised = match repr.control {
+            Control::Syn => repr.window as usize,
+            _ => (repr.window as usize) << self.rcv_wnd_shift,
+        };
+        Segment {
+            src_addr: self.local.ip(),
+            dest_addr: self.remote.ip(),
+            repr,
+            payload,
+            ttl: self.ttl,
+            tos: self.tos,
+        }
+    }
+
+    /// Returns the TSval clock, offset by the initial sequence number so it does not start at zero.
+    fn ts_value(&mut self, now: Instant) -> u32 {
+        let epoch = *self.ts_epoch.get_or_insert(now);
+        let millis = now.saturating_duration_since(epoch).as_millis() as u32;
+        self.iss.0.wrapping_add(millis)
+    }
+
+    /// Add the Timestamps option when it was negotiated, then build the segment.
+    fn transmit(&mut self, now: Instant, mut repr: Repr, payload: Vec<u8>) -> Segment {
+        if self.timestamps || (repr.control == Control::Syn && self.state == State::SynSent) {
+            repr.timestamps = Some((self.ts_value(now), self.ts_recent));
+        }
+        self.segment(repr, payload)
+    }
+
+    /// The PAWS test of RFC 7323 section 5.3, returns whether the segment is an old duplicate.
+    fn paws_reject(&mut self, now: Instant, repr: &Repr) -> bool {
+        let tsval = match (self.timestamps, repr.timestamps) {
+            (true, Some((tsval, _))) => tsval,
+            _ => return false,
+        };
+
+        if let Some(age) = self.ts_recent_age {
+            if now.saturating_duration_since(age) > consts::PAWS_IDLE_TIMEOUT {
+                // TS.Recent is too old to be compared with.
+                self.ts_recent = tsval;
+                self.ts_recent_age = Some(now);
+                return false;
+            }
+        }
+
+        repr.control != Control::Rst && (tsval.wrapping_sub(self.ts_recent) as i32) < 0
+    }
+
+    /// The segment acceptance test of RFC 793 page 69.
+    fn acceptable(&self, seq: SeqNumber, seg_len: usize) -> bool {
+        let rcv_wnd = self.rcv_wnd();
+        let in_window = |seq: SeqNumber| self.rcv_nxt <= seq && seq < self.rcv_nxt + rcv_wnd;
+
+        match (seg_len, rcv_wnd) {
+            // Valid ACKs and RSTs are still processed when the window is closed.
+            (_, 0) => seq == self.rcv_nxt,
+            (0, _) => in_window(seq),
+            (_, _) => in_window(seq) || in_window(seq + seg_len - 1),
+        }
+    }
+
+    fn process_ack(&mut self, now: Instant, ack: SeqNumber, ts_echo: Option<u32>) {
+        let data_start = self.data_start();
+        let acked_end = match self.fin_seq {
+            Some(fin_seq) if ack > fin_seq => fin_seq,
+            _ => ack,
+        };
+
+        let acked = if acked_end > data_start {
+            (acked_end - data_start).min(self.tx_buffer.len())
+        } else {
+            0
+        };
+        self.tx_buffer.dequeue_allocated(acked);
+
+        self.dup_acks = 0;
+        match self.recover {
+            Some(recover) if ack >= recover => {
+                self.recover = None;
+                self.congestion.on_recovery_end(now);
+            }
+            Some(_) => {
+                // A partial ACK, the next hole is retransmitted right away (RFC 6582 section 3.2).
+                self.congestion.on_ack(now, acked, true);
+                self.fast_retransmit = true;
+                if !self.sack_enabled {
+                    self.rexmit_next = ack;
+                }
+            }
+            None if acked > 0 => self.congestion.on_ack(now, acked, false),
+            None => {}
+        }
+
+        self.snd_una = ack;
+        if self.snd_nxt < ack {
+            self.snd_nxt = ack;
+        }
+        self.sacked.retain(|(_, end)| *end > ack);
+        if let Some((start, _)) = self.sacked.first_mut() {
+            *start = (*start).max(ack);
+        }
+        self.rexmit_next = self.rexmit_next.max(ack);
+
+        let sample = self.retransmit.ack(ack, now);
+        match ts_echo.filter(|_| self.timestamps) {
+            // The echoed timestamp measures retransmitted segments as well (RFC 7323 section 4.1).
+            Some(tsecr) => {
+                let rtt = self.ts_value(now).wrapping_sub(tsecr);
+                self.rtt.sample(Duration::from_millis(rtt as u64));
+            }
+            None => {
+                if let Some(rtt) = sample {
+                    self.rtt.sample(rtt);
+                }
+            }
+        }
+        self.retransmissions = 0;
+        // The peer is reachable again.
+        self.icmp_error = None;
+        // Restart the timer for the remaining data, or stop it when everything is acknowledged.
+        self.rto_deadline = if self.snd_una == self.snd_max {
+            None
+        } else {
+            Some(now + self.rtt.rto())
+        };
+    }
+
+    /// Place received data in the receive buffer, or in the assembler when it arrived out of order.
+    fn receive_data(&mut self, seq: SeqNumber, payload: &[u8]) {
+        let rcv_wnd = self.rcv_wnd();
+
+        if seq <= self.rcv_nxt {
+            let offset = self.rcv_nxt - seq;
+            if offset < payload.len() {
+                let data = &payload[offset..];
+                let len = self.rx_buffer.enqueue_slice(&data[..data.len().min(rcv_wnd)]);
+                self.rcv_nxt += len;
+                self.assembler.advance(len);
+            }
+        } else {
+            let offset = seq - self.rcv_nxt;
+            if offset < rcv_wnd {
+                let len = payload.len().min(rcv_wnd - offset);
+                self.assembler.insert(offset, &payload[..len]);
+                self.sack_recent = Some(seq);
+            }
+        }
+
+        while let Some(data) = self.assembler.pop_front() {
+            let len = self.rx_buffer.enqueue_slice(&data);
+            self.rcv_nxt += len;
+        }
+
+        if self.rx_shutdown {
+            self.rx_buffer.clear();
+        }
+    }
+
+    /// Count a duplicate ACK, a loss is assumed after `DUP_ACK_THRESHOLD` of them (RFC 5681 section 3.2).
+    fn on_dup_ack(&mut self, now: Instant) {
+        self.dup_acks += 1;
+
+        if self.recover.is_some() {
+            self.congestion.on_dup_ack(now);
+            // With SACK every duplicate ACK lets another hole be retransmitted.
+            self.fast_retransmit |= self.sack_enabled;
+        } else if self.dup_acks == consts::DUP_ACK_THRESHOLD {
+            self.congestion.on_loss(now, self.snd_max - self.snd_una);
+            self.recover = Some(self.snd_max);
+            self.rexmit_next = self.snd_una;
+            self.fast_retransmit = true;
+        }
+    }
+
+    /// Take the options negotiated by the SYN of the peer into account.
+    fn process_syn_options(&mut self, repr: &Repr) {
+        self.remote_mss = repr.max_seg_size.map_or(consts::DEFAULT_MSS, |mss| mss as usize);
+        self.congestion.set_mss(self.remote_mss);
+        self.sack_enabled = repr.sack_permitted;
+
+        self.timestamps = repr.timestamps.is_some();
+        if let Some((tsval, _)) = repr.timestamps {
+            self.ts_recent = tsval;
+        }
+
+        match repr.window_scale {
+            Some(shift) => {
+                self.window_scaling = true;
+                self.snd_wnd_shift = shift.min(consts::MAX_WINDOW_SHIFT);
+                if self.state == State::Listen {
+                    self.rcv_wnd_shift = self.local_wnd_shift();
+                }
+            }
+            None => {
+                // Scaling is only used when both ends sent the option.
+                self.window_scaling = false;
+                self.snd_wnd_shift = 0;
+                self.rcv_wnd_shift = 0;
+            }
+        }
+    }
+
+    /// Returns the smallest shift that lets the whole receive buffer be advertised.
+    fn local_wnd_shift(&self) -> u8 {
+        let mut shift = 0;
+        while shift < consts::MAX_WINDOW_SHIFT && self.rx_buffer.capacity() >> shift > consts::MAX_WINDOW {
+            shift += 1;
+        }
+        shift
+    }
+
+    /// Returns when the connection leaves TIME-WAIT, if it is in TIME-WAIT.
+    pub(crate) fn time_wait_deadline(&self) -> Option<Instant> {
+        self.time_wait_deadline.filter(|_| self.state == State::TimeWait)
+    }
+
+    /// Close a TIME-WAIT connection early so a new SYN for the same pair can open a new one
+    /// (RFC 1122 section 4.2.2.13, RFC 6191). The SYN must be newer than anything seen so far:
+    /// with timestamps, its timestamp is greater than the last one, otherwise its sequence number
+    /// is beyond the end of the old connection. Returns the sequence number the new ISS must exceed.
+    pub(crate) fn recycle(&mut self, repr: &Repr) -> Option<SeqNumber> {
+        if self.state != State::TimeWait || repr.control != Control::Syn || repr.ack_number.is_some() {
+            return None;
+        }
+
+        let newer = match (self.timestamps, repr.timestamps) {
+            (true, Some((tsval, _))) => (tsval.wrapping_sub(self.ts_recent) as i32) > 0,
+            (true, None) => false,
+            (false, _) => SeqNumber(repr.seq_number) > self.rcv_nxt,
+        };
+        if !newer {
+            return None;
+        }
+
+        self.set_state(State::Closed);
+        self.time_wait_deadline = None;
+        Some(self.snd_max)
+    }
+
+    fn enter_time_wait(&mut self, now: Instant) {
+        self.set_state(State::TimeWait);
+        self.time_wait_deadline = Some(now + consts::TIME_WAIT_TIMEOUT);
+    }
+
+    /// Process a segment received from `src_addr` and sent to `dest_addr` (RFC 793 page 65).
+    /// Returns a segment that must be sent immediately, such as a RST.
+    pub fn on_segment(
+        &mut self,
+        now: Instant,
+        src_addr: IpAddr,
+        dest_addr: IpAddr,
+        repr: &Repr,
+        payload: &[u8],
+    ) -> Option<Segment> {
+        let seq = SeqNumber(repr.seq_number);
+
+        match self.state {
+            State::Closed => {
+                if repr.control == Control::Rst {
+                    return None;
+                }
+                return Some(reset_reply(dest_addr, src_addr, repr));
+            }
+            State::Listen => {
+                if repr.control == Control::Rst {
+                    return None;
+                }
+                if repr.ack_number.is_some() {
+                    return Some(reset_reply(dest_addr, src_addr, repr));
+                }
+                if repr.control != Control::Syn {
+                    return None;
+                }
+
+                self.local = SocketAddr::new(dest_addr, self.local.port());
+                self.remote = SocketAddr::new(src_addr, repr.src_port);
+                self.irs = seq;
+                self.rcv_nxt = seq + 1;
+                self.snd_wnd = repr.window as usize;
+                self.snd_wl1 = seq;
+                self.snd_wl2 = self.iss;
+                self.process_syn_options(repr);
+                self.set_state(State::SynReceived);
+                return None;
+            }
+            State::SynSent => {
+                if let Some(ack) = repr.ack_number.map(SeqNumber) {
+                    if ack <= self.iss || ack > self.snd_max {
+                        if repr.control == Control::Rst {
+                            return None;
+                        }
+                        return Some(reset_reply(dest_addr, src_addr, repr));
+                    }
+                }
+
+                if repr.control == Control::Rst {
+                    if repr.ack_number.is_some() {
+                        self.reset();
+                    }
+                    return None;
+                }
+
+                if repr.control == Control::Syn {
+                    self.irs = seq;
+                    self.rcv_nxt = seq + 1;
+                    self.snd_wnd = repr.window as usize;
+                    self.snd_wl1 = seq;
+                    self.process_syn_options(repr);
+
+                    match repr.ack_number.map(SeqNumber) {
+                        Some(ack) => {
+                            self.process_ack(now, ack, repr.timestamps.map(|(_, tsecr)| tsecr));
+                            self.snd_wl2 = ack;
+                            self.set_state(State::Established);
+                            self.ack_pending = true;
+                        }
+                        None => {
+                            // Simultaneous open, the SYN is retransmitted as a SYN-ACK.
+                            self.snd_wl2 = self.iss;
+                            self.snd_nxt = self.iss;
+                            self.stop_retransmit_timer();
+                            self.set_state(State::SynReceived);
+                        }
+                    }
+                }
+                return None;
+            }
+            _ => {}
+        }
+
+        if self.state == State::TimeWait && repr.control == Control::Fin {
+            // The peer retransmitted its FIN, acknowledge it and restart the 2MSL timer.
+            self.ack_pending = true;
+            self.time_wait_deadline = Some(now + consts::TIME_WAIT_TIMEOUT);
+            return None;
+        }
+
+        // A retransmitted SYN gets the SYN-ACK again. The SYN-ACK of a simultaneous open falls below
+        // the window, though its ACK is taken like Linux does: it completes our handshake even before
+        // our own SYN-ACK went out, and the ACK sent in reply completes the handshake of the peer.
+        if self.state == State::SynReceived && repr.control == Control::Syn && seq == self.irs {
+            match repr.ack_number.map(SeqNumber) {
+                None => {
+                    self.snd_nxt = self.iss;
+                    self.stop_retransmit_timer();
+                    return None;
+                }
+                Some(ack) if self.snd_una < ack && ack <= self.snd_max => {
+                    self.stop_retransmit_timer();
+                    self.process_ack(now, ack, repr.timestamps.map(|(_, tsecr)| tsecr));
+                    self.snd_wl2 = ack;
+                    self.set_state(State::Established);
+                    self.ack_pending = true;
+                    return None;
+                }
+                Some(_) => {}
+            }
+        }
+
+        if self.paws_reject(now, repr) {
+            self.ack_pending = true;
+            return None;
+        }
+
+        // Any segment from the peer, even a duplicate answering a probe, shows it is alive.
+        if let Some(keep_alive) = self.keep_alive {
+            self.keep_alive_deadline = Some(now + keep_alive.idle);
+            self.keep_alive_probes = 0;
+        }
+
+        if !self.acceptable(seq, repr.segment_len()) {
+            if repr.control != Control::Rst {
+                self.ack_pending = true;
+            }
+            return None;
+        }
+
+        if let Some((tsval, _)) = repr.timestamps.filter(|_| self.timestamps) {
+            // Remember the timestamp to echo (RFC 7323 section 4.3).
+            if (tsval.wrapping_sub(self.ts_recent) as i32) >= 0 && seq <= self.last_ack_sent {
+                self.ts_recent = tsval;
+                self.ts_recent_age = Some(now);
+            }
+        }
+
+        match repr.control {
+            Control::Rst => {
+                if self.state == State::SynReceived && self.passive {
+                    let local = SocketAddr::new(unspecified(self.local.ip()), self.local.port());
+                    *self = Self::listen(local, self.iss);
+                } else {
+                    self.reset();
+                }
+                return None;
+            }
+            Control::Syn => {
+                let segment = self.abort();
+                self.reset();
+                return segment;
+            }
+            _ => {}
+        }
+
+        let ack = SeqNumber(repr.ack_number?);
+
+        if self.state == State::SynReceived {
+            if self.snd_una < ack && ack <= self.snd_max {
+                self.set_state(State::Established);
+            } else {
+                return Some(reset_reply(dest_addr, src_addr, repr));
+            }
+        }
+
+        if ack > self.snd_max {
+            self.ack_pending = true;
+            return None;
+        }
+
+        self.process_sack(repr);
+
+        if self.snd_una < ack {
+            self.process_ack(now, ack, repr.timestamps.map(|(_, tsecr)| tsecr));
+        } else if ack == self.snd_una
+            && self.snd_una < self.snd_max
+            && repr.payload_len == 0
+            && repr.control == Control::None
+            && (repr.window as usize) << self.snd_wnd_shift == self.snd_wnd
+        {
+            self.on_dup_ack(now);
+        }
+
+        if self.snd_wl1 < seq || (self.snd_wl1 == seq && self.snd_wl2 <= ack) {
+            self.snd_wnd = (repr.window as usize) << self.snd_wnd_shift;
+            self.snd_wl1 = seq;
+            self.snd_wl2 = ack;
+        }
+
+        let fin_acked = self.fin_acked();
+        match self.state {
+            State::FinWait1 if fin_acked => self.set_state(State::FinWait2),
+            State::Closing if fin_acked => self.enter_time_wait(now),
+            State::LastAck if fin_acked => {
+                self.set_state(State::Closed);
+                return None;
+            }
+            _ => {}
+        }
+
+        if matches!(self.state, State::Established | State::FinWait1 | State::FinWait2) {
+            if repr.payload_len > 0 {
+                self.receive_data(seq, payload);
+                self.ack_pending = true;
+            }
+            if repr.control == Control::Fin {
+                // The FIN is processed once all the data before it arrived.
+                self.remote_fin = Some(seq + repr.payload_len);
+                self.ack_pending = true;
+            }
+        }
+
+        if self.remote_fin.is_some() && self.remote_fin == Some(self.rcv_nxt) {
+            self.remote_fin = None;
+            self.rcv_nxt += 1;
+            self.ack_pending = true;
+
+            match self.state {
+                State::SynReceived | State::Established => self.set_state(State::CloseWait),
+                State::FinWait1 if fin_acked => self.enter_time_wait(now),
+                State::FinWait1 => self.set_state(State::Closing),
+                State::FinWait2 => self.enter_time_wait(now),
+                _ => {}
+            }
+        }
+
+        None
+    }
+
+    /// Handle expired timers.
+    pub fn on_timer(&mut self, now: Instant) {
+        if let Some(deadline) = self.rto_deadline {
+            if now >= deadline {
+                self.on_retransmit_timeout(now);
+            }
+        }
+
+        if let Some(deadline) = self.keep_alive_deadline {
+            if now >= deadline {
+                self.on_keep_alive_timeout(now);
+            }
+        }
+
+        if let Some(deadline) = self.persist_deadline {
+            if now >= deadline {
+                self.persist_deadline = None;
+                self.persist_backoff += 1;
+                self.probe_pending = true;
+            }
+        }
+
+        if self.state == State::TimeWait {
+            if let Some(deadline) = self.time_wait_deadline {
+                if now >= deadline {
+                    self.set_state(State::Closed);
+                    self.time_wait_deadline = None;
+                }
+            }
+        }
+    }
+
+    /// Send a keep-alive probe if nothing is in flight, the retransmission timer covers the connection otherwise.
+    fn on_keep_alive_timeout(&mut self, now: Instant) {
+        let keep_alive = match self.keep_alive {
+            Some(keep_alive) if matches!(self.state, State::Established | State::CloseWait) => keep_alive,
+            _ => {
+                self.keep_alive_deadline = None;
+                return;
+            }
+        };
+
+        if self.snd_una != self.snd_max {
+            self.keep_alive_deadline = Some(now + keep_alive.idle);
+        } else if self.keep_alive_probes >= keep_alive.probes {
+            self.set_state(State::Closed);
+            self.timed_out = true;
+            self.tx_buffer.clear();
+            self.stop_retransmit_timer();
+            self.keep_alive_deadline = None;
+        } else {
+            self.keep_alive_probes += 1;
+            self.keep_alive_pending = true;
+            self.keep_alive_deadline = Some(now + keep_alive.interval);
+        }
+    }
+
+    /// Go back to the oldest unacknowledged octet and back off the timer (RFC 6298 section 5.4-5.6).
+    fn on_retransmit_timeout(&mut self, now: Instant) {
+        self.rto_deadline = None;
+        self.retransmissions += 1;
+
+        if self.retransmissions > retransmit_consts::MAX_RETRANSMISSIONS {
+            self.set_state(State::Closed);
+            self.timed_out = true;
+            self.tx_buffer.clear();
+            self.stop_retransmit_timer();
+            return;
+        }
+
+        self.rtt.on_timeout();
+        self.congestion.on_rto(now, self.snd_max - self.snd_una);
+        self.dup_acks = 0;
+        self.recover = None;
+        self.fast_retransmit = false;
+        // The receiver may have discarded sacked data (RFC 2018 section 8).
+        self.sacked.clear();
+        // The segments sent again must not be used for RTT samples (Karn's algorithm).
+        self.retransmit.clear();
+        self.snd_nxt = self.snd_una;
+    }
+
+    /// Returns the earliest instant at which `on_timer` has work to do.
+    pub fn poll_at(&self) -> Option<Instant> {
+        let unsent = self.tx_buffer.len() > self.sent_data_len();
+        let pacing_next = self.pacing_next.filter(|_| unsent);
+
+        [
+            self.rto_deadline,
+            self.time_wait_deadline,
+            self.persist_deadline,
+            self.keep_alive_deadline,
+            pacing_next,
+        ]
+        .iter()
+        .flatten()
+        .min()
+        .copied()
+    }
+
+    /// Returns the time to wait after sending `len` octets, at a rate of `PACING_GAIN * cwnd / srtt`
+    /// that is doubled during slow start so the window can still grow.
+    fn pacing_interval(&self, len: usize) -> Option<Duration> {
+        let srtt = self.rtt.srtt()?;
+        let gain = if self.congestion.in_slow_start() {
+            2.0 * consts::PACING_GAIN
+        } else {
+            consts::PACING_GAIN
+        };
+        let rate = gain * self.congestion.cwnd().max(1) as f64;
+        Some(srtt.mul_f64(len as f64 / rate))
+    }
+
+    /// Start the persist timer when queued data is held back by a zero window and nothing is in flight,
+    /// stop it once the window opens. The interval backs off exponentially, but unlike the
+    /// retransmission timer the connection is never dropped while the peer answers the probes.
+    fn update_persist_timer(&mut self, now: Instant) {
+        let synchronized = matches!(
+            self.state,
+            State::Established | State::CloseWait | State::FinWait1 | State::Closing | State::LastAck
+        );
+        let unsent = self.tx_buffer.len() > self.sent_data_len();
+        let blocked = synchronized && unsent && self.snd_wnd == 0 && self.snd_una == self.snd_max;
+
+        if !blocked {
+            self.persist_deadline = None;
+            self.persist_backoff = 0;
+            self.probe_pending = false;
+        } else if self.persist_deadline.is_none() && !self.probe_pending {
+            let interval = self
+                .rtt
+                .rto()
+                .checked_mul(1 << self.persist_backoff.min(16))
+                .unwrap_or(retransmit_consts::MAX_RTO)
+                .min(retransmit_consts::MAX_RTO);
+            self.persist_deadline = Some(now + interval);
+        }
+    }
+
+    /// Record a segment occupying sequence space, and start the retransmission timer if it is not running.
+    fn on_send(&mut self, now: Instant, start: SeqNumber, end: SeqNumber) {
+        self.retransmit.push(start, end, now, start < self.snd_max);
+        self.snd_max = self.snd_max.max(end);
+
+        if self.rto_deadline.is_none() {
+            self.rto_deadline = Some(now + self.rtt.rto());
+        }
+    }
+
+    /// Returns the next segment to be sent, if any.
+    pub fn poll_transmit(&mut self, now: Instant) -> Option<Segment> {
+        match self.state {
+            State::Closed | State::Listen => return None,
+            State::SynSent | State::SynReceived => {
+                if self.snd_nxt != self.iss {
+                    // Only a segment falling below the window, such as the SYN-ACK of a
+                    // simultaneous open, is answered before the handshake completes.
+                    if self.state != State::SynReceived || !self.ack_pending {
+                        return None;
+                    }
+                    self.ack_pending = false;
+                    let repr = self.repr(Control::None, self.snd_nxt);
+                    return Some(self.transmit(now, repr, vec![]));
+                }
+
+                let mut repr = self.repr(Control::Syn, self.iss);
+                repr.max_seg_size = Some(local_mss(self.local.ip()) as u16);
+                if self.state == State::SynSent {
+                    repr.ack_number = None;
+                    repr.sack_permitted = true;
+                    self.rcv_wnd_shift = self.local_wnd_shift();
+                    repr.window_scale = Some(self.rcv_wnd_shift);
+                } else {
+                    repr.sack_permitted = self.sack_enabled;
+                    repr.window_scale = self.window_scaling.then_some(self.rcv_wnd_shift);
+                }
+                // The window of a SYN is never scaled.
+                repr.window = self.rcv_wnd().min(consts::MAX_WINDOW) as u16;
+
+                self.snd_nxt = self.iss + 1;
+                self.ack_pending = false;
+                self.on_send(now, self.iss, self.snd_nxt);
+                return Some(self.transmit(now, repr, vec![]));
+            }
+            _ => {}
+        }
+
+        let synchronized = matches!(
+            self.state,
+            State::Established | State::CloseWait | State::FinWait1 | State::Closing | State::LastAck
+        );
+        // Nothing is sent past the FIN, unless a timeout went back before it.
+        let can_send = synchronized && self.fin_seq.is_none_or(|fin_seq| self.snd_nxt <= fin_seq);
+
+        if self.keep_alive_deadline.is_none() && matches!(self.state, State::Established | State::CloseWait) {
+            self.keep_alive_deadline = self.keep_alive.map(|keep_alive| now + keep_alive.idle);
+        }
+
+        self.update_persist_timer(now);
+        if self.probe_pending || self.keep_alive_pending {
+            // A window or keep-alive probe: an old sequence number the peer must answer with an ACK.
+            self.probe_pending = false;
+            self.keep_alive_pending = false;
+            self.ack_pending = false;
+            let repr = self.repr(Control::None, self.snd_una - 1);
+            return Some(self.transmit(now, repr, vec![]));
+        }
+
+        if synchronized && self.fast_retransmit {
+            self.fast_retransmit = false;
+
+            // Send the oldest unacknowledged segment again, or the next hole when the peer sent SACKs,
+            // without going back with `snd_nxt`.
+            let data_end = self.snd_una + self.sent_data_len();
+            let (seq, hole_end) = match self.next_hole(self.rexmit_next.max(self.snd_una)) {
+                Some(hole) => hole,
+                None if self.rexmit_next <= self.snd_una => (self.snd_una, self.snd_nxt),
+                None => (self.snd_una, self.snd_una),
+            };
+            let len = if seq < data_end {
+                (data_end - seq).min(hole_end - seq).min(self.remote_mss)
+            } else {
+                0
+            };
+            let fin = self.fin_seq == Some(seq + len) && hole_end > seq + len && self.snd_nxt > seq + len;
+
+            if len > 0 || fin {
+                let control = if fin { Control::Fin } else { Control::None };
+                let mut payload = vec![0; len];
+                self.tx_buffer.read_allocated(seq - self.snd_una, &mut payload);
+                let end = seq + len + fin as usize;
+                self.rexmit_next = end;
+
+                self.ack_pending = false;
+                self.on_send(now, seq, end);
+
+                let repr = self.repr(control, seq);
+                return Some(self.transmit(now, repr, payload));
+            }
+        }
+
+        if can_send {
+            let sent = self.sent_data_len();
+            let unsent = self.tx_buffer.len() - sent;
+            let len = unsent.min(self.available_window()).min(self.remote_mss);
+            let send_fin = self.fin_queued && len == unsent;
+            // Nagle's algorithm: hold a small segment while data is in flight (RFC 1122 section 4.2.3.4).
+            let nagle = !self.nodelay && !send_fin && len < self.remote_mss && self.snd_una < self.snd_nxt;
+            let paced = self.pacing && self.pacing_next.is_some_and(|next| now < next);
+
+            if ((len > 0 && !nagle) || send_fin) && !paced {
+                let seq = self.snd_nxt;
+                let control = if send_fin { Control::Fin } else { Control::None };
+                let mut payload = vec![0; len];
+                self.tx_buffer.read_allocated(sent, &mut payload);
+
+                self.snd_nxt += len;
+                if send_fin {
+                    self.fin_seq = Some(self.snd_nxt);
+                    self.snd_nxt += 1;
+                }
+                self.ack_pending = false;
+                self.on_send(now, seq, self.snd_nxt);
+                if self.pacing {
+                    self.pacing_next = self.pacing_interval(len).map(|interval| now + interval);
+                }
+
+                let mut repr = self.repr(control, seq);
+                // The segment carrying the last of the data queued is pushed, along with the FIN or not.
+                repr.flags.set(TcpFlags::PSH, len > 0 && len == unsent);
+                return Some(self.transmit(now, repr, payload));
+            }
+        }
+
+        if self.ack_pending {
+            self.ack_pending = false;
+            let repr = self.repr(Control::None, self.snd_nxt);
+            return Some(self.transmit(now, repr, vec![]));
+        }
+
+        None
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use std::net::{Ipv4Addr, SocketAddr};
+    use std::time::{Duration, Instant};
+
+    use super::{consts, Connection, Segment, State};
+    use crate::icmpv4::packet::{DestinationUnreachablePacketCode, ErrorMessage};
+    use crate::options::KeepAlive;
+    use crate::tcp::repr::Control;
+    use crate::tcp::retransmit::consts as retransmit_consts;
+    use crate::tcp::seq::SeqNumber;
+
+    const CLIENT_ADDR: Ipv4Addr = Ipv4Addr::new(192, 168, 233, 234);
+    const SERVER_ADDR: Ipv4Addr = Ipv4Addr::new(192, 168, 233, 233);
+
+    fn deliver(now: Instant, segment: Segment, to: &mut Connection) -> Option<Segment> {
+        to.on_segment(
+            now,
+            segment.src_addr,
+            segment.dest_addr,
+            &segment.repr,
+            &segment.payload,
+        )
+    }
+
+    /// Exchange segments until both connections are quiet.
+    fn exchange(now: Instant, a: &mut Connection, b: &mut Connection) {
+        loop {
+            let mut quiet = true;
+            while let Some(segment) = a.poll_transmit(now) {
+                quiet = false;
+                assert!(deliver(now, segment, b).is_none());
+            }
+            while let Some(segment) = b.poll_transmit(now) {
+                quiet = false;
+                assert!(deliver(now, segment, a).is_none());
+            }
+            if quiet {
+                break;
+            }
+        }
+    }
+
+    fn established(now: Instant) -> (Connection, Connection) {
+        let mut server = Connection::listen(SocketAddr::from((Ipv4Addr::UNSPECIFIED, 80)), SeqNumber(1000));
+        let mut client = Connection::connect(
+            SocketAddr::from((CLIENT_ADDR, 40000)),
+            SocketAddr::from((SERVER_ADDR, 80)),
+            SeqNumber(u32::MAX - 1),
+        );
+
+        exchange(now, &mut client, &mut server);
+        (client, server)
+    }
+
+    #[test]
+    fn handshake() {
+        let now = Instant::now();
+        let (client, server) = established(now);
+
+        assert_eq!(client.state(), State::Established);
+        assert_eq!(server.state(), State::Established);
+        assert_eq!(server.local(), SocketAddr::from((SERVER_ADDR, 80)));
+        assert_eq!(server.remote(), SocketAddr::from((CLIENT_ADDR, 40000)));
+        assert_eq!(client.remote_mss, consts::LOCAL_MSS);
+    }
+
+    #[test]
+    fn transfer_and_close() {
+        let now = Instant::now();
+        let (mut client, mut server) = established(now);
+
+        let data: Vec<u8> = (0..4000).map(|i| i as u8).collect();
+        assert_eq!(client.send(&data), data.len());
+        exchange(now, &mut client, &mut server);
+
+        let mut received = vec![0; 8000];
+        assert_eq!(server.recv(&mut received), data.len());
+        assert_eq!(&received[..data.len()], data.as_slice());
+        assert_eq!(client.send_queue(), 0);
+
+        client.close();
+        exchange(now, &mut client, &mut server);
+        assert_eq!(client.state(), State::FinWait2);
+        assert_eq!(server.state(), State::CloseWait);
+
+        server.close();
+        exchange(now, &mut client, &mut server);
+        assert_eq!(client.state(), State::TimeWait);
+        assert_eq!(server.state(), State::Closed);
+
+        client.on_timer(now + consts::TIME_WAIT_TIMEOUT);
+        assert_eq!(client.state(), State::Closed);
+    }
+
+    #[test]
+    fn simultaneous_close() {
+        let now = Instant::now();
+        let (mut client, mut server) = established(now);
+
+        client.close();
+        server.close();
+
+        let client_fin = client.poll_transmit(now).unwrap();
+        let server_fin = server.poll_transmit(now).unwrap();
+        assert_eq!(client_fin.repr.control, Control::Fin);
+        assert_eq!(server_fin.repr.control, Control::Fin);
+
+        deliver(now, client_fin, &mut server);
+        deliver(now, server_fin, &mut client);
+        assert_eq!(client.state(), State::Closing);
+        assert_eq!(server.state(), State::Closing);
+
+        exchange(now, &mut client, &mut server);
+        assert_eq!(client.state(), State::TimeWait);
+        assert_eq!(server.state(), State::TimeWait);
+    }
+
+    #[test]
+    fn reset() {
+        let now = Instant::now();
+        let (mut client, mut server) = established(now);
+
+        let rst = client.abort().unwrap();
+        assert_eq!(rst.repr.control, Control::Rst);
+        assert!(deliver(now, rst, &mut server).is_none());
+
+        assert_eq!(client.state(), State::Closed);
+        assert_eq!(server.state(), State::Closed);
+        assert!(server.was_reset());
+    }
+
+    #[test]
+    fn unacceptable_segment() {
+        let now = Instant::now();
+        let (mut client, mut server) = established(now);
+
+        client.send(b"hello");
+        let mut segment = client.poll_transmit(now).unwrap();
+        segment.repr.seq_number = segment.repr.seq_number.wrapping_add(100_000);
+
+        assert!(deliver(now, segment, &mut server).is_none());
+        assert_eq!(server.recv_queue(), 0);
+
+        // The server answers with a duplicate ACK.
+        let ack = server.poll_transmit(now).unwrap();
+        assert_eq!(ack.payload.len(), 0);
+        assert_eq!(ack.repr.ack_number, Some(server.rcv_nxt.into()));
+    }
+
+    #[test]
+    fn retransmission() {
+        let now = Instant::now();
+        let (mut client, mut server) = established(now);
+
+        client.send(b"lost");
+        let lost = client.poll_transmit(now).unwrap();
+        assert_eq!(lost.payload, b"lost");
+        assert!(client.poll_transmit(now).is_none());
+
+        let rto = client.poll_at().unwrap();
+        assert_eq!(rto, now + retransmit_consts::INITIAL_RTO);
+
+        client.on_timer(rto);
+        let retransmitted = client.poll_transmit(rto).unwrap();
+        assert_eq!(retransmitted.repr.seq_number, lost.repr.seq_number);
+        assert_eq!(retransmitted.payload, lost.payload);
+        assert_eq!(client.poll_at().unwrap(), rto + retransmit_consts::INITIAL_RTO * 2);
+
+        assert!(deliver(rto, retransmitted, &mut server).is_none());
+        exchange(rto, &mut client, &mut server);
+        assert_eq!(client.send_queue(), 0);
+        assert_eq!(client.poll_at(), None);
+
+        let mut buf = [0; 8];
+        assert_eq!(server.recv(&mut buf), 4);
+        assert_eq!(&buf[..4], b"lost");
+    }
+
+    #[test]
+    fn retransmission_timeout() {
+        let mut now = Instant::now();
+        let mut client = Connection::connect(
+            SocketAddr::from((CLIENT_ADDR, 40000)),
+            SocketAddr::from((SERVER_ADDR, 80)),
+            SeqNumber(0),
+        );
+
+        for _ in 0..=retransmit_consts::MAX_RETRANSMISSIONS {
+            assert_eq!(client.poll_transmit(now).unwrap().repr.control, Control::Syn);
+            now = client.poll_at().unwrap();
+            client.on_timer(now);
+        }
+
+        assert_eq!(client.state(), State::Closed);
+        assert!(client.was_timed_out());
+        assert!(client.poll_transmit(now).is_none());
+    }
+
+    #[test]
+    fn fast_retransmit() {
+        let now = Instant::now();
+        let (mut client, mut server) = established(now);
+
+        let data: Vec<u8> = (0..3 * consts::LOCAL_MSS).map(|i| i as u8).collect();
+        client.send(&data);
+
+        let lost = client.poll_transmit(now).unwrap();
+        let second = client.poll_transmit(now).unwrap();
+        let third = client.poll_transmit(now).unwrap();
+        assert!(client.poll_transmit(now).is_none());
+
+        deliver(now, second, &mut server);
+        let dup_ack = server.poll_transmit(now).unwrap();
+        deliver(now, third, &mut server);
+        assert!(server.poll_transmit(now).is_some());
+
+        for _ in 0..consts::DUP_ACK_THRESHOLD {
+            deliver(now, dup_ack.clone(), &mut client);
+        }
+
+        let cwnd = client.congestion_control().cwnd();
+        let retransmitted = client.poll_transmit(now).unwrap();
+        assert_eq!(retransmitted, lost);
+        assert!(client.poll_transmit(now).is_none());
+
+        // The retransmission fills the hole in front of the segments held by the assembler.
+        deliver(now, retransmitted, &mut server);
+        let ack = server.poll_transmit(now).unwrap();
+        assert_eq!(ack.repr.ack_number, Some((SeqNumber(u32::MAX) + data.len()).into()));
+        deliver(now, ack, &mut client);
+        assert_eq!(client.send_queue(), 0);
+        assert!(client.congestion_control().cwnd() < cwnd);
+
+        let mut received = vec![0; data.len()];
+        assert_eq!(server.recv(&mut received), data.len());
+        assert_eq!(received, data);
+    }
+
+    #[test]
+    fn out_of_order() {
+        let now = Instant::now();
+        let (mut client, mut server) = established(now);
+
+        let data: Vec<u8> = (0..3 * consts::LOCAL_MSS).map(|i| i as u8).collect();
+        client.send(&data);
+        client.close();
+
+        let mut segments = vec![];
+        while let Some(segment) = client.poll_transmit(now) {
+            segments.push(segment);
+        }
+        assert_eq!(segments.len(), 3);
+        assert_eq!(segments[2].repr.control, Control::Fin);
+
+        for segment in segments.into_iter().rev() {
+            deliver(now, segment, &mut server);
+            assert_eq!(server.poll_transmit(now).unwrap().repr.control, Control::None);
+        }
+
+        assert_eq!(server.state(), State::CloseWait);
+        let mut received = vec![0; data.len()];
+        assert_eq!(server.recv(&mut received), data.len());
+        assert_eq!(received, data);
+    }
+
+    #[test]
+    fn window() {
+        let now = Instant::now();
+        let mut server = Connection::listen(SocketAddr::from((Ipv4Addr::UNSPECIFIED, 80)), SeqNumber(1000));
+        server.set_recv_buffer_size(1000);
+        let mut client = Connection::connect(
+            SocketAddr::from((CLIENT_ADDR, 40000)),
+            SocketAddr::from((SERVER_ADDR, 80)),
+            SeqNumber(0),
+        );
+        client.set_send_buffer_size(2500);
+        exchange(now, &mut client, &mut server);
+
+        let data: Vec<u8> = (0..3000).map(|i| i as u8).collect();
+        assert_eq!(client.send(&data), 2500);
+        exchange(now, &mut client, &mut server);
+        assert_eq!(server.recv_queue(), 1000);
+        assert_eq!(client.send_queue(), 1500);
+
+        let mut received = vec![0; 3000];
+        assert_eq!(server.recv(&mut received[..600]), 600);
+        exchange(now, &mut client, &mut server);
+        assert_eq!(server.recv_queue(), 1000);
+        assert_eq!(client.send_queue(), 900);
+    }
+
+    #[test]
+    fn selective_retransmit() {
+        let now = Instant::now();
+        let (mut client, mut server) = established(now);
+        assert!(client.sack_enabled && server.sack_enabled);
+
+        let mut buf = vec![0; consts::DEFAULT_BUFFER_SIZE];
+        while client.congestion_control().cwnd() < 6 * consts::LOCAL_MSS {
+            client.send(&buf[..4 * consts::LOCAL_MSS]);
+            exchange(now, &mut client, &mut server);
+            server.recv(&mut buf);
+            exchange(now, &mut client, &mut server);
+        }
+
+        client.send(&buf[..6 * consts::LOCAL_MSS]);
+        let segments: Vec<_> = (0..6).map(|_| client.poll_transmit(now).unwrap()).collect();
+
+        let mut dup_acks = vec![];
+        for segment in [&segments[1], &segments[3], &segments[4], &segments[5]] {
+            deliver(now, segment.clone(), &mut server);
+            dup_acks.push(server.poll_transmit(now).unwrap());
+        }
+        assert_eq!(
+            dup_acks[3].repr.sack_ranges[0],
+            Some((
+                segments[3].repr.seq_number,
+                segments[5].repr.seq_number + consts::LOCAL_MSS as u32
+            ))
+        );
+        assert_eq!(
+            dup_acks[3].repr.sack_ranges[1].map(|(start, _)| start),
+            Some(segments[1].repr.seq_number)
+        );
+
+        for dup_ack in dup_acks.drain(..3) {
+            deliver(now, dup_ack, &mut client);
+        }
+        assert_eq!(client.poll_transmit(now).unwrap(), segments[0]);
+        assert!(client.poll_transmit(now).is_none());
+
+        deliver(now, dup_acks.remove(0), &mut client);
+        assert_eq!(
+            client.poll_transmit(now).unwrap().repr.seq_number,
+            segments[2].repr.seq_number
+        );
+
+        deliver(now, segments[0].clone(), &mut server);
+        deliver(now, segments[2].clone(), &mut server);
+        exchange(now, &mut client, &mut server);
+        assert_eq!(client.send_queue(), 0);
+        assert_eq!(server.recv_queue(), 6 * consts::LOCAL_MSS);
+    }
+
+    #[test]
+    fn window_scaling() {
+        let now = Instant::now();
+        let mut server = Connection::listen(SocketAddr::from((Ipv4Addr::UNSPECIFIED, 80)), SeqNumber(1000));
+        server.set_recv_buffer_size(1 << 20);
+        let mut client = Connection::connect(
+            SocketAddr::from((CLIENT_ADDR, 40000)),
+            SocketAddr::from((SERVER_ADDR, 80)),
+            SeqNumber(0),
+        );
+
+        let syn = client.poll_transmit(now).unwrap();
+        assert_eq!(syn.repr.window_scale, Some(0));
+        deliver(now, syn, &mut server);
+
+        let syn_ack = server.poll_transmit(now).unwrap();
+        assert_eq!(syn_ack.repr.window_scale, Some(5));
+        assert_eq!(syn_ack.repr.window as usize, consts::MAX_WINDOW);
+        deliver(now, syn_ack, &mut client);
+        exchange(now, &mut client, &mut server);
+
+        assert_eq!(client.state(), State::Established);
+        client.send(b"x");
+        exchange(now, &mut client, &mut server);
+        assert_eq!(client.snd_wnd, (1 << 20) - 32);
+
+        // Without the option from the peer, neither end scales.
+        let mut server = Connection::listen(SocketAddr::from((Ipv4Addr::UNSPECIFIED, 80)), SeqNumber(1000));
+        server.set_recv_buffer_size(1 << 20);
+        let mut client = Connection::connect(
+            SocketAddr::from((CLIENT_ADDR, 40000)),
+            SocketAddr::from((SERVER_ADDR, 80)),
+            SeqNumber(0),
+        );
+
+        let mut syn = client.poll_transmit(now).unwrap();
+        syn.repr.window_scale = None;
+        deliver(now, syn, &mut server);
+        let syn_ack = server.poll_transmit(now).unwrap();
+        assert_eq!(syn_ack.repr.window_scale, None);
+        deliver(now, syn_ack, &mut client);
+        client.send(b"x");
+        exchange(now, &mut client, &mut server);
+        // The right edge does not move for a single octet.
+        assert_eq!(client.snd_wnd, consts::MAX_WINDOW - 1);
+    }
+
+    #[test]
+    fn timestamps() {
+        let now = Instant::now();
+        let (mut client, mut server) = established(now);
+        assert!(client.timestamps && server.timestamps);
+
+        // The echoed timestamp measures the RTT of a retransmitted segment.
+        client.send(b"lost");
+        client.poll_transmit(now).unwrap();
+        let rto = client.poll_at().unwrap();
+        client.on_timer(rto);
+        let retransmitted = client.poll_transmit(rto).unwrap();
+
+        let later = rto + Duration::from_millis(100);
+        deliver(later, retransmitted.clone(), &mut server);
+        let ack = server.poll_transmit(later).unwrap();
+        assert_eq!(ack.repr.timestamps.unwrap().1, retransmitted.repr.timestamps.unwrap().0);
+        deliver(later, ack, &mut client);
+        assert!(client.rtt().srtt().unwrap() > Duration::from_millis(10));
+
+        // An old duplicate is rejected by PAWS even though its sequence number is acceptable.
+        client.send(b"new");
+        let mut segment = client.poll_transmit(later).unwrap();
+        let tsval = segment.repr.timestamps.unwrap().0;
+        segment.repr.timestamps = Some((tsval.wrapping_sub(1_000_000), 0));
+        deliver(later, segment, &mut server);
+        assert_eq!(server.recv_queue(), 4);
+        assert!(server.poll_transmit(later).is_some());
+    }
+
+    #[test]
+    fn nagle() {
+        let now = Instant::now();
+        let (mut client, mut server) = established(now);
+
+        client.send(b"a");
+        let first = client.poll_transmit(now).unwrap();
+        client.send(b"b");
+        client.send(b"c");
+        assert!(client.poll_transmit(now).is_none());
+
+        deliver(now, first, &mut server);
+        deliver(now, server.poll_transmit(now).unwrap(), &mut client);
+        assert_eq!(client.poll_transmit(now).unwrap().payload, b"bc");
+
+        client.set_nodelay(true);
+        client.send(b"d");
+        assert_eq!(client.poll_transmit(now).unwrap().payload, b"d");
+    }
+
+    #[test]
+    fn persist() {
+        let now = Instant::now();
+        let mut server = Connection::listen(SocketAddr::from((Ipv4Addr::UNSPECIFIED, 80)), SeqNumber(1000));
+        server.set_recv_buffer_size(1000);
+        let mut client = Connection::connect(
+            SocketAddr::from((CLIENT_ADDR, 40000)),
+            SocketAddr::from((SERVER_ADDR, 80)),
+            SeqNumber(0),
+        );
+        exchange(now, &mut client, &mut server);
+
+        assert_eq!(client.send(&[0; 2000]), 2000);
+        exchange(now, &mut client, &mut server);
+        assert_eq!(server.recv_queue(), 1000);
+        assert_eq!(client.snd_wnd, 0);
+        assert!(client.poll_transmit(now).is_none());
+
+        // The peer keeps the window closed, the probes back off.
+        let first = client.poll_at().expect("the persist timer");
+        client.on_timer(first);
+        let probe = client.poll_transmit(first).expect("a window probe");
+        assert!(probe.payload.is_empty());
+        deliver(first, probe, &mut server);
+        let ack = server.poll_transmit(first).expect("an ack");
+        assert_eq!(ack.repr.window, 0);
+        deliver(first, ack, &mut client);
+        assert!(client.poll_transmit(first).is_none());
+
+        let second = client.poll_at().expect("the persist timer");
+        assert!(second - first > first - now);
+
+        // The window update is lost, the next probe discovers the open window.
+        let mut buf = [0; 1000];
+        assert_eq!(server.recv(&mut buf), 1000);
+        assert!(server.poll_transmit(first).is_some());
+
+        client.on_timer(second);
+        let probe = client.poll_transmit(second).expect("a window probe");
+        deliver(second, probe, &mut server);
+        exchange(second, &mut client, &mut server);
+        assert_eq!(server.recv_queue(), 1000);
+        assert_eq!(client.send_queue(), 0);
+        assert!(client.poll_at().is_none());
+    }
+
+    #[test]
+    fn half_close() {
+        let now = Instant::now();
+        let (mut client, mut server) = established(now);
+
+        client.close();
+        exchange(now, &mut client, &mut server);
+        assert_eq!(client.state(), State::FinWait2);
+        assert!(!client.may_send() && client.may_recv());
+        assert!(server.may_send() && !server.may_recv());
+
+        assert_eq!(server.send(b"response"), 8);
+        exchange(now, &mut client, &mut server);
+        let mut buf = [0; 16];
+        assert_eq!(client.recv(&mut buf), 8);
+        assert_eq!(&buf[..8], b"response");
+
+        client.shutdown_read();
+        assert_eq!(server.send(&[0; 4000]), 4000);
+        exchange(now, &mut client, &mut server);
+        assert_eq!(client.recv_queue(), 0);
+        assert_eq!(server.send_queue(), 0);
+
+        server.close();
+        exchange(now, &mut client, &mut server);
+        assert_eq!(client.state(), State::TimeWait);
+        assert_eq!(server.state(), State::Closed);
+    }
+
+    #[test]
+    fn simultaneous_open() {
+        let now = Instant::now();
+        let mut a = Connection::connect(
+            SocketAddr::from((CLIENT_ADDR, 40000)),
+            SocketAddr::from((SERVER_ADDR, 40001)),
+            SeqNumber(100),
+        );
+        let mut b = Connection::connect(
+            SocketAddr::from((SERVER_ADDR, 40001)),
+            SocketAddr::from((CLIENT_ADDR, 40000)),
+            SeqNumber(300),
+        );
+
+        // The SYNs cross, each end answers with a SYN-ACK.
+        let a_syn = a.poll_transmit(now).unwrap();
+        let b_syn = b.poll_transmit(now).unwrap();
+        assert!(deliver(now, b_syn, &mut a).is_none());
+        assert!(deliver(now, a_syn, &mut b).is_none());
+        assert_eq!(a.state(), State::SynReceived);
+        assert_eq!(b.state(), State::SynReceived);
+
+        let a_syn_ack = a.poll_transmit(now).unwrap();
+        let b_syn_ack = b.poll_transmit(now).unwrap();
+        assert_eq!(a_syn_ack.repr.control, Control::Syn);
+        assert_eq!(a_syn_ack.repr.ack_number, Some(301));
+        assert_eq!(b_syn_ack.repr.ack_number, Some(101));
+
+        assert!(deliver(now, b_syn_ack, &mut a).is_none());
+        assert!(deliver(now, a_syn_ack, &mut b).is_none());
+        let a_ack = a.poll_transmit(now).unwrap();
+        let b_ack = b.poll_transmit(now).unwrap();
+        assert_eq!(a_ack.repr.control, Control::None);
+        assert!(deliver(now, b_ack, &mut a).is_none());
+        assert!(deliver(now, a_ack, &mut b).is_none());
+        assert_eq!(a.state(), State::Established);
+        assert_eq!(b.state(), State::Established);
+
+        exchange(now, &mut a, &mut b);
+        assert!(a.sack_enabled && a.window_scaling && a.timestamps);
+        assert_eq!(a.send(b"ping"), 4);
+        exchange(now, &mut a, &mut b);
+        let mut buf = [0; 16];
+        assert_eq!(b.recv(&mut buf), 4);
+        assert_eq!(a.poll_at(), None);
+    }
+
+    #[test]
+    fn simultaneous_open_crossing() {
+        let now = Instant::now();
+        let mut a = Connection::connect(
+            SocketAddr::from((CLIENT_ADDR, 40000)),
+            SocketAddr::from((SERVER_ADDR, 40001)),
+            SeqNumber(100),
+        );
+        let mut b = Connection::connect(
+            SocketAddr::from((SERVER_ADDR, 40001)),
+            SocketAddr::from((CLIENT_ADDR, 40000)),
+            SeqNumber(300),
+        );
+
+        // The SYN-ACK of `a` reaches `b` before `b` sent its own, its ACK completes the handshake of `b`.
+        let a_syn = a.poll_transmit(now).unwrap();
+        let b_syn = b.poll_transmit(now).unwrap();
+        assert!(deliver(now, b_syn, &mut a).is_none());
+        let a_syn_ack = a.poll_transmit(now).unwrap();
+        assert!(deliver(now, a_syn, &mut b).is_none());
+        assert!(deliver(now, a_syn_ack, &mut b).is_none());
+        assert_eq!(b.state(), State::Established);
+
+        let b_ack = b.poll_transmit(now).unwrap();
+        assert_eq!(b_ack.repr.control, Control::None);
+        assert_eq!(b_ack.repr.ack_number, Some(101));
+        assert!(deliver(now, b_ack, &mut a).is_none());
+        assert_eq!(a.state(), State::Established);
+        assert_eq!(a.poll_at(), None);
+        assert_eq!(b.poll_at(), None);
+    }
+
+    #[test]
+    fn pacing() {
+        let now = Instant::now();
+        let (mut client, mut server) = established(now);
+        client.set_nodelay(true);
+
+        // Take a non-zero RTT sample.
+        let later = now + Duration::from_millis(100);
+        client.send(b"ping");
+        let segment = client.poll_transmit(now).unwrap();
+        deliver(now, segment, &mut server);
+        exchange(later, &mut client, &mut server);
+        let srtt = client.rtt().srtt().unwrap();
+        assert!(srtt > Duration::from_millis(0));
+
+        client.set_pacing(true);
+        let cwnd = client.congestion_control().cwnd();
+        client.send(&vec![0; 3 * consts::LOCAL_MSS]);
+        assert!(client.poll_transmit(later).is_some());
+        assert!(client.poll_transmit(later).is_none());
+
+        // Slow start paces at twice the gain.
+        let interval = srtt.mul_f64(consts::LOCAL_MSS as f64 / (2.5 * cwnd as f64));
+        assert_eq!(client.poll_at(), Some(later + interval));
+        assert!(client.poll_transmit(later + interval).is_some());
+        assert!(client.poll_transmit(later + interval).is_none());
+    }
+
+    #[test]
+    fn receiver_silly_window() {
+        let now = Instant::now();
+        let mut server = Connection::listen(SocketAddr::from((Ipv4Addr::UNSPECIFIED, 80)), SeqNumber(1000));
+        server.set_recv_buffer_size(4000);
+        let mut client = Connection::connect(
+            SocketAddr::from((CLIENT_ADDR, 40000)),
+            SocketAddr::from((SERVER_ADDR, 80)),
+            SeqNumber(0),
+        );
+        exchange(now, &mut client, &mut server);
+
+        client.send(&[0; 6000]);
+        exchange(now, &mut client, &mut server);
+        assert_eq!(server.recv_queue(), 4000);
+        assert_eq!(client.snd_wnd, 0);
+
+        // Small reads leave the window closed.
+        let mut buf = [0; 1000];
+        assert_eq!(server.recv(&mut buf[..100]), 100);
+        assert!(server.poll_transmit(now).is_none());
+        assert_eq!(server.recv(&mut buf[..1000]), 1000);
+        assert!(server.poll_transmit(now).is_none());
+
+        // A full segment of space opens it.
+        assert_eq!(server.recv(&mut buf[..360]), 360);
+        let update = server.poll_transmit(now).unwrap();
+        assert_eq!(update.repr.window, 1460);
+        deliver(now, update, &mut client);
+        let segment = client.poll_transmit(now).unwrap();
+        assert_eq!(segment.payload.len(), 1460);
+    }
+
+    #[test]
+    fn keep_alive() {
+        let now = Instant::now();
+        let (mut client, mut server) = established(now);
+        client.set_keep_alive(Some(KeepAlive {
+            idle: Duration::from_secs(10),
+            interval: Duration::from_secs(1),
+            probes: 2,
+        }));
+        assert!(client.poll_transmit(now).is_none());
+        assert_eq!(client.poll_at(), Some(now + Duration::from_secs(10)));
+
+        // An answered probe restarts the idle time.
+        let idle = now + Duration::from_secs(10);
+        client.on_timer(idle);
+        let probe = client.poll_transmit(idle).expect("a keep-alive probe");
+        assert!(probe.payload.is_empty());
+        assert_eq!(SeqNumber(probe.repr.seq_number), client.snd_una - 1);
+        deliver(idle, probe, &mut server);
+        let ack = server.poll_transmit(idle).expect("an ack");
+        deliver(idle, ack, &mut client);
+        assert_eq!(client.poll_at(), Some(idle + Duration::from_secs(10)));
+
+        // Unanswered probes drop the connection.
+        let idle = idle + Duration::from_secs(10);
+        for i in 0..2 {
+            let at = idle + Duration::from_secs(i);
+            client.on_timer(at);
+            assert!(client.poll_transmit(at).is_some());
+        }
+        client.on_timer(idle + Duration::from_secs(2));
+        assert_eq!(client.state(), State::Closed);
+        assert!(client.was_timed_out());
+    }
+
+    #[test]
+    fn icmp_error() {
+        let now = Instant::now();
+        let (mut client, mut server) = established(now);
+        client.set_nodelay(true);
+
+        assert_eq!(client.send(&[0; 2000]), 2000);
+        let mut segments = vec![];
+        while let Some(segment) = client.poll_transmit(now) {
+            segments.push(segment);
+        }
+        assert_eq!(segments.len(), 2);
+
+        // Errors about sequence numbers not in flight are ignored.
+        let unreachable = ErrorMessage::Unreachable(DestinationUnreachablePacketCode::HostUnreachable);
+        client.on_icmp_error(unreachable, client.snd_max);
+        assert!(client.icmp_error().is_none());
+        client.on_icmp_error(unreachable, client.snd_una);
+        assert_eq!(client.icmp_error(), Some(unreachable));
+        assert_eq!(client.state(), State::Established);
+
+        // A smaller path MTU sends the outstanding data again in smaller segments.
+        client.on_icmp_error(ErrorMessage::FragmentationNeeded(1000), client.snd_una);
+        assert_eq!(client.remote_mss, 960);
+        let resent = client.poll_transmit(now).expect("a retransmission");
+        assert_eq!(SeqNumber(resent.repr.seq_number), client.snd_una);
+        assert_eq!(resent.payload.len(), 960);
+
+        for segment in segments.into_iter().chain(Some(resent)) {
+            deliver(now, segment, &mut server);
+        }
+        exchange(now, &mut client, &mut server);
+        assert_eq!(server.recv_queue(), 2000);
+        assert!(client.icmp_error().is_none());
+
+        // A hard error aborts the connection.
+        assert_eq!(client.send(b"ping"), 4);
+        assert!(client.poll_transmit(now).is_some());
+        let port_unreachable = ErrorMessage::Unreachable(DestinationUnreachablePacketCode::PortUnreachable);
+        client.on_icmp_error(port_unreachable, client.snd_una);
+        assert_eq!(client.state(), State::Closed);
+        assert_eq!(client.icmp_error(), Some(port_unreachable));
+        assert!(client.poll_at().is_none());
+    }
+}
@@ -0,0 +1,113 @@
+#[allow(deprecated)]
+use core::hash::{BuildHasher, SipHasher};
+use core::net::SocketAddr;
+#[cfg(feature = "std")]
+use std::collections::hash_map::RandomState;
+
+use crate::tcp::seq::SeqNumber;
+
+pub mod consts {
+    use core::time::Duration;
+
+    pub const COUNTER_PERIOD: Duration = Duration::from_secs(64); // RFC 4987 section 3.6
+    pub const MAX_AGE: u32 = 1; // Counter periods a cookie stays valid after the one it was issued in
+    pub const MSS_TABLE: [u16; 8] = [216, 536, 1024, 1220, 1300, 1400, 1440, 1460];
+}
+
+/// The key of the hashes generating initial sequence numbers and SYN cookies (RFC 6528),
+/// which must be unpredictable to the peers.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Secret {
+    keys: (u64, u64),
+}
+
+impl Secret {
+    /// A key drawn by the caller, such as from the random number generator of the device.
+    pub fn from_keys(k0: u64, k1: u64) -> Self {
+        Self { keys: (k0, k1) }
+    }
+
+    /// A random key, drawn like the keys of the hash maps of the standard library.
+    #[cfg(feature = "std")]
+    pub fn random() -> Self {
+        let state = RandomState::new();
+        Self::from_keys(state.hash_one(0u64), state.hash_one(1u64))
+    }
+}
+
+/// SipHash-2-4 keyed by the secret, the hasher `core` provides.
+#[allow(deprecated)]
+impl BuildHasher for Secret {
+    type Hasher = SipHasher;
+
+    fn build_hasher(&self) -> SipHasher {
+        SipHasher::new_with_keys(self.keys.0, self.keys.1)
+    }
+}
+
+/// Encode the state of a half-open connection in its initial sequence number (RFC 4987 section 3.6):
+/// 5 bits of a slowly increasing counter, 3 bits indexing the peer MSS, and 24 bits of a keyed hash
+/// of the connection identifiers, the counter and the peer's initial sequence number.
+pub fn generate(
+    secret: &Secret,
+    counter: u32,
+    local: SocketAddr,
+    remote: SocketAddr,
+    irs: SeqNumber,
+    mss: u16,
+) -> SeqNumber {
+    let counter = counter & 0x1f;
+    let index = consts::MSS_TABLE.iter().rposition(|entry| *entry <= mss).unwrap_or(0) as u32;
+    SeqNumber(counter << 27 | index << 24 | hash(secret, counter, local, remote, irs))
+}
+
+/// Check the cookie `iss` returned by the peer, returns the peer MSS it encodes if it is valid.
+pub fn check(
+    secret: &Secret,
+    counter: u32,
+    local: SocketAddr,
+    remote: SocketAddr,
+    irs: SeqNumber,
+    iss: SeqNumber,
+) -> Option<u16> {
+    let issued = iss.0 >> 27;
+    if counter.wrapping_sub(issued) & 0x1f > consts::MAX_AGE {
+        return None;
+    }
+
+    if iss.0 & 0xff_ffff != hash(secret, issued, local, remote, irs) {
+        return None;
+    }
+
+    Some(consts::MSS_TABLE[(iss.0 >> 24 & 0x7) as usize])
+}
+
+fn hash(secret: &Secret, counter: u32, local: SocketAddr, remote: SocketAddr, irs: SeqNumber) -> u32 {
+    secret.hash_one((local, remote, counter, irs.0)) as u32 & 0xff_ffff
+}
+
+#[cfg(test)]
+mod tests {
+    use core::net::{Ipv4Addr, SocketAddr};
+
+    use super::{check, generate, Secret};
+    use crate::tcp::seq::SeqNumber;
+
+    #[test]
+    fn cookie() {
+        let secret = Secret::from_keys(0x0123_4567_89ab_cdef, 0xfedc_ba98_7654_3210);
+        let local = SocketAddr::from((Ipv4Addr::new(192, 168, 233, 233), 80));
+        let remote = SocketAddr::from((Ipv4Addr::new(192, 168, 233, 234), 40000));
+        let irs = SeqNumber(1000);
+
+        let iss = generate(&secret, 31, local, remote, irs, 1460);
+        assert_eq!(check(&secret, 31, local, remote, irs, iss), Some(1460));
+        assert_eq!(check(&secret, 32, local, remote, irs, iss), Some(1460));
+        assert_eq!(check(&secret, 33, local, remote, irs, iss), None);
+        assert_eq!(check(&secret, 31, local, remote, SeqNumber(1001), iss), None);
+        assert_eq!(check(&secret, 31, remote, local, irs, iss), None);
+
+        let iss = generate(&secret, 0, local, remote, irs, 1000);
+        assert_eq!(check(&secret, 0, local, remote, irs, iss), Some(536));
+    }
+}
@@ -1,16 +1,30 @@
-use std::fmt::{Display, Formatter};
+use core::fmt::{Display, Formatter};
 
 #[derive(Debug)]
 pub enum Error {
     InvalidDataOffset,
+    InvalidOptionLen,
+    InvalidChecksum,
+    InvalidPort,
+    InvalidFlags,
+    AddressInUse,
+    InvalidAddress,
+    UnsupportedOption,
 }
 
 impl Display for Error {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
         match self {
             Error::InvalidDataOffset => write!(f, "invalid data offset"),
+            Error::InvalidOptionLen => write!(f, "invalid option length"),
+            Error::InvalidChecksum => write!(f, "invalid checksum"),
+            Error::InvalidPort => write!(f, "invalid port"),
+            Error::InvalidFlags => write!(f, "invalid flags"),
+            Error::AddressInUse => write!(f, "address in use"),
+            Error::InvalidAddress => write!(f, "invalid address"),
+            Error::UnsupportedOption => write!(f, "unsupported option"),
         }
     }
 }
 
-impl std::error::Error for Error {}
+impl core::error::Error for Error {}
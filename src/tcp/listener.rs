@@ -0,0 +1,154 @@
+use std::io::{Error as IOError, ErrorKind};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::error::Result;
+use crate::ipv4::interface::{consts as interface_consts, Interface};
+use crate::net_device::tun::TunDevice;
+use crate::net_device::Device;
+use crate::options::SocketConfig;
+use crate::tcp::socket::{consts, ListenerHandle};
+use crate::tcp::stream::TcpStream;
+
+/// A TCP listener bound to a port of an interface.
+///
+/// The listener completes handshakes on its own while the interface is polled,
+/// and queues up to `backlog` connections until they are accepted.
+pub struct TcpListener<D: Device = TunDevice> {
+    interface: Arc<Mutex<Interface<D>>>,
+    handle: ListenerHandle,
+    local: SocketAddr,
+    nonblocking: bool,
+    linger: Option<Duration>,
+}
+
+impl<D: Device> TcpListener<D> {
+    /// Listen on `port` of every IPv4 address of the interface, with the default backlog.
+    pub fn bind(interface: &Arc<Mutex<Interface<D>>>, port: u16) -> Result<Self> {
+        Self::bind_with_backlog(interface, port, consts::DEFAULT_BACKLOG)
+    }
+
+    pub fn bind_with_backlog(interface: &Arc<Mutex<Interface<D>>>, port: u16, backlog: usize) -> Result<Self> {
+        Self::bind_with_config(interface, Ipv4Addr::UNSPECIFIED, port, backlog, SocketConfig::default())
+    }
+
+    /// Listen on `port` of `addr`, the accepted streams inherit the options of `config`.
+    /// An unspecified address listens on every address of its family.
+    pub fn bind_with_config(
+        interface: &Arc<Mutex<Interface<D>>>,
+        addr: impl Into<IpAddr>,
+        port: u16,
+        backlog: usize,
+        config: SocketConfig,
+    ) -> Result<Self> {
+        let local = SocketAddr::new(addr.into(), port);
+        let linger = config.linger_time();
+        let handle = interface
+            .lock()
+            .unwrap()
+            .sockets_mut()
+            .bind_with_config(local, backlog, config)?;
+
+        Ok(Self {
+            interface: interface.clone(),
+            handle,
+            local,
+            nonblocking: false,
+            linger,
+        })
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local
+    }
+
+    /// In non-blocking mode `accept` fails with `WouldBlock` instead of polling the interface.
+    pub fn set_nonblocking(&mut self, nonblocking: bool) {
+        self.nonblocking = nonblocking;
+    }
+
+    /// Answer SYNs with SYN cookies, so half-open connections take no memory during a SYN flood.
+    pub fn set_syn_cookies(&self, syn_cookies: bool) {
+        let mut interface = self.interface.lock().unwrap();
+        interface.sockets_mut().set_syn_cookies(self.handle, syn_cookies);
+    }
+
+    /// Accept an established connection, returns it and the address of the peer.
+    pub fn accept(&self) -> Result<(TcpStream<D>, SocketAddr)> {
+        loop {
+            let mut interface = self.interface.lock().unwrap();
+
+            if let Some(handle) = interface.sockets_mut().accept(self.handle) {
+                drop(interface);
+                let mut stream = TcpStream::new(&self.interface, handle);
+                stream.set_linger(self.linger);
+                let remote = stream.peer_addr();
+                return Ok((stream, remote));
+            }
+
+            if self.nonblocking {
+                let now = interface.now();
+                interface.dispatch(now)?;
+                return Err(IOError::from(ErrorKind::WouldBlock).into());
+            }
+
+            interface.poll_wait(interface_consts::MAX_POLL_WAIT)?;
+        }
+    }
+}
+
+impl<D: Device> Drop for TcpListener<D> {
+    fn drop(&mut self) {
+        let mut interface = self.interface.lock().unwrap();
+
+        for segment in interface.sockets_mut().unbind(self.handle) {
+            let _ = interface.send_segment(&segment);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::ErrorKind;
+    use std::net::Ipv4Addr;
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+
+    use super::TcpListener;
+    use crate::ipv4::interface::Interface;
+    use crate::ipv4::reassembly::Reassembler;
+    use crate::net_device::channel::ChannelDevice;
+    use crate::tcp::stream::TcpStream;
+
+    const CLIENT_ADDR: Ipv4Addr = Ipv4Addr::new(10, 0, 0, 1);
+    const SERVER_ADDR: Ipv4Addr = Ipv4Addr::new(10, 0, 0, 2);
+
+    fn interface(device: ChannelDevice, addr: Ipv4Addr) -> Arc<Mutex<Interface<ChannelDevice>>> {
+        let mut interface = Interface::new(device, Reassembler::default());
+        interface.set_ip_addr(addr);
+        Arc::new(Mutex::new(interface))
+    }
+
+    #[test]
+    fn accept() {
+        let (client_device, mut server_device) = ChannelDevice::pair();
+        server_device.set_nonblocking(true);
+        let client = interface(client_device, CLIENT_ADDR);
+        let server = interface(server_device, SERVER_ADDR);
+
+        let mut listener = TcpListener::bind(&server, 80).unwrap();
+        listener.set_nonblocking(true);
+        let e = listener.accept().err().unwrap();
+        assert_eq!(e.kind(), ErrorKind::WouldBlock);
+
+        let connect = thread::spawn(move || TcpStream::connect(&client, (SERVER_ADDR, 80)).unwrap());
+        listener.set_nonblocking(false);
+        let (stream, remote) = listener.accept().unwrap();
+        let client_stream = connect.join().unwrap();
+
+        assert_eq!(remote, client_stream.local_addr());
+        assert_eq!(stream.local_addr(), (SERVER_ADDR, 80).into());
+        assert_eq!(stream.peer_addr(), client_stream.local_addr());
+    }
+}
@@ -1,2 +1,16 @@
+pub mod assembler;
+pub mod builder;
+pub mod congestion;
+pub mod connection;
+pub mod cookie;
 pub mod error;
+#[cfg(feature = "std")]
+pub mod listener;
 pub mod packet;
+pub mod repr;
+pub mod retransmit;
+pub mod ring_buffer;
+pub mod seq;
+pub mod socket;
+#[cfg(feature = "std")]
+pub mod stream;
@@ -1,8 +1,39 @@
-use std::fmt::{Debug, Formatter};
+use alloc::vec::Vec;
+use core::fmt::{Debug, Formatter};
 
+use bitflags::bitflags;
+
+use crate::checksum::{pseudo_header_checksum, IpAddress};
 use crate::error::Result;
+use crate::ipv4::packet::Protocol;
 use crate::tcp::error::Error;
 
+pub mod consts {
+    pub const MIN_HEADER_LEN: u8 = 5; // Minimum data offset, in 32-bit words
+}
+
+bitflags! {
+    /// The nine control bits of the TCP header (RFC 793, RFC 3168 and RFC 3540)
+    pub struct TcpFlags: u16 {
+        const FIN = 0x001;
+        const SYN = 0x002;
+        const RST = 0x004;
+        const PSH = 0x008;
+        const ACK = 0x010;
+        const URG = 0x020;
+        const ECE = 0x040;
+        const CWR = 0x080;
+        const NS = 0x100;
+    }
+}
+
+#[cfg(feature = "defmt")]
+impl defmt::Format for TcpFlags {
+    fn format(&self, f: defmt::Formatter) {
+        defmt::write!(f, "TcpFlags({=u16:#x})", self.bits());
+    }
+}
+
 pub struct Packet<Buf> {
     buffer: Buf,
 }
@@ -22,9 +53,15 @@ where
     }
 
     pub fn check_len(&self) -> Result<()> {
+        let buf_len = self.buffer.as_ref().len();
+
+        if buf_len < (consts::MIN_HEADER_LEN * 4) as usize {
+            return Err(Error::InvalidDataOffset.into());
+        }
+
         let header_bytes_len: usize = (self.data_offset() * 4) as usize;
 
-        if header_bytes_len > self.buffer.as_ref().len() {
+        if header_bytes_len < (consts::MIN_HEADER_LEN * 4) as usize || header_bytes_len > buf_len {
             return Err(Error::InvalidDataOffset.into());
         }
 
@@ -65,6 +102,11 @@ where
         ((self.buffer.as_ref()[12] & 0x0f) << 2) | (self.buffer.as_ref()[13] >> 6)
     }
 
+    pub fn flags(&self) -> TcpFlags {
+        let bits = u16::from_be_bytes([self.buffer.as_ref()[12], self.buffer.as_ref()[13]]);
+        TcpFlags::from_bits_truncate(bits)
+    }
+
     pub fn urg(&self) -> bool {
         ((self.buffer.as_ref()[13] & 0x20) >> 5) == 1
     }
@@ -101,7 +143,10 @@ where
         u16::from_be_bytes([self.buffer.as_ref()[18], self.buffer.as_ref()[19]])
     }
 
-    // TODO: option method
+    pub fn options(&self) -> OptionIterator<'_> {
+        let header_bytes_len: usize = (self.data_offset() * 4) as usize;
+        OptionIterator::new(&self.buffer.as_ref()[20..header_bytes_len])
+    }
 
     pub fn payload(&self) -> &[u8] {
         let header_bytes_len: usize = (self.data_offset() * 4) as usize;
@@ -138,6 +183,13 @@ where
         self.buffer.as_mut()[13] = (self.buffer.as_mut()[13] & 0x3f) | (reserved << 6);
     }
 
+    /// Set all nine control bits at once, the NS bit shares an octet with the data offset.
+    pub fn set_flags(&mut self, flags: TcpFlags) {
+        let [high, low] = flags.bits().to_be_bytes();
+        self.buffer.as_mut()[12] = (self.buffer.as_mut()[12] & 0xfe) | high;
+        self.buffer.as_mut()[13] = low;
+    }
+
     pub fn set_urg(&mut self, urg: bool) {
         let bit = if urg { 1 } else { 0 };
         self.buffer.as_mut()[13] = (self.buffer.as_mut()[13] & 0xdf) | (bit << 5);
@@ -180,7 +232,9 @@ where
         self.buffer.as_mut()[18..=19].copy_from_slice(urgent_pointer.to_be_bytes().as_ref());
     }
 
-    // TODO: set_option method
+    pub fn set_options(&mut self, options: &[u8]) {
+        self.buffer.as_mut()[20..20 + options.len()].copy_from_slice(options);
+    }
 }
 
 impl<Buf> Packet<Buf>
@@ -195,13 +249,32 @@ where
     pub fn set_payload(&mut self, payload: Buf) {
         self.payload_mut()[..payload.as_ref().len()].copy_from_slice(payload.as_ref());
     }
+
+    /// Compute and fill the checksum, using the pseudo-header of the IPv4 or IPv6 addresses.
+    pub fn fill_checksum<A: IpAddress>(&mut self, src_addr: A, dest_addr: A) {
+        self.set_checksum(0);
+        let segment = self.buffer.as_ref();
+        let checksum_value = pseudo_header_checksum(src_addr, dest_addr, Protocol::Tcp, segment.len(), segment);
+        self.set_checksum(checksum_value);
+    }
+}
+
+impl<Buf> Packet<Buf>
+where
+    Buf: AsRef<[u8]>,
+{
+    /// Validate the checksum, using the pseudo-header of the IPv4 or IPv6 addresses.
+    pub fn verify_checksum<A: IpAddress>(&self, src_addr: A, dest_addr: A) -> bool {
+        let segment = self.buffer.as_ref();
+        pseudo_header_checksum(src_addr, dest_addr, Protocol::Tcp, segment.len(), segment) == 0
+    }
 }
 
 impl<Buf> Debug for Packet<Buf>
 where
     Buf: AsRef<[u8]>,
 {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
         write!(
             f,
             "source port: {:?}, destination port: {:?}, sequence number: {:#x}, acknowledgment number: {:#x}, data offset: {:?}, reserved: {:#x}, URG: {:?}, ACK: {:?}, PSH: {:?}, RST: {:?}, SYN: {:?}, FIN: {:?}, window: {:#x}, checksum: {:#x}, urgent pointer: {:#x}",
@@ -242,8 +315,161 @@ where
     }
 }
 
+pub struct OptionIterator<'buf> {
+    buffer: &'buf [u8],
+    cursor: usize,
+}
+
+impl<'buf> OptionIterator<'buf> {
+    pub fn new(buffer: &'buf [u8]) -> Self {
+        OptionIterator { buffer, cursor: 0 }
+    }
+}
+
+impl<'buf> Iterator for OptionIterator<'buf> {
+    type Item = Result<TcpOption<'buf>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.cursor >= self.buffer.len() {
+            return None;
+        }
+
+        match TcpOption::parse(&self.buffer[self.cursor..]) {
+            Ok((len, option)) => {
+                self.cursor += len;
+                if option == TcpOption::EndOfList {
+                    self.cursor = self.buffer.len();
+                    None
+                } else {
+                    Some(Ok(option))
+                }
+            }
+            Err(err) => {
+                self.cursor = self.buffer.len(); // Stop iterating after a malformed option.
+                Some(Err(err))
+            }
+        }
+    }
+}
+
+mod option_kind {
+    pub const END_OF_LIST: u8 = 0;
+    pub const NO_OPERATION: u8 = 1;
+    pub const MAX_SEGMENT_SIZE: u8 = 2;
+    pub const WINDOW_SCALE: u8 = 3;
+    pub const SACK_PERMITTED: u8 = 4;
+    pub const SACK_RANGE: u8 = 5;
+    pub const TIMESTAMPS: u8 = 8;
+}
+
+/// TCP options defined in RFC 793, RFC 2018 and RFC 7323
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TcpOption<'buf> {
+    EndOfList,
+    NoOperation,
+    MaxSegmentSize(u16),
+    WindowScale(u8),
+    SackPermitted,
+    SackRange([Option<(u32, u32)>; 4]),
+    Timestamps { tsval: u32, tsecr: u32 },
+    Unknown { kind: u8, data: &'buf [u8] },
+}
+
+impl<'buf> TcpOption<'buf> {
+    /// Parse an option from the front of `buffer`, returning the consumed length and the option.
+    pub fn parse(buffer: &'buf [u8]) -> Result<(usize, TcpOption<'buf>)> {
+        let kind = *buffer.first().ok_or(Error::InvalidOptionLen)?;
+
+        match kind {
+            option_kind::END_OF_LIST => return Ok((1, TcpOption::EndOfList)),
+            option_kind::NO_OPERATION => return Ok((1, TcpOption::NoOperation)),
+            _ => {}
+        }
+
+        let length = *buffer.get(1).ok_or(Error::InvalidOptionLen)? as usize;
+        if length < 2 || length > buffer.len() {
+            return Err(Error::InvalidOptionLen.into());
+        }
+        let data = &buffer[2..length];
+
+        let option = match (kind, length) {
+            (option_kind::MAX_SEGMENT_SIZE, 4) => TcpOption::MaxSegmentSize(u16::from_be_bytes([data[0], data[1]])),
+            (option_kind::WINDOW_SCALE, 3) => TcpOption::WindowScale(data[0]),
+            (option_kind::SACK_PERMITTED, 2) => TcpOption::SackPermitted,
+            (option_kind::SACK_RANGE, _) if (length - 2).is_multiple_of(8) && length > 2 && length <= 34 => {
+                let mut ranges = [None; 4];
+                for (range, block) in ranges.iter_mut().zip(data.chunks(8)) {
+                    let left = u32::from_be_bytes([block[0], block[1], block[2], block[3]]);
+                    let right = u32::from_be_bytes([block[4], block[5], block[6], block[7]]);
+                    *range = Some((left, right));
+                }
+                TcpOption::SackRange(ranges)
+            }
+            (option_kind::TIMESTAMPS, 10) => TcpOption::Timestamps {
+                tsval: u32::from_be_bytes([data[0], data[1], data[2], data[3]]),
+                tsecr: u32::from_be_bytes([data[4], data[5], data[6], data[7]]),
+            },
+            (option_kind::MAX_SEGMENT_SIZE, _)
+            | (option_kind::WINDOW_SCALE, _)
+            | (option_kind::SACK_PERMITTED, _)
+            | (option_kind::SACK_RANGE, _)
+            | (option_kind::TIMESTAMPS, _) => return Err(Error::InvalidOptionLen.into()),
+            (kind, _) => TcpOption::Unknown { kind, data },
+        };
+
+        Ok((length, option))
+    }
+
+    /// Returns the number of octets needed to emit the option.
+    pub fn buffer_len(&self) -> usize {
+        match self {
+            TcpOption::EndOfList | TcpOption::NoOperation => 1,
+            TcpOption::MaxSegmentSize(_) => 4,
+            TcpOption::WindowScale(_) => 3,
+            TcpOption::SackPermitted => 2,
+            TcpOption::SackRange(ranges) => 2 + ranges.iter().flatten().count() * 8,
+            TcpOption::Timestamps { .. } => 10,
+            TcpOption::Unknown { data, .. } => 2 + data.len(),
+        }
+    }
+
+    /// Append the option to `buffer`.
+    pub fn emit(&self, buffer: &mut Vec<u8>) {
+        match *self {
+            TcpOption::EndOfList => buffer.push(option_kind::END_OF_LIST),
+            TcpOption::NoOperation => buffer.push(option_kind::NO_OPERATION),
+            TcpOption::MaxSegmentSize(mss) => {
+                buffer.extend_from_slice(&[option_kind::MAX_SEGMENT_SIZE, 4]);
+                buffer.extend_from_slice(mss.to_be_bytes().as_ref());
+            }
+            TcpOption::WindowScale(shift) => {
+                buffer.extend_from_slice(&[option_kind::WINDOW_SCALE, 3, shift]);
+            }
+            TcpOption::SackPermitted => buffer.extend_from_slice(&[option_kind::SACK_PERMITTED, 2]),
+            TcpOption::SackRange(ranges) => {
+                buffer.extend_from_slice(&[option_kind::SACK_RANGE, self.buffer_len() as u8]);
+                for (left, right) in ranges.iter().flatten() {
+                    buffer.extend_from_slice(left.to_be_bytes().as_ref());
+                    buffer.extend_from_slice(right.to_be_bytes().as_ref());
+                }
+            }
+            TcpOption::Timestamps { tsval, tsecr } => {
+                buffer.extend_from_slice(&[option_kind::TIMESTAMPS, 10]);
+                buffer.extend_from_slice(tsval.to_be_bytes().as_ref());
+                buffer.extend_from_slice(tsecr.to_be_bytes().as_ref());
+            }
+            TcpOption::Unknown { kind, data } => {
+                buffer.extend_from_slice(&[kind, self.buffer_len() as u8]);
+                buffer.extend_from_slice(data);
+            }
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
+    use super::{TcpFlags, TcpOption};
+
     #[test]
     fn new_checked() {
         let mut tcp_header_bytes: Vec<u8> = vec![
@@ -273,21 +499,54 @@ mod tests {
         assert_eq!(packet.seq_number(), 0x57162308);
         assert_eq!(packet.ack_number(), 0x60822590);
         assert_eq!(packet.data_offset(), 8);
-        assert_eq!(packet.urg(), false);
-        assert_eq!(packet.ack(), true);
-        assert_eq!(packet.psh(), true);
-        assert_eq!(packet.rst(), false);
-        assert_eq!(packet.syn(), false);
-        assert_eq!(packet.fin(), false);
+        assert!(!packet.urg());
+        assert!(packet.ack());
+        assert!(packet.psh());
+        assert!(!packet.rst());
+        assert!(!packet.syn());
+        assert!(!packet.fin());
         assert_eq!(packet.window(), 0x18eb);
         assert_eq!(packet.checksum(), 0xfe76);
         assert_eq!(packet.urgent_pointer(), 0x0000);
+
+        let mut option_iterator = packet.options();
+        assert_eq!(option_iterator.next().unwrap().unwrap(), TcpOption::NoOperation);
+        assert_eq!(option_iterator.next().unwrap().unwrap(), TcpOption::NoOperation);
+        assert_eq!(
+            option_iterator.next().unwrap().unwrap(),
+            TcpOption::Timestamps {
+                tsval: 0xf151fbc9,
+                tsecr: 0xa810910d
+            }
+        );
+        assert!(option_iterator.next().is_none());
+    }
+
+    #[test]
+    fn option_round_trip() {
+        let options = [
+            TcpOption::MaxSegmentSize(1460),
+            TcpOption::WindowScale(7),
+            TcpOption::SackPermitted,
+            TcpOption::SackRange([Some((1, 2)), Some((3, 4)), None, None]),
+            TcpOption::Timestamps { tsval: 1, tsecr: 2 },
+        ];
+
+        for option in options.iter() {
+            let mut buffer = vec![];
+            option.emit(&mut buffer);
+            assert_eq!(buffer.len(), option.buffer_len());
+            assert_eq!(TcpOption::parse(&buffer).unwrap(), (buffer.len(), *option));
+        }
+
+        assert!(TcpOption::parse(&[2, 4, 0x05]).is_err());
+        assert!(TcpOption::parse(&[3, 4, 0, 0]).is_err());
     }
 
     #[test]
     fn setter() {
-        let data_offset = 5 as usize;
-        let payload_len = 8 as usize;
+        let data_offset = 5usize;
+        let payload_len = 8usize;
         let total_len = data_offset * 4 + payload_len;
 
         let buffer: Vec<u8> = vec![0; total_len];
@@ -312,22 +571,22 @@ mod tests {
         assert_eq!(packet.reserved(), 0b111111);
 
         packet.set_urg(true);
-        assert_eq!(packet.urg(), true);
+        assert!(packet.urg());
 
         packet.set_ack(true);
-        assert_eq!(packet.ack(), true);
+        assert!(packet.ack());
 
         packet.set_psh(true);
-        assert_eq!(packet.psh(), true);
+        assert!(packet.psh());
 
         packet.set_rst(true);
-        assert_eq!(packet.rst(), true);
+        assert!(packet.rst());
 
         packet.set_syn(true);
-        assert_eq!(packet.syn(), true);
+        assert!(packet.syn());
 
         packet.set_fin(true);
-        assert_eq!(packet.fin(), true);
+        assert!(packet.fin());
 
         packet.set_window(0x45bd);
         assert_eq!(packet.window(), 0x45bd);
@@ -341,4 +600,28 @@ mod tests {
         packet.set_payload(vec![1, 2, 3, 4, 5, 6, 7, 8]);
         assert_eq!(packet.payload(), vec![1, 2, 3, 4, 5, 6, 7, 8].as_slice());
     }
+
+    #[test]
+    fn flags() {
+        let mut packet = super::Packet::new_unchecked(vec![0; 20]);
+        packet.set_data_offset(5);
+
+        packet.set_flags(TcpFlags::SYN | TcpFlags::ACK | TcpFlags::NS);
+        assert!(packet.flags().contains(TcpFlags::SYN | TcpFlags::ACK));
+        assert!(!packet.flags().contains(TcpFlags::FIN));
+        assert!(packet.syn());
+        assert!(packet.ack());
+        assert_eq!(packet.data_offset(), 5);
+        assert_eq!(packet.reserved(), 0b000100);
+
+        packet.set_fin(true);
+        assert_eq!(
+            packet.flags(),
+            TcpFlags::SYN | TcpFlags::ACK | TcpFlags::NS | TcpFlags::FIN
+        );
+
+        packet.set_flags(TcpFlags::empty());
+        assert_eq!(packet.flags(), TcpFlags::empty());
+        assert_eq!(packet.data_offset(), 5);
+    }
 }
@@ -0,0 +1,331 @@
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::checksum::IpAddress;
+use crate::error::Result;
+use crate::tcp::error::Error;
+use crate::tcp::packet::{consts, Packet, TcpFlags, TcpOption};
+
+/// The control flag carried by a segment, at most one of SYN, FIN and RST is set at a time.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Control {
+    None,
+    Syn,
+    Fin,
+    Rst,
+}
+
+impl Control {
+    /// Returns the sequence space consumed by the control flag.
+    pub fn len(&self) -> usize {
+        match self {
+            Control::Syn | Control::Fin => 1,
+            _ => 0,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// A high-level representation of a TCP segment header.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Repr {
+    pub src_port: u16,
+    pub dest_port: u16,
+    pub control: Control,
+    /// The flags besides the control one and ACK, which `control` and `ack_number` tell: PSH, URG and the ECN bits.
+    pub flags: TcpFlags,
+    pub seq_number: u32,
+    pub ack_number: Option<u32>,
+    pub window: u16,
+    pub urgent_pointer: u16,
+    pub max_seg_size: Option<u16>,
+    pub window_scale: Option<u8>,
+    pub sack_permitted: bool,
+    pub sack_ranges: [Option<(u32, u32)>; 3],
+    pub timestamps: Option<(u32, u32)>,
+    pub payload_len: usize,
+}
+
+impl Repr {
+    /// Parse and validate a segment received from `src_addr` and sent to `dest_addr`.
+    pub fn parse<Buf, A: IpAddress>(packet: &Packet<Buf>, src_addr: A, dest_addr: A) -> Result<Self>
+    where
+        Buf: AsRef<[u8]>,
+    {
+        packet.check_len()?;
+
+        if packet.src_port() == 0 || packet.dest_port() == 0 {
+            return Err(Error::InvalidPort.into());
+        }
+
+        if !packet.verify_checksum(src_addr, dest_addr) {
+            return Err(Error::InvalidChecksum.into());
+        }
+
+        let control = match (packet.syn(), packet.fin(), packet.rst()) {
+            (false, false, false) => Control::None,
+            (true, false, false) => Control::Syn,
+            (false, true, false) => Control::Fin,
+            (false, false, true) => Control::Rst,
+            _ => return Err(Error::InvalidFlags.into()),
+        };
+
+        let ack_number = if packet.ack() { Some(packet.ack_number()) } else { None };
+
+        let mut repr = Repr {
+            src_port: packet.src_port(),
+            dest_port: packet.dest_port(),
+            control,
+            flags: packet.flags() - control_flags(),
+            seq_number: packet.seq_number(),
+            ack_number,
+            window: packet.window(),
+            urgent_pointer: packet.urgent_pointer(),
+            max_seg_size: None,
+            window_scale: None,
+            sack_permitted: false,
+            sack_ranges: [None; 3],
+            timestamps: None,
+            payload_len: packet.payload().len(),
+        };
+
+        for option in packet.options() {
+            match option? {
+                TcpOption::MaxSegmentSize(mss) => repr.max_seg_size = Some(mss),
+                TcpOption::WindowScale(shift) => repr.window_scale = Some(shift.min(14)),
+                TcpOption::SackPermitted => repr.sack_permitted = true,
+                TcpOption::SackRange(ranges) => repr.sack_ranges.copy_from_slice(&ranges[..3]),
+                TcpOption::Timestamps { tsval, tsecr } => repr.timestamps = Some((tsval, tsecr)),
+                _ => {}
+            }
+        }
+
+        Ok(repr)
+    }
+
+    /// Returns the options to be emitted, in order.
+    fn options(&self) -> Vec<TcpOption<'static>> {
+        let mut options = vec![];
+
+        if let Some(mss) = self.max_seg_size {
+            options.push(TcpOption::MaxSegmentSize(mss));
+        }
+        if let Some(shift) = self.window_scale {
+            options.push(TcpOption::NoOperation);
+            options.push(TcpOption::WindowScale(shift));
+        }
+        if self.sack_permitted {
+            options.push(TcpOption::NoOperation);
+            options.push(TcpOption::NoOperation);
+            options.push(TcpOption::SackPermitted);
+        }
+        if self.sack_ranges.iter().any(Option::is_some) {
+            let mut ranges = [None; 4];
+            ranges[..3].copy_from_slice(&self.sack_ranges);
+            options.push(TcpOption::NoOperation);
+            options.push(TcpOption::NoOperation);
+            options.push(TcpOption::SackRange(ranges));
+        }
+        if let Some((tsval, tsecr)) = self.timestamps {
+            options.push(TcpOption::NoOperation);
+            options.push(TcpOption::NoOperation);
+            options.push(TcpOption::Timestamps { tsval, tsecr });
+        }
+
+        options
+    }
+
+    /// Returns the header length in octets, including padded options.
+    pub fn header_len(&self) -> usize {
+        let options_len: usize = self.options().iter().map(TcpOption::buffer_len).sum();
+        (consts::MIN_HEADER_LEN * 4) as usize + options_len.div_ceil(4) * 4
+    }
+
+    /// Returns the length of the whole segment in octets.
+    pub fn buffer_len(&self) -> usize {
+        self.header_len() + self.payload_len
+    }
+
+    /// Returns the sequence space consumed by the segment.
+    pub fn segment_len(&self) -> usize {
+        self.payload_len + self.control.len()
+    }
+
+    /// Returns every flag of the segment, including the control one and ACK.
+    pub fn all_flags(&self) -> TcpFlags {
+        let mut flags = self.flags - control_flags();
+        flags.set(TcpFlags::SYN, self.control == Control::Syn);
+        flags.set(TcpFlags::FIN, self.control == Control::Fin);
+        flags.set(TcpFlags::RST, self.control == Control::Rst);
+        flags.set(TcpFlags::ACK, self.ack_number.is_some());
+        flags
+    }
+
+    /// Emit the header into `packet` and fill the checksum.
+    ///
+    /// The buffer must be `buffer_len()` octets long, and the payload is expected to be already
+    /// present at `header_len()..`.
+    pub fn emit<Buf, A: IpAddress>(&self, packet: &mut Packet<Buf>, src_addr: A, dest_addr: A)
+    where
+        Buf: AsRef<[u8]> + AsMut<[u8]>,
+    {
+        let header_len = self.header_len();
+
+        let mut options = vec![];
+        for option in self.options() {
+            option.emit(&mut options);
+        }
+        options.resize(header_len - (consts::MIN_HEADER_LEN * 4) as usize, 0);
+
+        packet.set_src_port(self.src_port);
+        packet.set_dest_port(self.dest_port);
+        packet.set_seq_number(self.seq_number);
+        packet.set_ack_number(self.ack_number.unwrap_or(0));
+        packet.set_data_offset((header_len / 4) as u8);
+        packet.set_reserved(0);
+        packet.set_flags(self.all_flags());
+        packet.set_window(self.window);
+        packet.set_urgent_pointer(self.urgent_pointer);
+        packet.set_options(options.as_slice());
+        packet.fill_checksum(src_addr, dest_addr);
+    }
+}
+
+/// The flags a `Repr` keeps in `control` and `ack_number` rather than in `flags`.
+fn control_flags() -> TcpFlags {
+    TcpFlags::SYN | TcpFlags::FIN | TcpFlags::RST | TcpFlags::ACK
+}
+
+impl Default for Repr {
+    fn default() -> Self {
+        Self {
+            src_port: 0,
+            dest_port: 0,
+            control: Control::None,
+            flags: TcpFlags::empty(),
+            seq_number: 0,
+            ack_number: None,
+            window: 0,
+            urgent_pointer: 0,
+            max_seg_size: None,
+            window_scale: None,
+            sack_permitted: false,
+            sack_ranges: [None; 3],
+            timestamps: None,
+            payload_len: 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use super::{Control, Repr};
+    use crate::tcp::builder::PacketBuilder;
+    use crate::tcp::packet::{Packet, TcpFlags};
+
+    const SRC_ADDR: Ipv4Addr = Ipv4Addr::new(192, 168, 233, 233);
+    const DEST_ADDR: Ipv4Addr = Ipv4Addr::new(192, 168, 233, 234);
+
+    #[test]
+    fn emit_and_parse() {
+        let payload = [0xaa, 0xbb, 0xcc];
+        let repr = Repr {
+            src_port: 49307,
+            dest_port: 3000,
+            control: Control::Syn,
+            flags: TcpFlags::ECE | TcpFlags::CWR,
+            seq_number: 0x57162308,
+            ack_number: Some(0x60822590),
+            window: 0x18eb,
+            urgent_pointer: 0,
+            max_seg_size: Some(1460),
+            window_scale: Some(7),
+            sack_permitted: true,
+            sack_ranges: [Some((10, 20)), None, None],
+            timestamps: Some((1, 2)),
+            payload_len: payload.len(),
+        };
+
+        let mut buffer = vec![0; repr.buffer_len()];
+        buffer[repr.header_len()..].copy_from_slice(&payload);
+
+        let mut packet = Packet::new_unchecked(buffer);
+        repr.emit(&mut packet, SRC_ADDR, DEST_ADDR);
+
+        assert_eq!(packet.data_offset() as usize * 4, repr.header_len());
+        assert_eq!(packet.payload(), &payload);
+        assert!(packet.verify_checksum(SRC_ADDR, DEST_ADDR));
+        assert_eq!(Repr::parse(&packet, SRC_ADDR, DEST_ADDR).unwrap(), repr);
+    }
+
+    #[test]
+    fn parse_and_emit() {
+        let payload = [0xaa, 0xbb, 0xcc];
+        let packet = PacketBuilder::default()
+            .src_port(49307)
+            .dest_port(3000)
+            .seq_number(0x57162308)
+            .ack_number(0x60822590)
+            .ack(true)
+            .psh(true)
+            .fin(true)
+            .urg(true)
+            .urgent_pointer(2)
+            .window(0x18eb)
+            .src_addr(SRC_ADDR)
+            .dest_addr(DEST_ADDR)
+            .payload(payload.to_vec())
+            .build_vec();
+        let packet = Packet::new_unchecked(packet);
+
+        let repr = Repr::parse(&packet, SRC_ADDR, DEST_ADDR).unwrap();
+        assert_eq!(repr.control, Control::Fin);
+        assert_eq!(repr.flags, TcpFlags::PSH | TcpFlags::URG);
+        assert_eq!(repr.urgent_pointer, 2);
+        assert_eq!(
+            repr.all_flags(),
+            TcpFlags::FIN | TcpFlags::PSH | TcpFlags::ACK | TcpFlags::URG
+        );
+
+        // Emitting what was parsed gives back the same segment.
+        let mut buffer = vec![0; repr.buffer_len()];
+        buffer[repr.header_len()..].copy_from_slice(&payload);
+        let mut emitted = Packet::new_unchecked(buffer);
+        repr.emit(&mut emitted, SRC_ADDR, DEST_ADDR);
+        assert_eq!(emitted.as_ref(), packet.as_ref());
+    }
+
+    #[test]
+    fn parse_invalid() {
+        let bad_flags = PacketBuilder::default()
+            .src_port(1)
+            .dest_port(2)
+            .syn(true)
+            .fin(true)
+            .src_addr(SRC_ADDR)
+            .dest_addr(DEST_ADDR)
+            .build();
+        assert!(Repr::parse(&bad_flags, SRC_ADDR, DEST_ADDR).is_err());
+
+        let bad_checksum = PacketBuilder::default()
+            .src_port(1)
+            .dest_port(2)
+            .checksum(0x1234)
+            .build();
+        assert!(Repr::parse(&bad_checksum, SRC_ADDR, DEST_ADDR).is_err());
+
+        let zero_port = PacketBuilder::default()
+            .dest_port(2)
+            .src_addr(SRC_ADDR)
+            .dest_addr(DEST_ADDR)
+            .build();
+        assert!(Repr::parse(&zero_port, SRC_ADDR, DEST_ADDR).is_err());
+    }
+}
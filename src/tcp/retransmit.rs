@@ -0,0 +1,201 @@
+use alloc::collections::VecDeque;
+
+use crate::tcp::seq::SeqNumber;
+use crate::time::{Duration, Instant};
+
+pub mod consts {
+    use core::time::Duration;
+
+    pub const INITIAL_RTO: Duration = Duration::from_secs(1); // RFC 6298 section 2.1
+    pub const MIN_RTO: Duration = Duration::from_secs(1); // RFC 6298 section 2.4
+    pub const MAX_RTO: Duration = Duration::from_secs(60); // RFC 6298 section 2.5
+    pub const CLOCK_GRANULARITY: Duration = Duration::from_millis(1);
+    pub const MAX_RETRANSMISSIONS: u32 = 12; // Consecutive timeouts before the connection is dropped
+}
+
+/// The round-trip time estimator and retransmission timer of RFC 6298.
+#[derive(Debug, Clone)]
+pub struct RttEstimator {
+    srtt: Option<Duration>,
+    rttvar: Duration,
+    rto: Duration,
+    backoff: u32,
+}
+
+impl RttEstimator {
+    pub fn srtt(&self) -> Option<Duration> {
+        self.srtt
+    }
+
+    pub fn rttvar(&self) -> Duration {
+        self.rttvar
+    }
+
+    /// Returns the current retransmission timeout, including the exponential backoff.
+    pub fn rto(&self) -> Duration {
+        let rto = self
+            .rto
+            .checked_mul(1 << self.backoff.min(16))
+            .unwrap_or(consts::MAX_RTO);
+        rto.min(consts::MAX_RTO)
+    }
+
+    /// Update the estimate with a round-trip time measured on a segment that was not retransmitted.
+    pub fn sample(&mut self, rtt: Duration) {
+        match self.srtt {
+            None => {
+                self.srtt = Some(rtt);
+                self.rttvar = rtt / 2;
+            }
+            Some(srtt) => {
+                let delta = srtt.abs_diff(rtt);
+                // RTTVAR <- 3/4 * RTTVAR + 1/4 * |SRTT - R'|, SRTT <- 7/8 * SRTT + 1/8 * R'
+                self.rttvar = (self.rttvar * 3 + delta) / 4;
+                self.srtt = Some((srtt * 7 + rtt) / 8);
+            }
+        }
+
+        let srtt = self.srtt.unwrap();
+        self.rto = (srtt + consts::CLOCK_GRANULARITY.max(self.rttvar * 4)).max(consts::MIN_RTO);
+        self.backoff = 0;
+    }
+
+    /// Back off the timer after a timeout.
+    pub fn on_timeout(&mut self) {
+        self.backoff += 1;
+    }
+
+    pub fn backoff(&self) -> u32 {
+        self.backoff
+    }
+}
+
+impl Default for RttEstimator {
+    fn default() -> Self {
+        Self {
+            srtt: None,
+            rttvar: Duration::from_secs(0),
+            rto: consts::INITIAL_RTO,
+            backoff: 0,
+        }
+    }
+}
+
+/// A segment that was sent and not acknowledged yet.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+struct Entry {
+    start: SeqNumber,
+    end: SeqNumber,
+    sent_at: Instant,
+    retransmitted: bool,
+}
+
+/// The sequence ranges in flight, with their transmission times.
+///
+/// The octets themselves stay in the transmit buffer until acknowledged,
+/// the queue only keeps what is needed for RTT sampling (with Karn's algorithm) and the timer.
+#[derive(Debug, Clone, Default)]
+pub struct RetransmitQueue {
+    entries: VecDeque<Entry>,
+}
+
+impl RetransmitQueue {
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Record a segment occupying `start..end` sent at `now`.
+    pub fn push(&mut self, start: SeqNumber, end: SeqNumber, now: Instant, retransmitted: bool) {
+        self.entries.push_back(Entry {
+            start,
+            end,
+            sent_at: now,
+            retransmitted,
+        });
+    }
+
+    /// Drop the segments covered by `ack`, returns an RTT sample if one may be taken.
+    pub fn ack(&mut self, ack: SeqNumber, now: Instant) -> Option<Duration> {
+        let mut sample = None;
+
+        while let Some(entry) = self.entries.front() {
+            if entry.end > ack {
+                break;
+            }
+
+            sample = if entry.retransmitted {
+                None
+            } else {
+                Some(now.saturating_duration_since(entry.sent_at))
+            };
+            self.entries.pop_front();
+        }
+
+        sample
+    }
+
+    /// Returns the transmission time of the oldest segment in flight.
+    pub fn oldest(&self) -> Option<Instant> {
+        self.entries.front().map(|entry| entry.sent_at)
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::{consts, RetransmitQueue, RttEstimator};
+    use crate::tcp::seq::SeqNumber;
+
+    #[test]
+    fn estimator() {
+        let mut rtt = RttEstimator::default();
+        assert_eq!(rtt.rto(), consts::INITIAL_RTO);
+
+        rtt.sample(Duration::from_millis(800));
+        assert_eq!(rtt.srtt(), Some(Duration::from_millis(800)));
+        assert_eq!(rtt.rttvar(), Duration::from_millis(400));
+        assert_eq!(rtt.rto(), Duration::from_millis(2400));
+
+        rtt.sample(Duration::from_millis(400));
+        assert_eq!(rtt.srtt(), Some(Duration::from_millis(750)));
+        assert_eq!(rtt.rttvar(), Duration::from_millis(400));
+
+        rtt.sample(Duration::from_millis(10));
+        rtt.on_timeout();
+        rtt.on_timeout();
+        assert_eq!(rtt.backoff(), 2);
+        assert!(rtt.rto() >= consts::MIN_RTO * 4);
+
+        for _ in 0..20 {
+            rtt.on_timeout();
+        }
+        assert_eq!(rtt.rto(), consts::MAX_RTO);
+    }
+
+    #[test]
+    fn karn() {
+        let now = Instant::now();
+        let mut queue = RetransmitQueue::default();
+
+        queue.push(SeqNumber(0), SeqNumber(100), now, false);
+        queue.push(SeqNumber(100), SeqNumber(200), now, true);
+        queue.push(SeqNumber(200), SeqNumber(300), now, false);
+
+        let later = now + Duration::from_millis(50);
+        assert_eq!(queue.ack(SeqNumber(50), later), None);
+        assert_eq!(queue.len(), 3);
+        assert_eq!(queue.ack(SeqNumber(100), later), Some(Duration::from_millis(50)));
+        assert_eq!(queue.ack(SeqNumber(200), later), None);
+        assert_eq!(queue.ack(SeqNumber(300), later), Some(Duration::from_millis(50)));
+        assert!(queue.is_empty());
+    }
+}
@@ -0,0 +1,159 @@
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// A fixed-capacity FIFO of octets, stored in a ring.
+///
+/// The storage is a vector by default, or any buffer the caller provides, such as an array,
+/// so a socket needs no heap.
+#[derive(Debug, Clone)]
+pub struct RingBuffer<S = Vec<u8>> {
+    storage: S,
+    read_at: usize,
+    length: usize,
+}
+
+impl RingBuffer {
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self::new(vec![0; capacity])
+    }
+
+    /// Change the capacity, which never drops below the number of octets held.
+    pub fn resize(&mut self, capacity: usize) {
+        let mut storage = vec![0; capacity.max(self.length)];
+        let len = self.read_allocated(0, &mut storage);
+
+        self.storage = storage;
+        self.read_at = 0;
+        self.length = len;
+    }
+}
+
+impl<S> RingBuffer<S>
+where
+    S: AsRef<[u8]> + AsMut<[u8]>,
+{
+    /// An empty ring stored in `storage`, whose length is the capacity.
+    pub fn new(storage: S) -> Self {
+        Self {
+            storage,
+            read_at: 0,
+            length: 0,
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.storage.as_ref().len()
+    }
+
+    pub fn len(&self) -> usize {
+        self.length
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.length == 0
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.length == self.capacity()
+    }
+
+    /// Returns the number of octets that can still be enqueued.
+    pub fn window(&self) -> usize {
+        self.capacity() - self.length
+    }
+
+    /// Append as much of `data` as fits, returns the number of octets enqueued.
+    pub fn enqueue_slice(&mut self, data: &[u8]) -> usize {
+        let len = data.len().min(self.window());
+        if len == 0 {
+            return 0;
+        }
+
+        let write_at = (self.read_at + self.length) % self.capacity();
+        let first = len.min(self.capacity() - write_at);
+        let storage = self.storage.as_mut();
+        storage[write_at..write_at + first].copy_from_slice(&data[..first]);
+        storage[..len - first].copy_from_slice(&data[first..len]);
+
+        self.length += len;
+        len
+    }
+
+    /// Copy the octets starting `offset` octets after the head into `buf`, without removing them.
+    pub fn read_allocated(&self, offset: usize, buf: &mut [u8]) -> usize {
+        if offset >= self.length {
+            return 0;
+        }
+
+        let len = buf.len().min(self.length - offset);
+        let read_at = (self.read_at + offset) % self.capacity();
+        let first = len.min(self.capacity() - read_at);
+        let storage = self.storage.as_ref();
+        buf[..first].copy_from_slice(&storage[read_at..read_at + first]);
+        buf[first..len].copy_from_slice(&storage[..len - first]);
+
+        len
+    }
+
+    /// Remove up to `len` octets from the head, returns the number of octets removed.
+    pub fn dequeue_allocated(&mut self, len: usize) -> usize {
+        let len = len.min(self.length);
+        if len > 0 {
+            self.read_at = (self.read_at + len) % self.capacity();
+            self.length -= len;
+        }
+        len
+    }
+
+    /// Move octets from the head into `buf`, returns the number of octets dequeued.
+    pub fn dequeue_slice(&mut self, buf: &mut [u8]) -> usize {
+        let len = self.read_allocated(0, buf);
+        self.dequeue_allocated(len)
+    }
+
+    pub fn clear(&mut self) {
+        self.read_at = 0;
+        self.length = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RingBuffer;
+
+    #[test]
+    fn wrap_around() {
+        let mut ring = RingBuffer::with_capacity(8);
+
+        assert_eq!(ring.enqueue_slice(b"abcdef"), 6);
+        let mut buf = [0; 4];
+        assert_eq!(ring.dequeue_slice(&mut buf), 4);
+        assert_eq!(&buf, b"abcd");
+
+        assert_eq!(ring.enqueue_slice(b"ghijklmn"), 6);
+        assert!(ring.is_full());
+        assert_eq!(ring.window(), 0);
+
+        let mut buf = [0; 3];
+        assert_eq!(ring.read_allocated(4, &mut buf), 3);
+        assert_eq!(&buf, b"ijk");
+
+        assert_eq!(ring.dequeue_allocated(5), 5);
+        ring.resize(4);
+        assert_eq!(ring.capacity(), 4);
+
+        let mut buf = [0; 8];
+        assert_eq!(ring.dequeue_slice(&mut buf), 3);
+        assert_eq!(&buf[..3], b"jkl");
+        assert!(ring.is_empty());
+
+        // The same ring in an array
+        let mut ring = RingBuffer::new([0; 4]);
+        assert_eq!(ring.enqueue_slice(b"abc"), 3);
+        assert_eq!(ring.dequeue_allocated(2), 2);
+        assert_eq!(ring.enqueue_slice(b"def"), 3);
+        let mut buf = [0; 4];
+        assert_eq!(ring.dequeue_slice(&mut buf), 4);
+        assert_eq!(&buf, b"cdef");
+    }
+}
@@ -0,0 +1,100 @@
+use core::cmp::Ordering;
+use core::fmt::{Display, Formatter};
+use core::ops::{Add, AddAssign, Sub};
+
+/// A TCP sequence number, compared and added modulo 2^32 (RFC 793 section 3.3).
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+pub struct SeqNumber(pub u32);
+
+impl SeqNumber {
+    pub fn max(self, other: SeqNumber) -> SeqNumber {
+        if self > other {
+            self
+        } else {
+            other
+        }
+    }
+
+    pub fn min(self, other: SeqNumber) -> SeqNumber {
+        if self < other {
+            self
+        } else {
+            other
+        }
+    }
+}
+
+impl Add<usize> for SeqNumber {
+    type Output = SeqNumber;
+
+    fn add(self, rhs: usize) -> SeqNumber {
+        SeqNumber(self.0.wrapping_add(rhs as u32))
+    }
+}
+
+impl AddAssign<usize> for SeqNumber {
+    fn add_assign(&mut self, rhs: usize) {
+        *self = *self + rhs;
+    }
+}
+
+impl Sub<usize> for SeqNumber {
+    type Output = SeqNumber;
+
+    fn sub(self, rhs: usize) -> SeqNumber {
+        SeqNumber(self.0.wrapping_sub(rhs as u32))
+    }
+}
+
+impl Sub for SeqNumber {
+    type Output = usize;
+
+    /// Returns the distance from `rhs` to `self`, which must not be negative.
+    fn sub(self, rhs: SeqNumber) -> usize {
+        let diff = self.0.wrapping_sub(rhs.0) as i32;
+        debug_assert!(diff >= 0, "sequence number subtraction underflow");
+        diff.max(0) as usize
+    }
+}
+
+impl PartialOrd for SeqNumber {
+    fn partial_cmp(&self, other: &SeqNumber) -> Option<Ordering> {
+        Some((self.0.wrapping_sub(other.0) as i32).cmp(&0))
+    }
+}
+
+impl From<u32> for SeqNumber {
+    fn from(value: u32) -> Self {
+        SeqNumber(value)
+    }
+}
+
+impl From<SeqNumber> for u32 {
+    fn from(value: SeqNumber) -> Self {
+        value.0
+    }
+}
+
+impl Display for SeqNumber {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SeqNumber;
+
+    #[test]
+    fn wrapping() {
+        let near_max = SeqNumber(u32::MAX - 1);
+        let wrapped = near_max + 4;
+
+        assert_eq!(wrapped, SeqNumber(2));
+        assert!(wrapped > near_max);
+        assert!(near_max < wrapped);
+        assert_eq!(wrapped - near_max, 4);
+        assert_eq!(wrapped - 4, near_max);
+        assert_eq!(near_max.max(wrapped), wrapped);
+    }
+}
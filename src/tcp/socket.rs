@@ -0,0 +1,1108 @@
+use alloc::vec;
+use alloc::vec::Vec;
+use core::hash::BuildHasher;
+use core::net::{IpAddr, SocketAddr};
+
+use crate::error::Result;
+use crate::icmpv4::packet::{ErrorMessage, Quoted};
+use crate::macros::diagnostics::debug;
+use crate::options::{SocketConfig, SocketOption};
+use crate::tcp::connection::consts::{DEFAULT_BUFFER_SIZE, MAX_WINDOW};
+use crate::tcp::connection::{local_mss, reset_reply, Connection, Segment, State};
+use crate::tcp::cookie::{self, Secret};
+use crate::tcp::error::Error;
+use crate::tcp::packet::Packet;
+use crate::tcp::repr::{Control, Repr};
+use crate::tcp::seq::SeqNumber;
+use crate::time::Instant;
+
+pub mod consts {
+    pub const EPHEMERAL_PORT_FIRST: u16 = 49152; // Dynamic port range of RFC 6335
+    pub const EPHEMERAL_PORT_LAST: u16 = 65535;
+    pub const DEFAULT_BACKLOG: usize = 128;
+    pub const MAX_TIME_WAIT: usize = 4096; // Released connections kept in TIME-WAIT
+    pub const TIME_WAIT_ISS_GAP: usize = 1 << 17; // Past the largest unscaled window of the old connection
+}
+
+/// The tables of a `SocketSet`: hash maps with `std`, B-trees without, as `alloc` has no hash map.
+#[cfg(feature = "std")]
+type Map<K, V> = std::collections::HashMap<K, V>;
+#[cfg(not(feature = "std"))]
+type Map<K, V> = alloc::collections::BTreeMap<K, V>;
+
+/// A handle to a connection stored in a `SocketSet`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct SocketHandle(usize);
+
+/// A handle to a listener stored in a `SocketSet`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct ListenerHandle(usize);
+
+/// A listening port, which spawns a connection for every incoming SYN.
+struct Listener {
+    local: SocketAddr,
+    backlog: usize,
+    /// Connections spawned by this listener and not accepted yet, in arrival order.
+    pending: Vec<SocketHandle>,
+    /// Answer SYNs with SYN cookies instead of keeping half-open connections.
+    syn_cookies: bool,
+    /// The options of the listener, which the connections it spawns inherit.
+    config: SocketConfig,
+}
+
+impl Listener {
+    fn matches(&self, local: SocketAddr) -> bool {
+        self.local.port() == local.port()
+            && self.local.is_ipv4() == local.is_ipv4()
+            && (self.local.ip().is_unspecified() || self.local.ip() == local.ip())
+    }
+}
+
+/// The TCP demultiplexer, which owns every connection of an interface.
+///
+/// Segments are demultiplexed through hash tables: connections by their `(local, remote)` pair,
+/// listening connections and listeners by local port. Entries are checked against the connection
+/// they point to, so a connection changing state behind a handle never gets segments it should not.
+pub struct SocketSet {
+    connections: Vec<Option<Connection>>,
+    /// Free slots of `connections`.
+    vacant: Vec<usize>,
+    listeners: Vec<Option<Listener>>,
+    table: Map<(SocketAddr, SocketAddr), SocketHandle>,
+    listen_table: Map<u16, Vec<SocketHandle>>,
+    listener_table: Map<u16, Vec<ListenerHandle>>,
+    /// Connections closed by their owner, freed once the close completes.
+    released: Vec<SocketHandle>,
+    epoch: Instant,
+    secret: Secret,
+    next_ephemeral_port: u16,
+    send_buffer_size: usize,
+    recv_buffer_size: usize,
+    max_time_wait: usize,
+}
+
+impl SocketSet {
+    #[cfg(feature = "std")]
+    pub fn new() -> Self {
+        Self::with_secret(Instant::now(), Secret::random())
+    }
+
+    /// A set keyed by `secret`, whose sequence number clock starts at `now`.
+    /// Without `std` there is neither a clock nor randomness to draw them from.
+    pub fn with_secret(now: Instant, secret: Secret) -> Self {
+        Self {
+            connections: vec![],
+            vacant: vec![],
+            listeners: vec![],
+            table: Map::new(),
+            listen_table: Map::new(),
+            listener_table: Map::new(),
+            released: vec![],
+            epoch: now,
+            secret,
+            next_ephemeral_port: consts::EPHEMERAL_PORT_FIRST,
+            send_buffer_size: DEFAULT_BUFFER_SIZE,
+            recv_buffer_size: DEFAULT_BUFFER_SIZE,
+            max_time_wait: consts::MAX_TIME_WAIT,
+        }
+    }
+
+    /// Set the buffer sizes of the connections opened from now on.
+    pub fn set_buffer_sizes(&mut self, send_buffer_size: usize, recv_buffer_size: usize) {
+        self.send_buffer_size = send_buffer_size;
+        self.recv_buffer_size = recv_buffer_size;
+    }
+
+    /// Bound the number of released connections in TIME-WAIT, the oldest ones are dropped first.
+    pub fn set_max_time_wait(&mut self, max_time_wait: usize) {
+        self.max_time_wait = max_time_wait;
+    }
+
+    fn insert(&mut self, mut connection: Connection) -> SocketHandle {
+        connection.set_send_buffer_size(self.send_buffer_size);
+        connection.set_recv_buffer_size(self.recv_buffer_size);
+
+        let handle = match self.vacant.pop() {
+            Some(index) => {
+                self.connections[index] = Some(connection);
+                SocketHandle(index)
+            }
+            None => {
+                self.connections.push(Some(connection));
+                SocketHandle(self.connections.len() - 1)
+            }
+        };
+        self.index(handle);
+        handle
+    }
+
+    /// Record a synchronizing or synchronized connection under its `(local, remote)` pair.
+    fn index(&mut self, handle: SocketHandle) {
+        if let Some(connection) = self.get(handle) {
+            if !matches!(connection.state(), State::Closed | State::Listen) {
+                let key = (connection.local(), connection.remote());
+                self.table.insert(key, handle);
+            }
+        }
+    }
+
+    /// Generate an initial sequence number as recommended by RFC 6528:
+    /// a 4 microsecond clock plus a keyed hash of the connection identifiers.
+    fn generate_iss(&self, now: Instant, local: SocketAddr, remote: SocketAddr) -> SeqNumber {
+        let hash = self.secret.hash_one((local, remote));
+        let clock = (now.saturating_duration_since(self.epoch).as_micros() / 4) as u32;
+        SeqNumber(clock.wrapping_add(hash as u32))
+    }
+
+    fn port_in_use(&self, port: u16) -> bool {
+        self.connections
+            .iter()
+            .flatten()
+            .any(|connection| connection.local().port() == port)
+            || self
+                .listeners
+                .iter()
+                .flatten()
+                .any(|listener| listener.local.port() == port)
+    }
+
+    fn listening(&self, local: SocketAddr) -> bool {
+        let connections = self.listen_table.get(&local.port()).into_iter().flatten();
+        let listeners = self.listener_table.get(&local.port()).into_iter().flatten();
+
+        connections
+            .filter_map(|handle| self.get(*handle))
+            .any(|connection| connection.state() == State::Listen && connection.local() == local)
+            || listeners
+                .filter_map(|handle| self.listeners[handle.0].as_ref())
+                .any(|listener| listener.local == local)
+    }
+
+    /// Allocate an unused port from the dynamic range.
+    pub fn ephemeral_port(&mut self) -> Result<u16> {
+        let range_len = (consts::EPHEMERAL_PORT_LAST - consts::EPHEMERAL_PORT_FIRST) as usize + 1;
+
+        for _ in 0..range_len {
+            let port = self.next_ephemeral_port;
+            self.next_ephemeral_port = if port == consts::EPHEMERAL_PORT_LAST {
+                consts::EPHEMERAL_PORT_FIRST
+            } else {
+                port + 1
+            };
+
+            if !self.port_in_use(port) {
+                return Ok(port);
+            }
+        }
+
+        Err(Error::AddressInUse.into())
+    }
+
+    /// Open a listening connection on `local`, an unspecified address matches every local address.
+    /// The connection itself goes through the handshake, so it accepts a single peer.
+    pub fn listen(&mut self, local: impl Into<SocketAddr>) -> Result<SocketHandle> {
+        let local = local.into();
+        if self.listening(local) {
+            return Err(Error::AddressInUse.into());
+        }
+
+        // The ISS is generated once the SYN arrives.
+        let handle = self.insert(Connection::listen(local, SeqNumber(0)));
+        self.listen_table.entry(local.port()).or_default().push(handle);
+        Ok(handle)
+    }
+
+    /// Open a connection to `remote`, a zero local port is replaced by an ephemeral port.
+    /// Both addresses must be of the same family.
+    pub fn connect(
+        &mut self,
+        now: Instant,
+        local: impl Into<SocketAddr>,
+        remote: impl Into<SocketAddr>,
+    ) -> Result<SocketHandle> {
+        self.connect_with_config(now, local, remote, &SocketConfig::default())
+    }
+
+    /// Open a connection like `connect`, with the options of `config` that apply to connections.
+    pub fn connect_with_config(
+        &mut self,
+        now: Instant,
+        local: impl Into<SocketAddr>,
+        remote: impl Into<SocketAddr>,
+        config: &SocketConfig,
+    ) -> Result<SocketHandle> {
+        let (local, remote) = (local.into(), remote.into());
+        if local.is_ipv4() != remote.is_ipv4() {
+            return Err(Error::InvalidAddress.into());
+        }
+        let local = if local.port() == 0 {
+            SocketAddr::new(local.ip(), self.ephemeral_port()?)
+        } else {
+            local
+        };
+
+        let iss = self.generate_iss(now, local, remote);
+        let mut connection = Connection::connect(local, remote, iss);
+        configure(&mut connection, config);
+        Ok(self.insert(connection))
+    }
+
+    /// Open a listener on `local`, which completes handshakes on its own and queues
+    /// up to `backlog` connections that are established or still in the handshake.
+    pub fn bind(&mut self, local: impl Into<SocketAddr>, backlog: usize) -> Result<ListenerHandle> {
+        self.bind_with_config(local, backlog, SocketConfig::default())
+    }
+
+    /// Open a listener like `bind`, with options inherited by the connections it spawns.
+    ///
+    /// The port must not be used by other connections unless `reuse_address` is set,
+    /// and the address must not be bound by another listener unless both set `reuse_port`.
+    pub fn bind_with_config(
+        &mut self,
+        local: impl Into<SocketAddr>,
+        backlog: usize,
+        config: SocketConfig,
+    ) -> Result<ListenerHandle> {
+        let local = local.into();
+        let shared = config.is_reuse_port()
+            && self
+                .listener_table
+                .get(&local.port())
+                .into_iter()
+                .flatten()
+                .filter_map(|handle| self.listeners[handle.0].as_ref())
+                .all(|listener| listener.local != local || listener.config.is_reuse_port());
+        let connected = self.connections.iter().flatten().any(|connection| {
+            connection.local().port() == local.port() && !matches!(connection.state(), State::Closed | State::Listen)
+        });
+
+        if (self.listening(local) && !shared) || (connected && !config.is_reuse_address()) {
+            return Err(Error::AddressInUse.into());
+        }
+
+        let listener = Listener {
+            local,
+            backlog,
+            pending: vec![],
+            syn_cookies: false,
+            config,
+        };
+
+        let handle = match self.listeners.iter().position(Option::is_none) {
+            Some(index) => {
+                self.listeners[index] = Some(listener);
+                ListenerHandle(index)
+            }
+            None => {
+                self.listeners.push(Some(listener));
+                ListenerHandle(self.listeners.len() - 1)
+            }
+        };
+        self.listener_table.entry(local.port()).or_default().push(handle);
+        Ok(handle)
+    }
+
+    /// Take the oldest established connection off the accept queue of a listener.
+    pub fn accept(&mut self, handle: ListenerHandle) -> Option<SocketHandle> {
+        self.prune_pending();
+        let listener = self.listeners.get(handle.0)?.as_ref()?;
+
+        let position = listener.pending.iter().position(|pending| {
+            self.get(*pending)
+                .map(|connection| !matches!(connection.state(), State::SynReceived))
+                .unwrap_or(false)
+        })?;
+
+        let listener = self.listeners[handle.0].as_mut().unwrap();
+        Some(listener.pending.remove(position))
+    }
+
+    /// Enable or disable SYN cookies (RFC 4987) on a listener. A SYN then only gets a SYN-ACK
+    /// encoding the connection in its sequence number, the connection is created by the ACK that
+    /// completes the handshake. Window scaling, SACK and timestamps are not negotiated in this mode.
+    pub fn set_syn_cookies(&mut self, handle: ListenerHandle, syn_cookies: bool) {
+        if let Some(listener) = self.listeners.get_mut(handle.0).and_then(Option::as_mut) {
+            listener.syn_cookies = syn_cookies;
+        }
+    }
+
+    fn cookie_counter(&self, now: Instant) -> u32 {
+        (now.saturating_duration_since(self.epoch).as_secs() / cookie::consts::COUNTER_PERIOD.as_secs()) as u32
+    }
+
+    /// Returns the SYN-ACK answering `repr` with a SYN cookie.
+    fn cookie_reply(&self, now: Instant, local: SocketAddr, remote: SocketAddr, repr: &Repr) -> Segment {
+        let irs = SeqNumber(repr.seq_number);
+        let mss = repr.max_seg_size.unwrap_or(0);
+        let iss = cookie::generate(&self.secret, self.cookie_counter(now), local, remote, irs, mss);
+
+        Segment {
+            src_addr: local.ip(),
+            dest_addr: remote.ip(),
+            repr: Repr {
+                src_port: local.port(),
+                dest_port: remote.port(),
+                control: Control::Syn,
+                seq_number: iss.into(),
+                ack_number: Some((irs + 1).into()),
+                window: self.recv_buffer_size.min(MAX_WINDOW) as u16,
+                max_seg_size: Some(local_mss(local.ip()) as u16),
+                ..Repr::default()
+            },
+            payload: vec![],
+            ttl: None,
+            tos: 0,
+        }
+    }
+
+    /// Returns the local address of a listener.
+    pub fn listener_addr(&self, handle: ListenerHandle) -> Option<SocketAddr> {
+        Some(self.listeners.get(handle.0)?.as_ref()?.local)
+    }
+
+    /// Close a listener, connections it did not hand out yet are aborted.
+    pub fn unbind(&mut self, handle: ListenerHandle) -> Vec<Segment> {
+        let listener = match self.listeners.get_mut(handle.0).and_then(Option::take) {
+            Some(listener) => listener,
+            None => return vec![],
+        };
+        if let Some(handles) = self.listener_table.get_mut(&listener.local.port()) {
+            handles.retain(|bound| *bound != handle);
+            if handles.is_empty() {
+                self.listener_table.remove(&listener.local.port());
+            }
+        }
+
+        listener
+            .pending
+            .into_iter()
+            .filter_map(|pending| self.remove(pending)?.abort())
+            .collect()
+    }
+
+    /// Forget connections that were reset before being accepted.
+    fn prune_pending(&mut self) {
+        let mut closed = vec![];
+
+        for listener in self.listeners.iter_mut().flatten() {
+            let connections = &self.connections;
+            listener.pending.retain(|pending| {
+                let alive = connections[pending.0]
+                    .as_ref()
+                    .map(|connection| !matches!(connection.state(), State::Closed | State::Listen))
+                    .unwrap_or(false);
+                if !alive {
+                    closed.push(*pending);
+                }
+                alive
+            });
+        }
+
+        for handle in closed {
+            self.remove(handle);
+        }
+    }
+
+    pub fn get(&self, handle: SocketHandle) -> Option<&Connection> {
+        self.connections.get(handle.0)?.as_ref()
+    }
+
+    pub fn get_mut(&mut self, handle: SocketHandle) -> Option<&mut Connection> {
+        self.connections.get_mut(handle.0)?.as_mut()
+    }
+
+    pub fn remove(&mut self, handle: SocketHandle) -> Option<Connection> {
+        let connection = self.connections.get_mut(handle.0)?.take()?;
+        self.vacant.push(handle.0);
+
+        let key = (connection.local(), connection.remote());
+        if self.table.get(&key) == Some(&handle) {
+            self.table.remove(&key);
+        }
+        let port = connection.local().port();
+        if let Some(handles) = self.listen_table.get_mut(&port) {
+            handles.retain(|listening| *listening != handle);
+            if handles.is_empty() {
+                self.listen_table.remove(&port);
+            }
+        }
+
+        Some(connection)
+    }
+
+    /// Close a connection on behalf of an owner that no longer needs it,
+    /// it is removed once the closing handshake is over.
+    pub fn release(&mut self, handle: SocketHandle) {
+        if let Some(connection) = self.get_mut(handle) {
+            connection.close();
+            if connection.state() == State::Closed {
+                self.remove(handle);
+            } else {
+                self.released.push(handle);
+            }
+        }
+    }
+
+    pub fn handles(&self) -> impl Iterator<Item = SocketHandle> + '_ {
+        self.connections
+            .iter()
+            .enumerate()
+            .filter(|(_, connection)| connection.is_some())
+            .map(|(index, _)| SocketHandle(index))
+    }
+
+    /// Find the connection a segment belongs to: an exact match first, then a listening connection.
+    fn lookup(&self, src_addr: IpAddr, dest_addr: IpAddr, repr: &Repr) -> Option<usize> {
+        let local = SocketAddr::new(dest_addr, repr.dest_port);
+        let remote = SocketAddr::new(src_addr, repr.src_port);
+
+        let exact = self.table.get(&(local, remote)).copied().filter(|handle| {
+            self.get(*handle).is_some_and(|connection| {
+                !matches!(connection.state(), State::Closed | State::Listen)
+                    && connection.local() == local
+                    && connection.remote() == remote
+            })
+        });
+
+        let handle = exact.or_else(|| {
+            self.listen_table.get(&local.port())?.iter().copied().find(|handle| {
+                self.get(*handle).is_some_and(|connection| {
+                    connection.state() == State::Listen
+                        && connection.local().is_ipv4() == local.is_ipv4()
+                        && (connection.local().ip().is_unspecified() || connection.local().ip() == local.ip())
+                })
+            })
+        });
+        handle.map(|handle| handle.0)
+    }
+
+    /// Hand an ICMP error to the connection that sent the quoted segment.
+    pub fn process_icmp(&mut self, message: ErrorMessage, quoted: &Quoted) {
+        let (src_port, dest_port) = quoted.ports();
+        let local = SocketAddr::new(quoted.src_addr.into(), src_port);
+        let remote = SocketAddr::new(quoted.dest_addr.into(), dest_port);
+        let seq = &quoted.payload[4..8];
+
+        if let Some(handle) = self.table.get(&(local, remote)).copied() {
+            if let Some(connection) = self.get_mut(handle) {
+                if connection.local() == local && connection.remote() == remote {
+                    connection.on_icmp_error(message, SeqNumber(u32::from_be_bytes([seq[0], seq[1], seq[2], seq[3]])));
+                }
+            }
+        }
+    }
+
+    /// Find the listener bound to `local`, listeners sharing the address get a share of the peers each.
+    fn lookup_listener(&self, local: SocketAddr, remote: SocketAddr) -> Option<usize> {
+        let matching: Vec<usize> = self
+            .listener_table
+            .get(&local.port())?
+            .iter()
+            .filter(|handle| {
+                self.listeners[handle.0]
+                    .as_ref()
+                    .is_some_and(|listener| listener.matches(local))
+            })
+            .map(|handle| handle.0)
+            .collect();
+
+        match matching.len() {
+            0 => None,
+            1 => Some(matching[0]),
+            len => Some(matching[self.secret.hash_one(remote) as usize % len]),
+        }
+    }
+
+    /// Demultiplex a TCP segment received from `src_addr` and sent to `dest_addr`.
+    /// Returns a segment that must be sent immediately, such as a RST for a closed port.
+    pub fn process(
+        &mut self,
+        now: Instant,
+        src_addr: impl Into<IpAddr>,
+        dest_addr: impl Into<IpAddr>,
+        buffer: &[u8],
+    ) -> Result<Option<Segment>> {
+        let (src_addr, dest_addr) = (src_addr.into(), dest_addr.into());
+        let packet = Packet::new_checked(buffer)?;
+        let repr = Repr::parse(&packet, src_addr, dest_addr)?;
+
+        let local = SocketAddr::new(dest_addr, repr.dest_port);
+        let mut target = self.lookup(src_addr, dest_addr, &repr);
+
+        // A new SYN may take over the pair of a connection in TIME-WAIT.
+        let mut recycled = None;
+        if let Some(index) = target {
+            if let Some(snd_max) = self.connections[index].as_mut().unwrap().recycle(&repr) {
+                debug!("{} reused while in TIME-WAIT", local);
+                recycled = Some(snd_max);
+                if let Some(position) = self.released.iter().position(|handle| handle.0 == index) {
+                    let handle = self.released.remove(position);
+                    self.remove(handle);
+                }
+                target = self.lookup(src_addr, dest_addr, &repr);
+            }
+        }
+
+        let listening =
+            target.is_none_or(|index| self.connections[index].as_ref().map(Connection::state) == Some(State::Listen));
+        if listening {
+            let remote = SocketAddr::new(src_addr, repr.src_port);
+            if let Some(index) = self.lookup_listener(local, remote) {
+                let syn = repr.control == Control::Syn && repr.ack_number.is_none();
+                let cookies = self.listeners[index].as_ref().unwrap().syn_cookies;
+
+                if cookies && syn {
+                    return Ok(Some(self.cookie_reply(now, local, remote, &repr)));
+                } else if cookies && repr.ack_number.is_some() && !matches!(repr.control, Control::Syn | Control::Rst) {
+                    let irs = SeqNumber(repr.seq_number) - 1;
+                    let iss = SeqNumber(repr.ack_number.unwrap()) - 1;
+                    target = None;
+                    if let Some(mss) = cookie::check(&self.secret, self.cookie_counter(now), local, remote, irs, iss) {
+                        self.prune_pending();
+                        let listener = self.listeners[index].as_ref().unwrap();
+                        if listener.pending.len() >= listener.backlog {
+                            debug!("backlog of {} is full, cookie dropped", listener.local);
+                            return Ok(None);
+                        }
+
+                        let mut connection = Connection::from_cookie(local, remote, iss, irs, mss);
+                        configure(&mut connection, &listener.config);
+                        let handle = self.insert(connection);
+                        self.listeners[index].as_mut().unwrap().pending.push(handle);
+                        target = Some(handle.0);
+                    }
+                } else if !syn {
+                    target = None;
+                } else {
+                    self.prune_pending();
+                    let listener = self.listeners[index].as_ref().unwrap();
+                    if listener.pending.len() >= listener.backlog {
+                        debug!("backlog of {} is full, syn dropped", listener.local);
+                        return Ok(None);
+                    }
+
+                    let mut connection = Connection::listen(listener.local, SeqNumber(0));
+                    configure(&mut connection, &listener.config);
+                    let handle = self.insert(connection);
+                    self.listeners[index].as_mut().unwrap().pending.push(handle);
+                    target = Some(handle.0);
+                }
+            }
+        }
+
+        match target {
+            Some(index) => {
+                let mut iss = self.generate_iss(
+                    now,
+                    SocketAddr::new(dest_addr, repr.dest_port),
+                    SocketAddr::new(src_addr, repr.src_port),
+                );
+                if let Some(snd_max) = recycled {
+                    // Keep old duplicates out of the new connection's sequence space.
+                    if iss <= snd_max {
+                        iss = snd_max + consts::TIME_WAIT_ISS_GAP;
+                    }
+                }
+                let connection = self.connections[index].as_mut().unwrap();
+                connection.set_iss(iss);
+                let reply = connection.on_segment(now, src_addr, dest_addr, &repr, packet.payload());
+                // A listening connection got the addresses of its peer.
+                self.index(SocketHandle(index));
+                Ok(reply)
+            }
+            None if repr.control == Control::Rst => Ok(None),
+            None => Ok(Some(reset_reply(dest_addr, src_addr, &repr))),
+        }
+    }
+
+    /// Run the timers of every connection and collect the segments they want to send.
+    pub fn dispatch(&mut self, now: Instant) -> Vec<Segment> {
+        let mut segments = vec![];
+
+        for connection in self.connections.iter_mut().flatten() {
+            connection.on_timer(now);
+            while let Some(segment) = connection.poll_transmit(now) {
+                segments.push(segment);
+            }
+        }
+
+        let mut closed = vec![];
+        let connections = &self.connections;
+        self.released
+            .retain(|handle| match connections[handle.0].as_ref().map(Connection::state) {
+                Some(State::Closed) => {
+                    closed.push(*handle);
+                    false
+                }
+                Some(_) => true,
+                None => false,
+            });
+        for handle in closed {
+            self.remove(handle);
+        }
+        self.limit_time_wait();
+
+        segments
+    }
+
+    /// Returns the earliest instant at which a connection has a timer to run or a paced segment to send.
+    pub fn poll_at(&self) -> Option<Instant> {
+        self.connections.iter().flatten().filter_map(Connection::poll_at).min()
+    }
+
+    /// Drop the oldest released connections in TIME-WAIT beyond the limit.
+    fn limit_time_wait(&mut self) {
+        let mut time_wait: Vec<(Instant, SocketHandle)> = self
+            .released
+            .iter()
+            .filter_map(|handle| Some((self.get(*handle)?.time_wait_deadline()?, *handle)))
+            .collect();
+        if time_wait.len() <= self.max_time_wait {
+            return;
+        }
+
+        time_wait.sort_by_key(|(deadline, _)| *deadline);
+        for (_, handle) in time_wait.drain(..time_wait.len() - self.max_time_wait) {
+            debug!("{} dropped from TIME-WAIT", self.get(handle).unwrap().local());
+            self.released.retain(|released| *released != handle);
+            self.remove(handle);
+        }
+    }
+}
+
+/// Apply the options of `config` that belong to the connection.
+fn configure(connection: &mut Connection, config: &SocketConfig) {
+    for option in config.options().into_iter().filter(SocketOption::is_connection_option) {
+        connection.set_option(option).unwrap();
+    }
+}
+
+#[cfg(feature = "std")]
+impl Default for SocketSet {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use std::net::{Ipv4Addr, SocketAddr};
+    use std::time::{Duration, Instant};
+
+    use super::SocketSet;
+    use crate::icmpv4::packet::{DestinationUnreachablePacketCode, ErrorMessage, Quoted};
+    use crate::ipv4::packet::Protocol;
+    use crate::options::SocketConfig;
+    use crate::tcp::connection::State;
+    use crate::tcp::repr::Control;
+
+    const CLIENT_ADDR: Ipv4Addr = Ipv4Addr::new(192, 168, 233, 234);
+    const SERVER_ADDR: Ipv4Addr = Ipv4Addr::new(192, 168, 233, 233);
+
+    /// Exchange segments between two socket sets until both are quiet.
+    fn exchange(now: Instant, a: &mut SocketSet, b: &mut SocketSet) {
+        loop {
+            let a_segments = a.dispatch(now);
+            let b_segments = b.dispatch(now);
+            if a_segments.is_empty() && b_segments.is_empty() {
+                break;
+            }
+            for segment in a_segments {
+                let reply = b.process(now, segment.src_addr, segment.dest_addr, &segment.build_vec());
+                assert!(reply.unwrap().is_none());
+            }
+            for segment in b_segments {
+                let reply = a.process(now, segment.src_addr, segment.dest_addr, &segment.build_vec());
+                assert!(reply.unwrap().is_none());
+            }
+        }
+    }
+
+    #[test]
+    fn demultiplex() {
+        let now = Instant::now();
+        let mut server = SocketSet::new();
+        let mut client = SocketSet::new();
+
+        let listener = server.listen(SocketAddr::from((Ipv4Addr::UNSPECIFIED, 80))).unwrap();
+        assert!(server.listen(SocketAddr::from((Ipv4Addr::UNSPECIFIED, 80))).is_err());
+
+        let handle = client
+            .connect(
+                now,
+                SocketAddr::from((CLIENT_ADDR, 0)),
+                SocketAddr::from((SERVER_ADDR, 80)),
+            )
+            .unwrap();
+        assert!(client.get(handle).unwrap().local().port() >= super::consts::EPHEMERAL_PORT_FIRST);
+
+        exchange(now, &mut client, &mut server);
+
+        assert_eq!(client.get(handle).unwrap().state(), State::Established);
+        assert_eq!(server.get(listener).unwrap().state(), State::Established);
+
+        client.get_mut(handle).unwrap().send(b"ping");
+        exchange(now, &mut client, &mut server);
+
+        let mut buf = [0; 16];
+        let len = server.get_mut(listener).unwrap().recv(&mut buf);
+        assert_eq!(&buf[..len], b"ping");
+    }
+
+    #[test]
+    fn closed_port() {
+        let now = Instant::now();
+        let mut server = SocketSet::new();
+        let mut client = SocketSet::new();
+
+        let handle = client
+            .connect(
+                now,
+                SocketAddr::from((CLIENT_ADDR, 40000)),
+                SocketAddr::from((SERVER_ADDR, 81)),
+            )
+            .unwrap();
+
+        let syn = client.dispatch(now).remove(0);
+        let rst = server
+            .process(now, syn.src_addr, syn.dest_addr, &syn.build_vec())
+            .unwrap()
+            .expect("a reset");
+        assert_eq!(rst.repr.control, Control::Rst);
+
+        client
+            .process(now, rst.src_addr, rst.dest_addr, &rst.build_vec())
+            .unwrap();
+        assert_eq!(client.get(handle).unwrap().state(), State::Closed);
+        assert!(client.get(handle).unwrap().was_reset());
+    }
+
+    #[test]
+    fn backlog() {
+        let now = Instant::now();
+        let mut server = SocketSet::new();
+        let mut client = SocketSet::new();
+
+        let listener = server.bind(SocketAddr::from((Ipv4Addr::UNSPECIFIED, 80)), 2).unwrap();
+        assert!(server.listen(SocketAddr::from((Ipv4Addr::UNSPECIFIED, 80))).is_err());
+
+        let handles: Vec<_> = (0..3)
+            .map(|_| {
+                client
+                    .connect(
+                        now,
+                        SocketAddr::from((CLIENT_ADDR, 0)),
+                        SocketAddr::from((SERVER_ADDR, 80)),
+                    )
+                    .unwrap()
+            })
+            .collect();
+
+        exchange(now, &mut client, &mut server);
+
+        assert_eq!(client.get(handles[0]).unwrap().state(), State::Established);
+        assert_eq!(client.get(handles[1]).unwrap().state(), State::Established);
+        assert_eq!(client.get(handles[2]).unwrap().state(), State::SynSent);
+
+        let first = server.accept(listener).unwrap();
+        assert_eq!(
+            server.get(first).unwrap().remote(),
+            client.get(handles[0]).unwrap().local()
+        );
+        assert!(server.accept(listener).is_some());
+        assert!(server.accept(listener).is_none());
+
+        assert!(server.unbind(listener).is_empty());
+        assert!(server.accept(listener).is_none());
+    }
+
+    #[test]
+    fn release() {
+        let now = Instant::now();
+        let mut server = SocketSet::new();
+        let mut client = SocketSet::new();
+
+        let listener = server.listen(SocketAddr::from((Ipv4Addr::UNSPECIFIED, 80))).unwrap();
+        let handle = client
+            .connect(
+                now,
+                SocketAddr::from((CLIENT_ADDR, 0)),
+                SocketAddr::from((SERVER_ADDR, 80)),
+            )
+            .unwrap();
+        exchange(now, &mut client, &mut server);
+
+        client.release(handle);
+        exchange(now, &mut client, &mut server);
+        assert_eq!(client.get(handle).unwrap().state(), State::FinWait2);
+
+        server.release(listener);
+        exchange(now, &mut client, &mut server);
+        assert!(server.get(listener).is_none());
+        assert_eq!(client.get(handle).unwrap().state(), State::TimeWait);
+
+        client.dispatch(now + crate::tcp::connection::consts::TIME_WAIT_TIMEOUT);
+        assert!(client.get(handle).is_none());
+    }
+
+    #[test]
+    fn time_wait() {
+        let now = Instant::now();
+        let mut server = SocketSet::new();
+        let mut client = SocketSet::new();
+        server.set_max_time_wait(1);
+
+        let listener = server.bind(SocketAddr::from((Ipv4Addr::UNSPECIFIED, 80)), 8).unwrap();
+        let handles: Vec<_> = [40000, 40001]
+            .iter()
+            .map(|port| {
+                client
+                    .connect(
+                        now,
+                        SocketAddr::from((CLIENT_ADDR, *port)),
+                        SocketAddr::from((SERVER_ADDR, 80)),
+                    )
+                    .unwrap()
+            })
+            .collect();
+        exchange(now, &mut client, &mut server);
+
+        // The server closes first, so it keeps both pairs in TIME-WAIT and evicts the oldest.
+        let first = server.accept(listener).unwrap();
+        let second = server.accept(listener).unwrap();
+        server.release(first);
+        exchange(now, &mut client, &mut server);
+        client.release(handles[0]);
+        exchange(now, &mut client, &mut server);
+        assert_eq!(server.get(first).unwrap().state(), State::TimeWait);
+
+        server.release(second);
+        client.release(handles[1]);
+        exchange(now, &mut client, &mut server);
+        assert!(server.get(first).is_none());
+        assert_eq!(server.get(second).unwrap().state(), State::TimeWait);
+
+        // A newer SYN for the pair still in TIME-WAIT opens a new connection.
+        let later = now + Duration::from_secs(1);
+        let handle = client
+            .connect(
+                later,
+                SocketAddr::from((CLIENT_ADDR, 40001)),
+                SocketAddr::from((SERVER_ADDR, 80)),
+            )
+            .unwrap();
+        exchange(later, &mut client, &mut server);
+        assert_eq!(client.get(handle).unwrap().state(), State::Established);
+
+        let accepted = server.accept(listener).unwrap();
+        assert_eq!(server.get(accepted).unwrap().state(), State::Established);
+        assert_eq!(server.handles().count(), 1);
+    }
+
+    #[test]
+    fn syn_cookies() {
+        let now = Instant::now();
+        let mut server = SocketSet::new();
+        let mut client = SocketSet::new();
+
+        let listener = server.bind(SocketAddr::from((Ipv4Addr::UNSPECIFIED, 80)), 8).unwrap();
+        server.set_syn_cookies(listener, true);
+        let handle = client
+            .connect(
+                now,
+                SocketAddr::from((CLIENT_ADDR, 40000)),
+                SocketAddr::from((SERVER_ADDR, 80)),
+            )
+            .unwrap();
+
+        // The SYN is answered without keeping any state.
+        let syn = client.dispatch(now).remove(0);
+        let syn_ack = server
+            .process(now, syn.src_addr, syn.dest_addr, &syn.build_vec())
+            .unwrap()
+            .expect("a syn-ack");
+        assert_eq!(server.handles().count(), 0);
+
+        // An ACK that does not carry a valid cookie is reset.
+        let reply = client.process(now, syn_ack.src_addr, syn_ack.dest_addr, &syn_ack.build_vec());
+        assert!(reply.unwrap().is_none());
+        let ack = client.dispatch(now).remove(0);
+        let mut bad_ack = ack.clone();
+        bad_ack.repr.ack_number = bad_ack.repr.ack_number.map(|ack| ack.wrapping_add(1));
+        let rst = server
+            .process(now, bad_ack.src_addr, bad_ack.dest_addr, &bad_ack.build_vec())
+            .unwrap()
+            .expect("a reset");
+        assert_eq!(rst.repr.control, Control::Rst);
+        assert_eq!(server.handles().count(), 0);
+
+        let reply = server.process(now, ack.src_addr, ack.dest_addr, &ack.build_vec());
+        assert!(reply.unwrap().is_none());
+        let accepted = server.accept(listener).unwrap();
+        assert_eq!(server.get(accepted).unwrap().state(), State::Established);
+        assert_eq!(client.get(handle).unwrap().state(), State::Established);
+
+        client.get_mut(handle).unwrap().send(b"ping");
+        exchange(now, &mut client, &mut server);
+        let mut buf = [0; 16];
+        let len = server.get_mut(accepted).unwrap().recv(&mut buf);
+        assert_eq!(&buf[..len], b"ping");
+    }
+
+    #[test]
+    fn reuse() {
+        let now = Instant::now();
+        let mut server = SocketSet::new();
+        let mut client = SocketSet::new();
+
+        // A port used by a connection is only bound again with `reuse_address`.
+        let local = SocketAddr::from((CLIENT_ADDR, 40000));
+        client.connect(now, local, SocketAddr::from((SERVER_ADDR, 80))).unwrap();
+        client.dispatch(now);
+        assert!(client.bind(local, 8).is_err());
+        assert!(client
+            .bind_with_config(local, 8, SocketConfig::default().reuse_address(true))
+            .is_ok());
+
+        // Listeners sharing a port with `reuse_port` split the connections.
+        let addr = SocketAddr::from((Ipv4Addr::UNSPECIFIED, 80));
+        let config = SocketConfig::default().reuse_port(true).ttl(32);
+        let first = server.bind_with_config(addr, 64, config.clone()).unwrap();
+        assert!(server.bind(addr, 64).is_err());
+        let second = server.bind_with_config(addr, 64, config).unwrap();
+
+        let mut client = SocketSet::new();
+        for port in 40000..40032 {
+            client
+                .connect(
+                    now,
+                    SocketAddr::from((CLIENT_ADDR, port)),
+                    SocketAddr::from((SERVER_ADDR, 80)),
+                )
+                .unwrap();
+        }
+        for syn in client.dispatch(now) {
+            let reply = server.process(now, syn.src_addr, syn.dest_addr, &syn.build_vec());
+            assert!(reply.unwrap().is_none());
+        }
+        for syn_ack in server.dispatch(now) {
+            assert_eq!(syn_ack.ttl, Some(32));
+            let reply = client.process(now, syn_ack.src_addr, syn_ack.dest_addr, &syn_ack.build_vec());
+            assert!(reply.unwrap().is_none());
+        }
+        exchange(now, &mut client, &mut server);
+
+        let accepted = |server: &mut SocketSet, listener| std::iter::from_fn(|| server.accept(listener)).count();
+        let (first, second) = (accepted(&mut server, first), accepted(&mut server, second));
+        assert_eq!(first + second, 32);
+        assert!(first > 0 && second > 0);
+    }
+
+    #[test]
+    fn icmp_error() {
+        let now = Instant::now();
+        let mut client = SocketSet::new();
+        let handle = client
+            .connect(
+                now,
+                SocketAddr::from((CLIENT_ADDR, 40000)),
+                SocketAddr::from((SERVER_ADDR, 80)),
+            )
+            .unwrap();
+        let syn = client.dispatch(now).remove(0).build_vec();
+
+        let message = ErrorMessage::Unreachable(DestinationUnreachablePacketCode::PortUnreachable);
+        let mut quoted = Quoted {
+            src_addr: CLIENT_ADDR,
+            dest_addr: SERVER_ADDR,
+            protocol: Protocol::Tcp,
+            payload: &syn[..8],
+        };
+
+        // An error quoting another destination does not match the connection.
+        quoted.dest_addr = CLIENT_ADDR;
+        client.process_icmp(message, &quoted);
+        assert_eq!(client.get(handle).unwrap().state(), State::SynSent);
+
+        quoted.dest_addr = SERVER_ADDR;
+        client.process_icmp(message, &quoted);
+        assert_eq!(client.get(handle).unwrap().state(), State::Closed);
+        assert_eq!(client.get(handle).unwrap().icmp_error(), Some(message));
+    }
+
+    #[test]
+    fn simultaneous_open() {
+        let now = Instant::now();
+        let mut a = SocketSet::new();
+        let mut b = SocketSet::new();
+
+        let a_handle = a
+            .connect(
+                now,
+                SocketAddr::from((CLIENT_ADDR, 40000)),
+                SocketAddr::from((SERVER_ADDR, 40001)),
+            )
+            .unwrap();
+        let b_handle = b
+            .connect(
+                now,
+                SocketAddr::from((SERVER_ADDR, 40001)),
+                SocketAddr::from((CLIENT_ADDR, 40000)),
+            )
+            .unwrap();
+        exchange(now, &mut a, &mut b);
+
+        assert_eq!(a.get(a_handle).unwrap().state(), State::Established);
+        assert_eq!(b.get(b_handle).unwrap().state(), State::Established);
+    }
+
+    #[test]
+    fn lookup_table() {
+        let now = Instant::now();
+        let mut server = SocketSet::new();
+        let mut client = SocketSet::new();
+        server.set_buffer_sizes(1024, 1024);
+        client.set_buffer_sizes(1024, 1024);
+
+        let listener = server
+            .bind(SocketAddr::from((Ipv4Addr::UNSPECIFIED, 80)), 1000)
+            .unwrap();
+        let handles: Vec<_> = (0..1000)
+            .map(|_| {
+                client
+                    .connect(
+                        now,
+                        SocketAddr::from((CLIENT_ADDR, 0)),
+                        SocketAddr::from((SERVER_ADDR, 80)),
+                    )
+                    .unwrap()
+            })
+            .collect();
+        exchange(now, &mut client, &mut server);
+        assert_eq!(server.table.len(), 1000);
+
+        for (i, handle) in handles.iter().enumerate() {
+            client.get_mut(*handle).unwrap().send(&[i as u8]);
+        }
+        exchange(now, &mut client, &mut server);
+        while let Some(accepted) = server.accept(listener) {
+            let connection = server.get_mut(accepted).unwrap();
+            let index = handles
+                .iter()
+                .position(|handle| client.get(*handle).unwrap().local() == connection.remote())
+                .unwrap();
+            let mut buf = [0; 1];
+            assert_eq!(connection.recv(&mut buf), 1);
+            assert_eq!(buf[0], index as u8);
+            server.release(accepted);
+        }
+
+        for handle in handles {
+            client.release(handle);
+        }
+        exchange(now, &mut client, &mut server);
+        server.dispatch(now + crate::tcp::connection::consts::TIME_WAIT_TIMEOUT);
+        assert_eq!(server.handles().count(), 0);
+        assert!(server.table.is_empty());
+    }
+}
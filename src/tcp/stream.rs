@@ -0,0 +1,464 @@
+use std::io::{Error as IOError, ErrorKind, Read, Result as IOResult, Write};
+use std::net::{Shutdown, SocketAddr};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use crate::error::Result;
+use crate::icmpv4::packet::ErrorMessage;
+use crate::ipv4::interface::{consts, Interface};
+use crate::net_device::tun::TunDevice;
+use crate::net_device::Device;
+use crate::options::{SocketConfig, SocketOption};
+use crate::tcp::connection::State;
+use crate::tcp::error::Error;
+use crate::tcp::socket::SocketHandle;
+
+/// A TCP connection of an interface, usable through `std::io::Read` and `std::io::Write`.
+///
+/// Blocking calls drive the interface by polling it until they can make progress, running the timers of
+/// the connection meanwhile, and release it between waits so other sockets of the interface can use it.
+pub struct TcpStream<D: Device = TunDevice> {
+    interface: Arc<Mutex<Interface<D>>>,
+    handle: SocketHandle,
+    local: SocketAddr,
+    remote: SocketAddr,
+    nonblocking: bool,
+    read_timeout: Option<Duration>,
+    write_timeout: Option<Duration>,
+    read_shutdown: bool,
+    linger: Option<Duration>,
+}
+
+impl<D: Device> TcpStream<D> {
+    pub(crate) fn new(interface: &Arc<Mutex<Interface<D>>>, handle: SocketHandle) -> Self {
+        let (local, remote) = {
+            let interface = interface.lock().unwrap();
+            let connection = interface.sockets().get(handle).unwrap();
+            (connection.local(), connection.remote())
+        };
+
+        Self {
+            interface: interface.clone(),
+            handle,
+            local,
+            remote,
+            nonblocking: false,
+            read_timeout: None,
+            write_timeout: None,
+            read_shutdown: false,
+            linger: None,
+        }
+    }
+
+    /// Open a connection to `remote` from an address of the interface of its family and an ephemeral port,
+    /// and wait for the handshake to complete.
+    pub fn connect(interface: &Arc<Mutex<Interface<D>>>, remote: impl Into<SocketAddr>) -> Result<Self> {
+        Self::connect_with_config(interface, remote, &SocketConfig::default())
+    }
+
+    /// Open a connection like `connect`, failing with `TimedOut` when the handshake has not completed
+    /// within `timeout`.
+    pub fn connect_timeout(
+        interface: &Arc<Mutex<Interface<D>>>,
+        remote: impl Into<SocketAddr>,
+        timeout: Duration,
+    ) -> Result<Self> {
+        Self::connect_inner(interface, remote.into(), &SocketConfig::default(), Some(timeout))
+    }
+
+    /// Open a connection like `connect`, with the options of `config`.
+    pub fn connect_with_config(
+        interface: &Arc<Mutex<Interface<D>>>,
+        remote: impl Into<SocketAddr>,
+        config: &SocketConfig,
+    ) -> Result<Self> {
+        Self::connect_inner(interface, remote.into(), config, None)
+    }
+
+    fn connect_inner(
+        interface: &Arc<Mutex<Interface<D>>>,
+        remote: SocketAddr,
+        config: &SocketConfig,
+        timeout: Option<Duration>,
+    ) -> Result<Self> {
+        let (handle, deadline) = {
+            let mut interface = interface.lock().unwrap();
+            let ip_addr = interface
+                .source_addr(remote.ip())
+                .ok_or_else(|| IOError::from(ErrorKind::AddrNotAvailable))?;
+
+            let now = interface.now();
+            let handle =
+                interface
+                    .sockets_mut()
+                    .connect_with_config(now, SocketAddr::new(ip_addr, 0), remote, config)?;
+            interface.dispatch(now)?;
+            (handle, timeout.map(|timeout| now + timeout))
+        };
+
+        let mut stream = Self::new(interface, handle);
+        stream.linger = config.linger_time();
+
+        loop {
+            let mut interface = stream.lock();
+            let connection = interface.sockets().get(handle).unwrap();
+
+            match connection.state() {
+                State::SynSent | State::SynReceived => {}
+                State::Closed if connection.was_reset() => {
+                    return Err(IOError::from(ErrorKind::ConnectionRefused).into())
+                }
+                State::Closed if connection.was_timed_out() => {
+                    let kind = connection
+                        .icmp_error()
+                        .map_or(ErrorKind::TimedOut, |message| message.kind());
+                    return Err(IOError::from(kind).into());
+                }
+                State::Closed => {
+                    let kind = match connection.icmp_error().filter(ErrorMessage::is_hard) {
+                        Some(message) => message.kind(),
+                        None => ErrorKind::ConnectionAborted,
+                    };
+                    return Err(IOError::from(kind).into());
+                }
+                _ => break,
+            }
+
+            wait(&mut interface, deadline)?;
+        }
+
+        Ok(stream)
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Interface<D>> {
+        self.interface.lock().unwrap()
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local
+    }
+
+    pub fn peer_addr(&self) -> SocketAddr {
+        self.remote
+    }
+
+    /// In non-blocking mode `read` and `write` fail with `WouldBlock` instead of polling the interface.
+    pub fn set_nonblocking(&mut self, nonblocking: bool) {
+        self.nonblocking = nonblocking;
+    }
+
+    /// Make `read` fail with `TimedOut` when nothing could be read within `read_timeout`.
+    pub fn set_read_timeout(&mut self, read_timeout: Option<Duration>) {
+        self.read_timeout = read_timeout;
+    }
+
+    /// Make `write` fail with `TimedOut` when nothing could be queued within `write_timeout`.
+    pub fn set_write_timeout(&mut self, write_timeout: Option<Duration>) {
+        self.write_timeout = write_timeout;
+    }
+
+    pub fn nodelay(&self) -> bool {
+        self.lock().sockets().get(self.handle).unwrap().nodelay()
+    }
+
+    /// Disable Nagle's algorithm, so small writes are sent right away.
+    pub fn set_nodelay(&self, nodelay: bool) {
+        self.lock()
+            .sockets_mut()
+            .get_mut(self.handle)
+            .unwrap()
+            .set_nodelay(nodelay);
+    }
+
+    pub fn pacing(&self) -> bool {
+        self.lock().sockets().get(self.handle).unwrap().pacing()
+    }
+
+    /// Spread the segments sent over the round-trip time, which avoids losses on rate-limited links.
+    pub fn set_pacing(&self, pacing: bool) {
+        self.lock()
+            .sockets_mut()
+            .get_mut(self.handle)
+            .unwrap()
+            .set_pacing(pacing);
+    }
+
+    /// Set an option of the connection, or the linger time used when the stream is dropped.
+    pub fn set_option(&mut self, option: SocketOption) -> Result<()> {
+        match option {
+            SocketOption::Linger(linger) => self.linger = linger,
+            SocketOption::ReuseAddress(_) | SocketOption::ReusePort(_) => return Err(Error::UnsupportedOption.into()),
+            option => self
+                .lock()
+                .sockets_mut()
+                .get_mut(self.handle)
+                .unwrap()
+                .set_option(option)?,
+        }
+        Ok(())
+    }
+
+    pub(crate) fn set_linger(&mut self, linger: Option<Duration>) {
+        self.linger = linger;
+    }
+
+    /// Shut down the read half, the write half (which sends a FIN while data can still be read)
+    /// or both halves of the connection.
+    pub fn shutdown(&mut self, how: Shutdown) -> Result<()> {
+        let mut interface = self.lock();
+        let connection = interface.sockets_mut().get_mut(self.handle).unwrap();
+
+        if how != Shutdown::Write {
+            connection.shutdown_read();
+        }
+        if how != Shutdown::Read {
+            connection.close();
+        }
+        let now = interface.now();
+        interface.dispatch(now)?;
+        drop(interface);
+
+        if how != Shutdown::Write {
+            self.read_shutdown = true;
+        }
+        Ok(())
+    }
+}
+
+impl<D: Device> Read for TcpStream<D> {
+    fn read(&mut self, buf: &mut [u8]) -> IOResult<usize> {
+        if self.read_shutdown || buf.is_empty() {
+            return Ok(0);
+        }
+
+        let deadline = self.read_timeout.map(|timeout| self.lock().now() + timeout);
+        loop {
+            // The interface is released between waits, for the other sockets sharing it.
+            let mut interface = self.lock();
+            let connection = interface.sockets_mut().get_mut(self.handle).unwrap();
+
+            if connection.recv_queue() > 0 {
+                let len = connection.recv(buf);
+                // The window grew, let the peer know.
+                let now = interface.now();
+                interface.dispatch(now).map_err(IOError::from)?;
+                return Ok(len);
+            }
+            if connection.was_reset() {
+                return Err(ErrorKind::ConnectionReset.into());
+            }
+            if connection.was_timed_out() {
+                return Err(connection
+                    .icmp_error()
+                    .map_or(ErrorKind::TimedOut, |message| message.kind())
+                    .into());
+            }
+            if let Some(message) = connection.icmp_error().filter(ErrorMessage::is_hard) {
+                return Err(message.kind().into());
+            }
+            if !connection.may_recv() {
+                return Ok(0);
+            }
+            if self.nonblocking {
+                let now = interface.now();
+                interface.dispatch(now).map_err(IOError::from)?;
+                return Err(ErrorKind::WouldBlock.into());
+            }
+
+            wait(&mut interface, deadline).map_err(IOError::from)?;
+        }
+    }
+}
+
+impl<D: Device> Write for TcpStream<D> {
+    fn write(&mut self, buf: &[u8]) -> IOResult<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        let deadline = self.write_timeout.map(|timeout| self.lock().now() + timeout);
+        loop {
+            let mut interface = self.lock();
+            let connection = interface.sockets_mut().get_mut(self.handle).unwrap();
+
+            if connection.was_reset() {
+                return Err(ErrorKind::ConnectionReset.into());
+            }
+            if connection.was_timed_out() {
+                return Err(connection
+                    .icmp_error()
+                    .map_or(ErrorKind::TimedOut, |message| message.kind())
+                    .into());
+            }
+            if let Some(message) = connection.icmp_error().filter(ErrorMessage::is_hard) {
+                return Err(message.kind().into());
+            }
+            if !connection.may_send() {
+                return Err(ErrorKind::BrokenPipe.into());
+            }
+
+            let len = connection.send(buf);
+            if len > 0 {
+                let now = interface.now();
+                interface.dispatch(now).map_err(IOError::from)?;
+                return Ok(len);
+            }
+            if self.nonblocking {
+                let now = interface.now();
+                interface.dispatch(now).map_err(IOError::from)?;
+                return Err(ErrorKind::WouldBlock.into());
+            }
+
+            wait(&mut interface, deadline).map_err(IOError::from)?;
+        }
+    }
+
+    fn flush(&mut self) -> IOResult<()> {
+        let mut interface = self.lock();
+        let now = interface.now();
+        interface.dispatch(now).map_err(IOError::from)
+    }
+}
+
+impl<D: Device> Drop for TcpStream<D> {
+    /// Close the connection in the background, or with a linger time wait for the FIN to be acknowledged.
+    /// A zero linger time resets the connection.
+    fn drop(&mut self) {
+        let mut interface = self.lock();
+
+        if self.linger == Some(Duration::from_secs(0)) {
+            let reset = interface
+                .sockets_mut()
+                .remove(self.handle)
+                .and_then(|mut connection| connection.abort());
+            if let Some(segment) = reset {
+                let _ = interface.send_segment(&segment);
+            }
+            return;
+        }
+
+        interface.sockets_mut().release(self.handle);
+        let now = interface.now();
+        let _ = interface.dispatch(now);
+
+        if let Some(linger) = self.linger {
+            let deadline = interface.now() + linger;
+            let (local, remote) = (self.local, self.remote);
+            let closing = |interface: &Interface<D>| {
+                interface.sockets().get(self.handle).is_some_and(|connection| {
+                    connection.local() == local
+                        && connection.remote() == remote
+                        && matches!(connection.state(), State::FinWait1 | State::Closing | State::LastAck)
+                })
+            };
+
+            drop(interface);
+
+            loop {
+                let mut interface = self.lock();
+                let now = interface.now();
+                if !closing(&interface) || now >= deadline {
+                    break;
+                }
+                let timeout = deadline.saturating_duration_since(now).min(consts::MAX_POLL_WAIT);
+                if interface.poll_wait(timeout).is_err() {
+                    break;
+                }
+            }
+        }
+    }
+}
+
+/// Poll the interface once, waiting no later than `deadline`, and fail with `TimedOut` once it has passed.
+fn wait<D: Device>(interface: &mut Interface<D>, deadline: Option<Instant>) -> Result<()> {
+    let timeout = match deadline {
+        Some(deadline) => {
+            let now = interface.now();
+            if now >= deadline {
+                return Err(IOError::from(ErrorKind::TimedOut).into());
+            }
+            deadline.saturating_duration_since(now).min(consts::MAX_POLL_WAIT)
+        }
+        None => consts::MAX_POLL_WAIT,
+    };
+    interface.poll_wait(timeout)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{ErrorKind, Read, Write};
+    use std::net::Ipv4Addr;
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+    use std::time::Duration;
+
+    use super::TcpStream;
+    use crate::clock::MockClock;
+    use crate::ipv4::interface::Interface;
+    use crate::ipv4::reassembly::Reassembler;
+    use crate::net_device::channel::ChannelDevice;
+    use crate::tcp::listener::TcpListener;
+    use crate::tcp::retransmit::consts::MAX_RTO;
+
+    const CLIENT_ADDR: Ipv4Addr = Ipv4Addr::new(10, 0, 0, 1);
+    const SERVER_ADDR: Ipv4Addr = Ipv4Addr::new(10, 0, 0, 2);
+
+    fn interface(device: ChannelDevice, addr: Ipv4Addr) -> Arc<Mutex<Interface<ChannelDevice>>> {
+        let mut interface = Interface::new(device, Reassembler::default());
+        interface.set_ip_addr(addr);
+        Arc::new(Mutex::new(interface))
+    }
+
+    #[test]
+    fn echo() {
+        let (client_device, mut server_device) = ChannelDevice::pair();
+        // A non-blocking device must not make blocking calls fail with `WouldBlock`.
+        server_device.set_nonblocking(true);
+        let client = interface(client_device, CLIENT_ADDR);
+        let server = interface(server_device, SERVER_ADDR);
+
+        let listener = TcpListener::bind(&server, 7).unwrap();
+        let echo = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut buf = [0; 64];
+            loop {
+                match stream.read(&mut buf).unwrap() {
+                    0 => break,
+                    len => stream.write_all(&buf[..len]).unwrap(),
+                }
+            }
+        });
+
+        let mut stream = TcpStream::connect(&client, (SERVER_ADDR, 7)).unwrap();
+        assert_eq!(stream.peer_addr(), (SERVER_ADDR, 7).into());
+        stream.write_all(b"hello").unwrap();
+        let mut buf = [0; 5];
+        stream.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"hello");
+
+        // The FIN ends the echo loop.
+        drop(stream);
+        echo.join().unwrap();
+    }
+
+    #[test]
+    fn syn_lost() {
+        let (client_device, _peer) = ChannelDevice::pair();
+        let client = interface(client_device, CLIENT_ADDR);
+        let clock = MockClock::new();
+        client.lock().unwrap().set_clock(Arc::new(clock.clone()));
+
+        // Nobody answers, the SYN is retransmitted until the connection times out.
+        let connect = thread::spawn({
+            let client = client.clone();
+            move || TcpStream::connect(&client, (SERVER_ADDR, 7)).map(|_| ())
+        });
+        while !connect.is_finished() {
+            clock.advance(MAX_RTO);
+            thread::sleep(Duration::from_millis(1));
+        }
+
+        let e = connect.join().unwrap().unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::TimedOut);
+    }
+}
@@ -0,0 +1,143 @@
+use std::io::{Read, Write};
+use std::net::{Ipv4Addr, SocketAddrV4};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::error::Result;
+use crate::ipv4::interface::Interface;
+use crate::net_device::tun::TunDevice;
+use crate::net_device::Device;
+use crate::tftp::error::Error;
+use crate::tftp::packet::{consts as packet_consts, ErrorCode, Message, Request};
+use crate::tftp::transfer::Transfer;
+use crate::udp::udp_socket::UdpSocket;
+
+pub mod consts {
+    use std::time::Duration;
+
+    pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(1);
+    pub const DEFAULT_RETRIES: usize = 5;
+}
+
+/// A TFTP client (RFC 1350), reading and writing files of a server in octet mode.
+pub struct Client<D: Device = TunDevice> {
+    interface: Arc<Mutex<Interface<D>>>,
+    server: SocketAddrV4,
+    timeout: Duration,
+    retries: usize,
+    block_size: Option<usize>,
+}
+
+impl<D: Device> Client<D> {
+    /// Talk to `server`, such as 192.0.2.69:69.
+    pub fn new(interface: &Arc<Mutex<Interface<D>>>, server: SocketAddrV4) -> Self {
+        Self {
+            interface: interface.clone(),
+            server,
+            timeout: consts::DEFAULT_TIMEOUT,
+            retries: consts::DEFAULT_RETRIES,
+            block_size: None,
+        }
+    }
+
+    /// How long to wait for a packet of the server before retransmitting the last one.
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+
+    /// How many times a packet is retransmitted before the transfer is given up.
+    pub fn set_retries(&mut self, retries: usize) {
+        self.retries = retries;
+    }
+
+    /// Ask the server for blocks of `block_size` (RFC 2348), `None` keeps the 512 octets of RFC 1350.
+    pub fn set_block_size(&mut self, block_size: Option<usize>) {
+        self.block_size =
+            block_size.map(|block_size| block_size.clamp(packet_consts::MIN_BLOCK_SIZE, packet_consts::MAX_BLOCK_SIZE));
+    }
+
+    fn request(&self, filename: &str) -> Request {
+        let options = match self.block_size {
+            Some(block_size) => vec![(packet_consts::OPTION_BLOCK_SIZE.to_string(), block_size.to_string())],
+            None => vec![],
+        };
+        Request {
+            filename: filename.to_string(),
+            mode: packet_consts::MODE_OCTET.to_string(),
+            options,
+        }
+    }
+
+    fn transfer(&self) -> Result<Transfer<D>> {
+        let socket = UdpSocket::bind(&self.interface, Ipv4Addr::UNSPECIFIED, 0)?;
+        // The server answers from the port of the transfer, which is learnt from its first packet.
+        Ok(Transfer::new(
+            socket,
+            self.server.into(),
+            false,
+            self.timeout,
+            self.retries,
+        ))
+    }
+
+    /// The block size accepted by the server in its option acknowledgment.
+    fn accepted_block_size(&self, transfer: &Transfer<D>, options: &[(String, String)]) -> Result<usize> {
+        let accepted = options
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(packet_consts::OPTION_BLOCK_SIZE))
+            .map(|(_, value)| value.parse::<usize>().ok());
+        match (accepted, self.block_size) {
+            (None, _) => Ok(packet_consts::DEFAULT_BLOCK_SIZE),
+            (Some(Some(accepted)), Some(asked)) if (packet_consts::MIN_BLOCK_SIZE..=asked).contains(&accepted) => {
+                Ok(accepted)
+            }
+            _ => {
+                transfer.send_error(ErrorCode::OptionRefused, "invalid block size")?;
+                Err(Error::InvalidRequest.into())
+            }
+        }
+    }
+
+    /// Read `filename` from the server into `output`, returns the number of octets received.
+    pub fn get(&self, filename: &str, output: &mut impl Write) -> Result<usize> {
+        let mut transfer = self.transfer()?;
+        let request = Message::ReadRequest(self.request(filename));
+        let answer = transfer.exchange(&request, |answer| {
+            matches!(answer, Message::Data { block: 1, .. } | Message::OptionAck(_))
+        })?;
+
+        match answer {
+            Message::OptionAck(options) => {
+                let block_size = self.accepted_block_size(&transfer, &options)?;
+                transfer.receive_blocks(Message::Ack(0), 0, output, block_size)
+            }
+            Message::Data { data, .. } => {
+                // Without an option acknowledgment, the server ignored the options.
+                output.write_all(&data)?;
+                if data.len() < packet_consts::DEFAULT_BLOCK_SIZE {
+                    transfer.send(&Message::Ack(1))?;
+                    return Ok(data.len());
+                }
+                let received =
+                    transfer.receive_blocks(Message::Ack(1), 1, output, packet_consts::DEFAULT_BLOCK_SIZE)?;
+                Ok(data.len() + received)
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    /// Write the content of `input` to `filename` on the server, returns the number of octets sent.
+    pub fn put(&self, filename: &str, input: &mut impl Read) -> Result<usize> {
+        let mut transfer = self.transfer()?;
+        let request = Message::WriteRequest(self.request(filename));
+        let answer = transfer.exchange(&request, |answer| {
+            matches!(answer, Message::Ack(0) | Message::OptionAck(_))
+        })?;
+
+        let block_size = match answer {
+            Message::OptionAck(options) => self.accepted_block_size(&transfer, &options)?,
+            _ => packet_consts::DEFAULT_BLOCK_SIZE,
+        };
+        transfer.send_blocks(input, block_size)
+    }
+}
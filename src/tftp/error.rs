@@ -0,0 +1,32 @@
+use alloc::string::String;
+use core::fmt::{Display, Formatter};
+
+use crate::tftp::packet::ErrorCode;
+
+#[derive(Debug)]
+pub enum Error {
+    InvalidLength,
+    InvalidOpcode,
+    InvalidRequest,
+    /// The file name leaves the root directory of the server.
+    InvalidPath,
+    /// The peer ended the transfer with an error packet.
+    Remote(ErrorCode, String),
+    /// The peer did not answer after every retransmission.
+    TimedOut,
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Error::InvalidLength => write!(f, "invalid length"),
+            Error::InvalidOpcode => write!(f, "invalid opcode"),
+            Error::InvalidRequest => write!(f, "invalid request"),
+            Error::InvalidPath => write!(f, "invalid path"),
+            Error::Remote(code, message) => write!(f, "remote error {:?}: {}", code, message),
+            Error::TimedOut => write!(f, "transfer timed out"),
+        }
+    }
+}
+
+impl core::error::Error for Error {}
@@ -0,0 +1,8 @@
+#[cfg(feature = "std")]
+pub mod client;
+pub mod error;
+pub mod packet;
+#[cfg(feature = "std")]
+pub mod server;
+#[cfg(feature = "std")]
+mod transfer;
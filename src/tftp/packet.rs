@@ -0,0 +1,265 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::c_like_enum;
+use crate::error::Result;
+use crate::tftp::error::Error;
+
+pub mod consts {
+    pub const PORT: u16 = 69;
+    pub const DEFAULT_BLOCK_SIZE: usize = 512; // RFC 1350
+    pub const MIN_BLOCK_SIZE: usize = 8; // RFC 2348
+    pub const MAX_BLOCK_SIZE: usize = 65464;
+    pub const MTU_BLOCK_SIZE: usize = 1468; // Largest block fitting the default MTU, blocks are not fragmented
+    pub const HEADER_LEN: usize = 4; // Opcode and block number of DATA and ACK packets
+    pub const MODE_OCTET: &str = "octet";
+    pub const OPTION_BLOCK_SIZE: &str = "blksize";
+}
+
+c_like_enum!(
+    /// TFTP opcodes (RFC 1350 section 5 and RFC 2347)
+    #[derive(Debug, Copy, Clone, PartialEq, Eq)]
+    pub enum Opcode(u16) {
+        ReadRequest = 1,
+        WriteRequest = 2,
+        Data = 3,
+        Ack = 4,
+        Error = 5,
+        OptionAck = 6,
+    }
+);
+
+c_like_enum!(
+    /// TFTP error codes (RFC 1350 appendix and RFC 2347)
+    #[derive(Debug, Copy, Clone, PartialEq, Eq)]
+    pub enum ErrorCode(u16) {
+        NotDefined = 0,
+        FileNotFound = 1,
+        AccessViolation = 2,
+        DiskFull = 3,
+        IllegalOperation = 4,
+        UnknownTransferId = 5,
+        FileExists = 6,
+        NoSuchUser = 7,
+        OptionRefused = 8,
+    }
+);
+
+/// A read or write request.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Request {
+    pub filename: String,
+    pub mode: String,
+    /// The options of RFC 2347, as name and value.
+    pub options: Vec<(String, String)>,
+}
+
+impl Request {
+    pub fn option(&self, name: &str) -> Option<&str> {
+        self.options
+            .iter()
+            .find(|(option, _)| option.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// A TFTP packet.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Message {
+    ReadRequest(Request),
+    WriteRequest(Request),
+    Data {
+        block: u16,
+        data: Vec<u8>,
+    },
+    Ack(u16),
+    Error {
+        code: ErrorCode,
+        message: String,
+    },
+    /// The options accepted by the server (RFC 2347).
+    OptionAck(Vec<(String, String)>),
+}
+
+impl Message {
+    pub fn parse(buffer: &[u8]) -> Result<Self> {
+        if buffer.len() < 2 {
+            return Err(Error::InvalidLength.into());
+        }
+        let opcode: Opcode = u16::from_be_bytes([buffer[0], buffer[1]]).into();
+        let body = &buffer[2..];
+
+        match opcode {
+            Opcode::ReadRequest | Opcode::WriteRequest => {
+                let mut strings = strings(body)?.into_iter();
+                let filename = strings.next().ok_or(Error::InvalidRequest)?;
+                let mode = strings.next().ok_or(Error::InvalidRequest)?;
+                let request = Request {
+                    filename,
+                    mode,
+                    options: pairs(strings)?,
+                };
+                Ok(if opcode == Opcode::ReadRequest {
+                    Message::ReadRequest(request)
+                } else {
+                    Message::WriteRequest(request)
+                })
+            }
+            Opcode::Data => {
+                if body.len() < 2 {
+                    return Err(Error::InvalidLength.into());
+                }
+                Ok(Message::Data {
+                    block: u16::from_be_bytes([body[0], body[1]]),
+                    data: body[2..].to_vec(),
+                })
+            }
+            Opcode::Ack => match body {
+                [high, low] => Ok(Message::Ack(u16::from_be_bytes([*high, *low]))),
+                _ => Err(Error::InvalidLength.into()),
+            },
+            Opcode::Error => {
+                if body.len() < 2 {
+                    return Err(Error::InvalidLength.into());
+                }
+                // Some implementations leave out the terminating zero of the message.
+                let text = &body[2..];
+                let text = text.strip_suffix(&[0]).unwrap_or(text);
+                Ok(Message::Error {
+                    code: u16::from_be_bytes([body[0], body[1]]).into(),
+                    message: String::from_utf8_lossy(text).into_owned(),
+                })
+            }
+            Opcode::OptionAck => Ok(Message::OptionAck(pairs(strings(body)?.into_iter())?)),
+            Opcode::Unknown(_) => Err(Error::InvalidOpcode.into()),
+        }
+    }
+
+    pub fn opcode(&self) -> Opcode {
+        match self {
+            Message::ReadRequest(_) => Opcode::ReadRequest,
+            Message::WriteRequest(_) => Opcode::WriteRequest,
+            Message::Data { .. } => Opcode::Data,
+            Message::Ack(_) => Opcode::Ack,
+            Message::Error { .. } => Opcode::Error,
+            Message::OptionAck(_) => Opcode::OptionAck,
+        }
+    }
+
+    pub fn build_vec(&self) -> Vec<u8> {
+        let mut buffer = u16::from(self.opcode()).to_be_bytes().to_vec();
+        let push_string = |buffer: &mut Vec<u8>, string: &str| {
+            buffer.extend_from_slice(string.as_bytes());
+            buffer.push(0);
+        };
+
+        match self {
+            Message::ReadRequest(request) | Message::WriteRequest(request) => {
+                push_string(&mut buffer, &request.filename);
+                push_string(&mut buffer, &request.mode);
+                for (name, value) in request.options.iter() {
+                    push_string(&mut buffer, name);
+                    push_string(&mut buffer, value);
+                }
+            }
+            Message::Data { block, data } => {
+                buffer.extend_from_slice(block.to_be_bytes().as_ref());
+                buffer.extend_from_slice(data);
+            }
+            Message::Ack(block) => buffer.extend_from_slice(block.to_be_bytes().as_ref()),
+            Message::Error { code, message } => {
+                buffer.extend_from_slice(u16::from(*code).to_be_bytes().as_ref());
+                push_string(&mut buffer, message);
+            }
+            Message::OptionAck(options) => {
+                for (name, value) in options.iter() {
+                    push_string(&mut buffer, name);
+                    push_string(&mut buffer, value);
+                }
+            }
+        }
+
+        buffer
+    }
+}
+
+/// Split zero terminated strings.
+fn strings(buffer: &[u8]) -> Result<Vec<String>> {
+    if buffer.last().is_some_and(|last| *last != 0) {
+        return Err(Error::InvalidRequest.into());
+    }
+    Ok(buffer
+        .split(|octet| *octet == 0)
+        .take(buffer.iter().filter(|octet| **octet == 0).count())
+        .map(|string| String::from_utf8_lossy(string).into_owned())
+        .collect())
+}
+
+fn pairs(strings: impl Iterator<Item = String>) -> Result<Vec<(String, String)>> {
+    let strings: Vec<String> = strings.collect();
+    if !strings.len().is_multiple_of(2) {
+        return Err(Error::InvalidRequest.into());
+    }
+    Ok(strings
+        .chunks(2)
+        .map(|pair| (pair[0].clone(), pair[1].clone()))
+        .collect())
+}
+
+/// Returns the block size asked for by the `blksize` option, clamped to `max` (RFC 2348).
+/// `None` when the option is missing or invalid, the transfer then uses the default block size.
+pub fn negotiate_block_size(request: &Request, max: usize) -> Option<usize> {
+    let block_size: usize = request.option(consts::OPTION_BLOCK_SIZE)?.parse().ok()?;
+    if block_size < consts::MIN_BLOCK_SIZE {
+        return None;
+    }
+    Some(block_size.min(max).min(consts::MAX_BLOCK_SIZE))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{consts, negotiate_block_size, ErrorCode, Message, Request};
+
+    #[test]
+    fn parse() {
+        let bytes = b"\x00\x01boot.img\x00octet\x00blksize\x001428\x00";
+        let message = Message::parse(bytes).unwrap();
+        let request = match &message {
+            Message::ReadRequest(request) => request.clone(),
+            message => panic!("unexpected {:?}", message),
+        };
+        assert_eq!(request.filename, "boot.img");
+        assert_eq!(request.mode, "octet");
+        assert_eq!(request.option("BLKSIZE"), Some("1428"));
+        assert_eq!(message.build_vec(), bytes.to_vec());
+
+        assert_eq!(negotiate_block_size(&request, 1024), Some(1024));
+        assert_eq!(negotiate_block_size(&request, 1500), Some(1428));
+        let small = Request {
+            options: vec![(consts::OPTION_BLOCK_SIZE.to_string(), "4".to_string())],
+            ..request.clone()
+        };
+        assert_eq!(negotiate_block_size(&small, 1500), None);
+
+        assert_eq!(
+            Message::parse(b"\x00\x03\x00\x02abc").unwrap(),
+            Message::Data {
+                block: 2,
+                data: b"abc".to_vec()
+            }
+        );
+        assert_eq!(Message::parse(b"\x00\x04\xff\xff").unwrap(), Message::Ack(0xffff));
+        assert_eq!(
+            Message::parse(b"\x00\x05\x00\x01no such file").unwrap(),
+            Message::Error {
+                code: ErrorCode::FileNotFound,
+                message: "no such file".to_string()
+            }
+        );
+
+        assert!(Message::parse(b"\x00\x01boot.img").is_err());
+        assert!(Message::parse(b"\x00\x01boot.img\x00octet\x00blksize\x00").is_err());
+        assert!(Message::parse(b"\x00\x04\x00").is_err());
+        assert!(Message::parse(b"\x00\x09").is_err());
+    }
+}
@@ -0,0 +1,220 @@
+use std::fs::{File, OpenOptions};
+use std::io::ErrorKind;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::error::Result;
+use crate::ipv4::interface::Interface;
+use crate::macros::diagnostics::debug;
+use crate::net_device::tun::TunDevice;
+use crate::net_device::Device;
+use crate::tftp::error::Error;
+use crate::tftp::packet::{consts as packet_consts, negotiate_block_size, ErrorCode, Message, Request};
+use crate::tftp::transfer::Transfer;
+use crate::udp::udp_socket::UdpSocket;
+
+pub mod consts {
+    use std::time::Duration;
+
+    pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(1);
+    pub const DEFAULT_RETRIES: usize = 5;
+}
+
+/// A TFTP server (RFC 1350) giving access to the files under a root directory, in octet mode.
+/// Every transfer runs from a new port, the transfer identifier of the server.
+pub struct Server<D: Device = TunDevice> {
+    interface: Arc<Mutex<Interface<D>>>,
+    socket: UdpSocket<D>,
+    root: PathBuf,
+    timeout: Duration,
+    retries: usize,
+    writable: bool,
+    max_block_size: usize,
+}
+
+impl<D: Device> Server<D> {
+    /// Listen on the TFTP port, serving the files under `root`. Writes are refused until enabled.
+    pub fn bind(interface: &Arc<Mutex<Interface<D>>>, root: impl Into<PathBuf>) -> Result<Self> {
+        let socket = UdpSocket::bind(interface, Ipv4Addr::UNSPECIFIED, packet_consts::PORT)?;
+        Ok(Self {
+            interface: interface.clone(),
+            socket,
+            root: root.into(),
+            timeout: consts::DEFAULT_TIMEOUT,
+            retries: consts::DEFAULT_RETRIES,
+            writable: false,
+            max_block_size: packet_consts::MTU_BLOCK_SIZE,
+        })
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.socket.local_addr()
+    }
+
+    /// How long to wait for a packet of the client before retransmitting the last one.
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+
+    /// How many times a packet is retransmitted before the transfer is given up.
+    pub fn set_retries(&mut self, retries: usize) {
+        self.retries = retries;
+    }
+
+    /// Whether clients may write new files, existing files are never overwritten.
+    pub fn set_writable(&mut self, writable: bool) {
+        self.writable = writable;
+    }
+
+    /// The largest block size granted to the clients asking for one.
+    pub fn set_max_block_size(&mut self, max_block_size: usize) {
+        self.max_block_size = max_block_size;
+    }
+
+    /// Receive a request and run its transfer, returns the number of octets transferred.
+    /// Packets which are not requests are dropped.
+    pub fn serve(&self) -> Result<usize> {
+        let mut buf = vec![0; packet_consts::MAX_BLOCK_SIZE + packet_consts::HEADER_LEN];
+
+        loop {
+            let (len, remote) = self.socket.recv_from(&mut buf)?;
+            match Message::parse(&buf[..len]) {
+                Ok(Message::ReadRequest(request)) => return self.read(request, remote),
+                Ok(Message::WriteRequest(request)) => return self.write(request, remote),
+                Ok(message) => debug!("tftp {:?} from {} dropped: not a request", message.opcode(), remote),
+                Err(e) => debug!("tftp packet from {} dropped: {}", remote, e),
+            }
+        }
+    }
+
+    /// Start the transfer of `request`, returns the path of its file and the block size,
+    /// or `None` when the request was refused.
+    fn start(&self, request: &Request, transfer: &Transfer<D>) -> Result<Option<(PathBuf, Option<usize>)>> {
+        if !request.mode.eq_ignore_ascii_case(packet_consts::MODE_OCTET) {
+            transfer.send_error(ErrorCode::IllegalOperation, "only octet mode is supported")?;
+            return Ok(None);
+        }
+        match resolve(&self.root, &request.filename) {
+            Ok(path) => Ok(Some((path, negotiate_block_size(request, self.max_block_size)))),
+            Err(e) => {
+                transfer.send_error(ErrorCode::AccessViolation, &e.to_string())?;
+                Ok(None)
+            }
+        }
+    }
+
+    fn transfer(&self, remote: SocketAddr) -> Result<Transfer<D>> {
+        let socket = UdpSocket::bind(&self.interface, Ipv4Addr::UNSPECIFIED, 0)?;
+        Ok(Transfer::new(socket, remote, true, self.timeout, self.retries))
+    }
+
+    fn read(&self, request: Request, remote: SocketAddr) -> Result<usize> {
+        let mut transfer = self.transfer(remote)?;
+        let (path, block_size) = match self.start(&request, &transfer)? {
+            Some(started) => started,
+            None => return Ok(0),
+        };
+
+        let mut file = match File::open(&path) {
+            Ok(file) => file,
+            Err(e) => {
+                let code = match e.kind() {
+                    ErrorKind::NotFound => ErrorCode::FileNotFound,
+                    ErrorKind::PermissionDenied => ErrorCode::AccessViolation,
+                    _ => ErrorCode::NotDefined,
+                };
+                transfer.send_error(code, &e.to_string())?;
+                return Ok(0);
+            }
+        };
+
+        if let Some(block_size) = block_size {
+            // The client acknowledges the options with block 0 (RFC 2347).
+            transfer.exchange(&option_ack(block_size), |answer| *answer == Message::Ack(0))?;
+        }
+        transfer.send_blocks(&mut file, block_size.unwrap_or(packet_consts::DEFAULT_BLOCK_SIZE))
+    }
+
+    fn write(&self, request: Request, remote: SocketAddr) -> Result<usize> {
+        let mut transfer = self.transfer(remote)?;
+        if !self.writable {
+            transfer.send_error(ErrorCode::AccessViolation, "writes are disabled")?;
+            return Ok(0);
+        }
+        let (path, block_size) = match self.start(&request, &transfer)? {
+            Some(started) => started,
+            None => return Ok(0),
+        };
+
+        let mut file = match OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(file) => file,
+            Err(e) => {
+                let code = match e.kind() {
+                    ErrorKind::AlreadyExists => ErrorCode::FileExists,
+                    ErrorKind::NotFound | ErrorKind::PermissionDenied => ErrorCode::AccessViolation,
+                    _ => ErrorCode::NotDefined,
+                };
+                transfer.send_error(code, &e.to_string())?;
+                return Ok(0);
+            }
+        };
+
+        let first = match block_size {
+            Some(block_size) => option_ack(block_size),
+            None => Message::Ack(0),
+        };
+        transfer.receive_blocks(
+            first,
+            0,
+            &mut file,
+            block_size.unwrap_or(packet_consts::DEFAULT_BLOCK_SIZE),
+        )
+    }
+}
+
+fn option_ack(block_size: usize) -> Message {
+    Message::OptionAck(vec![(
+        packet_consts::OPTION_BLOCK_SIZE.to_string(),
+        block_size.to_string(),
+    )])
+}
+
+/// Returns the path of `filename` under `root`, refusing names that leave it:
+/// absolute paths and parent directory components.
+pub fn resolve(root: &Path, filename: &str) -> Result<PathBuf> {
+    let relative = Path::new(filename);
+    if filename.is_empty()
+        || !relative
+            .components()
+            .all(|component| matches!(component, Component::Normal(_) | Component::CurDir))
+    {
+        return Err(Error::InvalidPath.into());
+    }
+    Ok(root.join(relative))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use super::resolve;
+
+    #[test]
+    fn resolve_path() {
+        let root = Path::new("/srv/tftp");
+        assert_eq!(
+            resolve(root, "boot/pxelinux.0").unwrap(),
+            PathBuf::from("/srv/tftp/boot/pxelinux.0")
+        );
+        assert_eq!(
+            resolve(root, "./boot.img").unwrap(),
+            PathBuf::from("/srv/tftp/boot.img")
+        );
+        assert!(resolve(root, "/etc/passwd").is_err());
+        assert!(resolve(root, "../etc/passwd").is_err());
+        assert!(resolve(root, "boot/../../etc/passwd").is_err());
+        assert!(resolve(root, "").is_err());
+    }
+}
@@ -0,0 +1,183 @@
+use std::io::{ErrorKind, Read, Write};
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+use crate::error::{Error as RadishError, Result};
+use crate::macros::diagnostics::debug;
+use crate::net_device::Device;
+use crate::tftp::error::Error;
+use crate::tftp::packet::{consts, ErrorCode, Message};
+use crate::udp::udp_socket::UdpSocket;
+
+/// The lock-step exchange of a transfer with its peer, identified by the address and port it sends from.
+pub(crate) struct Transfer<D: Device> {
+    socket: UdpSocket<D>,
+    remote: SocketAddr,
+    /// Whether the port of the peer is known, a server answers a request from a new port.
+    locked: bool,
+    timeout: Duration,
+    retries: usize,
+}
+
+impl<D: Device> Transfer<D> {
+    pub fn new(socket: UdpSocket<D>, remote: SocketAddr, locked: bool, timeout: Duration, retries: usize) -> Self {
+        Self {
+            socket,
+            remote,
+            locked,
+            timeout,
+            retries,
+        }
+    }
+
+    pub fn send(&self, message: &Message) -> Result<()> {
+        self.socket.send_to(&message.build_vec(), self.remote)?;
+        Ok(())
+    }
+
+    /// Tell the peer the transfer is aborted.
+    pub fn send_error(&self, code: ErrorCode, message: &str) -> Result<()> {
+        self.send(&Message::Error {
+            code,
+            message: message.to_string(),
+        })
+    }
+
+    /// Send `message` until the peer answers with a packet `accept` takes, retransmitting it on timeout.
+    /// Other packets, such as duplicates, are ignored; an error packet ends the transfer.
+    pub fn exchange(&mut self, message: &Message, mut accept: impl FnMut(&Message) -> bool) -> Result<Message> {
+        let packet = message.build_vec();
+        for _ in 0..=self.retries {
+            self.socket.send_to(&packet, self.remote)?;
+            if let Some(answer) = self.receive(&mut accept)? {
+                return Ok(answer);
+            }
+        }
+        Err(Error::TimedOut.into())
+    }
+
+    /// Receive until a packet `accept` takes arrives, returns `None` when the timeout elapses first.
+    fn receive(&mut self, accept: &mut impl FnMut(&Message) -> bool) -> Result<Option<Message>> {
+        let deadline = Instant::now() + self.timeout;
+        let mut buf = vec![0; consts::MAX_BLOCK_SIZE + consts::HEADER_LEN];
+
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining == Duration::ZERO {
+                return Ok(None);
+            }
+            self.socket.set_read_timeout(Some(remaining));
+
+            let (len, sender) = match self.socket.recv_from(&mut buf) {
+                Ok(received) => received,
+                Err(RadishError::Io(e)) if e.kind() == ErrorKind::TimedOut => return Ok(None),
+                Err(e) => return Err(e),
+            };
+
+            let from_peer = if self.locked {
+                sender == self.remote
+            } else {
+                sender.ip() == self.remote.ip()
+            };
+            if !from_peer {
+                // RFC 1350 section 4, a packet of another transfer does not abort this one.
+                let error = Message::Error {
+                    code: ErrorCode::UnknownTransferId,
+                    message: "unknown transfer id".to_string(),
+                };
+                self.socket.send_to(&error.build_vec(), sender)?;
+                continue;
+            }
+
+            let message = match Message::parse(&buf[..len]) {
+                Ok(message) => message,
+                Err(e) => {
+                    debug!("tftp packet from {} dropped: {}", sender, e);
+                    continue;
+                }
+            };
+            if !self.locked {
+                self.remote = sender;
+                self.locked = true;
+            }
+
+            if let Message::Error { code, message } = message {
+                return Err(Error::Remote(code, message).into());
+            }
+            if accept(&message) {
+                return Ok(Some(message));
+            }
+        }
+    }
+
+    /// Send `input` in blocks of `block_size`, each one acknowledged before the next.
+    /// A transfer ends with a block shorter than `block_size`, empty if needed.
+    pub fn send_blocks(&mut self, input: &mut impl Read, block_size: usize) -> Result<usize> {
+        let mut block: u16 = 1;
+        let mut total = 0;
+        let mut data = vec![0; block_size];
+
+        loop {
+            let len = read_block(input, &mut data)?;
+            let message = Message::Data {
+                block,
+                data: data[..len].to_vec(),
+            };
+            self.exchange(&message, |answer| *answer == Message::Ack(block))?;
+
+            total += len;
+            if len < block_size {
+                return Ok(total);
+            }
+            block = block.wrapping_add(1);
+        }
+    }
+
+    /// Receive blocks of `block_size` into `output`, sending `first` to get the block after `last_block`
+    /// and acknowledging each block received.
+    pub fn receive_blocks(
+        &mut self,
+        first: Message,
+        mut last_block: u16,
+        output: &mut impl Write,
+        block_size: usize,
+    ) -> Result<usize> {
+        let mut total = 0;
+        let mut message = first;
+
+        loop {
+            let expected = last_block.wrapping_add(1);
+            let data = match self.exchange(
+                &message,
+                |answer| matches!(answer, Message::Data { block, .. } if *block == expected),
+            )? {
+                Message::Data { data, .. } => data,
+                _ => unreachable!(),
+            };
+
+            output.write_all(&data)?;
+            total += data.len();
+            last_block = expected;
+            message = Message::Ack(last_block);
+
+            if data.len() < block_size {
+                self.send(&message)?;
+                return Ok(total);
+            }
+        }
+    }
+}
+
+/// Fill `buf` from `input`, returns less than its length only at the end of `input`.
+fn read_block(input: &mut impl Read, buf: &mut [u8]) -> Result<usize> {
+    let mut len = 0;
+    while len < buf.len() {
+        match input.read(&mut buf[len..]) {
+            Ok(0) => break,
+            Ok(read) => len += read,
+            Err(e) if e.kind() == ErrorKind::Interrupted => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Ok(len)
+}
@@ -0,0 +1,100 @@
+//! The time of the state machines, which only need a monotonic instant and durations.
+//!
+//! With `std` the instant is the one of the standard library. Without it, `Instant` counts microseconds
+//! from an epoch of the caller's choosing, such as the boot of the device, and is handed in by a `Clock`.
+
+pub use core::time::Duration;
+#[cfg(feature = "std")]
+pub use std::time::Instant;
+
+#[cfg(not(feature = "std"))]
+pub use self::instant::Instant;
+
+#[cfg(not(feature = "std"))]
+mod instant {
+    use core::convert::TryFrom;
+    use core::ops::{Add, AddAssign, Sub, SubAssign};
+    use core::time::Duration;
+
+    /// A point in time, in microseconds from the epoch of the clock it was read from.
+    #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+    pub struct Instant {
+        micros: u64,
+    }
+
+    impl Instant {
+        pub const fn from_micros(micros: u64) -> Self {
+            Self { micros }
+        }
+
+        pub const fn from_millis(millis: u64) -> Self {
+            Self::from_micros(millis * 1000)
+        }
+
+        /// Returns the microseconds elapsed since the epoch.
+        pub const fn total_micros(&self) -> u64 {
+            self.micros
+        }
+
+        pub fn checked_duration_since(&self, earlier: Instant) -> Option<Duration> {
+            self.micros.checked_sub(earlier.micros).map(Duration::from_micros)
+        }
+
+        /// Returns the time elapsed since `earlier`, zero if `earlier` is later.
+        pub fn saturating_duration_since(&self, earlier: Instant) -> Duration {
+            self.checked_duration_since(earlier).unwrap_or_default()
+        }
+
+        pub fn duration_since(&self, earlier: Instant) -> Duration {
+            self.saturating_duration_since(earlier)
+        }
+
+        pub fn checked_add(&self, duration: Duration) -> Option<Instant> {
+            let micros = u64::try_from(duration.as_micros()).ok()?;
+            self.micros.checked_add(micros).map(Self::from_micros)
+        }
+
+        pub fn checked_sub(&self, duration: Duration) -> Option<Instant> {
+            let micros = u64::try_from(duration.as_micros()).ok()?;
+            self.micros.checked_sub(micros).map(Self::from_micros)
+        }
+    }
+
+    impl Add<Duration> for Instant {
+        type Output = Instant;
+
+        fn add(self, duration: Duration) -> Instant {
+            self.checked_add(duration)
+                .expect("overflow when adding duration to instant")
+        }
+    }
+
+    impl AddAssign<Duration> for Instant {
+        fn add_assign(&mut self, duration: Duration) {
+            *self = *self + duration;
+        }
+    }
+
+    impl Sub<Duration> for Instant {
+        type Output = Instant;
+
+        fn sub(self, duration: Duration) -> Instant {
+            self.checked_sub(duration)
+                .expect("overflow when subtracting duration from instant")
+        }
+    }
+
+    impl SubAssign<Duration> for Instant {
+        fn sub_assign(&mut self, duration: Duration) {
+            *self = *self - duration;
+        }
+    }
+
+    impl Sub<Instant> for Instant {
+        type Output = Duration;
+
+        fn sub(self, earlier: Instant) -> Duration {
+            self.saturating_duration_since(earlier)
+        }
+    }
+}
@@ -0,0 +1,114 @@
+use alloc::vec;
+use alloc::vec::Vec;
+use core::net::{IpAddr, Ipv4Addr};
+
+use crate::udp::packet::{consts, Packet};
+
+pub struct PacketBuilder {
+    src_port: u16,
+    dest_port: u16,
+    checksum: Option<u16>,
+    src_addr: IpAddr,
+    dest_addr: IpAddr,
+    payload: Vec<u8>,
+}
+
+impl PacketBuilder {
+    pub fn src_port(mut self, src_port: u16) -> Self {
+        self.src_port = src_port;
+        self
+    }
+
+    pub fn dest_port(mut self, dest_port: u16) -> Self {
+        self.dest_port = dest_port;
+        self
+    }
+
+    /// Set the checksum instead of computing it, zero sends the datagram without a checksum.
+    pub fn checksum(mut self, checksum: u16) -> Self {
+        self.checksum = Some(checksum);
+        self
+    }
+
+    /// Source address used by the pseudo-header checksum.
+    pub fn src_addr(mut self, src_addr: impl Into<IpAddr>) -> Self {
+        self.src_addr = src_addr.into();
+        self
+    }
+
+    /// Destination address used by the pseudo-header checksum.
+    pub fn dest_addr(mut self, dest_addr: impl Into<IpAddr>) -> Self {
+        self.dest_addr = dest_addr.into();
+        self
+    }
+
+    pub fn payload(mut self, payload: Vec<u8>) -> Self {
+        self.payload = payload;
+        self
+    }
+
+    pub fn build_vec(mut self) -> Vec<u8> {
+        let mut buffer: Vec<u8> = vec![0; consts::HEADER_LEN];
+        buffer.append(&mut self.payload);
+
+        let length = buffer.len() as u16;
+        let mut packet = Packet::new_unchecked(buffer.as_mut_slice());
+        packet.set_src_port(self.src_port);
+        packet.set_dest_port(self.dest_port);
+        packet.set_length(length);
+
+        match self.checksum {
+            Some(checksum) => packet.set_checksum(checksum),
+            None => packet.fill_checksum(self.src_addr, self.dest_addr),
+        }
+
+        buffer
+    }
+
+    pub fn build(self) -> Packet<Vec<u8>> {
+        Packet::new_unchecked(self.build_vec())
+    }
+}
+
+impl Default for PacketBuilder {
+    fn default() -> Self {
+        Self {
+            src_port: 0,
+            dest_port: 0,
+            checksum: None,
+            src_addr: Ipv4Addr::UNSPECIFIED.into(),
+            dest_addr: Ipv4Addr::UNSPECIFIED.into(),
+            payload: vec![],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    #[test]
+    fn build() {
+        let src_addr = Ipv4Addr::new(192, 168, 233, 234);
+        let dest_addr = Ipv4Addr::new(192, 168, 233, 233);
+        let payload = vec![1, 2, 3, 4, 5];
+
+        let packet = super::PacketBuilder::default()
+            .src_port(54321)
+            .dest_port(53)
+            .src_addr(src_addr)
+            .dest_addr(dest_addr)
+            .payload(payload.clone())
+            .build();
+
+        assert_eq!(packet.src_port(), 54321);
+        assert_eq!(packet.dest_port(), 53);
+        assert_eq!(packet.length(), 13);
+        assert_eq!(packet.payload(), payload.as_slice());
+        assert!(packet.verify_checksum(src_addr, dest_addr));
+
+        let packet = super::PacketBuilder::default().checksum(0).payload(payload).build();
+        assert_eq!(packet.checksum(), 0);
+        assert!(packet.verify_checksum(src_addr, dest_addr));
+    }
+}
@@ -0,0 +1,38 @@
+use core::fmt::{Display, Formatter};
+
+#[derive(Debug)]
+pub enum Error {
+    InvalidLength,
+    InvalidCoverage,
+    InvalidChecksum,
+    InvalidPort,
+    InvalidAddress,
+    AddressInUse,
+    NotMember,
+    BroadcastDenied,
+    PortUnreachable,
+    QueueFull,
+    MessageTooLong,
+    UnsupportedOption,
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Error::InvalidLength => write!(f, "invalid length"),
+            Error::InvalidCoverage => write!(f, "invalid checksum coverage"),
+            Error::InvalidChecksum => write!(f, "invalid checksum"),
+            Error::InvalidPort => write!(f, "invalid port"),
+            Error::InvalidAddress => write!(f, "invalid address"),
+            Error::AddressInUse => write!(f, "address in use"),
+            Error::NotMember => write!(f, "not a member of the multicast group"),
+            Error::BroadcastDenied => write!(f, "broadcast not enabled on the socket"),
+            Error::PortUnreachable => write!(f, "port unreachable"),
+            Error::QueueFull => write!(f, "receive queue full"),
+            Error::MessageTooLong => write!(f, "message too long"),
+            Error::UnsupportedOption => write!(f, "unsupported option"),
+        }
+    }
+}
+
+impl core::error::Error for Error {}
@@ -0,0 +1,223 @@
+use core::fmt::{Debug, Formatter};
+
+use crate::checksum::{pseudo_header_checksum, IpAddress};
+use crate::error::Result;
+use crate::ipv4::packet::Protocol;
+use crate::udp::error::Error;
+use crate::udp::packet::consts;
+
+/// A UDP-Lite datagram (RFC 3828).
+///
+/// The length field of UDP is replaced by the checksum coverage, the number of octets covered
+/// by the checksum from the start of the header, so damaged payload octets past it are still delivered.
+/// The length of the datagram is the one of the IP payload.
+pub struct Packet<Buf> {
+    buffer: Buf,
+}
+
+impl<Buf> Packet<Buf>
+where
+    Buf: AsRef<[u8]>,
+{
+    pub fn new_unchecked(buffer: Buf) -> Self {
+        Packet { buffer }
+    }
+
+    pub fn new_checked(buffer: Buf) -> Result<Self> {
+        let packet = Self::new_unchecked(buffer);
+        packet.check_len()?;
+        Ok(packet)
+    }
+
+    /// The coverage must include the header and fit in the datagram, zero covers the whole datagram (RFC 3828 section 3.1).
+    pub fn check_len(&self) -> Result<()> {
+        let buf_len = self.buffer.as_ref().len();
+
+        if buf_len < consts::HEADER_LEN {
+            return Err(Error::InvalidLength.into());
+        }
+
+        let coverage = self.checksum_coverage() as usize;
+
+        if coverage != 0 && (coverage < consts::HEADER_LEN || coverage > buf_len) {
+            return Err(Error::InvalidCoverage.into());
+        }
+
+        Ok(())
+    }
+
+    pub fn src_port(&self) -> u16 {
+        u16::from_be_bytes([self.buffer.as_ref()[0], self.buffer.as_ref()[1]])
+    }
+
+    pub fn dest_port(&self) -> u16 {
+        u16::from_be_bytes([self.buffer.as_ref()[2], self.buffer.as_ref()[3]])
+    }
+
+    pub fn checksum_coverage(&self) -> u16 {
+        u16::from_be_bytes([self.buffer.as_ref()[4], self.buffer.as_ref()[5]])
+    }
+
+    pub fn checksum(&self) -> u16 {
+        u16::from_be_bytes([self.buffer.as_ref()[6], self.buffer.as_ref()[7]])
+    }
+
+    /// Returns the number of octets covered by the checksum, header included.
+    pub fn covered_len(&self) -> usize {
+        match self.checksum_coverage() {
+            0 => self.buffer.as_ref().len(),
+            coverage => coverage as usize,
+        }
+    }
+
+    pub fn payload(&self) -> &[u8] {
+        &self.buffer.as_ref()[consts::HEADER_LEN..]
+    }
+
+    /// Validate the checksum of the covered octets, using the pseudo-header of the IPv4 or IPv6 addresses.
+    /// Unlike UDP the checksum is mandatory, a zero checksum is invalid.
+    pub fn verify_checksum<A: IpAddress>(&self, src_addr: A, dest_addr: A) -> bool {
+        if self.checksum() == 0 {
+            return false;
+        }
+        let buffer = self.buffer.as_ref();
+        pseudo_header_checksum(
+            src_addr,
+            dest_addr,
+            Protocol::UdpLite,
+            buffer.len(),
+            &buffer[..self.covered_len()],
+        ) == 0
+    }
+}
+
+impl<Buf> Packet<Buf>
+where
+    Buf: AsRef<[u8]> + AsMut<[u8]>,
+{
+    pub fn set_src_port(&mut self, src_port: u16) {
+        self.buffer.as_mut()[0..=1].copy_from_slice(src_port.to_be_bytes().as_ref());
+    }
+
+    pub fn set_dest_port(&mut self, dest_port: u16) {
+        self.buffer.as_mut()[2..=3].copy_from_slice(dest_port.to_be_bytes().as_ref());
+    }
+
+    pub fn set_checksum_coverage(&mut self, checksum_coverage: u16) {
+        self.buffer.as_mut()[4..=5].copy_from_slice(checksum_coverage.to_be_bytes().as_ref());
+    }
+
+    pub fn set_checksum(&mut self, checksum: u16) {
+        self.buffer.as_mut()[6..=7].copy_from_slice(checksum.to_be_bytes().as_ref());
+    }
+
+    pub fn payload_mut(&mut self) -> &mut [u8] {
+        &mut self.buffer.as_mut()[consts::HEADER_LEN..]
+    }
+
+    pub fn set_payload(&mut self, payload: &[u8]) {
+        self.payload_mut()[..payload.len()].copy_from_slice(payload);
+    }
+
+    /// Compute and fill the checksum of the covered octets, using the pseudo-header of the IPv4 or IPv6 addresses.
+    /// A computed checksum of zero is sent as all ones.
+    pub fn fill_checksum<A: IpAddress>(&mut self, src_addr: A, dest_addr: A) {
+        self.set_checksum(0);
+        let covered_len = self.covered_len();
+        let buffer = self.buffer.as_ref();
+        let checksum_value = match pseudo_header_checksum(
+            src_addr,
+            dest_addr,
+            Protocol::UdpLite,
+            buffer.len(),
+            &buffer[..covered_len],
+        ) {
+            0 => 0xffff,
+            checksum_value => checksum_value,
+        };
+        self.set_checksum(checksum_value);
+    }
+}
+
+impl<Buf> Debug for Packet<Buf>
+where
+    Buf: AsRef<[u8]>,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "source port: {:?}, destination port: {:?}, checksum coverage: {:?}, checksum: {:#x}",
+            self.src_port(),
+            self.dest_port(),
+            self.checksum_coverage(),
+            self.checksum(),
+        )
+    }
+}
+
+impl<Buf> AsRef<[u8]> for Packet<Buf>
+where
+    Buf: AsRef<[u8]>,
+{
+    fn as_ref(&self) -> &[u8] {
+        self.buffer.as_ref()
+    }
+}
+
+impl<Buf> AsMut<[u8]> for Packet<Buf>
+where
+    Buf: AsMut<[u8]>,
+{
+    fn as_mut(&mut self) -> &mut [u8] {
+        self.buffer.as_mut()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    #[test]
+    fn partial_checksum() {
+        let src_addr = Ipv4Addr::new(192, 168, 233, 234);
+        let dest_addr = Ipv4Addr::new(192, 168, 233, 233);
+
+        let mut packet = super::Packet::new_unchecked(vec![0; 28]);
+        packet.set_src_port(5004);
+        packet.set_dest_port(5004);
+        packet.set_checksum_coverage(16);
+        packet.set_payload(b"rtp header and media");
+        assert!(!packet.verify_checksum(src_addr, dest_addr));
+
+        packet.fill_checksum(src_addr, dest_addr);
+        assert!(packet.verify_checksum(src_addr, dest_addr));
+        assert_eq!(packet.covered_len(), 16);
+
+        // Damage past the coverage is tolerated, not inside it.
+        packet.payload_mut()[12] ^= 0xff;
+        assert!(packet.verify_checksum(src_addr, dest_addr));
+        packet.payload_mut()[0] ^= 0xff;
+        assert!(!packet.verify_checksum(src_addr, dest_addr));
+
+        // Full coverage.
+        packet.set_checksum_coverage(0);
+        packet.fill_checksum(src_addr, dest_addr);
+        assert_eq!(packet.covered_len(), 28);
+        packet.payload_mut()[12] ^= 0xff;
+        assert!(!packet.verify_checksum(src_addr, dest_addr));
+    }
+
+    #[test]
+    fn new_checked() {
+        let mut bytes = vec![0; 16];
+        assert!(super::Packet::new_checked(bytes.as_slice()).is_ok());
+
+        bytes[5] = 7;
+        assert!(super::Packet::new_checked(bytes.as_slice()).is_err());
+        bytes[5] = 17;
+        assert!(super::Packet::new_checked(bytes.as_slice()).is_err());
+        bytes[5] = 16;
+        assert!(super::Packet::new_checked(bytes.as_slice()).is_ok());
+        assert!(super::Packet::new_checked(&bytes[..7]).is_err());
+    }
+}
@@ -0,0 +1,8 @@
+pub mod builder;
+pub mod error;
+pub mod lite;
+pub mod packet;
+#[cfg(feature = "std")]
+pub mod socket;
+#[cfg(feature = "std")]
+pub mod udp_socket;
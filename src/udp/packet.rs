@@ -0,0 +1,254 @@
+use core::fmt::{Debug, Formatter};
+
+use crate::checksum::{pseudo_header_checksum, IpAddress};
+use crate::error::Result;
+use crate::ipv4::packet::Protocol;
+use crate::udp::error::Error;
+
+pub mod consts {
+    pub const HEADER_LEN: usize = 8; // RFC 768
+}
+
+pub struct Packet<Buf> {
+    buffer: Buf,
+}
+
+impl<Buf> Packet<Buf>
+where
+    Buf: AsRef<[u8]>,
+{
+    pub fn new_unchecked(buffer: Buf) -> Self {
+        Packet { buffer }
+    }
+
+    pub fn new_checked(buffer: Buf) -> Result<Self> {
+        let packet = Self::new_unchecked(buffer);
+        packet.check_len()?;
+        Ok(packet)
+    }
+
+    /// The length field must cover the header and fit in the buffer, octets after it are padding.
+    pub fn check_len(&self) -> Result<()> {
+        let buf_len = self.buffer.as_ref().len();
+
+        if buf_len < consts::HEADER_LEN {
+            return Err(Error::InvalidLength.into());
+        }
+
+        let length = self.length() as usize;
+
+        if length < consts::HEADER_LEN || length > buf_len {
+            return Err(Error::InvalidLength.into());
+        }
+
+        Ok(())
+    }
+
+    pub fn src_port(&self) -> u16 {
+        u16::from_be_bytes([self.buffer.as_ref()[0], self.buffer.as_ref()[1]])
+    }
+
+    pub fn dest_port(&self) -> u16 {
+        u16::from_be_bytes([self.buffer.as_ref()[2], self.buffer.as_ref()[3]])
+    }
+
+    /// Returns the length of the header and the payload.
+    pub fn length(&self) -> u16 {
+        u16::from_be_bytes([self.buffer.as_ref()[4], self.buffer.as_ref()[5]])
+    }
+
+    pub fn checksum(&self) -> u16 {
+        u16::from_be_bytes([self.buffer.as_ref()[6], self.buffer.as_ref()[7]])
+    }
+
+    pub fn payload(&self) -> &[u8] {
+        &self.buffer.as_ref()[consts::HEADER_LEN..self.length() as usize]
+    }
+}
+
+impl<Buf> Packet<Buf>
+where
+    Buf: AsRef<[u8]> + AsMut<[u8]>,
+{
+    pub fn set_src_port(&mut self, src_port: u16) {
+        self.buffer.as_mut()[0..=1].copy_from_slice(src_port.to_be_bytes().as_ref());
+    }
+
+    pub fn set_dest_port(&mut self, dest_port: u16) {
+        self.buffer.as_mut()[2..=3].copy_from_slice(dest_port.to_be_bytes().as_ref());
+    }
+
+    pub fn set_length(&mut self, length: u16) {
+        self.buffer.as_mut()[4..=5].copy_from_slice(length.to_be_bytes().as_ref());
+    }
+
+    pub fn set_checksum(&mut self, checksum: u16) {
+        self.buffer.as_mut()[6..=7].copy_from_slice(checksum.to_be_bytes().as_ref());
+    }
+
+    pub fn payload_mut(&mut self) -> &mut [u8] {
+        let length = self.length() as usize;
+        &mut self.buffer.as_mut()[consts::HEADER_LEN..length]
+    }
+
+    pub fn set_payload(&mut self, payload: &[u8]) {
+        self.payload_mut()[..payload.len()].copy_from_slice(payload);
+    }
+
+    /// Compute and fill the checksum, using the pseudo-header of the IPv4 or IPv6 addresses.
+    /// A computed checksum of zero is sent as all ones, zero means no checksum (RFC 768).
+    pub fn fill_checksum<A: IpAddress>(&mut self, src_addr: A, dest_addr: A) {
+        self.set_checksum(0);
+        let length = self.length() as usize;
+        let checksum_value = match pseudo_header_checksum(
+            src_addr,
+            dest_addr,
+            Protocol::Udp,
+            length,
+            &self.buffer.as_ref()[..length],
+        ) {
+            0 => 0xffff,
+            checksum_value => checksum_value,
+        };
+        self.set_checksum(checksum_value);
+    }
+}
+
+impl<Buf> Packet<Buf>
+where
+    Buf: AsRef<[u8]>,
+{
+    /// Validate the checksum, using the pseudo-header of the IPv4 or IPv6 addresses.
+    /// A datagram sent without a checksum is valid over IPv4 only, IPv6 requires one (RFC 8200 section 8.1).
+    pub fn verify_checksum<A: IpAddress>(&self, src_addr: A, dest_addr: A) -> bool {
+        if self.checksum() == 0 {
+            return src_addr.version() == 4;
+        }
+        let length = self.length() as usize;
+        pseudo_header_checksum(
+            src_addr,
+            dest_addr,
+            Protocol::Udp,
+            length,
+            &self.buffer.as_ref()[..length],
+        ) == 0
+    }
+}
+
+impl<Buf> Debug for Packet<Buf>
+where
+    Buf: AsRef<[u8]>,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "source port: {:?}, destination port: {:?}, length: {:?}, checksum: {:#x}",
+            self.src_port(),
+            self.dest_port(),
+            self.length(),
+            self.checksum(),
+        )
+    }
+}
+
+impl<Buf> AsRef<[u8]> for Packet<Buf>
+where
+    Buf: AsRef<[u8]>,
+{
+    fn as_ref(&self) -> &[u8] {
+        self.buffer.as_ref()
+    }
+}
+
+impl<Buf> AsMut<[u8]> for Packet<Buf>
+where
+    Buf: AsMut<[u8]>,
+{
+    fn as_mut(&mut self) -> &mut [u8] {
+        self.buffer.as_mut()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    #[test]
+    fn new_checked() {
+        let bytes: Vec<u8> = vec![
+            // udp header
+            0xd4, 0x31, 0x00, 0x35, 0x00, 0x0d, 0x5a, 0x5b, //
+            // udp payload
+            0x68, 0x65, 0x6c, 0x6c, 0x6f, //
+            // padding after the datagram
+            0x00, 0x00,
+        ];
+
+        let packet = super::Packet::new_checked(bytes.as_slice()).expect("a valid udp packet");
+
+        assert_eq!(packet.src_port(), 54321);
+        assert_eq!(packet.dest_port(), 53);
+        assert_eq!(packet.length(), 13);
+        assert_eq!(packet.checksum(), 0x5a5b);
+        assert_eq!(packet.payload(), b"hello");
+
+        assert!(super::Packet::new_checked(&bytes[..7]).is_err());
+        assert!(super::Packet::new_checked(&bytes[..12]).is_err());
+
+        let mut short = bytes.clone();
+        short[5] = 7;
+        assert!(super::Packet::new_checked(short).is_err());
+    }
+
+    #[test]
+    fn setter() {
+        let mut packet = super::Packet::new_unchecked(vec![0; 12]);
+
+        packet.set_src_port(68);
+        packet.set_dest_port(67);
+        packet.set_length(12);
+        packet.set_checksum(0xbeef);
+        packet.set_payload(b"dhcp");
+
+        assert_eq!(packet.src_port(), 68);
+        assert_eq!(packet.dest_port(), 67);
+        assert_eq!(packet.length(), 12);
+        assert_eq!(packet.checksum(), 0xbeef);
+        assert_eq!(packet.payload(), b"dhcp");
+    }
+
+    #[test]
+    fn checksum() {
+        let src_addr = Ipv4Addr::new(192, 168, 233, 234);
+        let dest_addr = Ipv4Addr::new(192, 168, 233, 233);
+        let mut packet = super::Packet::new_unchecked(vec![
+            0xd4, 0x31, 0x00, 0x35, 0x00, 0x0d, 0, 0, 0x68, 0x65, 0x6c, 0x6c, 0x6f,
+        ]);
+
+        // A zero checksum was not computed by the sender.
+        assert!(packet.verify_checksum(src_addr, dest_addr));
+
+        packet.fill_checksum(src_addr, dest_addr);
+        assert_ne!(packet.checksum(), 0);
+        assert!(packet.verify_checksum(src_addr, dest_addr));
+        assert!(!packet.verify_checksum(src_addr, Ipv4Addr::new(10, 0, 0, 1)));
+
+        // A payload word equal to the checksum of the rest makes the computed checksum zero.
+        let mut packet = super::Packet::new_unchecked(vec![0xd4, 0x31, 0x00, 0x35, 0x00, 0x0a, 0, 0, 0, 0]);
+        packet.fill_checksum(src_addr, dest_addr);
+        let word = packet.checksum();
+        packet.set_payload(&word.to_be_bytes());
+        packet.fill_checksum(src_addr, dest_addr);
+        assert_eq!(packet.checksum(), 0xffff);
+        assert!(packet.verify_checksum(src_addr, dest_addr));
+
+        // The same datagram over IPv6, where the checksum is mandatory.
+        let src_addr: Ipv6Addr = "2001:db8::1".parse().unwrap();
+        let dest_addr: Ipv6Addr = "2001:db8::2".parse().unwrap();
+        packet.set_checksum(0);
+        assert!(!packet.verify_checksum(src_addr, dest_addr));
+        packet.fill_checksum(src_addr, dest_addr);
+        assert!(packet.verify_checksum(src_addr, dest_addr));
+        assert!(!packet.verify_checksum(Ipv6Addr::LOCALHOST, dest_addr));
+    }
+}
@@ -0,0 +1,657 @@
+use std::collections::{HashMap, VecDeque};
+use std::net::{IpAddr, SocketAddr};
+
+use crate::error::Result;
+use crate::icmpv4::packet::{ErrorMessage, Quoted};
+use crate::options::{SocketConfig, SocketOption};
+use crate::tcp::socket::consts::{EPHEMERAL_PORT_FIRST, EPHEMERAL_PORT_LAST};
+use crate::udp::error::Error;
+use crate::udp::packet::Packet;
+
+pub mod consts {
+    pub const DEFAULT_QUEUE_DEPTH: usize = 64; // Datagrams queued on a socket, later ones are dropped
+    pub const MAX_PAYLOAD_LEN: usize = 65507; // Largest IPv4 datagram minus the minimum IPv4 and UDP headers
+    pub const DEFAULT_MULTICAST_TTL: u8 = 1; // RFC 1112 section 6.1, multicast stays on the local network by default
+}
+
+/// A handle to a socket stored in a `SocketSet`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct SocketHandle(usize);
+
+/// A datagram to be sent by the interface, an unspecified source address is replaced by the interface address.
+/// Both addresses are of the same family.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Datagram {
+    pub src_addr: SocketAddr,
+    pub dest_addr: SocketAddr,
+    pub payload: Vec<u8>,
+    /// The TTL of the datagram, `None` for the interface default.
+    pub ttl: Option<u8>,
+    pub tos: u8,
+}
+
+/// A bound UDP port and the datagrams received on it.
+///
+/// A socket is bound to an IPv4 or an IPv6 address and only exchanges datagrams of that family,
+/// a socket bound to the unspecified IPv6 address does not receive IPv4 datagrams.
+#[derive(Debug)]
+pub struct Socket {
+    local: SocketAddr,
+    /// The peer of a connected socket, the only one datagrams are received from.
+    remote: Option<SocketAddr>,
+    /// An ICMP error about a datagram sent by a connected socket, reported by the next call.
+    pending_error: Option<ErrorMessage>,
+    rx_queue: VecDeque<(SocketAddr, Vec<u8>)>,
+    queue_depth: usize,
+    reuse_address: bool,
+    /// Whether datagrams may be sent to a broadcast address.
+    broadcast: bool,
+    /// The multicast groups whose datagrams are received.
+    groups: Vec<IpAddr>,
+    ttl: Option<u8>,
+    tos: u8,
+}
+
+impl Socket {
+    fn new(local: SocketAddr) -> Self {
+        Self {
+            local,
+            remote: None,
+            pending_error: None,
+            rx_queue: VecDeque::new(),
+            queue_depth: consts::DEFAULT_QUEUE_DEPTH,
+            reuse_address: false,
+            broadcast: false,
+            groups: vec![],
+            ttl: None,
+            tos: 0,
+        }
+    }
+
+    pub fn local(&self) -> SocketAddr {
+        self.local
+    }
+
+    pub fn remote(&self) -> Option<SocketAddr> {
+        self.remote
+    }
+
+    /// Connect the socket to `remote`: datagrams from other peers are dropped from now on,
+    /// and ICMP errors about the datagrams sent to `remote` are reported. `None` disconnects it.
+    pub fn connect(&mut self, remote: Option<SocketAddr>) -> Result<()> {
+        if remote.is_some_and(|remote| remote.port() == 0) {
+            return Err(Error::InvalidPort.into());
+        }
+        if remote.is_some_and(|remote| !same_family(remote.ip(), self.local.ip())) {
+            return Err(Error::InvalidAddress.into());
+        }
+
+        self.remote = remote;
+        self.pending_error = None;
+        if let Some(remote) = remote {
+            self.rx_queue.retain(|(sender, _)| *sender == remote);
+        }
+        Ok(())
+    }
+
+    /// Take the ICMP error reported since the last call.
+    pub fn take_error(&mut self) -> Option<ErrorMessage> {
+        self.pending_error.take()
+    }
+
+    /// Returns the number of datagrams waiting to be received.
+    pub fn recv_queue(&self) -> usize {
+        self.rx_queue.len()
+    }
+
+    pub fn queue_depth(&self) -> usize {
+        self.queue_depth
+    }
+
+    /// Bound the number of datagrams waiting to be received, datagrams arriving on a full queue are dropped.
+    pub fn set_queue_depth(&mut self, queue_depth: usize) {
+        self.queue_depth = queue_depth;
+        self.rx_queue.truncate(queue_depth);
+    }
+
+    pub fn broadcast(&self) -> bool {
+        self.broadcast
+    }
+
+    /// Allow sending to a broadcast address, which is refused by default like on other stacks.
+    pub fn set_broadcast(&mut self, broadcast: bool) {
+        self.broadcast = broadcast;
+    }
+
+    /// Returns the multicast groups joined.
+    pub fn groups(&self) -> &[IpAddr] {
+        &self.groups
+    }
+
+    /// Receive the datagrams sent to the multicast `group`, of the family of the socket.
+    pub fn join_multicast(&mut self, group: impl Into<IpAddr>) -> Result<()> {
+        let group = group.into();
+        if !group.is_multicast() || !same_family(group, self.local.ip()) {
+            return Err(Error::InvalidAddress.into());
+        }
+        if self.groups.contains(&group) {
+            return Err(Error::AddressInUse.into());
+        }
+
+        self.groups.push(group);
+        Ok(())
+    }
+
+    pub fn leave_multicast(&mut self, group: impl Into<IpAddr>) -> Result<()> {
+        let group = group.into();
+        let index = self.groups.iter().position(|joined| *joined == group);
+        self.groups.remove(index.ok_or(Error::NotMember)?);
+        Ok(())
+    }
+
+    pub fn set_option(&mut self, option: SocketOption) -> Result<()> {
+        match option {
+            SocketOption::Ttl(ttl) => self.ttl = Some(ttl),
+            SocketOption::Tos(tos) => self.tos = tos,
+            SocketOption::ReuseAddress(reuse_address) => self.reuse_address = reuse_address,
+            _ => return Err(Error::UnsupportedOption.into()),
+        }
+        Ok(())
+    }
+
+    /// Take the oldest datagram, copying as much of it as fits in `buf`.
+    /// Returns the length copied and the sender, the rest of a datagram larger than `buf` is dropped.
+    pub fn recv_from(&mut self, buf: &mut [u8]) -> Option<(usize, SocketAddr)> {
+        let (remote, payload) = self.rx_queue.pop_front()?;
+        let len = payload.len().min(buf.len());
+        buf[..len].copy_from_slice(&payload[..len]);
+        Some((len, remote))
+    }
+
+    /// Build the datagram carrying `payload` to `remote`.
+    ///
+    /// `broadcast` tells whether `remote` is a broadcast address of the interface, which needs `set_broadcast`.
+    /// Datagrams sent to a multicast group get a TTL of 1 unless a TTL is set.
+    pub fn send_to(&self, payload: &[u8], remote: impl Into<SocketAddr>, broadcast: bool) -> Result<Datagram> {
+        let remote = remote.into();
+        if payload.len() > consts::MAX_PAYLOAD_LEN {
+            return Err(Error::MessageTooLong.into());
+        }
+        if remote.port() == 0 {
+            return Err(Error::InvalidPort.into());
+        }
+        if !same_family(remote.ip(), self.local.ip()) {
+            return Err(Error::InvalidAddress.into());
+        }
+        if broadcast && !self.broadcast {
+            return Err(Error::BroadcastDenied.into());
+        }
+
+        let ttl = match self.ttl {
+            None if remote.ip().is_multicast() => Some(consts::DEFAULT_MULTICAST_TTL),
+            ttl => ttl,
+        };
+
+        Ok(Datagram {
+            src_addr: self.local,
+            dest_addr: remote,
+            payload: payload.to_vec(),
+            ttl,
+            tos: self.tos,
+        })
+    }
+
+    fn matches(&self, dest_addr: IpAddr) -> bool {
+        (self.local.ip().is_unspecified() && same_family(self.local.ip(), dest_addr)) || self.local.ip() == dest_addr
+    }
+
+    fn accepts(&self, local: SocketAddr, remote: SocketAddr) -> bool {
+        self.matches(local.ip()) && self.remote.is_none_or(|connected| connected == remote)
+    }
+
+    fn enqueue(&mut self, remote: SocketAddr, payload: &[u8]) -> Result<()> {
+        if self.rx_queue.len() >= self.queue_depth {
+            return Err(Error::QueueFull.into());
+        }
+        self.rx_queue.push_back((remote, payload.to_vec()));
+        Ok(())
+    }
+}
+
+/// The UDP demultiplexer, which owns every socket of an interface.
+pub struct SocketSet {
+    sockets: Vec<Option<Socket>>,
+    /// Sockets by local port, in binding order.
+    table: HashMap<u16, Vec<SocketHandle>>,
+    next_ephemeral_port: u16,
+}
+
+impl SocketSet {
+    pub fn new() -> Self {
+        Self {
+            sockets: vec![],
+            table: HashMap::new(),
+            next_ephemeral_port: EPHEMERAL_PORT_FIRST,
+        }
+    }
+
+    pub fn get(&self, handle: SocketHandle) -> Option<&Socket> {
+        self.sockets.get(handle.0)?.as_ref()
+    }
+
+    pub fn get_mut(&mut self, handle: SocketHandle) -> Option<&mut Socket> {
+        self.sockets.get_mut(handle.0)?.as_mut()
+    }
+
+    /// Allocate an unused port from the dynamic range.
+    pub fn ephemeral_port(&mut self) -> Result<u16> {
+        let range_len = (EPHEMERAL_PORT_LAST - EPHEMERAL_PORT_FIRST) as usize + 1;
+
+        for _ in 0..range_len {
+            let port = self.next_ephemeral_port;
+            self.next_ephemeral_port = if port == EPHEMERAL_PORT_LAST {
+                EPHEMERAL_PORT_FIRST
+            } else {
+                port + 1
+            };
+
+            if !self.table.contains_key(&port) {
+                return Ok(port);
+            }
+        }
+
+        Err(Error::AddressInUse.into())
+    }
+
+    /// Bind `local`, an unspecified address receives on every address and a zero port is replaced by an ephemeral port.
+    pub fn bind(&mut self, local: impl Into<SocketAddr>) -> Result<SocketHandle> {
+        self.bind_with_config(local, &SocketConfig::default())
+    }
+
+    /// Bind like `bind`, with the options of `config`.
+    ///
+    /// An address overlapping the one of another socket can only be bound if both set `reuse_address`,
+    /// datagrams then go to the socket bound last.
+    pub fn bind_with_config(&mut self, local: impl Into<SocketAddr>, config: &SocketConfig) -> Result<SocketHandle> {
+        let local = local.into();
+        let local = if local.port() == 0 {
+            SocketAddr::new(local.ip(), self.ephemeral_port()?)
+        } else {
+            local
+        };
+
+        let conflict = self.bound(local.port()).any(|(_, socket)| {
+            (socket.matches(local.ip()) || (local.ip().is_unspecified() && same_family(socket.local.ip(), local.ip())))
+                && !(socket.reuse_address && config.is_reuse_address())
+        });
+        if conflict {
+            return Err(Error::AddressInUse.into());
+        }
+
+        let mut socket = Socket::new(local);
+        for option in config.options() {
+            socket.set_option(option)?;
+        }
+
+        let handle = match self.sockets.iter().position(Option::is_none) {
+            Some(index) => {
+                self.sockets[index] = Some(socket);
+                SocketHandle(index)
+            }
+            None => {
+                self.sockets.push(Some(socket));
+                SocketHandle(self.sockets.len() - 1)
+            }
+        };
+        self.table.entry(local.port()).or_default().push(handle);
+        Ok(handle)
+    }
+
+    /// Close a socket, dropping the datagrams it did not receive.
+    pub fn remove(&mut self, handle: SocketHandle) -> Option<Socket> {
+        let socket = self.sockets.get_mut(handle.0)?.take()?;
+
+        let port = socket.local.port();
+        if let Some(handles) = self.table.get_mut(&port) {
+            handles.retain(|bound| *bound != handle);
+            if handles.is_empty() {
+                self.table.remove(&port);
+            }
+        }
+
+        Some(socket)
+    }
+
+    fn bound(&self, port: u16) -> impl Iterator<Item = (SocketHandle, &Socket)> + '_ {
+        self.table
+            .get(&port)
+            .into_iter()
+            .flatten()
+            .filter_map(move |handle| Some((*handle, self.get(*handle)?)))
+    }
+
+    /// Find the socket receiving datagrams sent from `remote` to `local`: a socket connected to `remote`
+    /// before an unconnected one, one bound to the address itself before one bound to the unspecified address,
+    /// the one bound last among equals.
+    fn lookup(&self, local: SocketAddr, remote: SocketAddr) -> Option<SocketHandle> {
+        self.bound(local.port())
+            .filter(|(_, socket)| socket.accepts(local, remote))
+            .max_by_key(|(handle, socket)| {
+                let position = self.table[&local.port()].iter().position(|bound| bound == handle);
+                (socket.remote.is_some(), !socket.local.ip().is_unspecified(), position)
+            })
+            .map(|(handle, _)| handle)
+    }
+
+    /// Report an ICMP error to the connected socket that sent the quoted datagram.
+    /// Like other stacks, only hard errors are reported, and unconnected sockets do not get them.
+    pub fn process_icmp(&mut self, message: ErrorMessage, quoted: &Quoted) {
+        if !message.is_hard() {
+            return;
+        }
+
+        let (src_port, dest_port) = quoted.ports();
+        let local = SocketAddr::new(quoted.src_addr.into(), src_port);
+        let remote = SocketAddr::new(quoted.dest_addr.into(), dest_port);
+
+        let handles: Vec<SocketHandle> = self
+            .bound(local.port())
+            .filter(|(_, socket)| socket.remote == Some(remote) && socket.matches(local.ip()))
+            .map(|(handle, _)| handle)
+            .collect();
+        for handle in handles {
+            self.get_mut(handle).unwrap().pending_error = Some(message);
+        }
+    }
+
+    /// Handle a datagram received by the interface, queueing its payload on the socket bound to its destination.
+    pub fn process(
+        &mut self,
+        src_addr: impl Into<IpAddr>,
+        dest_addr: impl Into<IpAddr>,
+        buffer: &[u8],
+    ) -> Result<SocketHandle> {
+        let (src_addr, dest_addr) = (src_addr.into(), dest_addr.into());
+        let packet = parse(src_addr, dest_addr, buffer)?;
+        let local = SocketAddr::new(dest_addr, packet.dest_port());
+        let remote = SocketAddr::new(src_addr, packet.src_port());
+        let handle = self.lookup(local, remote).ok_or(Error::PortUnreachable)?;
+
+        self.get_mut(handle).unwrap().enqueue(remote, packet.payload())?;
+        Ok(handle)
+    }
+
+    /// Handle a datagram sent to a broadcast address or a multicast group, queueing a copy of its payload
+    /// on every socket receiving it: the sockets bound to its port that joined the group, or for a broadcast,
+    /// the ones bound to the unspecified address or to the broadcast address. Returns the sockets queueing it.
+    pub fn process_multicast(
+        &mut self,
+        src_addr: impl Into<IpAddr>,
+        dest_addr: impl Into<IpAddr>,
+        buffer: &[u8],
+    ) -> Result<Vec<SocketHandle>> {
+        let (src_addr, dest_addr) = (src_addr.into(), dest_addr.into());
+        let packet = parse(src_addr, dest_addr, buffer)?;
+        let local = SocketAddr::new(dest_addr, packet.dest_port());
+        let remote = SocketAddr::new(src_addr, packet.src_port());
+
+        let handles: Vec<SocketHandle> = self
+            .bound(local.port())
+            .filter(|(_, socket)| {
+                socket.accepts(local, remote) && (!dest_addr.is_multicast() || socket.groups.contains(&dest_addr))
+            })
+            .map(|(handle, _)| handle)
+            .collect();
+        if handles.is_empty() {
+            return Err(Error::PortUnreachable.into());
+        }
+
+        let payload = packet.payload();
+        Ok(handles
+            .into_iter()
+            .filter(|handle| self.get_mut(*handle).unwrap().enqueue(remote, payload).is_ok())
+            .collect())
+    }
+}
+
+fn parse(src_addr: IpAddr, dest_addr: IpAddr, buffer: &[u8]) -> Result<Packet<&[u8]>> {
+    let packet = Packet::new_checked(buffer)?;
+    if !packet.verify_checksum(src_addr, dest_addr) {
+        return Err(Error::InvalidChecksum.into());
+    }
+    Ok(packet)
+}
+
+fn same_family(addr: IpAddr, other: IpAddr) -> bool {
+    addr.is_ipv4() == other.is_ipv4()
+}
+
+impl Default for SocketSet {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr};
+
+    use super::SocketSet;
+    use crate::icmpv4::packet::{DestinationUnreachablePacketCode, ErrorMessage, Quoted};
+    use crate::ipv4::packet::Protocol;
+    use crate::options::SocketConfig;
+    use crate::tcp::socket::consts::EPHEMERAL_PORT_FIRST;
+    use crate::udp::builder::PacketBuilder;
+
+    const CLIENT_ADDR: Ipv4Addr = Ipv4Addr::new(192, 168, 233, 234);
+    const SERVER_ADDR: Ipv4Addr = Ipv4Addr::new(192, 168, 233, 233);
+
+    fn datagram(src_port: u16, dest_port: u16, payload: &[u8]) -> Vec<u8> {
+        PacketBuilder::default()
+            .src_port(src_port)
+            .dest_port(dest_port)
+            .src_addr(CLIENT_ADDR)
+            .dest_addr(SERVER_ADDR)
+            .payload(payload.to_vec())
+            .build_vec()
+    }
+
+    #[test]
+    fn demultiplex() {
+        let mut sockets = SocketSet::new();
+        let any = sockets.bind(SocketAddr::from((Ipv4Addr::UNSPECIFIED, 53))).unwrap();
+        assert!(sockets.bind(SocketAddr::from((SERVER_ADDR, 53))).is_err());
+        let other = sockets.bind(SocketAddr::from((SERVER_ADDR, 0))).unwrap();
+        assert_eq!(sockets.get(other).unwrap().local().port(), EPHEMERAL_PORT_FIRST);
+
+        assert_eq!(
+            sockets
+                .process(CLIENT_ADDR, SERVER_ADDR, &datagram(40000, 53, b"query"))
+                .unwrap(),
+            any
+        );
+        let mut buf = [0; 3];
+        let (len, remote) = sockets.get_mut(any).unwrap().recv_from(&mut buf).unwrap();
+        assert_eq!(&buf[..len], b"que");
+        assert_eq!(remote, SocketAddr::from((CLIENT_ADDR, 40000)));
+        assert!(sockets.get_mut(any).unwrap().recv_from(&mut buf).is_none());
+
+        assert!(sockets
+            .process(CLIENT_ADDR, SERVER_ADDR, &datagram(40000, 54, b""))
+            .is_err());
+        let mut corrupted = datagram(40000, 53, b"query");
+        corrupted[8] ^= 0xff;
+        assert!(sockets.process(CLIENT_ADDR, SERVER_ADDR, &corrupted).is_err());
+
+        // A socket bound to the address itself takes over from the unspecified address.
+        let config = SocketConfig::default().reuse_address(true);
+        assert!(sockets
+            .bind_with_config(SocketAddr::from((SERVER_ADDR, 53)), &config)
+            .is_err());
+        sockets.remove(any);
+        let first = sockets
+            .bind_with_config(SocketAddr::from((Ipv4Addr::UNSPECIFIED, 53)), &config)
+            .unwrap();
+        let second = sockets
+            .bind_with_config(SocketAddr::from((SERVER_ADDR, 53)), &config)
+            .unwrap();
+        assert_eq!(
+            sockets
+                .process(CLIENT_ADDR, SERVER_ADDR, &datagram(40000, 53, b""))
+                .unwrap(),
+            second
+        );
+        sockets.remove(second);
+        assert_eq!(
+            sockets
+                .process(CLIENT_ADDR, SERVER_ADDR, &datagram(40000, 53, b""))
+                .unwrap(),
+            first
+        );
+
+        // Sockets of the two families share a port without receiving each other's datagrams.
+        let ipv6 = sockets.bind(SocketAddr::from((Ipv6Addr::UNSPECIFIED, 53))).unwrap();
+        let (client, server) = (Ipv6Addr::new(0xfd00, 0, 0, 0, 0, 0, 0, 1), Ipv6Addr::LOCALHOST);
+        let bytes = PacketBuilder::default()
+            .src_port(40000)
+            .dest_port(53)
+            .src_addr(client)
+            .dest_addr(server)
+            .build_vec();
+        assert_eq!(sockets.process(client, server, &bytes).unwrap(), ipv6);
+        assert!(sockets
+            .get(ipv6)
+            .unwrap()
+            .send_to(b"", SocketAddr::from((CLIENT_ADDR, 40000)), false)
+            .is_err());
+    }
+
+    #[test]
+    fn queue_depth() {
+        let mut sockets = SocketSet::new();
+        let handle = sockets.bind(SocketAddr::from((Ipv4Addr::UNSPECIFIED, 53))).unwrap();
+        sockets.get_mut(handle).unwrap().set_queue_depth(2);
+
+        for payload in [b"one", b"two", b"six"].iter() {
+            let _ = sockets.process(CLIENT_ADDR, SERVER_ADDR, &datagram(40000, 53, *payload));
+        }
+
+        let socket = sockets.get_mut(handle).unwrap();
+        assert_eq!(socket.recv_queue(), 2);
+        let mut buf = [0; 8];
+        assert_eq!(socket.recv_from(&mut buf).unwrap().0, 3);
+        assert_eq!(&buf[..3], b"one");
+        assert_eq!(socket.recv_from(&mut buf).unwrap().0, 3);
+        assert_eq!(&buf[..3], b"two");
+    }
+
+    #[test]
+    fn connected() {
+        let mut sockets = SocketSet::new();
+        let config = SocketConfig::default().reuse_address(true);
+        let any = sockets
+            .bind_with_config(SocketAddr::from((Ipv4Addr::UNSPECIFIED, 53)), &config)
+            .unwrap();
+        let connected = sockets
+            .bind_with_config(SocketAddr::from((Ipv4Addr::UNSPECIFIED, 53)), &config)
+            .unwrap();
+        let peer = SocketAddr::from((CLIENT_ADDR, 40000));
+        sockets.get_mut(connected).unwrap().connect(Some(peer)).unwrap();
+        sockets.get_mut(any).unwrap().connect(None).unwrap();
+
+        // Datagrams from the peer go to the connected socket, the others to the unconnected one.
+        assert_eq!(
+            sockets
+                .process(CLIENT_ADDR, SERVER_ADDR, &datagram(40000, 53, b""))
+                .unwrap(),
+            connected
+        );
+        assert_eq!(
+            sockets
+                .process(CLIENT_ADDR, SERVER_ADDR, &datagram(40001, 53, b""))
+                .unwrap(),
+            any
+        );
+        sockets.remove(any);
+        assert!(sockets
+            .process(CLIENT_ADDR, SERVER_ADDR, &datagram(40001, 53, b""))
+            .is_err());
+
+        // A hard error about a datagram sent to the peer is reported once.
+        let sent = PacketBuilder::default().src_port(53).dest_port(40000).build_vec();
+        let quoted = Quoted {
+            src_addr: SERVER_ADDR,
+            dest_addr: CLIENT_ADDR,
+            protocol: Protocol::Udp,
+            payload: &sent,
+        };
+        sockets.process_icmp(
+            ErrorMessage::Unreachable(DestinationUnreachablePacketCode::HostUnreachable),
+            &quoted,
+        );
+        assert!(sockets.get_mut(connected).unwrap().take_error().is_none());
+
+        let port_unreachable = ErrorMessage::Unreachable(DestinationUnreachablePacketCode::PortUnreachable);
+        sockets.process_icmp(port_unreachable, &quoted);
+        assert_eq!(sockets.get_mut(connected).unwrap().take_error(), Some(port_unreachable));
+        assert!(sockets.get_mut(connected).unwrap().take_error().is_none());
+    }
+
+    #[test]
+    fn broadcast_multicast() {
+        let group = Ipv4Addr::new(239, 1, 2, 3);
+        let datagram_to = |dest_addr: Ipv4Addr, payload: &[u8]| {
+            PacketBuilder::default()
+                .src_port(40000)
+                .dest_port(5353)
+                .src_addr(CLIENT_ADDR)
+                .dest_addr(dest_addr)
+                .payload(payload.to_vec())
+                .build_vec()
+        };
+
+        let mut sockets = SocketSet::new();
+        let config = SocketConfig::default().reuse_address(true);
+        let first = sockets
+            .bind_with_config(SocketAddr::from((Ipv4Addr::UNSPECIFIED, 5353)), &config)
+            .unwrap();
+        let second = sockets
+            .bind_with_config(SocketAddr::from((Ipv4Addr::UNSPECIFIED, 5353)), &config)
+            .unwrap();
+
+        // A broadcast goes to every socket bound to the port.
+        let broadcast = datagram_to(Ipv4Addr::BROADCAST, b"hello");
+        assert_eq!(
+            sockets
+                .process_multicast(CLIENT_ADDR, Ipv4Addr::BROADCAST, &broadcast)
+                .unwrap(),
+            vec![first, second]
+        );
+
+        // A multicast datagram only goes to the sockets that joined the group.
+        let multicast = datagram_to(group, b"hello");
+        assert!(sockets.process_multicast(CLIENT_ADDR, group, &multicast).is_err());
+        assert!(sockets.get_mut(second).unwrap().join_multicast(SERVER_ADDR).is_err());
+        sockets.get_mut(second).unwrap().join_multicast(group).unwrap();
+        assert!(sockets.get_mut(second).unwrap().join_multicast(group).is_err());
+        assert_eq!(
+            sockets.process_multicast(CLIENT_ADDR, group, &multicast).unwrap(),
+            vec![second]
+        );
+        assert_eq!(sockets.get(first).unwrap().recv_queue(), 1);
+        assert_eq!(sockets.get(second).unwrap().recv_queue(), 2);
+        sockets.get_mut(second).unwrap().leave_multicast(group).unwrap();
+        assert!(sockets.get_mut(second).unwrap().leave_multicast(group).is_err());
+
+        // Sending to a broadcast address is opt-in, multicast datagrams stay on the local network.
+        let socket = sockets.get_mut(first).unwrap();
+        let remote = SocketAddr::from((Ipv4Addr::BROADCAST, 5353));
+        assert!(socket.send_to(b"hello", remote, true).is_err());
+        socket.set_broadcast(true);
+        assert_eq!(socket.send_to(b"hello", remote, true).unwrap().dest_addr, remote);
+        let datagram = socket
+            .send_to(b"hello", SocketAddr::from((group, 5353)), false)
+            .unwrap();
+        assert_eq!(datagram.ttl, Some(1));
+    }
+}
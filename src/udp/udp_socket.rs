@@ -0,0 +1,332 @@
+use std::io::{Error as IOError, ErrorKind};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+
+use crate::error::Result;
+use crate::ipv4::interface::{consts, Interface};
+use crate::net_device::tun::TunDevice;
+use crate::net_device::Device;
+use crate::options::{SocketConfig, SocketOption};
+use crate::udp::socket::{Socket, SocketHandle};
+
+/// A UDP socket bound to a port of an interface.
+///
+/// The interface is polled while waiting for datagrams, so several sockets can share it.
+pub struct UdpSocket<D: Device = TunDevice> {
+    interface: Arc<Mutex<Interface<D>>>,
+    handle: SocketHandle,
+    local: SocketAddr,
+    nonblocking: bool,
+    read_timeout: Option<Duration>,
+}
+
+impl<D: Device> UdpSocket<D> {
+    /// Bind `port` on `addr`, the unspecified address receives on every address of its family
+    /// and a zero port picks an ephemeral port.
+    pub fn bind(interface: &Arc<Mutex<Interface<D>>>, addr: impl Into<IpAddr>, port: u16) -> Result<Self> {
+        Self::bind_with_config(interface, addr, port, &SocketConfig::default())
+    }
+
+    /// Bind like `bind`, with the options of `config`.
+    pub fn bind_with_config(
+        interface: &Arc<Mutex<Interface<D>>>,
+        addr: impl Into<IpAddr>,
+        port: u16,
+        config: &SocketConfig,
+    ) -> Result<Self> {
+        let mut locked = interface.lock().unwrap();
+        let handle = locked
+            .udp_sockets_mut()
+            .bind_with_config(SocketAddr::new(addr.into(), port), config)?;
+        let local = locked.udp_sockets().get(handle).unwrap().local();
+        drop(locked);
+
+        Ok(Self {
+            interface: interface.clone(),
+            handle,
+            local,
+            nonblocking: false,
+            read_timeout: None,
+        })
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Interface<D>> {
+        self.interface.lock().unwrap()
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local
+    }
+
+    /// Returns the peer of a connected socket.
+    pub fn peer_addr(&self) -> Result<SocketAddr> {
+        let remote = self.lock().udp_sockets().get(self.handle).unwrap().remote();
+        remote.ok_or_else(|| IOError::from(ErrorKind::NotConnected).into())
+    }
+
+    /// Connect the socket to `remote`, which `send` sends to and the only peer datagrams are received from.
+    /// ICMP errors about the datagrams sent to `remote`, such as port unreachable, fail the next call.
+    pub fn connect(&self, remote: impl Into<SocketAddr>) -> Result<()> {
+        self.lock()
+            .udp_sockets_mut()
+            .get_mut(self.handle)
+            .unwrap()
+            .connect(Some(remote.into()))
+    }
+
+    /// In non-blocking mode `recv_from` fails with `WouldBlock` instead of polling the interface.
+    pub fn set_nonblocking(&mut self, nonblocking: bool) {
+        self.nonblocking = nonblocking;
+    }
+
+    /// Make `recv_from` fail with `TimedOut` when no datagram arrived within `read_timeout`.
+    /// The interface is released between polls, none of which waits past the deadline.
+    pub fn set_read_timeout(&mut self, read_timeout: Option<Duration>) {
+        self.read_timeout = read_timeout;
+    }
+
+    /// Bound the number of datagrams waiting to be received, datagrams arriving on a full queue are dropped.
+    pub fn set_queue_depth(&self, queue_depth: usize) {
+        self.lock()
+            .udp_sockets_mut()
+            .get_mut(self.handle)
+            .unwrap()
+            .set_queue_depth(queue_depth);
+    }
+
+    pub fn broadcast(&self) -> bool {
+        self.lock().udp_sockets().get(self.handle).unwrap().broadcast()
+    }
+
+    /// Allow sending to the limited broadcast address and to the subnet broadcast address of the interface.
+    pub fn set_broadcast(&self, broadcast: bool) {
+        self.lock()
+            .udp_sockets_mut()
+            .get_mut(self.handle)
+            .unwrap()
+            .set_broadcast(broadcast);
+    }
+
+    /// Join the multicast `group` on the interface, the socket then receives the datagrams sent to it.
+    pub fn join_multicast_v4(&self, group: Ipv4Addr) -> Result<()> {
+        self.join_multicast(group.into())
+    }
+
+    pub fn leave_multicast_v4(&self, group: Ipv4Addr) -> Result<()> {
+        self.leave_multicast(group.into())
+    }
+
+    /// Join the IPv6 multicast `group` like `join_multicast_v4`.
+    pub fn join_multicast_v6(&self, group: Ipv6Addr) -> Result<()> {
+        self.join_multicast(group.into())
+    }
+
+    pub fn leave_multicast_v6(&self, group: Ipv6Addr) -> Result<()> {
+        self.leave_multicast(group.into())
+    }
+
+    fn join_multicast(&self, group: IpAddr) -> Result<()> {
+        let mut interface = self.lock();
+        interface
+            .udp_sockets_mut()
+            .get_mut(self.handle)
+            .unwrap()
+            .join_multicast(group)?;
+        interface.join_multicast_group(group);
+        Ok(())
+    }
+
+    fn leave_multicast(&self, group: IpAddr) -> Result<()> {
+        let mut interface = self.lock();
+        interface
+            .udp_sockets_mut()
+            .get_mut(self.handle)
+            .unwrap()
+            .leave_multicast(group)?;
+        interface.leave_multicast_group(group);
+        Ok(())
+    }
+
+    pub fn set_option(&self, option: SocketOption) -> Result<()> {
+        self.lock()
+            .udp_sockets_mut()
+            .get_mut(self.handle)
+            .unwrap()
+            .set_option(option)
+    }
+
+    /// Send `buf` as a single datagram to `remote`, returns the number of octets sent.
+    /// Sending to a broadcast address needs `set_broadcast(true)`.
+    pub fn send_to(&self, buf: &[u8], remote: impl Into<SocketAddr>) -> Result<usize> {
+        let remote = remote.into();
+        let mut interface = self.lock();
+        let broadcast = match remote.ip() {
+            IpAddr::V4(addr) => interface.is_broadcast(addr),
+            IpAddr::V6(_) => false,
+        };
+        let socket = interface.udp_sockets_mut().get_mut(self.handle).unwrap();
+        check_error(socket)?;
+        let datagram = socket.send_to(buf, remote, broadcast)?;
+        interface.send_datagram(&datagram)?;
+        Ok(buf.len())
+    }
+
+    /// Send `buf` to the peer of a connected socket.
+    pub fn send(&self, buf: &[u8]) -> Result<usize> {
+        self.send_to(buf, self.peer_addr()?)
+    }
+
+    /// Receive a datagram, returns the number of octets copied into `buf` and the sender.
+    /// The octets of a datagram that do not fit in `buf` are dropped.
+    pub fn recv_from(&self, buf: &mut [u8]) -> Result<(usize, SocketAddr)> {
+        let deadline = self.read_timeout.map(|timeout| self.lock().now() + timeout);
+        loop {
+            let mut interface = self.lock();
+            let socket = interface.udp_sockets_mut().get_mut(self.handle).unwrap();
+
+            check_error(socket)?;
+            if let Some(received) = socket.recv_from(buf) {
+                return Ok(received);
+            }
+
+            if self.nonblocking {
+                let now = interface.now();
+                interface.dispatch(now)?;
+                return Err(IOError::from(ErrorKind::WouldBlock).into());
+            }
+            let now = interface.now();
+            if deadline.is_some_and(|deadline| now >= deadline) {
+                return Err(IOError::from(ErrorKind::TimedOut).into());
+            }
+
+            let timeout = deadline.map_or(consts::MAX_POLL_WAIT, |deadline| {
+                deadline.saturating_duration_since(now).min(consts::MAX_POLL_WAIT)
+            });
+            interface.poll_wait(timeout)?;
+        }
+    }
+
+    /// Receive a datagram from the peer of a connected socket.
+    pub fn recv(&self, buf: &mut [u8]) -> Result<usize> {
+        self.peer_addr()?;
+        Ok(self.recv_from(buf)?.0)
+    }
+}
+
+/// Fail with the ICMP error reported since the last call, if any.
+fn check_error(socket: &mut Socket) -> Result<()> {
+    match socket.take_error() {
+        Some(message) => Err(IOError::from(message.kind()).into()),
+        None => Ok(()),
+    }
+}
+
+impl<D: Device> Drop for UdpSocket<D> {
+    fn drop(&mut self) {
+        let mut interface = self.lock();
+        if let Some(socket) = interface.udp_sockets_mut().remove(self.handle) {
+            for group in socket.groups() {
+                interface.leave_multicast_group(*group);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::ErrorKind;
+    use std::net::{Ipv4Addr, SocketAddr};
+    use std::sync::{Arc, Mutex};
+
+    use super::UdpSocket;
+    use crate::ipv4::interface::Interface;
+    use crate::ipv4::reassembly::Reassembler;
+    use crate::net_device::channel::ChannelDevice;
+    use crate::options::SocketConfig;
+
+    const CLIENT_ADDR: Ipv4Addr = Ipv4Addr::new(10, 0, 0, 1);
+    const SERVER_ADDR: Ipv4Addr = Ipv4Addr::new(10, 0, 0, 2);
+
+    type Shared = Arc<Mutex<Interface<ChannelDevice>>>;
+
+    fn interface(device: ChannelDevice, addr: Ipv4Addr) -> Shared {
+        let mut interface = Interface::new(device, Reassembler::default());
+        interface.set_ip_addr(addr);
+        Arc::new(Mutex::new(interface))
+    }
+
+    fn pair() -> (Shared, Shared) {
+        let (client_device, server_device) = ChannelDevice::pair();
+        (
+            interface(client_device, CLIENT_ADDR),
+            interface(server_device, SERVER_ADDR),
+        )
+    }
+
+    #[test]
+    fn send_to_and_recv_from() {
+        let (client, server) = pair();
+        let client_socket = UdpSocket::bind(&client, Ipv4Addr::UNSPECIFIED, 0).unwrap();
+        let server_socket = UdpSocket::bind(&server, Ipv4Addr::UNSPECIFIED, 7).unwrap();
+        let mut buf = [0; 4];
+
+        // The interface address stands in for the unspecified one, and what does not fit is dropped.
+        assert_eq!(client_socket.send_to(b"hello", (SERVER_ADDR, 7)).unwrap(), 5);
+        let (len, remote) = server_socket.recv_from(&mut buf).unwrap();
+        assert_eq!(&buf[..len], b"hell");
+        assert_eq!(
+            remote,
+            SocketAddr::from((CLIENT_ADDR, client_socket.local_addr().port()))
+        );
+
+        server_socket.connect(remote).unwrap();
+        assert_eq!(server_socket.peer_addr().unwrap(), remote);
+        server_socket.send(b"bye").unwrap();
+        let (len, remote) = client_socket.recv_from(&mut buf).unwrap();
+        assert_eq!(&buf[..len], b"bye");
+        assert_eq!(remote, SocketAddr::from((SERVER_ADDR, 7)));
+
+        // An unconnected socket has no peer to send to.
+        let e = client_socket.send(b"bye").unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::NotConnected);
+    }
+
+    #[test]
+    fn nonblocking() {
+        let (client, server) = pair();
+        let mut server_socket = UdpSocket::bind(&server, SERVER_ADDR, 7).unwrap();
+        server_socket.set_nonblocking(true);
+        let mut buf = [0; 16];
+
+        let e = server_socket.recv_from(&mut buf).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::WouldBlock);
+
+        // Datagrams already received are returned without waiting.
+        let client_socket = UdpSocket::bind(&client, CLIENT_ADDR, 0).unwrap();
+        client_socket.send_to(b"ping", (SERVER_ADDR, 7)).unwrap();
+        let now = server.lock().unwrap().now();
+        server.lock().unwrap().poll(now).unwrap();
+        assert_eq!(server_socket.recv_from(&mut buf).unwrap().0, 4);
+        let e = server_socket.recv_from(&mut buf).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::WouldBlock);
+    }
+
+    #[test]
+    fn address_in_use() {
+        let (_, server) = pair();
+        let socket = UdpSocket::bind(&server, SERVER_ADDR, 7).unwrap();
+
+        let e = UdpSocket::bind(&server, SERVER_ADDR, 7).err().unwrap();
+        assert_eq!(e.kind(), ErrorKind::AddrInUse);
+        let e = UdpSocket::bind(&server, Ipv4Addr::UNSPECIFIED, 7).err().unwrap();
+        assert_eq!(e.kind(), ErrorKind::AddrInUse);
+
+        // Unless both sockets reuse the address, or the first one is gone.
+        let config = SocketConfig::default().reuse_address(true);
+        assert!(UdpSocket::bind_with_config(&server, SERVER_ADDR, 7, &config).is_err());
+        drop(socket);
+        let _first = UdpSocket::bind_with_config(&server, SERVER_ADDR, 7, &config).unwrap();
+        UdpSocket::bind_with_config(&server, SERVER_ADDR, 7, &config).unwrap();
+    }
+}
@@ -0,0 +1,334 @@
+use std::collections::HashMap;
+use std::env;
+use std::io::ErrorKind;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use radish::clock::MockClock;
+use radish::ipv4::builder::PacketBuilder;
+use radish::ipv4::interface::Interface;
+use radish::ipv4::packet::{Packet, Protocol};
+use radish::ipv4::reassembly::Reassembler;
+use radish::metrics::{Kind, Registry, Snapshot};
+use radish::net_device::channel::ChannelDevice;
+use radish::net_device::fault::{FaultDevice, Faults};
+use radish::tcp::connection::State;
+use radish::tcp::socket::SocketHandle;
+use radish::udp::builder::PacketBuilder as UdpPacketBuilder;
+
+/// Soak test harness.
+///
+/// Runs two interfaces over a lossy, reordering link, round after round: each round sends
+/// fragmented UDP datagrams and opens, fills and closes TCP connections, then lets every timer expire.
+/// It asserts invariants that catch slow state leaks:
+/// - every datagram and stream is delivered intact, a datagram at most once,
+/// - no counter of either interface ever goes down,
+/// - after each round the reassemblers and socket tables of both interfaces are empty again.
+///
+/// `soak_short` runs a single round with a fixed seed as part of the normal test suite. `soak` runs for
+/// `RADISH_SOAK_SECS` seconds (300 by default) with `RADISH_SOAK_CONNECTIONS` concurrent connections
+/// (2048 by default) and a random seed unless `RADISH_SOAK_SEED` is set:
+/// `cargo test --release --test soak -- --ignored`.
+const DATAGRAMS_PER_ROUND: usize = 200;
+const SHORT_CONNECTIONS: usize = 16;
+const SHORT_SEED: u64 = 0x5eed_5eed;
+const CONNECTIONS: usize = 2048;
+const MTU: usize = 576;
+const STEP: Duration = Duration::from_millis(10);
+const MAX_STEPS: usize = 100_000;
+
+const CLIENT_ADDR: Ipv4Addr = Ipv4Addr::new(10, 0, 0, 1);
+const SERVER_ADDR: Ipv4Addr = Ipv4Addr::new(10, 0, 0, 2);
+const UDP_PORT: u16 = 7;
+const TCP_PORT: u16 = 80;
+
+type SoakInterface = Interface<FaultDevice<ChannelDevice>>;
+
+/// A small deterministic generator, so failures are reproducible from the printed seed.
+struct Random(u64);
+
+impl Random {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn below(&mut self, bound: usize) -> usize {
+        (self.next() % bound as u64) as usize
+    }
+}
+
+/// The payload of a flow, telling by its content which flow it belongs to.
+fn payload(flow: usize, len: usize) -> Vec<u8> {
+    (0..len).map(|i| (i + flow) as u8).collect()
+}
+
+struct Link {
+    client: SoakInterface,
+    server: SoakInterface,
+    clock: MockClock,
+    /// The registries of the client and the server, with their snapshots after the last step.
+    registries: [(Registry, Snapshot); 2],
+}
+
+impl Link {
+    fn new(seed: u64) -> Self {
+        let (left, right) = ChannelDevice::pair();
+        let clock = MockClock::new();
+        let faults = Faults {
+            loss: 0.02,
+            reordering: 0.02,
+            ..Faults::default()
+        };
+        let end = |mut device: ChannelDevice, addr, seed, registry: &Registry| {
+            device.set_mtu(MTU);
+            device.set_nonblocking(true);
+            let mut interface = Interface::new(FaultDevice::new(device, faults, seed), Reassembler::default());
+            interface.set_ip_addr(addr);
+            interface.set_clock(Arc::new(clock.clone()));
+            interface.set_metrics(registry);
+            interface
+        };
+        let registries = [Registry::new(), Registry::new()];
+
+        Self {
+            client: end(left, CLIENT_ADDR, seed, &registries[0]),
+            server: end(right, SERVER_ADDR, seed.rotate_left(32) | 1, &registries[1]),
+            clock,
+            registries: registries.map(|registry| {
+                let snapshot = registry.snapshot();
+                (registry, snapshot)
+            }),
+        }
+    }
+
+    /// Let both interfaces receive what the other sent and run their timers, then move the clock on.
+    fn step(&mut self) {
+        for interface in [&mut self.client, &mut self.server] {
+            match interface.poll(interface.now()) {
+                Err(e) if e.kind() == ErrorKind::WouldBlock => {}
+                result => result.unwrap(),
+            }
+        }
+        self.clock.advance(STEP);
+        self.check_counters();
+    }
+
+    /// Assert no counter went down since the last step.
+    fn check_counters(&mut self) {
+        for (registry, previous) in self.registries.iter_mut() {
+            let snapshot = registry.snapshot();
+            for sample in snapshot.samples().iter().filter(|sample| sample.kind == Kind::Counter) {
+                let before = previous.get(sample.name).unwrap_or(0);
+                assert!(
+                    sample.value >= before,
+                    "counter {} went down from {} to {}",
+                    sample.name,
+                    before,
+                    sample.value
+                );
+            }
+            *previous = snapshot;
+        }
+    }
+
+    fn is_idle(&self) -> bool {
+        [&self.client, &self.server]
+            .iter()
+            .all(|interface| interface.reassembler().is_empty() && interface.sockets().handles().count() == 0)
+    }
+}
+
+/// Send fragmented datagrams from the client, each delivered at most once and intact.
+fn run_datagrams(random: &mut Random, link: &mut Link) {
+    let handle = link.server.udp_sockets_mut().bind((SERVER_ADDR, UDP_PORT)).unwrap();
+    let mut delivered = [false; DATAGRAMS_PER_ROUND];
+    let mut buf = vec![0; 4 * MTU];
+    let mut step = |link: &mut Link| {
+        link.step();
+        let socket = link.server.udp_sockets_mut().get_mut(handle).unwrap();
+        while let Some((len, remote)) = socket.recv_from(&mut buf) {
+            let flow = (remote.port() - 1024) as usize;
+            assert!(!delivered[flow], "datagram {} delivered twice", flow);
+            assert_eq!(buf[..len], payload(flow, len), "datagram {} corrupted", flow);
+            delivered[flow] = true;
+        }
+    };
+
+    for flow in 0..DATAGRAMS_PER_ROUND {
+        let len = 1 + random.below(4 * MTU);
+        let datagram = UdpPacketBuilder::default()
+            .src_port(1024 + flow as u16)
+            .dest_port(UDP_PORT)
+            .src_addr(CLIENT_ADDR)
+            .dest_addr(SERVER_ADDR)
+            .payload(payload(flow, len))
+            .build_vec();
+        let packet = PacketBuilder::default()
+            .identification(random.next() as u16)
+            .ttl(64)
+            .protocol(Protocol::Udp)
+            .src_addr(CLIENT_ADDR)
+            .dest_addr(SERVER_ADDR)
+            .payload(datagram)
+            .build_vec();
+        link.client.send(Packet::new_unchecked(packet.as_slice())).unwrap();
+        step(link);
+    }
+    for _ in 0..10 {
+        step(link);
+    }
+
+    // Most datagrams lose none of their fragments.
+    assert!(delivered.iter().filter(|delivered| **delivered).count() > DATAGRAMS_PER_ROUND / 2);
+    link.server.udp_sockets_mut().remove(handle);
+}
+
+/// Open connections from the client, each sending a stream the server reads whole before both close.
+/// All `connections` are open at once, each is released as soon as it completed.
+fn run_connections(random: &mut Random, link: &mut Link, connections: usize) {
+    let listener = link
+        .server
+        .sockets_mut()
+        .bind((SERVER_ADDR, TCP_PORT), connections)
+        .unwrap();
+
+    // The data left to send of every client connection, and what every server connection received.
+    let mut clients: HashMap<SocketHandle, (Vec<u8>, usize)> = HashMap::new();
+    let mut streams: HashMap<SocketAddr, Vec<u8>> = HashMap::new();
+    for flow in 0..connections {
+        let now = link.client.now();
+        let handle = link
+            .client
+            .sockets_mut()
+            .connect(now, (CLIENT_ADDR, 0), (SERVER_ADDR, TCP_PORT))
+            .unwrap();
+        let local = link.client.sockets().get(handle).unwrap().local();
+        let data = payload(flow, random.below(64 * 1024));
+        streams.insert(local, data.clone());
+        clients.insert(handle, (data, 0));
+    }
+
+    let mut servers: Vec<(SocketHandle, Vec<u8>)> = vec![];
+    let mut completed = 0;
+    let mut buf = [0; 4096];
+    for _ in 0..MAX_STEPS {
+        link.step();
+
+        // The client releases a connection once established with all its data queued,
+        // which sends a FIN after the data.
+        clients.retain(|handle, (data, sent)| {
+            let connection = link.client.sockets_mut().get_mut(*handle).unwrap();
+            *sent += connection.send(&data[*sent..]);
+            if *sent < data.len() || connection.state() == State::SynSent {
+                return true;
+            }
+            link.client.sockets_mut().release(*handle);
+            false
+        });
+
+        while let Some(handle) = link.server.sockets_mut().accept(listener) {
+            servers.push((handle, vec![]));
+        }
+        servers.retain_mut(|(handle, received)| {
+            let connection = link.server.sockets_mut().get_mut(*handle).unwrap();
+            loop {
+                let len = connection.recv(&mut buf);
+                if len == 0 {
+                    break;
+                }
+                received.extend_from_slice(&buf[..len]);
+            }
+            if connection.state() != State::CloseWait {
+                return true;
+            }
+
+            let remote = connection.remote();
+            assert!(streams[&remote] == *received, "stream from {} corrupted", remote);
+            completed += 1;
+            link.server.sockets_mut().release(*handle);
+            false
+        });
+
+        if completed == connections {
+            break;
+        }
+    }
+    assert_eq!(completed, connections, "streams not completed");
+    assert!(link.server.sockets_mut().unbind(listener).is_empty());
+}
+
+fn run_round(random: &mut Random, link: &mut Link, connections: usize) {
+    run_datagrams(random, link);
+    run_connections(random, link, connections);
+
+    // Once every timer expired, from TIME-WAIT to the reassembly timeout, nothing is left behind.
+    for _ in 0..MAX_STEPS {
+        if link.is_idle() {
+            break;
+        }
+        link.step();
+    }
+    for interface in [&link.client, &link.server] {
+        assert!(
+            interface.reassembler().is_empty(),
+            "reassembler leaked {} datagrams",
+            interface.reassembler().len()
+        );
+        assert_eq!(interface.sockets().handles().count(), 0, "connections leaked");
+    }
+}
+
+/// Run rounds of `connections` concurrent connections until `duration` elapsed, at least one.
+fn soak_for(duration: Duration, seed: u64, connections: usize) {
+    let seed = seed | 1;
+    println!("soak seed: {}, {} connections", seed, connections);
+
+    let mut random = Random(seed);
+    let mut link = Link::new(seed);
+    let started = Instant::now();
+    let mut rounds = 0;
+
+    loop {
+        run_round(&mut random, &mut link, connections);
+        rounds += 1;
+
+        if started.elapsed() >= duration {
+            break;
+        }
+    }
+
+    let metrics = link.server.metrics();
+    println!(
+        "soak finished: {} rounds, {} datagrams reassembled, {} segments received",
+        rounds,
+        metrics.datagrams_reassembled.get(),
+        metrics.tcp_segments_received.get()
+    );
+}
+
+#[test]
+fn soak_short() {
+    soak_for(Duration::from_secs(0), SHORT_SEED, SHORT_CONNECTIONS);
+}
+
+#[test]
+#[ignore]
+fn soak() {
+    let secs = env::var("RADISH_SOAK_SECS")
+        .ok()
+        .and_then(|secs| secs.parse().ok())
+        .unwrap_or(300);
+    let seed = env::var("RADISH_SOAK_SEED")
+        .ok()
+        .and_then(|seed| seed.parse().ok())
+        .unwrap_or_else(|| SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_nanos() as u64);
+    let connections = env::var("RADISH_SOAK_CONNECTIONS")
+        .ok()
+        .and_then(|connections| connections.parse().ok())
+        .unwrap_or(CONNECTIONS);
+    soak_for(Duration::from_secs(secs), seed, connections);
+}